@@ -0,0 +1,72 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Block header midstate and hash computation
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+use ii_bitcoin::{HashTrait as _, TEST_BLOCKS};
+
+fn test_header() -> ii_bitcoin::BlockHeader {
+    let block = &TEST_BLOCKS[0];
+    ii_bitcoin::BlockHeader {
+        version: block.version,
+        previous_hash: block.previous_hash.into_inner(),
+        merkle_root: block.merkle_root.into_inner(),
+        time: block.time,
+        bits: block.bits,
+        nonce: block.nonce,
+    }
+}
+
+fn bench_midstate(c: &mut Criterion) {
+    let header = test_header();
+    let mut group = c.benchmark_group("midstate");
+    group.throughput(Throughput::Elements(1));
+    group.bench_function("midstate", |b| b.iter(|| black_box(&header).midstate()));
+    group.bench_function("header_hash", |b| b.iter(|| black_box(&header).hash()));
+    group.bench_function("header_pack", |b| b.iter(|| black_box(header).into_bytes()));
+    group.finish();
+}
+
+/// Midstates of all version rolling variants of one header as computed for an AsicBoost work
+fn bench_version_rolling(c: &mut Criterion) {
+    const MIDSTATE_COUNT: u32 = 4;
+    let header = test_header();
+    let mut group = c.benchmark_group("midstate");
+    group.throughput(Throughput::Elements(MIDSTATE_COUNT as u64));
+    group.bench_function("version_rolling", |b| {
+        b.iter(|| {
+            (0..MIDSTATE_COUNT)
+                .map(|i| {
+                    let mut header = black_box(header);
+                    header.version |= i << ii_bitcoin::BIP320_VERSION_SHIFT;
+                    header.midstate()
+                })
+                .collect::<Vec<_>>()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_midstate, bench_version_rolling);
+criterion_main!(benches);
@@ -0,0 +1,293 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Throughput of the V2->V1 translation of the stratum proxy
+//!
+//! The translation is driven directly without any networking. Each benchmark starts with the
+//! serialized incoming message, runs it through decoding and the translation and ends with all
+//! outgoing messages serialized.
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+
+use ii_async_compat::bytes::BytesMut;
+use ii_async_compat::futures::{self, channel::mpsc};
+use ii_async_compat::tokio_util::codec::{Decoder, Encoder};
+
+use ii_stratum::test_utils::common::{MINING_WORK_NTIME, MINING_WORK_VERSION};
+use ii_stratum::test_utils::v1::MINING_NOTIFY_JSON;
+use ii_stratum::test_utils::v2::{build_open_channel, build_setup_connection};
+use ii_stratum::v1;
+use ii_stratum::v2;
+
+use ii_stratum_proxy::translation::{V2ToV1Translation, V2ToV1TranslationOptions};
+
+use async_trait::async_trait;
+
+use std::convert::{TryFrom, TryInto};
+use std::str::FromStr;
+
+/// Translation opens a single channel with this ID
+const CHANNEL_ID: u32 = 0;
+/// Capacity of the channels between the translation and the benchmark, it has to hold all
+/// messages produced in reaction to one incoming message
+const QUEUE_SIZE: usize = 16;
+const EXTRA_NONCE_1: &str = "deadbeef";
+const EXTRA_NONCE_2_SIZE: usize = 4;
+
+fn v1_rpc(json: serde_json::Value) -> v1::rpc::Rpc {
+    v1::rpc::Rpc::from_str(&json.to_string()).expect("BUG: invalid V1 message")
+}
+
+fn v1_result(id: Option<u32>, result: serde_json::Value) -> v1::rpc::Rpc {
+    v1_rpc(serde_json::json!({"id": id, "result": result, "error": null}))
+}
+
+/// Remembers the ID of the last job sent downstream
+#[derive(Default)]
+struct JobIdCollector(Option<u32>);
+
+#[async_trait]
+impl v2::Handler for JobIdCollector {
+    async fn visit_new_mining_job(
+        &mut self,
+        _header: &v2::framing::Header,
+        payload: &v2::messages::NewMiningJob,
+    ) {
+        self.0 = Some(payload.job_id);
+    }
+}
+
+/// Translation with operational channel and codecs for both of its ends
+struct Proxy {
+    translation: V2ToV1Translation,
+    v1_rx: mpsc::Receiver<v1::Frame>,
+    v2_rx: mpsc::Receiver<v2::Frame>,
+    v1_codec: v1::Codec,
+    v2_codec: v2::Codec,
+    /// Job for submitting shares
+    job_id: u32,
+}
+
+impl Proxy {
+    /// Performs the whole handshake with a V2 miner and a V1 pool and sends the first job
+    fn new() -> Self {
+        let (v1_tx, v1_rx) = mpsc::channel(QUEUE_SIZE);
+        let (v2_tx, v2_rx) = mpsc::channel(QUEUE_SIZE);
+        let mut proxy = Self {
+            translation: V2ToV1Translation::new(
+                v1_tx,
+                v2_tx,
+                V2ToV1TranslationOptions {
+                    try_enable_xnsub: false,
+                },
+            ),
+            v1_rx,
+            v2_rx,
+            v1_codec: v1::Codec::default(),
+            v2_codec: v2::Codec::default(),
+            job_id: 0,
+        };
+
+        proxy.send_v2(build_setup_connection());
+        let configure_id = proxy.v1_request_ids()[0];
+        proxy.send_v1(v1_result(
+            configure_id,
+            serde_json::json!({
+                "version-rolling": true,
+                "version-rolling.mask": format!("{:08x}", ii_stratum::BIP320_N_VERSION_MASK),
+            }),
+        ));
+
+        proxy.send_v2(build_open_channel());
+        let ids = proxy.v1_request_ids();
+        let (subscribe_id, authorize_id) = match ids.as_slice() {
+            [subscribe_id, authorize_id] => (*subscribe_id, *authorize_id),
+            _ => panic!("BUG: unexpected requests {:?}", ids),
+        };
+        proxy.send_v1(v1_result(
+            subscribe_id,
+            serde_json::json!([
+                [["mining.set_difficulty", "1"], ["mining.notify", "1"]],
+                EXTRA_NONCE_1,
+                EXTRA_NONCE_2_SIZE,
+            ]),
+        ));
+        proxy.send_v1(v1_result(authorize_id, serde_json::json!(true)));
+        proxy.send_v1(v1_rpc(serde_json::json!({
+            "id": null,
+            "method": "mining.set_difficulty",
+            "params": [1024.0],
+        })));
+        proxy.send_v1(v1::rpc::Rpc::from_str(MINING_NOTIFY_JSON).expect("BUG: invalid notify"));
+
+        let mut collector = JobIdCollector::default();
+        while let Ok(Some(frame)) = proxy.v2_rx.try_next() {
+            let message = v2::build_message_from_frame(frame).expect("BUG: cannot deserialize");
+            futures::executor::block_on(message.accept(&mut collector));
+        }
+        proxy.job_id = collector.0.expect("BUG: no job sent downstream");
+        proxy
+    }
+
+    fn send_v1(&mut self, message: v1::rpc::Rpc) {
+        let frame: v1::Frame = message.try_into().expect("BUG: cannot build frame");
+        let message = v1::build_message_from_frame(frame).expect("BUG: cannot deserialize");
+        futures::executor::block_on(message.accept(&mut self.translation));
+    }
+
+    fn send_v2<M>(&mut self, message: M)
+    where
+        M: TryInto<v2::Frame, Error = ii_stratum::error::Error>,
+    {
+        let frame = message.try_into().expect("BUG: cannot build frame");
+        let message = v2::build_message_from_frame(frame).expect("BUG: cannot deserialize");
+        futures::executor::block_on(message.accept(&mut self.translation));
+    }
+
+    /// Returns IDs of all requests sent upstream since the last call
+    fn v1_request_ids(&mut self) -> Vec<Option<u32>> {
+        let mut ids = vec![];
+        while let Ok(Some(frame)) = self.v1_rx.try_next() {
+            match v1::rpc::Rpc::try_from(frame) {
+                Ok(v1::rpc::Rpc::Request(request)) => ids.push(request.id),
+                _ => panic!("BUG: unexpected V1 message sent upstream"),
+            }
+        }
+        ids
+    }
+
+    /// Decodes a V1 line received from the pool and passes it to the translation
+    fn receive_v1(&mut self, mut bytes: BytesMut) {
+        let frame = self
+            .v1_codec
+            .decode(&mut bytes)
+            .expect("BUG: cannot decode V1 frame")
+            .expect("BUG: incomplete V1 frame");
+        let message = v1::build_message_from_frame(frame).expect("BUG: cannot deserialize");
+        futures::executor::block_on(message.accept(&mut self.translation));
+    }
+
+    /// Decodes a V2 frame received from the miner and passes it to the translation
+    fn receive_v2(&mut self, mut bytes: BytesMut) {
+        let frame = self
+            .v2_codec
+            .decode(&mut bytes)
+            .expect("BUG: cannot decode V2 frame")
+            .expect("BUG: incomplete V2 frame");
+        let message = v2::build_message_from_frame(frame).expect("BUG: cannot deserialize");
+        futures::executor::block_on(message.accept(&mut self.translation));
+    }
+
+    /// Serializes all messages sent upstream since the last call
+    fn flush_v1(&mut self) -> BytesMut {
+        let mut bytes = BytesMut::new();
+        while let Ok(Some(frame)) = self.v1_rx.try_next() {
+            self.v1_codec
+                .encode(frame, &mut bytes)
+                .expect("BUG: cannot encode V1 frame");
+        }
+        bytes
+    }
+
+    /// Serializes all messages sent downstream since the last call
+    fn flush_v2(&mut self) -> BytesMut {
+        let mut bytes = BytesMut::new();
+        while let Ok(Some(frame)) = self.v2_rx.try_next() {
+            self.v2_codec
+                .encode(frame, &mut bytes)
+                .expect("BUG: cannot encode V2 frame");
+        }
+        bytes
+    }
+}
+
+/// Returns serialized share for job `job_id`
+fn share(job_id: u32, seq_num: u32) -> BytesMut {
+    let share = v2::messages::SubmitSharesStandard {
+        channel_id: CHANNEL_ID,
+        seq_num,
+        job_id,
+        nonce: seq_num,
+        ntime: MINING_WORK_NTIME,
+        version: MINING_WORK_VERSION,
+    };
+    let frame = share.try_into().expect("BUG: cannot build frame");
+    let mut bytes = BytesMut::new();
+    v2::Codec::default()
+        .encode(frame, &mut bytes)
+        .expect("BUG: cannot encode V2 frame");
+    bytes
+}
+
+/// New job from the pool translated into `NewMiningJob` and `SetNewPrevHash`
+fn bench_job(c: &mut Criterion) {
+    // Clean jobs keep the job table of the translation from growing
+    let notify = format!("{}\n", MINING_NOTIFY_JSON.replace(",false]", ",true]"));
+    let mut proxy = Proxy::new();
+    let mut group = c.benchmark_group("translation");
+    group.throughput(Throughput::Elements(1));
+    group.bench_function("job", |b| {
+        b.iter_batched(
+            || BytesMut::from(notify.as_bytes()),
+            |bytes| {
+                proxy.receive_v1(bytes);
+                proxy.flush_v2()
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+/// Share from the miner translated into `mining.submit` and the pool result translated back
+/// into `SubmitSharesSuccess`. The ID of the submit is parsed on the pool side out of the
+/// translated request, which is included in the measurement.
+fn bench_share(c: &mut Criterion) {
+    let mut proxy = Proxy::new();
+    let job_id = proxy.job_id;
+    let mut seq_num = 0u32;
+    let mut group = c.benchmark_group("translation");
+    group.throughput(Throughput::Elements(1));
+    group.bench_function("share", |b| {
+        b.iter_batched(
+            || {
+                seq_num = seq_num.wrapping_add(1);
+                share(job_id, seq_num)
+            },
+            |bytes| {
+                proxy.receive_v2(bytes);
+                let submit = proxy.flush_v1();
+                let id = serde_json::from_slice::<serde_json::Value>(&submit)
+                    .expect("BUG: invalid submit")["id"]
+                    .clone();
+                let result = format!(r#"{{"id":{},"result":true,"error":null}}"#, id) + "\n";
+                proxy.receive_v1(BytesMut::from(black_box(result).as_bytes()));
+                proxy.flush_v2()
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_job, bench_share);
+criterion_main!(benches);
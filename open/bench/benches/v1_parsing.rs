@@ -0,0 +1,110 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Parsing and serialization of Stratum V1 JSON messages
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+
+use ii_async_compat::bytes::BytesMut;
+use ii_async_compat::tokio_util::codec::{Decoder, Encoder};
+
+use ii_stratum::test_utils::v1::{MINING_NOTIFY_JSON, MINING_SUBMIT_JSON};
+use ii_stratum::v1;
+
+use std::convert::{TryFrom, TryInto};
+use std::str::FromStr;
+
+/// Parses `json` into a request and converts it into V1 message `M`
+fn parse<M>(json: &str) -> M
+where
+    M: TryFrom<v1::rpc::Request, Error = ii_stratum::error::Error>,
+{
+    match v1::rpc::Rpc::from_str(json).expect("BUG: cannot parse JSON") {
+        v1::rpc::Rpc::Request(request) => M::try_from(request).expect("BUG: invalid request"),
+        _ => panic!("BUG: not a request"),
+    }
+}
+
+fn bench_parse<M>(c: &mut Criterion, name: &str, json: &str)
+where
+    M: TryFrom<v1::rpc::Request, Error = ii_stratum::error::Error>,
+{
+    let mut group = c.benchmark_group("v1_parse");
+    group.throughput(Throughput::Bytes(json.len() as u64));
+    group.bench_function(name, |b| b.iter(|| parse::<M>(black_box(json))));
+    group.finish();
+}
+
+/// Decoding of a line received from the network including dispatch to the message
+fn bench_decode(c: &mut Criterion, name: &str, json: &str) {
+    let line = format!("{}\n", json);
+    let mut group = c.benchmark_group("v1_decode");
+    group.throughput(Throughput::Bytes(line.len() as u64));
+    let mut codec = v1::Codec::default();
+    group.bench_function(name, |b| {
+        b.iter_batched(
+            || BytesMut::from(line.as_bytes()),
+            |mut bytes| {
+                let frame = codec
+                    .decode(&mut bytes)
+                    .expect("BUG: cannot decode frame")
+                    .expect("BUG: incomplete frame");
+                v1::build_message_from_frame(frame).expect("BUG: cannot build message")
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+fn bench_encode(c: &mut Criterion, name: &str, json: &str) {
+    let mut group = c.benchmark_group("v1_encode");
+    group.throughput(Throughput::Bytes(json.len() as u64));
+    let mut codec = v1::Codec::default();
+    let mut bytes = BytesMut::new();
+    group.bench_function(name, |b| {
+        b.iter_batched(
+            || v1::rpc::Rpc::from_str(json).expect("BUG: cannot parse JSON"),
+            |rpc| {
+                let frame: v1::Frame = rpc.try_into().expect("BUG: cannot build frame");
+                bytes.clear();
+                codec
+                    .encode(frame, &mut bytes)
+                    .expect("BUG: cannot encode frame");
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+fn bench_v1_parsing(c: &mut Criterion) {
+    bench_parse::<v1::messages::Notify>(c, "notify", MINING_NOTIFY_JSON);
+    bench_parse::<v1::messages::Submit>(c, "submit", MINING_SUBMIT_JSON);
+    bench_decode(c, "notify", MINING_NOTIFY_JSON);
+    bench_decode(c, "submit", MINING_SUBMIT_JSON);
+    bench_encode(c, "notify", MINING_NOTIFY_JSON);
+    bench_encode(c, "submit", MINING_SUBMIT_JSON);
+}
+
+criterion_group!(benches, bench_v1_parsing);
+criterion_main!(benches);
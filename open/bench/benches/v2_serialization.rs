@@ -0,0 +1,129 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Encoding and decoding of Stratum V2 mining messages
+//!
+//! Encoding covers conversion of a message into a frame and its serialization by the codec,
+//! decoding covers the reverse path including building of the message from the frame.
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+
+use ii_async_compat::bytes::BytesMut;
+use ii_async_compat::tokio_util::codec::{Decoder, Encoder};
+
+use ii_stratum::test_utils::v2::{build_new_mining_job, build_submit_shares};
+use ii_stratum::v2;
+
+use std::convert::{TryFrom, TryInto};
+
+/// Returns `message` serialized by the V2 codec
+fn encode<M>(message: M) -> BytesMut
+where
+    M: TryInto<v2::Frame, Error = ii_stratum::error::Error>,
+{
+    let frame = message.try_into().expect("BUG: cannot build frame");
+    let mut bytes = BytesMut::new();
+    v2::Codec::default()
+        .encode(frame, &mut bytes)
+        .expect("BUG: cannot encode frame");
+    bytes
+}
+
+fn bench_encode<M>(c: &mut Criterion, name: &str, message: M)
+where
+    M: Clone + TryInto<v2::Frame, Error = ii_stratum::error::Error>,
+{
+    let mut group = c.benchmark_group("v2_encode");
+    group.throughput(Throughput::Bytes(encode(message.clone()).len() as u64));
+    let mut codec = v2::Codec::default();
+    let mut bytes = BytesMut::new();
+    group.bench_function(name, |b| {
+        b.iter(|| {
+            let frame: v2::Frame = black_box(message.clone())
+                .try_into()
+                .expect("BUG: cannot build frame");
+            bytes.clear();
+            codec
+                .encode(frame, &mut bytes)
+                .expect("BUG: cannot encode frame");
+        })
+    });
+    group.finish();
+}
+
+fn bench_decode<M>(c: &mut Criterion, name: &str, message: M)
+where
+    M: TryInto<v2::Frame, Error = ii_stratum::error::Error>
+        + TryFrom<v2::Frame, Error = ii_stratum::error::Error>,
+{
+    let bytes = encode(message);
+    let mut group = c.benchmark_group("v2_decode");
+    group.throughput(Throughput::Bytes(bytes.len() as u64));
+    let mut codec = v2::Codec::default();
+    group.bench_function(name, |b| {
+        b.iter_batched(
+            || bytes.clone(),
+            |mut bytes| {
+                let frame = codec
+                    .decode(&mut bytes)
+                    .expect("BUG: cannot decode frame")
+                    .expect("BUG: incomplete frame");
+                M::try_from(frame).expect("BUG: cannot build message")
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+/// Decoding through the dynamic dispatch used by the protocol handlers
+fn bench_build_message(c: &mut Criterion) {
+    let bytes = encode(build_submit_shares());
+    let mut group = c.benchmark_group("v2_decode");
+    group.throughput(Throughput::Bytes(bytes.len() as u64));
+    let mut codec = v2::Codec::default();
+    group.bench_function("build_message_from_frame", |b| {
+        b.iter_batched(
+            || bytes.clone(),
+            |mut bytes| {
+                let frame = codec
+                    .decode(&mut bytes)
+                    .expect("BUG: cannot decode frame")
+                    .expect("BUG: incomplete frame");
+                v2::build_message_from_frame(frame).expect("BUG: cannot build message")
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+fn bench_v2_serialization(c: &mut Criterion) {
+    bench_encode(c, "new_mining_job", build_new_mining_job());
+    bench_encode(c, "submit_shares_standard", build_submit_shares());
+    bench_decode(c, "new_mining_job", build_new_mining_job());
+    bench_decode(c, "submit_shares_standard", build_submit_shares());
+    bench_build_message(c);
+}
+
+criterion_group!(benches, bench_v2_serialization);
+criterion_main!(benches);
@@ -0,0 +1,91 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Submission of work into the work TX FIFO of Antminer S9
+//!
+//! The FIFO is a memory mapped register and each check of its status is another bus transaction.
+//! Both registers are simulated with volatile accesses to memory which are much cheaper than
+//! the bus transactions, so the results compare the number of accesses and not the time spent on
+//! the hardware.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+use bosminer_am1_s9::io;
+use bosminer_am1_s9::null_work;
+
+use std::ptr;
+
+/// Status and data register of work TX FIFO
+#[derive(Default)]
+struct SimulatedFifo {
+    status: u32,
+    data: u32,
+}
+
+impl SimulatedFifo {
+    const FULL: u32 = 1;
+
+    #[inline(never)]
+    fn is_full(&self) -> bool {
+        unsafe { ptr::read_volatile(&self.status) & Self::FULL != 0 }
+    }
+
+    #[inline(never)]
+    fn write(&mut self, word: u32) {
+        unsafe { ptr::write_volatile(&mut self.data, word) }
+    }
+}
+
+fn bench_work_tx(c: &mut Criterion) {
+    let mut group = c.benchmark_group("work_tx");
+    for midstate_count in [1, 2, 4].iter() {
+        let work = null_work::prepare_opencore(true, *midstate_count);
+        let mut frame = io::WorkFrame::with_capacity(io::WorkFrame::len_for(*midstate_count));
+        let mut fifo = SimulatedFifo::default();
+        group.throughput(Throughput::Elements(1));
+
+        group.bench_function(format!("assemble/{}", midstate_count), |b| {
+            b.iter(|| frame.assemble(black_box(&work), 0))
+        });
+        frame.assemble(&work, 0);
+        group.bench_function(format!("per_word/{}", midstate_count), |b| {
+            b.iter(|| {
+                for word in black_box(frame.words()).iter() {
+                    while fifo.is_full() {}
+                    fifo.write(*word);
+                }
+            })
+        });
+        group.bench_function(format!("burst/{}", midstate_count), |b| {
+            b.iter(|| {
+                while fifo.is_full() {}
+                for word in black_box(frame.words()).iter() {
+                    fifo.write(*word);
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_work_tx);
+criterion_main!(benches);
@@ -29,14 +29,13 @@ use futures::channel::mpsc;
 use futures::channel::oneshot;
 use futures::executor::block_on;
 use futures::stream::StreamExt;
-use ii_async_compat::{futures, tokio};
-use tokio::task;
+use ii_async_compat::{futures, runtime};
 
-use embedded_hal::blocking::i2c::{Read, Write};
+use embedded_hal::blocking::i2c::{Read, Write, WriteRead};
 use linux_embedded_hal::I2cdev;
 
+use crate::error::ResultExt;
 use crate::error::{self, ErrorKind};
-use failure::ResultExt;
 
 use std::convert::AsRef;
 use std::path::Path;
@@ -54,6 +53,14 @@ enum Request {
         /// Channel used to send back result
         reply: oneshot::Sender<error::Result<()>>,
     },
+    /// Write followed by read in a single transaction (with repeated start)
+    WriteRead {
+        address: u8,
+        bytes: Vec<u8>,
+        num_bytes: usize,
+        /// Channel used to send back result
+        reply: oneshot::Sender<error::Result<Vec<u8>>>,
+    },
 }
 
 /// Server for I2C read/write requests
@@ -74,8 +81,7 @@ fn serve_requests(
                 let result = i2c_device
                     .read(address, &mut bytes)
                     .with_context(|e| ErrorKind::I2c(e.to_string()))
-                    .map(|_| bytes)
-                    .map_err(|e| e.into());
+                    .map(|_| bytes);
                 if reply.send(result).is_err() {
                     warn!("AsyncI2c reply send failed - remote side may have ended");
                 }
@@ -87,8 +93,22 @@ fn serve_requests(
             } => {
                 let result = i2c_device
                     .write(address, &bytes)
+                    .with_context(|e| ErrorKind::I2c(e.to_string()));
+                if reply.send(result).is_err() {
+                    warn!("AsyncI2c reply send failed - remote side may have ended");
+                }
+            }
+            Request::WriteRead {
+                address,
+                bytes,
+                num_bytes,
+                reply,
+            } => {
+                let mut buffer = vec![0; num_bytes];
+                let result = i2c_device
+                    .write_read(address, &bytes, &mut buffer)
                     .with_context(|e| ErrorKind::I2c(e.to_string()))
-                    .map_err(|e| e.into());
+                    .map(|_| buffer);
                 if reply.send(result).is_err() {
                     warn!("AsyncI2c reply send failed - remote side may have ended");
                 }
@@ -117,7 +137,7 @@ impl AsyncI2cDev {
 
         // Spawn the future in a separate blocking pool (for blocking operations)
         // so that this doesn't block the regular threadpool.
-        task::spawn_blocking(move || {
+        runtime::spawn_blocking(move || {
             if let Err(e) = serve_requests(i2c_device, request_rx) {
                 error!("{}", e);
             }
@@ -151,6 +171,25 @@ impl AsyncI2cDev {
             .expect("I2C request failed");
         reply_rx.await.expect("failed to receive I2C reply")
     }
+
+    pub async fn write_read(
+        &self,
+        address: u8,
+        bytes: Vec<u8>,
+        num_bytes: usize,
+    ) -> error::Result<Vec<u8>> {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        let request = Request::WriteRead {
+            address,
+            bytes,
+            num_bytes,
+            reply: reply_tx,
+        };
+        self.request_tx
+            .unbounded_send(request)
+            .expect("I2C request failed");
+        reply_rx.await.expect("failed to receive I2C reply")
+    }
 }
 
 // Please somebody write tests here
@@ -20,8 +20,13 @@
 // of such proprietary license or if you have any other questions, please
 // contact us at opensource@braiins.com.
 
+pub mod command;
 pub mod i2c;
 
+pub use command::{
+    CmdResponse, Command, GetStatusCmd, InactivateFromChainCmd, SetChipAddressCmd, SetConfigCmd,
+};
+
 use crate::error::{self, ErrorKind};
 
 use packed_struct::prelude::*;
@@ -35,7 +40,6 @@ use ii_fpga_io_am1_s9::common::ctrl_reg::MIDSTATE_CNT_A;
 use std::convert::TryInto;
 use std::default::Default;
 use std::fmt::Debug;
-use std::mem::size_of;
 
 #[allow(dead_code)]
 pub const HASH_COUNTING_REG: u8 = 0x14;
@@ -68,11 +72,16 @@ pub struct MidstateCount {
 impl MidstateCount {
     /// Construct Self, panic if number of midstates is not valid for this hw
     pub fn new(count: usize) -> Self {
+        Self::try_new(count).unwrap_or_else(|| panic!("Unsupported S9 midstate count {}", count))
+    }
+
+    /// Construct Self, return `None` if number of midstates is not valid for this hw
+    pub fn try_new(count: usize) -> Option<Self> {
         match count {
-            1 => Self { log2: 0 },
-            2 => Self { log2: 1 },
-            4 => Self { log2: 2 },
-            _ => panic!("Unsupported S9 midstate count {}", count),
+            1 => Some(Self { log2: 0 }),
+            2 => Some(Self { log2: 1 }),
+            4 => Some(Self { log2: 2 }),
+            _ => None,
         }
     }
 
@@ -105,6 +114,9 @@ impl MidstateCount {
     }
 }
 
+/// Highest linear chip address which still fits into the hardware address byte
+pub const MAX_CHIP_ADDRESS: usize = 0xff / 4;
+
 /// This enum is a bridge between chip address representation as we tend to
 /// think about it (addresses `0..=62`) and how the hardware addresses them
 /// (in increments of four).
@@ -116,6 +128,17 @@ pub enum ChipAddress {
 }
 
 impl ChipAddress {
+    /// Construct unicast address of chip `chip_idx` with checking that it is addressable
+    pub fn unicast(chip_idx: usize) -> error::Result<Self> {
+        if chip_idx > MAX_CHIP_ADDRESS {
+            Err(ErrorKind::Hashchip(format!(
+                "chip address {} is out of range 0..={}",
+                chip_idx, MAX_CHIP_ADDRESS
+            )))?
+        }
+        Ok(ChipAddress::One(chip_idx))
+    }
+
     /// Return if address is a broadcast
     pub fn is_broadcast(&self) -> bool {
         match self {
@@ -155,152 +178,11 @@ impl CoreAddress {
             core: (nonce >> 24) & 0x7f,
         }
     }
-}
-
-/// Control or work command layout
-#[derive(PackedStruct, Debug)]
-#[packed_struct(size_bytes = "1", bit_numbering = "lsb0")]
-pub struct Cmd {
-    #[packed_field(bits = "0:3")]
-    code: Integer<u8, packed_bits::Bits4>,
-    #[packed_field(bits = "4")]
-    to_all: bool,
-    #[packed_field(bits = "5:7", ty = "enum")]
-    cmd_type: CmdType,
-}
 
-impl Cmd {
-    fn new(code: u8, to_all: bool) -> Self {
-        Self {
-            code: code.into(),
-            to_all,
-            cmd_type: CmdType::VilCtlCmd,
-        }
-    }
-}
-
-/// Command types
-#[derive(PrimitiveEnum_u8, Clone, Copy, Debug, PartialEq)]
-enum CmdType {
-    /// Control command for the chip
-    VilCtlCmd = 0x02,
-}
-
-#[derive(PackedStruct, Debug)]
-pub struct CmdHeader {
-    #[packed_field(element_size_bytes = "1")]
-    cmd: Cmd,
-    length: u8,
-    hw_addr: u8,
-}
-
-impl CmdHeader {
-    /// Create a new header with custom checksum_size
-    ///
-    /// * `length` - size of the command excluding checksum
-    /// * `checksum_size` - Size of checksum needs to be known as it is accounted in the length
-    /// field
-    fn new_extended(
-        code: u8,
-        length: usize,
-        chip_address: ChipAddress,
-        checksum_size: usize,
-    ) -> Self {
-        Self {
-            cmd: Cmd::new(code, chip_address.is_broadcast()),
-            length: (length + checksum_size) as u8,
-            hw_addr: chip_address.to_hw_addr(),
-        }
-    }
-
-    /// Helper builder for control commands
-    /// Control commands CRC5 checksum that fits into 1 byte
-    /// * `length` - length of the command without checksum
-    fn new(code: u8, length: usize, chip_address: ChipAddress) -> Self {
-        Self::new_extended(code, length, chip_address, size_of::<u8>())
-    }
-}
-
-/// Command response
-#[derive(PackedStruct, Debug)]
-#[packed_struct(endian = "msb")]
-pub struct CmdResponse {
-    pub value: u32,
-    _zero_in_bm1387_but_its_chip_address_in_bm1391: u8,
-    _zero_in_bm1387_but_its_register_number_in_bm1391: u8,
-}
-
-/// Sets configuration register
-#[derive(PackedStruct, Debug)]
-#[packed_struct(endian = "msb")]
-pub struct SetConfigCmd {
-    #[packed_field(element_size_bytes = "3")]
-    pub header: CmdHeader,
-    register: u8,
-    value: u32,
-}
-
-impl SetConfigCmd {
-    pub fn new(chip_address: ChipAddress, register: u8, value: u32) -> Self {
-        // payload consists of 1 byte register address and 4 byte value
-        let header = CmdHeader::new(0x08, Self::packed_bytes(), chip_address);
-        Self {
-            header,
-            register,
-            value,
-        }
-    }
-}
-
-#[derive(PackedStruct, Debug)]
-#[packed_struct(endian = "msb")]
-pub struct GetStatusCmd {
-    #[packed_field(element_size_bytes = "3")]
-    header: CmdHeader,
-    register: u8,
-}
-
-impl GetStatusCmd {
-    pub fn new(chip_address: ChipAddress, register: u8) -> Self {
-        let header = CmdHeader::new(0x04, Self::packed_bytes(), chip_address);
-        Self { header, register }
-    }
-}
-
-#[derive(PackedStruct, Debug)]
-#[packed_struct(endian = "msb")]
-pub struct SetChipAddressCmd {
-    #[packed_field(element_size_bytes = "3")]
-    pub header: CmdHeader,
-    _reserved: u8,
-}
-
-impl SetChipAddressCmd {
-    pub fn new(chip_address: ChipAddress) -> Self {
-        assert!(!chip_address.is_broadcast());
-        let header = CmdHeader::new(0x01, Self::packed_bytes(), chip_address);
-        Self {
-            header,
-            _reserved: 0,
-        }
-    }
-}
-
-#[derive(PackedStruct, Debug)]
-#[packed_struct(endian = "msb")]
-pub struct InactivateFromChainCmd {
-    #[packed_field(element_size_bytes = "3")]
-    header: CmdHeader,
-    _reserved: u8,
-}
-
-impl InactivateFromChainCmd {
-    pub fn new() -> Self {
-        let header = CmdHeader::new(0x05, Self::packed_bytes(), ChipAddress::All);
-        Self {
-            header,
-            _reserved: 0,
-        }
+    /// Checks that the nonce lies in the subrange of an existing core of one of `chip_count`
+    /// enumerated chips. Nonces from other subranges indicate wrong addressing of chips.
+    pub fn is_within(&self, chip_count: usize) -> bool {
+        self.chip < chip_count && self.core < NUM_CORES_ON_CHIP
     }
 }
 
@@ -395,6 +277,33 @@ impl Default for ChipRev {
     }
 }
 
+/// Chip detected during enumeration of the hash chain
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChipInfo {
+    /// Index of the chip on the chain which is used in `ChipAddress::One`
+    pub address: usize,
+    /// Core revision reported by the chip
+    pub chip_rev: ChipRev,
+}
+
+impl ChipInfo {
+    /// Builds the list of chips from responses to broadcast read of `GetAddressReg`. Addresses are
+    /// assigned in the order in which the chips responded.
+    pub fn from_responses(responses: &[GetAddressReg]) -> error::Result<Vec<Self>> {
+        responses
+            .iter()
+            .enumerate()
+            .map(|(address, addr_reg)| match addr_reg.chip_rev {
+                EnumCatchAll::Enum(chip_rev) => Ok(Self { address, chip_rev }),
+                EnumCatchAll::CatchAll(_) => Err(ErrorKind::ChipEnumeration(format!(
+                    "unexpected revision of chip {} (expected: {:#x?} received: {:#x?})",
+                    address, CHIP_REV_BM1387, addr_reg.chip_rev,
+                )))?,
+            })
+            .collect()
+    }
+}
+
 /// Chip revision with `EnumCatchAll` wrapper so we would have to import `packed_struct`
 /// everywhere.
 pub const CHIP_REV_BM1387: EnumCatchAll<ChipRev> = EnumCatchAll::Enum(ChipRev::Bm1387);
@@ -757,6 +666,21 @@ mod test {
         ChipAddress::One(0x40).to_hw_addr();
     }
 
+    #[test]
+    fn test_chip_address_unicast() {
+        assert_eq!(
+            ChipAddress::unicast(9).expect("valid address rejected"),
+            ChipAddress::One(9)
+        );
+        assert_eq!(
+            ChipAddress::unicast(MAX_CHIP_ADDRESS)
+                .expect("valid address rejected")
+                .to_hw_addr(),
+            0xfc
+        );
+        ChipAddress::unicast(MAX_CHIP_ADDRESS + 1).expect_err("invalid address accepted");
+    }
+
     /// Builds a sample set_config command (here the PLL register @ 0x0c with a value of
     /// 0x00680221 that corresponds to
     /// and verifies correct serialization
@@ -901,6 +825,48 @@ mod test {
         assert_eq!(reg.chip_rev, EnumCatchAll::CatchAll(0x1386));
     }
 
+    #[test]
+    fn test_midstate_count() {
+        for count in [1, 2, 4].iter() {
+            let midstate_count = MidstateCount::try_new(*count).expect("valid midstate count");
+            assert_eq!(midstate_count.to_count(), *count);
+        }
+        assert!(MidstateCount::try_new(0).is_none());
+        assert!(MidstateCount::try_new(3).is_none());
+        assert!(MidstateCount::try_new(8).is_none());
+    }
+
+    #[test]
+    fn test_chip_info_from_responses() {
+        let reg = |chip_rev| GetAddressReg {
+            chip_rev,
+            _reserved1: 0x90,
+            addr: 0x00,
+        };
+        let chips = ChipInfo::from_responses(&[reg(CHIP_REV_BM1387), reg(CHIP_REV_BM1387)])
+            .expect("enumeration failed");
+        assert_eq!(
+            chips,
+            vec![
+                ChipInfo {
+                    address: 0,
+                    chip_rev: ChipRev::Bm1387
+                },
+                ChipInfo {
+                    address: 1,
+                    chip_rev: ChipRev::Bm1387
+                },
+            ]
+        );
+        assert!(ChipInfo::from_responses(&[])
+            .expect("enumeration failed")
+            .is_empty());
+
+        // a chip with unknown revision fails the whole enumeration
+        ChipInfo::from_responses(&[reg(CHIP_REV_BM1387), reg(EnumCatchAll::CatchAll(0x1386))])
+            .expect_err("unknown revision accepted");
+    }
+
     #[test]
     fn build_misc_control_reg() {
         let reg = MiscCtrlReg {
@@ -1074,6 +1040,15 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_core_address_subrange() {
+        assert!(CoreAddress::new(0x40e55650).is_within(63));
+        // chip 20 has not been enumerated
+        assert!(!CoreAddress::new(0x40e55650).is_within(20));
+        // cores above 113 do not exist
+        assert!(!CoreAddress::new(0xffffff00).is_within(63));
+    }
+
     #[test]
     fn test_midstate_count_instance() {
         MidstateCount::new(1);
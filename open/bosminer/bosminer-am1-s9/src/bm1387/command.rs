@@ -0,0 +1,269 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Commands for BM1387 chips
+//!
+//! Each command consists of a header with command code and destination chip address, payload and
+//! CRC5 checksum. The IP core appends the checksum to commands on its own, complete frames built by
+//! `Command::to_frame` are meant for other transports and for verification of the wire format.
+
+use super::ChipAddress;
+
+use packed_struct::prelude::*;
+use packed_struct_codegen::PackedStruct;
+use packed_struct_codegen::PrimitiveEnum_u8;
+
+use std::mem::size_of;
+
+/// Initial value of CRC5 register
+const CRC5_INIT: u8 = 0x1f;
+/// CRC5 polynomial x^5 + x^2 + 1 without the leading term
+const CRC5_POLY: u8 = 0x05;
+
+/// Computes CRC5 checksum of `data` (processed from the most significant bit) which is used by
+/// the chips for commands and responses
+pub fn crc5(data: &[u8]) -> u8 {
+    let mut crc = CRC5_INIT;
+    for byte in data {
+        for i in (0..8).rev() {
+            let feedback = (crc >> 4) ^ (byte >> i);
+            crc = (crc << 1) & 0x1f;
+            if feedback & 1 != 0 {
+                crc ^= CRC5_POLY;
+            }
+        }
+    }
+    crc
+}
+
+/// Command which can be sent to the chips
+pub trait Command {
+    /// Serialized command without checksum as expected by the IP core
+    fn to_bytes(&self) -> Vec<u8>;
+
+    /// Complete frame including CRC5 checksum
+    fn to_frame(&self) -> Vec<u8> {
+        let mut frame = self.to_bytes();
+        frame.push(crc5(&frame));
+        frame
+    }
+}
+
+/// Control or work command layout
+#[derive(PackedStruct, Debug)]
+#[packed_struct(size_bytes = "1", bit_numbering = "lsb0")]
+pub struct Cmd {
+    #[packed_field(bits = "0:3")]
+    code: Integer<u8, packed_bits::Bits4>,
+    #[packed_field(bits = "4")]
+    to_all: bool,
+    #[packed_field(bits = "5:7", ty = "enum")]
+    cmd_type: CmdType,
+}
+
+impl Cmd {
+    fn new(code: u8, to_all: bool) -> Self {
+        Self {
+            code: code.into(),
+            to_all,
+            cmd_type: CmdType::VilCtlCmd,
+        }
+    }
+}
+
+/// Command types
+#[derive(PrimitiveEnum_u8, Clone, Copy, Debug, PartialEq)]
+enum CmdType {
+    /// Control command for the chip
+    VilCtlCmd = 0x02,
+}
+
+#[derive(PackedStruct, Debug)]
+pub struct CmdHeader {
+    #[packed_field(element_size_bytes = "1")]
+    cmd: Cmd,
+    length: u8,
+    hw_addr: u8,
+}
+
+impl CmdHeader {
+    /// Create a new header with custom checksum_size
+    ///
+    /// * `length` - size of the command excluding checksum
+    /// * `checksum_size` - Size of checksum needs to be known as it is accounted in the length
+    /// field
+    fn new_extended(
+        code: u8,
+        length: usize,
+        chip_address: ChipAddress,
+        checksum_size: usize,
+    ) -> Self {
+        Self {
+            cmd: Cmd::new(code, chip_address.is_broadcast()),
+            length: (length + checksum_size) as u8,
+            hw_addr: chip_address.to_hw_addr(),
+        }
+    }
+
+    /// Helper builder for control commands
+    /// Control commands CRC5 checksum that fits into 1 byte
+    /// * `length` - length of the command without checksum
+    fn new(code: u8, length: usize, chip_address: ChipAddress) -> Self {
+        Self::new_extended(code, length, chip_address, size_of::<u8>())
+    }
+}
+
+/// Command response
+#[derive(PackedStruct, Debug)]
+#[packed_struct(endian = "msb")]
+pub struct CmdResponse {
+    pub value: u32,
+    _zero_in_bm1387_but_its_chip_address_in_bm1391: u8,
+    _zero_in_bm1387_but_its_register_number_in_bm1391: u8,
+}
+
+/// Sets configuration register
+#[derive(PackedStruct, Debug)]
+#[packed_struct(endian = "msb")]
+pub struct SetConfigCmd {
+    #[packed_field(element_size_bytes = "3")]
+    pub header: CmdHeader,
+    register: u8,
+    value: u32,
+}
+
+impl SetConfigCmd {
+    pub fn new(chip_address: ChipAddress, register: u8, value: u32) -> Self {
+        // payload consists of 1 byte register address and 4 byte value
+        let header = CmdHeader::new(0x08, Self::packed_bytes(), chip_address);
+        Self {
+            header,
+            register,
+            value,
+        }
+    }
+}
+
+#[derive(PackedStruct, Debug)]
+#[packed_struct(endian = "msb")]
+pub struct GetStatusCmd {
+    #[packed_field(element_size_bytes = "3")]
+    header: CmdHeader,
+    register: u8,
+}
+
+impl GetStatusCmd {
+    pub fn new(chip_address: ChipAddress, register: u8) -> Self {
+        let header = CmdHeader::new(0x04, Self::packed_bytes(), chip_address);
+        Self { header, register }
+    }
+}
+
+#[derive(PackedStruct, Debug)]
+#[packed_struct(endian = "msb")]
+pub struct SetChipAddressCmd {
+    #[packed_field(element_size_bytes = "3")]
+    pub header: CmdHeader,
+    _reserved: u8,
+}
+
+impl SetChipAddressCmd {
+    pub fn new(chip_address: ChipAddress) -> Self {
+        assert!(!chip_address.is_broadcast());
+        let header = CmdHeader::new(0x01, Self::packed_bytes(), chip_address);
+        Self {
+            header,
+            _reserved: 0,
+        }
+    }
+}
+
+#[derive(PackedStruct, Debug)]
+#[packed_struct(endian = "msb")]
+pub struct InactivateFromChainCmd {
+    #[packed_field(element_size_bytes = "3")]
+    header: CmdHeader,
+    _reserved: u8,
+}
+
+impl InactivateFromChainCmd {
+    pub fn new() -> Self {
+        let header = CmdHeader::new(0x05, Self::packed_bytes(), ChipAddress::All);
+        Self {
+            header,
+            _reserved: 0,
+        }
+    }
+}
+
+macro_rules! impl_command {
+    ($($cmd:ty),*) => {
+        $(
+            impl Command for $cmd {
+                fn to_bytes(&self) -> Vec<u8> {
+                    self.pack().to_vec()
+                }
+            }
+        )*
+    };
+}
+
+impl_command!(
+    SetConfigCmd,
+    GetStatusCmd,
+    SetChipAddressCmd,
+    InactivateFromChainCmd
+);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_crc5() {
+        assert_eq!(crc5(&[]), CRC5_INIT);
+        assert_eq!(crc5(&[0x54, 0x05, 0x00, 0x00]), 0x19);
+        assert_eq!(crc5(&[0x55, 0x05, 0x00, 0x00]), 0x10);
+    }
+
+    #[test]
+    fn test_command_frames() {
+        assert_eq!(
+            GetStatusCmd::new(ChipAddress::All, 0).to_frame(),
+            vec![0x54, 0x05, 0x00, 0x00, 0x19]
+        );
+        assert_eq!(
+            InactivateFromChainCmd::new().to_frame(),
+            vec![0x55, 0x05, 0x00, 0x00, 0x10]
+        );
+        assert_eq!(
+            SetChipAddressCmd::new(ChipAddress::One(1)).to_frame(),
+            vec![0x41, 0x05, 0x04, 0x00, 0x0a]
+        );
+        let cmd = SetConfigCmd::new(ChipAddress::All, 0x18, 0xfc);
+        assert_eq!(cmd.to_bytes(), cmd.pack().to_vec());
+        assert_eq!(
+            cmd.to_frame(),
+            vec![0x58, 0x09, 0x00, 0x18, 0x00, 0x00, 0x00, 0xfc, 0x06]
+        );
+    }
+}
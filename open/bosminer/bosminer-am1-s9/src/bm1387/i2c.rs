@@ -24,15 +24,15 @@ use crate::bm1387::{self, ChipAddress, Register};
 use crate::command::Interface as CommandInterface;
 use crate::i2c;
 
+use crate::error::ResultExt;
 use crate::error::{self, ErrorKind};
-use failure::ResultExt;
 
 use ii_logging::macros::*;
 
 use std::time::Duration;
 
-use ii_async_compat::tokio;
-use tokio::time::delay_for;
+use ii_async_compat::runtime;
+use runtime::delay_for;
 
 /// Represents I2C bus that is implemented by sending chip commands
 /// to a particular chip on hashchain.
@@ -174,6 +174,7 @@ mod test {
     use super::*;
     use bm1387::{I2cControlReg, MiscCtrlReg};
     use i2c::AsyncBus;
+    use ii_async_compat::tokio;
     use std::sync::Arc;
 
     use futures::lock::Mutex;
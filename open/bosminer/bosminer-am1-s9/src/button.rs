@@ -0,0 +1,189 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Handling of the front panel button
+//!
+//! The button is polled periodically. A short press turns the identification of the miner by
+//! front panel LEDs on or off. A long press restarts all running hash chains the same way as the
+//! `restart` API command does, pool connections are not affected.
+
+use ii_logging::macros::*;
+
+use crate::error;
+use crate::gpio;
+use crate::led;
+use crate::restart;
+use crate::Manager;
+
+use embedded_hal::digital::v2::InputPin;
+
+use ii_async_compat::runtime;
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Period of sampling of the button state
+pub const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Shorter presses are considered to be a contact bounce
+pub const DEBOUNCE_TIME: Duration = Duration::from_millis(50);
+
+/// The button has to be held at least this time for a long press
+pub const LONG_PRESS_TIME: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Press {
+    Short,
+    Long,
+}
+
+/// Classifies presses from samples of the button state
+#[derive(Debug, Default)]
+pub struct Detector {
+    pressed_since: Option<Instant>,
+    /// Long press is reported as soon as it is recognized, not on release
+    long_reported: bool,
+}
+
+impl Detector {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Accounts state of the button sampled at time `now`
+    pub fn sample(&mut self, pressed: bool, now: Instant) -> Option<Press> {
+        match (pressed, self.pressed_since) {
+            (true, None) => {
+                self.pressed_since = Some(now);
+                self.long_reported = false;
+                None
+            }
+            (true, Some(since)) => {
+                if !self.long_reported && now.saturating_duration_since(since) >= LONG_PRESS_TIME {
+                    self.long_reported = true;
+                    Some(Press::Long)
+                } else {
+                    None
+                }
+            }
+            (false, Some(since)) => {
+                self.pressed_since = None;
+                if !self.long_reported && now.saturating_duration_since(since) >= DEBOUNCE_TIME {
+                    Some(Press::Short)
+                } else {
+                    None
+                }
+            }
+            (false, None) => None,
+        }
+    }
+}
+
+pub struct Handler {
+    pin: gpio::PinIn,
+    managers: Vec<Arc<Manager>>,
+    led_controller: Option<Arc<led::Controller>>,
+}
+
+impl Handler {
+    pub fn new(
+        gpio_mgr: &gpio::ControlPinManager,
+        managers: Vec<Arc<Manager>>,
+        led_controller: Option<Arc<led::Controller>>,
+    ) -> error::Result<Self> {
+        Ok(Self {
+            pin: gpio_mgr.get_pin_in(gpio::PinInName::ResetButton)?,
+            managers,
+            led_controller,
+        })
+    }
+
+    async fn handle(&self, press: Press) {
+        match press {
+            Press::Short => match self.led_controller.as_ref() {
+                Some(led_controller) => {
+                    led_controller.toggle_identify();
+                }
+                None => info!("Button: identification needs front panel LEDs to be enabled"),
+            },
+            Press::Long => {
+                info!("Button: restarting mining");
+                restart::restart_chains(&self.managers).await;
+            }
+        }
+    }
+
+    pub async fn task(self) {
+        let mut detector = Detector::new();
+        let mut interval = runtime::Interval::new(POLL_INTERVAL);
+        while interval.tick().await.is_some() {
+            // the button pulls the pin low when it is pressed
+            let pressed = match self.pin.is_low() {
+                Ok(pressed) => pressed,
+                Err(e) => {
+                    error!("Button: failed to read the button state: {}", e);
+                    return;
+                }
+            };
+            if let Some(press) = detector.sample(pressed, Instant::now()) {
+                self.handle(press).await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_short_press() {
+        let mut detector = Detector::new();
+        let now = Instant::now();
+        assert_eq!(detector.sample(false, now), None);
+        assert_eq!(detector.sample(true, now), None);
+        assert_eq!(detector.sample(true, now + POLL_INTERVAL), None);
+        assert_eq!(
+            detector.sample(false, now + Duration::from_millis(300)),
+            Some(Press::Short)
+        );
+        // contact bounce is ignored
+        let now = now + Duration::from_secs(1);
+        assert_eq!(detector.sample(true, now), None);
+        assert_eq!(detector.sample(false, now + DEBOUNCE_TIME / 2), None);
+    }
+
+    #[test]
+    fn test_long_press() {
+        let mut detector = Detector::new();
+        let now = Instant::now();
+        assert_eq!(detector.sample(true, now), None);
+        assert_eq!(detector.sample(true, now + Duration::from_secs(1)), None);
+        assert_eq!(
+            detector.sample(true, now + LONG_PRESS_TIME),
+            Some(Press::Long)
+        );
+        // long press is reported only once and the release is not a short press
+        assert_eq!(detector.sample(true, now + LONG_PRESS_TIME * 2), None);
+        assert_eq!(detector.sample(false, now + LONG_PRESS_TIME * 2), None);
+    }
+}
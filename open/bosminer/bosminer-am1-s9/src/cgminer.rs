@@ -20,20 +20,43 @@
 // of such proprietary license or if you have any other questions, please
 // contact us at opensource@braiins.com.
 
-use ii_cgminer_api::command::{DEVDETAILS, FANS, TEMPCTRL, TEMPS};
+use ii_cgminer_api::command::{
+    CHIPS, CHIP_ERRORS, CHIP_TEMPS, DEVDETAILS, EFFICIENCY, FANS, LOST_WORK, MIDSTATE_COUNT, PAUSE,
+    POWER_TARGET, PRESET, PSU, REGISTERS, RESET_CHIP_ERRORS, RESTART, RESUME, SELF_TEST, TEMPCTRL,
+    TEMPS,
+};
 use ii_cgminer_api::{command, commands, response};
 
+use bosminer::node::WorkSolverStats as _;
+use bosminer::stats;
+
 use serde::Serialize;
+use serde_json as json;
 
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
+use crate::bm1387::MidstateCount;
+use crate::config;
 use crate::monitor;
+use crate::pause;
+use crate::power_target;
+use crate::preset;
+use crate::psu;
+use crate::restart;
 use crate::sensor;
+use crate::ChainControl as _;
 
 #[derive(Eq, PartialEq, Copy, Clone, Debug)]
 #[repr(u32)]
 pub enum StatusCode {
     NotReady = 1,
+    InvalidPauseTimeout = 2,
+    NotPaused = 3,
+    InvalidPowerTarget = 4,
+    InvalidMidstateCount = 5,
+    InvalidPreset = 6,
+    PresetFailed = 7,
 }
 
 impl From<StatusCode> for u32 {
@@ -44,12 +67,39 @@ impl From<StatusCode> for u32 {
 
 pub enum ErrorCode {
     NotReady,
+    InvalidPauseTimeout(String),
+    NotPaused,
+    InvalidPowerTarget(String),
+    InvalidMidstateCount(String),
+    InvalidPreset(String),
+    PresetFailed(String),
 }
 
 impl From<ErrorCode> for response::Error {
     fn from(code: ErrorCode) -> Self {
         let (code, msg) = match code {
             ErrorCode::NotReady => (StatusCode::NotReady, "Not ready".to_string()),
+            ErrorCode::InvalidPauseTimeout(value) => (
+                StatusCode::InvalidPauseTimeout,
+                format!("Invalid pause timeout '{}'", value),
+            ),
+            ErrorCode::NotPaused => (StatusCode::NotPaused, "Mining is not paused".to_string()),
+            ErrorCode::InvalidPowerTarget(value) => (
+                StatusCode::InvalidPowerTarget,
+                format!("Invalid power target '{}'", value),
+            ),
+            ErrorCode::InvalidMidstateCount(value) => (
+                StatusCode::InvalidMidstateCount,
+                format!("Invalid midstate count '{}'", value),
+            ),
+            ErrorCode::InvalidPreset(value) => (
+                StatusCode::InvalidPreset,
+                format!("Invalid preset '{}'", value),
+            ),
+            ErrorCode::PresetFailed(reason) => (
+                StatusCode::PresetFailed,
+                format!("Failed to apply preset: {}", reason),
+            ),
         };
 
         Self::from_custom_error(code, msg)
@@ -66,6 +116,10 @@ pub struct DevDetailInfo {
     pub chips: u32,
     #[serde(rename = "Cores")]
     pub cores: u32,
+    #[serde(rename = "Serial")]
+    pub serial: String,
+    #[serde(rename = "Chip Bin")]
+    pub chip_bin: i32,
 }
 
 #[derive(Serialize, PartialEq, Clone, Debug)]
@@ -80,6 +134,10 @@ pub struct Handler {
     model: String,
     managers: Vec<Arc<crate::Manager>>,
     monitor: Arc<monitor::Monitor>,
+    pause_controller: Arc<pause::Controller>,
+    power_target_controller: Arc<power_target::Controller>,
+    preset_controller: Arc<preset::Controller>,
+    psu_monitor: Option<Arc<psu::Monitor>>,
 }
 
 impl Handler {
@@ -87,14 +145,122 @@ impl Handler {
         model: String,
         managers: Vec<Arc<crate::Manager>>,
         monitor: Arc<monitor::Monitor>,
+        pause_controller: Arc<pause::Controller>,
+        power_target_controller: Arc<power_target::Controller>,
+        preset_controller: Arc<preset::Controller>,
+        psu_monitor: Option<Arc<psu::Monitor>>,
     ) -> Self {
         Self {
             model,
             managers,
             monitor,
+            pause_controller,
+            power_target_controller,
+            preset_controller,
+            psu_monitor,
+        }
+    }
+
+    /// Optional timeout of pause in seconds can be passed as a number or a string
+    fn parse_pause_timeout(parameter: Option<&json::Value>) -> command::Result<Option<Duration>> {
+        let seconds = match parameter {
+            None => return Ok(None),
+            Some(json::Value::Number(value)) => value.as_u64(),
+            Some(json::Value::String(value)) if value.is_empty() => return Ok(None),
+            Some(json::Value::String(value)) => value.parse::<u64>().ok(),
+            Some(_) => None,
+        };
+        match seconds {
+            Some(seconds) if seconds > 0 => Ok(Some(Duration::from_secs(seconds))),
+            _ => Err(ErrorCode::InvalidPauseTimeout(
+                parameter.map(|value| value.to_string()).unwrap_or_default(),
+            )
+            .into()),
+        }
+    }
+
+    fn check_pause(_command: &str, parameter: &Option<&json::Value>) -> command::Result<()> {
+        Self::parse_pause_timeout(*parameter).map(|_| ())
+    }
+
+    /// Power target in W can be passed as a number or a string. Zero disables the power target
+    /// mode and missing parameter keeps the current one.
+    fn parse_power_target(parameter: Option<&json::Value>) -> command::Result<Option<Option<f64>>> {
+        let power = match parameter {
+            None => return Ok(None),
+            Some(json::Value::Number(value)) => value.as_f64(),
+            Some(json::Value::String(value)) if value.is_empty() => return Ok(None),
+            Some(json::Value::String(value)) => value.parse::<f64>().ok(),
+            Some(_) => None,
+        };
+        match power {
+            Some(0.0) => Ok(Some(None)),
+            Some(power) if power >= config::POWER_TARGET_W_MIN => Ok(Some(Some(power))),
+            _ => Err(ErrorCode::InvalidPowerTarget(
+                parameter.map(|value| value.to_string()).unwrap_or_default(),
+            )
+            .into()),
+        }
+    }
+
+    fn check_power_target(_command: &str, parameter: &Option<&json::Value>) -> command::Result<()> {
+        Self::parse_power_target(*parameter).map(|_| ())
+    }
+
+    /// Midstate count can be passed as a number or a string, it cannot exceed the configured
+    /// one. Missing parameter keeps the current one.
+    fn parse_midstate_count(
+        parameter: Option<&json::Value>,
+    ) -> command::Result<Option<MidstateCount>> {
+        let count = match parameter {
+            None => return Ok(None),
+            Some(json::Value::Number(value)) => value.as_u64(),
+            Some(json::Value::String(value)) if value.is_empty() => return Ok(None),
+            Some(json::Value::String(value)) => value.parse::<u64>().ok(),
+            Some(_) => None,
+        };
+        match count.and_then(|count| MidstateCount::try_new(count as usize)) {
+            Some(midstate_count) => Ok(Some(midstate_count)),
+            None => Err(ErrorCode::InvalidMidstateCount(
+                parameter.map(|value| value.to_string()).unwrap_or_default(),
+            )
+            .into()),
         }
     }
 
+    /// Preset is selected for all chains by its name or for one chain by `<chain>,<name>`.
+    /// Missing parameter only lists the selected presets.
+    fn parse_preset(
+        parameter: Option<&json::Value>,
+    ) -> command::Result<Option<(Option<usize>, &str)>> {
+        let value = match parameter {
+            None => return Ok(None),
+            Some(json::Value::String(value)) if value.is_empty() => return Ok(None),
+            Some(json::Value::String(value)) => value.as_str(),
+            Some(value) => Err(ErrorCode::InvalidPreset(value.to_string()))?,
+        };
+        let mut parts = value.splitn(2, ',');
+        match (parts.next(), parts.next()) {
+            (Some(name), None) => Ok(Some((None, name))),
+            (Some(chain), Some(name)) if !name.is_empty() => match chain.parse::<usize>() {
+                Ok(hashboard_idx) => Ok(Some((Some(hashboard_idx), name))),
+                Err(_) => Err(ErrorCode::InvalidPreset(value.to_string()).into()),
+            },
+            _ => Err(ErrorCode::InvalidPreset(value.to_string()).into()),
+        }
+    }
+
+    fn check_preset(_command: &str, parameter: &Option<&json::Value>) -> command::Result<()> {
+        Self::parse_preset(*parameter).map(|_| ())
+    }
+
+    fn check_midstate_count(
+        _command: &str,
+        parameter: &Option<&json::Value>,
+    ) -> command::Result<()> {
+        Self::parse_midstate_count(*parameter).map(|_| ())
+    }
+
     fn get_monitor_status(&self) -> command::Result<monitor::Status> {
         match self.monitor.status_receiver.borrow().clone() {
             Some(status) => Ok(status),
@@ -109,10 +275,12 @@ impl Handler {
             let mut chip_count = 0;
             let mut voltage = 0.0;
             let mut frequency = 0;
+            let mut board_info = crate::hashboard::Info::default();
             if let Some(hash_chain) = inner.hash_chain.as_ref() {
                 chip_count = hash_chain.chip_count;
                 voltage = hash_chain.get_voltage().await.as_volts() as f64;
                 frequency = hash_chain.get_frequency().await.avg() as u32;
+                board_info = hash_chain.board_info.clone();
             }
             list.push(response::DevDetail {
                 idx: list.len() as i32,
@@ -127,6 +295,8 @@ impl Handler {
                     frequency,
                     chips: chip_count as u32,
                     cores: (chip_count * crate::bm1387::NUM_CORES_ON_CHIP) as u32,
+                    serial: board_info.serial.unwrap_or_default(),
+                    chip_bin: board_info.chip_bin.map(i32::from).unwrap_or(-1),
                 },
             });
         }
@@ -184,6 +354,297 @@ impl Handler {
         Ok(response::ext::Temps { list: list })
     }
 
+    async fn handle_efficiency(&self) -> command::Result<response::ext::Efficiency> {
+        let mut list = vec![];
+        for manager in self.managers.iter() {
+            let efficiency = manager.get_efficiency().await;
+            list.push(response::ext::ChainEfficiency {
+                idx: list.len() as i32,
+                id: manager.hashboard_idx as i32,
+                power: efficiency.power,
+                efficiency_5s: efficiency.efficiency_5s.unwrap_or_default(),
+                efficiency_1m: efficiency.efficiency_1m.unwrap_or_default(),
+                efficiency_15m: efficiency.efficiency_15m.unwrap_or_default(),
+            });
+        }
+        Ok(response::ext::Efficiency { list })
+    }
+
+    async fn handle_chips(&self) -> command::Result<response::ext::Chips> {
+        let mut list = vec![];
+        let now = Instant::now();
+        for manager in self.managers.iter() {
+            let chips = manager
+                .work_solver_stats()
+                .valid_chip_backend_diff()
+                .take_snapshot()
+                .await;
+            for (chip_idx, chip) in chips.iter().enumerate() {
+                let mhs = |interval| chip.to_mega_hashes(interval, now).into_f64();
+                list.push(response::ext::ChipHashrate {
+                    idx: list.len() as i32,
+                    id: manager.hashboard_idx as i32,
+                    chip: chip_idx as i32,
+                    solutions: chip.solutions,
+                    mhs_1m: mhs(*stats::TIME_MEAN_INTERVAL_1M),
+                    mhs_15m: mhs(*stats::TIME_MEAN_INTERVAL_15M),
+                    mhs_24h: mhs(*stats::TIME_MEAN_INTERVAL_24H),
+                });
+            }
+        }
+        Ok(response::ext::Chips { list })
+    }
+
+    async fn handle_chip_errors(&self) -> command::Result<response::ext::ChipErrors> {
+        let mut list = vec![];
+        for manager in self.managers.iter() {
+            let inner = manager.inner.lock().await;
+            if let Some(hash_chain) = inner.hash_chain.as_ref() {
+                let counter = hash_chain.snapshot_counter().await;
+                for (chip_idx, chip) in counter.chip.iter().enumerate() {
+                    list.push(response::ext::ChipError {
+                        idx: list.len() as i32,
+                        id: manager.hashboard_idx as i32,
+                        chip: chip_idx as i32,
+                        nonces: (chip.valid / counter.asic_difficulty.max(1)) as u64,
+                        errors: chip.errors as u64,
+                        duplicates: chip.duplicates as u64,
+                        error_rate: chip.error_rate(counter.asic_difficulty).unwrap_or_default(),
+                        chain_crc_errors: counter.crc_errors as u64,
+                        chain_rx_errors: counter.rx_errors as u64,
+                        chain_subrange_errors: counter.subrange_errors as u64,
+                        isolated: hash_chain.is_chip_isolated(chip_idx),
+                    });
+                }
+            }
+        }
+        Ok(response::ext::ChipErrors { list })
+    }
+
+    async fn handle_chip_temps(&self) -> command::Result<response::ext::ChipTemps> {
+        let mut list = vec![];
+        for manager in self.managers.iter() {
+            let inner = manager.inner.lock().await;
+            if let Some(hash_chain) = inner.hash_chain.as_ref() {
+                for chip in hash_chain.chip_temperatures() {
+                    let sensor::Temperature { local, remote } = chip.temperature;
+                    list.push(response::ext::ChipTemp {
+                        idx: list.len() as i32,
+                        id: manager.hashboard_idx as i32,
+                        chip: chip.chip_idx as i32,
+                        board: Option::from(local).unwrap_or(0.0) as f64,
+                        temperature: Option::from(remote).unwrap_or(0.0) as f64,
+                    });
+                }
+            }
+        }
+        Ok(response::ext::ChipTemps { list })
+    }
+
+    async fn handle_self_test(&self) -> command::Result<response::ext::SelfTest> {
+        let mut list = vec![];
+        for manager in self.managers.iter() {
+            let inner = manager.inner.lock().await;
+            let report = inner
+                .hash_chain
+                .as_ref()
+                .and_then(|hash_chain| hash_chain.get_self_test_report());
+            if let Some(report) = report {
+                for (chip_idx, status) in report.chips.iter().enumerate() {
+                    list.push(response::ext::ChipSelfTest {
+                        idx: list.len() as i32,
+                        id: manager.hashboard_idx as i32,
+                        chip: chip_idx as i32,
+                        status: status.to_string(),
+                    });
+                }
+            }
+        }
+        Ok(response::ext::SelfTest { list })
+    }
+
+    async fn handle_registers(&self) -> command::Result<response::ext::Registers> {
+        let mut list = vec![];
+        for manager in self.managers.iter() {
+            let inner = manager.inner.lock().await;
+            if let Some(hash_chain) = inner.hash_chain.as_ref() {
+                let (dump, error) = match hash_chain.dump_registers().await {
+                    Ok(report) => (
+                        Some(json::to_value(report).expect("BUG: cannot serialize register dump")),
+                        None,
+                    ),
+                    Err(e) => (None, Some(e.to_string())),
+                };
+                list.push(response::ext::ChainRegisters {
+                    idx: list.len() as i32,
+                    id: manager.hashboard_idx as i32,
+                    dump,
+                    error,
+                });
+            }
+        }
+        Ok(response::ext::Registers { list })
+    }
+
+    async fn handle_reset_chip_errors(&self) -> command::Result<response::ext::ResetChipErrors> {
+        let mut reset_chains = 0;
+        for manager in self.managers.iter() {
+            let inner = manager.inner.lock().await;
+            if let Some(hash_chain) = inner.hash_chain.as_ref() {
+                hash_chain.reset_counter().await;
+                reset_chains += 1;
+            }
+        }
+        Ok(response::ext::ResetChipErrors {
+            state: response::ext::ResetChipErrorsState { reset_chains },
+        })
+    }
+
+    async fn handle_lost_work(&self) -> command::Result<response::ext::LostWork> {
+        let mut list = vec![];
+        for manager in self.managers.iter() {
+            let inner = manager.inner.lock().await;
+            if let Some(hash_chain) = inner.hash_chain.as_ref() {
+                let stats = hash_chain.get_work_stats().await;
+                let midstate_count = hash_chain.midstate_count.to_count();
+                list.push(response::ext::ChainLostWork {
+                    idx: list.len() as i32,
+                    id: manager.hashboard_idx as i32,
+                    retired_work: stats.retired,
+                    empty_work: stats.empty,
+                    expected_empty_work: stats
+                        .expected_empty(midstate_count, hash_chain.asic_difficulty),
+                    lost_work: stats.lost(midstate_count, hash_chain.asic_difficulty),
+                });
+            }
+        }
+        Ok(response::ext::LostWork { list })
+    }
+
+    fn mining_state(status: pause::Status) -> response::ext::MiningState {
+        response::ext::MiningState {
+            paused: status.paused,
+            paused_chains: status.paused_chains as u32,
+            resume_in: status.resume_in.map(|resume_in| resume_in.as_secs()),
+        }
+    }
+
+    async fn handle_pause(
+        &self,
+        parameter: Option<&json::Value>,
+    ) -> command::Result<response::ext::Pause> {
+        let timeout = Self::parse_pause_timeout(parameter)?;
+        let status = self.pause_controller.clone().pause(timeout).await;
+        Ok(response::ext::Pause {
+            state: Self::mining_state(status),
+        })
+    }
+
+    async fn handle_resume(&self) -> command::Result<response::ext::Resume> {
+        if !self.pause_controller.resume().await {
+            Err(ErrorCode::NotPaused)?;
+        }
+        Ok(response::ext::Resume {
+            state: Self::mining_state(self.pause_controller.status().await),
+        })
+    }
+
+    async fn handle_power_target(
+        &self,
+        parameter: Option<&json::Value>,
+    ) -> command::Result<response::ext::PowerTarget> {
+        let status = match Self::parse_power_target(parameter)? {
+            Some(target) => self.power_target_controller.set_target(target).await,
+            None => self.power_target_controller.status().await,
+        };
+        Ok(response::ext::PowerTarget {
+            state: response::ext::PowerTargetState {
+                target: status.target,
+                power: status.power,
+                limited_chains: status.limited_chains as u32,
+            },
+        })
+    }
+
+    async fn handle_preset(
+        &self,
+        parameter: Option<&json::Value>,
+    ) -> command::Result<response::ext::Presets> {
+        if let Some((hashboard_idx, name)) = Self::parse_preset(parameter)? {
+            self.preset_controller
+                .select(hashboard_idx, name)
+                .await
+                .map_err(|e| match e {
+                    preset::Error::UnknownPreset(_) | preset::Error::UnknownChain(_) => {
+                        ErrorCode::InvalidPreset(e.to_string())
+                    }
+                    _ => ErrorCode::PresetFailed(e.to_string()),
+                })?;
+        }
+        let mut list = vec![];
+        for chain in self.preset_controller.status().await {
+            list.push(response::ext::ChainPreset {
+                idx: list.len() as i32,
+                id: chain.hashboard_idx as i32,
+                preset: chain.name.unwrap_or_default(),
+            });
+        }
+        Ok(response::ext::Presets { list })
+    }
+
+    async fn handle_psu(&self) -> command::Result<response::ext::Psu> {
+        // all values are missing when the monitoring is disabled
+        let status = match self.psu_monitor.as_ref() {
+            Some(psu_monitor) => psu_monitor.status().await,
+            None => Default::default(),
+        };
+        let reading = status.reading.unwrap_or_default();
+        Ok(response::ext::Psu {
+            state: response::ext::PsuState {
+                input_voltage: reading.input_voltage,
+                output_current: reading.output_current,
+                temperature: reading.temperature,
+                under_voltage: status.under_voltage,
+            },
+        })
+    }
+
+    async fn handle_restart(&self) -> command::Result<response::ext::Restart> {
+        let restarted_chains = restart::restart_chains(&self.managers).await;
+        Ok(response::ext::Restart {
+            state: response::ext::RestartState {
+                restarted_chains: restarted_chains as u32,
+            },
+        })
+    }
+
+    async fn handle_midstate_count(
+        &self,
+        parameter: Option<&json::Value>,
+    ) -> command::Result<response::ext::MidstateCount> {
+        let restarted_chains = match Self::parse_midstate_count(parameter)? {
+            Some(midstate_count) => restart::set_midstate_count(&self.managers, midstate_count)
+                .await
+                .map_err(|_| {
+                    ErrorCode::InvalidMidstateCount(
+                        parameter.map(|value| value.to_string()).unwrap_or_default(),
+                    )
+                })?,
+            None => 0,
+        };
+        let midstate_count = self
+            .managers
+            .first()
+            .map(|manager| manager.get_midstate_count().to_count())
+            .unwrap_or_default();
+        Ok(response::ext::MidstateCount {
+            state: response::ext::MidstateCountState {
+                midstate_count: midstate_count as u32,
+                restarted_chains: restarted_chains as u32,
+            },
+        })
+    }
+
     async fn handle_fans(&self) -> command::Result<response::ext::Fans> {
         let status = self.get_monitor_status()?;
         let speed = status.fan_speed.map(|speed| speed.to_pwm()).unwrap_or(0);
@@ -208,15 +669,52 @@ pub fn create_custom_commands(
     backend: Arc<crate::Backend>,
     managers: Vec<Arc<crate::Manager>>,
     monitor: Arc<monitor::Monitor>,
+    pause_controller: Arc<pause::Controller>,
+    power_target_controller: Arc<power_target::Controller>,
+    preset_controller: Arc<preset::Controller>,
+    psu_monitor: Option<Arc<psu::Monitor>>,
 ) -> Option<command::Map> {
-    let handler = Arc::new(Handler::new(backend.to_string(), managers, monitor));
+    let handler = Arc::new(Handler::new(
+        backend.to_string(),
+        managers,
+        monitor,
+        pause_controller,
+        power_target_controller,
+        preset_controller,
+        psu_monitor,
+    ));
 
-    let custom_commands = commands![
+    let mut custom_commands = command::read_only(commands![
         (DEVDETAILS: ParameterLess -> handler.handle_dev_details),
         (TEMPCTRL: ParameterLess -> handler.handle_temp_ctrl),
         (TEMPS: ParameterLess -> handler.handle_temps),
-        (FANS: ParameterLess -> handler.handle_fans)
-    ];
+        (FANS: ParameterLess -> handler.handle_fans),
+        (PSU: ParameterLess -> handler.handle_psu),
+        (EFFICIENCY: ParameterLess -> handler.handle_efficiency),
+        (LOST_WORK: ParameterLess -> handler.handle_lost_work),
+        (CHIPS: ParameterLess -> handler.handle_chips),
+        (CHIP_ERRORS: ParameterLess -> handler.handle_chip_errors),
+        (SELF_TEST: ParameterLess -> handler.handle_self_test),
+        (CHIP_TEMPS: ParameterLess -> handler.handle_chip_temps)
+    ]);
+
+    // mining control requires administrator privilege
+    let check_pause: command::ParameterCheckHandler = Box::new(Handler::check_pause);
+    let check_power_target: command::ParameterCheckHandler = Box::new(Handler::check_power_target);
+    let check_preset: command::ParameterCheckHandler = Box::new(Handler::check_preset);
+    let check_midstate_count: command::ParameterCheckHandler =
+        Box::new(Handler::check_midstate_count);
+    custom_commands.extend(commands![
+        (PAUSE: Parameter(check_pause) -> handler.handle_pause),
+        (RESUME: ParameterLess -> handler.handle_resume),
+        (RESTART: ParameterLess -> handler.handle_restart),
+        (RESET_CHIP_ERRORS: ParameterLess -> handler.handle_reset_chip_errors),
+        (POWER_TARGET: Parameter(check_power_target) -> handler.handle_power_target),
+        (PRESET: Parameter(check_preset) -> handler.handle_preset),
+        (MIDSTATE_COUNT: Parameter(check_midstate_count) -> handler.handle_midstate_count),
+        // reading of chip registers competes with the chain for the command interface
+        (REGISTERS: ParameterLess -> handler.handle_registers)
+    ]);
 
     Some(custom_commands)
 }
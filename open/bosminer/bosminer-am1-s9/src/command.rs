@@ -40,8 +40,8 @@ use futures::lock::Mutex;
 use ii_async_compat::futures;
 use std::sync::Arc;
 
+use crate::error::ResultExt;
 use crate::error::{self, ErrorKind};
-use failure::ResultExt;
 
 /// Interface definition for command-stack API - reading and writing of registers
 ///
@@ -75,9 +75,46 @@ pub trait Interface: Send + Sync {
     ) -> error::Result<T> {
         assert!(!chip_address.is_broadcast());
         let mut responses = self.read_register::<T>(chip_address).await?;
+        if responses.len() != 1 {
+            Err(ErrorKind::Hashchip(format!(
+                "chip {:?} returned {} replies for register {:#x} instead of one",
+                chip_address,
+                responses.len(),
+                T::REG_NUM
+            )))?
+        }
         return Ok(responses.remove(0));
     }
 
+    /// Read register of all chips on the chain (broadcast), replies are ordered by chip address
+    async fn read_all_registers<T: bm1387::Register>(&self) -> error::Result<Vec<T>> {
+        self.read_register::<T>(ChipAddress::All).await
+    }
+
+    /// Read register of the single chip `chip_idx`
+    async fn read_chip_register<T: bm1387::Register>(&self, chip_idx: usize) -> error::Result<T> {
+        self.read_one_register::<T>(ChipAddress::unicast(chip_idx)?)
+            .await
+    }
+
+    /// Write register of all chips on the chain (broadcast)
+    async fn broadcast_register<'a, T: bm1387::Register>(
+        &'a self,
+        value: &'a T,
+    ) -> error::Result<()> {
+        self.write_register(ChipAddress::All, value).await
+    }
+
+    /// Write register of the single chip `chip_idx`, other chips are left intact
+    async fn write_chip_register<'a, T: bm1387::Register>(
+        &'a self,
+        chip_idx: usize,
+        value: &'a T,
+    ) -> error::Result<()> {
+        self.write_register(ChipAddress::unicast(chip_idx)?, value)
+            .await
+    }
+
     /// Write register(s) and read it/them back to verify they were written correctly
     /// Same as `write_register`, but followed by `read_register` on the same register.
     async fn write_register_readback<'a, T: bm1387::Register>(
@@ -117,6 +154,8 @@ pub struct InnerContext {
     /// If `chip_count` is `None`, number of chips haven't been determined yet so
     /// skip the check.
     chip_count: Option<usize>,
+    /// Number of unsolicited or malformed responses (they passed CRC check in the IP core)
+    rx_errors: usize,
 }
 
 /// Interface to access chip registers via series of commands
@@ -127,11 +166,43 @@ impl InnerContext {
     /// How long to wait for command RX queue flush
     const COMMAND_FLUSH_TIMEOUT: Duration = Duration::from_micros(5);
 
+    /// Number of attempts to read register before the read fails
+    const COMMAND_READ_ATTEMPTS: usize = 3;
+
     /// Read register(s)
     ///
+    /// Reading of registers has no side effects so the read is retried when unexpected number
+    /// of replies has been received (e.g. reply lost due to CRC error or unsolicited reply
+    /// received). Replies of BM1387 do not carry chip address nor register number, so they
+    /// are matched to chips only by their order and stale replies are flushed before retry.
+    async fn read_register<T: bm1387::Register>(
+        &mut self,
+        chip_address: ChipAddress,
+    ) -> error::Result<Vec<T>> {
+        let mut attempt = 1;
+        loop {
+            match self.try_read_register::<T>(chip_address).await {
+                Err(e) if attempt < Self::COMMAND_READ_ATTEMPTS => {
+                    warn!(
+                        "Chain {}: read of register {:#x} failed (attempt {}): {}",
+                        self.command_io.hashboard_idx,
+                        T::REG_NUM,
+                        attempt,
+                        e
+                    );
+                    self.flush_command_rx().await?;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Read register(s) once
+    ///
     /// Throw an error if unexpected number of replies have been received.
     /// (expected number is one reply per chip)
-    async fn read_register<T: bm1387::Register>(
+    async fn try_read_register<T: bm1387::Register>(
         &mut self,
         chip_address: ChipAddress,
     ) -> error::Result<Vec<T>> {
@@ -150,8 +221,13 @@ impl InnerContext {
                 .await?
             {
                 Some(one_response) => {
-                    let one_response = bm1387::CmdResponse::unpack_from_slice(&one_response)
-                        .context(format!("response unpacking failed"))?;
+                    let one_response = match bm1387::CmdResponse::unpack_from_slice(&one_response) {
+                        Ok(one_response) => one_response,
+                        Err(e) => {
+                            self.rx_errors += 1;
+                            Err(e).context(format!("response unpacking failed"))?
+                        }
+                    };
                     responses.push(one_response.value);
                     // exit early if we expect just one response
                     if chip_address != ChipAddress::All {
@@ -164,26 +240,30 @@ impl InnerContext {
 
         // figure out how many responses are we expecting, and thrown an error
         // if less were received
-        if chip_address == ChipAddress::All {
-            if let Some(chip_count) = self.chip_count {
-                // for broadcast we expect chip_count responses
-                if chip_count != responses.len() {
-                    Err(ErrorKind::Hashchip(format!(
-                        "Number of responses {} of GetStatusCmd(reg={:#x}) doesn't match chip count {}",
-                        responses.len(),
-                        T::REG_NUM,
-                        chip_count
-                    )))?;
+        match chip_address {
+            ChipAddress::All => {
+                if let Some(chip_count) = self.chip_count {
+                    // for broadcast we expect chip_count responses
+                    if chip_count != responses.len() {
+                        Err(ErrorKind::Hashchip(format!(
+                            "Number of responses {} of GetStatusCmd(reg={:#x}) doesn't match chip count {}",
+                            responses.len(),
+                            T::REG_NUM,
+                            chip_count
+                        )))?;
+                    }
                 }
             }
-        } else {
-            if responses.len() != 1 {
-                Err(ErrorKind::Hashchip(format!(
-                    "No response for GetStatusCmd(reg={:#x}) from chip {:?}",
+            ChipAddress::One(chip) => match responses.len() {
+                0 => Err(ErrorKind::ChipTimeout { chip })?,
+                1 => {}
+                count => Err(ErrorKind::Hashchip(format!(
+                    "Number of responses {} of GetStatusCmd(reg={:#x}) from chip {} is not 1",
+                    count,
                     T::REG_NUM,
-                    chip_address
-                )))?;
-            }
+                    chip
+                )))?,
+            },
         }
 
         // convert to registers
@@ -200,6 +280,7 @@ impl InnerContext {
             .await?
         {
             warn!("extra garbage command response: {:#x?}", response);
+            self.rx_errors += 1;
         }
         Ok(())
     }
@@ -236,10 +317,15 @@ impl InnerContext {
         self.chip_count = Some(chip_count);
     }
 
+    fn rx_error_count(&self) -> usize {
+        self.rx_errors
+    }
+
     pub fn new(command_io: io::CommandRxTx) -> Self {
         Self {
             command_io,
             chip_count: None,
+            rx_errors: 0,
         }
     }
 }
@@ -281,6 +367,11 @@ impl Context {
         inner.set_chip_count(chip_count);
     }
 
+    /// Number of unsolicited or malformed command responses received so far
+    pub async fn rx_error_count(&self) -> usize {
+        self.inner.lock().await.rx_error_count()
+    }
+
     pub fn new(command_io: io::CommandRxTx) -> Self {
         Self {
             inner: Arc::new(Mutex::new(InnerContext::new(command_io))),
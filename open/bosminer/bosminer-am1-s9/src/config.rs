@@ -31,27 +31,48 @@ use ii_logging::macros::*;
 
 pub mod api;
 mod metadata;
+pub mod stock;
 pub mod support;
 
-use crate::bm1387::MidstateCount;
+use crate::bm1387::{self, MidstateCount};
 use crate::fan;
+use crate::governor;
+use crate::hashboard;
 use crate::hooks;
+use crate::io;
+use crate::isolation;
+use crate::led;
 use crate::monitor;
+use crate::pause;
 use crate::power;
+use crate::preset;
+use crate::psu;
+use crate::ramp;
+use crate::recovery;
+use crate::throttle;
+use crate::tuner;
 use crate::FrequencySettings;
 
 use support::OptionDefault;
 
+use bosminer::alert;
 use bosminer::client;
 use bosminer::hal::{self, BackendConfig as _};
+use bosminer::influx;
+use bosminer::mqtt;
 
+use bosminer_config::ii_config::{self, Secret};
 use bosminer_config::{ClientDescriptor, ClientUserInfo};
 
+use ii_cgminer_api::command;
+
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 use std::collections::{BTreeMap, HashSet};
+use std::convert::TryFrom;
 use std::fmt;
 use std::fs;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -71,12 +92,40 @@ pub const ASYNC_LOGGER_DRAIN_CHANNEL_SIZE: usize = 4096;
 /// TODO: Maybe don't add `.toml` prefix so we could use even JSON
 pub const DEFAULT_CONFIG_PATH: &'static str = "/etc/bosminer.toml";
 
+/// Prefix of environment variables overriding the configuration file, the frequency is set e.g. by
+/// `BOSMINER__HASH_CHAIN_GLOBAL__FREQUENCY`
+pub const ENV_PREFIX: &'static str = "BOSMINER";
+
 /// Default Hardware ID path
 pub const DEFAULT_HW_ID_PATH: &'static str = "/tmp/miner_hwid";
 
+/// File with hostname used as default MQTT client identifier
+pub const HOSTNAME_PATH: &'static str = "/proc/sys/kernel/hostname";
+
+/// MQTT client identifier used when hostname cannot be read
+pub const DEFAULT_MQTT_CLIENT_ID: &'static str = "bosminer";
+
+/// InfluxDB `host` tag used when hostname cannot be read
+pub const DEFAULT_INFLUX_HOST: &'static str = "bosminer";
+
+/// Location of lifetime statistics which are kept across restarts
+pub const DEFAULT_PERSISTENT_STATS_PATH: &'static str = "/etc/bosminer_stats.json";
+
+/// Location of operating points found by autotuning which are kept across restarts
+pub const DEFAULT_AUTOTUNING_STATE_PATH: &'static str = "/etc/bosminer_autotuning.json";
+
 /// Default value for hash chain enabled flag
 pub const DEFAULT_HASH_CHAIN_ENABLED: bool = true;
 
+/// Default baud rate of communication with chips when hashing at full speed (it matches the
+/// divisors of chips and IP core exactly)
+pub const DEFAULT_CHIP_BAUD_RATE: usize = 1_562_500;
+
+/// Default frequency increment of one step of the frequency ramp during chain start
+pub const DEFAULT_FREQUENCY_RAMP_STEP_MHZ: f64 = 50.0;
+/// Default time the chain settles after each step of the frequency ramp
+pub const DEFAULT_FREQUENCY_RAMP_DELAY_MS: u64 = 100;
+
 /// Default value for pool enabled flag
 pub const DEFAULT_POOL_ENABLED: bool = true;
 
@@ -86,6 +135,12 @@ pub const ASIC_BOOST_MIDSTATE_COUNT: usize = 4;
 /// Default number of midstates
 pub const DEFAULT_ASIC_BOOST: bool = true;
 
+/// Default maximal age of a job in seconds
+pub const DEFAULT_MAX_WORK_AGE_S: u64 = 120;
+
+/// Minimal age of a job in seconds which is still usable
+pub const MAX_WORK_AGE_S_MIN: u64 = 1;
+
 /// Default PLL frequency for clocking the chips in MHz
 pub const DEFAULT_FREQUENCY_MHZ: f64 = 650.0;
 
@@ -99,6 +154,14 @@ pub const DEFAULT_TEMP_CONTROL_MODE: TempControlMode = TempControlMode::Auto;
 pub const DEFAULT_TARGET_TEMP_C: f64 = 89.0;
 pub const DEFAULT_HOT_TEMP_C: f64 = 100.0;
 pub const DEFAULT_DANGEROUS_TEMP_C: f64 = 110.0;
+pub const DEFAULT_CRITICAL_TEMP_C: f64 = 105.0;
+pub const DEFAULT_THROTTLE_HYSTERESIS_C: f64 = 5.0;
+
+/// Default temperature limits in immersion mode. Chips cooled by the fluid run colder so that
+/// a rising temperature signals a failure of the cooling loop much sooner.
+pub const DEFAULT_IMMERSION_HOT_TEMP_C: f64 = 75.0;
+pub const DEFAULT_IMMERSION_DANGEROUS_TEMP_C: f64 = 90.0;
+pub const DEFAULT_IMMERSION_CRITICAL_TEMP_C: f64 = 85.0;
 
 /// Default fan speed for manual target speed
 pub const DEFAULT_FAN_SPEED: usize = 100;
@@ -106,6 +169,30 @@ pub const DEFAULT_FAN_SPEED: usize = 100;
 /// Default minimal running fans for monitoring
 pub const DEFAULT_MIN_FANS: usize = 1;
 
+/// Default lower limit of fan speed in automatic mode
+pub const DEFAULT_MIN_FAN_SPEED: usize = 1;
+
+/// Default state of dynamic frequency scaling
+pub const DEFAULT_FREQUENCY_SCALING_ENABLED: bool = false;
+
+/// Default state of frequency and voltage autotuning
+pub const DEFAULT_AUTOTUNING_ENABLED: bool = false;
+
+/// Default state of power supply monitoring when its section is present
+pub const DEFAULT_PSU_ENABLED: bool = true;
+
+/// Default state of signalling of the miner state by front panel LEDs
+pub const DEFAULT_LED_ENABLED: bool = true;
+
+/// Default state of handling of the front panel button
+pub const DEFAULT_BUTTON_ENABLED: bool = true;
+
+/// Default state of automatic re-initialization of dead hash chains
+pub const DEFAULT_AUTO_RECOVERY_ENABLED: bool = true;
+
+/// Miner fails to start by default when any detected hash chain has missing UIO devices
+pub const DEFAULT_SKIP_UNAVAILABLE_CHAINS: bool = false;
+
 /// Index of hashboard that is to be instantiated
 pub const S9_HASHBOARD_INDEX: usize = 8;
 
@@ -121,6 +208,16 @@ pub const FREQUENCY_MHZ_MAX: f64 = 900.0;
 pub const VOLTAGE_V_MIN: f64 = 7.95;
 pub const VOLTAGE_V_MAX: f64 = 9.4;
 
+/// Minimal power target of the whole miner in W
+pub const POWER_TARGET_W_MIN: f64 = 100.0;
+
+/// Minimal hashrate cap of the whole miner in TH/s
+pub const HASHRATE_CAP_TH_MIN: f64 = 1.0;
+
+/// Range of power supply input voltage protected against under-voltage
+pub const PSU_INPUT_VOLTAGE_V_MIN: f64 = 100.0;
+pub const PSU_INPUT_VOLTAGE_V_MAX: f64 = 300.0;
+
 /// Range of monitored temperature
 pub const TEMPERATURE_C_MIN: f64 = 0.0;
 pub const TEMPERATURE_C_MAX: f64 = 200.0;
@@ -133,7 +230,7 @@ pub const FAN_SPEED_MAX: usize = 100;
 pub const FANS_MIN: usize = 0;
 pub const FANS_MAX: usize = 4;
 
-/// Default ASIC difficulty
+/// Default ASIC difficulty, chips report only nonces meeting it (set by the ticket mask register)
 pub const DEFAULT_ASIC_DIFFICULTY: usize = 64;
 
 /// Default hashrate interval used for statistics in seconds
@@ -147,6 +244,16 @@ pub struct ResolvedChainConfig {
     pub frequency: FrequencySettings,
     pub voltage: power::Voltage,
     pub enabled: bool,
+    pub fifo_mode: io::FifoMode,
+    pub baud_rate: usize,
+    pub asic_difficulty: usize,
+    pub chip_temp_sensors: Vec<usize>,
+    pub frequency_ramp: Option<ramp::Config>,
+    pub self_test: bool,
+    pub chip_isolation: Option<isolation::Config>,
+    /// Profiles selected by chip bin of the hashboard. They are empty when frequency or voltage
+    /// of the chain is configured explicitly.
+    pub chip_bin_profiles: Vec<hashboard::Profile>,
 }
 
 #[derive(Serialize, Deserialize, Copy, Clone, Debug)]
@@ -155,6 +262,8 @@ pub enum TempControlMode {
     Auto,
     Manual,
     Disabled,
+    /// Hash boards are cooled by immersion fluid, there are no fans to control or to check
+    Immersion,
 }
 
 impl std::string::ToString for TempControlMode {
@@ -163,6 +272,7 @@ impl std::string::ToString for TempControlMode {
             Self::Auto => "auto".to_string(),
             Self::Manual => "manual".to_string(),
             Self::Disabled => "disabled".to_string(),
+            Self::Immersion => "immersion".to_string(),
         }
     }
 }
@@ -182,6 +292,62 @@ pub struct Format {
 pub struct HashChainGlobal {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub asic_boost: Option<bool>,
+    /// Maximal age of a job in seconds after which its solutions are considered stale
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_work_age: Option<u64>,
+    /// Waiting for FIFO events by interrupts (`irq`) or by polling (`polling`) for boards without
+    /// interrupt lines in the device tree
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fifo_mode: Option<io::FifoMode>,
+    /// Dead hash chains are powered down and initialized again without restart of the miner
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auto_recovery: Option<bool>,
+    /// Time in seconds without any valid nonce (while work is being sent) after which the chain
+    /// is considered dead
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auto_recovery_timeout: Option<u64>,
+    /// Number of failed re-initializations of a dead chain after which the recovery gives up
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auto_recovery_attempts: Option<usize>,
+    /// Paused hash chains are either powered down (`power_off`) or kept initialized at the lowest
+    /// frequency without any work (`idle`) so that they can be resumed quickly
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pause_mode: Option<pause::Mode>,
+    /// Hash chains with missing UIO devices are skipped and the miner starts with the rest
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skip_unavailable_chains: Option<bool>,
+    /// Baud rate of communication with chips after their initialization
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub baud_rate: Option<usize>,
+    /// Difficulty of nonces reported by chips (power of two), higher values lower the load of
+    /// the work RX FIFO at the cost of coarser hashrate and error accounting
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub asic_difficulty: Option<usize>,
+    /// Indexes of chips with temperature sensors attached to their I2C bus which are read in
+    /// addition to the board sensor
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chip_temp_sensors: Option<Vec<usize>>,
+    /// Frequency increment in MHz of one step of the frequency ramp during chain start, zero
+    /// sets the target frequency at once
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frequency_ramp_step: Option<f64>,
+    /// Time in milliseconds the chain settles after each step of the frequency ramp
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frequency_ramp_delay: Option<u64>,
+    /// Chips are tested with work of known solutions during initialization of the chain
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub self_test: Option<bool>,
+    /// Solutions of faulty chips are dropped and not accounted to the chain. Their nonce
+    /// subranges are not reassigned to other chips (see `isolation` module).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chip_isolation: Option<bool>,
+    /// Percentage of hardware errors of a chip within one check interval which is counted as
+    /// faulty interval
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chip_isolation_error_rate: Option<f64>,
+    /// Number of consecutive faulty check intervals after which the chip is isolated
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chip_isolation_check_count: Option<usize>,
     #[serde(flatten)]
     pub overridable: Option<HashChain>,
 }
@@ -208,6 +374,16 @@ pub struct TempControl {
     hot_temp: Option<f64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     dangerous_temp: Option<f64>,
+    /// Chains above this temperature are clocked down, throttling is disabled when it is not set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    throttle_temp: Option<f64>,
+    /// Chains above this temperature are stopped
+    #[serde(skip_serializing_if = "Option::is_none")]
+    critical_temp: Option<f64>,
+    /// Degrees below `throttle_temp` to which the temperature has to drop before the chains are
+    /// clocked up again
+    #[serde(skip_serializing_if = "Option::is_none")]
+    throttle_hysteresis: Option<f64>,
 }
 
 #[derive(Serialize, Deserialize, Default, Clone, Debug)]
@@ -217,6 +393,474 @@ pub struct FanControl {
     speed: Option<usize>,
     #[serde(skip_serializing_if = "Option::is_none")]
     min_fans: Option<usize>,
+    /// Lower limit of fan speed in percent when it is controlled by target temperature
+    #[serde(skip_serializing_if = "Option::is_none")]
+    min_speed: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Alerts {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    webhooks: Option<Vec<String>>,
+    /// Minimal period in seconds between two alerts of the same kind from the same source
+    #[serde(skip_serializing_if = "Option::is_none")]
+    min_interval: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    retries: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Mqtt {
+    /// Broker URL in format `mqtt://host[:port]` or `mqtts://host[:port]`
+    broker: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    client_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    user: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    password: Option<Secret<String>>,
+    /// Prefix of all published topics
+    #[serde(skip_serializing_if = "Option::is_none")]
+    topic: Option<String>,
+    /// Period of telemetry publishing in seconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    interval: Option<u64>,
+}
+
+impl Mqtt {
+    fn resolve(&self) -> Result<mqtt::Config, String> {
+        let client_id = match self.client_id.as_ref() {
+            Some(client_id) => client_id.clone(),
+            None => fs::read_to_string(HOSTNAME_PATH)
+                .map(|hostname| hostname.trim().to_string())
+                .unwrap_or_else(|_| DEFAULT_MQTT_CLIENT_ID.to_string()),
+        };
+        let mut config =
+            mqtt::Config::new(self.broker.as_str(), client_id).map_err(|e| e.to_string())?;
+        config.username = self.user.clone();
+        config.password = self
+            .password
+            .as_ref()
+            .map(|password| password.expose().clone());
+        if let Some(topic) = self.topic.as_ref() {
+            config.topic = topic.clone();
+        }
+        if let Some(interval) = self.interval {
+            config.interval = Duration::from_secs(interval);
+        }
+        Ok(config)
+    }
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Influx {
+    /// Endpoint URL in format `http://host[:port]/write?db=<database>` or `udp://host[:port]`
+    url: String,
+    /// Value of `host` tag attached to all points
+    #[serde(skip_serializing_if = "Option::is_none")]
+    host: Option<String>,
+    /// Authentication token for InfluxDB 2.x
+    #[serde(skip_serializing_if = "Option::is_none")]
+    token: Option<Secret<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    measurement: Option<String>,
+    /// Period of telemetry export in seconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    interval: Option<u64>,
+}
+
+impl Influx {
+    fn resolve(&self) -> Result<influx::Config, String> {
+        let host = match self.host.as_ref() {
+            Some(host) => host.clone(),
+            None => fs::read_to_string(HOSTNAME_PATH)
+                .map(|hostname| hostname.trim().to_string())
+                .unwrap_or_else(|_| DEFAULT_INFLUX_HOST.to_string()),
+        };
+        let mut config = influx::Config::new(self.url.as_str(), host).map_err(|e| e.to_string())?;
+        config.token = self.token.as_ref().map(|token| token.expose().clone());
+        if let Some(measurement) = self.measurement.as_ref() {
+            config.measurement = measurement.clone();
+        }
+        if let Some(interval) = self.interval {
+            config.interval = Duration::from_secs(interval);
+        }
+        Ok(config)
+    }
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Autotuning {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    enabled: Option<bool>,
+    /// Bounds and step of swept frequency in MHz
+    #[serde(skip_serializing_if = "Option::is_none")]
+    frequency_min: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    frequency_max: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    frequency_step: Option<f64>,
+    /// Bounds and step of swept voltage in V
+    #[serde(skip_serializing_if = "Option::is_none")]
+    voltage_min: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    voltage_max: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    voltage_step: Option<f64>,
+    /// Time in seconds for chips to settle after a change of operating point
+    #[serde(skip_serializing_if = "Option::is_none")]
+    settle_time: Option<u64>,
+    /// Time in seconds of measurement of each operating point
+    #[serde(skip_serializing_if = "Option::is_none")]
+    measure_time: Option<u64>,
+    /// Maximal percentage of hardware errors of an acceptable operating point
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_error_rate: Option<f64>,
+    /// File with tuned operating points which are kept across restarts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    state_path: Option<String>,
+}
+
+impl Autotuning {
+    /// Returns `None` when autotuning is disabled
+    fn resolve(&self) -> Result<Option<tuner::Config>, String> {
+        if !self.enabled.unwrap_or(DEFAULT_AUTOTUNING_ENABLED) {
+            return Ok(None);
+        }
+        let mut config = tuner::Config::default();
+        let mhz = |frequency: f64| (frequency * 1_000_000.0) as usize;
+        let mhz_f64 = |frequency: usize| frequency as f64 / 1_000_000.0;
+        let frequency_min = self.frequency_min.unwrap_or(mhz_f64(config.frequency_min));
+        let frequency_max = self.frequency_max.unwrap_or(mhz_f64(config.frequency_max));
+        let voltage_min = self.voltage_min.unwrap_or(config.voltage_min as f64);
+        let voltage_max = self.voltage_max.unwrap_or(config.voltage_max as f64);
+        if frequency_min > frequency_max
+            || frequency_min < FREQUENCY_MHZ_MIN
+            || frequency_max > FREQUENCY_MHZ_MAX
+        {
+            Err(format!(
+                "autotuning frequency '{}..{}' is out of range '{}..{}'",
+                frequency_min, frequency_max, FREQUENCY_MHZ_MIN, FREQUENCY_MHZ_MAX
+            ))?;
+        }
+        if voltage_min > voltage_max || voltage_min < VOLTAGE_V_MIN || voltage_max > VOLTAGE_V_MAX {
+            Err(format!(
+                "autotuning voltage '{}..{}' is out of range '{}..{}'",
+                voltage_min, voltage_max, VOLTAGE_V_MIN, VOLTAGE_V_MAX
+            ))?;
+        }
+        config.frequency_min = mhz(frequency_min);
+        config.frequency_max = mhz(frequency_max);
+        config.voltage_min = voltage_min as f32;
+        config.voltage_max = voltage_max as f32;
+
+        if let Some(frequency_step) = self.frequency_step {
+            if mhz(frequency_step) < tuner::MIN_FREQUENCY_STEP {
+                Err(format!(
+                    "autotuning frequency step must be at least {} MHz",
+                    tuner::MIN_FREQUENCY_STEP / 1_000_000
+                ))?;
+            }
+            config.frequency_step = mhz(frequency_step);
+        }
+        if let Some(voltage_step) = self.voltage_step {
+            if !voltage_step.is_finite() || (voltage_step as f32) < tuner::MIN_VOLTAGE_STEP {
+                Err(format!(
+                    "autotuning voltage step must be at least {} V",
+                    tuner::MIN_VOLTAGE_STEP
+                ))?;
+            }
+            config.voltage_step = voltage_step as f32;
+        }
+        if let Some(settle_time) = self.settle_time {
+            config.settle_time = Duration::from_secs(settle_time);
+        }
+        if let Some(measure_time) = self.measure_time {
+            if measure_time == 0 {
+                Err("autotuning measure time must be positive")?;
+            }
+            config.measure_time = Duration::from_secs(measure_time);
+        }
+        if let Some(max_error_rate) = self.max_error_rate {
+            if !(0.0..=100.0).contains(&max_error_rate) {
+                Err(format!(
+                    "autotuning error rate {} % is out of range '0..100'",
+                    max_error_rate
+                ))?;
+            }
+            config.max_error_rate = max_error_rate / 100.0;
+        }
+        if self.state_path.as_deref() == Some("") {
+            Err("autotuning state path cannot be empty")?;
+        }
+        Ok(Some(config))
+    }
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct FrequencyScaling {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    enabled: Option<bool>,
+    /// Temperature above which chains are clocked down
+    #[serde(skip_serializing_if = "Option::is_none")]
+    target_temp: Option<f64>,
+    /// Degrees below `target_temp` the temperature has to be before chains are clocked up
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hysteresis: Option<f64>,
+    /// Bounds of frequency in MHz
+    #[serde(skip_serializing_if = "Option::is_none")]
+    frequency_min: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    frequency_max: Option<f64>,
+    /// Percentage of hardware errors within one check interval above which chains are clocked
+    /// down
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_error_rate: Option<f64>,
+}
+
+impl FrequencyScaling {
+    /// Returns `None` when frequency scaling is disabled
+    fn resolve(&self) -> Result<Option<governor::Config>, String> {
+        if !self.enabled.unwrap_or(DEFAULT_FREQUENCY_SCALING_ENABLED) {
+            return Ok(None);
+        }
+        let mut config = governor::Config::default();
+        let mhz = |frequency: f64| (frequency * 1_000_000.0) as usize;
+        let mhz_f64 = |frequency: usize| frequency as f64 / 1_000_000.0;
+        let frequency_min = self.frequency_min.unwrap_or(mhz_f64(config.frequency_min));
+        let frequency_max = self.frequency_max.unwrap_or(mhz_f64(config.frequency_max));
+        if frequency_min > frequency_max
+            || frequency_min < FREQUENCY_MHZ_MIN
+            || frequency_max > FREQUENCY_MHZ_MAX
+        {
+            Err(format!(
+                "frequency scaling frequency '{}..{}' is out of range '{}..{}'",
+                frequency_min, frequency_max, FREQUENCY_MHZ_MIN, FREQUENCY_MHZ_MAX
+            ))?;
+        }
+        config.frequency_min = mhz(frequency_min);
+        config.frequency_max = mhz(frequency_max);
+
+        if let Some(target_temp) = self.target_temp {
+            config.target_temp = target_temp as f32;
+        }
+        if let Some(hysteresis) = self.hysteresis {
+            if hysteresis.is_nan() || hysteresis < 0.0 {
+                Err("frequency scaling hysteresis must not be negative")?;
+            }
+            config.hysteresis = hysteresis as f32;
+        }
+        if let Some(max_error_rate) = self.max_error_rate {
+            if !(0.0..=100.0).contains(&max_error_rate) {
+                Err(format!(
+                    "frequency scaling error rate {} % is out of range '0..100'",
+                    max_error_rate
+                ))?;
+            }
+            config.max_error_rate = max_error_rate;
+        }
+        Ok(Some(config))
+    }
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct PowerTarget {
+    /// Power limit of the whole miner in W
+    power: f64,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ChipBinProfile {
+    /// Frequency bin of chips programmed in the PIC of the hashboard
+    bin: u8,
+    /// Frequency in MHz
+    frequency: f64,
+    /// Voltage in V
+    voltage: f64,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Preset {
+    name: String,
+    /// Frequency in MHz
+    frequency: f64,
+    /// Voltage in V
+    voltage: f64,
+    /// Fan target temperature, the current one is kept when it is not set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    target_temp: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct HashrateCap {
+    /// Nominal hashrate limit of the whole miner in TH/s
+    hashrate: f64,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Psu {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    enabled: Option<bool>,
+    /// I2C address (7-bit) of the power supply
+    #[serde(skip_serializing_if = "Option::is_none")]
+    address: Option<u8>,
+    /// Input voltage in V under which mining is paused
+    #[serde(skip_serializing_if = "Option::is_none")]
+    min_input_voltage: Option<f64>,
+    /// Volts above `min_input_voltage` the input voltage has to be before mining is resumed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hysteresis: Option<f64>,
+}
+
+impl Psu {
+    /// Returns `None` when power supply monitoring is disabled
+    fn resolve(&self) -> Result<Option<psu::Config>, String> {
+        if !self.enabled.unwrap_or(DEFAULT_PSU_ENABLED) {
+            return Ok(None);
+        }
+        let mut config = psu::Config::default();
+        if let Some(address) = self.address {
+            if address >= 0x80 {
+                Err(format!("PSU I2C address '{:#x}' is not 7-bit", address))?;
+            }
+            config.address = address;
+        }
+        if let Some(min_input_voltage) = self.min_input_voltage {
+            if !(PSU_INPUT_VOLTAGE_V_MIN..=PSU_INPUT_VOLTAGE_V_MAX).contains(&min_input_voltage) {
+                Err(format!(
+                    "PSU minimal input voltage '{}' is out of range '{}..{}'",
+                    min_input_voltage, PSU_INPUT_VOLTAGE_V_MIN, PSU_INPUT_VOLTAGE_V_MAX
+                ))?;
+            }
+            config.min_input_voltage = Some(min_input_voltage);
+        }
+        if let Some(hysteresis) = self.hysteresis {
+            if hysteresis.is_nan() || hysteresis < 0.0 {
+                Err("PSU voltage hysteresis must not be negative")?;
+            }
+            config.hysteresis = hysteresis;
+        }
+        Ok(Some(config))
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct LedPattern {
+    color: led::Color,
+    /// Time in ms the LED is lit
+    on_time: u64,
+    /// Time in ms the LED is off, it is lit steadily when it is not set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    off_time: Option<u64>,
+}
+
+impl LedPattern {
+    fn resolve(&self) -> Result<led::Pattern, String> {
+        if self.on_time == 0 {
+            Err("LED on time must be positive")?;
+        }
+        Ok(led::Pattern {
+            color: self.color,
+            on_time: Duration::from_millis(self.on_time),
+            off_time: Duration::from_millis(self.off_time.unwrap_or(0)),
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Led {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    enabled: Option<bool>,
+    /// Patterns of individual states of the miner
+    #[serde(skip_serializing_if = "Option::is_none")]
+    normal: Option<LedPattern>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    no_pool: Option<LedPattern>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    degraded: Option<LedPattern>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    overheat: Option<LedPattern>,
+    /// Pattern of identification of the miner by the front panel button
+    #[serde(skip_serializing_if = "Option::is_none")]
+    identify: Option<LedPattern>,
+}
+
+impl Led {
+    /// Returns `None` when the LEDs are not driven by the miner
+    fn resolve(&self) -> Result<Option<led::Config>, String> {
+        if !self.enabled.unwrap_or(DEFAULT_LED_ENABLED) {
+            return Ok(None);
+        }
+        let default = led::Config::default();
+        let resolve = |pattern: &Option<LedPattern>, default| {
+            pattern.as_ref().map_or(Ok(default), LedPattern::resolve)
+        };
+        Ok(Some(led::Config {
+            normal: resolve(&self.normal, default.normal)?,
+            no_pool: resolve(&self.no_pool, default.no_pool)?,
+            degraded: resolve(&self.degraded, default.degraded)?,
+            overheat: resolve(&self.overheat, default.overheat)?,
+            identify: resolve(&self.identify, default.identify)?,
+        }))
+    }
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Button {
+    /// Short press identifies the miner by LEDs and long press restarts hash chains
+    #[serde(skip_serializing_if = "Option::is_none")]
+    enabled: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Api {
+    /// Token granting access to all commands including the ones changing configuration
+    #[serde(skip_serializing_if = "Option::is_none")]
+    admin_token: Option<String>,
+    /// Token granting access to read-only commands. Requests without a token are denied when
+    /// it is set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    read_token: Option<String>,
+}
+
+impl Api {
+    fn resolve(&self) -> Result<Option<command::AccessControl>, String> {
+        let admin_token = match self.admin_token.as_ref() {
+            Some(admin_token) => admin_token,
+            None if self.read_token.is_some() => {
+                Err("API read token cannot be used without admin token".to_string())?
+            }
+            None => return Ok(None),
+        };
+        if admin_token.is_empty() || self.read_token.as_deref() == Some("") {
+            Err("API token cannot be empty".to_string())?
+        }
+        let anonymous = match self.read_token {
+            Some(_) => None,
+            None => Some(command::Privilege::ReadOnly),
+        };
+
+        let mut access_control = command::AccessControl::new(anonymous);
+        if let Some(read_token) = self.read_token.as_ref() {
+            access_control.add_token(read_token.clone(), command::Privilege::ReadOnly);
+        }
+        access_control.add_token(admin_token.clone(), command::Privilege::Admin);
+        Ok(Some(access_control))
+    }
 }
 
 #[derive(Serialize, Deserialize, Default, Debug)]
@@ -238,6 +882,36 @@ pub struct Backend {
     temp_control: Option<TempControl>,
     #[serde(skip_serializing_if = "Option::is_none")]
     fan_control: Option<FanControl>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    alerts: Option<Alerts>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mqtt: Option<Mqtt>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    influx: Option<Influx>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    autotuning: Option<Autotuning>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    frequency_scaling: Option<FrequencyScaling>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    power_target: Option<PowerTarget>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hashrate_cap: Option<HashrateCap>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    psu: Option<Psu>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    led: Option<Led>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    button: Option<Button>,
+    /// Default frequency and voltage of hash chains by chip bin of their hashboard
+    #[serde(rename = "chip_bin_profile")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    chip_bin_profiles: Option<Vec<ChipBinProfile>>,
+    /// Named presets selectable at runtime by API
+    #[serde(rename = "preset")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    presets: Option<Vec<Preset>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    api: Option<Api>,
     #[serde(rename = "group")]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub groups: Option<Vec<bosminer_config::GroupConfig>>,
@@ -293,6 +967,10 @@ pub struct FormatWrapper<B> {
     pub body: B,
 }
 
+/// The body is checked separately by `FormatWrapper::sanity_check()` to distinguish incompatible
+/// format version from an invalid configuration
+impl<B> ii_config::Validate for FormatWrapper<B> {}
+
 impl<B> FormatWrapper<B>
 where
     B: ConfigBody,
@@ -326,9 +1004,13 @@ where
     }
 
     pub fn parse(config_path: &str) -> Result<Self, FormatWrapperError<B>> {
-        // Parse config file - either user specified or the default one
-        let mut config: Self = bosminer_config::parse(config_path)
-            .map_err(|msg| FormatWrapperError::ParsingError(msg))?;
+        Self::load(&ii_config::Loader::new().file(config_path))
+    }
+
+    /// Loads configuration file with overrides from the environment and the command line
+    pub fn load(loader: &ii_config::Loader) -> Result<Self, FormatWrapperError<B>> {
+        let mut config: Self =
+            bosminer_config::load(loader).map_err(|msg| FormatWrapperError::ParsingError(msg))?;
 
         match config.sanity_check() {
             Ok(_) => Ok(config),
@@ -393,11 +1075,271 @@ impl Backend {
             frequency: FrequencySettings::from_frequency((*frequency * 1_000_000.0) as usize),
             // TODO: handle config errors
             voltage: power::Voltage::from_volts(*voltage as f32)
-                .expect("TODO: bad voltage requested"),
+                .expect("BUG: voltage should be checked by sanity check"),
             enabled,
+            fifo_mode: self
+                .hash_chain_global
+                .as_ref()
+                .and_then(|v| v.fifo_mode)
+                .unwrap_or_default(),
+            baud_rate: self
+                .hash_chain_global
+                .as_ref()
+                .and_then(|v| v.baud_rate)
+                .unwrap_or(DEFAULT_CHIP_BAUD_RATE),
+            asic_difficulty: self.resolve_asic_difficulty(),
+            chip_temp_sensors: self
+                .hash_chain_global
+                .as_ref()
+                .and_then(|v| v.chip_temp_sensors.clone())
+                .unwrap_or_default(),
+            frequency_ramp: self.resolve_frequency_ramp(),
+            self_test: self
+                .hash_chain_global
+                .as_ref()
+                .and_then(|v| v.self_test)
+                .unwrap_or(false),
+            chip_isolation: self.resolve_chip_isolation(),
+            chip_bin_profiles: if frequency.is_some() || voltage.is_some() {
+                vec![]
+            } else {
+                self.resolve_chip_bin_profiles()
+            },
+        }
+    }
+
+    fn resolve_chip_bin_profiles(&self) -> Vec<hashboard::Profile> {
+        self.chip_bin_profiles
+            .iter()
+            .flatten()
+            .map(|profile| hashboard::Profile {
+                chip_bin: profile.bin,
+                frequency: FrequencySettings::from_frequency(
+                    (profile.frequency * 1_000_000.0) as usize,
+                ),
+                voltage: power::Voltage::from_volts(profile.voltage as f32)
+                    .expect("BUG: voltage should be checked by sanity check"),
+            })
+            .collect()
+    }
+
+    pub fn resolve_alert_config(&self) -> alert::Config {
+        let mut config = alert::Config::default();
+        if let Some(alerts) = self.alerts.as_ref() {
+            for url in alerts.webhooks.iter().flatten() {
+                config
+                    .add_webhook(url)
+                    .expect("BUG: webhook URL should be checked by sanity check");
+            }
+            if let Some(min_interval) = alerts.min_interval {
+                config.min_interval = Duration::from_secs(min_interval);
+            }
+            if let Some(retries) = alerts.retries {
+                config.retries = retries;
+            }
+        }
+        config
+    }
+
+    /// Returns `None` when MQTT telemetry is disabled
+    pub fn resolve_mqtt_config(&self) -> Option<mqtt::Config> {
+        self.mqtt.as_ref().map(|mqtt| {
+            mqtt.resolve()
+                .expect("BUG: MQTT configuration should be checked by sanity check")
+        })
+    }
+
+    /// Returns `None` when InfluxDB telemetry is disabled
+    pub fn resolve_influx_config(&self) -> Option<influx::Config> {
+        self.influx.as_ref().map(|influx| {
+            influx
+                .resolve()
+                .expect("BUG: InfluxDB configuration should be checked by sanity check")
+        })
+    }
+
+    /// Returns `None` when autotuning is disabled
+    pub fn resolve_autotuning_state_path(&self) -> PathBuf {
+        PathBuf::from(
+            self.autotuning
+                .as_ref()
+                .and_then(|autotuning| autotuning.state_path.as_deref())
+                .unwrap_or(DEFAULT_AUTOTUNING_STATE_PATH),
+        )
+    }
+
+    pub fn resolve_tuner_config(&self) -> Option<tuner::Config> {
+        self.autotuning.as_ref().and_then(|autotuning| {
+            autotuning
+                .resolve()
+                .expect("BUG: autotuning configuration should be checked by sanity check")
+        })
+    }
+
+    /// Returns `None` when power supply monitoring is disabled
+    pub fn resolve_psu_config(&self) -> Option<psu::Config> {
+        self.psu.as_ref().and_then(|psu| {
+            psu.resolve()
+                .expect("BUG: PSU configuration should be checked by sanity check")
+        })
+    }
+
+    /// Returns `None` when the front panel LEDs are not driven by the miner
+    pub fn resolve_led_config(&self) -> Option<led::Config> {
+        match self.led.as_ref() {
+            Some(led) => led
+                .resolve()
+                .expect("BUG: LED configuration should be checked by sanity check"),
+            None if DEFAULT_LED_ENABLED => Some(Default::default()),
+            None => None,
         }
     }
 
+    pub fn resolve_button_enabled(&self) -> bool {
+        self.button
+            .as_ref()
+            .and_then(|button| button.enabled)
+            .unwrap_or(DEFAULT_BUTTON_ENABLED)
+    }
+
+    /// Returns `None` when dynamic frequency scaling is disabled
+    pub fn resolve_governor_config(&self) -> Option<governor::Config> {
+        self.frequency_scaling.as_ref().and_then(|scaling| {
+            scaling
+                .resolve()
+                .expect("BUG: frequency scaling configuration should be checked by sanity check")
+        })
+    }
+
+    /// Returns `None` when the frequency ramp is disabled
+    pub fn resolve_frequency_ramp(&self) -> Option<ramp::Config> {
+        let hash_chain_global = self.hash_chain_global.as_ref();
+        let step = hash_chain_global
+            .and_then(|v| v.frequency_ramp_step)
+            .unwrap_or(DEFAULT_FREQUENCY_RAMP_STEP_MHZ);
+        let delay = hash_chain_global
+            .and_then(|v| v.frequency_ramp_delay)
+            .unwrap_or(DEFAULT_FREQUENCY_RAMP_DELAY_MS);
+        // zero step disables the ramp
+        if step == 0.0 {
+            return None;
+        }
+        Some(ramp::Config {
+            step: (step * 1_000_000.0) as usize,
+            delay: Duration::from_millis(delay),
+        })
+    }
+
+    /// Returns `None` when isolation of faulty chips is disabled
+    pub fn resolve_chip_isolation(&self) -> Option<isolation::Config> {
+        let hash_chain_global = self.hash_chain_global.as_ref()?;
+        if !hash_chain_global.chip_isolation.unwrap_or(false) {
+            return None;
+        }
+        let mut config = isolation::Config::default();
+        if let Some(error_rate) = hash_chain_global.chip_isolation_error_rate {
+            config.error_rate = error_rate;
+        }
+        if let Some(check_count) = hash_chain_global.chip_isolation_check_count {
+            config.check_count = check_count;
+        }
+        Some(config)
+    }
+
+    pub fn resolve_recovery_config(&self) -> Option<recovery::Config> {
+        let hash_chain_global = self.hash_chain_global.as_ref();
+        if !hash_chain_global
+            .and_then(|v| v.auto_recovery)
+            .unwrap_or(DEFAULT_AUTO_RECOVERY_ENABLED)
+        {
+            return None;
+        }
+        let mut config = recovery::Config::default();
+        if let Some(timeout) = hash_chain_global.and_then(|v| v.auto_recovery_timeout) {
+            config.dead_chain_timeout = Duration::from_secs(timeout);
+        }
+        if let Some(attempts) = hash_chain_global.and_then(|v| v.auto_recovery_attempts) {
+            config.max_attempts = attempts;
+        }
+        Some(config)
+    }
+
+    pub fn resolve_asic_difficulty(&self) -> usize {
+        self.hash_chain_global
+            .as_ref()
+            .and_then(|v| v.asic_difficulty)
+            .unwrap_or(DEFAULT_ASIC_DIFFICULTY)
+    }
+
+    pub fn resolve_pause_mode(&self) -> pause::Mode {
+        self.hash_chain_global
+            .as_ref()
+            .and_then(|v| v.pause_mode)
+            .unwrap_or_default()
+    }
+
+    pub fn resolve_skip_unavailable_chains(&self) -> bool {
+        self.hash_chain_global
+            .as_ref()
+            .and_then(|v| v.skip_unavailable_chains)
+            .unwrap_or(DEFAULT_SKIP_UNAVAILABLE_CHAINS)
+    }
+
+    /// Returns `None` when the power target mode is disabled
+    pub fn resolve_power_target(&self) -> Option<f64> {
+        self.power_target
+            .as_ref()
+            .map(|power_target| power_target.power)
+    }
+
+    /// Returns hashrate cap in H/s or `None` when the hashrate cap mode is disabled
+    pub fn resolve_hashrate_cap(&self) -> Option<f64> {
+        self.hashrate_cap
+            .as_ref()
+            .map(|hashrate_cap| hashrate_cap.hashrate * 1e12)
+    }
+
+    pub fn resolve_presets(&self) -> Vec<preset::Preset> {
+        self.presets
+            .iter()
+            .flatten()
+            .map(|preset| preset::Preset {
+                name: preset.name.clone(),
+                frequency: FrequencySettings::from_frequency(
+                    (preset.frequency * 1_000_000.0) as usize,
+                ),
+                voltage: power::Voltage::from_volts(preset.voltage as f32)
+                    .expect("BUG: voltage should be checked by sanity check"),
+                target_temp: preset.target_temp.map(|target_temp| target_temp as f32),
+            })
+            .collect()
+    }
+
+    /// Returns `None` when all API commands are allowed without authentication
+    pub fn resolve_api_access_control(&self) -> Option<command::AccessControl> {
+        self.api.as_ref().and_then(|api| {
+            api.resolve()
+                .expect("BUG: API configuration should be checked by sanity check")
+        })
+    }
+
+    /// Returns `None` when thermal throttling is disabled
+    pub fn resolve_throttle_config(&self) -> Option<throttle::Config> {
+        let temp_control = self.temp_control.as_ref()?;
+        let warn_temp = temp_control.throttle_temp?;
+        let default_critical_temp = match temp_control.mode {
+            Some(TempControlMode::Immersion) => DEFAULT_IMMERSION_CRITICAL_TEMP_C,
+            _ => DEFAULT_CRITICAL_TEMP_C,
+        };
+        Some(throttle::Config {
+            warn_temp: warn_temp as f32,
+            critical_temp: temp_control.critical_temp.unwrap_or(default_critical_temp) as f32,
+            hysteresis: temp_control
+                .throttle_hysteresis
+                .unwrap_or(DEFAULT_THROTTLE_HYSTERESIS_C) as f32,
+        })
+    }
+
     pub fn resolve_monitor_config(&self) -> monitor::Config {
         // Get temperature control settings
         let mode = OptionDefault::new(
@@ -408,13 +1350,20 @@ impl Backend {
             self.temp_control.as_ref().and_then(|v| v.target_temp),
             DEFAULT_TARGET_TEMP_C,
         );
+        let (default_hot_temp, default_dangerous_temp) = match *mode {
+            TempControlMode::Immersion => (
+                DEFAULT_IMMERSION_HOT_TEMP_C,
+                DEFAULT_IMMERSION_DANGEROUS_TEMP_C,
+            ),
+            _ => (DEFAULT_HOT_TEMP_C, DEFAULT_DANGEROUS_TEMP_C),
+        };
         let hot_temp = OptionDefault::new(
             self.temp_control.as_ref().and_then(|v| v.hot_temp),
-            DEFAULT_HOT_TEMP_C,
+            default_hot_temp,
         );
         let dangerous_temp = OptionDefault::new(
             self.temp_control.as_ref().and_then(|v| v.dangerous_temp),
-            DEFAULT_DANGEROUS_TEMP_C,
+            default_dangerous_temp,
         );
 
         // Get fan control settings
@@ -426,13 +1375,17 @@ impl Backend {
             self.fan_control.as_ref().and_then(|v| v.min_fans),
             DEFAULT_MIN_FANS,
         );
+        let min_fan_speed = OptionDefault::new(
+            self.fan_control.as_ref().and_then(|v| v.min_speed),
+            DEFAULT_MIN_FAN_SPEED,
+        );
 
         let temp_config;
         let fan_config;
 
         // Configure temperature controller
         match *mode {
-            TempControlMode::Auto | TempControlMode::Manual => {
+            TempControlMode::Auto | TempControlMode::Manual | TempControlMode::Immersion => {
                 temp_config = Some(monitor::TempControlConfig {
                     dangerous_temp: *dangerous_temp as f32,
                     hot_temp: *hot_temp as f32,
@@ -462,6 +1415,7 @@ impl Backend {
                 fan_config = Some(monitor::FanControlConfig {
                     mode: monitor::FanControlMode::TargetTemperature(*target_temp as f32),
                     min_fans: *min_fans,
+                    min_speed: fan::Speed::new(*min_fan_speed),
                 });
                 // do sanity checks
                 if fan_speed.is_some() {
@@ -479,6 +1433,7 @@ impl Backend {
                     Some(monitor::FanControlConfig {
                         mode: monitor::FanControlMode::FixedSpeed(fan::Speed::new(*fan_speed)),
                         min_fans: *min_fans,
+                        min_speed: fan::Speed::STOPPED,
                     })
                 };
                 // do sanity checks
@@ -488,6 +1443,26 @@ impl Backend {
                         *fan_speed
                     );
                 }
+                if min_fan_speed.is_some() {
+                    warn!(
+                        "Unused fan 'min_speed' ({}) because 'auto' mode is not set",
+                        *min_fan_speed
+                    );
+                }
+            }
+            TempControlMode::Immersion => {
+                // fans are not controlled and their presence or failure is not checked
+                fan_config = None;
+                // do sanity checks
+                if self.fan_control.is_some() {
+                    warn!("Unused 'fan_control' because 'immersion' mode is set");
+                }
+                if target_temp.is_some() {
+                    warn!(
+                        "Unused 'target_temp' ({}) because 'immersion' mode is set",
+                        *target_temp
+                    );
+                }
             }
         };
 
@@ -542,6 +1517,173 @@ impl ConfigBody for Backend {
             }
         }
 
+        // Check that voltage controllers are not asked for voltage out of the supported range
+        let global_voltage = self
+            .hash_chain_global
+            .as_ref()
+            .and_then(|v| v.overridable.as_ref())
+            .and_then(|v| v.voltage);
+        let chain_voltages = self
+            .hash_chains
+            .iter()
+            .flat_map(|hash_chains| hash_chains.values())
+            .filter_map(|hash_chain| hash_chain.voltage);
+        for voltage in global_voltage.into_iter().chain(chain_voltages) {
+            if !(VOLTAGE_V_MIN..=VOLTAGE_V_MAX).contains(&voltage) {
+                Err(format!(
+                    "voltage {} V is out of range '{}..{}'",
+                    voltage, VOLTAGE_V_MIN, VOLTAGE_V_MAX
+                ))?;
+            }
+        }
+
+        let mut chip_bins = HashSet::new();
+        for profile in self.chip_bin_profiles.iter().flatten() {
+            if !chip_bins.insert(profile.bin) {
+                Err(format!("duplicate profile of chip bin {}", profile.bin))?;
+            }
+            if !(FREQUENCY_MHZ_MIN..=FREQUENCY_MHZ_MAX).contains(&profile.frequency) {
+                Err(format!(
+                    "frequency {} MHz of chip bin {} is out of range '{}..{}'",
+                    profile.frequency, profile.bin, FREQUENCY_MHZ_MIN, FREQUENCY_MHZ_MAX
+                ))?;
+            }
+            if !(VOLTAGE_V_MIN..=VOLTAGE_V_MAX).contains(&profile.voltage) {
+                Err(format!(
+                    "voltage {} V of chip bin {} is out of range '{}..{}'",
+                    profile.voltage, profile.bin, VOLTAGE_V_MIN, VOLTAGE_V_MAX
+                ))?;
+            }
+        }
+
+        let mut preset_names = HashSet::new();
+        for preset in self.presets.iter().flatten() {
+            if preset.name.is_empty() || preset.name.contains(',') {
+                Err(format!("invalid preset name '{}'", preset.name))?;
+            }
+            if !preset_names.insert(preset.name.as_str()) {
+                Err(format!("duplicate preset '{}'", preset.name))?;
+            }
+            if !(FREQUENCY_MHZ_MIN..=FREQUENCY_MHZ_MAX).contains(&preset.frequency) {
+                Err(format!(
+                    "frequency {} MHz of preset '{}' is out of range '{}..{}'",
+                    preset.frequency, preset.name, FREQUENCY_MHZ_MIN, FREQUENCY_MHZ_MAX
+                ))?;
+            }
+            if !(VOLTAGE_V_MIN..=VOLTAGE_V_MAX).contains(&preset.voltage) {
+                Err(format!(
+                    "voltage {} V of preset '{}' is out of range '{}..{}'",
+                    preset.voltage, preset.name, VOLTAGE_V_MIN, VOLTAGE_V_MAX
+                ))?;
+            }
+            if let Some(target_temp) = preset.target_temp {
+                if !(TEMPERATURE_C_MIN..=TEMPERATURE_C_MAX).contains(&target_temp) {
+                    Err(format!(
+                        "target temperature {} C of preset '{}' is out of range '{}..{}'",
+                        target_temp, preset.name, TEMPERATURE_C_MIN, TEMPERATURE_C_MAX
+                    ))?;
+                }
+            }
+        }
+
+        if let Some(step) = self
+            .hash_chain_global
+            .as_ref()
+            .and_then(|v| v.frequency_ramp_step)
+        {
+            if !step.is_finite() || step < 0.0 {
+                Err(format!("frequency ramp step {} MHz is not valid", step))?;
+            }
+            if step != 0.0 && ((step * 1_000_000.0) as usize) < ramp::MIN_STEP {
+                Err(format!(
+                    "frequency ramp step {} MHz must be either 0 or at least {} MHz",
+                    step,
+                    ramp::MIN_STEP / 1_000_000
+                ))?;
+            }
+        }
+
+        if let Some(error_rate) = self
+            .hash_chain_global
+            .as_ref()
+            .and_then(|v| v.chip_isolation_error_rate)
+        {
+            if !(error_rate > 0.0 && error_rate <= 100.0) {
+                Err(format!(
+                    "chip isolation error rate {}% is out of range (0, 100]",
+                    error_rate
+                ))?;
+            }
+        }
+        if let Some(0) = self
+            .hash_chain_global
+            .as_ref()
+            .and_then(|v| v.chip_isolation_check_count)
+        {
+            Err("chip isolation check count must be at least 1")?;
+        }
+        if let Some(timeout) = self
+            .hash_chain_global
+            .as_ref()
+            .and_then(|v| v.auto_recovery_timeout)
+        {
+            if timeout < recovery::CHECK_INTERVAL.as_secs() {
+                Err(format!(
+                    "auto recovery timeout {} s is shorter than the check interval {} s",
+                    timeout,
+                    recovery::CHECK_INTERVAL.as_secs()
+                ))?;
+            }
+        }
+        if let Some(0) = self
+            .hash_chain_global
+            .as_ref()
+            .and_then(|v| v.auto_recovery_attempts)
+        {
+            Err("auto recovery attempts must be at least 1")?;
+        }
+        for chip_idx in self
+            .hash_chain_global
+            .as_ref()
+            .and_then(|v| v.chip_temp_sensors.as_ref())
+            .into_iter()
+            .flatten()
+        {
+            if *chip_idx >= crate::EXPECTED_CHIPS_ON_CHAIN {
+                Err(format!(
+                    "chip {} with temperature sensor is out of range 0..{}",
+                    chip_idx,
+                    crate::EXPECTED_CHIPS_ON_CHAIN
+                ))?;
+            }
+        }
+
+        if let Some(throttle_config) = self.resolve_throttle_config() {
+            if throttle_config.warn_temp >= throttle_config.critical_temp {
+                Err(format!(
+                    "throttle temperature {} C must be lower than critical temperature {} C",
+                    throttle_config.warn_temp, throttle_config.critical_temp
+                ))?;
+            }
+            if throttle_config.hysteresis.is_nan() || throttle_config.hysteresis < 0.0 {
+                Err("throttle hysteresis must not be negative")?;
+            }
+        }
+
+        if let Some(baud_rate) = self.hash_chain_global.as_ref().and_then(|v| v.baud_rate) {
+            crate::check_chip_baud_rate(baud_rate).map_err(|e| e.to_string())?;
+        }
+
+        if let Some(asic_difficulty) = self
+            .hash_chain_global
+            .as_ref()
+            .and_then(|v| v.asic_difficulty)
+        {
+            let difficulty = u32::try_from(asic_difficulty)
+                .map_err(|_| format!("ASIC difficulty {} is too big", asic_difficulty))?;
+            bm1387::TicketMaskReg::new(difficulty).map_err(|e| e.to_string())?;
+        }
+
         // Analyze group configuration, make sure the groups are unique, and build descriptor
         // topology out of the configuration data
         // Don't worry if is this section missing, maybe there are some pools on command line
@@ -555,7 +1697,12 @@ impl ConfigBody for Backend {
                     for pool in pools {
                         let _ = ClientDescriptor::create(
                             pool.url.as_str(),
-                            &ClientUserInfo::new(pool.user.as_str(), pool.password.as_deref()),
+                            &ClientUserInfo::new(
+                                pool.user.as_str(),
+                                pool.password
+                                    .as_ref()
+                                    .map(|password| password.expose().as_str()),
+                            ),
                             pool.enabled.unwrap_or(DEFAULT_POOL_ENABLED),
                         )
                         .map_err(|e| {
@@ -566,6 +1713,118 @@ impl ConfigBody for Backend {
             }
         }
 
+        // Check that all webhooks can be notified
+        if let Some(webhooks) = self.alerts.as_ref().and_then(|v| v.webhooks.as_ref()) {
+            let mut alert_config = alert::Config::default();
+            for url in webhooks {
+                alert_config.add_webhook(url).map_err(|e| e.to_string())?;
+            }
+        }
+
+        if let Some(mqtt) = self.mqtt.as_ref() {
+            mqtt.resolve()?;
+        }
+
+        if let Some(influx) = self.influx.as_ref() {
+            influx.resolve()?;
+        }
+
+        if let Some(autotuning) = self.autotuning.as_ref() {
+            autotuning.resolve()?;
+        }
+
+        if let Some(frequency_scaling) = self.frequency_scaling.as_ref() {
+            if let Some(governor_config) = frequency_scaling.resolve()? {
+                // both would fight over the frequency
+                if self.resolve_tuner_config().is_some() {
+                    Err("frequency scaling and autotuning cannot be enabled at the same time")?;
+                }
+                if self.presets.iter().flatten().next().is_some() {
+                    Err("frequency scaling and presets cannot be enabled at the same time")?;
+                }
+                if let Some(throttle_config) = self.resolve_throttle_config() {
+                    if governor_config.target_temp >= throttle_config.warn_temp {
+                        Err(format!(
+                            "frequency scaling target temperature {} C must be lower than \
+                             throttle temperature {} C",
+                            governor_config.target_temp, throttle_config.warn_temp
+                        ))?;
+                    }
+                }
+            }
+        }
+
+        if let Some(power) = self.resolve_power_target() {
+            if power < POWER_TARGET_W_MIN {
+                Err(format!(
+                    "power target must be at least {} W",
+                    POWER_TARGET_W_MIN
+                ))?;
+            }
+            // all of them would fight over the frequency
+            if self.resolve_tuner_config().is_some() {
+                Err("power target and autotuning cannot be enabled at the same time")?;
+            }
+            if self.resolve_governor_config().is_some() {
+                Err("power target and frequency scaling cannot be enabled at the same time")?;
+            }
+            if self.presets.iter().flatten().next().is_some() {
+                Err("power target and presets cannot be enabled at the same time")?;
+            }
+        }
+
+        if let Some(hashrate_cap) = self.hashrate_cap.as_ref() {
+            if hashrate_cap.hashrate < HASHRATE_CAP_TH_MIN {
+                Err(format!(
+                    "hashrate cap must be at least {} TH/s",
+                    HASHRATE_CAP_TH_MIN
+                ))?;
+            }
+            // all of them would fight over the frequency
+            if self.resolve_power_target().is_some() {
+                Err("hashrate cap and power target cannot be enabled at the same time")?;
+            }
+            if self.resolve_tuner_config().is_some() {
+                Err("hashrate cap and autotuning cannot be enabled at the same time")?;
+            }
+            if self.resolve_governor_config().is_some() {
+                Err("hashrate cap and frequency scaling cannot be enabled at the same time")?;
+            }
+            if self.presets.iter().flatten().next().is_some() {
+                Err("hashrate cap and presets cannot be enabled at the same time")?;
+            }
+        }
+
+        if let Some(psu) = self.psu.as_ref() {
+            psu.resolve()?;
+        }
+
+        if let Some(led) = self.led.as_ref() {
+            led.resolve()?;
+        }
+
+        if let Some(api) = self.api.as_ref() {
+            api.resolve()?;
+        }
+
+        if let Some(min_speed) = self.fan_control.as_ref().and_then(|v| v.min_speed) {
+            if min_speed > FAN_SPEED_MAX {
+                Err(format!(
+                    "minimal fan speed must be at most {} %",
+                    FAN_SPEED_MAX
+                ))?;
+            }
+        }
+
+        if let Some(max_work_age) = self.hash_chain_global.as_ref().and_then(|v| v.max_work_age) {
+            if max_work_age < MAX_WORK_AGE_S_MIN {
+                Err(format!(
+                    "maximal work age must be at least {} s",
+                    MAX_WORK_AGE_S_MIN
+                ))?;
+            }
+        }
+
         Ok(())
     }
 
@@ -593,6 +1852,15 @@ impl hal::BackendConfig for Backend {
         }
     }
 
+    fn max_work_age(&self) -> Duration {
+        Duration::from_secs(
+            self.hash_chain_global
+                .as_ref()
+                .and_then(|v| v.max_work_age)
+                .unwrap_or(DEFAULT_MAX_WORK_AGE_S),
+        )
+    }
+
     fn set_client_manager(&mut self, client_manager: client::Manager) {
         self.client_manager.replace(client_manager);
     }
@@ -600,4 +1868,8 @@ impl hal::BackendConfig for Backend {
     fn info(&self) -> Option<hal::BackendInfo> {
         Some(self.info.clone())
     }
+
+    fn persistent_stats_path(&self) -> Option<PathBuf> {
+        Some(PathBuf::from(DEFAULT_PERSISTENT_STATS_PATH))
+    }
 }
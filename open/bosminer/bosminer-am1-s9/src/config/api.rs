@@ -37,7 +37,7 @@ use std::time::SystemTime;
 pub struct UnixTime;
 
 impl UnixTime {
-    fn now() -> u32 {
+    pub(super) fn now() -> u32 {
         SystemTime::now()
             .duration_since(SystemTime::UNIX_EPOCH)
             .map(|duration| duration.as_secs() as u32)
@@ -45,7 +45,7 @@ impl UnixTime {
     }
 }
 
-fn generator_string<B: ConfigBody>() -> String {
+pub(super) fn generator_string<B: ConfigBody>() -> String {
     format!("{} {}", B::variant(), bosminer::version::STRING.clone())
 }
 
@@ -112,13 +112,13 @@ struct SaveResponse {
     pub data: Option<SaveSuccess>,
 }
 
-struct FileGuard<'a> {
+pub(super) struct FileGuard<'a> {
     path: Option<&'a Path>,
     file: Option<fs::File>,
 }
 
 impl<'a> FileGuard<'a> {
-    fn create(path: &'a Path) -> io::Result<Self> {
+    pub(super) fn create(path: &'a Path) -> io::Result<Self> {
         Ok(Self {
             path: Some(path),
             file: Some(
@@ -130,7 +130,7 @@ impl<'a> FileGuard<'a> {
         })
     }
 
-    fn persist<P: AsRef<Path>>(mut self, path: P) -> io::Result<()> {
+    pub(super) fn persist<P: AsRef<Path>>(mut self, path: P) -> io::Result<()> {
         // Close the file before moving
         let _ = self
             .file
@@ -277,4 +277,37 @@ impl<'a> Handler<'a> {
 
         self.send_response(response);
     }
+
+    /// Imports the stock firmware configuration from `stock_path` as if it was saved by 'save'
+    /// request
+    pub fn handle_import_stock(self, stock_path: &str) {
+        let config_path = Path::new(self.config_path);
+        let response = match stock::import_file(Path::new(stock_path), config_path) {
+            Ok(config) => SaveResponse {
+                status: Status::new::<_, Backend>(StatusCode::Success, None),
+                data: Some(SaveSuccess {
+                    path: config_path
+                        .canonicalize()
+                        .expect("TODO: path.canonicalize")
+                        .into_os_string()
+                        .into_string()
+                        .expect("TODO: into_os_string"),
+                    format: config.format,
+                }),
+            },
+            Err(e) => {
+                let code = match e {
+                    stock::ImportError::MissingFile(_) => StatusCode::MissingFile,
+                    stock::ImportError::InvalidFormat(_) => StatusCode::InvalidFormat,
+                    stock::ImportError::SystemError(_) => StatusCode::SystemError,
+                };
+                SaveResponse {
+                    status: Status::new::<_, Backend>(code, e.to_string()),
+                    data: None,
+                }
+            }
+        };
+
+        self.send_response(response);
+    }
 }
@@ -24,7 +24,7 @@
 
 use super::*;
 
-use bosminer_config::CLIENT_URL_JAVA_SCRIPT_REGEX;
+use bosminer_config::{GroupDescriptor, CLIENT_URL_JAVA_SCRIPT_REGEX};
 
 const DESCRIPTION_CAUTION_OVERCLOCKING: &'static str =
     "Caution: Overclocking may damage your device. Proceed at your own risk!";
@@ -33,6 +33,9 @@ const DESCRIPTION_CAUTION_CHANGING_DEFAULT: &'static str =
      shutdown of the system or even irreversible hardware damage. Proceed at your own risk!";
 const DESCRIPTION_NUMBER_OF_FANS: &'static str =
     "Number of fans required for system to run. For immersion cooling, use the value '0'.";
+const DESCRIPTION_FAIL_BACK_DELAY: &'static str =
+    "Number of seconds a recovered pool with higher priority has to be running before mining is \
+     switched back to it.";
 
 use serde_json::{self, json};
 
@@ -124,6 +127,17 @@ pub fn for_backend() -> serde_json::Value {
                                 "span": 3
                             }
                         ],
+                        [
+                            "fail_back_delay",
+                            {
+                                "type": "number",
+                                "label": "Fail-Back Delay",
+                                "description": DESCRIPTION_FAIL_BACK_DELAY,
+                                "min": 0,
+                                "default": GroupDescriptor::DEFAULT_FAIL_BACK_DELAY,
+                                "span": 6
+                            }
+                        ],
                         [
                             "pool",
                             {
@@ -195,6 +209,16 @@ pub fn for_backend() -> serde_json::Value {
                             "default": DEFAULT_ASIC_BOOST
                         }
                     ],
+                    [
+                        "max_work_age",
+                        {
+                            "type": "number",
+                            "label": "Maximal Work Age",
+                            "unit": "s",
+                            "min": MAX_WORK_AGE_S_MIN,
+                            "default": DEFAULT_MAX_WORK_AGE_S
+                        }
+                    ],
                     [
                         "frequency",
                         {
@@ -298,6 +322,11 @@ pub fn for_backend() -> serde_json::Value {
                                     "key": TempControlMode::Disabled.to_string(),
                                     "label": "Disabled",
                                     "alert": DESCRIPTION_CAUTION_CHANGING_DEFAULT
+                                },
+                                {
+                                    "key": TempControlMode::Immersion.to_string(),
+                                    "label": "Immersion",
+                                    "alert": DESCRIPTION_CAUTION_CHANGING_DEFAULT
                                 }
                             ],
                             "default": TempControlMode::Auto.to_string()
@@ -370,6 +399,19 @@ pub fn for_backend() -> serde_json::Value {
                             "disabled": ["$eq", ["$get", "temp_control", "mode"], "auto"]
                         }
                     ],
+                    [
+                        "min_speed",
+                        {
+                            "type": "number",
+                            "label": "Minimum Speed",
+                            "unit": "%",
+                            "min": FAN_SPEED_MIN,
+                            "max": FAN_SPEED_MAX,
+                            "step": 1,
+                            "default": DEFAULT_MIN_FAN_SPEED,
+                            "disabled": ["$neq", ["$get", "temp_control", "mode"], "auto"]
+                        }
+                    ],
                     [
                         "min_fans",
                         {
@@ -0,0 +1,353 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Import of the Antminer stock firmware configuration
+//!
+//! The stock firmware keeps its configuration in JSON file `bmminer.conf` (`cgminer.conf` on older
+//! releases) on the config partition. Pools with worker credentials, chip frequency and fan
+//! settings are converted to the bosminer configuration so that the miner keeps mining to the same
+//! pools after Braiins OS has been flashed. The stock `bitmain-voltage` is not imported because it
+//! is not used by the stock S9 firmware, the default voltage is used instead.
+
+use super::api::{generator_string, FileGuard, UnixTime};
+use super::*;
+
+use std::io::Write;
+use std::path::Path;
+
+/// Locations of the stock firmware configuration in order of preference
+pub const STOCK_CONFIG_PATHS: &[&str] = &["/config/bmminer.conf", "/config/cgminer.conf"];
+
+/// Scheme assumed for stock pool URLs without any
+const DEFAULT_POOL_SCHEME: &str = "stratum+tcp";
+
+#[derive(Debug)]
+pub enum ImportError {
+    MissingFile(String),
+    InvalidFormat(String),
+    SystemError(String),
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingFile(message)
+            | Self::InvalidFormat(message)
+            | Self::SystemError(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+#[derive(Deserialize, Debug)]
+struct StockPool {
+    #[serde(default)]
+    url: String,
+    #[serde(default)]
+    user: String,
+    #[serde(default)]
+    pass: String,
+}
+
+/// Subset of the stock configuration which is imported, numeric values are stored either as
+/// strings or numbers depending on the firmware release
+#[derive(Deserialize, Debug)]
+struct StockConfig {
+    #[serde(default)]
+    pools: Vec<StockPool>,
+    #[serde(rename = "bitmain-freq")]
+    frequency: Option<serde_json::Value>,
+    #[serde(rename = "bitmain-fan-ctrl")]
+    #[serde(default)]
+    fan_ctrl: bool,
+    #[serde(rename = "bitmain-fan-pwm")]
+    fan_pwm: Option<serde_json::Value>,
+}
+
+fn parse_number(value: &serde_json::Value) -> Option<f64> {
+    match value {
+        serde_json::Value::Number(number) => number.as_f64(),
+        serde_json::Value::String(string) => string.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// Frequency is either plain number of MHz or `<timeout>:<frequency>:<register>` triplet used
+/// by older stock firmware
+fn parse_frequency(value: &serde_json::Value) -> Option<f64> {
+    match value {
+        serde_json::Value::String(string) if string.contains(':') => string
+            .split(':')
+            .nth(1)
+            .and_then(|frequency| frequency.trim().parse().ok()),
+        _ => parse_number(value),
+    }
+}
+
+impl StockConfig {
+    fn pools(&self) -> Vec<bosminer_config::PoolConfig> {
+        self.pools
+            .iter()
+            // Stock firmware always stores three pool slots, the unused ones are empty
+            .filter(|pool| !pool.url.trim().is_empty())
+            .filter_map(|pool| {
+                let url = pool.url.trim();
+                let url = if url.contains("://") {
+                    url.to_string()
+                } else {
+                    format!("{}://{}", DEFAULT_POOL_SCHEME, url)
+                };
+                let password = Some(pool.pass.as_str()).filter(|password| !password.is_empty());
+                match ClientDescriptor::create(
+                    url.as_str(),
+                    &ClientUserInfo::new(pool.user.as_str(), password),
+                    DEFAULT_POOL_ENABLED,
+                ) {
+                    Ok(_) => Some(bosminer_config::PoolConfig {
+                        enabled: None,
+                        url,
+                        user: pool.user.clone(),
+                        password: password.map(|password| Secret::new(password.to_string())),
+                    }),
+                    Err(e) => {
+                        warn!("Skipping stock pool '{}@{}': {}", url, pool.user, e);
+                        None
+                    }
+                }
+            })
+            .collect()
+    }
+
+    fn frequency(&self) -> Option<f64> {
+        let frequency = parse_frequency(self.frequency.as_ref()?);
+        match frequency {
+            Some(frequency) if (FREQUENCY_MHZ_MIN..=FREQUENCY_MHZ_MAX).contains(&frequency) => {
+                Some(frequency)
+            }
+            _ => {
+                warn!("Skipping stock frequency {:?}", self.frequency);
+                None
+            }
+        }
+    }
+
+    /// Fixed fan speed is only used when the stock fan control is enabled
+    fn fan_speed(&self) -> Option<usize> {
+        if !self.fan_ctrl {
+            return None;
+        }
+        let speed = parse_number(self.fan_pwm.as_ref()?);
+        match speed {
+            Some(speed) if (FAN_SPEED_MIN as f64..=FAN_SPEED_MAX as f64).contains(&speed) => {
+                Some(speed as usize)
+            }
+            _ => {
+                warn!("Skipping stock fan speed {:?}", self.fan_pwm);
+                None
+            }
+        }
+    }
+
+    fn into_backend(self) -> Backend {
+        let pools = self.pools();
+        let frequency = self.frequency();
+        let fan_speed = self.fan_speed();
+
+        let mut backend = Backend::default();
+        if !pools.is_empty() {
+            backend.groups = Some(vec![bosminer_config::GroupConfig {
+                descriptor: bosminer_config::GroupDescriptor::new(
+                    bosminer_config::GroupDescriptor::DEFAULT_NAME.to_string(),
+                    false,
+                    None,
+                ),
+                pools: Some(pools),
+            }]);
+        }
+        if let Some(frequency) = frequency {
+            backend.hash_chain_global = Some(HashChainGlobal {
+                overridable: Some(HashChain {
+                    frequency: Some(frequency),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            });
+        }
+        if let Some(speed) = fan_speed {
+            backend.temp_control = Some(TempControl {
+                mode: Some(TempControlMode::Manual),
+                ..Default::default()
+            });
+            backend.fan_control = Some(FanControl {
+                speed: Some(speed),
+                ..Default::default()
+            });
+        }
+        backend
+    }
+}
+
+/// Converts content of the stock configuration file to the bosminer configuration
+pub fn convert(content: &str) -> Result<FormatWrapper<Backend>, ImportError> {
+    let stock: StockConfig = serde_json::from_str(content)
+        .map_err(|e| ImportError::InvalidFormat(format!("invalid stock configuration: {}", e)))?;
+    let mut config = FormatWrapper {
+        format: Format {
+            version: Backend::version(),
+            model: Backend::model(),
+            generator: generator_string::<Backend>().into(),
+            timestamp: UnixTime::now().into(),
+        },
+        body: stock.into_backend(),
+    };
+    config
+        .sanity_check()
+        .map_err(|e| ImportError::InvalidFormat(e.to_string()))?;
+    Ok(config)
+}
+
+/// Returns the first existing stock configuration file
+pub fn find_stock_config() -> Option<&'static str> {
+    STOCK_CONFIG_PATHS
+        .iter()
+        .copied()
+        .find(|path| Path::new(path).is_file())
+}
+
+/// Converts stock configuration file `stock_path` and saves the result to `config_path`
+pub fn import_file(
+    stock_path: &Path,
+    config_path: &Path,
+) -> Result<FormatWrapper<Backend>, ImportError> {
+    let content = fs::read_to_string(stock_path).map_err(|e| {
+        ImportError::MissingFile(format!("cannot read '{}': {}", stock_path.display(), e))
+    })?;
+    let config = convert(content.as_str())?;
+
+    let system_error = |e: std::io::Error| {
+        ImportError::SystemError(format!("cannot write '{}': {}", config_path.display(), e))
+    };
+    let config_tmp_path = config_path.with_extension(api::Handler::CONFIG_TMP_EXTENSION);
+    let mut file = FileGuard::create(&config_tmp_path).map_err(system_error)?;
+    let content = toml::to_string_pretty(&config)
+        .map_err(|e| ImportError::SystemError(format!("cannot serialize configuration: {}", e)))?;
+    file.write_all(content.as_bytes()).map_err(system_error)?;
+    file.persist(config_path).map_err(system_error)?;
+
+    Ok(config)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_convert() {
+        let config = convert(
+            r#"{
+                "pools" : [
+                    {
+                        "url" : "stratum+tcp://stratum.slushpool.com:3333",
+                        "user" : "braiins.worker1",
+                        "pass" : "x"
+                    },
+                    {
+                        "url" : "eu.stratum.slushpool.com:3333",
+                        "user" : "braiins.worker1",
+                        "pass" : ""
+                    },
+                    {
+                        "url" : "",
+                        "user" : "",
+                        "pass" : ""
+                    }
+                ],
+                "api-listen" : true,
+                "bitmain-use-vil" : true,
+                "bitmain-freq" : "550",
+                "bitmain-voltage" : "0706",
+                "bitmain-fan-ctrl" : true,
+                "bitmain-fan-pwm" : "80"
+            }"#,
+        )
+        .expect("BUG: cannot convert stock configuration");
+        assert_eq!(config.format.model, FORMAT_MODEL);
+
+        let backend = &config.body;
+        let groups = backend.groups.as_ref().expect("BUG: missing groups");
+        assert_eq!(groups.len(), 1);
+        let pools = groups[0].pools.as_ref().expect("BUG: missing pools");
+        assert_eq!(pools.len(), 2);
+        assert_eq!(pools[0].url, "stratum+tcp://stratum.slushpool.com:3333");
+        assert_eq!(pools[0].user, "braiins.worker1");
+        assert_eq!(
+            pools[0]
+                .password
+                .as_ref()
+                .map(|password| password.expose().as_str()),
+            Some("x")
+        );
+        assert_eq!(pools[1].url, "stratum+tcp://eu.stratum.slushpool.com:3333");
+        assert!(pools[1].password.is_none());
+
+        assert_eq!(
+            backend
+                .hash_chain_global
+                .as_ref()
+                .and_then(|v| v.overridable.as_ref())
+                .and_then(|v| v.frequency),
+            Some(550.0)
+        );
+        assert_eq!(backend.fan_control.as_ref().and_then(|v| v.speed), Some(80));
+
+        // the result is a valid configuration file
+        let content = toml::to_string_pretty(&config).expect("BUG: cannot serialize");
+        let _: FormatWrapper<Backend> = toml::from_str(&content).expect("BUG: cannot parse");
+    }
+
+    #[test]
+    fn test_convert_defaults() {
+        // Fan control is left to bosminer unless enabled in the stock firmware and values out of
+        // range are skipped
+        let config = convert(
+            r#"{
+                "pools" : [],
+                "bitmain-freq" : "18:1500:1106",
+                "bitmain-fan-ctrl" : false,
+                "bitmain-fan-pwm" : "80"
+            }"#,
+        )
+        .expect("BUG: cannot convert stock configuration");
+        assert!(config.body.groups.is_none());
+        assert!(config.body.hash_chain_global.is_none());
+        assert!(config.body.temp_control.is_none());
+        assert!(config.body.fan_control.is_none());
+
+        assert_eq!(
+            parse_frequency(&serde_json::json!("18:218.75:1106")),
+            Some(218.75)
+        );
+        assert_eq!(parse_frequency(&serde_json::json!(650)), Some(650.0));
+        assert!(convert("not json").is_err());
+    }
+}
@@ -23,11 +23,31 @@
 //! Nonce and error counters for estimating hashrate
 //!
 //! Note: `valid` counter is in shares, `errors` are in error event instances (not in shares)
+//!
+//! Duplicate nonces are accounted as errors and also separately in `duplicates` counters.
+//!
+//! Responses dropped by FPGA due to CRC mismatch cannot be attributed to any chip so they are
+//! only counted for the whole chain. The same holds for responses which passed the CRC check
+//! but are malformed (RX errors).
+//!
+//! Nonces outside of the subranges of enumerated chips and their existing cores are counted as
+//! subrange errors of the whole chain. They are not accounted to any chip.
 
 use crate::bm1387;
 
 use std::time::{Duration, Instant};
 
+/// Percentage of errors out of all nonces including the invalid ones. Returns `None` when there
+/// is no nonce at all.
+fn error_rate(valid: usize, errors: usize, asic_difficulty: usize) -> Option<f64> {
+    let nonces = valid / asic_difficulty.max(1) + errors;
+    if nonces > 0 {
+        Some(errors as f64 / nonces as f64 * 100.0)
+    } else {
+        None
+    }
+}
+
 /// Per-core counters for valid nonces/errors
 #[derive(Clone, Copy)]
 pub struct Core {
@@ -54,6 +74,8 @@ pub struct Chip {
     pub core: [Core; super::CORE_ADR_SPACE_SIZE],
     pub valid: usize,
     pub errors: usize,
+    /// Suppressed solutions with nonce already received for the same work
+    pub duplicates: usize,
 }
 
 impl Chip {
@@ -61,6 +83,7 @@ impl Chip {
         Self {
             valid: 0,
             errors: 0,
+            duplicates: 0,
             core: [Core::new(); super::CORE_ADR_SPACE_SIZE],
         }
     }
@@ -68,10 +91,36 @@ impl Chip {
     pub fn reset(&mut self) {
         self.valid = 0;
         self.errors = 0;
+        self.duplicates = 0;
         for core in self.core.iter_mut() {
             core.reset();
         }
     }
+
+    pub fn error_rate(&self, asic_difficulty: usize) -> Option<f64> {
+        error_rate(self.valid, self.errors, asic_difficulty)
+    }
+
+    /// Counters accounted since `previous` snapshot. When the counters have been reset in the
+    /// meantime, all current values are taken.
+    fn delta(&self, previous: &Self) -> Self {
+        if self.valid < previous.valid || self.errors < previous.errors {
+            return *self;
+        }
+        let mut delta = Self::new();
+        delta.valid = self.valid - previous.valid;
+        delta.errors = self.errors - previous.errors;
+        delta.duplicates = self.duplicates.saturating_sub(previous.duplicates);
+        for (core, (current, previous)) in delta
+            .core
+            .iter_mut()
+            .zip(self.core.iter().zip(previous.core.iter()))
+        {
+            core.valid = current.valid.saturating_sub(previous.valid);
+            core.errors = current.errors.saturating_sub(previous.errors);
+        }
+        delta
+    }
 }
 
 #[derive(Clone)]
@@ -79,6 +128,14 @@ pub struct HashChain {
     pub chip: Vec<Chip>,
     pub valid: usize,
     pub errors: usize,
+    /// Suppressed duplicate solutions (they are included in `errors` as well)
+    pub duplicates: usize,
+    /// Responses dropped due to CRC mismatch
+    pub crc_errors: usize,
+    /// Malformed or unsolicited responses dropped by the driver
+    pub rx_errors: usize,
+    /// Nonces which do not belong to nonce subrange of any enumerated chip
+    pub subrange_errors: usize,
     pub started: Instant,
     pub stopped: Option<Instant>,
     pub asic_difficulty: usize,
@@ -89,6 +146,10 @@ impl HashChain {
         Self {
             valid: 0,
             errors: 0,
+            duplicates: 0,
+            crc_errors: 0,
+            rx_errors: 0,
+            subrange_errors: 0,
             started: Instant::now(),
             stopped: None,
             chip: vec![Chip::new(); chip_count],
@@ -99,6 +160,10 @@ impl HashChain {
     pub fn reset(&mut self) {
         self.valid = 0;
         self.errors = 0;
+        self.duplicates = 0;
+        self.crc_errors = 0;
+        self.rx_errors = 0;
+        self.subrange_errors = 0;
         for chip in self.chip.iter_mut() {
             chip.reset();
         }
@@ -120,10 +185,17 @@ impl HashChain {
             .duration_since(self.started)
     }
 
+    /// Accounts nonce from outside of subranges of enumerated chips. Returns `false` in such case.
+    fn check_subrange(&mut self, addr: bm1387::CoreAddress) -> bool {
+        if !addr.is_within(self.chip.len()) {
+            self.subrange_errors += 1;
+            return false;
+        }
+        true
+    }
+
     pub fn add_valid(&mut self, addr: bm1387::CoreAddress) {
-        if addr.chip >= self.chip.len() {
-            // nonce from non-existent chip
-            // TODO: what to do?
+        if !self.check_subrange(addr) {
             return;
         }
         self.valid += self.asic_difficulty;
@@ -132,9 +204,7 @@ impl HashChain {
     }
 
     pub fn add_error(&mut self, addr: bm1387::CoreAddress) {
-        if addr.chip >= self.chip.len() {
-            // nonce from non-existent chip
-            // TODO: what to do?
+        if !self.check_subrange(addr) {
             return;
         }
         self.errors += 1;
@@ -142,6 +212,49 @@ impl HashChain {
         self.chip[addr.chip].core[addr.core].errors += 1;
     }
 
+    /// Account a suppressed duplicate solution, it is an error of the chip too
+    pub fn add_duplicate(&mut self, addr: bm1387::CoreAddress) {
+        if !self.check_subrange(addr) {
+            return;
+        }
+        self.add_error(addr);
+        self.duplicates += 1;
+        self.chip[addr.chip].duplicates += 1;
+    }
+
+    pub fn add_crc_errors(&mut self, count: usize) {
+        self.crc_errors += count;
+    }
+
+    pub fn add_rx_errors(&mut self, count: usize) {
+        self.rx_errors += count;
+    }
+
+    pub fn error_rate(&self) -> Option<f64> {
+        error_rate(self.valid, self.errors, self.asic_difficulty)
+    }
+
+    /// Error rate of the chain since `previous` snapshot of the same chain
+    pub fn error_rate_since(&self, previous: &Self) -> Option<f64> {
+        error_rate(
+            self.valid.saturating_sub(previous.valid),
+            self.errors.saturating_sub(previous.errors),
+            self.asic_difficulty,
+        )
+    }
+
+    /// Per-chip counters accounted since `previous` snapshot of the same chain
+    pub fn chip_delta(&self, previous: &Self) -> Vec<Chip> {
+        self.chip
+            .iter()
+            .enumerate()
+            .map(|(i, chip)| match previous.chip.get(i) {
+                Some(previous_chip) => chip.delta(previous_chip),
+                None => *chip,
+            })
+            .collect()
+    }
+
     pub fn set_chip_count(&mut self, chip_count: usize) {
         self.chip.resize(chip_count, Chip::new());
     }
@@ -150,3 +263,77 @@ impl HashChain {
         self.chip.len()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_error_rate() {
+        let mut counter = HashChain::new(2, 4);
+        assert_eq!(counter.error_rate(), None);
+
+        let addr = |chip| bm1387::CoreAddress { chip, core: 0 };
+        for _ in 0..3 {
+            counter.add_valid(addr(0));
+        }
+        counter.add_error(addr(0));
+        counter.add_error(addr(1));
+        assert_eq!(counter.error_rate(), Some(40.0));
+        assert_eq!(counter.chip[0].error_rate(4), Some(25.0));
+        assert_eq!(counter.chip[1].error_rate(4), Some(100.0));
+
+        let previous = counter.snapshot();
+        counter.add_valid(addr(1));
+        let delta = counter.chip_delta(&previous);
+        assert_eq!(delta[0].error_rate(4), None);
+        assert_eq!(delta[1].valid, 4);
+        assert_eq!(delta[1].error_rate(4), Some(0.0));
+        assert_eq!(counter.error_rate_since(&previous), Some(0.0));
+        assert_eq!(counter.error_rate_since(&counter.snapshot()), None);
+
+        // counters reset in the meantime
+        counter.reset();
+        counter.add_error(addr(0));
+        let delta = counter.chip_delta(&previous);
+        assert_eq!(delta[0].errors, 1);
+        assert_eq!(delta[0].valid, 0);
+        assert_eq!(counter.crc_errors, 0);
+        assert_eq!(counter.rx_errors, 0);
+    }
+
+    #[test]
+    fn test_duplicates() {
+        let mut counter = HashChain::new(2, 4);
+        let addr = |chip| bm1387::CoreAddress { chip, core: 0 };
+        counter.add_valid(addr(1));
+        counter.add_duplicate(addr(1));
+        counter.add_duplicate(addr(2));
+        assert_eq!(counter.duplicates, 1);
+        assert_eq!(counter.errors, 1);
+        assert_eq!(counter.chip[1].duplicates, 1);
+        assert_eq!(counter.chip[1].error_rate(4), Some(50.0));
+
+        let previous = counter.snapshot();
+        counter.add_duplicate(addr(1));
+        let delta = counter.chip_delta(&previous);
+        assert_eq!(delta[1].duplicates, 1);
+        assert_eq!(delta[1].errors, 1);
+        assert_eq!(delta[0].duplicates, 0);
+    }
+
+    #[test]
+    fn test_subrange_errors() {
+        let mut counter = HashChain::new(2, 4);
+        counter.add_valid(bm1387::CoreAddress { chip: 1, core: 0 });
+        counter.add_valid(bm1387::CoreAddress { chip: 2, core: 0 });
+        counter.add_error(bm1387::CoreAddress { chip: 0, core: 120 });
+        counter.add_duplicate(bm1387::CoreAddress { chip: 5, core: 0 });
+        assert_eq!(counter.subrange_errors, 3);
+        assert_eq!(counter.valid, 4);
+        assert_eq!(counter.errors, 0);
+        assert_eq!(counter.duplicates, 0);
+        counter.reset();
+        assert_eq!(counter.subrange_errors, 0);
+    }
+}
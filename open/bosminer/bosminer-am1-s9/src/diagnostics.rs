@@ -0,0 +1,144 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+//! Register dump of a hash chain for attaching to bug reports about chain misbehavior
+//!
+//! The dump contains all IP core registers (except for FIFO data) and a selected set of chip
+//! registers read from all chips by broadcast. Chip registers are read one by one and a failed
+//! read is recorded in the report instead of failing the whole dump, because misbehaving chains
+//! are exactly the ones which are worth dumping.
+
+use crate::bm1387;
+use crate::command::{self, Interface as _};
+use crate::error;
+use crate::io;
+
+use serde::Serialize;
+
+/// Values of one chip register read from all chips on the chain
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ChipRegister {
+    pub name: &'static str,
+    pub reg_num: u8,
+    /// Raw register values indexed by chip, empty when the read failed
+    pub values: Vec<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl ChipRegister {
+    fn new<T: bm1387::Register>(name: &'static str, result: error::Result<Vec<T>>) -> Self {
+        let (values, error) = match result {
+            Ok(regs) => (regs.iter().map(|reg| reg.to_reg()).collect(), None),
+            Err(e) => (vec![], Some(e.to_string())),
+        };
+        Self {
+            name,
+            reg_num: T::REG_NUM,
+            values,
+            error,
+        }
+    }
+
+    async fn read<T: bm1387::Register>(
+        command_context: &command::Context,
+        name: &'static str,
+    ) -> Self {
+        Self::new(name, command_context.read_all_registers::<T>().await)
+    }
+}
+
+/// Register dump of one hash chain
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Report {
+    pub hashboard_idx: usize,
+    /// Number of chips detected during chain initialization
+    pub chip_count: usize,
+    pub ip_core: io::RegisterDump,
+    pub chips: Vec<ChipRegister>,
+}
+
+impl Report {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("BUG: cannot serialize register dump")
+    }
+}
+
+/// Read the selected set of chip registers from all chips
+pub async fn read_chip_registers(command_context: &command::Context) -> Vec<ChipRegister> {
+    vec![
+        ChipRegister::read::<bm1387::GetAddressReg>(command_context, "address").await,
+        ChipRegister::read::<bm1387::PllReg>(command_context, "pll").await,
+        ChipRegister::read::<bm1387::HashrateReg>(command_context, "hashrate").await,
+        ChipRegister::read::<bm1387::TicketMaskReg>(command_context, "ticket_mask").await,
+        ChipRegister::read::<bm1387::MiscCtrlReg>(command_context, "misc_ctrl").await,
+    ]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::error::ErrorKind;
+
+    #[test]
+    fn test_chip_register() {
+        let reg = ChipRegister::new(
+            "hashrate",
+            Ok(vec![
+                bm1387::HashrateReg { hashrate24: 1 },
+                bm1387::HashrateReg { hashrate24: 0x1234 },
+            ]),
+        );
+        assert_eq!(reg.reg_num, 0x08);
+        assert_eq!(reg.values, vec![1, 0x1234]);
+        assert_eq!(reg.error, None);
+
+        let reg = ChipRegister::new::<bm1387::HashrateReg>(
+            "hashrate",
+            Err(ErrorKind::Hashchip("no response".to_string()).into()),
+        );
+        assert!(reg.values.is_empty());
+        assert!(reg.error.is_some());
+    }
+
+    #[test]
+    fn test_report_json() {
+        let report = Report {
+            hashboard_idx: 6,
+            chip_count: 63,
+            ip_core: io::RegisterDump {
+                build_id: 0x5d3b7dae,
+                ..Default::default()
+            },
+            chips: vec![ChipRegister::new(
+                "hashrate",
+                Ok(vec![bm1387::HashrateReg { hashrate24: 7 }]),
+            )],
+        };
+        let json: serde_json::Value =
+            serde_json::from_str(&report.to_json()).expect("invalid JSON");
+        assert_eq!(json["hashboard_idx"], 6);
+        assert_eq!(json["ip_core"]["build_id"], 0x5d3b7dae);
+        assert_eq!(json["chips"][0]["name"], "hashrate");
+        assert_eq!(json["chips"][0]["values"][0], 7);
+        assert!(json["chips"][0].get("error").is_none());
+    }
+}
@@ -0,0 +1,243 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Estimation of power consumption and energy efficiency (J/TH) of hash chains
+//!
+//! S9 has no power metering so the power is estimated from the chain voltage and chip
+//! frequencies. The estimate is periodically sampled and the energy consumed in each window is
+//! divided by the amount of work done in the same window.
+
+use crate::{ChainControl as _, Manager};
+
+use bosminer::node::Stats as _;
+use bosminer::stats;
+
+use ii_stats::WindowedTimeMean;
+
+use futures::lock::Mutex;
+use ii_async_compat::{futures, runtime};
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Dynamic power of BM1387 chips in W / (V^2 * Hz). Together with `LEAKAGE_CURRENT` it is
+/// calibrated to about 430 W for a chain with 63 chips at 650 MHz and 8.8 V.
+pub const DYNAMIC_POWER_COEF: f64 = 1.32e-10;
+
+/// Static current of the whole chain in A
+pub const LEAKAGE_CURRENT: f64 = 1.5;
+
+/// Period of sampling power estimates
+pub const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Estimates power (in W) of a chain running at `voltage` (in V) from the sum of frequencies of
+/// all its chips (in Hz)
+pub fn estimate_power(voltage: f64, frequency_sum: u64) -> f64 {
+    DYNAMIC_POWER_COEF * voltage * voltage * frequency_sum as f64 + LEAKAGE_CURRENT * voltage
+}
+
+#[derive(Debug, Clone)]
+pub struct MeterSnapshot {
+    /// The last power estimate in W
+    pub power: f64,
+    /// Approximate arithmetic mean of consumed energy within given time intervals (in J/s)
+    time_means: Vec<WindowedTimeMean>,
+}
+
+impl MeterSnapshot {
+    /// Average power (in W) within given time `interval`
+    pub fn average_power(&self, interval: Duration) -> f64 {
+        self.time_means
+            .iter()
+            .find(|time_mean| time_mean.interval() == interval)
+            .expect("BUG: cannot find given time interval")
+            .measure(Instant::now())
+    }
+
+    /// Energy efficiency (in J/TH) within given time `interval`. Returns `None` when no work
+    /// has been done.
+    pub fn efficiency(&self, interval: Duration, tera_hashes: f64) -> Option<f64> {
+        if tera_hashes > 0.0 {
+            Some(self.average_power(interval) / tera_hashes)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Inner {
+    snapshot: MeterSnapshot,
+    last_sample: Option<Instant>,
+}
+
+/// Keeps power estimates of one hash chain
+#[derive(Debug)]
+pub struct Meter {
+    inner: Mutex<Inner>,
+}
+
+impl Meter {
+    pub fn new(intervals: &[Duration]) -> Self {
+        Self {
+            inner: Mutex::new(Inner {
+                snapshot: MeterSnapshot {
+                    power: 0.0,
+                    time_means: intervals
+                        .iter()
+                        .map(|&interval| WindowedTimeMean::new(interval))
+                        .collect(),
+                },
+                last_sample: None,
+            }),
+        }
+    }
+
+    pub async fn take_snapshot(&self) -> stats::Snapshot<MeterSnapshot> {
+        stats::Snapshot::new(self.inner.lock().await.snapshot.clone())
+    }
+
+    /// Accounts energy consumed at `power` (in W) since the last sample
+    pub async fn account_power(&self, power: f64, now: Instant) {
+        let mut inner = self.inner.lock().await;
+        if let Some(last_sample) = inner.last_sample.replace(now) {
+            let energy = power * now.saturating_duration_since(last_sample).as_secs_f64();
+            for time_mean in &mut inner.snapshot.time_means {
+                time_mean.insert(energy, now);
+            }
+        }
+        inner.snapshot.power = power;
+    }
+}
+
+impl Default for Meter {
+    fn default() -> Self {
+        Self::new(&[
+            *stats::TIME_MEAN_INTERVAL_5S,
+            *stats::TIME_MEAN_INTERVAL_1M,
+            *stats::TIME_MEAN_INTERVAL_15M,
+        ])
+    }
+}
+
+/// Energy efficiency of one hash chain
+#[derive(Debug, Clone, PartialEq)]
+pub struct Efficiency {
+    /// The last power estimate in W
+    pub power: f64,
+    /// J/TH within 5 seconds, 1 minute and 15 minutes
+    pub efficiency_5s: Option<f64>,
+    pub efficiency_1m: Option<f64>,
+    pub efficiency_15m: Option<f64>,
+}
+
+impl Manager {
+    /// Estimates current power of the chain. It is zero when the chain is not running and `None`
+    /// when the chain is being started or stopped.
+    async fn estimate_power(&self) -> Option<f64> {
+        let inner = self.inner.try_lock()?;
+        Some(match inner.hash_chain.as_ref() {
+            Some(hash_chain) => estimate_power(
+                hash_chain.get_voltage().await.as_volts() as f64,
+                hash_chain.get_frequency().await.total(),
+            ),
+            None => 0.0,
+        })
+    }
+
+    /// Combines power estimates with measured hashrate of the chain
+    pub async fn get_efficiency(&self) -> Efficiency {
+        let power = self.efficiency.take_snapshot().await;
+        let valid_backend_diff = self
+            .mining_stats()
+            .valid_backend_diff()
+            .take_snapshot()
+            .await;
+        let now = Instant::now();
+        let efficiency = |interval| {
+            let tera_hashes = valid_backend_diff.to_tera_hashes(interval, now).into_f64();
+            power.efficiency(interval, tera_hashes)
+        };
+
+        Efficiency {
+            power: power.power,
+            efficiency_5s: efficiency(*stats::TIME_MEAN_INTERVAL_5S),
+            efficiency_1m: efficiency(*stats::TIME_MEAN_INTERVAL_1M),
+            efficiency_15m: efficiency(*stats::TIME_MEAN_INTERVAL_15M),
+        }
+    }
+}
+
+/// Periodically samples power estimates of all chains
+pub async fn sampling_task(managers: Vec<Arc<Manager>>) {
+    let mut interval = runtime::Interval::new(SAMPLE_INTERVAL);
+    while interval.tick().await.is_some() {
+        for manager in managers.iter() {
+            // Skip the sample when the chain is busy, the energy is accounted by the next one
+            if let Some(power) = manager.estimate_power().await {
+                manager
+                    .efficiency
+                    .account_power(power, Instant::now())
+                    .await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use ii_async_compat::tokio;
+
+    #[test]
+    fn test_estimate_power() {
+        let power = estimate_power(8.8, 63 * 650_000_000);
+        assert!((power - 431.8).abs() < 0.1, "unexpected power {}", power);
+        assert_eq!(estimate_power(0.0, 63 * 650_000_000), 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_meter() {
+        let meter = Meter::default();
+        let interval = *stats::TIME_MEAN_INTERVAL_5S;
+        assert_eq!(meter.take_snapshot().await.efficiency(interval, 0.0), None);
+
+        // the first sample only starts accounting
+        let now = Instant::now();
+        meter.account_power(400.0, now).await;
+        let snapshot = meter.take_snapshot().await;
+        assert_eq!(snapshot.power, 400.0);
+        assert_eq!(snapshot.average_power(interval), 0.0);
+
+        meter
+            .account_power(500.0, now + Duration::from_secs(1))
+            .await;
+        let snapshot = meter.take_snapshot().await;
+        assert_eq!(snapshot.power, 500.0);
+        assert!(snapshot.average_power(interval) > 0.0);
+        assert_eq!(
+            snapshot.efficiency(interval, 5.0),
+            Some(snapshot.average_power(interval) / 5.0)
+        );
+    }
+}
@@ -21,156 +21,132 @@
 // contact us at opensource@braiins.com.
 
 //! The Antminer S9 errors
-
-use failure::{Backtrace, Context, Fail};
-use std::fmt::{self, Debug, Display};
+//!
+//! Failures of hash chains which callers may want to handle differently (e.g. a missing
+//! hashboard is not worth retrying) have their own structured kinds so that they can be matched
+//! on instead of parsing free text.
 
 use std::io;
 use sysfs_gpio;
+use thiserror::Error;
 use uio_async;
 
-pub struct Error {
-    inner: Context<ErrorKind>,
-}
-
-#[derive(Clone, Eq, PartialEq, Debug, Fail)]
+#[derive(Error, Clone, Eq, PartialEq, Debug)]
 pub enum ErrorKind {
     /// General error used for more specific input/output error.
-    #[fail(display = "{}", _0)]
+    #[error("{0}")]
     General(String),
 
     /// Standard input/output error.
-    #[fail(display = "IO: {}", _0)]
+    #[error("IO: {0}")]
     Io(String),
 
     /// Error tied to a particular UIO device
-    #[fail(display = "UIO device {}: {}", _0, _1)]
+    #[error("UIO device {0}: {1}")]
     UioDevice(String, String),
 
     /// Generic UIO error
-    #[fail(display = "UIO: {}", _0)]
+    #[error("UIO: {0}")]
     Uio(String),
 
     /// Unexpected version of something.
-    #[fail(display = "Unexpected {} version: {}, expected: {}", _0, _1, _2)]
+    #[error("Unexpected {0} version: {1}, expected: {2}")]
     UnexpectedVersion(String, String, String),
 
     /// Error concerning hashboard with specific index.
-    #[fail(display = "Hashboard {}: {}", _0, _1)]
+    #[error("Hashboard {0}: {1}")]
     Hashboard(usize, String),
 
+    /// Hash chain with specific index is not present (e.g. the hashboard is unplugged).
+    #[error("Hashboard {0}: not present")]
+    ChainNotFound(usize),
+
     /// Error concerning hashchip.
-    #[fail(display = "Hashchip: {}", _0)]
+    #[error("Hashchip: {0}")]
     Hashchip(String),
 
+    /// Chip did not reply to a command in time.
+    #[error("Hashchip {chip}: no response")]
+    ChipTimeout { chip: usize },
+
+    /// Responses dropped by the IP core due to CRC mismatch, `counter` is the number of dropped
+    /// responses.
+    #[error("{counter} response(s) dropped due to CRC mismatch")]
+    CrcError { counter: usize },
+
     /// Error concerning hashchip enumeration.
-    #[fail(display = "Enumeration: {}", _0)]
+    #[error("Enumeration: {0}")]
     ChipEnumeration(String),
 
+    /// Less chips than expected responded during enumeration.
+    #[error("Enumeration: detected {1} chips, expected {0}")]
+    ChipCountMismatch(usize, usize),
+
     /// Error concerning I2C on hashchip.
-    #[fail(display = "I2C hashchip: {}", _0)]
+    #[error("I2C hashchip: {0}")]
     I2cHashchip(String),
 
     /// Work or command FIFO timeout.
-    #[fail(display = "FIFO: {}: {}", _0, _1)]
+    #[error("FIFO: {0}: {1}")]
     Fifo(Fifo, String),
 
     /// Baud rate errors.
-    #[fail(display = "Baud rate: {}", _0)]
+    #[error("Baud rate: {0}")]
     BaudRate(String),
 
     /// GPIO errors.
-    #[fail(display = "GPIO: {}", _0)]
+    #[error("GPIO: {0}")]
     Gpio(String),
 
     /// I2C errors.
-    #[fail(display = "I2C: {}", _0)]
+    #[error("I2C: {0}")]
     I2c(String),
 
     /// Power controller errors.
-    #[fail(display = "Power: {}", _0)]
+    #[error("Power: {0}")]
     Power(String),
 
+    /// Voltage controller of hashboard with specific index failed to power the chips.
+    #[error("Hashboard {0}: power failure")]
+    PowerFailure(usize),
+
     /// PLL conversion error
-    #[fail(display = "PLL: {}", _0)]
+    #[error("PLL: {0}")]
     PLL(String),
 
     /// Error from hashchain manager.
-    #[fail(display = "HashChain Manager: {}", _0)]
+    #[error("HashChain Manager: {0}")]
     HashChainManager(HashChainManager),
 
-    /// Error when halting.
-    #[fail(display = "Halt: {}", _0)]
-    Halt(String),
-
     /// Error when dealing with sensors.
-    #[fail(display = "Sensors: {}", _0)]
+    #[error("Sensors: {0}")]
     Sensors(String),
 }
 
-#[derive(Clone, Eq, PartialEq, Debug, Fail)]
+ii_error::error_type!(ErrorKind);
+
+#[derive(Error, Clone, Eq, PartialEq, Debug)]
 pub enum HashChainManager {
-    #[fail(display = "HashChain parameters not set")]
+    #[error("HashChain parameters not set")]
     ParamsNotSet,
 }
 
-#[derive(Clone, Eq, PartialEq, Debug, Fail)]
+#[derive(Error, Clone, Eq, PartialEq, Debug)]
 pub enum Fifo {
-    #[fail(display = "timed out")]
+    #[error("timed out")]
     TimedOut,
 }
 
-/// Implement Fail trait instead of use Derive to get more control over custom type.
-/// The main advantage is customization of Context type which allows conversion of
-/// any error types to this custom error with general error kind by calling context
-/// method on any result type.
-impl Fail for Error {
-    fn cause(&self) -> Option<&dyn Fail> {
-        self.inner.cause()
-    }
-
-    fn backtrace(&self) -> Option<&Backtrace> {
-        self.inner.backtrace()
-    }
-}
-
-impl Display for Error {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        Display::fmt(&self.inner, f)
-    }
-}
-
-impl Debug for Error {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        Debug::fmt(&self.inner, f)
+/// Context message provided by `ResultExt::context()`
+impl From<&str> for ErrorKind {
+    fn from(info: &str) -> Self {
+        ErrorKind::General(info.to_string())
     }
 }
 
-impl Error {
-    pub fn kind(&self) -> ErrorKind {
-        self.inner.get_context().clone()
-    }
-}
-
-impl From<ErrorKind> for Error {
-    fn from(kind: ErrorKind) -> Self {
-        Self {
-            inner: Context::new(kind),
-        }
-    }
-}
-
-impl From<Context<ErrorKind>> for Error {
-    fn from(inner: Context<ErrorKind>) -> Self {
-        Self { inner }
-    }
-}
-
-impl From<Context<String>> for Error {
-    fn from(context: Context<String>) -> Self {
-        Self {
-            inner: context.map(|info| ErrorKind::General(info)),
-        }
+impl From<String> for ErrorKind {
+    fn from(info: String) -> Self {
+        ErrorKind::General(info)
     }
 }
 
@@ -182,39 +158,73 @@ impl From<String> for Error {
 
 impl From<std::num::ParseIntError> for Error {
     fn from(e: std::num::ParseIntError) -> Self {
-        let msg = e.to_string();
-        Self {
-            inner: e.context(ErrorKind::General(msg)),
-        }
+        Self::with_source(ErrorKind::General(e.to_string()), e)
     }
 }
 
 impl From<io::Error> for Error {
     fn from(e: io::Error) -> Self {
-        let msg = e.to_string();
-        Self {
-            inner: e.context(ErrorKind::Io(msg)),
-        }
+        Self::with_source(ErrorKind::Io(e.to_string()), e)
     }
 }
 
 impl From<uio_async::UioError> for Error {
     fn from(uio_error: uio_async::UioError) -> Self {
-        let msg = uio_error.to_string();
-        Self {
-            inner: uio_error.context(ErrorKind::Uio(msg)),
-        }
+        Self::with_source(ErrorKind::Uio(uio_error.to_string()), uio_error)
     }
 }
 
 impl From<sysfs_gpio::Error> for Error {
     fn from(gpio_error: sysfs_gpio::Error) -> Self {
-        let msg = gpio_error.to_string();
-        Self {
-            inner: gpio_error.context(ErrorKind::Gpio(msg)),
-        }
+        Self::with_source(ErrorKind::Gpio(gpio_error.to_string()), gpio_error)
+    }
+}
+
+impl From<Error> for bosminer::error::Error {
+    fn from(error: Error) -> Self {
+        bosminer::error::backend::from_error(error)
     }
 }
 
-/// A specialized `Result` type bound to [`Error`].
-pub type Result<T> = std::result::Result<T, Error>;
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::error::Error as StdError;
+
+    #[test]
+    fn test_chain_kinds() {
+        assert_eq!(
+            ErrorKind::ChainNotFound(8).to_string(),
+            "Hashboard 8: not present"
+        );
+        assert_eq!(
+            ErrorKind::ChipTimeout { chip: 3 }.to_string(),
+            "Hashchip 3: no response"
+        );
+        assert_eq!(
+            ErrorKind::PowerFailure(6).to_string(),
+            "Hashboard 6: power failure"
+        );
+    }
+
+    #[test]
+    fn test_crc_error_cause() {
+        // CRC errors during enumeration do not change the kind of the failure
+        let error = Error::with_source(
+            ErrorKind::ChipCountMismatch(63, 60),
+            ErrorKind::CrcError { counter: 3 },
+        );
+        assert_eq!(error.kind(), ErrorKind::ChipCountMismatch(63, 60));
+        assert_eq!(
+            error.to_string(),
+            "Enumeration: detected 60 chips, expected 63"
+        );
+        assert_eq!(
+            error
+                .source()
+                .expect("BUG: missing source of error")
+                .to_string(),
+            "3 response(s) dropped due to CRC mismatch"
+        );
+    }
+}
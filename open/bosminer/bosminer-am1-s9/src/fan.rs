@@ -24,8 +24,8 @@
 
 pub mod pid;
 
+use crate::error::ResultExt;
 use crate::error::{self, ErrorKind};
-use failure::ResultExt;
 
 use uio_async;
 
@@ -44,18 +44,22 @@ impl TempControl {
             pid,
             last_update: Instant::now(),
         };
-        temp_control.set_warm_up_limits();
+        temp_control.set_warm_up_limits(Speed::STOPPED);
         return temp_control;
     }
 
+    /// Fan speed never drops below this value when warming up
+    const WARM_UP_MIN_SPEED: usize = 60;
+
     /// set fan limits when warming up
-    pub fn set_warm_up_limits(&mut self) {
-        self.pid.set_limits(60.0, 100.0);
+    pub fn set_warm_up_limits(&mut self, min_speed: Speed) {
+        let min_speed = min_speed.to_pwm().max(Self::WARM_UP_MIN_SPEED);
+        self.pid.set_limits(min_speed as f64, 100.0);
     }
 
     /// set fan limits when in operation
-    pub fn set_normal_limits(&mut self) {
-        self.pid.set_limits(1.0, 100.0);
+    pub fn set_normal_limits(&mut self, min_speed: Speed) {
+        self.pid.set_limits(min_speed.to_pwm() as f64, 100.0);
     }
 
     pub fn set_target(&mut self, target: f64) {
@@ -70,3 +74,28 @@ impl TempControl {
         Speed::new(pwm as usize)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_min_speed() {
+        let mut temp_control = TempControl::new();
+        temp_control.set_target(75.0);
+
+        // cold miner would stop fans completely without lower limit
+        temp_control.set_normal_limits(Speed::new(30));
+        assert_eq!(temp_control.update(20.0).to_pwm(), 30);
+
+        // warm up limit takes precedence over lower configured limit
+        temp_control.set_warm_up_limits(Speed::new(30));
+        assert_eq!(temp_control.update(20.0).to_pwm(), 60);
+        temp_control.set_warm_up_limits(Speed::new(80));
+        assert_eq!(temp_control.update(20.0).to_pwm(), 80);
+
+        // hot miner runs fans at full speed
+        temp_control.set_normal_limits(Speed::new(30));
+        assert_eq!(temp_control.update(120.0).to_pwm(), 100);
+    }
+}
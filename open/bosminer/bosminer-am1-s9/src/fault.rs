@@ -0,0 +1,279 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Statistical detection of hash chain faults
+//!
+//! Hashes of valid solutions are random so nonce bits which are iterated by chip cores have to be
+//! evenly distributed and solutions have to arrive at random (exponentially distributed) intervals.
+//! Any significant deviation points to a broken chip (stuck nonce counter, repeated results) rather
+//! than to ordinary hardware errors which are accounted by `counters`.
+
+use bosminer::alert;
+
+use std::fmt;
+use std::time::Instant;
+
+/// Nonce bits iterated by a core. The remaining bits encode chip and core address
+/// (see `bm1387::CoreAddress`) which are not uniformly distributed for chains with less chips.
+pub const NONCE_COUNTER_MASK: u32 = 0x00ff_ff00;
+
+/// Number of solutions evaluated at once
+pub const WINDOW_SIZE: usize = 512;
+
+/// A nonce bit is considered stuck when its ratio of ones is outside of the range
+/// `[STUCK_BIT_RATIO, 1 - STUCK_BIT_RATIO]`
+pub const STUCK_BIT_RATIO: f64 = 0.05;
+
+/// Maximal ratio of duplicated solutions in the window
+pub const MAX_DUPLICATE_RATIO: f64 = 0.1;
+
+/// Minimal coefficient of variation of solution inter-arrival times. Its expected value for
+/// the exponential distribution is 1, much lower value means that solutions arrive periodically.
+pub const MIN_ARRIVAL_VARIATION: f64 = 0.25;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Fault {
+    /// Nonce bits given by `mask` have the same value in almost all solutions
+    StuckNonceBits { mask: u32 },
+    /// Hash chain repeatedly returns the same results
+    DuplicatedResults { ratio: f64 },
+    /// Solutions arrive in regular intervals
+    RegularArrivals { variation: f64 },
+}
+
+impl Fault {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::StuckNonceBits { .. } => "stuck_nonce_bits",
+            Self::DuplicatedResults { .. } => "duplicated_results",
+            Self::RegularArrivals { .. } => "regular_arrivals",
+        }
+    }
+
+    pub fn to_alert_event(&self, hashboard_idx: usize) -> alert::Event {
+        alert::Event::HardwareFault {
+            hashboard_idx,
+            fault: self.kind(),
+            reason: self.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::StuckNonceBits { mask } => write!(f, "nonce bits {:#010x} are stuck", mask),
+            Self::DuplicatedResults { ratio } => {
+                write!(f, "{:.1}% of results are duplicated", ratio * 100.0)
+            }
+            Self::RegularArrivals { variation } => write!(
+                f,
+                "solutions arrive periodically (variation of intervals {:.2})",
+                variation
+            ),
+        }
+    }
+}
+
+/// Accumulates statistics of solutions from one hash chain
+#[derive(Debug, Default)]
+pub struct Detector {
+    /// Number of unique solutions in current window
+    solutions: usize,
+    /// Number of duplicated solutions in current window
+    duplicates: usize,
+    /// Number of solutions with particular nonce bit set
+    ones: [usize; 32],
+    last_arrival: Option<Instant>,
+    /// Number of inter-arrival intervals, their sum and sum of squares (in seconds)
+    intervals: usize,
+    interval_sum: f64,
+    interval_square_sum: f64,
+}
+
+impl Detector {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    fn reset_window(&mut self) {
+        let last_arrival = self.last_arrival;
+        *self = Self::new();
+        self.last_arrival = last_arrival;
+    }
+
+    pub fn account_duplicate(&mut self) {
+        self.duplicates += 1;
+    }
+
+    /// Accounts unique solution with `nonce` that arrived at time `now`. Faults detected in the
+    /// window are returned when the window is full.
+    pub fn account_solution(&mut self, nonce: u32, now: Instant) -> Vec<Fault> {
+        self.solutions += 1;
+        for (i, ones) in self.ones.iter_mut().enumerate() {
+            if nonce & (1 << i) != 0 {
+                *ones += 1;
+            }
+        }
+        if let Some(last_arrival) = self.last_arrival.replace(now) {
+            let interval = now.saturating_duration_since(last_arrival).as_secs_f64();
+            self.intervals += 1;
+            self.interval_sum += interval;
+            self.interval_square_sum += interval * interval;
+        }
+
+        if self.solutions < WINDOW_SIZE {
+            return vec![];
+        }
+        let faults = self.evaluate();
+        self.reset_window();
+        faults
+    }
+
+    fn stuck_bits(&self) -> u32 {
+        let min_ones = (self.solutions as f64 * STUCK_BIT_RATIO) as usize;
+        let max_ones = self.solutions - min_ones;
+        self.ones
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| NONCE_COUNTER_MASK & (1 << i) != 0)
+            .filter(|(_, ones)| **ones < min_ones || **ones > max_ones)
+            .fold(0, |mask, (i, _)| mask | (1 << i))
+    }
+
+    fn arrival_variation(&self) -> Option<f64> {
+        if self.intervals < 2 {
+            return None;
+        }
+        let count = self.intervals as f64;
+        let mean = self.interval_sum / count;
+        if mean <= 0.0 {
+            return None;
+        }
+        let variance = (self.interval_square_sum / count - mean * mean).max(0.0);
+        Some(variance.sqrt() / mean)
+    }
+
+    fn evaluate(&self) -> Vec<Fault> {
+        let mut faults = vec![];
+
+        let mask = self.stuck_bits();
+        if mask != 0 {
+            faults.push(Fault::StuckNonceBits { mask });
+        }
+        let ratio = self.duplicates as f64 / (self.solutions + self.duplicates) as f64;
+        if ratio > MAX_DUPLICATE_RATIO {
+            faults.push(Fault::DuplicatedResults { ratio });
+        }
+        if let Some(variation) = self.arrival_variation() {
+            if variation < MIN_ARRIVAL_VARIATION {
+                faults.push(Fault::RegularArrivals { variation });
+            }
+        }
+        faults
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::time::Duration;
+
+    /// Simple xorshift generator to get deterministic pseudo-random nonces and intervals
+    struct Random(u32);
+
+    impl Random {
+        fn next(&mut self) -> u32 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 17;
+            self.0 ^= self.0 << 5;
+            self.0
+        }
+
+        /// Exponentially distributed interval with mean 10 ms
+        fn interval(&mut self) -> Duration {
+            let uniform = (self.next() as f64 + 1.0) / (u32::MAX as f64 + 2.0);
+            Duration::from_secs_f64(-uniform.ln() * 0.01)
+        }
+    }
+
+    fn run_window<F>(detector: &mut Detector, mut nonce: F) -> Vec<Fault>
+    where
+        F: FnMut(&mut Random) -> u32,
+    {
+        let mut random = Random(0x1234_5678);
+        let mut now = Instant::now();
+        for _ in 0..WINDOW_SIZE - 1 {
+            assert!(detector
+                .account_solution(nonce(&mut random), now)
+                .is_empty());
+            now += random.interval();
+        }
+        detector.account_solution(nonce(&mut random), now)
+    }
+
+    #[test]
+    fn test_healthy_chain() {
+        let mut detector = Detector::new();
+        for _ in 0..10 {
+            detector.account_duplicate();
+        }
+        assert_eq!(run_window(&mut detector, |random| random.next()), vec![]);
+    }
+
+    #[test]
+    fn test_stuck_nonce_bits() {
+        let mut detector = Detector::new();
+        // upper bits with core address are not checked
+        let faults = run_window(&mut detector, |random| random.next() & 0x00ef_ffff);
+        assert_eq!(faults, vec![Fault::StuckNonceBits { mask: 0x0010_0000 }]);
+    }
+
+    #[test]
+    fn test_duplicated_results() {
+        let mut detector = Detector::new();
+        for _ in 0..WINDOW_SIZE / 4 {
+            detector.account_duplicate();
+        }
+        let faults = run_window(&mut detector, |random| random.next());
+        assert_eq!(faults.len(), 1);
+        assert_eq!(faults[0].kind(), "duplicated_results");
+
+        // next window starts from scratch
+        assert_eq!(run_window(&mut detector, |random| random.next()), vec![]);
+    }
+
+    #[test]
+    fn test_regular_arrivals() {
+        let mut detector = Detector::new();
+        let mut random = Random(1);
+        let mut now = Instant::now();
+        let mut faults = vec![];
+        for _ in 0..WINDOW_SIZE {
+            faults = detector.account_solution(random.next(), now);
+            now += Duration::from_millis(10);
+        }
+        assert_eq!(faults.len(), 1);
+        assert_eq!(faults[0].kind(), "regular_arrivals");
+    }
+}
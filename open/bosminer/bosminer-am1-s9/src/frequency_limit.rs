@@ -0,0 +1,150 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Limiting of chip frequencies of running hash chains so that the whole miner fits into some
+//! budget (e.g. power target or hashrate cap)
+//!
+//! The budget is split evenly between running hash chains. Each chain exceeding its share is
+//! clocked down to the frequency given by the limit function of the particular mode. Original
+//! frequencies are restored as soon as the budget allows it or when the limit is removed.
+
+use ii_logging::macros::*;
+
+use crate::config;
+use crate::{ChainStatus, FrequencySettings, Manager};
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Frequency (in Hz) of all chips of a chain with `chip_count` chips running at `voltage` (in V)
+/// which fits into the chain share `budget` of the whole miner budget
+pub type LimitFn = fn(voltage: f64, chip_count: usize, budget: f64) -> usize;
+
+pub struct Limiter {
+    /// Owner of hash chains while their frequency is being adjusted
+    owner_name: &'static str,
+    managers: Vec<Arc<Manager>>,
+    frequency_for_budget: LimitFn,
+    /// Original frequencies of limited chains indexed by hashboard
+    original_frequencies: HashMap<usize, FrequencySettings>,
+}
+
+impl Limiter {
+    pub fn new(
+        owner_name: &'static str,
+        managers: Vec<Arc<Manager>>,
+        frequency_for_budget: LimitFn,
+    ) -> Self {
+        Self {
+            owner_name,
+            managers,
+            frequency_for_budget,
+            original_frequencies: HashMap::new(),
+        }
+    }
+
+    /// Number of hash chains clocked down below their original frequency
+    pub fn limited_chains(&self) -> usize {
+        self.original_frequencies.len()
+    }
+
+    /// Clocks running chains down or up so that they fit into the `budget` of the whole miner.
+    /// All limited chains are restored when `budget` is `None`.
+    pub async fn adjust(&mut self, budget: Option<f64>) {
+        if budget.is_none() && self.original_frequencies.is_empty() {
+            return;
+        }
+
+        let mut running_chains = vec![];
+        for manager in self.managers.iter() {
+            // Chains which are being started or tuned are adjusted the next time
+            if let Ok(ChainStatus::Running(running_chain)) =
+                manager.clone().acquire(self.owner_name).await
+            {
+                running_chains.push(running_chain);
+            }
+        }
+        if running_chains.is_empty() {
+            return;
+        }
+        let chain_budget = budget.map(|budget| budget / running_chains.len() as f64);
+        let min_frequency = (config::FREQUENCY_MHZ_MIN * 1_000_000.0) as usize;
+
+        for running_chain in running_chains {
+            let hashboard_idx = running_chain.manager.hashboard_idx;
+            let chip_count = running_chain.snapshot_counter().await.chip_count();
+            if chip_count == 0 {
+                // chips of the chain are still being enumerated so the limit cannot be computed
+                continue;
+            }
+            let current_frequency = running_chain.get_frequency().await;
+            let original_frequency = self
+                .original_frequencies
+                .get(&hashboard_idx)
+                .cloned()
+                .unwrap_or_else(|| current_frequency.clone());
+            let allowed_frequency = match chain_budget {
+                Some(chain_budget) => (self.frequency_for_budget)(
+                    running_chain.get_voltage().await.as_volts() as f64,
+                    chip_count,
+                    chain_budget,
+                )
+                .max(min_frequency),
+                None => usize::MAX,
+            };
+
+            let frequency = if original_frequency.max() <= allowed_frequency {
+                if self.original_frequencies.remove(&hashboard_idx).is_none() {
+                    // the chain is not limited
+                    continue;
+                }
+                info!(
+                    "Restoring frequency of chain {} limited by {}",
+                    hashboard_idx, self.owner_name
+                );
+                original_frequency
+            } else {
+                self.original_frequencies
+                    .entry(hashboard_idx)
+                    .or_insert(original_frequency);
+                if current_frequency.min() == allowed_frequency
+                    && current_frequency.max() == allowed_frequency
+                {
+                    continue;
+                }
+                info!(
+                    "Limiting chain {} to {} MHz by {}",
+                    hashboard_idx,
+                    allowed_frequency / 1_000_000,
+                    self.owner_name
+                );
+                FrequencySettings::from_frequency(allowed_frequency)
+            };
+            if let Err(e) = running_chain.set_frequency(&frequency).await {
+                error!(
+                    "Failed to set frequency of chain {} limited by {}: {}",
+                    hashboard_idx, self.owner_name, e
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,249 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+//! Dynamic frequency scaling of hash chains
+//!
+//! The governor periodically nudges frequency of each running chain by one step towards the
+//! highest one at which the chain stays below the target temperature and chips do not return too
+//! many hardware errors. The frequency is lowered when the chain is hotter than the target or when
+//! the error rate within the last check interval is too high, it is raised only when there is
+//! temperature headroom of at least the hysteresis. Thermal throttling (see `throttle`) stays in
+//! charge of chains exceeding the throttle temperature.
+
+use ii_logging::macros::*;
+
+use crate::counters;
+use crate::monitor::ChainTemperature;
+use crate::{ChainStatus, FrequencySettings, Manager, RunningChain};
+
+use ii_async_compat::runtime;
+use runtime::delay_for;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Owner of hash chains while their frequency is being adjusted
+const OWNER_NAME: &str = "governor";
+
+/// Period of checking running chains, it is also the interval in which the error rate is measured
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Frequency (in Hz) by which chips are clocked down or up in one check interval
+pub const FREQUENCY_STEP: usize = 10_000_000;
+
+pub const DEFAULT_TARGET_TEMP: f32 = 85.0;
+pub const DEFAULT_HYSTERESIS: f32 = 3.0;
+pub const DEFAULT_FREQUENCY_MIN: usize = 550_000_000;
+pub const DEFAULT_FREQUENCY_MAX: usize = 750_000_000;
+/// Maximal percentage of hardware errors within one check interval
+pub const DEFAULT_MAX_ERROR_RATE: f64 = 1.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    /// Temperature above which the chain is clocked down
+    pub target_temp: f32,
+    /// Degrees below `target_temp` the temperature has to be before the chain is clocked up
+    pub hysteresis: f32,
+    /// Bounds of frequency of chips in Hz
+    pub frequency_min: usize,
+    pub frequency_max: usize,
+    /// Percentage of hardware errors above which the chain is clocked down
+    pub max_error_rate: f64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            target_temp: DEFAULT_TARGET_TEMP,
+            hysteresis: DEFAULT_HYSTERESIS,
+            frequency_min: DEFAULT_FREQUENCY_MIN,
+            frequency_max: DEFAULT_FREQUENCY_MAX,
+            max_error_rate: DEFAULT_MAX_ERROR_RATE,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    /// Keep the current frequency
+    Hold,
+    /// Clock the chain down
+    Lower,
+    /// Clock the chain up
+    Raise,
+}
+
+impl Config {
+    /// Decides the next step from `temperature` and hardware `error_rate` (in percent) measured in
+    /// the last check interval. The error rate is unknown when the chain has not returned any
+    /// nonce, the frequency is not raised then.
+    pub fn decide(&self, temperature: f32, error_rate: Option<f64>) -> Action {
+        let errors_ok = error_rate.map(|error_rate| error_rate <= self.max_error_rate);
+        if temperature > self.target_temp || errors_ok == Some(false) {
+            Action::Lower
+        } else if temperature <= self.target_temp - self.hysteresis && errors_ok == Some(true) {
+            Action::Raise
+        } else {
+            Action::Hold
+        }
+    }
+
+    /// Moves frequency of all chips by one step in the direction of `action` within the
+    /// configured bounds
+    fn step_frequency(&self, current: &FrequencySettings, action: Action) -> FrequencySettings {
+        if action == Action::Hold {
+            return current.clone();
+        }
+        FrequencySettings {
+            chip: current
+                .chip
+                .iter()
+                .map(|&frequency| {
+                    let frequency = if action == Action::Lower {
+                        frequency.saturating_sub(FREQUENCY_STEP)
+                    } else {
+                        frequency + FREQUENCY_STEP
+                    };
+                    frequency.max(self.frequency_min).min(self.frequency_max)
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Checks the running chain and adjusts its frequency. `last_counter` is the counter snapshot
+/// of the previous check.
+async fn check_chain(
+    running_chain: &RunningChain,
+    hashboard_idx: usize,
+    config: &Config,
+    last_counter: &mut Option<counters::HashChain>,
+) {
+    if running_chain.is_idle().await {
+        last_counter.take();
+        return;
+    }
+    let counter = running_chain.snapshot_counter().await;
+    let error_rate = match last_counter.replace(counter.clone()) {
+        // counters are restarted together with the chain
+        Some(last_counter) if last_counter.started == counter.started => {
+            counter.error_rate_since(&last_counter)
+        }
+        _ => return,
+    };
+    let temperature = match running_chain
+        .current_temperature()
+        .await
+        .map(ChainTemperature::from_s9_sensor)
+    {
+        Some(ChainTemperature::Ok(temperature)) => temperature,
+        _ => return,
+    };
+
+    let action = config.decide(temperature, error_rate);
+    let current_frequency = running_chain.get_frequency().await;
+    let frequency = config.step_frequency(&current_frequency, action);
+    if frequency.chip == current_frequency.chip {
+        return;
+    }
+    info!(
+        "Governor: chain {} at {} C with error rate {:?} %, setting frequency {}",
+        hashboard_idx, temperature, error_rate, frequency
+    );
+    if let Err(e) = running_chain.set_frequency(&frequency).await {
+        error!(
+            "Governor: failed to set frequency of chain {}: {}",
+            hashboard_idx, e
+        );
+    }
+    // errors caused by the change are accounted to the new frequency
+    last_counter.replace(running_chain.snapshot_counter().await);
+}
+
+/// Scales frequency of the chain managed by `manager` while it is running
+pub async fn governor_task(manager: Arc<Manager>, config: Config) {
+    let mut last_counter = None;
+    loop {
+        delay_for(CHECK_INTERVAL).await;
+        match manager.clone().acquire(OWNER_NAME).await {
+            Ok(ChainStatus::Running(running_chain)) => {
+                check_chain(
+                    &running_chain,
+                    manager.hashboard_idx,
+                    &config,
+                    &mut last_counter,
+                )
+                .await
+            }
+            // Chains stopped or being handled by someone else are not checked
+            Ok(ChainStatus::Stopped(_)) | Err(_) => last_counter = None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_decide() {
+        let config = Config {
+            target_temp: 80.0,
+            hysteresis: 3.0,
+            max_error_rate: 2.0,
+            ..Default::default()
+        };
+        assert_eq!(config.decide(70.0, Some(0.5)), Action::Raise);
+        assert_eq!(config.decide(77.0, Some(2.0)), Action::Raise);
+        // no headroom or unknown error rate
+        assert_eq!(config.decide(78.0, Some(0.5)), Action::Hold);
+        assert_eq!(config.decide(80.0, Some(0.5)), Action::Hold);
+        assert_eq!(config.decide(70.0, None), Action::Hold);
+        // too hot or too many errors
+        assert_eq!(config.decide(80.5, Some(0.5)), Action::Lower);
+        assert_eq!(config.decide(80.5, None), Action::Lower);
+        assert_eq!(config.decide(70.0, Some(2.5)), Action::Lower);
+    }
+
+    #[test]
+    fn test_step_frequency() {
+        let config = Config {
+            frequency_min: 600_000_000,
+            frequency_max: 700_000_000,
+            ..Default::default()
+        };
+        let current = FrequencySettings {
+            chip: vec![650_000_000, 695_000_000, 605_000_000],
+        };
+        assert_eq!(
+            config.step_frequency(&current, Action::Raise).chip,
+            vec![660_000_000, 700_000_000, 615_000_000]
+        );
+        assert_eq!(
+            config.step_frequency(&current, Action::Lower).chip,
+            vec![640_000_000, 685_000_000, 600_000_000]
+        );
+        assert_eq!(
+            config.step_frequency(&current, Action::Hold).chip,
+            current.chip
+        );
+    }
+}
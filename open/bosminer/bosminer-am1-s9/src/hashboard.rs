@@ -0,0 +1,235 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Reset and power sequencing of hashboards
+//!
+//! Each hashboard has a plug pin signalling its presence and a reset line shared by all its
+//! chips. Both are GPIOs exported through sysfs. S9 hashboards have no power-enable GPIO, the
+//! power is switched by the PIC voltage controller over I2C instead.
+//!
+//! The bring-up sequence of a hashboard is:
+//!
+//! 1. hold the chips in reset and switch the power off, wait `POWER_OFF_DELAY`
+//! 2. switch the power on, wait `POWER_ON_DELAY` for the voltage to settle
+//! 3. deassert reset, wait `RESET_RELEASE_DELAY` before talking to the chips
+//!
+//! S9 hashboards have no separate EEPROM either. The identification programmed by the
+//! manufacturer (serial number and frequency bin of the chips) is read from the PIC.
+
+use ii_logging::macros::*;
+
+use crate::error::{self, ErrorKind, ResultExt};
+use crate::gpio;
+use crate::power;
+use crate::FrequencySettings;
+
+use embedded_hal::digital::v2::{InputPin, OutputPin};
+
+use ii_async_compat::runtime;
+use runtime::delay_for;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Time for which the hashboard is kept powered down in reset
+pub const POWER_OFF_DELAY: Duration = Duration::from_secs(1);
+
+/// Time for the voltage to settle after the power is switched on
+pub const POWER_ON_DELAY: Duration = Duration::from_secs(2);
+
+/// Time for the chips to wake up after reset is deasserted
+pub const RESET_RELEASE_DELAY: Duration = Duration::from_secs(1);
+
+/// Type representing plug pin
+#[derive(Clone)]
+pub struct PlugPin {
+    pin: gpio::PinIn,
+}
+
+impl PlugPin {
+    pub fn open(gpio_mgr: &gpio::ControlPinManager, hashboard_idx: usize) -> error::Result<Self> {
+        Ok(Self {
+            pin: gpio_mgr
+                .get_pin_in(gpio::PinInName::Plug(hashboard_idx))
+                .context(ErrorKind::Hashboard(
+                    hashboard_idx,
+                    "failed to initialize plug pin".to_string(),
+                ))?,
+        })
+    }
+
+    pub fn hashboard_present(&self) -> error::Result<bool> {
+        Ok(self.pin.is_high()?)
+    }
+}
+
+/// Type representing reset pin
+#[derive(Clone)]
+pub struct ResetPin {
+    pin: gpio::PinOut,
+}
+
+impl ResetPin {
+    pub fn open(gpio_mgr: &gpio::ControlPinManager, hashboard_idx: usize) -> error::Result<Self> {
+        Ok(Self {
+            pin: gpio_mgr
+                .get_pin_out(gpio::PinOutName::Rst(hashboard_idx))
+                .context(ErrorKind::Hashboard(
+                    hashboard_idx,
+                    "failed to initialize reset pin".to_string(),
+                ))?,
+        })
+    }
+
+    pub fn enter_reset(&mut self) -> error::Result<()> {
+        self.pin.set_low()?;
+        Ok(())
+    }
+
+    pub fn exit_reset(&mut self) -> error::Result<()> {
+        self.pin.set_high()?;
+        Ok(())
+    }
+}
+
+/// Identification of a hashboard programmed by the manufacturer
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Info {
+    /// Hash board ID in hex, `None` when it is not programmed
+    pub serial: Option<String>,
+    /// Frequency bin of the chips (base frequency index from `FREQ` section of PIC flash),
+    /// `None` when the section is not valid
+    pub chip_bin: Option<u8>,
+}
+
+impl Info {
+    /// Reads the identification from initialized voltage controller. Missing items are only
+    /// reported because older boards do not have them.
+    pub async fn read(voltage_ctrl: &power::Control) -> Self {
+        let serial = match voltage_ctrl.get_hash_board_id().await {
+            Ok(id) => parse_serial(&id),
+            Err(e) => {
+                warn!("Failed to read hash board ID: {}", e);
+                None
+            }
+        };
+        Self {
+            serial,
+            chip_bin: voltage_ctrl
+                .get_freq_flash()
+                .await
+                .map(|freq_flash| freq_flash.base_freq_index),
+        }
+    }
+}
+
+/// Converts hash board ID to hex string. Erased (all `0xff`) or zeroed ID is not programmed.
+fn parse_serial(id: &[u8]) -> Option<String> {
+    if id.iter().all(|&byte| byte == 0xff) || id.iter().all(|&byte| byte == 0) {
+        return None;
+    }
+    Some(id.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+/// Default frequency and voltage of hashboards with chips of particular bin
+#[derive(Clone)]
+pub struct Profile {
+    pub chip_bin: u8,
+    pub frequency: FrequencySettings,
+    pub voltage: power::Voltage,
+}
+
+impl Profile {
+    /// Finds profile matching chip bin of the hashboard described by `info`
+    pub fn select<'a>(profiles: &'a [Self], info: &Info) -> Option<&'a Self> {
+        let chip_bin = info.chip_bin?;
+        profiles.iter().find(|profile| profile.chip_bin == chip_bin)
+    }
+}
+
+/// Performs reset and power sequences of one hashboard
+pub struct PowerSequencer {
+    reset_pin: ResetPin,
+    voltage_ctrl: Arc<power::Control>,
+}
+
+impl PowerSequencer {
+    pub fn new(reset_pin: ResetPin, voltage_ctrl: Arc<power::Control>) -> Self {
+        Self {
+            reset_pin,
+            voltage_ctrl,
+        }
+    }
+
+    /// Holds the chips in reset and switches the power off
+    pub async fn power_down(&mut self) -> error::Result<()> {
+        // Warning: Reset pin DOESN'T reset the PIC. The PIC needs to be reset by other means.
+        self.reset_pin.enter_reset()?;
+        self.voltage_ctrl.disable_voltage().await
+    }
+
+    /// Power-cycles the hashboard and takes its chips out of reset (see the module documentation
+    /// for the sequence). The voltage controller has to be initialized already.
+    pub async fn bring_up(&mut self) -> error::Result<()> {
+        self.power_down().await?;
+        delay_for(POWER_OFF_DELAY).await;
+        self.voltage_ctrl.enable_voltage().await?;
+        delay_for(POWER_ON_DELAY).await;
+        self.reset_pin.exit_reset()?;
+        delay_for(RESET_RELEASE_DELAY).await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_serial() {
+        assert_eq!(parse_serial(&[0xff; 12]), None);
+        assert_eq!(parse_serial(&[0; 12]), None);
+        assert_eq!(
+            parse_serial(&[0x12, 0x34, 0xab, 0, 0, 0, 0, 0, 0, 0, 0, 0x01]),
+            Some("1234ab000000000000000001".to_string())
+        );
+    }
+
+    #[test]
+    fn test_select_profile() {
+        let profile = |chip_bin, frequency| Profile {
+            chip_bin,
+            frequency: FrequencySettings::from_frequency(frequency),
+            voltage: power::Voltage::from_volts(8.8).expect("BUG: invalid voltage"),
+        };
+        let profiles = vec![profile(1, 650_000_000), profile(2, 600_000_000)];
+        let info = |chip_bin| Info {
+            serial: None,
+            chip_bin,
+        };
+
+        let selected = Profile::select(&profiles, &info(Some(2))).expect("no profile selected");
+        assert_eq!(selected.frequency.max(), 600_000_000);
+        assert!(Profile::select(&profiles, &info(Some(3))).is_none());
+        assert!(Profile::select(&profiles, &info(None)).is_none());
+    }
+}
@@ -0,0 +1,118 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Hashrate cap mode limiting the nominal hashrate of the whole miner
+//!
+//! The hashrate cap is split evenly between running hash chains. Each chain is clocked down to the
+//! highest frequency whose nominal hashrate fits into its share (see `frequency_limit`). The cap
+//! cannot go below the hashrate of chains running at the minimal frequency. Original frequencies
+//! are restored as soon as the cap allows it (e.g. when a chain has been stopped).
+
+use ii_logging::macros::*;
+
+use crate::bm1387;
+use crate::frequency_limit;
+use crate::Manager;
+
+use futures::lock::Mutex;
+use ii_async_compat::{futures, runtime};
+
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Owner of hash chains while their frequency is being adjusted
+const OWNER_NAME: &str = "hashrate cap";
+
+/// Period of checking the hashrate of running chains
+pub const ADJUST_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Limited frequencies are rounded down to multiples of this step (in Hz)
+const FREQUENCY_STEP: usize = 5_000_000;
+
+/// Nominal hashrate (in H/s) of `chip_count` chips running at `frequency` (in Hz)
+pub fn nominal_hashrate(frequency: usize, chip_count: usize) -> f64 {
+    frequency as f64 * chip_count as f64 * bm1387::NUM_CORES_ON_CHIP as f64
+}
+
+/// Frequency (in Hz) of all chips of a chain with `chip_count` chips for which the nominal
+/// hashrate does not exceed `hashrate` (in H/s)
+pub fn frequency_for_hashrate(chip_count: usize, hashrate: f64) -> usize {
+    if hashrate <= 0.0 || chip_count == 0 {
+        return 0;
+    }
+    let frequency = hashrate / (chip_count as f64 * bm1387::NUM_CORES_ON_CHIP as f64);
+    frequency as usize / FREQUENCY_STEP * FREQUENCY_STEP
+}
+
+pub struct Controller {
+    /// Hashrate limit of the whole miner in H/s
+    cap: f64,
+    limiter: Mutex<frequency_limit::Limiter>,
+}
+
+impl Controller {
+    pub fn new(managers: Vec<Arc<Manager>>, cap: f64) -> Self {
+        Self {
+            cap,
+            limiter: Mutex::new(frequency_limit::Limiter::new(
+                OWNER_NAME,
+                managers,
+                |_voltage, chip_count, hashrate| frequency_for_hashrate(chip_count, hashrate),
+            )),
+        }
+    }
+
+    /// Clocks running chains down or up so that they fit into the hashrate cap
+    async fn adjust(&self) {
+        self.limiter.lock().await.adjust(Some(self.cap)).await;
+    }
+
+    /// Periodically adjusts chains started in the meantime
+    pub async fn task(self: Arc<Self>) {
+        info!("Hashrate cap set to {} TH/s", self.cap / 1e12);
+        let mut interval = runtime::Interval::new(ADJUST_INTERVAL);
+        while interval.tick().await.is_some() {
+            self.adjust().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_frequency_for_hashrate() {
+        let chip_count = 63;
+        let frequency = frequency_for_hashrate(chip_count, 4.0e12);
+        assert_eq!(frequency, 555_000_000);
+        assert!(nominal_hashrate(frequency, chip_count) <= 4.0e12);
+        assert!(nominal_hashrate(frequency + FREQUENCY_STEP, chip_count) > 4.0e12);
+
+        // the nominal hashrate of the frequency fits into the cap
+        let hashrate = nominal_hashrate(650_000_000, chip_count);
+        assert_eq!(frequency_for_hashrate(chip_count, hashrate), 650_000_000);
+
+        assert_eq!(frequency_for_hashrate(chip_count, 0.0), 0);
+        assert_eq!(frequency_for_hashrate(0, 4.0e12), 0);
+    }
+}
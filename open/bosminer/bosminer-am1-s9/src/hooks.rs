@@ -20,12 +20,13 @@
 // of such proprietary license or if you have any other questions, please
 // contact us at opensource@braiins.com.
 
-use crate::halt;
 use crate::monitor;
 use crate::Manager;
 
 use bosminer::client;
 
+use ii_stop::{HaltHandle, HaltReceiver};
+
 use std::fmt::Debug;
 use std::sync::Arc;
 
@@ -39,9 +40,9 @@ pub trait Hooks: Send + Sync + Debug {
     /// * `miner_halt_sender` is a parent halt context that knows how to shutdown the whole miner
     async fn halt_created(
         &self,
-        _sender: Arc<halt::Sender>,
-        _receiver: halt::Receiver,
-        _miner_halt_sender: Arc<halt::Sender>,
+        _sender: Arc<HaltHandle>,
+        _receiver: HaltReceiver,
+        _miner_halt_sender: Arc<HaltHandle>,
     ) {
     }
 
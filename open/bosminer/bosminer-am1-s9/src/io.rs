@@ -27,14 +27,32 @@
 //!     API to wait for events (via interrupts)
 //!   * `Control` layer knows about chip configuration (number of midstates)
 //!     and implements few higher-level functions to read/write work
+//!
+//! All FIFO accesses are futures driven by UIO interrupts or by timed polling (see `FifoMode`)
+//! so no thread is ever blocked waiting for hardware.
+//!
+//! Tasks feeding chips with work and collecting their solutions use only the `WorkSink` and
+//! `SolutionSource` traits so that hash chains with a different register map or chip protocol
+//! can provide their own implementation of the work path.
+//!
+//! Work is written into the work TX FIFO by the CPU (one burst per work). With feature
+//! `s9io-dma` the work is transferred by an AXI DMA engine instead when the bitstream provides
+//! one (see `dma` module), otherwise `WorkTx` falls back to the CPU writes.
 
+mod adapter;
+#[cfg(feature = "s9io-dma")]
+mod dma;
 mod ext_work_id;
 mod uio;
+mod work_frame;
 
 use crate::error::{self, ErrorKind};
 use crate::MidstateCount;
+pub use adapter::{SolutionRxStream, WorkItem, WorkTxSink};
 use ext_work_id::ExtWorkId;
+pub use work_frame::WorkFrame;
 
+use bosminer::async_trait;
 use bosminer::work;
 use std::convert::TryInto;
 use std::fmt;
@@ -44,12 +62,13 @@ use chrono::Utc;
 use std::time::{Duration, UNIX_EPOCH};
 
 use ii_async_compat::prelude::*;
-use tokio::time::delay_for;
 
 use ii_fpga_io_am1_s9::{self, common::version::MINER_TYPE_A, generic::Variant};
 
 use ii_logging::macros::*;
 
+use serde::{Deserialize, Serialize};
+
 /// We fail the initialization unless we find this s9-io of this version
 const EXPECTED_S9IO_VERSION: Version = Version {
     miner_type: MinerType::Known(MINER_TYPE_A::ANTMINER),
@@ -59,6 +78,17 @@ const EXPECTED_S9IO_VERSION: Version = Version {
     patch: 0,
 };
 
+/// Second generation of s9-io is accepted in any minor and patch version
+#[cfg(feature = "s9io-v2")]
+const EXPECTED_S9IO_V2_MAJOR: usize = 2;
+
+/// Upper bound of `work_id` count so that the wider work ID of the second generation s9-io does
+/// not make the work registry unreasonably big
+const MAX_WORK_ID_COUNT: usize = 0x10000;
+
+/// Period of polling FIFO status when it cannot be signalled by interrupt
+const FIFO_POLL_PERIOD: Duration = Duration::from_millis(1);
+
 /// Base clock speed of the IP core running in the FPGA
 pub const F_CLK_SPEED_HZ: usize = 50_000_000;
 /// Divisor of the base clock. The resulting clock is connected to UART
@@ -118,6 +148,99 @@ impl fmt::Display for Version {
     }
 }
 
+/// Layout of s9-io registers and FIFO words which differs between generations of the bitstream.
+/// It is selected at runtime from the `VERSION` register.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegisterLayout {
+    /// The original bitstream with 16-bit `ext_work_id`
+    V1,
+    /// Second generation bitstream with 24-bit `ext_work_id` and per-midstate status registers
+    #[cfg(feature = "s9io-v2")]
+    V2,
+}
+
+impl Default for RegisterLayout {
+    fn default() -> RegisterLayout {
+        RegisterLayout::V1
+    }
+}
+
+impl RegisterLayout {
+    /// Select layout of the bitstream `version` or return `None` when this driver does not
+    /// support it
+    fn from_version(version: &Version) -> Option<Self> {
+        if *version == EXPECTED_S9IO_VERSION {
+            return Some(Self::V1);
+        }
+        #[cfg(feature = "s9io-v2")]
+        {
+            if version.miner_type == EXPECTED_S9IO_VERSION.miner_type
+                && version.model == EXPECTED_S9IO_VERSION.model
+                && version.major == EXPECTED_S9IO_V2_MAJOR
+            {
+                return Some(Self::V2);
+            }
+        }
+        None
+    }
+
+    /// Description of bitstream versions supported by this driver
+    #[cfg(not(feature = "s9io-v2"))]
+    fn expected_versions() -> String {
+        EXPECTED_S9IO_VERSION.to_string()
+    }
+
+    #[cfg(feature = "s9io-v2")]
+    fn expected_versions() -> String {
+        format!(
+            "{} or {}.x.x",
+            EXPECTED_S9IO_VERSION, EXPECTED_S9IO_V2_MAJOR
+        )
+    }
+
+    /// Width of `ext_work_id` in work FIFOs and in `WORK_TX_LAST_ID`
+    pub fn ext_work_id_bits(&self) -> u32 {
+        match self {
+            Self::V1 => ExtWorkId::EXT_WORK_ID_BITS,
+            #[cfg(feature = "s9io-v2")]
+            Self::V2 => ExtWorkId::EXT_WORK_ID_BITS_V2,
+        }
+    }
+}
+
+/// Status of one midstate as reported by the second generation s9-io
+#[cfg(feature = "s9io-v2")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MidstateStatus {
+    /// Number of nonces received for the midstate
+    pub nonces: u32,
+    /// Number of nonces which failed the check in FPGA
+    pub errors: u32,
+}
+
+/// Raw values of s9-io registers of one hashboard. FIFO data registers are left out because
+/// reading them consumes the data.
+#[derive(Serialize, Debug, Clone, PartialEq, Default)]
+pub struct RegisterDump {
+    pub version: u32,
+    pub build_id: u32,
+    pub ctrl_reg: u32,
+    pub stat_reg: u32,
+    pub baud_reg: u32,
+    pub work_time: u32,
+    pub err_counter: u32,
+    /// Available only in the second generation of s9-io
+    pub midstate_stat: Vec<u32>,
+    pub cmd_ctrl_reg: u32,
+    pub cmd_stat_reg: u32,
+    pub work_rx_ctrl_reg: u32,
+    pub work_rx_stat_reg: u32,
+    pub work_tx_ctrl_reg: u32,
+    pub work_tx_stat_reg: u32,
+    pub work_tx_irq_thr: u32,
+    pub work_tx_last_id: u32,
+}
+
 #[derive(Clone, Debug)]
 pub struct Solution {
     /// Actual nonce
@@ -130,9 +253,75 @@ pub struct Solution {
     pub hardware_id: u32,
 }
 
+/// Hardware independent interface for sending work to a hash chain
+#[async_trait]
+pub trait WorkSink: Send + Sync + 'static {
+    /// Wait until there is room for at least one work in the output queue
+    async fn wait_for_room(&self) -> error::Result<()>;
+
+    /// Send `work` which is identified by `work_id` in solutions
+    async fn send_work(&mut self, work: &work::Assignment, work_id: usize) -> error::Result<()>;
+
+    /// Drop all work waiting in the output queue which hasn't been taken by the chips yet
+    fn purge(&mut self);
+
+    /// Return upper bound for `work_id`
+    /// Determines how big the work registry has to be
+    fn work_id_count(&self) -> usize;
+}
+
+/// Hardware independent interface for receiving solutions from a hash chain
+#[async_trait]
+pub trait SolutionSource: Send + Sync + 'static {
+    /// Wait for the next solution, `Solution::hardware_id` is the `work_id` of solved work
+    async fn recv_solution(&mut self) -> error::Result<Solution>;
+}
+
+/// Strategy of waiting for FIFO events
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum FifoMode {
+    /// Wait for UIO interrupts
+    Irq,
+    /// Check FIFO status periodically, it is meant for boards where the interrupt lines are not
+    /// wired in the device tree
+    Polling,
+}
+
+impl Default for FifoMode {
+    fn default() -> FifoMode {
+        FifoMode::Irq
+    }
+}
+
+/// UIO device of one FIFO together with the strategy of waiting for its events
+struct FifoEvents {
+    uio: uio_async::UioDevice,
+    mode: FifoMode,
+}
+
+impl FifoEvents {
+    /// Wait until `cond` is satisfied
+    async fn wait_cond<T>(&self, cond: T) -> error::Result<()>
+    where
+        T: Fn() -> bool,
+    {
+        match self.mode {
+            FifoMode::Irq => self.uio.async_irq_wait_cond(cond).await?,
+            FifoMode::Polling => {
+                let mut poll = runtime::Interval::new(FIFO_POLL_PERIOD);
+                while !cond() {
+                    poll.tick().await;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
 struct WorkRxFifo {
     regs: uio_async::UioTypedMapping<ii_fpga_io_am1_s9::workrx::RegisterBlock>,
-    uio: uio_async::UioDevice,
+    events: FifoEvents,
 }
 
 impl WorkRxFifo {
@@ -141,21 +330,11 @@ impl WorkRxFifo {
         self.regs.work_rx_stat_reg.read().rx_empty().bit()
     }
 
-    /// Try to read from work rx fifo.
-    /// Performs blocking read with timeout. Uses IRQ.
-    #[allow(dead_code)]
-    #[inline]
-    pub fn read(&mut self, timeout: Option<Duration>) -> error::Result<Option<u32>> {
-        let cond = || !self.is_empty();
-        let got_irq = self.uio.irq_wait_cond(cond, timeout)?;
-        Ok(got_irq.and_then(|_| Some(self.regs.work_rx_fifo.read().bits())))
-    }
-
-    /// Try to read from work rx fifo.
-    /// Async variant. Uses IRQ.
-    pub async fn async_read(&mut self) -> error::Result<u32> {
+    /// Read from work rx fifo.
+    /// Async variant. Uses IRQ or polling.
+    pub async fn read(&mut self) -> error::Result<u32> {
         let cond = || !self.is_empty();
-        self.uio.async_irq_wait_cond(cond).await?;
+        self.events.wait_cond(cond).await?;
         Ok(self.regs.work_rx_fifo.read().bits())
     }
 
@@ -167,22 +346,22 @@ impl WorkRxFifo {
         // enable IRQ_WORK_RX interrupt
         self.regs
             .work_rx_ctrl_reg
-            .modify(|_, w| w.irq_en().set_bit());
+            .modify(|_, w| w.irq_en().enabled());
         Ok(())
     }
 
-    pub fn new(hashboard_idx: usize) -> error::Result<Self> {
+    pub fn new(hashboard_idx: usize, mode: FifoMode) -> error::Result<Self> {
         let uio = uio::Device::open(hashboard_idx, uio::Type::WorkRx)?;
         Ok(Self {
             regs: uio.map()?,
-            uio: uio.uio,
+            events: FifoEvents { uio: uio.uio, mode },
         })
     }
 }
 
 struct WorkTxFifo {
     regs: uio_async::UioTypedMapping<ii_fpga_io_am1_s9::worktx::RegisterBlock>,
-    uio: uio_async::UioDevice,
+    events: FifoEvents,
 }
 
 impl WorkTxFifo {
@@ -203,7 +382,7 @@ impl WorkTxFifo {
 
     #[inline]
     pub fn has_space_for_one_job(&self) -> bool {
-        self.regs.work_tx_stat_reg.read().irq_pend().bit()
+        self.regs.work_tx_stat_reg.read().irq_pend().is_pending()
     }
 
     /// Return the value of last work ID send to ASICs
@@ -213,22 +392,35 @@ impl WorkTxFifo {
         self.regs.work_tx_last_id.read().bits()
     }
 
-    /// Try to write work item to work TX FIFO.
-    /// Performs blocking write without timeout. Uses IRQ.
-    /// The idea is that you don't call this function until you are sure you
-    /// can fit in all the entries you want.
-    #[inline]
-    pub fn write(&mut self, item: u32) -> error::Result<()> {
-        let cond = || !self.is_full();
-        self.uio.irq_wait_cond(cond, None)?;
-        self.regs.work_tx_fifo.write(|w| unsafe { w.bits(item) });
+    /// Drop all words waiting in work TX FIFO
+    pub fn reset(&mut self) {
+        self.regs
+            .work_tx_ctrl_reg
+            .modify(|_, w| w.rst_tx_fifo().set_bit());
+    }
+
+    /// Write the whole work `frame` into work TX FIFO.
+    /// Async variant. Uses IRQ or polling.
+    /// FIFO status is checked only once for the whole frame (and not before each word) because
+    /// there is always room for the biggest work when `wait_for_room` is satisfied.
+    pub async fn write_burst(&mut self, frame: &[u32]) -> error::Result<()> {
+        assert!(
+            frame.len() <= Self::BIGGEST_WORK as usize,
+            "BUG: work frame of {} words does not fit into work TX FIFO",
+            frame.len()
+        );
+        self.wait_for_room().await?;
+        for word in frame.iter() {
+            self.regs.work_tx_fifo.write(|w| unsafe { w.bits(*word) });
+        }
         Ok(())
     }
 
     /// Wait for output FIFO to make room for one work
-    pub async fn async_wait_for_room(&self) -> error::Result<()> {
+    /// Async variant. Uses IRQ or polling.
+    pub async fn wait_for_room(&self) -> error::Result<()> {
         let cond = || self.has_space_for_one_job();
-        self.uio.async_irq_wait_cond(cond).await?;
+        self.events.wait_cond(cond).await?;
         Ok(())
     }
 
@@ -239,21 +431,19 @@ impl WorkTxFifo {
             .work_tx_irq_thr
             .write(|w| unsafe { w.bits(Self::FIFO_THRESHOLD) });
         // reset output FIFO
-        self.regs
-            .work_tx_ctrl_reg
-            .modify(|_, w| w.rst_tx_fifo().set_bit());
+        self.reset();
         // enable IRQ_WORK_TX interrupt
         self.regs
             .work_tx_ctrl_reg
-            .modify(|_, w| w.irq_en().set_bit());
+            .modify(|_, w| w.irq_en().enabled());
         Ok(())
     }
 
-    pub fn new(hashboard_idx: usize) -> error::Result<Self> {
+    pub fn new(hashboard_idx: usize, mode: FifoMode) -> error::Result<Self> {
         let uio = uio::Device::open(hashboard_idx, uio::Type::WorkTx)?;
         Ok(Self {
             regs: uio.map()?,
-            uio: uio.uio,
+            events: FifoEvents { uio: uio.uio, mode },
         })
     }
 }
@@ -264,7 +454,7 @@ impl WorkTxFifo {
 /// TODO: Split this FIFO into two FIFOs.
 pub struct CommandRxTxFifos {
     regs: uio_async::UioTypedMapping<ii_fpga_io_am1_s9::command::RegisterBlock>,
-    uio: uio_async::UioDevice,
+    events: FifoEvents,
 }
 
 impl CommandRxTxFifos {
@@ -291,8 +481,9 @@ impl CommandRxTxFifos {
     /// Wait for command FIFO to become empty
     /// Uses timed polling
     pub async fn wait_tx_empty(&self) {
+        let mut poll = runtime::Interval::new(FIFO_POLL_PERIOD);
         while !self.is_tx_empty() {
-            delay_for(Duration::from_millis(1)).await;
+            poll.tick().await;
         }
     }
 
@@ -300,23 +491,24 @@ impl CommandRxTxFifos {
     /// Uses timed polling
     pub async fn write(&self, item: u32) {
         // wait for space in queue
+        let mut poll = runtime::Interval::new(FIFO_POLL_PERIOD);
         while self.is_tx_full() {
-            delay_for(Duration::from_millis(1)).await;
+            poll.tick().await;
         }
         // write command word
         self.regs.cmd_tx_fifo.write(|w| unsafe { w.bits(item) });
     }
 
     /// Read command from cmd rx fifo
-    /// Async variant. Uses IRQ.
+    /// Async variant. Uses IRQ or polling.
     pub async fn read(&mut self) -> error::Result<u32> {
         let cond = || !self.is_rx_empty();
-        self.uio.async_irq_wait_cond(cond).await?;
+        self.events.wait_cond(cond).await?;
         Ok(self.regs.cmd_rx_fifo.read().bits())
     }
 
     /// Read command from cmd rx fifo with timeout
-    /// Async variant. Uses IRQ.
+    /// Async variant. Uses IRQ or polling.
     /// Returns:
     ///     * `Ok(None)` on timeout
     ///     * `Ok(Some(_))` if something was received
@@ -343,15 +535,15 @@ impl CommandRxTxFifos {
             .cmd_ctrl_reg
             .modify(|_, w| w.rst_rx_fifo().set_bit().rst_tx_fifo().set_bit());
         // enable IRQ_CMD_RX interrupt
-        self.regs.cmd_ctrl_reg.modify(|_, w| w.irq_en().set_bit());
+        self.regs.cmd_ctrl_reg.modify(|_, w| w.irq_en().enabled());
         Ok(())
     }
 
-    pub fn new(hashboard_idx: usize) -> error::Result<Self> {
+    pub fn new(hashboard_idx: usize, mode: FifoMode) -> error::Result<Self> {
         let uio = uio::Device::open(hashboard_idx, uio::Type::Command)?;
         Ok(Self {
             regs: uio.map()?,
-            uio: uio.uio,
+            events: FifoEvents { uio: uio.uio, mode },
         })
     }
 }
@@ -368,12 +560,22 @@ struct WorkRxResponse {
 
 impl WorkRxResponse {
     /// Parse from FPGA response
-    /// The format is dependent on current `MidstateCount` settings
-    pub fn from_hw(midstate_count: MidstateCount, word1: u32, word2: u32) -> Self {
-        // NOTE: there's a CRC field in word2 that we ignore, because it's checked by FPGA core
-        let solution_idx = word2 & 0xff;
-        let ext_work_id = (word2 >> 8) & 0xffff;
-        let ext_work_id = ExtWorkId::from_hw(midstate_count, ext_work_id);
+    /// The format is dependent on current `MidstateCount` settings and on the register layout
+    pub fn from_hw(
+        layout: RegisterLayout,
+        midstate_count: MidstateCount,
+        word1: u32,
+        word2: u32,
+    ) -> Self {
+        let (solution_idx, ext_work_id) = match layout {
+            // NOTE: there's a CRC field in word2 that we ignore, because it's checked by FPGA core
+            RegisterLayout::V1 => (word2 & 0xff, (word2 >> 8) & 0xffff),
+            // The CRC field is not passed by the second generation at all
+            #[cfg(feature = "s9io-v2")]
+            RegisterLayout::V2 => (word2 >> 24, word2 & 0xff_ffff),
+        };
+        let ext_work_id =
+            ExtWorkId::from_hw(layout.ext_work_id_bits(), midstate_count, ext_work_id);
         Self {
             nonce: word1,
             solution_idx: solution_idx as usize,
@@ -386,32 +588,39 @@ impl WorkRxResponse {
 pub struct WorkRx {
     fifo: WorkRxFifo,
     midstate_count: MidstateCount,
+    layout: RegisterLayout,
 }
 
-impl WorkRx {
-    pub async fn recv_solution(mut self) -> Result<(Self, Solution), failure::Error> {
-        let word1 = self.fifo.async_read().await?;
-        let word2 = self.fifo.async_read().await?;
-        let resp = WorkRxResponse::from_hw(self.midstate_count, word1, word2);
+#[async_trait]
+impl SolutionSource for WorkRx {
+    async fn recv_solution(&mut self) -> error::Result<Solution> {
+        let word1 = self.fifo.read().await?;
+        let word2 = self.fifo.read().await?;
+        let resp = WorkRxResponse::from_hw(self.layout, self.midstate_count, word1, word2);
 
-        let solution = Solution {
+        Ok(Solution {
             nonce: resp.nonce,
             midstate_idx: resp.midstate_idx,
             solution_idx: resp.solution_idx,
             hardware_id: resp.work_id as u32,
-        };
-
-        Ok((self, solution))
+        })
     }
+}
 
+impl WorkRx {
     fn init(&mut self) -> error::Result<()> {
         self.fifo.init()
     }
 
-    fn new(hashboard_idx: usize, midstate_count: MidstateCount) -> error::Result<Self> {
+    fn new(
+        hashboard_idx: usize,
+        midstate_count: MidstateCount,
+        fifo_mode: FifoMode,
+    ) -> error::Result<Self> {
         Ok(Self {
-            fifo: WorkRxFifo::new(hashboard_idx)?,
+            fifo: WorkRxFifo::new(hashboard_idx, fifo_mode)?,
             midstate_count,
+            layout: Default::default(),
         })
     }
 }
@@ -419,13 +628,56 @@ impl WorkRx {
 pub struct WorkTx {
     fifo: WorkTxFifo,
     midstate_count: MidstateCount,
+    layout: RegisterLayout,
+    /// Buffer for work which is burst into the FIFO
+    frame: WorkFrame,
+    /// DMA engine feeding the FIFO, work is written by the CPU when it is missing
+    #[cfg(feature = "s9io-dma")]
+    dma: Option<dma::Engine>,
 }
 
-impl WorkTx {
-    pub async fn wait_for_room(&self) -> error::Result<()> {
-        self.fifo.async_wait_for_room().await
+#[async_trait]
+impl WorkSink for WorkTx {
+    async fn wait_for_room(&self) -> error::Result<()> {
+        self.fifo.wait_for_room().await
     }
 
+    async fn send_work(&mut self, work: &work::Assignment, work_id: usize) -> error::Result<()> {
+        self.assert_midstate_count(work.midstates.len());
+        let ext_work_id =
+            ExtWorkId::new(work_id, 0).to_hw(self.layout.ext_work_id_bits(), self.midstate_count);
+
+        self.frame.assemble(work, ext_work_id);
+        #[cfg(feature = "s9io-dma")]
+        {
+            if let Some(dma) = self.dma.as_mut() {
+                self.fifo.wait_for_room().await?;
+                return dma.transfer(self.frame.words()).await;
+            }
+        }
+        self.fifo.write_burst(self.frame.words()).await
+    }
+
+    fn purge(&mut self) {
+        #[cfg(feature = "s9io-dma")]
+        {
+            if let Some(dma) = self.dma.as_mut() {
+                // the engine must not push the rest of a stale transfer after the reset
+                if let Err(e) = dma.init() {
+                    error!("Cannot abort DMA transfer of work: {}", e);
+                }
+            }
+        }
+        self.fifo.reset();
+    }
+
+    fn work_id_count(&self) -> usize {
+        ExtWorkId::get_work_id_count(self.layout.ext_work_id_bits(), self.midstate_count)
+            .min(MAX_WORK_ID_COUNT)
+    }
+}
+
+impl WorkTx {
     pub fn assert_midstate_count(&self, expected_midstate_count: usize) {
         assert_eq!(
             expected_midstate_count,
@@ -436,42 +688,39 @@ impl WorkTx {
         );
     }
 
-    pub fn send_work(
-        &mut self,
-        work: &work::Assignment,
-        work_id: usize,
-    ) -> Result<(), failure::Error> {
-        self.assert_midstate_count(work.midstates.len());
-        let ext_work_id = ExtWorkId::new(work_id, 0);
-
-        self.fifo
-            .write(ext_work_id.to_hw(self.midstate_count).to_le())?;
-        self.fifo.write(work.bits().to_le())?;
-        self.fifo.write(work.ntime.to_le())?;
-        self.fifo.write(work.merkle_root_tail().to_le())?;
-
-        for mid in work.midstates.iter() {
-            for midstate_word in mid.state.words::<u32>().rev() {
-                self.fifo.write(midstate_word.to_be())?;
+    fn init(&mut self) -> error::Result<()> {
+        #[cfg(feature = "s9io-dma")]
+        {
+            if let Some(dma) = self.dma.as_mut() {
+                dma.init()?;
             }
         }
-        Ok(())
-    }
-
-    /// Return upper bound for `work_id`
-    /// Determines how big the work registry has to be
-    pub fn work_id_count(&self) -> usize {
-        ExtWorkId::get_work_id_count(self.midstate_count)
-    }
-
-    fn init(&mut self) -> error::Result<()> {
         self.fifo.init()
     }
 
-    fn new(hashboard_idx: usize, midstate_count: MidstateCount) -> error::Result<Self> {
+    fn new(
+        hashboard_idx: usize,
+        midstate_count: MidstateCount,
+        fifo_mode: FifoMode,
+    ) -> error::Result<Self> {
+        #[cfg(feature = "s9io-dma")]
+        let dma = {
+            let dma = dma::Engine::open(hashboard_idx, fifo_mode)?;
+            if dma.is_none() {
+                info!(
+                    "Hashboard {}: bitstream has no work TX DMA engine, falling back to CPU writes",
+                    hashboard_idx
+                );
+            }
+            dma
+        };
         Ok(Self {
-            fifo: WorkTxFifo::new(hashboard_idx)?,
+            fifo: WorkTxFifo::new(hashboard_idx, fifo_mode)?,
             midstate_count,
+            layout: Default::default(),
+            frame: WorkFrame::with_capacity(WorkTxFifo::BIGGEST_WORK as usize),
+            #[cfg(feature = "s9io-dma")]
+            dma,
         })
     }
 }
@@ -512,9 +761,12 @@ impl CommandRxTx {
     /// - A timeout when reading the first word is converted into an empty response.
     ///   The method propagates any error other than timeout
     /// - An error that occurs during reading the second word from the FIFO is propagated.
-    pub async fn recv_response(&mut self, timeout: Duration) -> error::Result<Option<Vec<u8>>> {
+    pub async fn recv_response(
+        &mut self,
+        timeout: Duration,
+    ) -> error::Result<Option<ii_bufpool::Buf>> {
         // assembled response
-        let mut cmd_resp = Vec::new();
+        let mut cmd_resp = ii_bufpool::command_pool().get();
 
         // fetch first word of command response from IP core's fifo
         match self.fifo.read_with_timeout(timeout).await? {
@@ -541,9 +793,9 @@ impl CommandRxTx {
         self.fifo.init()
     }
 
-    fn new(hashboard_idx: usize) -> error::Result<Self> {
+    fn new(hashboard_idx: usize, fifo_mode: FifoMode) -> error::Result<Self> {
         Ok(Self {
-            fifo: CommandRxTxFifos::new(hashboard_idx)?,
+            fifo: CommandRxTxFifos::new(hashboard_idx, fifo_mode)?,
             hashboard_idx,
         })
     }
@@ -555,6 +807,8 @@ pub struct Common {
     regs: uio_async::UioTypedMapping<ii_fpga_io_am1_s9::common::RegisterBlock>,
     /// Current midstate configuration
     midstate_count: MidstateCount,
+    /// Register layout determined from the bitstream version
+    layout: RegisterLayout,
     /// With which hashboard is this register block associated?
     /// This is required to print meaningful error messages.
     hashboard_idx: usize,
@@ -586,12 +840,12 @@ impl Common {
 
     #[inline]
     pub fn enable_ip_core(&self) {
-        self.regs.ctrl_reg.modify(|_, w| w.enable().bit(true));
+        self.regs.ctrl_reg.modify(|_, w| w.enable().enabled());
     }
 
     #[inline]
     pub fn disable_ip_core(&self) {
-        self.regs.ctrl_reg.modify(|_, w| w.enable().bit(false));
+        self.regs.ctrl_reg.modify(|_, w| w.enable().disabled());
     }
 
     #[inline]
@@ -619,7 +873,75 @@ impl Common {
             .modify(|_, w| w.midstate_cnt().variant(value));
     }
 
-    fn check_version(&mut self) -> error::Result<()> {
+    /// Number of responses dropped by the IP core due to CRC mismatch. The counter wraps around
+    /// and it is cleared only by reset of the IP core.
+    #[inline]
+    pub fn get_crc_error_count(&self) -> u32 {
+        self.regs.err_counter.read().bits()
+    }
+
+    pub fn layout(&self) -> RegisterLayout {
+        self.layout
+    }
+
+    /// Return status of midstate `midstate_idx`. It is available only in the second generation
+    /// of s9-io.
+    #[cfg(feature = "s9io-v2")]
+    pub fn get_midstate_status(&self, midstate_idx: usize) -> Option<MidstateStatus> {
+        match self.layout {
+            RegisterLayout::V1 => None,
+            RegisterLayout::V2 => self.regs.midstate_stat.get(midstate_idx).map(|reg| {
+                let stat = reg.read();
+                MidstateStatus {
+                    nonces: stat.nonce_cnt().bits(),
+                    errors: stat.err_cnt().bits() as u32,
+                }
+            }),
+        }
+    }
+
+    /// Read back all registers of the IP core except for FIFO data. The FIFO register blocks
+    /// are mapped once more because their owners are moved to the tasks driving the chain.
+    pub fn dump_registers(&self) -> error::Result<RegisterDump> {
+        let command: uio_async::UioTypedMapping<ii_fpga_io_am1_s9::command::RegisterBlock> =
+            uio::Device::open(self.hashboard_idx, uio::Type::Command)?.map()?;
+        let work_rx: uio_async::UioTypedMapping<ii_fpga_io_am1_s9::workrx::RegisterBlock> =
+            uio::Device::open(self.hashboard_idx, uio::Type::WorkRx)?.map()?;
+        let work_tx: uio_async::UioTypedMapping<ii_fpga_io_am1_s9::worktx::RegisterBlock> =
+            uio::Device::open(self.hashboard_idx, uio::Type::WorkTx)?.map()?;
+
+        let midstate_stat = match self.layout {
+            RegisterLayout::V1 => vec![],
+            #[cfg(feature = "s9io-v2")]
+            RegisterLayout::V2 => self
+                .regs
+                .midstate_stat
+                .iter()
+                .map(|reg| reg.read().bits())
+                .collect(),
+        };
+        Ok(RegisterDump {
+            version: self.regs.version.read().bits(),
+            build_id: self.regs.build_id.read().bits(),
+            ctrl_reg: self.regs.ctrl_reg.read().bits(),
+            stat_reg: self.regs.stat_reg.read().bits(),
+            baud_reg: self.regs.baud_reg.read().bits(),
+            work_time: self.regs.work_time.read().bits(),
+            err_counter: self.regs.err_counter.read().bits(),
+            midstate_stat,
+            cmd_ctrl_reg: command.cmd_ctrl_reg.read().bits(),
+            cmd_stat_reg: command.cmd_stat_reg.read().bits(),
+            work_rx_ctrl_reg: work_rx.work_rx_ctrl_reg.read().bits(),
+            work_rx_stat_reg: work_rx.work_rx_stat_reg.read().bits(),
+            work_tx_ctrl_reg: work_tx.work_tx_ctrl_reg.read().bits(),
+            work_tx_stat_reg: work_tx.work_tx_stat_reg.read().bits(),
+            work_tx_irq_thr: work_tx.work_tx_irq_thr.read().bits(),
+            work_tx_last_id: work_tx.work_tx_last_id.read().bits(),
+        })
+    }
+
+    /// Check the bitstream version and select the register layout according to it
+    fn check_version(&mut self) -> error::Result<RegisterLayout> {
         let version = self.get_version();
         let build_id = self.get_build_id();
 
@@ -637,28 +959,27 @@ impl Common {
             self.hashboard_idx, version, build_id
         );
 
-        // check it's the exact version
-        if version != EXPECTED_S9IO_VERSION {
-            Err(ErrorKind::UnexpectedVersion(
+        // check it's a supported version
+        self.layout = RegisterLayout::from_version(&version).ok_or_else(|| {
+            ErrorKind::UnexpectedVersion(
                 "s9-io bitstream".to_string(),
                 version.to_string(),
-                EXPECTED_S9IO_VERSION.to_string(),
-            ))?
-        }
-        Ok(())
+                RegisterLayout::expected_versions(),
+            )
+        })?;
+        Ok(self.layout)
     }
 
     pub fn set_midstate_count(&self) {
         self.set_ip_core_midstate_count(self.midstate_count.to_reg());
     }
 
-    fn init(&mut self) -> error::Result<()> {
+    fn init(&mut self) -> error::Result<RegisterLayout> {
         // reset ip core
         self.disable_ip_core();
         self.enable_ip_core();
         // check version
-        self.check_version()?;
-        Ok(())
+        self.check_version()
     }
 
     fn new(hashboard_idx: usize, midstate_count: MidstateCount) -> error::Result<Self> {
@@ -666,11 +987,37 @@ impl Common {
         Ok(Self {
             regs: uio.map()?,
             midstate_count,
+            layout: Default::default(),
             hashboard_idx,
         })
     }
 }
 
+/// Returns sorted indices of hash chains whose FPGA IP cores are available (described in the
+/// device tree). It does not tell whether a hashboard is plugged in the connector.
+pub fn detect_chains() -> error::Result<Vec<usize>> {
+    uio::defined_chains()
+}
+
+/// Check that all UIO devices of the hash chain can be opened so that a chain missing in the
+/// device tree is reported before it is started
+pub async fn check_chain_devices(hashboard_idx: usize) -> error::Result<()> {
+    uio::check_devices(hashboard_idx).await
+}
+
+/// Verify that FPGA has been loaded with s9-io bitstream compatible with this driver. It is meant
+/// to be called at startup so that a mismatched FPGA image is reported before any hash chain is
+/// initialized. The IP core is not reset by the check.
+pub fn check_bitstream(hashboard_idx: usize) -> error::Result<RegisterLayout> {
+    let mut common_io = Common::new(hashboard_idx, MidstateCount::new(1)).map_err(|e| {
+        ErrorKind::Hashboard(
+            hashboard_idx,
+            format!("no s9-io bitstream loaded in FPGA ({})", e),
+        )
+    })?;
+    common_io.check_version()
+}
+
 /// Represents the whole IP core
 pub struct Core {
     common_io: Common,
@@ -681,20 +1028,28 @@ pub struct Core {
 
 impl Core {
     /// Build a new IP core
-    pub fn new(hashboard_idx: usize, midstate_count: MidstateCount) -> error::Result<Self> {
+    ///
+    /// * `fifo_mode` - how to wait for events of all FIFOs
+    pub fn new(
+        hashboard_idx: usize,
+        midstate_count: MidstateCount,
+        fifo_mode: FifoMode,
+    ) -> error::Result<Self> {
         Ok(Self {
             common_io: Common::new(hashboard_idx, midstate_count)?,
-            command_io: CommandRxTx::new(hashboard_idx)?,
-            work_rx_io: WorkRx::new(hashboard_idx, midstate_count)?,
-            work_tx_io: WorkTx::new(hashboard_idx, midstate_count)?,
+            command_io: CommandRxTx::new(hashboard_idx, fifo_mode)?,
+            work_rx_io: WorkRx::new(hashboard_idx, midstate_count, fifo_mode)?,
+            work_tx_io: WorkTx::new(hashboard_idx, midstate_count, fifo_mode)?,
         })
     }
 
     /// Initialize the IP core and split it into components
     /// That way it's not possible to access un-initialized IO blocks
     pub fn init_and_split(mut self) -> error::Result<(Common, CommandRxTx, WorkRx, WorkTx)> {
-        // common_io has to go first to reset the IP core
-        self.common_io.init()?;
+        // common_io has to go first to reset the IP core and to determine the register layout
+        let layout = self.common_io.init()?;
+        self.work_rx_io.layout = layout;
+        self.work_tx_io.layout = layout;
 
         // Initialize fifos
         self.command_io.init()?;
@@ -719,9 +1074,11 @@ mod test {
     /// Test that we are able to construct HChainFifo instance
     #[test]
     fn test_fifo_initialization() {
-        let core =
-            Core::new(TEST_CHAIN_INDEX, MidstateCount::new(1)).expect("fifo construction failed");
-        core.init_and_split().expect("fifo initialization failed");
+        for fifo_mode in [FifoMode::Irq, FifoMode::Polling].iter() {
+            let core = Core::new(TEST_CHAIN_INDEX, MidstateCount::new(1), *fifo_mode)
+                .expect("fifo construction failed");
+            core.init_and_split().expect("fifo initialization failed");
+        }
     }
     /// This test verifies correct parsing of mining work solution for all multi-midstate
     /// configurations.
@@ -766,7 +1123,12 @@ mod test {
                 expected_solution_data.midstate_idx,
                 expected_solution_data.solution_idx,
             );
-            let resp = WorkRxResponse::from_hw(expected_solution_data.midstate_count, word1, word2);
+            let resp = WorkRxResponse::from_hw(
+                RegisterLayout::V1,
+                expected_solution_data.midstate_count,
+                word1,
+                word2,
+            );
 
             assert_eq!(resp.nonce, word1);
             assert_eq!(
@@ -787,6 +1149,75 @@ mod test {
         }
     }
 
+    #[cfg(feature = "s9io-v2")]
+    #[test]
+    fn test_work_rx_response_v2() {
+        let resp = WorkRxResponse::from_hw(
+            RegisterLayout::V2,
+            MidstateCount::new(4),
+            0xdead0666,
+            0x02123457,
+        );
+        assert_eq!(resp.nonce, 0xdead0666);
+        assert_eq!(resp.work_id, 0x48d15);
+        assert_eq!(resp.midstate_idx, 3);
+        assert_eq!(resp.solution_idx, 2);
+    }
+
+    #[test]
+    fn test_work_frame() {
+        let mut work = crate::null_work::prepare(0x0102);
+        work.ntime = 0x11223344;
+        let mut frame = WorkFrame::with_capacity(1);
+        frame.assemble(&work, 0xdead_beef);
+        frame.assemble(&work, 0x1234);
+        assert_eq!(
+            frame.words(),
+            &[
+                0x1234,
+                work.bits(),
+                0x11223344,
+                work.merkle_root_tail(),
+                0,
+                0,
+                0,
+                0,
+                0,
+                0,
+                0,
+                0x0201_0000
+            ]
+        );
+
+        // frames grow with the number of midstates
+        let work = crate::null_work::prepare_opencore(true, 4);
+        frame.assemble(&work, 0);
+        assert_eq!(frame.len(), 4 + 4 * 8);
+    }
+
+    #[test]
+    fn test_register_layout() {
+        let mut version = EXPECTED_S9IO_VERSION;
+        assert_eq!(
+            RegisterLayout::from_version(&version),
+            Some(RegisterLayout::V1)
+        );
+        assert_eq!(RegisterLayout::V1.ext_work_id_bits(), 16);
+
+        version.major = 2;
+        version.minor = 1;
+        #[cfg(not(feature = "s9io-v2"))]
+        assert_eq!(RegisterLayout::from_version(&version), None);
+        #[cfg(feature = "s9io-v2")]
+        assert_eq!(
+            RegisterLayout::from_version(&version),
+            Some(RegisterLayout::V2)
+        );
+
+        version.model = 17;
+        assert_eq!(RegisterLayout::from_version(&version), None);
+    }
+
     #[test]
     fn test_version_display() {
         let version = Version {
@@ -0,0 +1,359 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Adapters of the FIFO layer to futures `Sink` and `Stream`
+//!
+//! `WorkTxSink` accepts work identified by its `work_id` and it is ready only when there is room
+//! for the work in the TX FIFO so it applies backpressure to whoever feeds it. `SolutionRxStream`
+//! yields solutions as they are read out of the RX FIFO. Both of them can be composed with
+//! futures combinators, e.g. solutions of several chains can be selected into a single stream.
+//!
+//! The inner `WorkSink`/`SolutionSource` is moved into the pending operation while it is being
+//! polled and it is returned back once the operation finishes.
+
+use super::{Solution, SolutionSource, WorkSink};
+use crate::error;
+
+use bosminer::work;
+
+use ii_async_compat::futures;
+
+use futures::future::BoxFuture;
+use futures::prelude::*;
+use futures::task::{Context, Poll};
+
+use std::pin::Pin;
+
+/// Work together with its `work_id` reported in solutions
+pub type WorkItem = (work::Assignment, usize);
+
+enum TxState<T> {
+    /// No room has been reserved for the next work
+    Idle(T),
+    /// Waiting for room in the TX FIFO
+    Waiting(BoxFuture<'static, (T, error::Result<()>)>),
+    /// There is room for the next work
+    Ready(T),
+    /// The work is being written into the TX FIFO
+    Sending(BoxFuture<'static, (T, error::Result<()>)>),
+    /// The inner sink has been lost due to panic in a pending operation
+    Invalid,
+}
+
+/// Futures `Sink` of work for a hash chain
+pub struct WorkTxSink<T> {
+    state: TxState<T>,
+}
+
+// The inner sink is never pinned, it is moved in and out of the boxed futures
+impl<T> Unpin for WorkTxSink<T> {}
+
+impl<T: WorkSink> WorkTxSink<T> {
+    pub fn new(inner: T) -> Self {
+        Self {
+            state: TxState::Idle(inner),
+        }
+    }
+
+    /// Returns the inner sink when there is no pending operation
+    pub fn into_inner(self) -> Option<T> {
+        match self.state {
+            TxState::Idle(inner) | TxState::Ready(inner) => Some(inner),
+            _ => None,
+        }
+    }
+
+    /// Waits until there is room for the next work, it is the same as polling `Sink::poll_ready`
+    pub async fn wait_for_room(&mut self) -> error::Result<()> {
+        future::poll_fn(|cx| Pin::new(&mut *self).poll_ready(cx)).await
+    }
+
+    /// Drops all work waiting in the TX FIFO (see `WorkSink::purge`), work which is being
+    /// written at the moment is not affected
+    pub fn purge(&mut self) {
+        match &mut self.state {
+            TxState::Idle(inner) | TxState::Ready(inner) => inner.purge(),
+            _ => {}
+        }
+    }
+
+    /// Drives the pending write of work to its completion
+    fn poll_sending(&mut self, cx: &mut Context<'_>) -> Poll<error::Result<()>> {
+        if let TxState::Sending(future) = &mut self.state {
+            let (inner, result) = futures::ready!(future.as_mut().poll(cx));
+            self.state = TxState::Idle(inner);
+            result?;
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T: WorkSink> Sink<WorkItem> for WorkTxSink<T> {
+    type Error = error::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<error::Result<()>> {
+        let this = self.get_mut();
+        futures::ready!(this.poll_sending(cx))?;
+        loop {
+            match std::mem::replace(&mut this.state, TxState::Invalid) {
+                TxState::Idle(inner) => {
+                    this.state = TxState::Waiting(
+                        async move {
+                            let result = inner.wait_for_room().await;
+                            (inner, result)
+                        }
+                        .boxed(),
+                    );
+                }
+                TxState::Waiting(mut future) => match future.as_mut().poll(cx) {
+                    Poll::Pending => {
+                        this.state = TxState::Waiting(future);
+                        return Poll::Pending;
+                    }
+                    Poll::Ready((inner, result)) => {
+                        this.state = match result {
+                            Ok(()) => TxState::Ready(inner),
+                            Err(_) => TxState::Idle(inner),
+                        };
+                        return Poll::Ready(result);
+                    }
+                },
+                TxState::Ready(inner) => {
+                    this.state = TxState::Ready(inner);
+                    return Poll::Ready(Ok(()));
+                }
+                TxState::Sending(_) => unreachable!("pending work has been sent"),
+                TxState::Invalid => panic!("BUG: work TX sink polled after a failure"),
+            }
+        }
+    }
+
+    fn start_send(self: Pin<&mut Self>, (work, work_id): WorkItem) -> error::Result<()> {
+        let this = self.get_mut();
+        match std::mem::replace(&mut this.state, TxState::Invalid) {
+            TxState::Ready(mut inner) => {
+                this.state = TxState::Sending(
+                    async move {
+                        let result = inner.send_work(&work, work_id).await;
+                        (inner, result)
+                    }
+                    .boxed(),
+                );
+                Ok(())
+            }
+            _ => panic!("BUG: work sent without waiting for room in TX FIFO"),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<error::Result<()>> {
+        self.get_mut().poll_sending(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<error::Result<()>> {
+        self.get_mut().poll_sending(cx)
+    }
+}
+
+enum RxState<R> {
+    Idle(R),
+    Receiving(BoxFuture<'static, (R, error::Result<Solution>)>),
+    /// The inner source has been lost due to panic in a pending operation
+    Invalid,
+}
+
+/// Futures `Stream` of solutions from a hash chain. The stream never ends, FIFO errors are
+/// yielded as items.
+pub struct SolutionRxStream<R> {
+    state: RxState<R>,
+}
+
+// The inner source is never pinned, it is moved in and out of the boxed future
+impl<R> Unpin for SolutionRxStream<R> {}
+
+impl<R: SolutionSource> SolutionRxStream<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            state: RxState::Idle(inner),
+        }
+    }
+}
+
+impl<R: SolutionSource> Stream for SolutionRxStream<R> {
+    type Item = error::Result<Solution>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            match std::mem::replace(&mut this.state, RxState::Invalid) {
+                RxState::Idle(mut inner) => {
+                    this.state = RxState::Receiving(
+                        async move {
+                            let result = inner.recv_solution().await;
+                            (inner, result)
+                        }
+                        .boxed(),
+                    );
+                }
+                RxState::Receiving(mut future) => match future.as_mut().poll(cx) {
+                    Poll::Pending => {
+                        this.state = RxState::Receiving(future);
+                        return Poll::Pending;
+                    }
+                    Poll::Ready((inner, result)) => {
+                        this.state = RxState::Idle(inner);
+                        return Poll::Ready(Some(result));
+                    }
+                },
+                RxState::Invalid => panic!("BUG: solution RX stream polled after a failure"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::null_work;
+
+    use bosminer::async_trait;
+
+    use ii_async_compat::tokio;
+    use tokio::sync::{mpsc, Mutex};
+
+    use std::sync::{Arc, Mutex as StdMutex};
+
+    /// Sink with room for one work for each token received from `room`
+    struct TestSink {
+        room: Mutex<mpsc::UnboundedReceiver<()>>,
+        sent: Arc<StdMutex<Vec<usize>>>,
+    }
+
+    #[async_trait]
+    impl WorkSink for TestSink {
+        async fn wait_for_room(&self) -> error::Result<()> {
+            self.room.lock().await.recv().await;
+            Ok(())
+        }
+
+        async fn send_work(
+            &mut self,
+            _work: &work::Assignment,
+            work_id: usize,
+        ) -> error::Result<()> {
+            self.sent.lock().expect("BUG: lock poisoned").push(work_id);
+            Ok(())
+        }
+
+        fn purge(&mut self) {
+            self.sent.lock().expect("BUG: lock poisoned").clear();
+        }
+
+        fn work_id_count(&self) -> usize {
+            16
+        }
+    }
+
+    struct TestSource {
+        solutions: mpsc::UnboundedReceiver<u32>,
+    }
+
+    #[async_trait]
+    impl SolutionSource for TestSource {
+        async fn recv_solution(&mut self) -> error::Result<Solution> {
+            let nonce = self.solutions.recv().await.expect("BUG: no more solutions");
+            Ok(Solution {
+                nonce,
+                midstate_idx: 0,
+                solution_idx: 0,
+                hardware_id: nonce & 0xf,
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_work_backpressure() {
+        let (room_sender, room) = mpsc::unbounded_channel();
+        let sent = Arc::new(StdMutex::new(Vec::new()));
+        let mut sink = WorkTxSink::new(TestSink {
+            room: Mutex::new(room),
+            sent: sent.clone(),
+        });
+
+        let work = null_work::prepare(0);
+        room_sender.send(()).unwrap();
+        room_sender.send(()).unwrap();
+        sink.send((work.clone(), 1)).await.unwrap();
+        sink.send((work.clone(), 2)).await.unwrap();
+        assert_eq!(*sent.lock().unwrap(), vec![1, 2]);
+
+        // there is no room for the third work
+        let send = sink.send((work.clone(), 3));
+        futures::pin_mut!(send);
+        assert!(futures::poll!(send.as_mut()).is_pending());
+        assert_eq!(*sent.lock().unwrap(), vec![1, 2]);
+
+        room_sender.send(()).unwrap();
+        send.await.unwrap();
+        assert_eq!(*sent.lock().unwrap(), vec![1, 2, 3]);
+        assert!(sink.into_inner().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_work_purge() {
+        let (room_sender, room) = mpsc::unbounded_channel();
+        let sent = Arc::new(StdMutex::new(Vec::new()));
+        let mut sink = WorkTxSink::new(TestSink {
+            room: Mutex::new(room),
+            sent: sent.clone(),
+        });
+
+        room_sender.send(()).unwrap();
+        sink.send((null_work::prepare(0), 1)).await.unwrap();
+        room_sender.send(()).unwrap();
+        sink.wait_for_room().await.unwrap();
+        // work is purged while the sink is ready and it still accepts the next work
+        sink.purge();
+        assert!(sent.lock().unwrap().is_empty());
+        sink.send((null_work::prepare(1), 2)).await.unwrap();
+        assert_eq!(*sent.lock().unwrap(), vec![2]);
+    }
+
+    #[tokio::test]
+    async fn test_solution_stream() {
+        let (solution_sender, solutions) = mpsc::unbounded_channel();
+        let mut stream = SolutionRxStream::new(TestSource { solutions });
+
+        solution_sender.send(0x1234_5671).unwrap();
+        solution_sender.send(0x1234_5672).unwrap();
+        let solutions: Vec<_> = stream
+            .by_ref()
+            .take(2)
+            .map(|solution| solution.unwrap().hardware_id)
+            .collect()
+            .await;
+        assert_eq!(solutions, vec![1, 2]);
+
+        assert!(futures::poll!(stream.next()).is_pending());
+        solution_sender.send(0x1234_5673).unwrap();
+        assert_eq!(stream.next().await.unwrap().unwrap().nonce, 0x1234_5673);
+    }
+}
@@ -0,0 +1,207 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Common Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Common Public License for more details.
+//
+// You should have received a copy of the GNU Common Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Transmission of work through AXI DMA engine
+//!
+//! Bitstreams with DMA support connect MM2S channel of an AXI DMA engine (in direct register
+//! mode) to the work TX FIFO of each hash chain. The engine is described in the device tree as
+//! UIO device `chainN-work-tx-dma` with two memory regions: registers of the engine and a buffer
+//! in physically contiguous memory. Work frame is copied to the buffer and the engine bursts it
+//! into the FIFO so that the CPU does not have to write each word into the FIFO register.
+
+use super::uio;
+use super::{FifoEvents, FifoMode, WorkTxFifo};
+use crate::error::{self, ErrorKind};
+
+use std::cell::UnsafeCell;
+use std::convert::TryFrom;
+use std::mem;
+use std::ptr;
+use std::sync::atomic::{self, Ordering};
+
+/// Memory region of the UIO device with registers of the engine
+const REGISTERS_MAPPING: usize = 0;
+/// Memory region of the UIO device with the DMA buffer
+const BUFFER_MAPPING: usize = 1;
+
+/// Number of reads of the control register before soft reset of the engine is considered failed
+const RESET_POLL_COUNT: usize = 1000;
+
+/// DMACR: run/stop
+const DMACR_RS: u32 = 1 << 0;
+/// DMACR: soft reset, it is cleared by the engine once the reset is finished
+const DMACR_RESET: u32 = 1 << 2;
+/// DMACR: enable interrupt on completion of transfer
+const DMACR_IOC_IRQ_EN: u32 = 1 << 12;
+
+/// DMASR: the engine is stopped
+const DMASR_HALTED: u32 = 1 << 0;
+/// DMASR: the last transfer is finished
+const DMASR_IDLE: u32 = 1 << 1;
+/// DMASR: internal, slave and decode errors
+const DMASR_ERRORS: u32 = 0x70;
+/// DMASR: interrupt on completion, it is acknowledged by writing 1
+const DMASR_IOC_IRQ: u32 = 1 << 12;
+
+/// Volatile 32-bit register or word of the DMA buffer
+#[repr(transparent)]
+struct Reg(UnsafeCell<u32>);
+
+impl Reg {
+    #[inline]
+    fn read(&self) -> u32 {
+        unsafe { ptr::read_volatile(self.0.get()) }
+    }
+
+    #[inline]
+    fn write(&self, value: u32) {
+        unsafe { ptr::write_volatile(self.0.get(), value) }
+    }
+}
+
+/// Registers of MM2S channel of AXI DMA engine
+#[repr(C)]
+struct RegisterBlock {
+    /// 0x00: control register
+    dmacr: Reg,
+    /// 0x04: status register
+    dmasr: Reg,
+    _reserved0: [Reg; 4],
+    /// 0x18: source address of the transfer
+    sa: Reg,
+    /// 0x1c: upper half of the source address (unused on 32-bit platform)
+    _sa_msb: Reg,
+    _reserved1: [Reg; 2],
+    /// 0x28: length of the transfer in bytes, writing it starts the transfer
+    length: Reg,
+}
+
+/// The buffer has room for the biggest work accepted by the FIFO
+type Buffer = [Reg; WorkTxFifo::BIGGEST_WORK as usize];
+
+pub struct Engine {
+    regs: uio_async::UioTypedMapping<RegisterBlock>,
+    buffer: uio_async::UioTypedMapping<Buffer>,
+    /// Physical address of `buffer`
+    buffer_addr: u32,
+    events: FifoEvents,
+    /// A transfer has been started and its completion has not been checked yet
+    pending: bool,
+    hashboard_idx: usize,
+}
+
+impl Engine {
+    /// Open DMA engine of work TX FIFO of hash chain `hashboard_idx`. Returns `None` when the
+    /// bitstream does not provide it.
+    pub fn open(hashboard_idx: usize, mode: FifoMode) -> error::Result<Option<Self>> {
+        let uio = match uio::Device::open_optional(hashboard_idx, uio::Type::WorkTxDma)? {
+            Some(uio) => uio,
+            None => return Ok(None),
+        };
+        let (buffer_addr, buffer_size) = uio.mapping_region(BUFFER_MAPPING)?;
+        if buffer_size < mem::size_of::<Buffer>() {
+            Err(ErrorKind::Hashboard(
+                hashboard_idx,
+                format!(
+                    "DMA buffer of {} bytes is smaller than the biggest work",
+                    buffer_size
+                ),
+            ))?
+        }
+        let buffer_addr = u32::try_from(buffer_addr).map_err(|_| {
+            ErrorKind::Hashboard(
+                hashboard_idx,
+                format!("DMA buffer at {:#x} is not 32-bit addressable", buffer_addr),
+            )
+        })?;
+        Ok(Some(Self {
+            regs: uio.map_mapping(REGISTERS_MAPPING)?,
+            buffer: uio.map_mapping(BUFFER_MAPPING)?,
+            buffer_addr,
+            events: FifoEvents { uio: uio.uio, mode },
+            pending: false,
+            hashboard_idx,
+        }))
+    }
+
+    /// Reset the engine and start it. Any transfer in progress is aborted.
+    pub fn init(&mut self) -> error::Result<()> {
+        self.pending = false;
+        self.regs.dmacr.write(DMACR_RESET);
+        if (0..RESET_POLL_COUNT).all(|_| self.regs.dmacr.read() & DMACR_RESET != 0) {
+            Err(ErrorKind::Fifo(
+                error::Fifo::TimedOut,
+                format!("reset of DMA engine of chain {}", self.hashboard_idx),
+            ))?
+        }
+        self.regs.dmacr.write(DMACR_RS | DMACR_IOC_IRQ_EN);
+        Ok(())
+    }
+
+    #[inline]
+    fn is_idle(&self) -> bool {
+        self.regs.dmasr.read() & (DMASR_HALTED | DMASR_IDLE) != 0
+    }
+
+    /// Wait for completion of the last transfer and check that it has not failed
+    async fn wait_for_idle(&mut self) -> error::Result<()> {
+        if !self.pending {
+            return Ok(());
+        }
+        let cond = || self.is_idle();
+        self.events.wait_cond(cond).await?;
+        self.pending = false;
+
+        let status = self.regs.dmasr.read();
+        self.regs.dmasr.write(DMASR_IOC_IRQ);
+        if status & (DMASR_HALTED | DMASR_ERRORS) != 0 {
+            Err(ErrorKind::Hashboard(
+                self.hashboard_idx,
+                format!("DMA transfer of work failed (status {:#x})", status),
+            ))?
+        }
+        Ok(())
+    }
+
+    /// Copy `frame` into the DMA buffer and start its transfer into work TX FIFO. The transfer
+    /// is finished in background, it is waited for before the next one. The caller is
+    /// responsible for checking that there is room for the frame in the FIFO.
+    pub async fn transfer(&mut self, frame: &[u32]) -> error::Result<()> {
+        assert!(
+            frame.len() <= self.buffer.len(),
+            "BUG: work frame of {} words does not fit into DMA buffer",
+            frame.len()
+        );
+        self.wait_for_idle().await?;
+        for (word, slot) in frame.iter().zip(self.buffer.iter()) {
+            slot.write(*word);
+        }
+        // the engine must not see the buffer before it is completely written
+        atomic::fence(Ordering::SeqCst);
+        self.regs.sa.write(self.buffer_addr);
+        self.regs
+            .length
+            .write((frame.len() * mem::size_of::<u32>()) as u32);
+        self.pending = true;
+        Ok(())
+    }
+}
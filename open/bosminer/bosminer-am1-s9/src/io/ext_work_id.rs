@@ -29,7 +29,8 @@ use crate::bm1387::MidstateCount;
 /// of bits allocated to `midstate_idx` and some to `work_id`, depending
 /// on the midstate count configuration (ie. if IP is configured for 4
 /// midstates, then 2 bits are allocated for `midstate_idx` and 14 for
-/// `work_id`).
+/// `work_id`). The second generation of the FPGA core uses 24-bit word,
+/// so the word width `ext_bits` is passed to the methods explicitly.
 ///
 /// **Note**: this representation is specific to FPGA IP core we use.
 /// The hardware chip itself uses a different `work_id`: the chip `work_id`
@@ -45,8 +46,11 @@ pub struct ExtWorkId {
 }
 
 impl ExtWorkId {
-    /// Range is 16 bits
-    const EXT_WORK_ID_COUNT: u32 = 0x10000;
+    /// Width of `ext_work_id` in the first generation of FPGA core
+    pub const EXT_WORK_ID_BITS: u32 = 16;
+    /// Width of `ext_work_id` in the second generation of FPGA core
+    #[cfg(feature = "s9io-v2")]
+    pub const EXT_WORK_ID_BITS_V2: u32 = 24;
 
     pub fn new(work_id: usize, midstate_idx: usize) -> Self {
         Self {
@@ -59,16 +63,16 @@ impl ExtWorkId {
     /// `ext_work_id`. For example if two bits are used for midstates,
     /// we can use only 14 bits for `work_id` so return the number of
     /// work_ids that can fit in that.
-    pub fn get_work_id_count(midstate_count: MidstateCount) -> usize {
-        (Self::EXT_WORK_ID_COUNT >> midstate_count.to_bits()) as usize
+    pub fn get_work_id_count(ext_bits: u32, midstate_count: MidstateCount) -> usize {
+        (1 << (ext_bits - midstate_count.to_bits() as u32)) as usize
     }
 
     /// Create new `ExtWorkId` from FPGA core representation: divide
     /// the word into `midstate_idx` and `work_id` parts depending
     /// on the number of midstates we are using.
     /// As ext_id should be 16 bit, check if it isn't too large.
-    pub fn from_hw(midstate_count: MidstateCount, ext_id: u32) -> Self {
-        assert!(ext_id < Self::EXT_WORK_ID_COUNT);
+    pub fn from_hw(ext_bits: u32, midstate_count: MidstateCount, ext_id: u32) -> Self {
+        assert!(u64::from(ext_id) < 1 << ext_bits);
         let ext_id = ext_id as usize;
         Self {
             work_id: ext_id >> midstate_count.to_bits(),
@@ -77,8 +81,8 @@ impl ExtWorkId {
     }
 
     /// Serialize `Self` to FPGA core representation.
-    pub fn to_hw(&self, midstate_count: MidstateCount) -> u32 {
-        assert!(self.work_id < Self::get_work_id_count(midstate_count));
+    pub fn to_hw(&self, ext_bits: u32, midstate_count: MidstateCount) -> u32 {
+        assert!(self.work_id < Self::get_work_id_count(ext_bits, midstate_count));
         assert!(self.midstate_idx < midstate_count.to_count());
 
         ((self.work_id << midstate_count.to_bits()) | self.midstate_idx) as u32
@@ -93,15 +97,15 @@ pub mod test_utils {
     #[test]
     fn test_from_hw() {
         assert_eq!(
-            ExtWorkId::from_hw(MidstateCount::new(1), 0x8765),
+            ExtWorkId::from_hw(ExtWorkId::EXT_WORK_ID_BITS, MidstateCount::new(1), 0x8765),
             ExtWorkId::new(0x8765, 0)
         );
         assert_eq!(
-            ExtWorkId::from_hw(MidstateCount::new(2), 0x8765),
+            ExtWorkId::from_hw(ExtWorkId::EXT_WORK_ID_BITS, MidstateCount::new(2), 0x8765),
             ExtWorkId::new(0x43b2, 1)
         );
         assert_eq!(
-            ExtWorkId::from_hw(MidstateCount::new(4), 0x8765),
+            ExtWorkId::from_hw(ExtWorkId::EXT_WORK_ID_BITS, MidstateCount::new(4), 0x8765),
             ExtWorkId::new(0x21d9, 1)
         );
     }
@@ -110,15 +114,15 @@ pub mod test_utils {
     #[test]
     fn test_to_hw() {
         assert_eq!(
-            ExtWorkId::new(0x8765, 0).to_hw(MidstateCount::new(1)),
+            ExtWorkId::new(0x8765, 0).to_hw(ExtWorkId::EXT_WORK_ID_BITS, MidstateCount::new(1)),
             0x8765
         );
         assert_eq!(
-            ExtWorkId::new(0x43b2, 1).to_hw(MidstateCount::new(2)),
+            ExtWorkId::new(0x43b2, 1).to_hw(ExtWorkId::EXT_WORK_ID_BITS, MidstateCount::new(2)),
             0x8765
         );
         assert_eq!(
-            ExtWorkId::new(0x21d9, 1).to_hw(MidstateCount::new(4)),
+            ExtWorkId::new(0x21d9, 1).to_hw(ExtWorkId::EXT_WORK_ID_BITS, MidstateCount::new(4)),
             0x8765
         );
     }
@@ -127,23 +131,29 @@ pub mod test_utils {
     #[test]
     #[should_panic]
     fn test_to_hw_fail() {
-        ExtWorkId::new(0x8765, 2).to_hw(MidstateCount::new(2));
+        ExtWorkId::new(0x8765, 2).to_hw(ExtWorkId::EXT_WORK_ID_BITS, MidstateCount::new(2));
     }
 
     /// Test that trying to serialize `ExtWorkId` with too high `midstate_idx` would fail
     #[test]
     #[should_panic]
     fn test_to_hw_fail_2() {
-        ExtWorkId::new(0x8765, 1).to_hw(MidstateCount::new(1));
+        ExtWorkId::new(0x8765, 1).to_hw(ExtWorkId::EXT_WORK_ID_BITS, MidstateCount::new(1));
     }
 
     #[test]
     fn test_work_id_count() {
         assert_eq!(
-            ExtWorkId::get_work_id_count(MidstateCount::new(1)),
+            ExtWorkId::get_work_id_count(ExtWorkId::EXT_WORK_ID_BITS, MidstateCount::new(1)),
             0x10_000
         );
-        assert_eq!(ExtWorkId::get_work_id_count(MidstateCount::new(2)), 0x8_000);
-        assert_eq!(ExtWorkId::get_work_id_count(MidstateCount::new(4)), 0x4_000);
+        assert_eq!(
+            ExtWorkId::get_work_id_count(ExtWorkId::EXT_WORK_ID_BITS, MidstateCount::new(2)),
+            0x8_000
+        );
+        assert_eq!(
+            ExtWorkId::get_work_id_count(ExtWorkId::EXT_WORK_ID_BITS, MidstateCount::new(4)),
+            0x4_000
+        );
     }
 }
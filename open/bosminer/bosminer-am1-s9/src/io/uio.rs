@@ -22,31 +22,100 @@
 
 //! Simple wrapper around UIO device
 
+use crate::error::ResultExt;
 use crate::error::{self, ErrorKind};
-use failure::ResultExt;
 use uio_async;
 
+use ii_async_compat::runtime;
+use runtime::delay_for;
+
+use std::fs;
+use std::time::Duration;
+
+/// Directory with UIO devices instantiated from the device tree
+const SYSFS_UIO_PATH: &str = "/sys/class/uio";
+
+/// Number of attempts to open UIO device which is missing (it may still be being created by udev)
+const OPEN_ATTEMPTS: usize = 4;
+/// Delay before the first retry of UIO device open, it doubles with each retry
+const OPEN_RETRY_DELAY: Duration = Duration::from_millis(100);
+
 pub struct Device {
     pub uio: uio_async::UioDevice,
     uio_name: String,
 }
 
+#[derive(Clone, Copy)]
 pub enum Type {
     Common,
     WorkRx,
     WorkTx,
     Command,
+    /// Optional DMA engine feeding work TX FIFO
+    #[cfg(feature = "s9io-dma")]
+    WorkTxDma,
 }
 
 impl Type {
+    const ALL: [Type; 4] = [Type::Common, Type::WorkRx, Type::WorkTx, Type::Command];
+
     fn as_str(&self) -> &str {
         match self {
             &Type::Common => "common",
             &Type::WorkRx => "work-rx",
             &Type::WorkTx => "work-tx",
             &Type::Command => "cmd-rx",
+            #[cfg(feature = "s9io-dma")]
+            &Type::WorkTxDma => "work-tx-dma",
+        }
+    }
+}
+
+/// Parses index of hash chain from the name of its `common` UIO device (e.g. `chain8-common`)
+fn parse_chain_idx(uio_name: &str) -> Option<usize> {
+    uio_name
+        .strip_prefix("chain")?
+        .strip_suffix(Type::Common.as_str())?
+        .strip_suffix('-')?
+        .parse()
+        .ok()
+        .filter(|&hashboard_idx| hashboard_idx > 0)
+}
+
+/// Returns sorted indices of all hash chains with IP cores described in the device tree
+pub fn defined_chains() -> error::Result<Vec<usize>> {
+    let mut chains = vec![];
+    for entry in fs::read_dir(SYSFS_UIO_PATH)? {
+        let uio_name = fs::read_to_string(entry?.path().join("name"))?;
+        if let Some(hashboard_idx) = parse_chain_idx(uio_name.trim()) {
+            chains.push(hashboard_idx);
+        }
+    }
+    chains.sort();
+    chains.dedup();
+    Ok(chains)
+}
+
+/// Check that all UIO devices of hash chain `hashboard_idx` can be opened
+///
+/// Missing device is looked up again a few times with exponential backoff.
+pub async fn check_devices(hashboard_idx: usize) -> error::Result<()> {
+    for uio_type in Type::ALL.iter() {
+        let uio_name = Device::name(hashboard_idx, *uio_type);
+        let mut retry_delay = OPEN_RETRY_DELAY;
+        for _ in 1..OPEN_ATTEMPTS {
+            match uio_async::UioDevice::open_by_name(&uio_name) {
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    delay_for(retry_delay).await;
+                    retry_delay *= 2;
+                }
+                // Any other error is reported by the final attempt
+                _ => break,
+            }
         }
+        Device::open(hashboard_idx, *uio_type)?;
     }
+    Ok(())
 }
 
 impl Device {
@@ -56,20 +125,59 @@ impl Device {
     ///   connector J8 means `hashboard_idx=8`)
     /// * `uio_type` - type of uio device, determines what IO block to map
     pub fn open(hashboard_idx: usize, uio_type: Type) -> error::Result<Self> {
-        assert!(hashboard_idx > 0);
-        let uio_name = format!("chain{}-{}", hashboard_idx, uio_type.as_str());
+        let uio_name = Self::name(hashboard_idx, uio_type);
         let uio = uio_async::UioDevice::open_by_name(&uio_name).with_context(|_| {
-            ErrorKind::UioDevice(uio_name.clone(), "cannot open uio device".to_string())
+            ErrorKind::UioDevice(
+                uio_name.clone(),
+                format!(
+                    "cannot open uio device, check that the device tree defines node `{}` \
+                     and that it is listed in {}",
+                    uio_name, SYSFS_UIO_PATH
+                ),
+            )
         })?;
         Ok(Self { uio, uio_name })
     }
 
+    /// Open UIO device which is optional in the device tree, returns `None` when it is missing
+    #[cfg(feature = "s9io-dma")]
+    pub fn open_optional(hashboard_idx: usize, uio_type: Type) -> error::Result<Option<Self>> {
+        match uio_async::UioDevice::open_by_name(&Self::name(hashboard_idx, uio_type)) {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            _ => Self::open(hashboard_idx, uio_type).map(Some),
+        }
+    }
+
+    fn name(hashboard_idx: usize, uio_type: Type) -> String {
+        assert!(hashboard_idx > 0);
+        format!("chain{}-{}", hashboard_idx, uio_type.as_str())
+    }
+
     pub fn map<T>(&self) -> error::Result<uio_async::UioTypedMapping<T>> {
-        let map = self.uio.map_mapping(0).with_context(|_| {
+        self.map_mapping(0)
+    }
+
+    /// Map memory region `mapping` of the device (e.g. a DMA buffer)
+    pub fn map_mapping<T>(&self, mapping: usize) -> error::Result<uio_async::UioTypedMapping<T>> {
+        let map = self.uio.map_mapping(mapping).with_context(|_| {
             ErrorKind::UioDevice(self.uio_name.clone(), "cannot map uio device".to_string())
         })?;
         Ok(map.into_typed())
     }
+
+    /// Physical address and size of memory region `mapping`
+    #[cfg(feature = "s9io-dma")]
+    pub fn mapping_region(&self, mapping: usize) -> error::Result<(usize, usize)> {
+        let context = |_: &_| {
+            ErrorKind::UioDevice(
+                self.uio_name.clone(),
+                format!("cannot read address of mapping {}", mapping),
+            )
+        };
+        let addr = self.uio.map_addr(mapping).with_context(context)?;
+        let size = self.uio.map_size(mapping).with_context(context)?;
+        Ok((addr, size))
+    }
 }
 
 #[cfg(test)]
@@ -84,6 +192,16 @@ mod test {
     /// Index of chain for testing (must exist and be defined in DTS)
     const TEST_CHAIN_INDEX: usize = 8;
 
+    #[test]
+    fn test_parse_chain_idx() {
+        assert_eq!(parse_chain_idx("chain8-common"), Some(8));
+        assert_eq!(parse_chain_idx("chain12-common"), Some(12));
+        assert_eq!(parse_chain_idx("chain8-work-rx"), None);
+        assert_eq!(parse_chain_idx("chain0-common"), None);
+        assert_eq!(parse_chain_idx("chainX-common"), None);
+        assert_eq!(parse_chain_idx("fpga-common"), None);
+    }
+
     /// Try opening UIO device.
     /// This test needs properly configured UIO devices for hash-chain 8 in
     /// device-tree so that we have something to open.
@@ -125,7 +243,7 @@ mod test {
 
     fn flush_interrupts() {
         // Flush interrupts by IP core re-init
-        io::Core::new(TEST_CHAIN_INDEX, MidstateCount::new(1))
+        io::Core::new(TEST_CHAIN_INDEX, MidstateCount::new(1), io::FifoMode::Irq)
             .unwrap()
             .init_and_split()
             .unwrap();
@@ -0,0 +1,294 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Serialization of work into the format of work TX FIFO
+//!
+//! The frame is a sequence of 32-bit words written one by one into the FIFO register:
+//!
+//! | word     | field             | value of the word                                        |
+//! |----------|-------------------|----------------------------------------------------------|
+//! | 0        | extended work ID  | `ext_work_id` as is                                      |
+//! | 1        | nBits             | little endian field of the block header                  |
+//! | 2        | nTime             | little endian field of the block header                  |
+//! | 3        | merkle root tail  | last 4 bytes of the merkle root read as little endian    |
+//! | 4 + 8*i  | midstate `i`      | midstate bytes in reversed order read as little endian   |
+//!
+//! The words are defined by their values and not by the byte representation of the host so the
+//! frame is the same regardless of the CPU endianness. Bytes of the frame as they are stored in
+//! the FIFO memory (`WorkFrame::to_bytes`) are the little endian representation of the words.
+
+use bosminer::work;
+
+use ii_bitcoin::SHA256_DIGEST_SIZE;
+
+use std::mem::size_of;
+
+/// Work serialized for work TX FIFO
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkFrame {
+    words: Vec<u32>,
+}
+
+impl WorkFrame {
+    /// Number of words preceding the midstates
+    pub const HEADER_WORDS: usize = 4;
+    /// Number of words of each midstate
+    pub const MIDSTATE_WORDS: usize = SHA256_DIGEST_SIZE / size_of::<u32>();
+
+    /// Creates an empty frame with room for `capacity` words
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            words: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Number of words of the frame for work with `midstate_count` midstates
+    pub fn len_for(midstate_count: usize) -> usize {
+        Self::HEADER_WORDS + midstate_count * Self::MIDSTATE_WORDS
+    }
+
+    /// Serializes `work` into the frame. The frame is cleared first so that its allocation is
+    /// reused for the next work.
+    ///
+    /// There is no version field in the frame. The rolled version of each midstate is hashed
+    /// into the midstate itself and solutions are paired with it by the midstate index reported
+    /// by chips.
+    pub fn assemble(&mut self, work: &work::Assignment, ext_work_id: u32) {
+        self.words.clear();
+        self.words.push(ext_work_id);
+        self.words.push(work.bits());
+        self.words.push(work.ntime);
+        self.words.push(work.merkle_root_tail());
+
+        for mid in work.midstates.iter() {
+            // Reversing the order of words and the bytes in each word reverses the whole midstate
+            self.words
+                .extend(mid.state.words::<u32>().rev().map(u32::swap_bytes));
+        }
+    }
+
+    /// Words in the order in which they are written into the FIFO
+    pub fn words(&self) -> &[u32] {
+        &self.words
+    }
+
+    /// Bytes of the frame as they are stored in the FIFO memory
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.words
+            .iter()
+            .flat_map(|word| word.to_le_bytes().to_vec())
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bosminer::test_utils::TEST_BLOCKS;
+    use ii_bitcoin::HashTrait;
+
+    use std::sync::Arc;
+
+    // Frames written into work TX FIFO by testcase 2 of the s9-io testbench
+    // (`hw/zynq-io-am1-s9/design/src/ip_cores/axi_bm13xx/fve/axi_bm13xx_tb.sv`). The testbench
+    // compares the work sent to chips over UART with its own reference data so the frames are
+    // checked against the FPGA and not against this serializer.
+
+    /// Testcase 2a: work with all header fields set which enables all cores
+    const TESTBENCH_FRAME_MIDSTATE_1: [u32; 12] = [
+        0x00000000, 0xffffffff, 0xffffffff, 0xffffffff, 0x00000000, 0x00000000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000,
+    ];
+
+    /// Testcase 2b: first work of 2 midstates
+    const TESTBENCH_FRAME_MIDSTATE_2: [u32; 20] = [
+        0x00000000, 0x1725fd03, 0x5d0b7d52, 0xde474074, 0x41e79803, 0x634a932b, 0xd79ae784,
+        0xfe7179a6, 0x1ff0ccd2, 0x07d0c195, 0xc34829b3, 0x8a307647, 0xbeb7e7f6, 0x00de500f,
+        0x01e28acf, 0x7a97e115, 0xa3893221, 0x27b159fe, 0xdc0c2081, 0x7294fef6,
+    ];
+
+    /// Testcase 2c: work of 4 midstates
+    const TESTBENCH_FRAME_MIDSTATE_4: [u32; 36] = [
+        0x00000031, 0x17365a17, 0x5b51c8e6, 0x66014b9d, 0x1df9f7a3, 0xba9aca03, 0xc42b0a8c,
+        0xd89fc91a, 0x1046e72e, 0x46a47e9a, 0xf01c1b8e, 0xebc3c539, 0xe578935d, 0xc6419d97,
+        0x1ff8d327, 0x7bf6698e, 0xd757b9eb, 0x980317d2, 0xeafd359f, 0x9544a768, 0x0e1d09af,
+        0xc9316c84, 0x89bbde77, 0xcb13866a, 0x805beaaa, 0xffbbfdb1, 0xa1b617a9, 0xa81b497c,
+        0x93c5272d, 0xcd1b2770, 0x96ab3905, 0x7bfafae3, 0xf1004cdb, 0xb08d4078, 0xd82c00af,
+        0xe75b218b,
+    ];
+
+    /// Builds work of the testbench frame from its header fields and midstates
+    fn testbench_work(
+        bits: u32,
+        time: u32,
+        merkle_root_tail: u32,
+        midstates: &[&str],
+    ) -> work::Assignment {
+        let mut block = TEST_BLOCKS[0];
+        block.bits = bits;
+        block.time = time;
+        let mut merkle_root = [0u8; SHA256_DIGEST_SIZE];
+        merkle_root[SHA256_DIGEST_SIZE - size_of::<u32>()..]
+            .copy_from_slice(&merkle_root_tail.to_le_bytes());
+        block.merkle_root = ii_bitcoin::DHash::from_slice(&merkle_root).unwrap();
+
+        let midstates = midstates
+            .iter()
+            .map(|state| work::Midstate {
+                version: block.version,
+                state: ii_bitcoin::Midstate::from_hex(state).unwrap(),
+            })
+            .collect();
+        work::Assignment::new(Arc::new(block), midstates, time)
+    }
+
+    fn assemble(work: &work::Assignment, ext_work_id: u32) -> WorkFrame {
+        let mut frame = WorkFrame::with_capacity(WorkFrame::len_for(work.midstates.len()));
+        frame.assemble(work, ext_work_id);
+        frame
+    }
+
+    #[test]
+    fn test_testbench_frames() {
+        let work = testbench_work(
+            0xffff_ffff,
+            0xffff_ffff,
+            0xffff_ffff,
+            &["0000000000000000000000000000000000000000000000000000000000000000"],
+        );
+        assert_eq!(assemble(&work, 0).words(), TESTBENCH_FRAME_MIDSTATE_1);
+
+        let work = testbench_work(
+            0x1725_fd03,
+            0x5d0b_7d52,
+            0xde47_4074,
+            &[
+                "8a307647c34829b307d0c1951ff0ccd2fe7179a6d79ae784634a932b41e79803",
+                "7294fef6dc0c208127b159fea38932217a97e11501e28acf00de500fbeb7e7f6",
+            ],
+        );
+        assert_eq!(assemble(&work, 0).words(), TESTBENCH_FRAME_MIDSTATE_2);
+
+        let work = testbench_work(
+            0x1736_5a17,
+            0x5b51_c8e6,
+            0x6601_4b9d,
+            &[
+                "ebc3c539f01c1b8e46a47e9a1046e72ed89fc91ac42b0a8cba9aca031df9f7a3",
+                "9544a768eafd359f980317d2d757b9eb7bf6698e1ff8d327c6419d97e578935d",
+                "a81b497ca1b617a9ffbbfdb1805beaaacb13866a89bbde77c9316c840e1d09af",
+                "e75b218bd82c00afb08d4078f1004cdb7bfafae396ab3905cd1b277093c5272d",
+            ],
+        );
+        assert_eq!(assemble(&work, 0x31).words(), TESTBENCH_FRAME_MIDSTATE_4);
+    }
+
+    #[test]
+    fn test_frame_bytes() {
+        // FIFO memory holds the words in little endian regardless of the host
+        let work = testbench_work(0x1725_fd03, 0x5d0b_7d52, 0xde47_4074, &[]);
+        assert_eq!(
+            assemble(&work, 0x0102_0304).to_bytes(),
+            vec![
+                0x04, 0x03, 0x02, 0x01, 0x03, 0xfd, 0x25, 0x17, 0x52, 0x7d, 0x0b, 0x5d, 0x74, 0x40,
+                0x47, 0xde
+            ]
+        );
+    }
+
+    #[test]
+    fn test_header_fields() {
+        // the header fields in the frame are the same as in the serialized block header
+        for block in TEST_BLOCKS.iter() {
+            let bytes = assemble(&work::Assignment::from(block), 0).to_bytes();
+            assert_eq!(
+                bytes[4..8],
+                block.header_bytes[72..76],
+                "bits of {:?}",
+                block
+            );
+            assert_eq!(
+                bytes[8..12],
+                block.header_bytes[68..72],
+                "time of {:?}",
+                block
+            );
+            assert_eq!(
+                bytes[12..16],
+                block.header_bytes[64..68],
+                "merkle root tail of {:?}",
+                block
+            );
+            let mut midstate = block.midstate.as_ref().to_vec();
+            midstate.reverse();
+            assert_eq!(bytes[16..], midstate[..], "midstate of {:?}", block);
+        }
+    }
+
+    #[test]
+    fn test_midstate_order() {
+        let mut work = work::Assignment::from(&TEST_BLOCKS[0]);
+        let mut midstates = vec![work.midstates[0].clone(); 4];
+        for (i, mid) in midstates.iter_mut().enumerate() {
+            let mut state = [0u8; SHA256_DIGEST_SIZE];
+            state[0] = i as u8;
+            state[SHA256_DIGEST_SIZE - 1] = 0xf0 | i as u8;
+            mid.state = state.into();
+        }
+        work.midstates = midstates;
+
+        let frame = assemble(&work, 0);
+        assert_eq!(frame.len(), WorkFrame::len_for(4));
+        let bytes = frame.to_bytes();
+        for i in 0..4 {
+            // midstates follow each other in the order of the work
+            let offset = (WorkFrame::HEADER_WORDS + i * WorkFrame::MIDSTATE_WORDS) * 4;
+            assert_eq!(bytes[offset], 0xf0 | i as u8);
+            assert_eq!(bytes[offset + SHA256_DIGEST_SIZE - 1], i as u8);
+        }
+    }
+
+    #[test]
+    fn test_frame_reuse() {
+        let mut frame = assemble(&crate::null_work::prepare_opencore(true, 4), 1);
+        assert_eq!(frame.len(), 4 + 4 * 8);
+        let work = testbench_work(
+            0x1725_fd03,
+            0x5d0b_7d52,
+            0xde47_4074,
+            &[
+                "8a307647c34829b307d0c1951ff0ccd2fe7179a6d79ae784634a932b41e79803",
+                "7294fef6dc0c208127b159fea38932217a97e11501e28acf00de500fbeb7e7f6",
+            ],
+        );
+        frame.assemble(&work, 0);
+        assert_eq!(frame.words(), TESTBENCH_FRAME_MIDSTATE_2);
+    }
+}
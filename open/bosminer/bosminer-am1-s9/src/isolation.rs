@@ -0,0 +1,201 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Isolation of faulty chips
+//!
+//! Chips with persistently high hardware error rate or chips which stopped returning nonces
+//! while the rest of the chain keeps hashing are isolated. The chip itself keeps hashing its
+//! nonce subrange (the work is broadcast to the whole chain) but all its solutions are dropped so
+//! that they are neither submitted nor accounted to the chain counters.
+//!
+//! Isolation only filters solutions, the chain is not reconfigured. Nonce subranges are given by
+//! chip addresses assigned during enumeration, so handing the subrange of an isolated chip over
+//! to its neighbours would require a new enumeration of the whole chain. The subrange is
+//! therefore not searched anymore and the chain loses the hashrate of the chip.
+
+use crate::counters;
+
+use std::fmt;
+
+/// Default percentage of hardware errors of a chip within one check interval which is counted
+/// as a faulty interval
+pub const DEFAULT_ERROR_RATE: f64 = 50.0;
+/// Default number of consecutive faulty check intervals after which the chip is isolated
+pub const DEFAULT_CHECK_COUNT: usize = 3;
+
+/// A chip without any nonce is considered as stopped only when the chips on the chain have found
+/// at least this many nonces on average
+const MIN_AVERAGE_NONCES: f64 = 10.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Config {
+    /// Percentage of hardware errors within one check interval
+    pub error_rate: f64,
+    /// Number of consecutive faulty check intervals
+    pub check_count: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            error_rate: DEFAULT_ERROR_RATE,
+            check_count: DEFAULT_CHECK_COUNT,
+        }
+    }
+}
+
+/// Reason of chip isolation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Reason {
+    Errors,
+    Stopped,
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reason::Errors => write!(f, "too many hardware errors"),
+            Reason::Stopped => write!(f, "no nonces"),
+        }
+    }
+}
+
+/// Tracks consecutive faulty check intervals of all chips on one chain
+#[derive(Debug)]
+pub struct Detector {
+    config: Config,
+    /// Number of consecutive faulty intervals of each chip
+    faulty_intervals: Vec<usize>,
+    isolated: Vec<bool>,
+}
+
+impl Detector {
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            faulty_intervals: vec![],
+            isolated: vec![],
+        }
+    }
+
+    /// Accounts counters of all chips within one check interval and returns chips which have
+    /// just been isolated
+    pub fn account(
+        &mut self,
+        chips: &[counters::Chip],
+        asic_difficulty: usize,
+    ) -> Vec<(usize, Reason)> {
+        if chips.len() > self.isolated.len() {
+            self.faulty_intervals.resize(chips.len(), 0);
+            self.isolated.resize(chips.len(), false);
+        }
+        let nonces = |chip: &counters::Chip| chip.valid / asic_difficulty.max(1) + chip.errors;
+        let active_chips: Vec<_> = chips
+            .iter()
+            .enumerate()
+            .filter(|(chip_idx, _)| !self.is_isolated(*chip_idx))
+            .map(|(_, chip)| chip)
+            .collect();
+        let average_nonces = if active_chips.is_empty() {
+            0.0
+        } else {
+            active_chips.iter().map(|chip| nonces(chip)).sum::<usize>() as f64
+                / active_chips.len() as f64
+        };
+
+        let mut isolated = vec![];
+        for (chip_idx, chip) in chips.iter().enumerate() {
+            if self.isolated[chip_idx] {
+                continue;
+            }
+            let reason = match chip.error_rate(asic_difficulty) {
+                Some(error_rate) if error_rate > self.config.error_rate => Some(Reason::Errors),
+                None if average_nonces >= MIN_AVERAGE_NONCES => Some(Reason::Stopped),
+                _ => None,
+            };
+            match reason {
+                Some(reason) => {
+                    self.faulty_intervals[chip_idx] += 1;
+                    if self.faulty_intervals[chip_idx] >= self.config.check_count {
+                        self.isolated[chip_idx] = true;
+                        isolated.push((chip_idx, reason));
+                    }
+                }
+                None => self.faulty_intervals[chip_idx] = 0,
+            }
+        }
+        isolated
+    }
+
+    pub fn is_isolated(&self, chip_idx: usize) -> bool {
+        self.isolated.get(chip_idx).copied().unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn chip(valid: usize, errors: usize) -> counters::Chip {
+        let mut chip = counters::Chip::new();
+        chip.valid = valid;
+        chip.errors = errors;
+        chip
+    }
+
+    #[test]
+    fn test_isolate_errors() {
+        let mut detector = Detector::new(Config::default());
+        let faulty = vec![chip(20, 1), chip(1, 5), chip(20, 0)];
+        let healthy = vec![chip(20, 1), chip(20, 0), chip(20, 0)];
+
+        assert!(detector.account(&faulty, 1).is_empty());
+        // a healthy interval restarts the count
+        assert!(detector.account(&healthy, 1).is_empty());
+        assert!(detector.account(&faulty, 1).is_empty());
+        assert!(detector.account(&faulty, 1).is_empty());
+        assert_eq!(detector.account(&faulty, 1), vec![(1, Reason::Errors)]);
+        assert!(detector.is_isolated(1));
+        assert!(!detector.is_isolated(0));
+        // isolated chip is not reported again
+        assert!(detector.account(&faulty, 1).is_empty());
+    }
+
+    #[test]
+    fn test_isolate_stopped() {
+        let config = Config {
+            error_rate: DEFAULT_ERROR_RATE,
+            check_count: 1,
+        };
+        let mut detector = Detector::new(config);
+        // the whole chain is idle
+        assert!(detector
+            .account(&[chip(0, 0), chip(0, 0), chip(0, 0)], 1)
+            .is_empty());
+        assert_eq!(
+            detector.account(&[chip(40, 0), chip(0, 0), chip(40, 0)], 2),
+            vec![(1, Reason::Stopped)]
+        );
+        // chips out of range are ignored
+        assert!(!detector.is_isolated(5));
+    }
+}
@@ -0,0 +1,353 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Front panel LEDs signalling the state of the miner
+//!
+//! The state is evaluated periodically from temperatures reported by the monitor, from hash
+//! chains and from pool connections. Each state is shown by its own pattern of the red or green
+//! LED. When more states apply at once, the most severe one is shown.
+//!
+//! The miner can be identified (e.g. in a rack) by fast blinking which overrides the state for
+//! `IDENTIFY_TIMEOUT` or until it is turned off again.
+
+use ii_logging::macros::*;
+
+use crate::error;
+use crate::gpio;
+use crate::monitor;
+use crate::pause;
+use crate::Manager;
+
+use bosminer::client;
+
+use embedded_hal::digital::v2::OutputPin;
+
+use ii_async_compat::runtime;
+use runtime::delay_for;
+
+use serde::{Deserialize, Serialize};
+
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, Instant};
+
+/// Period of evaluation of the miner state
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Identification of the miner is turned off automatically after this time
+pub const IDENTIFY_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Color {
+    Red,
+    Green,
+}
+
+/// LED of given color is turned on for `on_time` and off for `off_time` repeatedly. It is lit
+/// steadily when `off_time` is zero.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pattern {
+    pub color: Color,
+    pub on_time: Duration,
+    pub off_time: Duration,
+}
+
+impl Pattern {
+    pub const fn steady(color: Color) -> Self {
+        Self {
+            color,
+            on_time: Duration::from_secs(1),
+            off_time: Duration::from_secs(0),
+        }
+    }
+
+    pub const fn blink(color: Color, period_ms: u64) -> Self {
+        Self {
+            color,
+            on_time: Duration::from_millis(period_ms / 2),
+            off_time: Duration::from_millis(period_ms / 2),
+        }
+    }
+}
+
+/// State of the miner ordered by severity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum State {
+    /// All hash chains are mining
+    Normal,
+    /// No pool is connected
+    NoPool,
+    /// Some hash chain is not running although mining is not paused
+    Degraded,
+    /// Temperature is above the hot limit
+    Overheat,
+}
+
+impl State {
+    pub fn decide(overheat: bool, degraded: bool, no_pool: bool) -> Self {
+        if overheat {
+            Self::Overheat
+        } else if degraded {
+            Self::Degraded
+        } else if no_pool {
+            Self::NoPool
+        } else {
+            Self::Normal
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    pub normal: Pattern,
+    pub no_pool: Pattern,
+    pub degraded: Pattern,
+    pub overheat: Pattern,
+    pub identify: Pattern,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            normal: Pattern::steady(Color::Green),
+            no_pool: Pattern::blink(Color::Green, 1000),
+            degraded: Pattern::blink(Color::Red, 1000),
+            overheat: Pattern::steady(Color::Red),
+            identify: Pattern::blink(Color::Red, 200),
+        }
+    }
+}
+
+impl Config {
+    pub fn pattern(&self, state: State) -> Pattern {
+        match state {
+            State::Normal => self.normal,
+            State::NoPool => self.no_pool,
+            State::Degraded => self.degraded,
+            State::Overheat => self.overheat,
+        }
+    }
+}
+
+/// Front panel LEDs
+pub struct Pins {
+    red: gpio::PinOut,
+    green: gpio::PinOut,
+}
+
+impl Pins {
+    pub fn open(gpio_mgr: &gpio::ControlPinManager) -> error::Result<Self> {
+        Ok(Self {
+            red: gpio_mgr.get_pin_out(gpio::PinOutName::LEDFrontRed)?,
+            green: gpio_mgr.get_pin_out(gpio::PinOutName::LEDFrontGreen)?,
+        })
+    }
+
+    /// Lights the LED of `color` and turns the other one off. Both LEDs are turned off when
+    /// `color` is `None`.
+    fn set(&mut self, color: Option<Color>) -> error::Result<()> {
+        let set_pin = |pin: &mut gpio::PinOut, on: bool| {
+            if on {
+                pin.set_high()
+            } else {
+                pin.set_low()
+            }
+        };
+        set_pin(&mut self.red, color == Some(Color::Red))?;
+        set_pin(&mut self.green, color == Some(Color::Green))?;
+        Ok(())
+    }
+}
+
+pub struct Controller {
+    config: Config,
+    state: StdMutex<State>,
+    /// Time when the identification has been turned on, it is `None` when it is off
+    identify_since: StdMutex<Option<Instant>>,
+    managers: Vec<Arc<Manager>>,
+    monitor: Arc<monitor::Monitor>,
+    pause_controller: Arc<pause::Controller>,
+    client_manager: client::Manager,
+}
+
+impl Controller {
+    pub fn new(
+        config: Config,
+        managers: Vec<Arc<Manager>>,
+        monitor: Arc<monitor::Monitor>,
+        pause_controller: Arc<pause::Controller>,
+        client_manager: client::Manager,
+    ) -> Self {
+        Self {
+            config,
+            state: StdMutex::new(State::Normal),
+            identify_since: StdMutex::new(None),
+            managers,
+            monitor,
+            pause_controller,
+            client_manager,
+        }
+    }
+
+    pub fn state(&self) -> State {
+        *self.state.lock().expect("BUG: failed to lock mutex")
+    }
+
+    fn set_state(&self, state: State) {
+        let mut current = self.state.lock().expect("BUG: failed to lock mutex");
+        if *current != state {
+            info!("LED: miner state changed to {:?}", state);
+            *current = state;
+        }
+    }
+
+    /// Turns the identification on or off. Returns `true` when it has been turned on.
+    pub fn toggle_identify(&self) -> bool {
+        let mut identify_since = self
+            .identify_since
+            .lock()
+            .expect("BUG: failed to lock mutex");
+        if identify_since.take().is_some() {
+            info!("LED: identification turned off");
+            false
+        } else {
+            info!("LED: identification turned on");
+            identify_since.replace(Instant::now());
+            true
+        }
+    }
+
+    fn is_identifying(&self) -> bool {
+        let mut identify_since = self
+            .identify_since
+            .lock()
+            .expect("BUG: failed to lock mutex");
+        match *identify_since {
+            Some(since) if since.elapsed() >= IDENTIFY_TIMEOUT => {
+                identify_since.take();
+                false
+            }
+            since => since.is_some(),
+        }
+    }
+
+    fn is_overheated(&self) -> bool {
+        let status = match self.monitor.status_receiver.borrow().clone() {
+            Some(status) => status,
+            None => return false,
+        };
+        match (status.input_temperature, status.config.temp_config) {
+            (monitor::ChainTemperature::Ok(temperature), Some(temp_config)) => {
+                temperature >= temp_config.hot_temp
+            }
+            _ => false,
+        }
+    }
+
+    async fn is_degraded(&self) -> bool {
+        // chains are stopped on purpose
+        if self.pause_controller.status().await.paused {
+            return false;
+        }
+        for manager in self.managers.iter() {
+            if manager.inner.lock().await.hash_chain.is_none() {
+                return true;
+            }
+        }
+        false
+    }
+
+    async fn is_pool_down(&self) -> bool {
+        for group in self.client_manager.get_groups().await {
+            for client in group.get_clients().await {
+                if client.is_running() {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    async fn update(&self) {
+        let state = State::decide(
+            self.is_overheated(),
+            self.is_degraded().await,
+            self.is_pool_down().await,
+        );
+        self.set_state(state);
+    }
+
+    /// Periodically evaluates the state of the miner
+    pub async fn watch_task(self: Arc<Self>) {
+        let mut interval = runtime::Interval::new(CHECK_INTERVAL);
+        while interval.tick().await.is_some() {
+            self.update().await;
+        }
+    }
+
+    /// Drives the LEDs with the pattern of the current state
+    pub async fn blink_task(self: Arc<Self>, mut pins: Pins) {
+        loop {
+            let pattern = if self.is_identifying() {
+                self.config.identify
+            } else {
+                self.config.pattern(self.state())
+            };
+            let mut result = pins.set(Some(pattern.color));
+            if result.is_ok() {
+                delay_for(pattern.on_time).await;
+                if pattern.off_time > Duration::from_secs(0) {
+                    result = pins.set(None);
+                    delay_for(pattern.off_time).await;
+                }
+            }
+            if let Err(e) = result {
+                error!("LED: failed to drive front panel LEDs: {}", e);
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_decide() {
+        assert_eq!(State::decide(false, false, false), State::Normal);
+        assert_eq!(State::decide(false, false, true), State::NoPool);
+        assert_eq!(State::decide(false, true, true), State::Degraded);
+        assert_eq!(State::decide(true, true, true), State::Overheat);
+        assert!(State::Overheat > State::Normal);
+    }
+
+    #[test]
+    fn test_patterns() {
+        let config = Config::default();
+        assert_eq!(config.pattern(State::Normal).color, Color::Green);
+        assert_eq!(config.pattern(State::Overheat).color, Color::Red);
+        let blink = config.pattern(State::Degraded);
+        assert_eq!(blink.on_time, Duration::from_millis(500));
+        assert_eq!(blink.off_time, blink.on_time);
+    }
+}
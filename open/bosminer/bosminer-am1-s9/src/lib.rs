@@ -19,26 +19,49 @@
 // under a proprietary license. For more information on the terms and conditions
 // of such proprietary license or if you have any other questions, please
 // contact us at opensource@braiins.com.
+
+// All of the crate drives Antminer S9 hardware so it is compiled only with `antminer_s9` feature
+#![cfg(feature = "antminer_s9")]
 #![recursion_limit = "256"]
 
 mod async_i2c;
 pub mod bm1387;
+pub mod button;
 mod cgminer;
 pub mod command;
 pub mod config;
 pub mod counters;
+pub mod diagnostics;
+pub mod efficiency;
 pub mod error;
 pub mod fan;
+pub mod fault;
+pub mod frequency_limit;
+pub mod governor;
 pub mod gpio;
-pub mod halt;
+pub mod hashboard;
+pub mod hashrate_cap;
 pub mod hooks;
 pub mod i2c;
 pub mod io;
+pub mod isolation;
+pub mod led;
 pub mod monitor;
 pub mod null_work;
+pub mod pause;
 pub mod power;
+pub mod power_target;
+pub mod preset;
+pub mod psu;
+pub mod ramp;
+pub mod recovery;
 pub mod registry;
+pub mod restart;
+pub mod selftest;
 pub mod sensor;
+pub mod telemetry;
+pub mod throttle;
+pub mod tuner;
 pub mod utils;
 
 #[cfg(test)]
@@ -46,8 +69,11 @@ pub mod test;
 
 use ii_logging::macros::*;
 
+use bosminer::alert;
 use bosminer::async_trait;
 use bosminer::hal::{self, BackendConfig as _};
+use bosminer::influx;
+use bosminer::mqtt;
 use bosminer::node;
 use bosminer::stats;
 use bosminer::work;
@@ -55,14 +81,18 @@ use bosminer::work;
 use bosminer_macros::WorkSolverNode;
 
 use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex as StdMutex};
 use std::time::{Duration, Instant};
 
+use crate::error::ResultExt;
+use crate::io::{SolutionSource as _, WorkSink as _};
 use error::ErrorKind;
-use failure::ResultExt;
 
 use futures::channel::mpsc;
+use futures::future;
 use futures::lock::{Mutex, MutexGuard};
+use futures::sink::SinkExt;
 use futures::stream::StreamExt;
 use ii_async_compat::futures;
 
@@ -71,19 +101,16 @@ use command::Interface;
 
 use packed_struct::PackedStruct;
 
-use embedded_hal::digital::v2::InputPin;
-use embedded_hal::digital::v2::OutputPin;
-
 use ii_bitcoin::MeetsTarget;
 
-use ii_async_compat::tokio;
+use ii_stop::{HaltHandle, HaltReceiver};
+
+use ii_async_compat::{runtime, tokio, FutureExt};
+use runtime::delay_for;
 use tokio::sync::watch;
-use tokio::time::delay_for;
 
 /// Timing constants
 const INACTIVATE_FROM_CHAIN_DELAY: Duration = Duration::from_millis(100);
-/// Base delay quantum during hashboard initialization
-const INIT_DELAY: Duration = Duration::from_secs(1);
 /// Time to wait between successive hashboard initialization attempts
 const ENUM_RETRY_DELAY: Duration = Duration::from_secs(10);
 /// How many times to retry the enumeration
@@ -100,15 +127,28 @@ pub const CHIP_OSC_CLK_HZ: usize = 25_000_000;
 
 /// Exact value of the initial baud rate after reset of the hashing chips.
 const INIT_CHIP_BAUD_RATE: usize = 115740;
-/// Exact desired target baud rate when hashing at full speed (matches the divisor, too)
-const TARGET_CHIP_BAUD_RATE: usize = 1562500;
 
-/// Address of chip with connected temp sensor
-const TEMP_CHIP: ChipAddress = ChipAddress::One(61);
+/// Index of chip with connected board temp sensor
+const TEMP_CHIP: usize = 61;
 
 /// Timeout for completion of haschain halt
 const HALT_TIMEOUT: Duration = Duration::from_secs(30);
 
+/// Maximal time the shutdown of hash chain waits for solutions of work already sent to chips
+const SHUTDOWN_DRAIN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Chips are considered to have finished their work when no solution arrives for this long
+const SHUTDOWN_QUIET_PERIOD: Duration = Duration::from_millis(200);
+
+/// How often idle chain checks whether it should send work again
+const IDLE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Period of accounting CRC errors and checking error rate of chips
+const HW_ERROR_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Chips with higher percentage of hardware errors within one check interval are reported
+const HW_ERROR_RATE_WARN: f64 = 10.0;
+
 /// Core address space size (it should be 114, but the addresses are non-consecutive)
 const CORE_ADR_SPACE_SIZE: usize = 128;
 
@@ -116,56 +156,33 @@ const CORE_ADR_SPACE_SIZE: usize = 128;
 /// TODO: Implement it as a proper type (not just alias)
 pub type Power = usize;
 
-/// Type representing plug pin
-#[derive(Clone)]
-pub struct PlugPin {
-    pin: gpio::PinIn,
+/// Reading of temperature sensor attached to I2C bus of one chip. The remote channel of the
+/// sensor measures die of the chip, the local one measures the board around it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChipTemperature {
+    pub chip_idx: usize,
+    pub temperature: sensor::Temperature,
 }
 
-impl PlugPin {
-    pub fn open(gpio_mgr: &gpio::ControlPinManager, hashboard_idx: usize) -> error::Result<Self> {
-        Ok(Self {
-            pin: gpio_mgr
-                .get_pin_in(gpio::PinInName::Plug(hashboard_idx))
-                .context(ErrorKind::Hashboard(
-                    hashboard_idx,
-                    "failed to initialize plug pin".to_string(),
-                ))?,
-        })
-    }
-
-    pub fn hashboard_present(&self) -> error::Result<bool> {
-        Ok(self.pin.is_high()?)
-    }
-}
+/// Hardware independent interface for controlling a hash chain
+///
+/// Management of running chains (tuning, presets, monitoring) goes only through this trait and
+/// the `io::WorkSink`/`io::SolutionSource` traits, a hash chain of another generation implements
+/// them for its own chip protocol.
+#[async_trait]
+pub trait ChainControl: Send + Sync + 'static {
+    /// Reset the hashboard and assign addresses to all chips found on the chain.
+    /// If not enough chips were found and `accept_less_chips` is not specified,
+    /// treat it as error.
+    async fn reset_and_enumerate(&mut self, accept_less_chips: bool) -> error::Result<()>;
 
-/// Type representing reset pin
-#[derive(Clone)]
-pub struct ResetPin {
-    pin: gpio::PinOut,
-}
+    async fn get_frequency(&self) -> FrequencySettings;
 
-impl ResetPin {
-    pub fn open(gpio_mgr: &gpio::ControlPinManager, hashboard_idx: usize) -> error::Result<Self> {
-        Ok(Self {
-            pin: gpio_mgr
-                .get_pin_out(gpio::PinOutName::Rst(hashboard_idx))
-                .context(ErrorKind::Hashboard(
-                    hashboard_idx,
-                    "failed to initialize reset pin".to_string(),
-                ))?,
-        })
-    }
+    async fn set_frequency(&self, frequency: &FrequencySettings) -> error::Result<()>;
 
-    pub fn enter_reset(&mut self) -> error::Result<()> {
-        self.pin.set_low()?;
-        Ok(())
-    }
+    async fn get_voltage(&self) -> power::Voltage;
 
-    pub fn exit_reset(&mut self) -> error::Result<()> {
-        self.pin.set_high()?;
-        Ok(())
-    }
+    async fn set_voltage(&self, voltage: power::Voltage) -> error::Result<()>;
 }
 
 /// Hash Chain Controller provides abstraction of the FPGA interface for operating hashing boards.
@@ -179,6 +196,8 @@ impl ResetPin {
 pub struct HashChain {
     /// Number of chips that have been detected
     chip_count: usize,
+    /// Chips detected during the last enumeration
+    chips: Vec<bm1387::ChipInfo>,
     /// Eliminates the need to query the IP core about the current number of configured midstates
     midstate_count: MidstateCount,
     /// ASIC difficulty
@@ -187,8 +206,8 @@ pub struct HashChain {
     asic_target: ii_bitcoin::Target,
     /// Voltage controller on this hashboard
     voltage_ctrl: Arc<power::Control>,
-    /// Pin for resetting the hashboard
-    reset_pin: ResetPin,
+    /// Reset and power sequencing of the hashboard
+    power_sequencer: hashboard::PowerSequencer,
     hashboard_idx: usize,
     pub command_context: command::Context,
     pub common_io: io::Common,
@@ -198,18 +217,52 @@ pub struct HashChain {
     /// Do not send open-core work if this is true (some tests that test chip initialization may
     /// want to do this).
     disable_init_work: bool,
+    /// Current baud rate of communication with chips
+    baud_rate: usize,
+    /// Baud rate which the chain is switched to after enumeration of chips
+    target_baud_rate: usize,
+    /// Raise frequency gradually during initialization
+    frequency_ramp: Option<ramp::Config>,
+    /// Run self-test of chips during initialization
+    self_test_enabled: bool,
+    /// Results of the last self-test of chips
+    self_test_report: Option<selftest::Report>,
+    /// Chips with temperature sensors which are read in addition to `TEMP_CHIP`
+    chip_temp_sensors: Vec<usize>,
+    /// Last readings of all temperature sensors ordered by chip index
+    chip_temperatures: StdMutex<Vec<ChipTemperature>>,
+    /// Default frequency and voltage used when the chip bin of the hashboard matches
+    chip_bin_profiles: Vec<hashboard::Profile>,
+    /// Identification of the hashboard read during initialization
+    board_info: hashboard::Info,
+    /// Detector of faulty chips (when their isolation is enabled)
+    isolation: StdMutex<Option<isolation::Detector>>,
+    /// Cleared on shutdown to stop sending new work to chips
+    work_tx_enabled: AtomicBool,
+    /// Set while mining is paused in idle mode, no work is taken from the work hub then
+    idle: AtomicBool,
+    /// Number of solutions received from chips
+    solution_count: AtomicUsize,
+    /// Number of work sent to chips
+    work_count: AtomicUsize,
+    /// Set while the work TX task is waiting for work from the work hub
+    waiting_for_work: AtomicBool,
     /// channels through which temperature status is sent
     temperature_sender: Mutex<Option<watch::Sender<Option<sensor::Temperature>>>>,
     temperature_receiver: watch::Receiver<Option<sensor::Temperature>>,
     /// nonce counter
     pub counter: Arc<Mutex<counters::HashChain>>,
     /// halter to stop this hashchain
-    halt_sender: Arc<halt::Sender>,
+    halt_sender: Arc<HaltHandle>,
     /// we need to keep the halt receiver around, otherwise the "stop-notify" channel closes when chain ends
     #[allow(dead_code)]
-    halt_receiver: halt::Receiver,
+    halt_receiver: HaltReceiver,
     /// Current hashchain settings
     frequency: Mutex<FrequencySettings>,
+    /// Registry of work sent to chips which is created during initialization
+    work_registry: Option<Arc<Mutex<registry::WorkRegistry>>>,
+    /// Reason of a failure of FPGA I/O which stopped the work or solution task
+    failure: StdMutex<Option<String>>,
 }
 
 impl HashChain {
@@ -219,41 +272,44 @@ impl HashChain {
     /// * `voltage_ctrl_backend` - communication backend for the voltage controller
     /// * `hashboard_idx` - index of this hashboard determines which FPGA IP core is to be mapped
     /// * `midstate_count` - see Self
+    /// * `fifo_mode` - how to wait for events of FPGA FIFOs
     /// * `asic_difficulty` - to what difficulty set the hardware target filter
     pub fn new(
-        reset_pin: ResetPin,
-        plug_pin: PlugPin,
+        reset_pin: hashboard::ResetPin,
+        plug_pin: hashboard::PlugPin,
         voltage_ctrl_backend: Arc<power::I2cBackend>,
         hashboard_idx: usize,
         midstate_count: MidstateCount,
+        fifo_mode: io::FifoMode,
         asic_difficulty: usize,
         monitor_tx: mpsc::UnboundedSender<monitor::Message>,
     ) -> error::Result<Self> {
-        let core = io::Core::new(hashboard_idx, midstate_count)?;
+        let core = io::Core::new(hashboard_idx, midstate_count, fifo_mode)?;
         // Unfortunately, we have to do IP core re-init here (but it should be OK, it's synchronous)
         let (common_io, command_io, work_rx_io, work_tx_io) = core.init_and_split()?;
 
         // check that the board is present
         if !plug_pin.hashboard_present()? {
-            Err(ErrorKind::Hashboard(
-                hashboard_idx,
-                "not present".to_string(),
-            ))?
+            Err(ErrorKind::ChainNotFound(hashboard_idx))?
         }
 
         // create temperature sending channel
         let (temperature_sender, temperature_receiver) = watch::channel(None);
 
         // create halt notification channel
-        let (halt_sender, halt_receiver) = halt::make_pair(HALT_TIMEOUT);
+        let (halt_sender, halt_receiver) = ii_stop::make_pair(HALT_TIMEOUT);
+
+        let voltage_ctrl = Arc::new(power::Control::new(voltage_ctrl_backend, hashboard_idx));
+        let power_sequencer = hashboard::PowerSequencer::new(reset_pin, voltage_ctrl.clone());
 
         Ok(Self {
             chip_count: 0,
+            chips: Vec::new(),
             midstate_count,
             asic_difficulty,
             asic_target: ii_bitcoin::Target::from_pool_difficulty(asic_difficulty),
-            voltage_ctrl: Arc::new(power::Control::new(voltage_ctrl_backend, hashboard_idx)),
-            reset_pin,
+            voltage_ctrl,
+            power_sequencer,
             hashboard_idx,
             common_io,
             command_context: command::Context::new(command_io),
@@ -261,6 +317,21 @@ impl HashChain {
             work_tx_io: Mutex::new(Some(work_tx_io)),
             monitor_tx,
             disable_init_work: false,
+            baud_rate: INIT_CHIP_BAUD_RATE,
+            target_baud_rate: config::DEFAULT_CHIP_BAUD_RATE,
+            frequency_ramp: None,
+            self_test_enabled: false,
+            self_test_report: None,
+            chip_temp_sensors: vec![],
+            chip_temperatures: StdMutex::new(vec![]),
+            chip_bin_profiles: vec![],
+            board_info: Default::default(),
+            isolation: StdMutex::new(None),
+            work_tx_enabled: AtomicBool::new(true),
+            idle: AtomicBool::new(false),
+            solution_count: AtomicUsize::new(0),
+            work_count: AtomicUsize::new(0),
+            waiting_for_work: AtomicBool::new(false),
             temperature_sender: Mutex::new(Some(temperature_sender)),
             temperature_receiver,
             counter: Arc::new(Mutex::new(counters::HashChain::new(
@@ -270,6 +341,8 @@ impl HashChain {
             halt_sender,
             halt_receiver,
             frequency: Mutex::new(FrequencySettings::from_frequency(0)),
+            work_registry: None,
+            failure: StdMutex::new(None),
         })
     }
 
@@ -277,6 +350,14 @@ impl HashChain {
         self.temperature_receiver.borrow().clone()
     }
 
+    /// Last temperatures of all chips with attached sensors
+    pub fn chip_temperatures(&self) -> Vec<ChipTemperature> {
+        self.chip_temperatures
+            .lock()
+            .expect("BUG: failed to lock chip temperatures")
+            .clone()
+    }
+
     async fn take_work_rx_io(&self) -> io::WorkRx {
         self.work_rx_io
             .lock()
@@ -293,23 +374,19 @@ impl HashChain {
             .expect("work-tx io missing")
     }
 
-    /// Calculate work_time for this instance of HChain
+    /// Set work time depending on PLL frequency of chips and midstate count of this chain
     ///
-    /// Returns number of ticks (suitable to be written to `WORK_TIME` register)
-    #[inline]
-    fn calculate_work_time(&self, max_pll_frequency: usize) -> u32 {
-        secs_to_fpga_ticks(calculate_work_delay_for_pll(
-            self.midstate_count.to_count(),
-            max_pll_frequency,
-        ))
-    }
-
-    /// Set work time depending on current PLL frequency
-    ///
-    /// This method sets work time so it's fast enough for `new_freq`
-    async fn set_work_time(&self, new_freq: usize) {
-        let new_work_time = self.calculate_work_time(new_freq);
-        info!("Using work time: {} for freq {}", new_work_time, new_freq);
+    /// The midstate count cannot change during lifetime of the instance, the chain is initialized
+    /// again (including `set_pll`) when the midstate mode is switched.
+    fn set_work_time(&self, frequency: &FrequencySettings) {
+        let new_work_time = calculate_work_time(frequency, self.chip_count, self.midstate_count);
+        info!(
+            "Using work time: {} for {} chips with max. freq {} in {} midstate mode",
+            new_work_time,
+            self.chip_count,
+            frequency.max_of(self.chip_count),
+            self.midstate_count.to_count()
+        );
         self.common_io.set_ip_core_work_time(new_work_time);
     }
 
@@ -317,27 +394,12 @@ impl HashChain {
     async fn ip_core_init(&mut self) -> error::Result<()> {
         // Configure IP core
         self.set_ip_core_baud_rate(INIT_CHIP_BAUD_RATE)?;
+        self.baud_rate = INIT_CHIP_BAUD_RATE;
         self.common_io.set_midstate_count();
 
         Ok(())
     }
 
-    /// Puts the board into reset mode and disables the associated IP core
-    fn enter_reset(&mut self) -> error::Result<()> {
-        self.common_io.disable_ip_core();
-        // Warning: Reset pin DOESN'T reset the PIC. The PIC needs to be reset by other means.
-        // Perform reset of the hashboard
-        self.reset_pin.enter_reset()?;
-        Ok(())
-    }
-
-    /// Leaves reset mode
-    fn exit_reset(&mut self) -> error::Result<()> {
-        self.reset_pin.exit_reset()?;
-        self.common_io.enable_ip_core();
-        Ok(())
-    }
-
     /// Configures difficulty globally on all chips within the hashchain
     async fn set_asic_diff(&mut self, difficulty: usize) -> error::Result<()> {
         let tm_reg = bm1387::TicketMaskReg::new(difficulty as u32)?;
@@ -352,7 +414,7 @@ impl HashChain {
         Ok(())
     }
 
-    /// Reset hashboard and try to enumerate the chips.
+    /// Reset hashboard, enumerate the chips and bring them to the initial frequency.
     /// If not enough chips were found and `accept_less_chips` is not specified,
     /// treat it as error.
     async fn reset_and_enumerate_and_init(
@@ -360,42 +422,13 @@ impl HashChain {
         accept_less_chips: bool,
         initial_frequency: &FrequencySettings,
     ) -> error::Result<()> {
-        // Reset hashboard, toggle voltage
-        info!("Resetting hash board");
-        self.enter_reset()?;
-        self.voltage_ctrl.disable_voltage().await?;
-        delay_for(INIT_DELAY).await;
-        self.voltage_ctrl.enable_voltage().await?;
-        delay_for(INIT_DELAY * 2).await;
-        self.exit_reset()?;
-        delay_for(INIT_DELAY).await;
-
-        // Enumerate chips
-        info!("Starting chip enumeration");
-        self.enumerate_chips().await?;
-
-        // Figure out if we found enough chips
-        info!("Discovered {} chips", self.chip_count);
-        self.command_context.set_chip_count(self.chip_count).await;
-        self.counter.lock().await.set_chip_count(self.chip_count);
-        self.frequency.lock().await.set_chip_count(self.chip_count);
-
-        // If we don't have full number of chips and we do not want incomplete chain, then raise
-        // an error
-        if self.chip_count < EXPECTED_CHIPS_ON_CHAIN && !accept_less_chips {
-            Err(ErrorKind::ChipEnumeration(
-                "Not enough chips on chain".into(),
-            ))?;
-        }
+        self.reset_and_enumerate(accept_less_chips).await?;
 
         // set PLL
-        self.set_pll(initial_frequency).await?;
+        self.ramp_frequency(initial_frequency).await?;
 
-        // configure the hashing chain to operate at desired baud rate. Note that gate block is
-        // enabled to allow continuous start of chips in the chain
-        self.configure_hash_chain(TARGET_CHIP_BAUD_RATE, false, true)
-            .await?;
-        self.set_ip_core_baud_rate(TARGET_CHIP_BAUD_RATE)?;
+        // configure the hashing chain to operate at desired baud rate
+        self.switch_baud_rate(self.target_baud_rate).await?;
 
         self.set_asic_diff(self.asic_difficulty).await?;
 
@@ -414,10 +447,32 @@ impl HashChain {
         accept_less_chips: bool,
     ) -> error::Result<Arc<Mutex<registry::WorkRegistry>>> {
         info!("Hashboard IP core initialized");
+        let hashboard_idx = self.hashboard_idx;
         self.voltage_ctrl
             .clone()
             .init(self.halt_receiver.clone())
-            .await?;
+            .await
+            .context(ErrorKind::PowerFailure(hashboard_idx))?;
+        self.board_info = hashboard::Info::read(&self.voltage_ctrl).await;
+        info!(
+            "Hashboard {}: serial {:?}, chip bin {:?}",
+            self.hashboard_idx, self.board_info.serial, self.board_info.chip_bin
+        );
+        let profile =
+            hashboard::Profile::select(&self.chip_bin_profiles, &self.board_info).cloned();
+        let (initial_frequency, initial_voltage) = match profile.as_ref() {
+            Some(profile) => {
+                info!(
+                    "Hashboard {}: using profile of chip bin {} ({} MHz, {})",
+                    self.hashboard_idx,
+                    profile.chip_bin,
+                    profile.frequency.max() / 1_000_000,
+                    profile.voltage
+                );
+                (&profile.frequency, profile.voltage)
+            }
+            None => (initial_frequency, initial_voltage),
+        };
 
         info!(
             "Initializing hash chain {}, (difficulty {})",
@@ -451,40 +506,40 @@ impl HashChain {
             .await
             .expect("lowering voltage failed");
 
-        // return work registry we created
+        if self.self_test_enabled {
+            let report = self.run_self_test(work_registry.clone()).await?;
+            if report.count(selftest::ChipStatus::Failed) > 0 {
+                warn!("Self-test of chain {}: {}", self.hashboard_idx, report);
+            } else {
+                info!("Self-test of chain {}: {}", self.hashboard_idx, report);
+            }
+            self.self_test_report.replace(report);
+        }
+
+        // keep work registry for statistics and return it
+        self.work_registry.replace(work_registry.clone());
         Ok(work_registry)
     }
 
-    /// Detects the number of chips on the hashing chain and assigns an address to each chip
-    async fn enumerate_chips(&mut self) -> error::Result<()> {
+    /// Detects chips on the hashing chain, assigns an address to each chip and returns the list
+    /// of detected chips
+    async fn enumerate_chips(&mut self) -> error::Result<Vec<bm1387::ChipInfo>> {
         // Enumerate all chips (broadcast read address register request)
         let responses = self
             .command_context
-            .read_register::<bm1387::GetAddressReg>(ChipAddress::All)
+            .read_all_registers::<bm1387::GetAddressReg>()
             .await?;
 
-        // Reset chip count (we might get called multiple times)
-        self.chip_count = 0;
         // Check if are responses meaningful
-        for (address, addr_reg) in responses.iter().enumerate() {
-            if addr_reg.chip_rev != bm1387::CHIP_REV_BM1387 {
-                Err(ErrorKind::ChipEnumeration(format!(
-                    "unexpected revision of chip {} (expected: {:#x?} received: {:#x?})",
-                    address,
-                    bm1387::CHIP_REV_BM1387,
-                    addr_reg.chip_rev,
-                )))?
-            }
-            self.chip_count += 1;
-        }
-        if self.chip_count >= MAX_CHIPS_ON_CHAIN {
+        let chips = bm1387::ChipInfo::from_responses(&responses)?;
+        if chips.len() >= MAX_CHIPS_ON_CHAIN {
             Err(ErrorKind::ChipEnumeration(format!(
                 "detected {} chips, expected less than {} chips on one chain. Possibly a hardware issue?",
-                self.chip_count,
+                chips.len(),
                 MAX_CHIPS_ON_CHAIN,
             )))?
         }
-        if self.chip_count == 0 {
+        if chips.is_empty() {
             Err(ErrorKind::ChipEnumeration(
                 "no chips detected on the current chain".to_string(),
             ))?
@@ -502,14 +557,14 @@ impl HashChain {
         }
 
         // Assign address to each chip
-        for i in 0..self.chip_count {
-            let cmd = bm1387::SetChipAddressCmd::new(ChipAddress::One(i));
+        for chip in chips.iter() {
+            let cmd = bm1387::SetChipAddressCmd::new(ChipAddress::One(chip.address));
             self.command_context
                 .send_raw_command(cmd.pack().to_vec(), false)
                 .await;
         }
 
-        Ok(())
+        Ok(chips)
     }
 
     /// Loads PLL register with a starting value
@@ -551,15 +606,16 @@ impl HashChain {
         } else {
             // Update chips one-by-one
             for i in 0..self.chip_count {
-                let new_freq = self.frequency.lock().await.chip[i];
-                if new_freq != frequency.chip[i] {
-                    self.set_chip_pll(ChipAddress::One(i), new_freq).await?;
+                let new_freq = frequency.chip[i];
+                if new_freq != self.frequency.lock().await.chip[i] {
+                    self.set_chip_pll(ChipAddress::unicast(i)?, new_freq)
+                        .await?;
                 }
             }
         }
 
         // Update worktime
-        self.set_work_time(frequency.max()).await;
+        self.set_work_time(frequency);
 
         // Remember what frequencies are set
         let mut cur_frequency = self.frequency.lock().await;
@@ -570,6 +626,183 @@ impl HashChain {
         Ok(())
     }
 
+    /// Sends work with known solutions to chips and checks that the expected solutions come
+    /// back. Generated work is sent as well and every chip has to find valid solutions of it in
+    /// its nonce subrange (see `selftest` module). FIFO failures are reported as failure of the
+    /// chain.
+    async fn run_self_test(
+        &mut self,
+        work_registry: Arc<Mutex<registry::WorkRegistry>>,
+    ) -> error::Result<selftest::Report> {
+        let midstate_count = self.midstate_count.to_count();
+        let vectors = selftest::vectors(midstate_count);
+        let generated_work = selftest::generated_work(midstate_count, self.chip_count);
+        let mut report = selftest::Report::new(self.chip_count);
+
+        // store the work as "initial work" so that solutions received after the self-test are
+        // ignored
+        let mut all_work = vec![];
+        let (vector_work_ids, generated_work_ids) = {
+            let mut work_registry = work_registry.lock().await;
+            let mut store_work = |work: &work::Assignment| {
+                let work_id = work_registry.store_work(work.clone(), true);
+                all_work.push((work.clone(), work_id));
+                work_id
+            };
+            let vector_work_ids: Vec<_> = vectors.iter().map(|v| store_work(&v.work)).collect();
+            let generated_work_ids: Vec<_> = generated_work.iter().map(&mut store_work).collect();
+            for _ in 0..selftest::FLUSH_WORK_COUNT {
+                store_work(&null_work::prepare_opencore(true, midstate_count));
+            }
+            (vector_work_ids, generated_work_ids)
+        };
+        trace!("Sending out {} self-test work items", all_work.len());
+
+        // chips have to report even low difficulty solutions of the generated work
+        self.set_asic_diff(selftest::DIFFICULTY).await?;
+        let target = ii_bitcoin::Target::from_pool_difficulty(selftest::DIFFICULTY);
+
+        let mut work_tx_io = self.work_tx_io.lock().await;
+        let tx_fifo = work_tx_io.as_mut().expect("BUG: work-tx io missing");
+        let mut work_rx_io = self.work_rx_io.lock().await;
+        let rx_fifo = work_rx_io.as_mut().expect("BUG: work-rx io missing");
+        let sending_done = AtomicBool::new(false);
+
+        // solutions are received while the work is being sent so that they do not overflow
+        // the RX FIFO
+        let send_work = async {
+            for (work, work_id) in all_work.iter() {
+                if let Err(e) = tx_fifo.wait_for_room().await {
+                    self.report_failure(format!(
+                        "self-test: waiting for TX FIFO room failed: {}",
+                        e
+                    ));
+                    break;
+                }
+                if let Err(e) = tx_fifo.send_work(work, *work_id).await {
+                    self.report_failure(format!("self-test: sending work failed: {}", e));
+                    break;
+                }
+            }
+            sending_done.store(true, Ordering::Relaxed);
+        };
+
+        let mut solved = vec![false; vectors.len()];
+        let mut chip_solved = vec![false; self.chip_count];
+        let receive_solutions = async {
+            loop {
+                let hw_solution = match rx_fifo
+                    .recv_solution()
+                    .timeout(selftest::SOLUTION_TIMEOUT)
+                    .await
+                {
+                    Ok(Ok(hw_solution)) => hw_solution,
+                    Ok(Err(e)) => {
+                        self.report_failure(format!("self-test: receiving solution failed: {}", e));
+                        break;
+                    }
+                    // no more solutions are expected after all work has been sent
+                    Err(_) if sending_done.load(Ordering::Relaxed) => break,
+                    Err(_) => continue,
+                };
+                let work_id = hw_solution.hardware_id as usize;
+                let chip = bm1387::CoreAddress::new(hw_solution.nonce).chip;
+                if let Some(vector_idx) = vector_work_ids.iter().position(|&id| id == work_id) {
+                    let vector = &vectors[vector_idx];
+                    let solution = work::Solution::new(
+                        vector.work.clone(),
+                        Solution::from_hw_solution(&hw_solution, target),
+                        None,
+                    );
+                    if solution.nonce() == vector.nonce {
+                        solved[vector_idx] = true;
+                    } else if !solution.hash().meets(solution.backend_target()) {
+                        // the work may have other valid nonces than the expected one
+                        report.account(chip, false);
+                    }
+                } else if let Some(work_idx) =
+                    generated_work_ids.iter().position(|&id| id == work_id)
+                {
+                    let solution = work::Solution::new(
+                        generated_work[work_idx].clone(),
+                        Solution::from_hw_solution(&hw_solution, target),
+                        None,
+                    );
+                    if solution.hash().meets(solution.backend_target()) {
+                        if let Some(chip_solved) = chip_solved.get_mut(chip) {
+                            *chip_solved = true;
+                        }
+                    } else {
+                        report.account(chip, false);
+                    }
+                }
+                // otherwise it is a solution of initial work
+            }
+        };
+        future::join(send_work, receive_solutions).await;
+
+        for (vector, &solved) in vectors.iter().zip(solved.iter()) {
+            report.account(vector.chip(), solved);
+        }
+        for (chip, &solved) in chip_solved.iter().enumerate() {
+            report.account(chip, solved);
+        }
+        drop(work_tx_io);
+        drop(work_rx_io);
+        self.set_asic_diff(self.asic_difficulty).await?;
+        Ok(report)
+    }
+
+    /// Results of the last self-test of chips (if it has been run)
+    pub fn get_self_test_report(&self) -> Option<&selftest::Report> {
+        self.self_test_report.as_ref()
+    }
+
+    /// Reads back all IP core registers and a selected set of chip registers
+    pub async fn dump_registers(&self) -> error::Result<diagnostics::Report> {
+        let ip_core = self.common_io.dump_registers()?;
+        let chips = diagnostics::read_chip_registers(&self.command_context).await;
+        Ok(diagnostics::Report {
+            hashboard_idx: self.hashboard_idx,
+            chip_count: self.chip_count,
+            ip_core,
+            chips,
+        })
+    }
+
+    /// Raises frequency of chips to `frequency` in steps of the frequency ramp (if enabled).
+    /// The chain settles after each step and it continues only when all chips respond.
+    async fn ramp_frequency(&self, frequency: &FrequencySettings) -> error::Result<()> {
+        let ramp = match self.frequency_ramp.as_ref() {
+            Some(ramp) => ramp,
+            None => return self.set_pll(frequency).await,
+        };
+        let steps = ramp.steps(frequency);
+        let step_count = steps.len();
+        for (i, step) in steps.iter().enumerate() {
+            self.set_pll(step).await?;
+            if i + 1 < step_count {
+                delay_for(ramp.delay).await;
+                self.verify_communication().await.with_context(|_| {
+                    ErrorKind::Hashboard(
+                        self.hashboard_idx,
+                        format!("frequency ramp failed at {}", step),
+                    )
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Load PLL register of one chip, the other chips keep their current frequency
+    ///
+    /// Takes care of adjusting `work_time`
+    pub async fn set_chip_frequency(&self, chip_idx: usize, freq: usize) -> error::Result<()> {
+        let mut frequency = self.frequency.lock().await.clone();
+        frequency.set_chip_frequency(chip_idx, freq)?;
+        self.set_pll(&frequency).await
+    }
+
     /// Configure all chips in the hash chain
     ///
     /// This method programs the MiscCtrl register of each chip in the hash chain.
@@ -602,10 +835,50 @@ impl HashChain {
             bm1387::MiscCtrlReg::new(not_set_baud, true, baud_clock_div, gate_block, true)?;
         // Do not read back the MiscCtrl register when setting baud rate: it will result
         // in serial speed mismatch and nothing being read.
+        self.command_context.broadcast_register(&ctl_reg).await?;
+        Ok(actual_baud_rate)
+    }
+
+    /// Switches communication with chips from the current baud rate to `baud_rate`. Chips are
+    /// reconfigured first and the IP core follows. Communication is then verified by reading
+    /// the address register of all chips and when some chip does not respond, chips and IP core
+    /// are switched back to the previous baud rate.
+    /// Returns actual baud rate that has been set on the chips or an error
+    pub async fn switch_baud_rate(&mut self, baud_rate: usize) -> error::Result<usize> {
+        let previous_baud_rate = self.baud_rate;
+        // Note that gate block is enabled to allow continuous start of chips in the chain
+        let actual_baud_rate = self.configure_hash_chain(baud_rate, false, true).await?;
+        self.set_ip_core_baud_rate(baud_rate)?;
+
+        match self.verify_communication().await {
+            Ok(_) => {
+                self.baud_rate = baud_rate;
+                Ok(actual_baud_rate)
+            }
+            Err(e) => {
+                warn!(
+                    "Chain {}: communication at {} baud failed ({}), switching back to {} baud",
+                    self.hashboard_idx, baud_rate, e, previous_baud_rate
+                );
+                // chips which have been switched understand only the new baud rate
+                self.configure_hash_chain(previous_baud_rate, false, true)
+                    .await?;
+                self.set_ip_core_baud_rate(previous_baud_rate)?;
+                self.verify_communication().await?;
+                Err(ErrorKind::BaudRate(format!(
+                    "communication at {} baud failed: {}",
+                    baud_rate, e
+                )))?
+            }
+        }
+    }
+
+    /// Checks that all chips respond to a register read
+    async fn verify_communication(&self) -> error::Result<()> {
         self.command_context
-            .write_register(ChipAddress::All, &ctl_reg)
+            .read_all_registers::<bm1387::GetAddressReg>()
             .await?;
-        Ok(actual_baud_rate)
+        Ok(())
     }
 
     /// This method only changes the communication speed of the FPGA IP core with the chips.
@@ -628,6 +901,11 @@ impl HashChain {
         self.chip_count
     }
 
+    /// Returns chips detected during the last enumeration
+    pub fn get_chips(&self) -> &[bm1387::ChipInfo] {
+        &self.chips
+    }
+
     /// Initialize cores by sending open-core work with correct nbits to each core
     async fn send_init_work(&mut self, work_registry: Arc<Mutex<registry::WorkRegistry>>) {
         // Each core gets one work
@@ -645,7 +923,7 @@ impl HashChain {
             // solutions
             let work_id = work_registry.lock().await.store_work(work.clone(), true);
             tx_fifo.wait_for_room().await.expect("wait for tx room");
-            tx_fifo.send_work(&work, work_id).expect("send work");
+            tx_fifo.send_work(&work, work_id).await.expect("send work");
         }
     }
 
@@ -653,22 +931,60 @@ impl HashChain {
     /// registry (to pair with `Assignment` later) and sends it out to hw.
     /// It makes sure that TX fifo is empty before requesting work from
     /// generator.
-    /// It exits when generator returns `None`.
-    async fn work_tx_task(
+    /// It exits when generator returns `None` or when the chain is being shut down.
+    /// The task exits on FPGA I/O failure which is reported for recovery of the chain.
+    async fn work_tx_task<T: io::WorkSink>(
+        self: Arc<Self>,
         work_registry: Arc<Mutex<registry::WorkRegistry>>,
-        mut tx_fifo: io::WorkTx,
+        tx_fifo: T,
         mut work_generator: work::Generator,
     ) {
+        let mut tx_fifo = io::WorkTxSink::new(tx_fifo);
+        let mut next_retire_expired = Instant::now();
         loop {
-            tx_fifo.wait_for_room().await.expect("wait for tx room");
+            let now = Instant::now();
+            if now >= next_retire_expired {
+                work_registry.lock().await.retire_expired(now);
+                next_retire_expired = now + registry::RETIRE_EXPIRED_INTERVAL;
+            }
+            if self.idle.load(Ordering::Relaxed) {
+                if !self.work_tx_enabled.load(Ordering::Relaxed) {
+                    return;
+                }
+                delay_for(IDLE_POLL_INTERVAL).await;
+                continue;
+            }
+            if let Err(e) = tx_fifo.wait_for_room().await {
+                self.report_failure(format!("waiting for TX FIFO room failed: {}", e));
+                return;
+            }
+            self.waiting_for_work.store(true, Ordering::Relaxed);
             let work = work_generator.generate().await;
+            self.waiting_for_work.store(false, Ordering::Relaxed);
+            if !self.work_tx_enabled.load(Ordering::Relaxed) {
+                return;
+            }
             match work {
                 None => return,
-                Some(work) => {
-                    // assign `work_id` to `work`
-                    let work_id = work_registry.lock().await.store_work(work.clone(), false);
-                    // send work is synchronous
-                    tx_fifo.send_work(&work, work_id).expect("send work");
+                Some(mut work) => {
+                    // work is generated with the configured number of midstates which can be
+                    // higher than the current one (see `Manager::set_midstate_count`)
+                    work.midstates.truncate(self.midstate_count.to_count());
+                    let work_id = {
+                        let mut work_registry = work_registry.lock().await;
+                        // work queued in TX FIFO before new block cannot produce useful
+                        // solutions anymore, so drop it instead of keeping the chips busy
+                        if work_registry.is_last_work_expired(Instant::now()) {
+                            tx_fifo.purge();
+                        }
+                        // assign `work_id` to `work`
+                        work_registry.store_work(work.clone(), false)
+                    };
+                    if let Err(e) = tx_fifo.send((work, work_id)).await {
+                        self.report_failure(format!("sending work failed: {}", e));
+                        return;
+                    }
+                    self.work_count.fetch_add(1, Ordering::Relaxed);
                 }
             }
         }
@@ -679,24 +995,51 @@ impl HashChain {
     /// sends them back to frontend (via `solution_sender`).
     /// If solution is duplicated, it gets dropped (and errors stats incremented).
     /// It prints warnings when solution doesn't hit ASIC target.
+    /// Suspected hardware faults detected from statistics of solutions are reported as alerts.
+    /// The task exits on FPGA I/O failure which is reported for recovery of the chain.
     /// TODO: this task is not very platform dependent, maybe move it somewhere else?
     /// TODO: figure out when and how to stop this task
-    async fn solution_rx_task(
+    async fn solution_rx_task<R: io::SolutionSource>(
         self: Arc<Self>,
         work_registry: Arc<Mutex<registry::WorkRegistry>>,
-        mut rx_fifo: io::WorkRx,
+        rx_fifo: R,
         solution_sender: work::SolutionSender,
         counter: Arc<Mutex<counters::HashChain>>,
+        alert_sender: alert::Sender,
+        chip_meters: stats::ChipMeters,
     ) {
+        let mut fault_detector = fault::Detector::new();
+        let mut solutions = io::SolutionRxStream::new(rx_fifo);
         // solution receiving/filtering part
-        loop {
-            let (rx_fifo_out, hw_solution) =
-                rx_fifo.recv_solution().await.expect("recv solution failed");
-            rx_fifo = rx_fifo_out;
+        while let Some(hw_solution) = solutions.next().await {
+            let hw_solution = match hw_solution {
+                Ok(hw_solution) => hw_solution,
+                Err(e) => {
+                    self.report_failure(format!("receiving solution failed: {}", e));
+                    return;
+                }
+            };
+            self.solution_count.fetch_add(1, Ordering::Relaxed);
+            // solutions of isolated chips are dropped without being accounted, the chips keep
+            // hashing their nonce subranges which are not reassigned (see `isolation` module)
+            if self.is_chip_isolated(bm1387::CoreAddress::new(hw_solution.nonce).chip) {
+                continue;
+            }
             let work_id = hw_solution.hardware_id;
             let solution = Solution::from_hw_solution(&hw_solution, self.asic_target);
             let mut work_registry = work_registry.lock().await;
 
+            // work ID which has never been assigned means that the response is corrupted
+            // even though it passed the CRC check
+            if work_id as usize >= work_registry.size() {
+                warn!(
+                    "Chain {}: dropping malformed solution with work ID {:#x}",
+                    self.hashboard_idx, work_id
+                );
+                counter.lock().await.add_rx_errors(1);
+                continue;
+            }
+
             let work = work_registry.find_work(work_id as usize);
             match work {
                 Some(work_item) => {
@@ -704,24 +1047,57 @@ impl HashChain {
                     if work_item.initial_work {
                         continue;
                     }
-                    let core_addr = bm1387::CoreAddress::new(solution.nonce);
+                    let nonce = solution.nonce;
+                    let core_addr = bm1387::CoreAddress::new(nonce);
                     let status = work_item.insert_solution(solution);
 
                     // work item detected a new unique solution, we will push it for further processing
                     if let Some(unique_solution) = status.unique_solution {
                         if !status.duplicate {
-                            let hash = unique_solution.hash();
-                            if !hash.meets(unique_solution.backend_target()) {
-                                info!("Solution from hashchain not hitting ASIC target; {}", hash);
+                            if !unique_solution.has_valid_midstate() {
+                                // the header hash cannot be recomputed, the hub drops the solution
+                                // and accounts it as a hardware error too
+                                info!(
+                                    "Solution from hashchain with invalid midstate {}",
+                                    unique_solution.midstate_idx()
+                                );
+                                counter.lock().await.add_error(core_addr);
+                            } else if !unique_solution
+                                .hash()
+                                .meets(unique_solution.backend_target())
+                            {
+                                info!(
+                                    "Solution from hashchain not hitting ASIC target; {}",
+                                    unique_solution.hash()
+                                );
                                 counter.lock().await.add_error(core_addr);
                             } else {
                                 counter.lock().await.add_valid(core_addr);
+                                chip_meters
+                                    .account_solution(
+                                        core_addr.chip,
+                                        unique_solution.backend_target(),
+                                        Instant::now(),
+                                    )
+                                    .await;
                             }
                             solution_sender.send(unique_solution);
+                            for fault in fault_detector.account_solution(nonce, Instant::now()) {
+                                warn!(
+                                    "Suspected hardware fault on chain {}: {}",
+                                    self.hashboard_idx, fault
+                                );
+                                alert_sender.notify(fault.to_alert_event(self.hashboard_idx));
+                            }
                         }
                     }
                     if status.duplicate {
-                        counter.lock().await.add_error(core_addr);
+                        debug!(
+                            "Chain {}: suppressed duplicate nonce {:#010x} of chip {}",
+                            self.hashboard_idx, nonce, core_addr.chip
+                        );
+                        counter.lock().await.add_duplicate(core_addr);
+                        fault_detector.account_duplicate();
                     }
                     if status.mismatched_nonce {
                         counter.lock().await.add_error(core_addr);
@@ -739,9 +1115,11 @@ impl HashChain {
 
     async fn try_to_initialize_sensor(
         command_context: command::Context,
+        chip_idx: usize,
     ) -> error::Result<Box<dyn sensor::Sensor>> {
         // construct I2C bus via command interface
-        let i2c_bus = bm1387::i2c::Bus::new_and_init(command_context, TEMP_CHIP)
+        let chip_address = ChipAddress::unicast(chip_idx)?;
+        let i2c_bus = bm1387::i2c::Bus::new_and_init(command_context, chip_address)
             .await
             .with_context(|_| ErrorKind::Sensors("bus construction failed".into()))?;
 
@@ -792,17 +1170,33 @@ impl HashChain {
 
         // Try to probe sensor
         // This may fail - in which case we put `None` into `sensor`
-        let mut sensor = match Self::try_to_initialize_sensor(self.command_context.clone())
-            .await
-            .with_context(|_| ErrorKind::Hashboard(self.hashboard_idx, "sensor error".into()))
-            .map_err(|e| e.into())
-        {
-            error::Result::Err(e) => {
-                error!("Sensor probing failed: {}", e);
-                None
+        let mut sensor =
+            match Self::try_to_initialize_sensor(self.command_context.clone(), TEMP_CHIP)
+                .await
+                .with_context(|_| ErrorKind::Hashboard(self.hashboard_idx, "sensor error".into()))
+                .map_err(|e| e.into())
+            {
+                error::Result::Err(e) => {
+                    error!("Sensor probing failed: {}", e);
+                    None
+                }
+                error::Result::Ok(sensor) => Some(sensor),
+            };
+
+        // Probe sensors of other chips, the ones which fail are not read at all
+        let mut chip_sensors = vec![];
+        for &chip_idx in self.chip_temp_sensors.iter() {
+            if chip_idx == TEMP_CHIP || chip_idx >= self.chip_count {
+                continue;
             }
-            error::Result::Ok(sensor) => Some(sensor),
-        };
+            match Self::try_to_initialize_sensor(self.command_context.clone(), chip_idx).await {
+                Ok(chip_sensor) => chip_sensors.push((chip_idx, chip_sensor)),
+                Err(e) => error!(
+                    "Chain {}: sensor probing of chip {} failed: {}",
+                    self.hashboard_idx, chip_idx, e
+                ),
+            }
+        }
 
         // "Watchdog" loop that pings monitor every some seconds
         loop {
@@ -830,6 +1224,30 @@ impl HashChain {
                 sensor::INVALID_TEMPERATURE_READING
             };
 
+            // Read sensors of other chips
+            let mut chip_temperatures = vec![ChipTemperature {
+                chip_idx: TEMP_CHIP,
+                temperature: temp.clone(),
+            }];
+            for (chip_idx, chip_sensor) in chip_sensors.iter_mut() {
+                let temperature = chip_sensor.read_temperature().await.unwrap_or_else(|e| {
+                    warn!(
+                        "Chain {}: temperature read of chip {} failed: {}",
+                        self.hashboard_idx, chip_idx, e
+                    );
+                    sensor::INVALID_TEMPERATURE_READING
+                });
+                chip_temperatures.push(ChipTemperature {
+                    chip_idx: *chip_idx,
+                    temperature,
+                });
+            }
+            chip_temperatures.sort_by_key(|chip| chip.chip_idx);
+            *self
+                .chip_temperatures
+                .lock()
+                .expect("BUG: failed to lock chip temperatures") = chip_temperatures;
+
             // Broadcast
             temperature_sender
                 .broadcast(Some(temp.clone()))
@@ -855,7 +1273,7 @@ impl HashChain {
 
             let responses = self
                 .command_context
-                .read_register::<bm1387::HashrateReg>(ChipAddress::All)
+                .read_all_registers::<bm1387::HashrateReg>()
                 .await
                 .expect("reading hashrate_reg failed");
 
@@ -877,6 +1295,8 @@ impl HashChain {
         work_generator: work::Generator,
         solution_sender: work::SolutionSender,
         work_registry: Arc<Mutex<registry::WorkRegistry>>,
+        alert_sender: alert::Sender,
+        chip_meters: stats::ChipMeters,
     ) {
         // spawn tx task
         let tx_fifo = self.take_work_tx_io().await;
@@ -884,6 +1304,7 @@ impl HashChain {
             .register_client("work-tx".into())
             .await
             .spawn(Self::work_tx_task(
+                self.clone(),
                 work_registry.clone(),
                 tx_fifo,
                 work_generator,
@@ -900,6 +1321,8 @@ impl HashChain {
                 rx_fifo,
                 solution_sender,
                 self.counter.clone(),
+                alert_sender,
+                chip_meters,
             ));
 
         // spawn hashrate monitor
@@ -916,26 +1339,234 @@ impl HashChain {
             .register_client("temperature monitor".into())
             .await
             .spawn(Self::monitor_watchdog_temp_task(self.clone()));
+
+        // spawn hardware error monitor
+        self.halt_receiver
+            .register_client("hardware error monitor".into())
+            .await
+            .spawn(Self::hw_error_monitor_task(self.clone()));
+    }
+
+    /// Periodically accounts responses dropped due to CRC mismatch, reports chips with too many
+    /// hardware errors or nonces outside of chip subranges and isolates faulty chips
+    async fn hw_error_monitor_task(self: Arc<Self>) {
+        let mut last_crc_errors = self.common_io.get_crc_error_count();
+        let mut last_rx_errors = self.command_context.rx_error_count().await;
+        let mut last_counter = self.snapshot_counter().await;
+        loop {
+            delay_for(HW_ERROR_CHECK_INTERVAL).await;
+
+            let crc_errors = self.common_io.get_crc_error_count();
+            let new_crc_errors = crc_errors.wrapping_sub(last_crc_errors);
+            last_crc_errors = crc_errors;
+            let rx_errors = self.command_context.rx_error_count().await;
+            let new_rx_errors = rx_errors - last_rx_errors;
+            last_rx_errors = rx_errors;
+            let counter = {
+                let mut counter = self.counter.lock().await;
+                counter.add_crc_errors(new_crc_errors as usize);
+                counter.add_rx_errors(new_rx_errors);
+                counter.snapshot()
+            };
+            if new_crc_errors > 0 {
+                warn!(
+                    "Chain {}: {} response(s) dropped due to CRC mismatch",
+                    self.hashboard_idx, new_crc_errors
+                );
+            }
+            // counters reset in the meantime are compared to zero
+            let new_subrange_errors = if counter.started == last_counter.started {
+                counter
+                    .subrange_errors
+                    .saturating_sub(last_counter.subrange_errors)
+            } else {
+                counter.subrange_errors
+            };
+            if new_subrange_errors > 0 {
+                warn!(
+                    "Chain {}: {} nonce(s) outside of subranges of {} enumerated chips, chip \
+                     addressing may be broken",
+                    self.hashboard_idx,
+                    new_subrange_errors,
+                    counter.chip_count()
+                );
+            }
+            let chip_delta = counter.chip_delta(&last_counter);
+            for (chip_idx, chip) in chip_delta.iter().enumerate() {
+                if let Some(error_rate) = chip.error_rate(counter.asic_difficulty) {
+                    if error_rate > HW_ERROR_RATE_WARN {
+                        warn!(
+                            "Chain {}: chip {} has {:.1}% hardware errors ({} errors)",
+                            self.hashboard_idx, chip_idx, error_rate, chip.errors
+                        );
+                    }
+                }
+            }
+            if let Some(detector) = self
+                .isolation
+                .lock()
+                .expect("BUG: failed to lock mutex")
+                .as_mut()
+            {
+                for (chip_idx, reason) in detector.account(&chip_delta, counter.asic_difficulty) {
+                    warn!(
+                        "Chain {}: isolating chip {} ({}), its solutions are dropped from now on",
+                        self.hashboard_idx, chip_idx, reason
+                    );
+                }
+            }
+            last_counter = counter;
+        }
     }
 
     pub async fn reset_counter(&self) {
         self.counter.lock().await.reset();
     }
 
+    /// Returns true when solutions of the chip are dropped due to its isolation. The chip itself
+    /// is not reconfigured, isolation only filters its solutions.
+    pub fn is_chip_isolated(&self, chip_idx: usize) -> bool {
+        self.isolation
+            .lock()
+            .expect("BUG: failed to lock mutex")
+            .as_ref()
+            .map(|detector| detector.is_isolated(chip_idx))
+            .unwrap_or(false)
+    }
+
+    /// Idle chain stays initialized but it does not take any work so that it can be resumed quickly
+    pub fn set_idle(&self, idle: bool) {
+        self.idle.store(idle, Ordering::Relaxed);
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.idle.load(Ordering::Relaxed)
+    }
+
+    pub fn work_count(&self) -> usize {
+        self.work_count.load(Ordering::Relaxed)
+    }
+
+    pub fn is_waiting_for_work(&self) -> bool {
+        self.waiting_for_work.load(Ordering::Relaxed)
+    }
+
+    /// Shuts the chain down gracefully: stops sending new work, waits (at most
+    /// `SHUTDOWN_DRAIN_TIMEOUT`) for solutions of work already sent to chips, disables the IP core
+    /// and halts all tasks of the chain which also powers down the voltage regulator
+    async fn shutdown(&self) {
+        info!("Shutting down hash chain {}", self.hashboard_idx);
+        self.work_tx_enabled.store(false, Ordering::Relaxed);
+
+        let deadline = Instant::now() + SHUTDOWN_DRAIN_TIMEOUT;
+        let mut solution_count = self.solution_count.load(Ordering::Relaxed);
+        while Instant::now() < deadline {
+            delay_for(SHUTDOWN_QUIET_PERIOD).await;
+            let current_count = self.solution_count.load(Ordering::Relaxed);
+            if current_count == solution_count {
+                break;
+            }
+            solution_count = current_count;
+        }
+
+        self.common_io.disable_ip_core();
+        self.halt_sender.clone().send_halt().await;
+    }
+
+    fn report_failure(&self, reason: String) {
+        error!("Chain {}: {}", self.hashboard_idx, reason);
+        self.failure
+            .lock()
+            .expect("BUG: failed to lock mutex")
+            .get_or_insert(reason);
+    }
+
+    /// Returns reason of FPGA I/O failure if the chain stopped working due to it
+    pub fn get_failure(&self) -> Option<String> {
+        self.failure
+            .lock()
+            .expect("BUG: failed to lock mutex")
+            .clone()
+    }
+
     pub async fn snapshot_counter(&self) -> counters::HashChain {
         self.counter.lock().await.snapshot()
     }
 
-    pub async fn get_frequency(&self) -> FrequencySettings {
+    /// Statistics of work retired from the work registry
+    pub async fn get_work_stats(&self) -> registry::WorkStats {
+        match self.work_registry.as_ref() {
+            Some(work_registry) => work_registry.lock().await.stats(),
+            None => Default::default(),
+        }
+    }
+}
+
+#[async_trait]
+impl ChainControl for HashChain {
+    async fn reset_and_enumerate(&mut self, accept_less_chips: bool) -> error::Result<()> {
+        // Reset hashboard, toggle voltage
+        info!("Resetting hash board");
+        self.common_io.disable_ip_core();
+        self.power_sequencer.bring_up().await?;
+        self.common_io.enable_ip_core();
+
+        // Enumerate chips
+        info!("Starting chip enumeration");
+        let crc_errors = self.common_io.get_crc_error_count();
+        self.chips = self.enumerate_chips().await?;
+        self.chip_count = self.chips.len();
+
+        // Figure out if we found enough chips
+        info!("Discovered {} chips", self.chip_count);
+        self.command_context.set_chip_count(self.chip_count).await;
+        self.counter.lock().await.set_chip_count(self.chip_count);
+        self.frequency.lock().await.set_chip_count(self.chip_count);
+
+        // If we don't have full number of chips and we do not want incomplete chain, then raise
+        // an error
+        if self.chip_count < EXPECTED_CHIPS_ON_CHAIN && !accept_less_chips {
+            let mismatch = ErrorKind::ChipCountMismatch(EXPECTED_CHIPS_ON_CHAIN, self.chip_count);
+            // replies of missing chips may have been dropped due to CRC mismatch so the number
+            // of dropped responses is attached to the error as its cause
+            let new_crc_errors = self
+                .common_io
+                .get_crc_error_count()
+                .wrapping_sub(crc_errors);
+            if new_crc_errors > 0 {
+                let crc_error = ErrorKind::CrcError {
+                    counter: new_crc_errors as usize,
+                };
+                warn!(
+                    "Hashboard {}: {} during enumeration",
+                    self.hashboard_idx, crc_error
+                );
+                return Err(error::Error::with_source(mismatch, crc_error));
+            }
+            Err(mismatch)?;
+        }
+
+        Ok(())
+    }
+
+    async fn get_frequency(&self) -> FrequencySettings {
         self.frequency.lock().await.clone()
     }
 
-    pub async fn get_voltage(&self) -> power::Voltage {
+    async fn set_frequency(&self, frequency: &FrequencySettings) -> error::Result<()> {
+        self.set_pll(frequency).await
+    }
+
+    async fn get_voltage(&self) -> power::Voltage {
         self.voltage_ctrl
             .get_current_voltage()
             .await
             .expect("BUG: no voltage on hashchain")
     }
+
+    async fn set_voltage(&self, voltage: power::Voltage) -> error::Result<()> {
+        self.voltage_ctrl.set_voltage(voltage).await
+    }
 }
 
 impl fmt::Debug for HashChain {
@@ -952,7 +1583,7 @@ impl fmt::Display for HashChain {
 
 type Frequency = usize;
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct FrequencySettings {
     pub chip: Vec<Frequency>,
 }
@@ -970,6 +1601,21 @@ impl FrequencySettings {
         self.chip.resize(chip_count, 0);
     }
 
+    /// Change frequency of one chip. The frequency has to be within the range supported by the
+    /// chip PLL.
+    pub fn set_chip_frequency(&mut self, chip_idx: usize, frequency: usize) -> error::Result<()> {
+        bm1387::PllFrequency::lookup_freq(frequency)?;
+        let chip_count = self.chip.len();
+        let chip_frequency = self.chip.get_mut(chip_idx).ok_or_else(|| {
+            ErrorKind::Hashchip(format!(
+                "chip {} out of range (there are {} chips)",
+                chip_idx, chip_count
+            ))
+        })?;
+        *chip_frequency = frequency;
+        Ok(())
+    }
+
     pub fn total(&self) -> u64 {
         self.chip.iter().fold(0, |total_f, &f| total_f + f as u64)
     }
@@ -984,6 +1630,16 @@ impl FrequencySettings {
         *self.chip.iter().max().expect("BUG: no chips on chain")
     }
 
+    /// Maximum frequency of the first `chip_count` chips
+    pub fn max_of(&self, chip_count: usize) -> usize {
+        *self
+            .chip
+            .iter()
+            .take(chip_count)
+            .max()
+            .expect("BUG: no chips on chain")
+    }
+
     pub fn avg(&self) -> usize {
         assert!(self.chip.len() > 0, "BUG: no chips on chain");
         let sum: u64 = self.chip.iter().map(|frequency| *frequency as u64).sum();
@@ -1075,7 +1731,10 @@ impl StoppedChain {
                 Err(e) => {
                     error!("Chain {} start failed: {}", self.manager.hashboard_idx, e);
 
-                    // retry if possible
+                    // retry if possible, there is no point in retrying without the hashboard
+                    if let ErrorKind::ChainNotFound(_) = e.kind() {
+                        return Err((self, e));
+                    }
                     if tries_left == 0 {
                         error!("No tries left");
                         return Err((self, e.into()));
@@ -1124,7 +1783,7 @@ impl RunningChain {
     }
 
     pub async fn stop(self) -> StoppedChain {
-        self.manager.stop_chain(false).await;
+        self.manager.stop_chain(false, false).await;
 
         StoppedChain {
             manager: self.manager.clone(),
@@ -1142,6 +1801,16 @@ impl RunningChain {
             .await
     }
 
+    pub async fn get_board_info(&self) -> hashboard::Info {
+        let inner = self.manager.inner.lock().await;
+        inner
+            .hash_chain
+            .as_ref()
+            .expect("BUG: hashchain is not running")
+            .board_info
+            .clone()
+    }
+
     /// TODO: for the love of god use macros or something
     pub async fn get_voltage(&self) -> power::Voltage {
         let inner = self.manager.inner.lock().await;
@@ -1159,7 +1828,17 @@ impl RunningChain {
             .hash_chain
             .as_ref()
             .expect("BUG: hashchain is not running")
-            .set_pll(frequency)
+            .set_frequency(frequency)
+            .await
+    }
+
+    pub async fn set_chip_frequency(&self, chip_idx: usize, frequency: usize) -> error::Result<()> {
+        let inner = self.manager.inner.lock().await;
+        inner
+            .hash_chain
+            .as_ref()
+            .expect("BUG: hashchain is not running")
+            .set_chip_frequency(chip_idx, frequency)
             .await
     }
 
@@ -1169,11 +1848,65 @@ impl RunningChain {
             .hash_chain
             .as_ref()
             .expect("BUG: hashchain is not running")
-            .voltage_ctrl
             .set_voltage(voltage)
             .await
     }
 
+    pub async fn get_failure(&self) -> Option<String> {
+        self.manager
+            .inner
+            .lock()
+            .await
+            .hash_chain
+            .as_ref()
+            .expect("not running")
+            .get_failure()
+    }
+
+    pub async fn set_idle(&self, idle: bool) {
+        self.manager
+            .inner
+            .lock()
+            .await
+            .hash_chain
+            .as_ref()
+            .expect("not running")
+            .set_idle(idle)
+    }
+
+    pub async fn is_idle(&self) -> bool {
+        self.manager
+            .inner
+            .lock()
+            .await
+            .hash_chain
+            .as_ref()
+            .expect("not running")
+            .is_idle()
+    }
+
+    pub async fn work_count(&self) -> usize {
+        self.manager
+            .inner
+            .lock()
+            .await
+            .hash_chain
+            .as_ref()
+            .expect("not running")
+            .work_count()
+    }
+
+    pub async fn is_waiting_for_work(&self) -> bool {
+        self.manager
+            .inner
+            .lock()
+            .await
+            .hash_chain
+            .as_ref()
+            .expect("not running")
+            .is_waiting_for_work()
+    }
+
     pub async fn reset_counter(&self) {
         self.manager
             .inner
@@ -1299,12 +2032,17 @@ pub struct Manager {
     pub hashboard_idx: usize,
     work_generator: work::Generator,
     solution_sender: work::SolutionSender,
-    plug_pin: PlugPin,
-    reset_pin: ResetPin,
+    plug_pin: hashboard::PlugPin,
+    reset_pin: hashboard::ResetPin,
     voltage_ctrl_backend: Arc<power::I2cBackend>,
-    midstate_count: MidstateCount,
+    /// Number of midstates used by the hash chain since its next start
+    midstate_count: StdMutex<MidstateCount>,
     /// channel to report to the monitor
     monitor_tx: mpsc::UnboundedSender<monitor::Message>,
+    /// Notifies about suspected hardware faults
+    alert_sender: alert::Sender,
+    /// Power estimates used for computation of energy efficiency
+    pub efficiency: efficiency::Meter,
     /// TODO: wrap this type in a structure (in Monitor)
     pub status_receiver: watch::Receiver<Option<monitor::Status>>,
     owned_by: StdMutex<Option<&'static str>>,
@@ -1335,6 +2073,41 @@ impl Manager {
         })
     }
 
+    pub fn get_midstate_count(&self) -> MidstateCount {
+        *self
+            .midstate_count
+            .lock()
+            .expect("BUG: failed to lock mutex")
+    }
+
+    /// Changes the number of midstates which is used since the next start of the chain (the
+    /// IP core midstate mode is configured during initialization). The work generator keeps
+    /// generating work with the configured number of midstates so it is also the maximum and
+    /// surplus midstates are not sent to chips.
+    pub fn set_midstate_count(&self, midstate_count: MidstateCount) -> error::Result<()> {
+        let max_midstate_count = self.chain_config.midstate_count;
+        if midstate_count.to_count() > max_midstate_count.to_count() {
+            Err(ErrorKind::Hashboard(
+                self.hashboard_idx,
+                format!(
+                    "midstate count {} exceeds configured midstate count {}",
+                    midstate_count.to_count(),
+                    max_midstate_count.to_count()
+                ),
+            ))?
+        }
+        *self
+            .midstate_count
+            .lock()
+            .expect("BUG: failed to lock mutex") = midstate_count;
+        Ok(())
+    }
+
+    /// Returns `false` also when presence of the hashboard cannot be detected
+    pub fn hashboard_present(&self) -> bool {
+        self.plug_pin.hashboard_present().unwrap_or(false)
+    }
+
     /// Initialize and start mining on hashchain
     /// TODO: this function is private and should be called only from `Stopped`
     async fn attempt_start_chain(
@@ -1360,16 +2133,36 @@ impl Manager {
         inner.start_count += 1;
 
         // make us a hash chain
-        let mut hash_chain = HashChain::new(
+        // it fails e.g. when the hashboard has been unplugged
+        let mut hash_chain = match HashChain::new(
             self.reset_pin.clone(),
             self.plug_pin.clone(),
             self.voltage_ctrl_backend.clone(),
             self.hashboard_idx,
-            self.midstate_count,
+            self.get_midstate_count(),
+            self.chain_config.fifo_mode,
             asic_difficulty,
             self.monitor_tx.clone(),
-        )
-        .expect("BUG: hashchain instantiation failed");
+        ) {
+            Err(e) => {
+                // deregister us
+                self.monitor_tx
+                    .unbounded_send(monitor::Message::Off)
+                    .expect("BUG: send failed");
+                return Err(e);
+            }
+            Ok(hash_chain) => hash_chain,
+        };
+        hash_chain.target_baud_rate = self.chain_config.baud_rate;
+        hash_chain.frequency_ramp = self.chain_config.frequency_ramp;
+        hash_chain.self_test_enabled = self.chain_config.self_test;
+        hash_chain.chip_temp_sensors = self.chain_config.chip_temp_sensors.clone();
+        hash_chain.chip_bin_profiles = self.chain_config.chip_bin_profiles.clone();
+        hash_chain.isolation = StdMutex::new(
+            self.chain_config
+                .chip_isolation
+                .map(isolation::Detector::new),
+        );
 
         // initialize it
         let work_registry = match hash_chain
@@ -1397,6 +2190,8 @@ impl Manager {
                 self.work_generator.clone(),
                 self.solution_sender.clone(),
                 work_registry,
+                self.alert_sender.clone(),
+                self.work_solver_stats.valid_chip_backend_diff.clone(),
             )
             .await;
 
@@ -1407,7 +2202,10 @@ impl Manager {
     }
 
     /// TODO: this function is private and should be called only from `RunningChain`
-    async fn stop_chain(&self, its_ok_if_its_missing: bool) {
+    ///
+    /// When `graceful` is set, solutions of work already sent to chips are collected before the
+    /// chain is stopped (see `HashChain::shutdown`)
+    async fn stop_chain(&self, its_ok_if_its_missing: bool, graceful: bool) {
         // lock inner to guarantee atomicity of hashchain stop
         let mut inner = self.inner.lock().await;
 
@@ -1419,7 +2217,11 @@ impl Manager {
         let hash_chain = hash_chain.expect("BUG: hashchain is missing");
 
         // stop everything
-        hash_chain.halt_sender.clone().send_halt().await;
+        if graceful {
+            hash_chain.shutdown().await;
+        } else {
+            hash_chain.halt_sender.clone().send_halt().await;
+        }
 
         // tell monitor we are done
         self.monitor_tx
@@ -1427,8 +2229,10 @@ impl Manager {
             .expect("BUG: send failed");
     }
 
+    /// Shuts the running chain down gracefully when the miner terminates so that hash boards are
+    /// not left running
     async fn termination_handler(self: Arc<Self>) {
-        self.stop_chain(true).await;
+        self.stop_chain(true, true).await;
     }
 }
 
@@ -1448,6 +2252,15 @@ impl node::WorkSolver for Manager {
             None => None,
         }
     }
+
+    async fn get_temperature(&self) -> Option<f32> {
+        let inner = self.inner.lock().await;
+        let temperature = inner.hash_chain.as_ref()?.current_temperature()?;
+        match monitor::ChainTemperature::from_s9_sensor(temperature) {
+            monitor::ChainTemperature::Ok(t) => Some(t),
+            monitor::ChainTemperature::Unknown | monitor::ChainTemperature::Failed => None,
+        }
+    }
 }
 
 impl fmt::Debug for Manager {
@@ -1524,9 +2337,9 @@ impl Backend {
     /// Enumerate present hashboards by querying the plug pin
     pub fn detect_hashboards(gpio_mgr: &gpio::ControlPinManager) -> error::Result<Vec<usize>> {
         let mut detected = vec![];
-        // TODO: configure this range somewhere
-        for hashboard_idx in 1..=8 {
-            let plug_pin = PlugPin::open(gpio_mgr, hashboard_idx)?;
+        // Only chains with IP cores in the FPGA can be driven
+        for hashboard_idx in io::detect_chains()? {
+            let plug_pin = hashboard::PlugPin::open(gpio_mgr, hashboard_idx)?;
             if plug_pin.hashboard_present()? {
                 detected.push(hashboard_idx);
             }
@@ -1534,10 +2347,40 @@ impl Backend {
         Ok(detected)
     }
 
-    /// Miner termination handler called when app is shutdown.
-    /// Just propagate the shutdown to all hashchain managers
-    async fn termination_handler(halt_sender: Arc<halt::Sender>) {
-        halt_sender.send_halt().await;
+    /// Check UIO devices of `chains`. Unavailable chains are left out when `skip_unavailable` is
+    /// set, otherwise the first unavailable chain fails the start of the miner.
+    async fn check_chain_devices(
+        chains: Vec<usize>,
+        skip_unavailable: bool,
+    ) -> error::Result<Vec<usize>> {
+        let mut available = vec![];
+        for hashboard_idx in chains {
+            match io::check_chain_devices(hashboard_idx).await {
+                Ok(()) => available.push(hashboard_idx),
+                Err(e) if skip_unavailable => {
+                    warn!("Chain {} skipped: {}", hashboard_idx, e);
+                }
+                Err(e) => Err(e)?,
+            }
+        }
+        Ok(available)
+    }
+
+    /// Halt the whole miner when any of its tasks panics for good instead of keeping the rest of
+    /// the miner running without it
+    async fn task_failure_task(
+        mut failures: ii_async_compat::task::FailureReceiver,
+        app_halt_sender: Arc<HaltHandle>,
+    ) {
+        while let Some(failure) = failures.next().await {
+            if failure.restarting {
+                error!("{}", failure);
+            } else {
+                crit!("{}", failure);
+                app_halt_sender.send_halt().await;
+                return;
+            }
+        }
     }
 
     /// Start miner
@@ -1547,8 +2390,9 @@ impl Backend {
         enabled_chains: Vec<usize>,
         work_hub: work::SolverBuilder<Backend>,
         backend_config: config::Backend,
-        app_halt_receiver: halt::Receiver,
-        app_halt_sender: Arc<halt::Sender>,
+        app_halt_receiver: HaltReceiver,
+        app_halt_sender: Arc<HaltHandle>,
+        alert_sender: alert::Sender,
     ) -> (Vec<Arc<Manager>>, Arc<monitor::Monitor>) {
         // Create hooks
         let hooks = match backend_config.hooks.as_ref() {
@@ -1556,12 +2400,11 @@ impl Backend {
             None => Arc::new(hooks::NoHooks),
         };
 
-        // Create new termination context and link it to the main (app) termination context
-        let (halt_sender, halt_receiver) = halt::make_pair(HALT_TIMEOUT);
-        app_halt_receiver
-            .register_client("miner termination".into())
-            .await
-            .spawn_halt_handler(Self::termination_handler(halt_sender.clone()));
+        // Create new termination context and link it to the main (app) termination context so
+        // that app shutdown is propagated to all hashchain managers
+        let (halt_sender, halt_receiver) = app_halt_receiver
+            .spawn_child("miner termination".into(), HALT_TIMEOUT)
+            .await;
         hooks
             .halt_created(
                 halt_sender.clone(),
@@ -1578,6 +2421,7 @@ impl Backend {
             monitor_config,
             app_halt_sender.clone(),
             app_halt_receiver.clone(),
+            alert_sender.clone(),
         )
         .await;
         hooks.monitor_started(monitor.clone()).await;
@@ -1606,17 +2450,19 @@ impl Backend {
                         // "physical-insertion" detection data. This structure will be persistent in
                         // between restarts and will enable early notification that there is no hashboard
                         // inserted (instead find out at mining-time).
-                        reset_pin: ResetPin::open(&gpio_mgr, hashboard_idx)
+                        reset_pin: hashboard::ResetPin::open(&gpio_mgr, hashboard_idx)
                             .expect("failed to make pin"),
-                        plug_pin: PlugPin::open(&gpio_mgr, hashboard_idx)
+                        plug_pin: hashboard::PlugPin::open(&gpio_mgr, hashboard_idx)
                             .expect("failed to make pin"),
                         voltage_ctrl_backend: voltage_ctrl_backend.clone(),
                         hashboard_idx,
-                        midstate_count: chain_config.midstate_count,
+                        midstate_count: StdMutex::new(chain_config.midstate_count),
                         work_solver_stats: Default::default(),
                         solution_sender,
                         work_generator,
                         monitor_tx,
+                        alert_sender: alert_sender.clone(),
+                        efficiency: Default::default(),
                         status_receiver,
                         owned_by: StdMutex::new(None),
                         inner: Mutex::new(ManagerInner {
@@ -1637,6 +2483,7 @@ impl Backend {
 
             let initial_frequency = manager.chain_config.frequency.clone();
             let initial_voltage = manager.chain_config.voltage;
+            let asic_difficulty = manager.chain_config.asic_difficulty;
             let hooks = hooks.clone();
 
             // Register handler to stop hashchain when miner is stopped
@@ -1647,20 +2494,26 @@ impl Backend {
 
             // Suppress haschain start if chain is either not enabled or haschain hook doesn't
             // want us to start it (default `NoHooks` has all chains enabled).
+            // Chains are started independently so that a failing hashboard does not prevent the
+            // other ones from mining.
             if hooks.can_start_chain(manager.clone()).await {
-                tokio::spawn(async move {
-                    manager
+                let alert_sender = alert_sender.clone();
+                runtime::spawn(async move {
+                    let hashboard_idx = manager.hashboard_idx;
+                    if let Err((_, e)) = manager
                         .acquire("main")
                         .await
                         .expect("BUG: failed to acquire hashchain")
                         .expect_stopped()
-                        .start(
-                            &initial_frequency,
-                            initial_voltage,
-                            config::DEFAULT_ASIC_DIFFICULTY,
-                        )
+                        .start(&initial_frequency, initial_voltage, asic_difficulty)
                         .await
-                        .expect("BUG: failed to start hashchain");
+                    {
+                        error!("Chain {} failed to start: {}", hashboard_idx, e);
+                        alert_sender.notify(alert::Event::ChainDead {
+                            hashboard_idx,
+                            reason: e.to_string(),
+                        });
+                    }
                 });
             }
         }
@@ -1693,30 +2546,62 @@ impl hal::Backend for Backend {
             .expect("BUG: missing client manager");
         let group_configs = backend_config.groups.take();
         let backend_info = backend_config.info();
+        let access_control = backend_config.resolve_api_access_control();
+        // Start alerting as soon as possible to be able to report failures of miner start
+        let mut alert_sender = alert::start(backend_config.resolve_alert_config());
+        // MQTT publisher has to subscribe for alerts before the sender is passed further
+        let mqtt_publisher = backend_config
+            .resolve_mqtt_config()
+            .map(|config| mqtt::Publisher::new(config, &mut alert_sender));
+        let influx_config = backend_config.resolve_influx_config();
+        let tuner_config = backend_config.resolve_tuner_config();
+        let autotuning_state_path = backend_config.resolve_autotuning_state_path();
+        let power_target = backend_config.resolve_power_target();
+        let hashrate_cap = backend_config.resolve_hashrate_cap();
+        let recovery_config = backend_config.resolve_recovery_config();
+        let throttle_config = backend_config.resolve_throttle_config();
+        let governor_config = backend_config.resolve_governor_config();
+        let pause_mode = backend_config.resolve_pause_mode();
+        let presets = backend_config.resolve_presets();
+        let psu_config = backend_config.resolve_psu_config();
+        let led_config = backend_config.resolve_led_config();
+        let button_enabled = backend_config.resolve_button_enabled();
 
         let backend = work_hub.to_node().clone();
         let gpio_mgr = gpio::ControlPinManager::new();
-        let (app_halt_sender, app_halt_receiver) = halt::make_pair(HALT_TIMEOUT);
+        let enabled_chains =
+            Self::detect_hashboards(&gpio_mgr).expect("failed detecting hashboards");
+        let enabled_chains = Self::check_chain_devices(
+            enabled_chains,
+            backend_config.resolve_skip_unavailable_chains(),
+        )
+        .await?;
+        // All hash chains are driven by the same FPGA bitstream so it is enough to check it once
+        if let Some(&hashboard_idx) = enabled_chains.first() {
+            io::check_bitstream(hashboard_idx)?;
+        }
+        let (app_halt_sender, app_halt_receiver) = ii_stop::make_pair(HALT_TIMEOUT);
+        // Alerts about failures which halted the miner have to be delivered before it exits
+        let exit_alert_sender = alert_sender.clone();
+        app_halt_sender
+            .add_exit_hook(async move {
+                exit_alert_sender.flush(alert::FLUSH_TIMEOUT).await;
+            })
+            .await;
+        if let Some(failures) = ii_async_compat::task::take_failures() {
+            runtime::spawn(Self::task_failure_task(failures, app_halt_sender.clone()));
+        }
         let (managers, monitor) = Self::start_miner(
             &gpio_mgr,
-            Self::detect_hashboards(&gpio_mgr).expect("failed detecting hashboards"),
+            enabled_chains,
             work_hub,
             backend_config,
             app_halt_receiver,
             app_halt_sender.clone(),
+            alert_sender.clone(),
         )
         .await;
 
-        // On miner exit, halt the whole program
-        app_halt_sender
-            .add_exit_hook(async {
-                println!("Exiting.");
-                std::process::exit(0);
-            })
-            .await;
-        // Hook `Ctrl-C`, `SIGTERM` and other termination methods
-        app_halt_sender.hook_termination_signals();
-
         // Load initial pool configuration
         client_manager
             .load_config(
@@ -1725,13 +2610,143 @@ impl hal::Backend for Backend {
                 config::DEFAULT_POOL_ENABLED,
             )
             .await?;
+        runtime::spawn(alert::client_watch_task(
+            client_manager.clone(),
+            alert_sender,
+        ));
+        let sampled_managers = managers.clone();
+        ii_async_compat::task::spawn_supervised(
+            "efficiency sampling",
+            ii_async_compat::task::RestartPolicy::always(),
+            move || efficiency::sampling_task(sampled_managers.clone()),
+        );
+        if let Some(tuner_config) = tuner_config {
+            let storage = Arc::new(tuner::Storage::new(autotuning_state_path));
+            for manager in managers.iter() {
+                runtime::spawn(tuner::tuning_task(
+                    manager.clone(),
+                    tuner_config.clone(),
+                    storage.clone(),
+                ));
+            }
+        }
+        if let Some(recovery_config) = recovery_config {
+            for manager in managers.iter() {
+                runtime::spawn(recovery::recovery_task(
+                    manager.clone(),
+                    recovery_config.clone(),
+                ));
+            }
+        }
+        if let Some(throttle_config) = throttle_config {
+            for manager in managers.iter() {
+                runtime::spawn(throttle::throttling_task(
+                    manager.clone(),
+                    throttle_config.clone(),
+                ));
+            }
+        }
+        if let Some(governor_config) = governor_config {
+            for manager in managers.iter() {
+                runtime::spawn(governor::governor_task(
+                    manager.clone(),
+                    governor_config.clone(),
+                ));
+            }
+        }
+        let pause_controller = Arc::new(pause::Controller::new(managers.clone(), pause_mode));
+        let power_target_controller = Arc::new(power_target::Controller::new(
+            managers.clone(),
+            power_target,
+        ));
+        runtime::spawn(power_target_controller.clone().task());
+        let preset_controller = Arc::new(preset::Controller::new(
+            managers.clone(),
+            monitor.clone(),
+            presets,
+        ));
+        if let Some(hashrate_cap) = hashrate_cap {
+            let hashrate_cap_controller = Arc::new(hashrate_cap::Controller::new(
+                managers.clone(),
+                hashrate_cap,
+            ));
+            runtime::spawn(hashrate_cap_controller.task());
+        }
+        let psu_monitor = psu_config.and_then(|psu_config| {
+            match psu::Psu::open(psu_config.i2c_interface_num, psu_config.address) {
+                Ok(psu) => {
+                    let psu_monitor = Arc::new(psu::Monitor::new(
+                        psu,
+                        &psu_config,
+                        pause_controller.clone(),
+                    ));
+                    runtime::spawn(psu_monitor.clone().task());
+                    Some(psu_monitor)
+                }
+                Err(e) => {
+                    error!("Failed to open power supply: {}", e);
+                    None
+                }
+            }
+        });
+        let led_controller = led_config.and_then(|led_config| match led::Pins::open(&gpio_mgr) {
+            Ok(pins) => {
+                let led_controller = Arc::new(led::Controller::new(
+                    led_config,
+                    managers.clone(),
+                    monitor.clone(),
+                    pause_controller.clone(),
+                    client_manager.clone(),
+                ));
+                runtime::spawn(led_controller.clone().watch_task());
+                runtime::spawn(led_controller.clone().blink_task(pins));
+                Some(led_controller)
+            }
+            Err(e) => {
+                error!("Failed to open front panel LEDs: {}", e);
+                None
+            }
+        });
+        if button_enabled {
+            match button::Handler::new(&gpio_mgr, managers.clone(), led_controller) {
+                Ok(button_handler) => {
+                    runtime::spawn(button_handler.task());
+                }
+                Err(e) => error!("Failed to open front panel button: {}", e),
+            }
+        }
+        if let Some(mqtt_publisher) = mqtt_publisher {
+            mqtt_publisher.start(
+                backend.clone(),
+                client_manager.clone(),
+                Arc::new(telemetry::Hardware::new(managers.clone(), monitor.clone())),
+            );
+        }
+        if let Some(influx_config) = influx_config {
+            influx::start(
+                influx_config,
+                backend.clone(),
+                client_manager.clone(),
+                Arc::new(telemetry::Hardware::new(managers.clone(), monitor.clone())),
+            );
+        }
         if let Some(hooks) = hooks {
             // Pass the client manager to hook for further processing
             hooks.clients_loaded(client_manager).await;
         }
 
         Ok(hal::FrontendConfig {
-            cgminer_custom_commands: cgminer::create_custom_commands(backend, managers, monitor),
+            cgminer_custom_commands: cgminer::create_custom_commands(
+                backend,
+                managers,
+                monitor,
+                pause_controller,
+                power_target_controller,
+                preset_controller,
+                psu_monitor,
+            ),
+            cgminer_access_control: access_control,
+            halt_handle: Some(app_halt_sender),
         })
     }
 
@@ -1756,6 +2771,25 @@ impl fmt::Display for Backend {
     }
 }
 
+/// Checks that chips and the FPGA IP core can communicate at `baud_rate`
+pub fn check_chip_baud_rate(baud_rate: usize) -> error::Result<()> {
+    let max_baud_rate = CHIP_OSC_CLK_HZ / bm1387::CHIP_OSC_CLK_BASE_BAUD_DIV;
+    if !(INIT_CHIP_BAUD_RATE..=max_baud_rate).contains(&baud_rate) {
+        Err(ErrorKind::BaudRate(format!(
+            "{} baud is out of range '{}..{}'",
+            baud_rate, INIT_CHIP_BAUD_RATE, max_baud_rate
+        )))?
+    }
+    let (baud_clock_div, _) = calc_baud_clock_div(
+        baud_rate,
+        CHIP_OSC_CLK_HZ,
+        bm1387::CHIP_OSC_CLK_BASE_BAUD_DIV,
+    )?;
+    bm1387::MiscCtrlReg::new(false, true, baud_clock_div, true, true)?;
+    calc_baud_clock_div(baud_rate, io::F_CLK_SPEED_HZ, io::F_CLK_BASE_BAUD_DIV)?;
+    Ok(())
+}
+
 /// Helper method that calculates baud rate clock divisor value for the specified baud rate.
 ///
 /// The calculation follows the same scheme for the hashing chips as well as for the FPGA IP core
@@ -1826,6 +2860,23 @@ fn calculate_work_delay_for_pll(n_midstates: usize, pll_frequency: usize) -> f64
     0.9 * (n_midstates as u64 * space_size_per_core) as f64 / pll_frequency as f64
 }
 
+/// Calculate value of `WORK_TIME` register for a chain of `chip_count` chips hashing at
+/// `frequency` in `midstate_count` mode
+///
+/// The work is sent as fast as the fastest chip exhausts its nonce space
+/// (see `calculate_work_delay_for_pll`), slower chips just don't finish the whole space. Settings
+/// of chips beyond `chip_count` (missing on the chain) are not taken into account.
+pub fn calculate_work_time(
+    frequency: &FrequencySettings,
+    chip_count: usize,
+    midstate_count: MidstateCount,
+) -> u32 {
+    secs_to_fpga_ticks(calculate_work_delay_for_pll(
+        midstate_count.to_count(),
+        frequency.max_of(chip_count),
+    ))
+}
+
 /// Helper method to convert seconds to FPGA ticks suitable to be written
 /// to `WORK_TIME` FPGA register.
 ///
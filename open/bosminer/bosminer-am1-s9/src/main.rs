@@ -25,12 +25,18 @@ use ii_logging::macros::*;
 use bosminer_am1_s9::config;
 
 use bosminer_config::clap;
+use bosminer_config::ii_config;
 use bosminer_config::{ClientDescriptor, ClientUserInfo, GroupConfig, PoolConfig};
 
-use ii_async_compat::tokio;
+use ii_async_compat::runtime;
 
-#[tokio::main]
-async fn main() {
+use std::path::Path;
+
+fn main() {
+    runtime::block_on(run())
+}
+
+async fn run() {
     let app = clap::App::new(bosminer::SIGNATURE)
         .version(bosminer::version::STRING.as_str())
         .arg(
@@ -105,9 +111,20 @@ async fn main() {
                         .required(false)
                         .takes_value(false),
                 )
+                .arg(
+                    clap::Arg::with_name("import-stock")
+                        .long("import-stock")
+                        .value_name("PATH")
+                        .help(
+                            "Save configuration imported from stock firmware configuration file \
+                             and write result to stdout",
+                        )
+                        .required(false)
+                        .takes_value(true),
+                )
                 .group(
                     clap::ArgGroup::with_name("command")
-                        .args(&["metadata", "data", "save"])
+                        .args(&["metadata", "data", "save", "import-stock"])
                         .required(true),
                 ),
         );
@@ -129,11 +146,72 @@ async fn main() {
             config_handler.handle_data::<config::Backend>();
         } else if matches.is_present("save") {
             config_handler.handle_save::<config::Backend>();
+        } else if let Some(stock_path) = matches.value_of("import-stock") {
+            config_handler.handle_import_stock(stock_path);
         }
         return;
     }
 
-    let mut backend_config: config::Backend = match config::FormatWrapper::parse(config_path) {
+    // Migrate the stock firmware configuration when the miner is started for the first time
+    if !Path::new(config_path).exists() {
+        if let Some(stock_path) = config::stock::find_stock_config() {
+            match config::stock::import_file(Path::new(stock_path), Path::new(config_path)) {
+                Ok(_) => info!(
+                    "Imported stock firmware configuration \"{}\" into \"{}\"",
+                    stock_path, config_path
+                ),
+                Err(e) => warn!(
+                    "Cannot import stock firmware configuration \"{}\": {}",
+                    stock_path, e
+                ),
+            }
+        }
+    }
+
+    // Command line options override the configuration file and the environment
+    let mut loader = ii_config::Loader::new()
+        .file(config_path)
+        .env_prefix(config::ENV_PREFIX);
+    if matches.is_present("disable-asic-boost") {
+        // Set just 1 midstate if user requested disabling asicboost
+        loader = loader.set(
+            "hash_chain_global.asic_boost",
+            false,
+            "--disable-asic-boost",
+        );
+    }
+    // NOTE: the values are parsed here because invalid values of the flattened hash chain
+    // settings would be silently ignored
+    if let Some(value) = matches.value_of("frequency") {
+        let frequency = match value.parse::<f64>() {
+            Ok(value) => value,
+            Err(e) => {
+                error!(
+                    "Cannot use frequency '{}' from command line: {}",
+                    value,
+                    e.to_string()
+                );
+                return;
+            }
+        };
+        loader = loader.set("hash_chain_global.frequency", frequency, "--frequency");
+    }
+    if let Some(value) = matches.value_of("voltage") {
+        let voltage = match value.parse::<f64>() {
+            Ok(value) => value,
+            Err(e) => {
+                error!(
+                    "Cannot use voltage '{}' from command line: {}",
+                    value,
+                    e.to_string()
+                );
+                return;
+            }
+        };
+        loader = loader.set("hash_chain_global.voltage", voltage, "--voltage");
+    }
+
+    let mut backend_config: config::Backend = match config::FormatWrapper::load(&loader) {
         Err(config::FormatWrapperError::IncompatibleVersion(version, Some(v))) => {
             warn!(
                 "Incompatible format version '{}', but continuing anyway",
@@ -169,7 +247,7 @@ async fn main() {
                 enabled: Default::default(),
                 url: url.to_string(),
                 user: user_info.user.to_string(),
-                password: user_info.password.map(|v| v.to_string()),
+                password: user_info.password.map(|v| v.to_string().into()),
             }]),
         };
 
@@ -193,55 +271,6 @@ async fn main() {
         return;
     }
 
-    // Set just 1 midstate if user requested disabling asicboost
-    if matches.is_present("disable-asic-boost") {
-        backend_config
-            .hash_chain_global
-            .get_or_insert_with(|| Default::default())
-            .asic_boost
-            .replace(false);
-    }
-    if let Some(value) = matches.value_of("frequency") {
-        let frequency = match value.parse::<f64>() {
-            Ok(value) => value,
-            Err(e) => {
-                error!(
-                    "Cannot use frequency '{}' from command line: {}",
-                    value,
-                    e.to_string()
-                );
-                return;
-            }
-        };
-        backend_config
-            .hash_chain_global
-            .get_or_insert_with(|| Default::default())
-            .overridable
-            .get_or_insert_with(|| Default::default())
-            .frequency
-            .replace(frequency);
-    }
-    if let Some(value) = matches.value_of("voltage") {
-        let voltage = match value.parse::<f64>() {
-            Ok(value) => value,
-            Err(e) => {
-                error!(
-                    "Cannot use voltage '{}' from command line: {}",
-                    value,
-                    e.to_string()
-                );
-                return;
-            }
-        };
-        backend_config
-            .hash_chain_global
-            .get_or_insert_with(|| Default::default())
-            .overridable
-            .get_or_insert_with(|| Default::default())
-            .voltage
-            .replace(voltage);
-    }
-
     if let Err(e) = backend_config.fill_info::<config::Backend>() {
         error!("Cannot get backend information: {}", e.to_string());
         return;
@@ -26,19 +26,23 @@
 use ii_logging::macros::*;
 
 use crate::fan;
-use crate::halt;
 use crate::sensor::{self, Measurement};
 
+use bosminer::alert;
+
+use ii_stop::{HaltHandle, HaltReceiver};
+
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use futures::channel::mpsc;
 use futures::lock::Mutex;
 use futures::stream::StreamExt;
+use ii_async_compat::clock::{self, DynClock};
 use ii_async_compat::futures;
+use ii_async_compat::runtime;
 use ii_async_compat::tokio;
 use tokio::sync::watch;
-use tokio::time::delay_for;
 
 /// If miner start takes longer than this, mark it as `Broken`
 const START_TIMEOUT: Duration = Duration::from_secs(180);
@@ -85,7 +89,7 @@ impl ChainTemperature {
     /// remote sensors fail while mining and instead of signalizing error they return non-sensical
     /// numbers.
     /// TODO: Is returning "Unknown" when sensor fails OK?
-    fn from_s9_sensor(temp: sensor::Temperature) -> Self {
+    pub fn from_s9_sensor(temp: sensor::Temperature) -> Self {
         match temp.remote {
             // remote is chip temperature
             Measurement::Ok(t) => Self::Ok(t),
@@ -222,6 +226,8 @@ pub struct FanControlConfig {
     /// Minimal number of fans - miner will refuse to work until at least
     /// this number of fans is spinning.
     pub min_fans: usize,
+    /// Lower limit of fan speed set by PID controller in `TargetTemperature` mode
+    pub min_speed: fan::Speed,
 }
 
 /// Temperature limit configuration
@@ -248,6 +254,30 @@ pub struct ControlDecisionExplained {
     pub reason: &'static str,
 }
 
+impl ControlDecisionExplained {
+    /// Describe the reason of decision for alerting
+    fn to_alert_event(
+        &self,
+        num_fans_running: usize,
+        input_temperature: ChainTemperature,
+    ) -> alert::Event {
+        if self.reason == ControlDecision::REASON_NOT_ENOUGH_FANS {
+            alert::Event::FanFailure {
+                fans_running: num_fans_running,
+                reason: self.reason.into(),
+            }
+        } else {
+            alert::Event::Overtemperature {
+                temperature: match input_temperature {
+                    ChainTemperature::Ok(temperature) => Some(temperature),
+                    ChainTemperature::Failed | ChainTemperature::Unknown => None,
+                },
+                reason: self.reason.into(),
+            }
+        }
+    }
+}
+
 /// Output of the decision process
 #[derive(Debug, Clone, PartialEq)]
 pub enum ControlDecision {
@@ -262,6 +292,8 @@ pub enum ControlDecision {
 }
 
 impl ControlDecision {
+    const REASON_NOT_ENOUGH_FANS: &'static str = "not enough fans";
+
     /// Decision rules if both fan control and temp control are enabled
     fn decide_fan_control(
         fan_config: &FanControlConfig,
@@ -371,7 +403,7 @@ impl ControlDecision {
                 if num_fans_running < fan_config.min_fans {
                     return ControlDecisionExplained {
                         decision: Self::Shutdown,
-                        reason: "not enough fans",
+                        reason: Self::REASON_NOT_ENOUGH_FANS,
                     };
                 }
             }
@@ -464,7 +496,13 @@ pub struct Monitor {
     pub status_receiver: watch::Receiver<Option<Status>>,
 
     /// Context to shutdown when miner enters critical state
-    miner_shutdown: Arc<halt::Sender>,
+    miner_shutdown: Arc<HaltHandle>,
+
+    /// Alerts about failures which lead to miner shutdown
+    alert_sender: alert::Sender,
+
+    /// Source of time for chain watchdog
+    clock: DynClock,
 
     /// Inner context
     inner: Mutex<MonitorInner>,
@@ -475,10 +513,12 @@ impl Monitor {
     ///
     /// * `miner_shutdown` - halt sender to shutdown the whole miner in case of a failure
     /// * `halt_receiver` - termination context in which to start the monitor
+    /// * `alert_sender` - notifies about failures which lead to miner shutdown
     pub async fn new_and_start(
         config: Config,
-        miner_shutdown: Arc<halt::Sender>,
-        halt_receiver: halt::Receiver,
+        miner_shutdown: Arc<HaltHandle>,
+        halt_receiver: HaltReceiver,
+        alert_sender: alert::Sender,
     ) -> Arc<Self> {
         let (status_sender, status_receiver) = watch::channel(None);
 
@@ -493,6 +533,8 @@ impl Monitor {
 
         let monitor = Arc::new(Monitor {
             miner_shutdown,
+            alert_sender,
+            clock: clock::system(),
             status_sender,
             status_receiver,
             inner: Mutex::new(inner),
@@ -548,10 +590,14 @@ impl Monitor {
         let mut miner_warming_up = false;
         for chain in inner.chains.iter() {
             let mut chain = chain.lock().await;
-            chain.state.tick(Instant::now());
+            chain.state.tick(self.clock.now());
 
             if let ChainState::Broken(reason) = chain.state {
                 // TODO: here comes "Shutdown"
+                self.alert_sender.notify(alert::Event::ChainDead {
+                    hashboard_idx: chain.hashboard_idx,
+                    reason: reason.into(),
+                });
                 let reason = format!("Chain {} is broken: {}", chain.hashboard_idx, reason);
                 // drop `chain` here to drop iterator which holds immutable reference
                 // to `monitor`
@@ -561,7 +607,7 @@ impl Monitor {
             }
             info!("chain {}: {:?}", chain.hashboard_idx, chain.state);
             temperature_accumulator.add_chain_temp(chain.state.get_temperature());
-            miner_warming_up |= chain.state.is_warming_up(Instant::now());
+            miner_warming_up |= chain.state.is_warming_up(self.clock.now());
         }
         let input_temperature = temperature_accumulator.calc_result();
 
@@ -579,6 +625,8 @@ impl Monitor {
         info!("Monitor: {:?}", decision_explained);
         match decision_explained.decision {
             ControlDecision::Shutdown => {
+                self.alert_sender
+                    .notify(decision_explained.to_alert_event(num_fans_running, input_temperature));
                 self.shutdown(&mut inner, decision_explained.reason.into())
                     .await;
             }
@@ -589,10 +637,16 @@ impl Monitor {
                 target_temp,
                 input_temp,
             } => {
+                let min_speed = inner
+                    .config
+                    .fan_config
+                    .as_ref()
+                    .map(|fan_config| fan_config.min_speed)
+                    .unwrap_or(fan::Speed::STOPPED);
                 if inner.config.fans_on_while_warming_up && miner_warming_up {
-                    inner.pid.set_warm_up_limits();
+                    inner.pid.set_warm_up_limits(min_speed);
                 } else {
-                    inner.pid.set_normal_limits();
+                    inner.pid.set_normal_limits(min_speed);
                 }
                 inner.pid.set_target(target_temp.into());
                 let speed = inner.pid.update(input_temp.into());
@@ -621,18 +675,22 @@ impl Monitor {
 
     /// Task performing temp control
     async fn tick_task(self: Arc<Self>) {
+        let mut interval = runtime::Interval::new(TICK_LENGTH);
         loop {
             self.do_tick().await;
-            // TODO: find some of kind "run every x secs" function
-            delay_for(TICK_LENGTH).await;
+            interval.tick().await;
         }
     }
 
     /// Per-chain task that collects hashchain status update messages
-    async fn recv_task(chain: Arc<Mutex<Chain>>, mut rx: mpsc::UnboundedReceiver<Message>) {
+    async fn recv_task(
+        chain: Arc<Mutex<Chain>>,
+        mut rx: mpsc::UnboundedReceiver<Message>,
+        clock: DynClock,
+    ) {
         while let Some(message) = rx.next().await {
             let mut chain = chain.lock().await;
-            chain.state.transition(Instant::now(), message);
+            chain.state.transition(clock.now(), message);
         }
     }
 
@@ -644,7 +702,7 @@ impl Monitor {
         {
             let mut inner = self.inner.lock().await;
             inner.chains.push(chain.clone());
-            tokio::spawn(Self::recv_task(chain, rx));
+            runtime::spawn(Self::recv_task(chain, rx, self.clock.clone()));
         }
         tx
     }
@@ -662,6 +720,7 @@ impl Monitor {
 mod test {
     use super::*;
     use approx::assert_relative_eq;
+    use ii_async_compat::clock::{Clock, ManualClock};
 
     macro_rules! assert_variant {
         ($value:expr, $pattern:pat) => {{
@@ -839,6 +898,33 @@ mod test {
         );
     }
 
+    /// Test that chain watchdog measures heartbeats with the monitor clock
+    #[tokio::test]
+    async fn test_monitor_watchdog() {
+        let temp = sensor::Temperature {
+            local: sensor::Measurement::Ok(10.0),
+            remote: sensor::Measurement::Ok(22.0),
+        };
+        let clock = Arc::new(ManualClock::new());
+        let chain = Arc::new(Mutex::new(Chain::new(0)));
+        let (tx, rx) = mpsc::unbounded();
+        tx.unbounded_send(Message::On).expect("send failed");
+        clock.advance(START_TIMEOUT / 2);
+        tx.unbounded_send(Message::Running(temp))
+            .expect("send failed");
+        drop(tx);
+        Monitor::recv_task(chain.clone(), rx, clock.clone()).await;
+
+        let mut chain = chain.lock().await;
+        assert_variant!(chain.state, ChainState::Running { .. });
+        clock.advance(RUN_UPDATE_TIMEOUT / 2);
+        chain.state.tick(clock.now());
+        assert_variant!(chain.state, ChainState::Running { .. });
+        clock.advance(RUN_UPDATE_TIMEOUT / 2);
+        chain.state.tick(clock.now());
+        assert_variant!(chain.state, ChainState::Broken(_));
+    }
+
     fn test_acc(temp1: ChainTemperature, temp2: ChainTemperature) -> ChainTemperature {
         let mut tacc = TemperatureAccumulator::new();
         tacc.add_chain_temp(temp1);
@@ -905,6 +991,7 @@ mod test {
         let fan_config = FanControlConfig {
             mode: FanControlMode::FixedSpeed(fan_speed),
             min_fans: 2,
+            min_speed: fan::Speed::STOPPED,
         };
         let fans_off = fan::Speed::STOPPED;
         let fans_off_config = Config {
@@ -912,6 +999,7 @@ mod test {
             fan_config: Some(FanControlConfig {
                 mode: FanControlMode::FixedSpeed(fans_off),
                 min_fans: 2,
+                min_speed: fan::Speed::STOPPED,
             }),
             temp_config: None,
         };
@@ -940,6 +1028,7 @@ mod test {
             fan_config: Some(FanControlConfig {
                 mode: FanControlMode::TargetTemperature(75.0),
                 min_fans: 2,
+                min_speed: fan::Speed::STOPPED,
             }),
             temp_config: Some(temp_config.clone()),
         };
@@ -0,0 +1,262 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Pausing of mining for maintenance windows and emergency curtailment
+//!
+//! Paused hash chains are stopped and powered down so that no work is distributed to them. They
+//! are started again on explicit request or automatically after an optional timeout. In the idle
+//! mode the chains stay initialized at the lowest frequency and they only stop taking work so
+//! that mining resumes without the lengthy initialization. Pool connections are kept alive in
+//! both modes.
+
+use ii_logging::macros::*;
+
+use crate::config;
+use crate::{ChainStatus, FrequencySettings, Manager, RunningChain};
+
+use serde::{Deserialize, Serialize};
+
+use futures::lock::Mutex;
+use ii_async_compat::{futures, runtime};
+use runtime::delay_for;
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Owner of hash chains while they are being paused or resumed
+const OWNER_NAME: &str = "pause";
+
+/// Idle chain which is busy (owned by someone else) is resumed again after this time
+const RESUME_RETRY_PERIOD: Duration = Duration::from_secs(1);
+
+/// Number of attempts to resume idle chain
+const RESUME_ATTEMPTS: usize = 30;
+
+/// What happens with paused hash chains
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Mode {
+    /// Chains are stopped and hashboards are powered down
+    PowerOff,
+    /// Chains are clocked down to the lowest frequency and no work is sent to them
+    Idle,
+}
+
+impl Default for Mode {
+    fn default() -> Mode {
+        Mode::PowerOff
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Status {
+    pub paused: bool,
+    /// Number of hash chains stopped by pause
+    pub paused_chains: usize,
+    /// Time remaining to automatic resume
+    pub resume_in: Option<Duration>,
+}
+
+/// Hash chain paused by the controller
+#[derive(Debug)]
+struct PausedChain {
+    manager: Arc<Manager>,
+    /// Frequency of idle chain before it was paused, it is `None` for chains powered down
+    frequency: Option<FrequencySettings>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    /// Hash chains paused by the controller which are started again on resume. It is `None` when
+    /// mining is not paused.
+    paused_chains: Option<Vec<PausedChain>>,
+    resume_at: Option<Instant>,
+    /// Incremented by every pause to cancel automatic resume scheduled by the previous one
+    pause_count: usize,
+}
+
+impl Inner {
+    fn status(&self) -> Status {
+        let now = Instant::now();
+        Status {
+            paused: self.paused_chains.is_some(),
+            paused_chains: self.paused_chains.as_ref().map_or(0, |chains| chains.len()),
+            resume_in: self
+                .resume_at
+                .map(|resume_at| resume_at.saturating_duration_since(now)),
+        }
+    }
+}
+
+pub struct Controller {
+    managers: Vec<Arc<Manager>>,
+    mode: Mode,
+    inner: Mutex<Inner>,
+}
+
+impl Controller {
+    pub fn new(managers: Vec<Arc<Manager>>, mode: Mode) -> Self {
+        Self {
+            managers,
+            mode,
+            inner: Mutex::new(Default::default()),
+        }
+    }
+
+    /// Stops taking work and clocks the chain down to the lowest frequency. Returns the original
+    /// frequency of the chain.
+    async fn idle_chain(running_chain: &RunningChain) -> FrequencySettings {
+        running_chain.set_idle(true).await;
+        let frequency = running_chain.get_frequency().await;
+        let idle_frequency =
+            FrequencySettings::from_frequency((config::FREQUENCY_MHZ_MIN * 1_000_000.0) as usize);
+        if let Err(e) = running_chain.set_frequency(&idle_frequency).await {
+            // the chain is idle anyway
+            warn!("Failed to clock down idle chain: {}", e);
+        }
+        frequency
+    }
+
+    pub async fn status(&self) -> Status {
+        self.inner.lock().await.status()
+    }
+
+    /// Stops all running hash chains. Mining is resumed automatically after `timeout` when it is
+    /// set. Pausing already paused mining only changes the timeout.
+    pub async fn pause(self: Arc<Self>, timeout: Option<Duration>) -> Status {
+        let mut inner = self.inner.lock().await;
+        if inner.paused_chains.is_none() {
+            let mut paused_chains = vec![];
+            for manager in self.managers.iter() {
+                match manager.clone().acquire(OWNER_NAME).await {
+                    Ok(ChainStatus::Running(running_chain)) => {
+                        info!("Pausing chain {} ({:?})", manager.hashboard_idx, self.mode);
+                        let frequency = match self.mode {
+                            Mode::PowerOff => {
+                                running_chain.stop().await;
+                                None
+                            }
+                            Mode::Idle => Some(Self::idle_chain(&running_chain).await),
+                        };
+                        paused_chains.push(PausedChain {
+                            manager: manager.clone(),
+                            frequency,
+                        });
+                    }
+                    Ok(ChainStatus::Stopped(_)) => {}
+                    Err(owner) => warn!(
+                        "Cannot pause chain {} which is busy (owned by '{}')",
+                        manager.hashboard_idx, owner
+                    ),
+                }
+            }
+            inner.paused_chains.replace(paused_chains);
+        }
+
+        inner.pause_count += 1;
+        inner.resume_at = timeout.map(|timeout| Instant::now() + timeout);
+        if let Some(timeout) = timeout {
+            info!("Mining paused, it will be resumed in {:?}", timeout);
+            runtime::spawn(self.clone().resume_task(timeout, inner.pause_count));
+        } else {
+            info!("Mining paused");
+        }
+        inner.status()
+    }
+
+    /// Starts all hash chains paused by the controller. Returns `false` when mining is not paused.
+    pub async fn resume(&self) -> bool {
+        Self::resume_chains(&mut *self.inner.lock().await)
+    }
+
+    fn resume_chains(inner: &mut Inner) -> bool {
+        let paused_chains = match inner.paused_chains.take() {
+            Some(paused_chains) => paused_chains,
+            None => return false,
+        };
+        inner.resume_at = None;
+
+        info!("Resuming mining");
+        for paused_chain in paused_chains {
+            // Chain start can take a long time so do not block the caller
+            runtime::spawn(Self::resume_chain(paused_chain));
+        }
+        true
+    }
+
+    async fn resume_chain(paused_chain: PausedChain) {
+        let manager = paused_chain.manager;
+        let mut attempts = 0;
+        let stopped_chain = loop {
+            attempts += 1;
+            match (
+                manager.clone().acquire(OWNER_NAME).await,
+                &paused_chain.frequency,
+            ) {
+                (Ok(ChainStatus::Stopped(stopped_chain)), _) => break stopped_chain,
+                (Ok(ChainStatus::Running(running_chain)), Some(frequency)) => {
+                    if let Err(e) = running_chain.set_frequency(frequency).await {
+                        error!(
+                            "Failed to restore frequency of chain {}: {}",
+                            manager.hashboard_idx, e
+                        );
+                    }
+                    running_chain.set_idle(false).await;
+                    return;
+                }
+                // chain has been started by someone else in the meantime
+                (Ok(ChainStatus::Running(_)), None) => return,
+                // idle chain is most likely checked by other controller for a moment
+                (Err(_), Some(_)) if attempts < RESUME_ATTEMPTS => {
+                    delay_for(RESUME_RETRY_PERIOD).await
+                }
+                (Err(owner), _) => {
+                    warn!(
+                        "Cannot resume chain {} which is busy (owned by '{}')",
+                        manager.hashboard_idx, owner
+                    );
+                    return;
+                }
+            }
+        };
+        // idle chain which has been stopped in the meantime is started as well
+        if let Err((_, e)) = stopped_chain
+            .start(
+                &manager.chain_config.frequency,
+                manager.chain_config.voltage,
+                manager.chain_config.asic_difficulty,
+            )
+            .await
+        {
+            error!("Failed to resume chain {}: {}", manager.hashboard_idx, e);
+        }
+    }
+
+    async fn resume_task(self: Arc<Self>, timeout: Duration, pause_count: usize) {
+        delay_for(timeout).await;
+        // Ignore the timeout when mining has been resumed or paused again in the meantime
+        let mut inner = self.inner.lock().await;
+        if inner.pause_count == pause_count {
+            Self::resume_chains(&mut inner);
+        }
+    }
+}
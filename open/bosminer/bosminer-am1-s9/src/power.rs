@@ -31,12 +31,13 @@ use std::time::Duration;
 
 use crate::async_i2c::AsyncI2cDev;
 use crate::error::{self, ErrorKind};
-use crate::halt;
+
+use ii_stop::HaltReceiver;
 
 use futures::lock::Mutex;
 use ii_async_compat::futures;
-use ii_async_compat::tokio;
-use tokio::time::delay_for;
+use ii_async_compat::runtime;
+use runtime::delay_for;
 
 use once_cell::sync::Lazy;
 
@@ -68,7 +69,6 @@ const SET_VOLTAGE: u8 = 0x10;
 const SET_VOLTAGE_TIME: u8 = 0x11;
 #[allow(dead_code)]
 const SET_HASH_BOARD_ID: u8 = 0x12;
-#[allow(dead_code)]
 const GET_HASH_BOARD_ID: u8 = 0x13;
 #[allow(dead_code)]
 const SET_HOST_MAC_ADDRESS: u8 = 0x14;
@@ -439,6 +439,9 @@ impl Control {
     /// Number of bytes in `SEND_DATA_TO_IIC` and `READ_DATA_FROM_IIC` command response/reply
     pub const FLASH_XFER_BLOCK_SIZE_BYTES: usize = 16;
 
+    /// Number of bytes in `GET_HASH_BOARD_ID` command reply
+    const HASH_BOARD_ID_BYTES: usize = 12;
+
     async fn read(&self, command: u8, length: usize) -> error::Result<Vec<u8>> {
         self.backend.lock().await.read(command, length).await
     }
@@ -543,6 +546,17 @@ impl Control {
         Ok(())
     }
 
+    /// Reads hash board ID (serial number) programmed by the manufacturer
+    pub async fn get_hash_board_id(&self) -> error::Result<Vec<u8>> {
+        self.read(GET_HASH_BOARD_ID, Self::HASH_BOARD_ID_BYTES)
+            .await
+    }
+
+    /// Returns `FREQ` section of PIC flash dumped during initialization
+    pub async fn get_freq_flash(&self) -> Option<FlashFreq> {
+        self.freq_flash.lock().await.clone()
+    }
+
     pub async fn get_version(&self) -> error::Result<u8> {
         let version = self.read(GET_PIC_SOFTWARE_VERSION, 1).await?[0];
         info!("Voltage controller firmware version {:#04x}", version);
@@ -688,8 +702,9 @@ impl Control {
 
     /// Initialize voltage controller
     /// TODO: decouple this code from `halt_receiver`
-    pub async fn init(self: Arc<Self>, halt_receiver: halt::Receiver) -> error::Result<()> {
+    pub async fn init(self: Arc<Self>, halt_receiver: HaltReceiver) -> error::Result<()> {
         let version = self.reset_and_start_app().await?;
+        info!("Voltage controller firmware version {:#x}", version);
         // TODO accept multiple
         if version != EXPECTED_VOLTAGE_CTRL_VERSION {
             info!("Bad firmware version! Reloading firmware...");
@@ -720,7 +735,7 @@ impl Control {
     ///
     /// The reason is to notify the voltage controller that we are alive so that it wouldn't
     /// cut-off power supply to the hashing chips on the board.
-    async fn start_heart_beat_task(self: Arc<Self>, halt_receiver: halt::Receiver) {
+    async fn start_heart_beat_task(self: Arc<Self>, halt_receiver: HaltReceiver) {
         // Start heartbeat thread in termination context
         let voltage_ctrl = self.clone();
         halt_receiver
@@ -22,9 +22,9 @@
 
 //! PIC firmware loader
 
+use crate::error::ResultExt;
 use crate::error::{self, ErrorKind};
 use crate::power::{PicAddress, PicWords};
-use failure::ResultExt;
 
 use std::convert::AsRef;
 use std::fs::File;
@@ -0,0 +1,163 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Power target mode limiting the power consumption of the whole miner
+//!
+//! The power budget is split evenly between running hash chains. When the power estimated from
+//! voltage and chip frequencies of a chain exceeds its share, all its chips are clocked down to
+//! fit into the budget (see `frequency_limit`). The original frequencies are restored as soon as
+//! the budget allows it or when the power target is removed.
+
+use ii_logging::macros::*;
+
+use crate::efficiency;
+use crate::frequency_limit;
+use crate::Manager;
+
+use futures::lock::Mutex;
+use ii_async_compat::{futures, runtime};
+
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Owner of hash chains while their frequency is being adjusted
+const OWNER_NAME: &str = "power target";
+
+/// Period of checking the power of running chains
+pub const ADJUST_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Limited frequencies are rounded down to multiples of this step (in Hz) to avoid reprogramming
+/// of PLLs on every small change of the power estimate
+const FREQUENCY_STEP: usize = 5_000_000;
+
+/// Frequency (in Hz) of all chips of a chain with `chip_count` chips running at `voltage` (in V)
+/// for which the estimated power does not exceed `power` (in W)
+pub fn frequency_for_power(voltage: f64, chip_count: usize, power: f64) -> usize {
+    let dynamic_power = power - efficiency::LEAKAGE_CURRENT * voltage;
+    if dynamic_power <= 0.0 || chip_count == 0 {
+        return 0;
+    }
+    let frequency =
+        dynamic_power / (efficiency::DYNAMIC_POWER_COEF * voltage * voltage * chip_count as f64);
+    frequency as usize / FREQUENCY_STEP * FREQUENCY_STEP
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Status {
+    /// Power limit of the whole miner in W, `None` when the mode is disabled
+    pub target: Option<f64>,
+    /// Sum of the last power estimates of all chains in W
+    pub power: f64,
+    /// Number of hash chains clocked down below their original frequency
+    pub limited_chains: usize,
+}
+
+struct Inner {
+    target: Option<f64>,
+    limiter: frequency_limit::Limiter,
+}
+
+pub struct Controller {
+    managers: Vec<Arc<Manager>>,
+    inner: Mutex<Inner>,
+}
+
+impl Controller {
+    pub fn new(managers: Vec<Arc<Manager>>, target: Option<f64>) -> Self {
+        Self {
+            inner: Mutex::new(Inner {
+                target,
+                limiter: frequency_limit::Limiter::new(
+                    OWNER_NAME,
+                    managers.clone(),
+                    frequency_for_power,
+                ),
+            }),
+            managers,
+        }
+    }
+
+    pub async fn status(&self) -> Status {
+        let mut power = 0.0;
+        for manager in self.managers.iter() {
+            power += manager.efficiency.take_snapshot().await.power;
+        }
+        let inner = self.inner.lock().await;
+        Status {
+            target: inner.target,
+            power,
+            limited_chains: inner.limiter.limited_chains(),
+        }
+    }
+
+    /// Changes the power limit (in W) and immediately adjusts running chains. The power target
+    /// mode is disabled when `target` is `None`.
+    pub async fn set_target(&self, target: Option<f64>) -> Status {
+        match target {
+            Some(target) => info!("Power target set to {} W", target),
+            None => info!("Power target disabled"),
+        }
+        self.inner.lock().await.target = target;
+        self.adjust().await;
+        self.status().await
+    }
+
+    /// Clocks running chains down or up so that they fit into the power budget
+    async fn adjust(&self) {
+        let mut inner = self.inner.lock().await;
+        let target = inner.target;
+        inner.limiter.adjust(target).await;
+    }
+
+    /// Periodically adjusts chains started in the meantime
+    pub async fn task(self: Arc<Self>) {
+        let mut interval = runtime::Interval::new(ADJUST_INTERVAL);
+        while interval.tick().await.is_some() {
+            self.adjust().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_frequency_for_power() {
+        let chip_count = 63;
+        let frequency = frequency_for_power(8.8, chip_count, 300.0);
+        assert_eq!(frequency % FREQUENCY_STEP, 0);
+        let power = efficiency::estimate_power(8.8, (frequency * chip_count) as u64);
+        assert!(power <= 300.0, "power {} over the limit", power);
+        let next_power =
+            efficiency::estimate_power(8.8, ((frequency + FREQUENCY_STEP) * chip_count) as u64);
+        assert!(next_power > 300.0, "frequency {} is too low", frequency);
+
+        // the default settings fit into the power of the default settings
+        let power = efficiency::estimate_power(8.8, 63 * 650_000_000);
+        assert!(frequency_for_power(8.8, chip_count, power + 1.0) >= 645_000_000);
+
+        // the budget does not cover leakage
+        assert_eq!(frequency_for_power(8.8, chip_count, 10.0), 0);
+        assert_eq!(frequency_for_power(8.8, 0, 300.0), 0);
+    }
+}
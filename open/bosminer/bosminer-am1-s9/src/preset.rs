@@ -0,0 +1,285 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Named presets of frequency, voltage and fan target temperature
+//!
+//! Presets are defined in the configuration and selected for running hash chains at runtime.
+//! The fan target temperature is shared by all hash chains so the last selected preset with
+//! the target temperature wins. The selection is forgotten when the chain is initialized again
+//! (e.g. by the recovery) with its configured frequency and voltage.
+//!
+//! Chips must not run at a frequency which is too high for their voltage so the frequency is
+//! lowered before the voltage when the preset reduces it and the voltage is raised first
+//! otherwise. A chain which fails to take the preset is returned to its original settings.
+
+use ii_logging::macros::*;
+
+use crate::error;
+use crate::monitor;
+use crate::power;
+use crate::{ChainStatus, FrequencySettings, Manager, RunningChain};
+
+use futures::lock::Mutex;
+use ii_async_compat::futures;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// Owner of hash chains while the preset is being applied
+const OWNER_NAME: &str = "preset";
+
+#[derive(Clone)]
+pub struct Preset {
+    pub name: String,
+    pub frequency: FrequencySettings,
+    pub voltage: power::Voltage,
+    /// Fan target temperature, the current one is kept when it is not set
+    pub target_temp: Option<f32>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    UnknownPreset(String),
+    UnknownChain(usize),
+    ChainNotRunning(usize),
+    /// The chain is owned by someone else (e.g. it is being tuned)
+    ChainBusy(usize, String),
+    Failed(usize, String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownPreset(name) => write!(f, "unknown preset '{}'", name),
+            Self::UnknownChain(idx) => write!(f, "unknown chain {}", idx),
+            Self::ChainNotRunning(idx) => write!(f, "chain {} is not running", idx),
+            Self::ChainBusy(idx, owner) => write!(f, "chain {} is owned by {}", idx, owner),
+            Self::Failed(idx, reason) => write!(f, "chain {}: {}", idx, reason),
+        }
+    }
+}
+
+/// Preset selected for one hash chain
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChainPreset {
+    pub hashboard_idx: usize,
+    /// `None` when the chain runs with its configured settings
+    pub name: Option<String>,
+}
+
+/// Frequency and voltage of one hash chain
+struct Settings {
+    frequency: FrequencySettings,
+    voltage: power::Voltage,
+}
+
+impl Settings {
+    /// Frequency has to be lowered before the voltage is lowered and the voltage has to be raised
+    /// before the frequency is raised
+    fn frequency_first(&self, current: &Settings) -> bool {
+        self.frequency.avg() < current.frequency.avg()
+    }
+
+    /// Sets these settings on a chain currently running with `current` settings
+    async fn set_from(&self, current: &Settings, chain: &RunningChain) -> error::Result<()> {
+        if self.frequency_first(current) {
+            chain.set_frequency(&self.frequency).await?;
+            chain.set_voltage(self.voltage).await
+        } else {
+            chain.set_voltage(self.voltage).await?;
+            chain.set_frequency(&self.frequency).await
+        }
+    }
+}
+
+pub struct Controller {
+    managers: Vec<Arc<Manager>>,
+    monitor: Arc<monitor::Monitor>,
+    presets: Vec<Preset>,
+    /// Selected preset names indexed by hashboard together with start ID of the chain
+    selected: Mutex<HashMap<usize, (String, usize)>>,
+}
+
+impl Controller {
+    pub fn new(
+        managers: Vec<Arc<Manager>>,
+        monitor: Arc<monitor::Monitor>,
+        presets: Vec<Preset>,
+    ) -> Self {
+        Self {
+            managers,
+            monitor,
+            presets,
+            selected: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub async fn status(&self) -> Vec<ChainPreset> {
+        let selected = self.selected.lock().await;
+        let mut list = vec![];
+        for manager in self.managers.iter() {
+            let inner = manager.inner.lock().await;
+            let name = match selected.get(&manager.hashboard_idx) {
+                Some((name, start_id))
+                    if inner.hash_chain.is_some() && *start_id == inner.start_count =>
+                {
+                    Some(name.clone())
+                }
+                _ => None,
+            };
+            list.push(ChainPreset {
+                hashboard_idx: manager.hashboard_idx,
+                name,
+            });
+        }
+        list
+    }
+
+    /// Applies preset `name` to the chain `hashboard_idx` or to all chains when it is `None`
+    pub async fn select(&self, hashboard_idx: Option<usize>, name: &str) -> Result<(), Error> {
+        let preset = self
+            .presets
+            .iter()
+            .find(|preset| preset.name == name)
+            .ok_or_else(|| Error::UnknownPreset(name.to_string()))?;
+        let managers: Vec<_> = self
+            .managers
+            .iter()
+            .filter(|manager| {
+                hashboard_idx.is_none() || hashboard_idx == Some(manager.hashboard_idx)
+            })
+            .collect();
+        if let Some(idx) = hashboard_idx {
+            if managers.is_empty() {
+                return Err(Error::UnknownChain(idx));
+            }
+        }
+
+        // The preset is applied to as many chains as possible and the first failure is reported
+        let mut result = Ok(());
+        for manager in managers {
+            if let Err(e) = self.apply(manager, preset).await {
+                error!("Preset: failed to apply '{}': {}", preset.name, e);
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
+        }
+        if let Some(target_temp) = preset.target_temp {
+            self.set_target_temp(target_temp).await;
+        }
+        result
+    }
+
+    async fn apply(&self, manager: &Arc<Manager>, preset: &Preset) -> Result<(), Error> {
+        let hashboard_idx = manager.hashboard_idx;
+        let running_chain = match manager.clone().acquire(OWNER_NAME).await {
+            Ok(ChainStatus::Running(running_chain)) => running_chain,
+            Ok(ChainStatus::Stopped(_)) => return Err(Error::ChainNotRunning(hashboard_idx)),
+            Err(owner) => return Err(Error::ChainBusy(hashboard_idx, owner.to_string())),
+        };
+
+        info!(
+            "Preset: applying '{}' to chain {}",
+            preset.name, hashboard_idx
+        );
+        let original = Settings {
+            frequency: running_chain.get_frequency().await,
+            voltage: running_chain.get_voltage().await,
+        };
+        let target = Settings {
+            frequency: preset.frequency.clone(),
+            voltage: preset.voltage,
+        };
+        if let Err(e) = target.set_from(&original, &running_chain).await {
+            // do not leave the chain with just a part of the preset
+            if let Err(e) = original.set_from(&target, &running_chain).await {
+                error!(
+                    "Preset: failed to restore original settings of chain {}: {}",
+                    hashboard_idx, e
+                );
+            }
+            return Err(Error::Failed(hashboard_idx, e.to_string()));
+        }
+        self.selected
+            .lock()
+            .await
+            .insert(hashboard_idx, (preset.name.clone(), running_chain.start_id));
+        Ok(())
+    }
+
+    async fn set_target_temp(&self, target_temp: f32) {
+        self.monitor
+            .with_configuration(|config| match config.fan_config.as_mut() {
+                Some(monitor::FanControlConfig {
+                    mode: monitor::FanControlMode::TargetTemperature(current),
+                    ..
+                }) => {
+                    info!("Preset: fan target temperature set to {} C", target_temp);
+                    *current = target_temp;
+                }
+                _ => warn!("Preset: fans are not controlled by target temperature"),
+            })
+            .await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn settings(frequency: usize, voltage: f32) -> Settings {
+        Settings {
+            frequency: FrequencySettings::from_frequency(frequency),
+            voltage: power::Voltage::from_volts(voltage).expect("BUG: invalid voltage"),
+        }
+    }
+
+    #[test]
+    fn test_frequency_first() {
+        let current = settings(650_000_000, 8.9);
+        // lower frequency is set before the voltage is lowered
+        assert!(settings(600_000_000, 8.6).frequency_first(&current));
+        // higher voltage is set before the frequency is raised
+        assert!(!settings(700_000_000, 9.2).frequency_first(&current));
+        assert!(!settings(650_000_000, 9.0).frequency_first(&current));
+        // the same order is used to return the original settings back
+        assert!(current.frequency_first(&settings(700_000_000, 9.2)));
+    }
+
+    #[test]
+    fn test_error() {
+        assert_eq!(
+            Error::UnknownPreset("eco".to_string()).to_string(),
+            "unknown preset 'eco'"
+        );
+        assert_eq!(
+            Error::ChainBusy(6, "tuner".to_string()).to_string(),
+            "chain 6 is owned by tuner"
+        );
+        assert_eq!(
+            Error::Failed(7, "I2C: timeout".to_string()).to_string(),
+            "chain 7: I2C: timeout"
+        );
+    }
+}
@@ -0,0 +1,313 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Monitoring of the power supply over PMBus
+//!
+//! Power supplies with a digital interface (APW3++ and newer) are connected to the same I2C bus
+//! as voltage controllers of hashboards. Input voltage, output current and temperature are read
+//! periodically and reported by the `psu` API command. Power supplies without the interface just
+//! do not respond so all their readings are missing.
+//!
+//! When the under-voltage protection is enabled, mining is paused as soon as the input voltage
+//! stays below the configured limit for several readings. Mining paused by the protection is
+//! resumed when the voltage rises above the limit plus hysteresis. Mining paused by someone else
+//! is left alone.
+
+use ii_logging::macros::*;
+
+use crate::async_i2c::AsyncI2cDev;
+use crate::error;
+use crate::pause;
+
+use futures::lock::Mutex;
+use ii_async_compat::{futures, runtime};
+
+use std::sync::Arc;
+use std::time::Duration;
+
+/// PMBus commands for reading of telemetry, all of them return a value in linear format
+const READ_VIN: u8 = 0x88;
+const READ_IOUT: u8 = 0x8c;
+const READ_TEMPERATURE_1: u8 = 0x8d;
+
+/// Default I2C address (7-bit) of the power supply
+pub const DEFAULT_ADDRESS: u8 = 0x58;
+
+/// Period of reading the power supply telemetry
+pub const READ_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Number of consecutive readings below the limit after which the mining is paused
+pub const UNDER_VOLTAGE_READINGS: usize = 3;
+
+/// Default difference of input voltage (in V) above the limit for resuming of mining
+pub const DEFAULT_HYSTERESIS: f64 = 10.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    /// I2C bus with the power supply
+    pub i2c_interface_num: usize,
+    pub address: u8,
+    /// Minimal input voltage in V, under-voltage protection is disabled when it is `None`
+    pub min_input_voltage: Option<f64>,
+    pub hysteresis: f64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            i2c_interface_num: 0,
+            address: DEFAULT_ADDRESS,
+            min_input_voltage: None,
+            hysteresis: DEFAULT_HYSTERESIS,
+        }
+    }
+}
+
+/// Converts PMBus linear data format with 5-bit exponent and 11-bit mantissa (both two's
+/// complement) to a real number
+pub fn linear11_to_f64(value: u16) -> f64 {
+    let exponent = (value as i16) >> 11;
+    // sign extension of the mantissa
+    let mantissa = ((value << 5) as i16) >> 5;
+    mantissa as f64 * 2f64.powi(exponent as i32)
+}
+
+/// One reading of power supply telemetry, values which cannot be read are missing
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Reading {
+    /// Input voltage in V
+    pub input_voltage: Option<f64>,
+    /// Output current in A
+    pub output_current: Option<f64>,
+    /// Temperature in degree celsius
+    pub temperature: Option<f64>,
+}
+
+/// Power supply on I2C bus
+pub struct Psu {
+    device: AsyncI2cDev,
+    address: u8,
+}
+
+impl Psu {
+    pub fn open(i2c_interface_num: usize, address: u8) -> error::Result<Self> {
+        Ok(Self {
+            device: AsyncI2cDev::open(format!("/dev/i2c-{}", i2c_interface_num))?,
+            address,
+        })
+    }
+
+    /// Reads PMBus word (little endian) in linear data format
+    async fn read_linear(&self, command: u8) -> error::Result<f64> {
+        let bytes = self
+            .device
+            .write_read(self.address, vec![command], 2)
+            .await?;
+        Ok(linear11_to_f64(u16::from_le_bytes([bytes[0], bytes[1]])))
+    }
+
+    pub async fn read(&self) -> Reading {
+        Reading {
+            input_voltage: self.read_linear(READ_VIN).await.ok(),
+            output_current: self.read_linear(READ_IOUT).await.ok(),
+            temperature: self.read_linear(READ_TEMPERATURE_1).await.ok(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    Pause,
+    Resume,
+}
+
+/// Decision logic of the under-voltage protection
+#[derive(Debug)]
+pub struct Protection {
+    min_input_voltage: f64,
+    hysteresis: f64,
+    /// Consecutive readings below the limit
+    low_readings: usize,
+    under_voltage: bool,
+}
+
+impl Protection {
+    pub fn new(min_input_voltage: f64, hysteresis: f64) -> Self {
+        Self {
+            min_input_voltage,
+            hysteresis,
+            low_readings: 0,
+            under_voltage: false,
+        }
+    }
+
+    /// Accounts one reading of input voltage, missing readings are ignored
+    pub fn check(&mut self, input_voltage: Option<f64>) -> Option<Action> {
+        let input_voltage = input_voltage?;
+        if self.under_voltage {
+            if input_voltage >= self.min_input_voltage + self.hysteresis {
+                self.under_voltage = false;
+                self.low_readings = 0;
+                return Some(Action::Resume);
+            }
+        } else if input_voltage < self.min_input_voltage {
+            self.low_readings += 1;
+            if self.low_readings >= UNDER_VOLTAGE_READINGS {
+                self.under_voltage = true;
+                return Some(Action::Pause);
+            }
+        } else {
+            self.low_readings = 0;
+        }
+        None
+    }
+
+    pub fn under_voltage(&self) -> bool {
+        self.under_voltage
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Status {
+    /// The last reading, it is `None` before the power supply is read for the first time
+    pub reading: Option<Reading>,
+    /// Mining is paused due to low input voltage
+    pub under_voltage: bool,
+}
+
+pub struct Monitor {
+    psu: Psu,
+    pause_controller: Arc<pause::Controller>,
+    protection: Mutex<Option<Protection>>,
+    /// Mining has been paused by the protection and it has to be resumed by it too
+    paused: Mutex<bool>,
+    status: Mutex<Status>,
+}
+
+impl Monitor {
+    pub fn new(psu: Psu, config: &Config, pause_controller: Arc<pause::Controller>) -> Self {
+        Self {
+            psu,
+            pause_controller,
+            protection: Mutex::new(
+                config
+                    .min_input_voltage
+                    .map(|voltage| Protection::new(voltage, config.hysteresis)),
+            ),
+            paused: Mutex::new(false),
+            status: Mutex::new(Default::default()),
+        }
+    }
+
+    pub async fn status(&self) -> Status {
+        self.status.lock().await.clone()
+    }
+
+    async fn protect(&self, action: Action, input_voltage: f64) {
+        let mut paused = self.paused.lock().await;
+        match action {
+            Action::Pause => {
+                if self.pause_controller.status().await.paused {
+                    // paused by someone else who is responsible for resuming
+                    warn!("PSU: input voltage {:.1} V is too low", input_voltage);
+                    return;
+                }
+                error!(
+                    "PSU: input voltage {:.1} V is too low, pausing mining",
+                    input_voltage
+                );
+                self.pause_controller.clone().pause(None).await;
+                *paused = true;
+            }
+            Action::Resume => {
+                info!(
+                    "PSU: input voltage {:.1} V is back to normal",
+                    input_voltage
+                );
+                if *paused {
+                    self.pause_controller.resume().await;
+                    *paused = false;
+                }
+            }
+        }
+    }
+
+    async fn update(&self) {
+        let reading = self.psu.read().await;
+        let mut under_voltage = false;
+        if let Some(protection) = self.protection.lock().await.as_mut() {
+            if let Some(action) = protection.check(reading.input_voltage) {
+                let input_voltage = reading
+                    .input_voltage
+                    .expect("BUG: action without input voltage");
+                self.protect(action, input_voltage).await;
+            }
+            under_voltage = protection.under_voltage();
+        }
+        *self.status.lock().await = Status {
+            reading: Some(reading),
+            under_voltage,
+        };
+    }
+
+    pub async fn task(self: Arc<Self>) {
+        let mut interval = runtime::Interval::new(READ_INTERVAL);
+        while interval.tick().await.is_some() {
+            self.update().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_linear11() {
+        // 230 V with exponent -2
+        assert_eq!(linear11_to_f64(0xf398), 230.0);
+        // 12.5 A with exponent -3
+        assert_eq!(linear11_to_f64(0xe864), 12.5);
+        // negative mantissa with positive exponent
+        assert_eq!(linear11_to_f64(0x0fff), -2.0);
+        assert_eq!(linear11_to_f64(0), 0.0);
+    }
+
+    #[test]
+    fn test_protection() {
+        let mut protection = Protection::new(200.0, 10.0);
+        assert_eq!(protection.check(Some(230.0)), None);
+        assert_eq!(protection.check(Some(190.0)), None);
+        // missing readings and occasional drops do not pause mining
+        assert_eq!(protection.check(None), None);
+        assert_eq!(protection.check(Some(205.0)), None);
+        for _ in 1..UNDER_VOLTAGE_READINGS {
+            assert_eq!(protection.check(Some(190.0)), None);
+        }
+        assert_eq!(protection.check(Some(190.0)), Some(Action::Pause));
+        assert!(protection.under_voltage());
+        // the voltage has to rise above the hysteresis
+        assert_eq!(protection.check(Some(205.0)), None);
+        assert_eq!(protection.check(Some(210.0)), Some(Action::Resume));
+        assert!(!protection.under_voltage());
+    }
+}
@@ -0,0 +1,117 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Gradual raising of chip frequency during start of hash chain
+//!
+//! Jumping from the reset frequency straight to the target one makes the current drawn by
+//! chips rise abruptly which stresses the power stage of the hash board. The ramp raises the
+//! frequency in steps, lets the chain settle after each of them and checks that all chips
+//! still respond before it continues.
+
+use crate::FrequencySettings;
+
+use std::time::Duration;
+
+/// The lowest frequency supported by chip PLL where the ramp starts
+pub const START_FREQUENCY: usize = 100_000_000;
+
+/// The smallest frequency increment of one step, finer steps would just prolong the start
+pub const MIN_STEP: usize = 1_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Config {
+    /// Frequency increment of one step in Hz
+    pub step: usize,
+    /// Time the chain settles after each step
+    pub delay: Duration,
+}
+
+impl Config {
+    /// Returns frequency settings of all steps which lead to `target`. Each chip is raised to
+    /// its own target frequency and the last step is always equal to `target`.
+    pub fn steps(&self, target: &FrequencySettings) -> Vec<FrequencySettings> {
+        assert!(self.step >= MIN_STEP, "BUG: too small frequency ramp step");
+        let mut steps = vec![];
+        let mut frequency = START_FREQUENCY;
+        while frequency < target.max() {
+            steps.push(FrequencySettings {
+                chip: target
+                    .chip
+                    .iter()
+                    .map(|&chip_frequency| chip_frequency.min(frequency))
+                    .collect(),
+            });
+            frequency += self.step;
+        }
+        steps.push(target.clone());
+        steps
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_steps() {
+        let ramp = Config {
+            step: 200_000_000,
+            delay: Duration::from_millis(100),
+        };
+        let mut target = FrequencySettings::from_frequency(650_000_000);
+        target.chip[1] = 400_000_000;
+
+        let steps: Vec<_> = ramp
+            .steps(&target)
+            .into_iter()
+            .map(|step| step.chip)
+            .collect();
+        assert_eq!(steps.len(), 4);
+        assert_eq!(steps[0][..2], [100_000_000, 100_000_000]);
+        assert_eq!(steps[1][..2], [300_000_000, 300_000_000]);
+        // chips with lower target frequency stop at it
+        assert_eq!(steps[2][..2], [500_000_000, 400_000_000]);
+        assert_eq!(steps[3], target.chip);
+    }
+
+    #[test]
+    fn test_steps_low_target() {
+        let ramp = Config {
+            step: 50_000_000,
+            delay: Duration::from_millis(100),
+        };
+        let target = FrequencySettings::from_frequency(START_FREQUENCY);
+        let steps = ramp.steps(&target);
+        assert_eq!(steps.len(), 1);
+        assert_eq!(steps[0].chip, target.chip);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_steps_too_small() {
+        let ramp = Config {
+            step: MIN_STEP - 1,
+            delay: Duration::from_millis(100),
+        };
+        ramp.steps(&FrequencySettings::from_frequency(650_000_000));
+    }
+}
@@ -0,0 +1,469 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Detection of dead hash chains and their automatic re-initialization
+//!
+//! A running chain is considered dead when the FPGA I/O fails, when the hashboard is unplugged,
+//! when no valid nonce arrives for `Config::dead_chain_timeout` although work is being sent to it
+//! or when responses are constantly dropped due to CRC mismatch. Such chain is powered down,
+//! reset and initialized again. The new instance mines for the same work hub so there is no need
+//! to restart the whole miner. Unplugged hashboards are started as soon as they are plugged in
+//! again. The recovery gives up after `Config::max_attempts` failed re-initializations and the
+//! chain is left powered down.
+//!
+//! Only chains stopped by the recovery itself are started again. Chains stopped by other owners
+//! (e.g. paused chains) are left alone.
+
+use ii_logging::macros::*;
+
+use crate::counters;
+use crate::error::ErrorKind;
+use crate::{ChainStatus, Manager};
+
+use bosminer::alert;
+
+use ii_async_compat::runtime;
+use runtime::delay_for;
+
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Owner of hash chains while they are being checked or recovered
+const OWNER_NAME: &str = "recovery";
+
+/// How often running chains are checked. It matches the interval in which CRC errors are
+/// accounted to chain counters.
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Default time without any valid nonce after which the chain is considered dead
+pub const DEAD_CHAIN_TIMEOUT: Duration = Duration::from_secs(180);
+
+/// Minimal number of CRC errors in one check interval for the check to be failing
+pub const CRC_ERRORS_THRESHOLD: usize = 100;
+
+/// Number of consecutive checks with CRC errors after which the chain is considered dead
+pub const CRC_FAILING_CHECKS: usize = 3;
+
+/// Powered down chain is kept in reset for this time before it is initialized again
+const POWER_DOWN_DELAY: Duration = Duration::from_secs(5);
+
+/// Default number of failed re-initializations of the chain after which the recovery gives up
+pub const MAX_RECOVERY_ATTEMPTS: usize = 5;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    /// Chain without any valid nonce for this time (while it is receiving work) is dead
+    pub dead_chain_timeout: Duration,
+    /// Number of failed re-initializations of the chain after which the recovery gives up
+    pub max_attempts: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            dead_chain_timeout: DEAD_CHAIN_TIMEOUT,
+            max_attempts: MAX_RECOVERY_ATTEMPTS,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Failure {
+    /// Reading from or writing to the FPGA failed
+    Io(String),
+    Unplugged,
+    NoValidNonces {
+        duration: Duration,
+    },
+    CrcErrors {
+        count: usize,
+    },
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(reason) => write!(f, "I/O failure: {}", reason),
+            Self::Unplugged => write!(f, "hashboard has been unplugged"),
+            Self::NoValidNonces { duration } => {
+                write!(f, "no valid nonce for {} s", duration.as_secs())
+            }
+            Self::CrcErrors { count } => write!(
+                f,
+                "{} responses dropped due to CRC mismatch in last {} checks",
+                count, CRC_FAILING_CHECKS
+            ),
+        }
+    }
+}
+
+/// Evaluates snapshots of chain counters taken in regular intervals
+pub struct Detector {
+    dead_chain_timeout: Duration,
+    last_counter: Option<counters::HashChain>,
+    /// Number of work sent to the chain at the last check
+    last_work_count: Option<usize>,
+    /// Time of the last check since which no valid nonce has arrived
+    idle_since: Option<Instant>,
+    /// CRC errors in consecutive failing checks
+    crc_failing_checks: usize,
+    crc_errors: usize,
+}
+
+impl Default for Detector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Detector {
+    pub fn new() -> Self {
+        Self::with_timeout(DEAD_CHAIN_TIMEOUT)
+    }
+
+    pub fn with_timeout(dead_chain_timeout: Duration) -> Self {
+        Self {
+            dead_chain_timeout,
+            last_counter: None,
+            last_work_count: None,
+            idle_since: None,
+            crc_failing_checks: 0,
+            crc_errors: 0,
+        }
+    }
+
+    /// Accounts the total number of work sent to the chain. It is called before each `check`
+    /// because a chain starved of work (e.g. without any pool) cannot find nonces either. The
+    /// chain is starved when no new work has been sent and it is `waiting_for_work` from the
+    /// work hub (and not for room in the work FIFO).
+    pub fn account_work(&mut self, work_count: usize, waiting_for_work: bool) {
+        if self.last_work_count.replace(work_count) == Some(work_count) && waiting_for_work {
+            self.idle_since = None;
+        }
+    }
+
+    /// Accounts `counter` snapshot taken at time `now` and returns a failure when the chain is
+    /// considered dead
+    pub fn check(&mut self, counter: counters::HashChain, now: Instant) -> Option<Failure> {
+        // Counters are compared to zero when they have been reset in the meantime
+        let (valid, crc_errors) = match self.last_counter.as_ref() {
+            Some(last) if last.started == counter.started => (
+                counter.valid.saturating_sub(last.valid),
+                counter.crc_errors.saturating_sub(last.crc_errors),
+            ),
+            _ => (counter.valid, counter.crc_errors),
+        };
+        let first_check = self.last_counter.is_none();
+        self.last_counter.replace(counter);
+
+        if valid > 0 {
+            self.idle_since = None;
+        } else {
+            let idle_since = *self.idle_since.get_or_insert(now);
+            let duration = now.saturating_duration_since(idle_since);
+            if duration >= self.dead_chain_timeout {
+                return Some(Failure::NoValidNonces { duration });
+            }
+        }
+        if first_check {
+            // The first snapshot is only a baseline for the next check
+            return None;
+        }
+
+        if crc_errors >= CRC_ERRORS_THRESHOLD {
+            self.crc_failing_checks += 1;
+            self.crc_errors += crc_errors;
+            if self.crc_failing_checks >= CRC_FAILING_CHECKS {
+                return Some(Failure::CrcErrors {
+                    count: self.crc_errors,
+                });
+            }
+        } else {
+            self.crc_failing_checks = 0;
+            self.crc_errors = 0;
+        }
+        None
+    }
+}
+
+/// State of a chain stopped by the recovery
+struct Recovery {
+    attempts: usize,
+    waiting_for_hashboard: bool,
+}
+
+/// Checks the running chain and stops it when it is dead. Returns `true` when the chain has been
+/// stopped.
+async fn check_chain(manager: &Arc<Manager>, config: &Config, detector: &mut Detector) -> bool {
+    let running_chain = match manager.clone().acquire(OWNER_NAME).await {
+        Ok(ChainStatus::Running(running_chain)) => running_chain,
+        // Chains stopped or being handled by someone else are not checked
+        Ok(ChainStatus::Stopped(_)) | Err(_) => {
+            *detector = Detector::with_timeout(config.dead_chain_timeout);
+            return false;
+        }
+    };
+
+    let failure = match running_chain.get_failure().await {
+        Some(reason) => Some(Failure::Io(reason)),
+        None if !manager.hashboard_present() => Some(Failure::Unplugged),
+        // Idle chain does not return any nonces
+        None if running_chain.is_idle().await => {
+            *detector = Detector::with_timeout(config.dead_chain_timeout);
+            None
+        }
+        None => {
+            detector.account_work(
+                running_chain.work_count().await,
+                running_chain.is_waiting_for_work().await,
+            );
+            detector.check(running_chain.snapshot_counter().await, Instant::now())
+        }
+    };
+    let failure = match failure {
+        Some(failure) => failure,
+        None => return false,
+    };
+
+    error!("Chain {} is dead: {}", manager.hashboard_idx, failure);
+    let counter = running_chain.snapshot_counter().await;
+    info!(
+        "Chain {} diagnostics: {} chips, {} work sent, {} valid nonces, {} errors, \
+         {} CRC errors, {} RX errors in {} s",
+        manager.hashboard_idx,
+        counter.chip_count(),
+        running_chain.work_count().await,
+        counter.valid,
+        counter.errors,
+        counter.crc_errors,
+        counter.rx_errors,
+        counter.duration().as_secs()
+    );
+    manager.alert_sender.notify(alert::Event::ChainDead {
+        hashboard_idx: manager.hashboard_idx,
+        reason: failure.to_string(),
+    });
+    // Stopping of the chain powers down the hashboard and keeps it in reset
+    running_chain.stop().await;
+    *detector = Detector::with_timeout(config.dead_chain_timeout);
+    true
+}
+
+/// Initializes again the chain stopped by the recovery. Returns `true` when the recovery is
+/// finished.
+async fn recover_chain(manager: &Arc<Manager>, config: &Config, recovery: &mut Recovery) -> bool {
+    let stopped_chain = match manager.clone().acquire(OWNER_NAME).await {
+        Ok(ChainStatus::Stopped(stopped_chain)) => stopped_chain,
+        // Someone else has started the chain in the meantime
+        Ok(ChainStatus::Running(_)) => return true,
+        Err(_) => return false,
+    };
+
+    if !manager.hashboard_present() {
+        if !recovery.waiting_for_hashboard {
+            info!(
+                "Chain {}: waiting for the hashboard to be plugged in",
+                manager.hashboard_idx
+            );
+            recovery.waiting_for_hashboard = true;
+        }
+        return false;
+    }
+    recovery.waiting_for_hashboard = false;
+    recovery.attempts += 1;
+
+    info!(
+        "Re-initializing chain {} (attempt {}/{})",
+        manager.hashboard_idx, recovery.attempts, config.max_attempts
+    );
+    match stopped_chain
+        .start(
+            &manager.chain_config.frequency,
+            manager.chain_config.voltage,
+            manager.chain_config.asic_difficulty,
+        )
+        .await
+    {
+        Ok(_) => {
+            info!("Chain {} recovered", manager.hashboard_idx);
+            true
+        }
+        Err((_, e)) => {
+            error!(
+                "Failed to re-initialize chain {}: {}",
+                manager.hashboard_idx, e
+            );
+            if let ErrorKind::ChainNotFound(_) = e.kind() {
+                // The hashboard has been unplugged during the initialization so the attempt is
+                // not accounted and the recovery waits for the hashboard again
+                recovery.attempts -= 1;
+                return false;
+            }
+            if recovery.attempts >= config.max_attempts {
+                error!(
+                    "Giving up recovery of chain {} after {} attempts",
+                    manager.hashboard_idx, recovery.attempts
+                );
+                manager.alert_sender.notify(alert::Event::ChainDead {
+                    hashboard_idx: manager.hashboard_idx,
+                    reason: format!(
+                        "recovery given up after {} failed attempts: {}",
+                        recovery.attempts, e
+                    ),
+                });
+                return true;
+            }
+            false
+        }
+    }
+}
+
+/// Watches the chain managed by `manager` and re-initializes it when it is dead
+pub async fn recovery_task(manager: Arc<Manager>, config: Config) {
+    let mut detector = Detector::with_timeout(config.dead_chain_timeout);
+    let mut recovery: Option<Recovery> = None;
+    loop {
+        match recovery.as_mut() {
+            None => {
+                delay_for(CHECK_INTERVAL).await;
+                if check_chain(&manager, &config, &mut detector).await {
+                    delay_for(POWER_DOWN_DELAY).await;
+                    recovery = Some(Recovery {
+                        attempts: 0,
+                        waiting_for_hashboard: false,
+                    });
+                }
+            }
+            Some(state) => {
+                if recover_chain(&manager, &config, state).await {
+                    recovery = None;
+                } else {
+                    delay_for(CHECK_INTERVAL).await;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config;
+
+    fn counter(base: &counters::HashChain, valid: usize, crc_errors: usize) -> counters::HashChain {
+        let mut counter = base.clone();
+        counter.valid = valid;
+        counter.crc_errors = crc_errors;
+        counter
+    }
+
+    #[test]
+    fn test_no_valid_nonces() {
+        let base = counters::HashChain::new(1, config::DEFAULT_ASIC_DIFFICULTY);
+        let mut detector = Detector::new();
+        let mut now = Instant::now();
+        for valid in 1..5 {
+            assert_eq!(detector.check(counter(&base, valid, 0), now), None);
+            now += CHECK_INTERVAL;
+        }
+        // the chain stops hashing
+        for _ in 0..DEAD_CHAIN_TIMEOUT.as_secs() / CHECK_INTERVAL.as_secs() {
+            assert_eq!(detector.check(counter(&base, 4, 0), now), None);
+            now += CHECK_INTERVAL;
+        }
+        assert_eq!(
+            detector.check(counter(&base, 4, 0), now),
+            Some(Failure::NoValidNonces {
+                duration: DEAD_CHAIN_TIMEOUT
+            })
+        );
+    }
+
+    #[test]
+    fn test_crc_errors() {
+        let base = counters::HashChain::new(1, config::DEFAULT_ASIC_DIFFICULTY);
+        let mut detector = Detector::new();
+        let now = Instant::now();
+        assert_eq!(detector.check(counter(&base, 1, 0), now), None);
+        assert_eq!(detector.check(counter(&base, 2, 100), now), None);
+        assert_eq!(detector.check(counter(&base, 3, 200), now), None);
+        // occasional errors reset the failing checks
+        assert_eq!(detector.check(counter(&base, 4, 210), now), None);
+        assert_eq!(detector.check(counter(&base, 5, 400), now), None);
+        assert_eq!(detector.check(counter(&base, 6, 600), now), None);
+        assert_eq!(
+            detector.check(counter(&base, 7, 800), now),
+            Some(Failure::CrcErrors { count: 590 })
+        );
+    }
+
+    #[test]
+    fn test_counter_reset() {
+        let mut base = counters::HashChain::new(1, config::DEFAULT_ASIC_DIFFICULTY);
+        let mut detector = Detector::new();
+        let now = Instant::now();
+        assert_eq!(detector.check(counter(&base, 100, 0), now), None);
+        assert_eq!(detector.check(counter(&base, 110, 1000), now), None);
+        // counters with less valid nonces and errors after reset are not considered dead
+        base.reset();
+        assert_eq!(detector.check(counter(&base, 10, 0), now), None);
+    }
+
+    #[test]
+    fn test_starved_chain() {
+        let base = counters::HashChain::new(1, config::DEFAULT_ASIC_DIFFICULTY);
+        let timeout = Duration::from_secs(300);
+        let mut detector = Detector::with_timeout(timeout);
+        let mut now = Instant::now();
+        detector.account_work(10, false);
+        assert_eq!(detector.check(counter(&base, 1, 0), now), None);
+        // the chain is waiting for work which does not arrive
+        for _ in 0..10 {
+            now += CHECK_INTERVAL;
+            detector.account_work(10, true);
+            assert_eq!(detector.check(counter(&base, 1, 0), now), None);
+        }
+        // the chain stops hashing although work is being sent
+        for i in 1..timeout.as_secs() / CHECK_INTERVAL.as_secs() {
+            now += CHECK_INTERVAL;
+            detector.account_work(20 + i as usize, false);
+            assert_eq!(detector.check(counter(&base, 1, 0), now), None);
+        }
+        now += CHECK_INTERVAL;
+        detector.account_work(100, false);
+        assert_eq!(
+            detector.check(counter(&base, 1, 0), now),
+            Some(Failure::NoValidNonces { duration: timeout })
+        );
+
+        // work FIFO which does not take any work does not prevent the detection
+        let mut detector = Detector::with_timeout(timeout);
+        for _ in 0..=timeout.as_secs() / CHECK_INTERVAL.as_secs() {
+            detector.account_work(10, false);
+            assert_eq!(detector.check(counter(&base, 1, 0), now), None);
+            now += CHECK_INTERVAL;
+        }
+        detector.account_work(10, false);
+        assert!(detector.check(counter(&base, 1, 0), now).is_some());
+    }
+}
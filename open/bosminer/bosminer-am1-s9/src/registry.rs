@@ -24,6 +24,13 @@ use crate::Solution;
 
 use bosminer::work;
 use std::iter::Iterator;
+use std::time::{Duration, Instant};
+
+/// Expired work is kept in the registry for this long so that solutions which are already on the
+/// way from chips are still paired with it (and accounted as stale)
+pub const EXPIRED_WORK_TIMEOUT: Duration = Duration::from_secs(10);
+/// How often the registry should be scanned for expired work
+pub const RETIRE_EXPIRED_INTERVAL: Duration = Duration::from_secs(1);
 
 /// Mining registry item contains work and solutions
 #[derive(Clone)]
@@ -34,6 +41,8 @@ pub struct WorkRegistryItem {
     solutions: std::vec::Vec<Solution>,
     /// Flag that work is only for initialization of the mining chips and any results coming from it should be ignored
     pub initial_work: bool,
+    /// Time when the work has been found expired by `WorkRegistry::retire_expired`
+    expired_at: Option<Instant>,
 }
 
 impl WorkRegistryItem {
@@ -48,11 +57,12 @@ impl WorkRegistryItem {
             mismatched_nonce: false,
             unique_solution: None,
         };
-        // scan the current solutions and detect a duplicate
-        let matching_solution = self
-            .solutions
-            .iter()
-            .find(|solution| solution.nonce == new_solution.nonce);
+        // scan the current solutions and detect a duplicate, the same nonce of another midstate
+        // is a different solution
+        let matching_solution = self.solutions.iter().find(|solution| {
+            solution.nonce == new_solution.nonce
+                && solution.midstate_idx == new_solution.midstate_idx
+        });
         if matching_solution.is_none() {
             // At this point, we know such solution has not been received yet. If it is valid (no
             // hardware error detected == meets the target), it can be appended to the solution list
@@ -82,6 +92,35 @@ pub struct InsertSolutionStatus {
     pub unique_solution: Option<work::Solution>,
 }
 
+/// Statistics of work retired from the registry used for detection of work lost by hardware
+/// (e.g. dropped by FIFO or driver). Work is retired long after chips should have exhausted its
+/// nonce space so work without any solution has never been processed or its results have been
+/// lost.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct WorkStats {
+    /// Number of retired work items which have been sent to chips
+    pub retired: u64,
+    /// Number of retired work items which have not returned any solution
+    pub empty: u64,
+}
+
+impl WorkStats {
+    /// Expected number of work items without any solution. Chips return only nonces meeting
+    /// ASIC target so the number of solutions of one work is Poisson distributed with mean
+    /// `midstate_count / asic_difficulty`.
+    pub fn expected_empty(&self, midstate_count: usize, asic_difficulty: usize) -> f64 {
+        let mean_solutions = midstate_count as f64 / asic_difficulty as f64;
+        self.retired as f64 * (-mean_solutions).exp()
+    }
+
+    /// Estimated number of work items lost by hardware
+    pub fn lost(&self, midstate_count: usize, asic_difficulty: usize) -> u64 {
+        (self.empty as f64 - self.expected_empty(midstate_count, asic_difficulty))
+            .max(0.0)
+            .round() as u64
+    }
+}
+
 /// Simple work registry with `work_id` allocator
 ///
 /// Registry is responsible for associating `work` with `work_id` and managing
@@ -93,14 +132,23 @@ pub struct InsertSolutionStatus {
 /// The idea behind this registry is that we manage `registry_size` of slots and
 /// we assign work to them (under `work_id` we generate for each inserted work), but
 /// we always keep at least `registry_size / 2` slots free, so that we can detect
-/// stale work.
+/// stale work. Late solutions of retired work then find an empty slot instead of colliding
+/// with new work which reuses the same `work_id` after wrap-around.
+///
+/// Work of invalidated jobs is garbage collected by `retire_expired` so that it does not have to
+/// wait for another `registry_size / 2` work items. Work is always retired in the order it has
+/// been stored so the registry only has to track the oldest `work_id`.
 pub struct WorkRegistry {
     /// Number of elements in registry. Determines `work_id` range
     registry_size: usize,
     /// Next id that is to be assigned to work, this increases modulo `registry_size`
     next_work_id: usize,
+    /// Id of the oldest work in the registry, all slots from this one up to `next_work_id` are
+    /// occupied
+    oldest_work_id: usize,
     /// Current pending work list Each work item has a list of associated work solutions
     pending_work_list: std::vec::Vec<Option<WorkRegistryItem>>,
+    stats: WorkStats,
 }
 
 impl WorkRegistry {
@@ -109,10 +157,17 @@ impl WorkRegistry {
         Self {
             registry_size,
             next_work_id: 0,
+            oldest_work_id: 0,
             pending_work_list: vec![None; registry_size],
+            stats: Default::default(),
         }
     }
 
+    /// Number of slots, valid `work_id` is always lower
+    pub fn size(&self) -> usize {
+        self.registry_size
+    }
+
     /// Allocate next `work_id`. IDs are assigned in circular fashion.
     /// This function is internal to the registry
     fn alloc_next_work_id(&mut self) -> usize {
@@ -129,27 +184,92 @@ impl WorkRegistry {
     /// As a side effect, retire stale work.
     /// Returns: new `work_id`
     pub fn store_work(&mut self, work: work::Assignment, initial_work: bool) -> usize {
-        let work_id = self.alloc_next_work_id();
-
         // retire stale work
-        let retire_id = (work_id + self.registry_size / 2) % self.registry_size;
-        self.pending_work_list[retire_id] = None;
+        if self.live_count() >= self.registry_size / 2 {
+            self.retire_oldest();
+        }
+
+        let work_id = self.alloc_next_work_id();
 
         // put new work into registry
         self.pending_work_list[work_id] = Some(WorkRegistryItem {
             work,
             solutions: std::vec::Vec::new(),
             initial_work,
+            expired_at: None,
         });
 
         // return assigned work id
         work_id
     }
 
-    /// Look-up work id
-    pub fn find_work(&mut self, work_id: usize) -> &mut Option<WorkRegistryItem> {
-        assert!(work_id < self.registry_size);
-        &mut self.pending_work_list[work_id]
+    /// Number of work items stored in the registry
+    fn live_count(&self) -> usize {
+        (self.next_work_id + self.registry_size - self.oldest_work_id) % self.registry_size
+    }
+
+    /// Removes the oldest work from the registry and accounts it in statistics
+    fn retire_oldest(&mut self) {
+        let work_id = self.oldest_work_id;
+        self.oldest_work_id = (work_id + 1) % self.registry_size;
+        if let Some(retired_work) = self.pending_work_list[work_id].take() {
+            if !retired_work.initial_work {
+                self.stats.retired += 1;
+                if retired_work.solutions.is_empty() {
+                    self.stats.empty += 1;
+                }
+            }
+        }
+    }
+
+    /// Retires work which has been expired for at least `EXPIRED_WORK_TIMEOUT`. Expired work
+    /// is only marked on the first scan so this should be called every
+    /// `RETIRE_EXPIRED_INTERVAL`.
+    /// Work is generated from jobs in order so only expired work from the oldest one is walked
+    /// and the scan stops at the first valid work. Expired work stored after valid work (e.g.
+    /// from another pool) is retired as usual when its slot is needed for new work.
+    /// Returns: number of retired work items
+    pub fn retire_expired(&mut self, now: Instant) -> usize {
+        let mut retired = 0;
+        let mut work_id = self.oldest_work_id;
+        while work_id != self.next_work_id {
+            let item = self.pending_work_list[work_id]
+                .as_mut()
+                .expect("BUG: missing work in registry");
+            if !item.work.is_expired(now) {
+                break;
+            }
+            let expired_at = *item.expired_at.get_or_insert(now);
+            // work can be retired only from the beginning of the registry
+            if work_id == self.oldest_work_id
+                && now.duration_since(expired_at) >= EXPIRED_WORK_TIMEOUT
+            {
+                self.retire_oldest();
+                retired += 1;
+            }
+            work_id = (work_id + 1) % self.registry_size;
+        }
+        retired
+    }
+
+    /// Checks whether the most recently stored work has expired, e.g. because its job has been
+    /// invalidated by a new block. All work queued in hardware up to now is then useless.
+    pub fn is_last_work_expired(&self, now: Instant) -> bool {
+        let last_work_id = (self.next_work_id + self.registry_size - 1) % self.registry_size;
+        self.pending_work_list[last_work_id]
+            .as_ref()
+            .map_or(false, |item| item.work.is_expired(now))
+    }
+
+    /// Look-up work id. Ids out of range (e.g. corrupted by hardware) have no work.
+    pub fn find_work(&mut self, work_id: usize) -> Option<&mut WorkRegistryItem> {
+        self.pending_work_list
+            .get_mut(work_id)
+            .and_then(|item| item.as_mut())
+    }
+
+    pub fn stats(&self) -> WorkStats {
+        self.stats
     }
 }
 
@@ -238,4 +358,177 @@ mod test {
             false
         );
     }
+
+    /// Test that retired work without solutions is accounted
+    #[test]
+    fn test_work_stats() {
+        const REGISTRY_SIZE: usize = 4;
+        let mut registry = WorkRegistry::new(REGISTRY_SIZE);
+
+        // initial work is not accounted
+        registry.store_work(null_work::prepare(0), true);
+        for i in 1..REGISTRY_SIZE {
+            registry.store_work(null_work::prepare(i as u64), false);
+        }
+        assert_eq!(
+            registry.stats(),
+            WorkStats {
+                retired: 1,
+                empty: 1,
+            }
+        );
+
+        // pending work 3 gets a solution before it is retired
+        let solution = Solution {
+            nonce: 0,
+            midstate_idx: 0,
+            solution_idx: 0,
+            target: ii_bitcoin::Target::from_pool_difficulty(1),
+        };
+        registry
+            .find_work(3)
+            .as_mut()
+            .expect("work not found")
+            .insert_solution(solution);
+        for i in 0..REGISTRY_SIZE {
+            registry.store_work(null_work::prepare(i as u64), false);
+        }
+        assert_eq!(
+            registry.stats(),
+            WorkStats {
+                retired: 5,
+                empty: 4,
+            }
+        );
+    }
+
+    /// Test that repeated nonce of the same midstate is detected as duplicate
+    #[test]
+    fn test_duplicate_solution() {
+        let mut registry = WorkRegistry::new(4);
+        registry.store_work(null_work::prepare(0), false);
+        let solution = |nonce, midstate_idx| Solution {
+            nonce,
+            midstate_idx,
+            solution_idx: 0,
+            target: ii_bitcoin::Target::from_pool_difficulty(1),
+        };
+        let work_item = registry.find_work(0).expect("work not found");
+
+        assert!(!work_item.insert_solution(solution(1, 0)).duplicate);
+        assert!(work_item.insert_solution(solution(1, 0)).duplicate);
+        assert!(!work_item.insert_solution(solution(1, 1)).duplicate);
+        assert!(!work_item.insert_solution(solution(2, 0)).duplicate);
+        assert!(work_item.insert_solution(solution(2, 0)).duplicate);
+    }
+
+    /// Test that expired work is retired after a timeout and that valid work is kept
+    #[test]
+    fn test_retire_expired() {
+        let mut registry = WorkRegistry::new(8);
+        let now = Instant::now();
+        registry.store_work(null_work::prepare(0).with_deadline(Some(now)), false);
+        registry.store_work(null_work::prepare(1), false);
+
+        // expired work is only marked on the first scan
+        assert_eq!(registry.retire_expired(now), 0);
+        assert!(registry.find_work(0).is_some());
+        assert_eq!(registry.retire_expired(now + EXPIRED_WORK_TIMEOUT / 2), 0);
+        assert_eq!(registry.retire_expired(now + EXPIRED_WORK_TIMEOUT), 1);
+        assert!(registry.find_work(0).is_none());
+        assert!(registry.find_work(1).is_some());
+        assert_eq!(
+            registry.stats(),
+            WorkStats {
+                retired: 1,
+                empty: 1,
+            }
+        );
+
+        // retired work id is reused as usual
+        for i in 2..8 {
+            registry.store_work(null_work::prepare(i), false);
+        }
+        assert_eq!(registry.store_work(null_work::prepare(8), false), 0);
+    }
+
+    /// Test that expired work is retired only up to the first valid work
+    #[test]
+    fn test_retire_expired_in_order() {
+        let mut registry = WorkRegistry::new(8);
+        let now = Instant::now();
+        registry.store_work(null_work::prepare(0).with_deadline(Some(now)), false);
+        registry.store_work(null_work::prepare(1), false);
+        registry.store_work(null_work::prepare(2).with_deadline(Some(now)), false);
+
+        assert_eq!(registry.retire_expired(now), 0);
+        assert_eq!(registry.retire_expired(now + EXPIRED_WORK_TIMEOUT), 1);
+        assert!(registry.find_work(0).is_none());
+        // expired work stored after valid work is kept until its slot is needed
+        assert!(registry.find_work(1).is_some());
+        assert!(registry.find_work(2).is_some());
+        for i in 3..7 {
+            registry.store_work(null_work::prepare(i), false);
+        }
+        assert!(registry.find_work(1).is_none());
+        assert!(registry.find_work(2).is_none());
+    }
+
+    /// Test that expiration of the last stored work is detected
+    #[test]
+    fn test_last_work_expired() {
+        let mut registry = WorkRegistry::new(4);
+        let now = Instant::now();
+        assert!(!registry.is_last_work_expired(now));
+
+        registry.store_work(null_work::prepare(0).with_deadline(Some(now)), false);
+        assert!(registry.is_last_work_expired(now));
+        // fresh work after the expired one is not affected
+        registry.store_work(null_work::prepare(1), false);
+        assert!(!registry.is_last_work_expired(now));
+
+        // detection works across wrap-around of work ids
+        registry.store_work(null_work::prepare(2), false);
+        registry.store_work(null_work::prepare(3).with_deadline(Some(now)), false);
+        assert!(registry.is_last_work_expired(now));
+        registry.store_work(null_work::prepare(4), false);
+        assert!(!registry.is_last_work_expired(now));
+    }
+
+    /// Test that ids which have never been allocated do not have any work
+    #[test]
+    fn test_find_work_out_of_range() {
+        let mut registry = WorkRegistry::new(4);
+        registry.store_work(null_work::prepare(0), false);
+        assert!(registry.find_work(4).is_none());
+        assert!(registry.find_work(usize::max_value()).is_none());
+    }
+
+    /// Test that the registry without free slots accounts replaced work
+    #[test]
+    fn test_single_slot_registry() {
+        let mut registry = WorkRegistry::new(1);
+        assert_eq!(registry.store_work(null_work::prepare(0), false), 0);
+        assert_eq!(registry.store_work(null_work::prepare(1), false), 0);
+        assert_eq!(registry.stats().retired, 1);
+    }
+
+    #[test]
+    fn test_lost_work() {
+        let stats = WorkStats {
+            retired: 1000,
+            empty: 1000,
+        };
+        // one solution per work is expected on average
+        let expected_empty = stats.expected_empty(4, 4);
+        assert!((expected_empty - 367.9).abs() < 0.1);
+        assert_eq!(stats.lost(4, 4), 632);
+
+        // no work is lost when there are less empty work items than expected
+        let stats = WorkStats {
+            retired: 1000,
+            empty: 100,
+        };
+        assert_eq!(stats.lost(4, 4), 0);
+    }
 }
@@ -0,0 +1,114 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Soft restart of the backend for recovering from hardware glitches
+//!
+//! Running hash chains are powered down and initialized again from scratch (reset, enumeration
+//! and configuration of chips) while the rest of the miner keeps running. Pool connections,
+//! share accounting and statistics are left intact.
+//!
+//! The restart is also used for switching the number of midstates (AsicBoost on/off) because
+//! the IP core midstate mode is configured during initialization of the chain.
+
+use ii_logging::macros::*;
+
+use crate::bm1387::MidstateCount;
+use crate::error;
+use crate::{ChainStatus, Manager, RunningChain};
+
+use bosminer::alert;
+
+use ii_async_compat::runtime;
+
+use std::sync::Arc;
+
+/// Owner of hash chains while they are being restarted
+const OWNER_NAME: &str = "restart";
+
+/// Restarts all running hash chains in the background and returns the number of chains being
+/// restarted. Stopped (e.g. paused) chains are left alone and busy chains are skipped.
+pub async fn restart_chains(managers: &[Arc<Manager>]) -> usize {
+    let mut running_chains = vec![];
+    for manager in managers.iter() {
+        match manager.clone().acquire(OWNER_NAME).await {
+            Ok(ChainStatus::Running(running_chain)) => running_chains.push(running_chain),
+            Ok(ChainStatus::Stopped(_)) => {}
+            Err(owner) => warn!(
+                "Cannot restart chain {} which is busy (owned by '{}')",
+                manager.hashboard_idx, owner
+            ),
+        }
+    }
+
+    let count = running_chains.len();
+    if count > 0 {
+        info!("Restarting {} chain(s)", count);
+        // Chain start can take a long time so do not block the caller
+        runtime::spawn(restart_task(running_chains));
+    }
+    count
+}
+
+/// Changes the number of midstates of all hash chains and restarts the running ones so that the
+/// IP core is switched to the new midstate mode and work in flight is flushed. Returns the number
+/// of chains being restarted.
+pub async fn set_midstate_count(
+    managers: &[Arc<Manager>],
+    midstate_count: MidstateCount,
+) -> error::Result<usize> {
+    let mut changed = false;
+    for manager in managers.iter() {
+        changed |= manager.get_midstate_count().to_count() != midstate_count.to_count();
+        manager.set_midstate_count(midstate_count)?;
+    }
+    if !changed {
+        return Ok(0);
+    }
+    info!("Switching to {} midstate(s)", midstate_count.to_count());
+    Ok(restart_chains(managers).await)
+}
+
+/// Chains are restarted one by one so that the others keep mining in the meantime
+async fn restart_task(running_chains: Vec<RunningChain>) {
+    for running_chain in running_chains {
+        let manager = running_chain.manager.clone();
+        info!("Restarting chain {}", manager.hashboard_idx);
+        let stopped_chain = running_chain.stop().await;
+        match stopped_chain
+            .start(
+                &manager.chain_config.frequency,
+                manager.chain_config.voltage,
+                manager.chain_config.asic_difficulty,
+            )
+            .await
+        {
+            Ok(_) => info!("Chain {} restarted", manager.hashboard_idx),
+            Err((_, e)) => {
+                error!("Failed to restart chain {}: {}", manager.hashboard_idx, e);
+                manager.alert_sender.notify(alert::Event::ChainDead {
+                    hashboard_idx: manager.hashboard_idx,
+                    reason: e.to_string(),
+                });
+            }
+        }
+    }
+}
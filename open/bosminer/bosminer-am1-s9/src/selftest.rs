@@ -0,0 +1,245 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Self-test of hashing chips with work of known solutions
+//!
+//! Work is broadcast to all chips on the chain and the nonce space is split among chips, so each
+//! test vector checks the chip which owns the nonce subrange of its winning nonce (see
+//! `bm1387::CoreAddress`). Known vectors cover only a few subranges so the chips are also given
+//! generated work with rolled ntime. Every chip is expected to find valid solutions of the
+//! generated work in its subrange when its difficulty is lowered to `DIFFICULTY`.
+
+use crate::bm1387;
+use crate::null_work;
+
+use bosminer::test_utils::TEST_BLOCKS;
+use bosminer::work;
+
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Time to wait for solutions of test vectors after the last work has been sent
+pub const SOLUTION_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Number of work items sent after the vectors to push their solutions out of chip queues
+pub const FLUSH_WORK_COUNT: usize = 3;
+
+/// Difficulty of chips during self-test. Each work with one midstate yields one solution of
+/// difficulty 1 on average in the whole nonce space.
+pub const DIFFICULTY: usize = 1;
+
+/// Number of generated work items (with one midstate) for each chip, i.e. the expected number of
+/// solutions found by each chip
+const GENERATED_WORK_PER_CHIP: usize = 16;
+
+/// Work with a known winning nonce
+#[derive(Debug, Clone)]
+pub struct Vector {
+    pub work: work::Assignment,
+    pub nonce: u32,
+}
+
+impl Vector {
+    /// Index of chip which is expected to find the nonce
+    pub fn chip(&self) -> usize {
+        bm1387::CoreAddress::new(self.nonce).chip
+    }
+}
+
+/// Builds all test vectors for chains running with `midstate_count` midstates. All midstates of
+/// the work are the same so the nonce is reported (at least) for one of them.
+pub fn vectors(midstate_count: usize) -> Vec<Vector> {
+    let mut vectors: Vec<_> = TEST_BLOCKS
+        .iter()
+        .map(|block| {
+            let mut work = work::Assignment::from(block);
+            work.midstates = vec![work.midstates[0].clone(); midstate_count];
+            Vector {
+                work,
+                nonce: block.nonce,
+            }
+        })
+        .collect();
+
+    // null work with empty midstate which has 2 valid nonces
+    let time = 0xffff_ffff;
+    let job = Arc::new(null_work::NullJob::new(time, 0xffff_ffff, 0));
+    let midstate = work::Midstate {
+        version: 0,
+        state: [0u8; ii_bitcoin::SHA256_DIGEST_SIZE].into(),
+    };
+    let work = work::Assignment::new(job, vec![midstate; midstate_count], time);
+    for &nonce in [0x83ea0372, 0x09f86be1].iter() {
+        vectors.push(Vector {
+            work: work.clone(),
+            nonce,
+        });
+    }
+    vectors
+}
+
+/// Builds work with rolled ntime and unknown solutions which can be verified in software. There is
+/// enough work for each of `chip_count` chips to find solutions in its nonce subrange.
+pub fn generated_work(midstate_count: usize, chip_count: usize) -> Vec<work::Assignment> {
+    let block = &TEST_BLOCKS[0];
+    let mut work = work::Assignment::from(block);
+    work.midstates = vec![work.midstates[0].clone(); midstate_count];
+    let count = (chip_count * GENERATED_WORK_PER_CHIP + midstate_count - 1) / midstate_count;
+    (1..=count as u32)
+        .map(|i| {
+            // ntime is not part of midstate so it is shared by all work
+            let mut work = work.clone();
+            work.ntime = block.time.wrapping_add(i);
+            work
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChipStatus {
+    /// No test vector covers the chip
+    Untested,
+    /// All test vectors of the chip have been solved correctly
+    Passed,
+    /// A test vector of the chip has not been solved or the chip returned an invalid solution
+    Failed,
+}
+
+impl ChipStatus {
+    fn symbol(&self) -> char {
+        match self {
+            ChipStatus::Untested => '-',
+            ChipStatus::Passed => 'o',
+            ChipStatus::Failed => 'x',
+        }
+    }
+}
+
+impl fmt::Display for ChipStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = match self {
+            ChipStatus::Untested => "untested",
+            ChipStatus::Passed => "passed",
+            ChipStatus::Failed => "failed",
+        };
+        write!(f, "{}", status)
+    }
+}
+
+/// Results of self-test of all chips on one chain
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    pub chips: Vec<ChipStatus>,
+}
+
+impl Report {
+    pub fn new(chip_count: usize) -> Self {
+        Self {
+            chips: vec![ChipStatus::Untested; chip_count],
+        }
+    }
+
+    /// Accounts result of one check of `chip`, a failure cannot be overridden by a later pass.
+    /// Results of non-existent chips are ignored.
+    pub fn account(&mut self, chip: usize, passed: bool) {
+        if let Some(status) = self.chips.get_mut(chip) {
+            *status = match (*status, passed) {
+                (ChipStatus::Failed, _) | (_, false) => ChipStatus::Failed,
+                (_, true) => ChipStatus::Passed,
+            };
+        }
+    }
+
+    pub fn count(&self, status: ChipStatus) -> usize {
+        self.chips.iter().filter(|&&chip| chip == status).count()
+    }
+}
+
+/// Prints counts of chips followed by a matrix with one symbol per chip
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} passed, {} failed, {} untested [",
+            self.count(ChipStatus::Passed),
+            self.count(ChipStatus::Failed),
+            self.count(ChipStatus::Untested),
+        )?;
+        for chip in self.chips.iter() {
+            write!(f, "{}", chip.symbol())?;
+        }
+        write!(f, "]")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_vectors() {
+        let vectors = vectors(4);
+        assert_eq!(vectors.len(), TEST_BLOCKS.len() + 2);
+        for vector in vectors.iter() {
+            assert_eq!(vector.work.midstates.len(), 4);
+        }
+        let chips: Vec<_> = vectors.iter().map(|vector| vector.chip()).collect();
+        assert_eq!(chips, vec![0, 16, 49, 28, 56]);
+    }
+
+    #[test]
+    fn test_generated_work() {
+        let work = generated_work(4, 63);
+        assert_eq!(work.len(), 63 * GENERATED_WORK_PER_CHIP / 4);
+        let mut ntimes: Vec<_> = work.iter().map(|work| work.ntime).collect();
+        ntimes.dedup();
+        assert_eq!(ntimes.len(), work.len());
+        // known solution of the original block cannot be confused with generated work
+        assert!(!ntimes.contains(&TEST_BLOCKS[0].time));
+        for work in work.iter() {
+            assert_eq!(work.midstates.len(), 4);
+        }
+        assert_eq!(generated_work(1, 63).len(), 63 * GENERATED_WORK_PER_CHIP);
+    }
+
+    #[test]
+    fn test_report() {
+        let mut report = Report::new(4);
+        report.account(1, true);
+        report.account(2, true);
+        report.account(2, false);
+        report.account(2, true);
+        // non-existent chip
+        report.account(10, false);
+        assert_eq!(
+            report.chips,
+            vec![
+                ChipStatus::Untested,
+                ChipStatus::Passed,
+                ChipStatus::Failed,
+                ChipStatus::Untested
+            ]
+        );
+        assert_eq!(report.to_string(), "1 passed, 1 failed, 2 untested [-ox-]");
+    }
+}
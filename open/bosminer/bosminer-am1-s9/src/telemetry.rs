@@ -0,0 +1,80 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! This module provides S9 specific telemetry (temperatures, fans and power estimate)
+
+use crate::monitor;
+
+use bosminer::async_trait;
+use bosminer::telemetry;
+
+use std::sync::Arc;
+
+pub struct Hardware {
+    managers: Vec<Arc<crate::Manager>>,
+    monitor: Arc<monitor::Monitor>,
+}
+
+impl Hardware {
+    pub fn new(managers: Vec<Arc<crate::Manager>>, monitor: Arc<monitor::Monitor>) -> Self {
+        Self { managers, monitor }
+    }
+
+    /// Sums power estimates of all hash chains
+    async fn estimate_power(&self) -> f64 {
+        let mut power = 0.0;
+        for manager in self.managers.iter() {
+            power += manager.efficiency.take_snapshot().await.power;
+        }
+        power
+    }
+}
+
+#[async_trait]
+impl telemetry::HardwareSource for Hardware {
+    async fn hardware(&self) -> telemetry::Hardware {
+        let status = self.monitor.status_receiver.borrow().clone();
+        let (temperatures, fan_speeds) = match status {
+            Some(status) => (
+                status
+                    .temperature_accumulator
+                    .chain_temperatures
+                    .iter()
+                    .map(|temperature| match temperature {
+                        monitor::ChainTemperature::Ok(t) => Some(*t),
+                        monitor::ChainTemperature::Unknown | monitor::ChainTemperature::Failed => {
+                            None
+                        }
+                    })
+                    .collect(),
+                status.fan_feedback.rpm,
+            ),
+            None => (vec![], vec![]),
+        };
+
+        telemetry::Hardware {
+            temperatures,
+            fan_speeds,
+            power: Some(self.estimate_power().await),
+        }
+    }
+}
@@ -23,6 +23,7 @@
 pub mod work_generation;
 
 use super::*;
+use crate::hashboard::{PlugPin, ResetPin};
 
 #[tokio::test]
 async fn test_hchain_ctl_instance() {
@@ -39,6 +40,7 @@ async fn test_hchain_ctl_instance() {
         voltage_ctrl_backend,
         hashboard_idx,
         MidstateCount::new(1),
+        io::FifoMode::default(),
         config::DEFAULT_ASIC_DIFFICULTY,
         monitor_sender,
     );
@@ -98,6 +100,15 @@ fn test_calc_baud_div_over_baud_rate_bm1387() {
     );
 }
 
+#[test]
+fn test_check_chip_baud_rate() {
+    check_chip_baud_rate(config::DEFAULT_CHIP_BAUD_RATE).expect("default baud rate rejected");
+    check_chip_baud_rate(INIT_CHIP_BAUD_RATE).expect("initial baud rate rejected");
+    assert!(check_chip_baud_rate(0).is_err());
+    assert!(check_chip_baud_rate(100_000).is_err());
+    assert!(check_chip_baud_rate(3_500_000).is_err());
+}
+
 /// Test work_time computation
 #[test]
 fn test_work_time_computation() {
@@ -106,4 +117,46 @@ fn test_work_time_computation() {
         secs_to_fpga_ticks(calculate_work_delay_for_pll(1, 650_000_000)),
         36296
     );
+
+    let mut frequency = FrequencySettings::from_frequency(650_000_000);
+    assert_eq!(
+        calculate_work_time(&frequency, EXPECTED_CHIPS_ON_CHAIN, MidstateCount::new(1)),
+        36296
+    );
+    assert_eq!(
+        calculate_work_time(&frequency, EXPECTED_CHIPS_ON_CHAIN, MidstateCount::new(4)),
+        145187
+    );
+    // the fastest chip determines work time
+    frequency
+        .set_chip_frequency(1, 700_000_000)
+        .expect("BUG: valid frequency rejected");
+    assert_eq!(
+        calculate_work_time(&frequency, EXPECTED_CHIPS_ON_CHAIN, MidstateCount::new(1)),
+        secs_to_fpga_ticks(calculate_work_delay_for_pll(1, 700_000_000))
+    );
+    // chips missing on the chain are ignored
+    assert_eq!(
+        calculate_work_time(&frequency, 1, MidstateCount::new(1)),
+        36296
+    );
+}
+
+#[test]
+fn test_set_chip_frequency() {
+    let mut frequency = FrequencySettings::from_frequency(650_000_000);
+    frequency.set_chip_count(3);
+    frequency
+        .set_chip_frequency(1, 600_000_000)
+        .expect("BUG: cannot set chip frequency");
+    assert_eq!(frequency.chip, vec![650_000_000, 600_000_000, 650_000_000]);
+    assert_eq!(frequency.min(), 600_000_000);
+    assert_eq!(frequency.max(), 650_000_000);
+
+    // chip does not exist
+    assert!(frequency.set_chip_frequency(3, 600_000_000).is_err());
+    // frequency is not supported by PLL
+    assert!(frequency.set_chip_frequency(0, 50_000_000).is_err());
+    assert!(frequency.set_chip_frequency(0, 1_200_000_001).is_err());
+    assert_eq!(frequency.chip, vec![650_000_000, 600_000_000, 650_000_000]);
 }
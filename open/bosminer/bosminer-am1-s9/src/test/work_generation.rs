@@ -25,6 +25,8 @@ use ii_logging::macros::*;
 use super::*;
 use crate::bm1387::MidstateCount;
 use crate::fan;
+use crate::hashboard::{PlugPin, ResetPin};
+use crate::io::{SolutionSource as _, WorkSink as _};
 use crate::{FrequencySettings, HashChain, Solution};
 
 use bosminer::work;
@@ -36,8 +38,8 @@ use std::sync::Arc;
 use futures::channel::mpsc;
 use futures::stream::StreamExt;
 
-use ii_async_compat::{tokio, FutureExt};
-use tokio::time::delay_for;
+use ii_async_compat::{runtime, tokio, FutureExt};
+use runtime::delay_for;
 
 const ASIC_DIFFICULTY: usize = 1;
 
@@ -65,8 +67,7 @@ async fn receiver_task(
     let target = ii_bitcoin::Target::from_pool_difficulty(ASIC_DIFFICULTY);
 
     loop {
-        let (rx_io_out, solution) = rx_io.recv_solution().await.expect("recv solution");
-        rx_io = rx_io_out;
+        let solution = rx_io.recv_solution().await.expect("recv solution");
         solution_sender
             .unbounded_send(Solution::from_hw_solution(&solution, target))
             .expect("solution send failed");
@@ -85,8 +86,7 @@ async fn sender_task(
         tx_io.wait_for_room().await.expect("wait for tx room");
         let work = work_receiver.next().await.expect("failed receiving work");
         let work_id = work_registry.store_work(work.clone(), false);
-        // send work is synchronous
-        tx_io.send_work(&work, work_id).expect("send work");
+        tx_io.send_work(&work, work_id).await.expect("send work");
     }
 }
 
@@ -145,6 +145,7 @@ async fn start_hchain(monitor_tx: mpsc::UnboundedSender<monitor::Message>) -> Ha
         voltage_ctrl_backend.clone(),
         hashboard_idx,
         MidstateCount::new(1),
+        io::FifoMode::default(),
         ASIC_DIFFICULTY,
         monitor_tx,
     )
@@ -187,10 +188,10 @@ async fn test_work_generation() {
     let hash_chain = Arc::new(start_hchain(monitor_sender).await);
 
     // start HW receiver
-    tokio::spawn(receiver_task(hash_chain.clone(), solution_sender));
+    runtime::spawn(receiver_task(hash_chain.clone(), solution_sender));
 
     // start HW sender
-    tokio::spawn(sender_task(hash_chain.clone(), work_receiver));
+    runtime::spawn(sender_task(hash_chain.clone(), work_receiver));
 
     // the first 3 work loads don't produce any solutions, these are merely to initialize the input
     // queue of each hashing chip
@@ -0,0 +1,311 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Thermal throttling of hash chains
+//!
+//! Chains hotter than the warning temperature are clocked down in steps until the temperature
+//! stops rising. Once the temperature drops below the warning temperature by the hysteresis, the
+//! frequency is raised back in steps up to the original one. A chain reaching the critical
+//! temperature is stopped (which powers the hash board down) and it is started again at the
+//! lowest frequency after a cool-down time because its temperature cannot be measured while it is
+//! stopped.
+
+use ii_logging::macros::*;
+
+use bosminer::alert;
+
+use crate::config;
+use crate::monitor::ChainTemperature;
+use crate::{ChainStatus, FrequencySettings, Manager};
+
+use ii_async_compat::runtime;
+use runtime::delay_for;
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Owner of hash chains while their temperature is being checked
+const OWNER_NAME: &str = "throttling";
+
+/// Period of checking the temperature of running chains
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Chain stopped due to critical temperature is started again after this time
+pub const COOL_DOWN_TIME: Duration = Duration::from_secs(60);
+
+/// Frequency (in Hz) by which chips are clocked down or up in one check interval
+const FREQUENCY_STEP: usize = 25_000_000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    /// Temperature above which the chain is clocked down
+    pub warn_temp: f32,
+    /// Temperature above which the chain is stopped
+    pub critical_temp: f32,
+    /// Degrees below `warn_temp` to which the temperature has to drop before the frequency is
+    /// raised
+    pub hysteresis: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    /// Keep the current frequency
+    Hold,
+    /// Clock the chain down
+    Lower,
+    /// Clock the chain up (towards its original frequency)
+    Raise,
+    /// Stop the chain
+    Disable,
+}
+
+impl Config {
+    pub fn decide(&self, temperature: f32) -> Action {
+        if temperature >= self.critical_temp {
+            Action::Disable
+        } else if temperature >= self.warn_temp {
+            Action::Lower
+        } else if temperature <= self.warn_temp - self.hysteresis {
+            Action::Raise
+        } else {
+            Action::Hold
+        }
+    }
+}
+
+/// Clocks all chips down by one step but not below `min_frequency`
+fn lower_frequency(current: &FrequencySettings, min_frequency: usize) -> FrequencySettings {
+    FrequencySettings {
+        chip: current
+            .chip
+            .iter()
+            .map(|&frequency| {
+                frequency
+                    .saturating_sub(FREQUENCY_STEP)
+                    .max(min_frequency.min(frequency))
+            })
+            .collect(),
+    }
+}
+
+/// Clocks all chips up by one step but not above their `original` frequency
+fn raise_frequency(current: &FrequencySettings, original: &FrequencySettings) -> FrequencySettings {
+    FrequencySettings {
+        chip: current
+            .chip
+            .iter()
+            .zip(original.chip.iter())
+            .map(|(&frequency, &original)| (frequency + FREQUENCY_STEP).min(original))
+            .collect(),
+    }
+}
+
+/// State of throttling of one chain
+#[derive(Default)]
+struct Throttling {
+    /// Frequency of the chain before it was clocked down for the first time
+    original_frequency: Option<FrequencySettings>,
+    /// Time when the chain was stopped due to critical temperature
+    disabled_since: Option<Instant>,
+}
+
+/// Checks temperature of the running chain and adjusts its frequency
+async fn check_chain(manager: &Arc<Manager>, config: &Config, throttling: &mut Throttling) {
+    let running_chain = match manager.clone().acquire(OWNER_NAME).await {
+        Ok(ChainStatus::Running(running_chain)) => running_chain,
+        // Chains stopped or being handled by someone else are not checked
+        Ok(ChainStatus::Stopped(_)) | Err(_) => return,
+    };
+    let temperature = match running_chain
+        .current_temperature()
+        .await
+        .map(ChainTemperature::from_s9_sensor)
+    {
+        Some(ChainTemperature::Ok(temperature)) => temperature,
+        _ => return,
+    };
+    let hashboard_idx = manager.hashboard_idx;
+    let current_frequency = running_chain.get_frequency().await;
+
+    let action = config.decide(temperature);
+    // Frequency of idle chain is restored when it is resumed so that only overheating is handled
+    if action != Action::Disable && running_chain.is_idle().await {
+        return;
+    }
+    let frequency = match action {
+        Action::Hold => return,
+        Action::Lower => {
+            throttling
+                .original_frequency
+                .get_or_insert_with(|| current_frequency.clone());
+            let min_frequency = (config::FREQUENCY_MHZ_MIN * 1_000_000.0) as usize;
+            let frequency = lower_frequency(&current_frequency, min_frequency);
+            if frequency.chip == current_frequency.chip {
+                return;
+            }
+            warn!(
+                "Throttling: chain {} at {} C, clocking down to {}",
+                hashboard_idx, temperature, frequency
+            );
+            frequency
+        }
+        Action::Raise => {
+            let original_frequency = match throttling.original_frequency.as_ref() {
+                Some(original_frequency) => original_frequency,
+                // the chain is not throttled
+                None => return,
+            };
+            let frequency = raise_frequency(&current_frequency, original_frequency);
+            if frequency.chip == original_frequency.chip {
+                info!(
+                    "Throttling: chain {} at {} C, restoring frequency {}",
+                    hashboard_idx, temperature, frequency
+                );
+                throttling.original_frequency = None;
+            } else {
+                info!(
+                    "Throttling: chain {} at {} C, clocking up to {}",
+                    hashboard_idx, temperature, frequency
+                );
+            }
+            frequency
+        }
+        Action::Disable => {
+            throttling
+                .original_frequency
+                .get_or_insert_with(|| current_frequency.clone());
+            let reason = format!(
+                "chain {} temperature above critical {} C",
+                hashboard_idx, config.critical_temp
+            );
+            error!(
+                "Throttling: stopping chain {}: {} C",
+                hashboard_idx, temperature
+            );
+            manager.alert_sender.notify(alert::Event::Overtemperature {
+                temperature: Some(temperature),
+                reason,
+            });
+            running_chain.stop().await;
+            throttling.disabled_since = Some(Instant::now());
+            return;
+        }
+    };
+    if let Err(e) = running_chain.set_frequency(&frequency).await {
+        error!(
+            "Throttling: failed to set frequency of chain {}: {}",
+            hashboard_idx, e
+        );
+    }
+}
+
+/// Starts again the chain stopped due to critical temperature. Returns `true` when the chain is
+/// running.
+async fn restart_chain(manager: &Arc<Manager>, disabled_since: Instant) -> bool {
+    if disabled_since.elapsed() < COOL_DOWN_TIME {
+        return false;
+    }
+    let stopped_chain = match manager.clone().acquire(OWNER_NAME).await {
+        Ok(ChainStatus::Stopped(stopped_chain)) => stopped_chain,
+        // Someone else has started the chain in the meantime
+        Ok(ChainStatus::Running(_)) => return true,
+        Err(_) => return false,
+    };
+    let frequency =
+        FrequencySettings::from_frequency((config::FREQUENCY_MHZ_MIN * 1_000_000.0) as usize);
+    info!(
+        "Throttling: starting chain {} at {} after cool-down",
+        manager.hashboard_idx, frequency
+    );
+    match stopped_chain
+        .start(
+            &frequency,
+            manager.chain_config.voltage,
+            manager.chain_config.asic_difficulty,
+        )
+        .await
+    {
+        Ok(_) => true,
+        Err((_, e)) => {
+            error!(
+                "Throttling: failed to start chain {}: {}",
+                manager.hashboard_idx, e
+            );
+            false
+        }
+    }
+}
+
+/// Watches temperature of the chain managed by `manager` and throttles it
+pub async fn throttling_task(manager: Arc<Manager>, config: Config) {
+    let mut throttling = Throttling::default();
+    loop {
+        delay_for(CHECK_INTERVAL).await;
+        match throttling.disabled_since {
+            Some(disabled_since) => {
+                if restart_chain(&manager, disabled_since).await {
+                    throttling.disabled_since = None;
+                }
+            }
+            None => check_chain(&manager, &config, &mut throttling).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_decide() {
+        let config = Config {
+            warn_temp: 90.0,
+            critical_temp: 105.0,
+            hysteresis: 5.0,
+        };
+        assert_eq!(config.decide(80.0), Action::Raise);
+        assert_eq!(config.decide(85.0), Action::Raise);
+        assert_eq!(config.decide(87.0), Action::Hold);
+        assert_eq!(config.decide(90.0), Action::Lower);
+        assert_eq!(config.decide(104.9), Action::Lower);
+        assert_eq!(config.decide(105.0), Action::Disable);
+    }
+
+    #[test]
+    fn test_frequency_steps() {
+        let original = FrequencySettings {
+            chip: vec![650_000_000, 210_000_000, 150_000_000],
+        };
+        let lowered = lower_frequency(&original, 200_000_000);
+        // chips never go below the minimum (unless they already are)
+        assert_eq!(lowered.chip, vec![625_000_000, 200_000_000, 150_000_000]);
+        let raised = raise_frequency(&lowered, &original);
+        assert_eq!(raised.chip, original.chip);
+        let raised = raise_frequency(
+            &FrequencySettings {
+                chip: vec![600_000_000, 200_000_000, 150_000_000],
+            },
+            &original,
+        );
+        assert_eq!(raised.chip, vec![625_000_000, 210_000_000, 150_000_000]);
+    }
+}
@@ -0,0 +1,617 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Automatic tuning of frequency and voltage of hash chains
+//!
+//! The tuner sweeps a grid of operating points of each hash chain. Every point is given some
+//! time to settle and then the effective hashrate and the rate of hardware errors are measured.
+//! Points with too many hardware errors are rejected and the remaining point with the best
+//! estimated energy efficiency (J/TH) is set on the chain when the sweep is finished.
+//!
+//! Tuned operating points are stored in a state file with hash boards identified by their serial
+//! number. A board with a stored point within the configured bounds is not tuned again after
+//! restart of the miner. Boards without a known serial number are tuned on every start.
+
+use ii_logging::macros::*;
+
+use crate::efficiency;
+use crate::power;
+use crate::{ChainStatus, FrequencySettings, Manager, RunningChain};
+
+use bosminer::file;
+
+use ii_async_compat::runtime;
+use runtime::delay_for;
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::Duration;
+
+/// Owner of hash chains while they are being tuned
+const OWNER_NAME: &str = "tuner";
+
+/// Period of checking whether the chain has been started and can be tuned
+const ACQUIRE_RETRY_PERIOD: Duration = Duration::from_secs(10);
+
+/// Bounds of tuned frequency (in Hz) and voltage (in V)
+pub const DEFAULT_FREQUENCY_MIN: usize = 550_000_000;
+pub const DEFAULT_FREQUENCY_MAX: usize = 750_000_000;
+pub const DEFAULT_FREQUENCY_STEP: usize = 25_000_000;
+/// Minimal steps of the grid which keep the number of operating points reasonable
+pub const MIN_FREQUENCY_STEP: usize = 1_000_000;
+pub const MIN_VOLTAGE_STEP: f32 = 0.01;
+pub const DEFAULT_VOLTAGE_MIN: f32 = 8.6;
+pub const DEFAULT_VOLTAGE_MAX: f32 = 9.2;
+pub const DEFAULT_VOLTAGE_STEP: f32 = 0.1;
+
+/// Time for chips to settle after a change of operating point
+pub const DEFAULT_SETTLE_TIME: Duration = Duration::from_secs(30);
+/// Time of measurement of each operating point
+pub const DEFAULT_MEASURE_TIME: Duration = Duration::from_secs(120);
+
+/// Maximal ratio of hardware errors to all nonces returned by chips
+pub const DEFAULT_MAX_ERROR_RATE: f64 = 0.01;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    pub frequency_min: usize,
+    pub frequency_max: usize,
+    pub frequency_step: usize,
+    pub voltage_min: f32,
+    pub voltage_max: f32,
+    pub voltage_step: f32,
+    pub settle_time: Duration,
+    pub measure_time: Duration,
+    pub max_error_rate: f64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            frequency_min: DEFAULT_FREQUENCY_MIN,
+            frequency_max: DEFAULT_FREQUENCY_MAX,
+            frequency_step: DEFAULT_FREQUENCY_STEP,
+            voltage_min: DEFAULT_VOLTAGE_MIN,
+            voltage_max: DEFAULT_VOLTAGE_MAX,
+            voltage_step: DEFAULT_VOLTAGE_STEP,
+            settle_time: DEFAULT_SETTLE_TIME,
+            measure_time: DEFAULT_MEASURE_TIME,
+            max_error_rate: DEFAULT_MAX_ERROR_RATE,
+        }
+    }
+}
+
+impl Config {
+    /// Grid of operating points ordered from the highest voltage and, for each voltage, from the
+    /// lowest frequency
+    fn points(&self) -> Vec<OperatingPoint> {
+        let mut points = vec![];
+        let mut voltage_step = 0;
+        loop {
+            let voltage = self.voltage_max - voltage_step as f32 * self.voltage_step;
+            // tolerate rounding errors of the last step
+            if voltage < self.voltage_min - self.voltage_step / 2.0 {
+                break;
+            }
+            let voltage = voltage.max(self.voltage_min);
+            let mut frequency = self.frequency_min;
+            while frequency <= self.frequency_max {
+                points.push(OperatingPoint { frequency, voltage });
+                if self.frequency_step == 0 {
+                    break;
+                }
+                frequency += self.frequency_step;
+            }
+            if self.voltage_step <= 0.0 {
+                break;
+            }
+            voltage_step += 1;
+        }
+        points
+    }
+
+    /// Checks whether `point` lies within the tuned bounds
+    fn contains(&self, point: &OperatingPoint) -> bool {
+        // tolerate rounding errors of stored voltage
+        const VOLTAGE_EPSILON: f32 = 0.001;
+        point.frequency >= self.frequency_min
+            && point.frequency <= self.frequency_max
+            && point.voltage >= self.voltage_min - VOLTAGE_EPSILON
+            && point.voltage <= self.voltage_max + VOLTAGE_EPSILON
+    }
+}
+
+/// Frequency of all chips (in Hz) and voltage (in V) of one hash chain
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct OperatingPoint {
+    pub frequency: usize,
+    pub voltage: f32,
+}
+
+impl OperatingPoint {
+    /// Estimated power (in W) of chain with `chip_count` chips
+    fn estimate_power(&self, chip_count: usize) -> f64 {
+        efficiency::estimate_power(
+            self.voltage as f64,
+            self.frequency as u64 * chip_count as u64,
+        )
+    }
+}
+
+/// Result of measurement of one operating point
+#[derive(Debug, Clone, PartialEq)]
+pub struct Measurement {
+    /// Effective hashrate in H/s
+    pub hashrate: f64,
+    /// Ratio of hardware errors to all nonces returned by chips
+    pub error_rate: f64,
+    /// Estimated power in W
+    pub power: f64,
+}
+
+impl Measurement {
+    /// Energy efficiency in J/TH. Returns `None` when no work has been done.
+    pub fn efficiency(&self) -> Option<f64> {
+        if self.hashrate > 0.0 {
+            Some(self.power / (self.hashrate * 1e-12))
+        } else {
+            None
+        }
+    }
+}
+
+/// Decision logic of the tuning of one hash chain
+#[derive(Debug)]
+struct Sweep {
+    /// Operating points remaining to be measured in reversed order
+    pending: Vec<OperatingPoint>,
+    max_error_rate: f64,
+    best: Option<(OperatingPoint, f64)>,
+}
+
+impl Sweep {
+    fn new(config: &Config) -> Self {
+        let mut pending = config.points();
+        pending.reverse();
+        Self {
+            pending,
+            max_error_rate: config.max_error_rate,
+            best: None,
+        }
+    }
+
+    fn next_point(&self) -> Option<OperatingPoint> {
+        self.pending.last().cloned()
+    }
+
+    /// Accounts the measurement of the point returned by `next_point`
+    fn record(&mut self, measurement: &Measurement) {
+        let point = self
+            .pending
+            .pop()
+            .expect("BUG: no operating point is being measured");
+        if measurement.error_rate > self.max_error_rate {
+            // Higher frequencies at the same voltage are not going to be any better
+            self.pending
+                .retain(|pending| pending.voltage != point.voltage);
+            return;
+        }
+        if let Some(efficiency) = measurement.efficiency() {
+            let is_better = match self.best {
+                Some((_, best)) => efficiency < best,
+                None => true,
+            };
+            if is_better {
+                self.best.replace((point, efficiency));
+            }
+        }
+    }
+
+    /// The most efficient operating point measured so far
+    fn best(&self) -> Option<OperatingPoint> {
+        self.best.map(|(point, _)| point)
+    }
+}
+
+/// Operating points found by previous runs of the tuner
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct State {
+    /// Tuned points of hash boards identified by their serial number
+    pub boards: BTreeMap<String, OperatingPoint>,
+}
+
+/// File storage for tuning results shared by tuners of all hash chains. Its methods do blocking
+/// I/O so tuners call them from blocking tasks.
+#[derive(Debug)]
+pub struct Storage {
+    path: PathBuf,
+    /// Serializes updates of the file from individual tuners
+    lock: StdMutex<()>,
+}
+
+impl Storage {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            lock: StdMutex::new(()),
+        }
+    }
+
+    /// Loads tuning results. Missing file is the same as no results.
+    pub fn load(&self) -> io::Result<State> {
+        match fs::read(&self.path) {
+            Ok(content) => Ok(serde_json::from_slice(&content)?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Default::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Atomically replaces stored results
+    pub fn store(&self, state: &State) -> io::Result<()> {
+        file::write_atomically(&self.path, &serde_json::to_vec(state)?)
+    }
+
+    /// Returns operating point stored for hash board with `serial` number
+    pub fn get(&self, serial: &str) -> Option<OperatingPoint> {
+        let _lock = self.lock.lock().expect("BUG: failed to lock mutex");
+        match self.load() {
+            Ok(state) => state.boards.get(serial).cloned(),
+            Err(e) => {
+                warn!(
+                    "Tuner: cannot load tuning results from '{}': {}",
+                    self.path.display(),
+                    e
+                );
+                None
+            }
+        }
+    }
+
+    /// Stores operating point of hash board with `serial` number and keeps points of other boards
+    pub fn update(&self, serial: &str, point: OperatingPoint) -> io::Result<()> {
+        let _lock = self.lock.lock().expect("BUG: failed to lock mutex");
+        // corrupted results are just overwritten
+        let mut state = self.load().unwrap_or_default();
+        state.boards.insert(serial.to_string(), point);
+        self.store(&state)
+    }
+}
+
+async fn set_point(chain: &RunningChain, point: OperatingPoint) -> crate::error::Result<()> {
+    chain
+        .set_voltage(power::Voltage::from_volts(point.voltage)?)
+        .await?;
+    chain
+        .set_frequency(&FrequencySettings::from_frequency(point.frequency))
+        .await
+}
+
+async fn measure(chain: &RunningChain, point: OperatingPoint, config: &Config) -> Measurement {
+    delay_for(config.settle_time).await;
+    // Counters are not reset because per-chip statistics are reported from them as well
+    let start = chain.snapshot_counter().await;
+    delay_for(config.measure_time).await;
+    let end = chain.snapshot_counter().await;
+
+    let valid = end.valid.saturating_sub(start.valid);
+    let errors = end.errors.saturating_sub(start.errors);
+    let nonces = valid / end.asic_difficulty.max(1);
+    let error_rate = if end.started == start.started && nonces + errors > 0 {
+        errors as f64 / (nonces + errors) as f64
+    } else {
+        // chain which does not return any nonce or which has been restarted is broken
+        1.0
+    };
+    let duration = end
+        .duration()
+        .checked_sub(start.duration())
+        .unwrap_or(config.measure_time);
+    Measurement {
+        hashrate: valid as f64 * (1u64 << 32) as f64 / duration.as_secs_f64(),
+        error_rate,
+        power: point.estimate_power(end.chip_count()),
+    }
+}
+
+/// Sweeps operating points of a running chain and sets the most efficient one. The original
+/// settings are kept when no point is good enough. Returns the point which has been set.
+async fn tune_chain(
+    chain: &RunningChain,
+    hashboard_idx: usize,
+    config: &Config,
+) -> Option<OperatingPoint> {
+    let original_frequency = chain.get_frequency().await;
+    let original_voltage = chain.get_voltage().await;
+
+    // Chips behave differently when cold so wait for the working temperature first
+    chain.wait_for_preheat().await;
+    let mut sweep = Sweep::new(config);
+    while let Some(point) = sweep.next_point() {
+        if let Err(e) = set_point(chain, point).await {
+            error!(
+                "Tuner: failed to set {:?} on chain {}: {}",
+                point, hashboard_idx, e
+            );
+            break;
+        }
+        let measurement = measure(chain, point, config).await;
+        info!(
+            "Tuner: chain {} at {:?}: {:?} ({:?} J/TH)",
+            hashboard_idx,
+            point,
+            measurement,
+            measurement.efficiency()
+        );
+        sweep.record(&measurement);
+    }
+
+    let best = sweep.best();
+    let result = match best {
+        Some(point) => {
+            info!("Tuner: chain {} tuned to {:?}", hashboard_idx, point);
+            set_point(chain, point).await
+        }
+        None => {
+            warn!(
+                "Tuner: no operating point of chain {} is good enough, restoring original settings",
+                hashboard_idx
+            );
+            match chain.set_voltage(original_voltage).await {
+                Ok(_) => chain.set_frequency(&original_frequency).await,
+                Err(e) => Err(e),
+            }
+        }
+    };
+    match result {
+        Ok(_) => best,
+        Err(e) => {
+            error!(
+                "Tuner: failed to set chain {} operating point: {}",
+                hashboard_idx, e
+            );
+            None
+        }
+    }
+}
+
+/// Sets operating point stored by previous run of the tuner. Returns `false` when the chain has
+/// to be tuned.
+async fn restore_chain(
+    chain: &RunningChain,
+    hashboard_idx: usize,
+    config: &Config,
+    point: OperatingPoint,
+) -> bool {
+    if !config.contains(&point) {
+        info!(
+            "Tuner: stored {:?} of chain {} is out of tuned bounds",
+            point, hashboard_idx
+        );
+        return false;
+    }
+    match set_point(chain, point).await {
+        Ok(_) => {
+            info!("Tuner: chain {} restored to {:?}", hashboard_idx, point);
+            true
+        }
+        Err(e) => {
+            error!(
+                "Tuner: failed to restore {:?} on chain {}: {}",
+                point, hashboard_idx, e
+            );
+            false
+        }
+    }
+}
+
+/// Waits for the chain to be started and tunes it unless it has been tuned before
+pub async fn tuning_task(manager: Arc<Manager>, config: Config, storage: Arc<Storage>) {
+    let hashboard_idx = manager.hashboard_idx;
+    loop {
+        match manager.clone().acquire(OWNER_NAME).await {
+            Ok(ChainStatus::Running(running_chain)) => {
+                let serial = running_chain.get_board_info().await.serial;
+                if let Some(serial) = serial.clone() {
+                    let storage = storage.clone();
+                    let point = runtime::spawn_blocking(move || storage.get(&serial))
+                        .await
+                        .expect("BUG: loading of tuning results failed");
+                    if let Some(point) = point {
+                        if restore_chain(&running_chain, hashboard_idx, &config, point).await {
+                            return;
+                        }
+                    }
+                }
+                info!("Tuner: tuning chain {}", hashboard_idx);
+                let point = tune_chain(&running_chain, hashboard_idx, &config).await;
+                match (serial, point) {
+                    (Some(serial), Some(point)) => {
+                        let result =
+                            runtime::spawn_blocking(move || storage.update(&serial, point))
+                                .await
+                                .expect("BUG: storing of tuning results failed");
+                        if let Err(e) = result {
+                            warn!(
+                                "Tuner: cannot store tuning result of chain {}: {}",
+                                hashboard_idx, e
+                            );
+                        }
+                    }
+                    (None, Some(_)) => warn!(
+                        "Tuner: chain {} has unknown serial number, tuning result is not stored",
+                        hashboard_idx
+                    ),
+                    _ => {}
+                }
+                return;
+            }
+            // chain is being started by someone else or it is stopped
+            Ok(ChainStatus::Stopped(_)) | Err(_) => delay_for(ACQUIRE_RETRY_PERIOD).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn test_config() -> Config {
+        Config {
+            frequency_min: 600_000_000,
+            frequency_max: 700_000_000,
+            frequency_step: 50_000_000,
+            voltage_min: 8.8,
+            voltage_max: 9.0,
+            voltage_step: 0.1,
+            ..Default::default()
+        }
+    }
+
+    fn measurement(point: OperatingPoint, error_rate: f64) -> Measurement {
+        Measurement {
+            // hashrate of ideal S9 chain
+            hashrate: point.frequency as f64 * 63.0 * 114.0,
+            error_rate,
+            power: point.estimate_power(63),
+        }
+    }
+
+    #[test]
+    fn test_points() {
+        let points = test_config().points();
+        assert_eq!(points.len(), 9);
+        assert_eq!(
+            points[0],
+            OperatingPoint {
+                frequency: 600_000_000,
+                voltage: 9.0
+            }
+        );
+        assert_eq!(points[2].frequency, 700_000_000);
+        assert_eq!(points[8].voltage, 8.8);
+        assert!(!Config::default().points().is_empty());
+
+        // zero steps do not loop forever
+        let config = Config {
+            frequency_step: 0,
+            voltage_step: 0.0,
+            ..test_config()
+        };
+        assert_eq!(config.points().len(), 1);
+    }
+
+    #[test]
+    fn test_sweep() {
+        let mut sweep = Sweep::new(&test_config());
+        assert_eq!(sweep.best(), None);
+
+        let mut measured = vec![];
+        while let Some(point) = sweep.next_point() {
+            measured.push(point);
+            // chips are not stable at high frequency with low voltage
+            let error_rate = if point.voltage < 8.85 && point.frequency > 600_000_000 {
+                0.1
+            } else {
+                0.0
+            };
+            sweep.record(&measurement(point, error_rate));
+        }
+        // frequencies above the failed point are skipped
+        assert_eq!(measured.len(), 8);
+        // the lowest voltage is the most efficient one according to the power model
+        assert_eq!(
+            sweep.best(),
+            Some(OperatingPoint {
+                frequency: 600_000_000,
+                voltage: 8.8
+            })
+        );
+    }
+
+    #[test]
+    fn test_efficiency() {
+        let point = OperatingPoint {
+            frequency: 650_000_000,
+            voltage: 8.8,
+        };
+        let efficiency = measurement(point, 0.0)
+            .efficiency()
+            .expect("BUG: no efficiency");
+        assert!(efficiency > 80.0 && efficiency < 100.0, "{}", efficiency);
+        let broken = Measurement {
+            hashrate: 0.0,
+            error_rate: 1.0,
+            power: 400.0,
+        };
+        assert_eq!(broken.efficiency(), None);
+    }
+
+    #[test]
+    fn test_contains() {
+        let config = test_config();
+        let point = |frequency, voltage| OperatingPoint { frequency, voltage };
+        assert!(config.contains(&point(650_000_000, 8.9)));
+        assert!(config.contains(&point(700_000_000, 9.0)));
+        assert!(!config.contains(&point(750_000_000, 8.9)));
+        assert!(!config.contains(&point(650_000_000, 8.7)));
+    }
+
+    #[test]
+    fn test_storage() {
+        let path = std::env::temp_dir().join(format!("bosminer_tuner_{}.json", std::process::id()));
+        let storage = Storage::new(&path);
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(storage.get("a"), None);
+        let point = OperatingPoint {
+            frequency: 650_000_000,
+            voltage: 8.8,
+        };
+        storage.update("a", point).expect("BUG: cannot store state");
+        storage
+            .update(
+                "b",
+                OperatingPoint {
+                    voltage: 9.0,
+                    ..point
+                },
+            )
+            .expect("BUG: cannot store state");
+        assert_eq!(storage.get("a"), Some(point));
+        assert_eq!(
+            storage.load().expect("BUG: cannot load state").boards.len(),
+            2
+        );
+
+        // corrupted file must be reported as an error
+        fs::write(&path, b"{").expect("BUG: cannot write state");
+        assert!(storage.load().is_err());
+        assert_eq!(storage.get("a"), None);
+        fs::remove_file(&path).expect("BUG: cannot remove state");
+    }
+}
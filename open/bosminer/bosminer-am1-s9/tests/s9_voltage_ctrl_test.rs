@@ -24,8 +24,9 @@ use ii_async_compat::tokio;
 use tokio::time::delay_for;
 
 use bosminer_am1_s9::gpio;
+use bosminer_am1_s9::hashboard::ResetPin;
 use bosminer_am1_s9::power;
-use bosminer_am1_s9::{Backend, ResetPin};
+use bosminer_am1_s9::Backend;
 
 use std::sync::Arc;
 
@@ -24,20 +24,23 @@ use crate::error;
 
 use ii_stratum::v2;
 
-use url::Url;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
 use std::convert::TryFrom;
 use std::fmt;
+use std::str::FromStr;
 
-use failure::ResultExt;
+use crate::error::ResultExt;
 
 pub const URL_JAVA_SCRIPT_REGEX: &'static str =
-    "(?:drain|(?:stratum2?\\+tcp(?:\\+insecure)?)):\\/\\/[\\w\\.-]+(?::\\d+)?(?:\\/[\\dA-HJ-NP-Za-km-z]+)?";
+    "(?:drain|stratum\\+ssl|(?:stratum2?\\+tcp(?:\\+insecure)?)):\\/\\/[\\w\\.-]+(?::\\d+)?(?:\\/[\\dA-HJ-NP-Za-km-z]+)?";
 
 #[derive(Clone, Debug)]
 pub enum Protocol {
     Drain,
     StratumV1,
+    /// Stratum V1 secured by TLS
+    StratumV1Tls,
     StratumV2(v2::noise::auth::EncodedEd25519PublicKey),
     StratumV2Insecure,
 }
@@ -45,11 +48,13 @@ pub enum Protocol {
 impl Protocol {
     pub const SCHEME_DRAIN: &'static str = "drain";
     pub const SCHEME_STRATUM_V1: &'static str = "stratum+tcp";
+    pub const SCHEME_STRATUM_V1_TLS: &'static str = "stratum+ssl";
     pub const SCHEME_STRATUM_V2: &'static str = "stratum2+tcp";
     pub const SCHEME_STRATUM_V2_INSECURE: &'static str = "stratum2+tcp+insecure";
 
     pub const DEFAULT_PORT_DRAIN: u16 = 0;
     pub const DEFAULT_PORT_STRATUM_V1: u16 = 3333;
+    pub const DEFAULT_PORT_STRATUM_V1_TLS: u16 = 443;
     pub const DEFAULT_PORT_STRATUM_V2: u16 = 3336;
     pub const DEFAULT_PORT_STRATUM_V2_INSECURE: u16 = 3336;
 
@@ -57,6 +62,7 @@ impl Protocol {
         match self {
             Self::Drain => Self::DEFAULT_PORT_DRAIN,
             Self::StratumV1 => Self::DEFAULT_PORT_STRATUM_V1,
+            Self::StratumV1Tls => Self::DEFAULT_PORT_STRATUM_V1_TLS,
             Self::StratumV2(_) => Self::DEFAULT_PORT_STRATUM_V2,
             Self::StratumV2Insecure => Self::DEFAULT_PORT_STRATUM_V2_INSECURE,
         }
@@ -66,18 +72,23 @@ impl Protocol {
     fn get_upstream_auth_public_key_from_string(
         public_key: &str,
     ) -> error::Result<v2::noise::auth::EncodedEd25519PublicKey> {
-        v2::noise::auth::EncodedEd25519PublicKey::try_from(public_key.to_string())
-            .context(format!(
-                "invalid upstream authority public key: {}",
-                public_key
-            ))
-            .map_err(Into::into)
+        v2::noise::auth::EncodedEd25519PublicKey::try_from(public_key.to_string()).context(format!(
+            "invalid upstream authority public key: {}",
+            public_key
+        ))
     }
 
     pub fn parse(scheme: &str, path: &str) -> error::Result<Self> {
         Ok(match scheme {
             Self::SCHEME_DRAIN => Self::Drain,
             Self::SCHEME_STRATUM_V1 => Self::StratumV1,
+            #[cfg(feature = "stratum-tls")]
+            Self::SCHEME_STRATUM_V1_TLS => Self::StratumV1Tls,
+            #[cfg(not(feature = "stratum-tls"))]
+            Self::SCHEME_STRATUM_V1_TLS => Err(error::ErrorKind::Client(format!(
+                "protocol '{}' is not supported by this build (missing 'stratum-tls' feature)",
+                scheme
+            )))?,
             Self::SCHEME_STRATUM_V2 => {
                 let upstream_authority_public_key = match path.get(1..) {
                     Some(s) => Self::get_upstream_auth_public_key_from_string(s)?,
@@ -100,6 +111,7 @@ impl Protocol {
         match self {
             Self::Drain => Self::SCHEME_DRAIN,
             Self::StratumV1 => Self::SCHEME_STRATUM_V1,
+            Self::StratumV1Tls => Self::SCHEME_STRATUM_V1_TLS,
             Self::StratumV2(_) => Self::SCHEME_STRATUM_V2,
             Self::StratumV2Insecure => Self::SCHEME_STRATUM_V2_INSECURE,
         }
@@ -111,6 +123,7 @@ impl fmt::Display for Protocol {
         match self {
             Protocol::Drain => write!(f, "Drain"),
             Protocol::StratumV1 => write!(f, "Stratum V1"),
+            Protocol::StratumV1Tls => write!(f, "Stratum V1 TLS"),
             Protocol::StratumV2(public_key) => {
                 write!(f, "Stratum V2 (authority key: {})", public_key)
             }
@@ -119,6 +132,68 @@ impl fmt::Display for Protocol {
     }
 }
 
+/// Pool URL in the form `scheme://host[:port][/authority_public_key][#fragment]`
+///
+/// The URL is (de)serialized as a string so that it can be used directly in configuration files.
+#[derive(Clone, Debug)]
+pub struct Url {
+    pub protocol: Protocol,
+    pub host: String,
+    pub port: Option<u16>,
+    pub fragment: Option<String>,
+}
+
+impl FromStr for Url {
+    type Err = error::Error;
+
+    fn from_str(url: &str) -> error::Result<Self> {
+        let url =
+            url::Url::parse(url).context(error::ErrorKind::Client("invalid URL".to_string()))?;
+
+        let protocol = Protocol::parse(url.scheme(), url.path())?;
+        let host = url
+            .host()
+            .ok_or(error::ErrorKind::Client("missing hostname".to_string()))?
+            .to_string();
+
+        Ok(Self {
+            protocol,
+            host,
+            port: url.port(),
+            fragment: url.fragment().map(|s| s.to_string()),
+        })
+    }
+}
+
+impl fmt::Display for Url {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}://{}", self.protocol.scheme(), self.host)?;
+        if let Some(port) = self.port {
+            write!(f, ":{}", port)?;
+        }
+        if let Protocol::StratumV2(public_key) = &self.protocol {
+            write!(f, "/{}", public_key)?;
+        }
+        if let Some(fragment) = &self.fragment {
+            write!(f, "#{}", fragment)?;
+        }
+        Ok(())
+    }
+}
+
+impl Serialize for Url {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Url {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let url = String::deserialize(deserializer)?;
+        Url::from_str(&url).map_err(de::Error::custom)
+    }
+}
+
 pub struct UserInfo<'a> {
     pub user: &'a str,
     pub password: Option<&'a str>,
@@ -189,26 +264,28 @@ impl Descriptor {
 
     /// Create client `Descriptor` from information provided by user.
     pub fn create(url: &str, user_info: &UserInfo, enabled: bool) -> error::Result<Self> {
-        let url = Url::parse(url).context(error::ErrorKind::Client("invalid URL".to_string()))?;
-
-        let protocol = Protocol::parse(url.scheme(), url.path())?;
-        let host = url
-            .host()
-            .ok_or(error::ErrorKind::Client("missing hostname".to_string()))?
-            .to_string();
-        let port = url.port();
-
-        // Parse fragment part
-        let fragment = url.fragment().map(|s| s.to_string());
+        Ok(Self::from_url(Url::from_str(url)?, user_info, enabled))
+    }
 
-        Ok(Descriptor {
-            protocol,
+    /// Create client `Descriptor` from already parsed URL
+    pub fn from_url(url: Url, user_info: &UserInfo, enabled: bool) -> Self {
+        Descriptor {
+            protocol: url.protocol,
             enabled,
             user: user_info.user.to_string(),
             password: user_info.password.map(|value| value.to_string()),
-            host,
-            port,
-            fragment,
-        })
+            host: url.host,
+            port: url.port,
+            fragment: url.fragment,
+        }
+    }
+
+    pub fn url(&self) -> Url {
+        Url {
+            protocol: self.protocol.clone(),
+            host: self.host.clone(),
+            port: self.port,
+            fragment: self.fragment.clone(),
+        }
     }
 }
@@ -20,87 +20,32 @@
 // of such proprietary license or if you have any other questions, please
 // contact us at opensource@braiins.com.
 
-//! The Block erupter errors
+//! The bosminer configuration errors
 
-use failure::{Backtrace, Context, Fail};
-use std::fmt::{self, Debug, Display};
+use thiserror::Error;
 
-pub struct Error {
-    inner: Context<ErrorKind>,
-}
-
-#[derive(Clone, Eq, PartialEq, Debug, Fail)]
+#[derive(Error, Clone, Eq, PartialEq, Debug)]
 pub enum ErrorKind {
     /// General error used for more specific input/output error.
-    #[fail(display = "{}", _0)]
+    #[error("{0}")]
     General(String),
 
     /// Error related to client settings.
-    #[fail(display = "{}", _0)]
+    #[error("{0}")]
     Client(String),
 }
 
-/// Implement Fail trait instead of use Derive to get more control over custom type.
-/// The main advantage is customization of Context type which allows conversion of
-/// any error types to this custom error with general error kind by calling context
-/// method on any result type.
-impl Fail for Error {
-    fn cause(&self) -> Option<&dyn Fail> {
-        self.inner.cause()
-    }
-
-    fn backtrace(&self) -> Option<&Backtrace> {
-        self.inner.backtrace()
-    }
-}
-
-impl Display for Error {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        Display::fmt(&self.inner, f)
-    }
-}
-
-impl Debug for Error {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        Debug::fmt(&self.inner, f)
-    }
-}
-
-impl Error {
-    pub fn kind(&self) -> ErrorKind {
-        self.inner.get_context().clone()
-    }
-}
+ii_error::error_type!(ErrorKind);
 
-impl From<ErrorKind> for Error {
-    fn from(kind: ErrorKind) -> Self {
-        Self {
-            inner: Context::new(kind),
-        }
+/// Context message provided by `ResultExt::context()`
+impl From<&str> for ErrorKind {
+    fn from(info: &str) -> Self {
+        ErrorKind::General(info.to_string())
     }
 }
 
-impl From<Context<ErrorKind>> for Error {
-    fn from(inner: Context<ErrorKind>) -> Self {
-        Self { inner }
+impl From<String> for ErrorKind {
+    fn from(info: String) -> Self {
+        ErrorKind::General(info)
     }
 }
-
-impl From<Context<&str>> for Error {
-    fn from(context: Context<&str>) -> Self {
-        Self {
-            inner: context.map(|info| ErrorKind::General(info.to_string())),
-        }
-    }
-}
-
-impl From<Context<String>> for Error {
-    fn from(context: Context<String>) -> Self {
-        Self {
-            inner: context.map(|info| ErrorKind::General(info)),
-        }
-    }
-}
-
-/// A specialized `Result` type bound to [`Error`].
-pub type Result<T> = std::result::Result<T, Error>;
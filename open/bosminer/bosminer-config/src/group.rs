@@ -22,6 +22,8 @@
 
 use serde::{Deserialize, Serialize};
 
+use std::time;
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(deny_unknown_fields)]
 pub enum LoadBalanceStrategy {
@@ -59,12 +61,17 @@ pub struct Descriptor {
     #[serde(flatten)]
     #[serde(skip_serializing_if = "Option::is_none")]
     strategy: Option<LoadBalanceStrategy>,
+    /// Number of seconds a recovered pool with higher priority has to be running before the work
+    /// is switched back to it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fail_back_delay: Option<u64>,
 }
 
 impl Descriptor {
     pub const DEFAULT_NAME: &'static str = "Default";
     pub const DEFAULT_INDEX: usize = 0;
     pub const DEFAULT_QUOTA: usize = 1;
+    pub const DEFAULT_FAIL_BACK_DELAY: u64 = 60;
 
     pub fn new<T>(name: String, private: bool, strategy: T) -> Self
     where
@@ -74,9 +81,15 @@ impl Descriptor {
             name,
             private,
             strategy: strategy.into(),
+            fail_back_delay: None,
         }
     }
 
+    pub fn with_fail_back_delay(mut self, seconds: u64) -> Self {
+        self.fail_back_delay = Some(seconds);
+        self
+    }
+
     pub fn strategy(&self) -> LoadBalanceStrategy {
         self.strategy
             .clone()
@@ -95,6 +108,13 @@ impl Descriptor {
             .as_ref()
             .and_then(|strategy| strategy.get_fixed_share_ratio())
     }
+
+    pub fn fail_back_delay(&self) -> time::Duration {
+        time::Duration::from_secs(
+            self.fail_back_delay
+                .unwrap_or(Self::DEFAULT_FAIL_BACK_DELAY),
+        )
+    }
 }
 
 impl Default for Descriptor {
@@ -103,6 +123,7 @@ impl Default for Descriptor {
             name: Self::DEFAULT_NAME.to_string(),
             private: false,
             strategy: None,
+            fail_back_delay: None,
         }
     }
 }
@@ -27,6 +27,7 @@ mod group;
 // Reexport inner structures
 pub use client::Descriptor as ClientDescriptor;
 pub use client::Protocol as ClientProtocol;
+pub use client::Url as ClientUrl;
 pub use client::UserInfo as ClientUserInfo;
 pub use client::URL_JAVA_SCRIPT_REGEX as CLIENT_URL_JAVA_SCRIPT_REGEX;
 
@@ -35,8 +36,11 @@ pub use group::LoadBalanceStrategy;
 
 // reexport common crates
 pub use clap;
-pub use config;
+pub use ii_config;
 
+use ii_config::Secret;
+
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -47,7 +51,7 @@ pub struct PoolConfig {
     pub url: String,
     pub user: String,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub password: Option<String>,
+    pub password: Option<Secret<String>>,
 }
 
 // NOTE: `#[serde(deny_unknown_fields)]` cannot be used due to flatten descriptor but the error is
@@ -61,16 +65,18 @@ pub struct GroupConfig {
     pub pools: Option<Vec<PoolConfig>>,
 }
 
-/// Parse a configuration file from `config_path`.
-pub fn parse<'a, T>(config_path: &str) -> Result<T, String>
+/// Load configuration from the layers (file, environment, command line) set up in `loader`
+pub fn load<T>(loader: &ii_config::Loader) -> Result<T, String>
 where
-    T: Deserialize<'a>,
+    T: DeserializeOwned + ii_config::Validate,
 {
-    let mut settings = config::Config::default();
-    settings
-        .merge(config::File::with_name(config_path))
-        .map_err(|e| format!("{}", e))?;
+    loader.load().map_err(|e| e.to_string())
+}
 
-    // Parse it into structure
-    settings.try_into::<T>().map_err(|e| format!("{}", e))
+/// Parse a configuration file from `config_path`.
+pub fn parse<T>(config_path: &str) -> Result<T, String>
+where
+    T: DeserializeOwned + ii_config::Validate,
+{
+    load(&ii_config::Loader::new().file(config_path))
 }
@@ -0,0 +1,255 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Mining backend which computes hashes on the host CPU
+//!
+//! The backend does not need any mining hardware so it can be used for development and testing
+//! of the generic bOSminer code base on a regular host machine.
+
+pub mod config;
+
+use ii_logging::macros::*;
+
+use bosminer::async_trait;
+use bosminer::hal;
+use bosminer::node;
+use bosminer::stats;
+use bosminer::work;
+use bosminer_macros::WorkSolverNode;
+
+use ii_bitcoin::{HashTrait as _, MeetsTarget as _};
+
+use futures::lock::Mutex;
+use ii_async_compat::prelude::*;
+
+use std::fmt;
+use std::mem;
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Offset of the nonce in the binary representation of the block header
+const NONCE_OFFSET: usize = ii_bitcoin::BLOCK_HEADER_SIZE - mem::size_of::<u32>();
+
+/// Number of nonces which are searched at once before checking that the work is still valid
+const NONCE_BATCH_SIZE: u32 = 0x10000;
+
+/// Maximal time spent by solving one work before fresh work is requested to pick up new jobs
+const MAX_WORK_TIME: Duration = Duration::from_secs(5);
+
+/// Represents solution found by the CPU
+#[derive(Debug)]
+pub struct Solution {
+    /// Actual nonce
+    nonce: u32,
+    /// Index of a midstate in the solved work
+    midstate_idx: usize,
+    /// Target which the solution has been searched for
+    target: ii_bitcoin::Target,
+}
+
+impl hal::BackendSolution for Solution {
+    #[inline]
+    fn nonce(&self) -> u32 {
+        self.nonce
+    }
+
+    #[inline]
+    fn midstate_idx(&self) -> usize {
+        self.midstate_idx
+    }
+
+    #[inline]
+    fn solution_idx(&self) -> usize {
+        0
+    }
+
+    #[inline]
+    fn target(&self) -> &ii_bitcoin::Target {
+        &self.target
+    }
+}
+
+#[derive(Debug, WorkSolverNode)]
+pub struct Backend {
+    #[member_work_solver_stats]
+    work_solver_stats: stats::BasicWorkSolver,
+    work_generator: Mutex<Option<work::Generator>>,
+    solution_sender: work::SolutionSender,
+}
+
+impl Backend {
+    pub fn new(work_generator: work::Generator, solution_sender: work::SolutionSender) -> Self {
+        Self {
+            work_solver_stats: Default::default(),
+            work_generator: Mutex::new(Some(work_generator)),
+            solution_sender,
+        }
+    }
+
+    /// Try all `nonces` with all midstates of `work` and return solutions meeting `target`
+    pub fn search(
+        work: &work::Assignment,
+        nonces: RangeInclusive<u32>,
+        target: &ii_bitcoin::Target,
+    ) -> Vec<Solution> {
+        let mut solutions = vec![];
+        for midstate_idx in 0..work.midstates.len() {
+            // only the nonce differs so the header is packed just once
+            let mut header_bytes = work.block_header(midstate_idx, 0).into_bytes();
+            for nonce in nonces.clone() {
+                header_bytes[NONCE_OFFSET..].copy_from_slice(&nonce.to_le_bytes());
+                if ii_bitcoin::DHash::hash(&header_bytes).meets(target) {
+                    solutions.push(Solution {
+                        nonce,
+                        midstate_idx,
+                        target: *target,
+                    });
+                }
+            }
+        }
+        solutions
+    }
+
+    /// Solve `work` in batches of nonces until the whole nonce space is exhausted, the work
+    /// expires or it is solved for too long
+    async fn solve(&self, work: work::Assignment) {
+        // Solutions of difficulty 1 are reported to get hashrate statistics, the target of the
+        // job is used only when it is even easier (e.g. test pools)
+        let target = work.target().max(Default::default());
+        let started = Instant::now();
+        let mut start = 0u32;
+        loop {
+            let now = Instant::now();
+            if work.is_expired(now) || now.duration_since(started) >= MAX_WORK_TIME {
+                break;
+            }
+            let end = start.saturating_add(NONCE_BATCH_SIZE - 1);
+            let batch_work = work.clone();
+            // hashing is CPU bound so it must not block the regular threadpool
+            let solutions =
+                runtime::spawn_blocking(move || Self::search(&batch_work, start..=end, &target))
+                    .await
+                    .expect("BUG: CPU solver failed");
+
+            for solution in solutions {
+                self.solution_sender
+                    .send(work::Solution::new(work.clone(), solution, None));
+            }
+            if end == u32::MAX {
+                break;
+            }
+            start = end + 1;
+        }
+    }
+
+    async fn run(self: Arc<Self>) {
+        let mut work_generator = self
+            .work_generator
+            .lock()
+            .await
+            .take()
+            .expect("BUG: missing work generator");
+
+        info!("CPU: solving work on host");
+        while let Some(work) = work_generator.generate().await {
+            self.solve(work).await;
+        }
+    }
+}
+
+#[async_trait]
+impl node::WorkSolver for Backend {
+    async fn get_nominal_hashrate(&self) -> Option<ii_bitcoin::HashesUnit> {
+        None
+    }
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "CPU")
+    }
+}
+
+#[async_trait]
+impl hal::Backend for Backend {
+    type Type = Self;
+    type Config = config::Backend;
+
+    const DEFAULT_HASHRATE_INTERVAL: Duration = config::DEFAULT_HASHRATE_INTERVAL;
+    const JOB_TIMEOUT: Duration = config::JOB_TIMEOUT;
+
+    fn create(_backend_config: &mut config::Backend) -> hal::WorkNode<Self> {
+        node::WorkSolverType::WorkSolver(Box::new(Self::new))
+    }
+
+    async fn init_work_hub(
+        _backend_config: config::Backend,
+        _work_hub: work::SolverBuilder<Self::Type>,
+    ) -> bosminer::Result<hal::FrontendConfig> {
+        panic!("BUG: called `init_work_hub`");
+    }
+
+    async fn init_work_solver(
+        config: config::Backend,
+        work_solver: Arc<Self>,
+    ) -> bosminer::Result<hal::FrontendConfig> {
+        runtime::spawn(work_solver.run());
+
+        // Create initial client configuration
+        config.init_client().await;
+
+        Ok(hal::FrontendConfig {
+            cgminer_custom_commands: None,
+            cgminer_access_control: None,
+            halt_handle: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use bosminer::test_utils::TEST_BLOCKS;
+
+    /// Number of nonces around the known solution which are searched
+    const SEARCH_RADIUS: u32 = 256;
+
+    #[test]
+    fn test_search() {
+        for block in TEST_BLOCKS.iter() {
+            let work: work::Assignment = block.into();
+            let nonces = block.nonce.saturating_sub(SEARCH_RADIUS)
+                ..=block.nonce.saturating_add(SEARCH_RADIUS);
+            let target = Default::default();
+
+            let mut solutions = Backend::search(&work, nonces, &target);
+            assert_eq!(solutions.len(), 1);
+            assert_eq!(solutions[0].nonce, block.nonce);
+            assert_eq!(solutions[0].midstate_idx, 0);
+
+            let solution = work::Solution::new(work, solutions.remove(0), None);
+            assert_eq!(*solution.hash(), block.hash);
+        }
+    }
+}
@@ -0,0 +1,207 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Integration test of the whole mining chain:
+//! scripted V1 pool <- stratum proxy (V2->V1 translation) <- bOSminer with the CPU backend
+//!
+//! The pool sets a difficulty which is low enough to be solved by the CPU in a fraction of second,
+//! verifies each submitted share against the job it has issued and answers with the result. Jobs
+//! are rotated after a few shares so that each test checks that jobs flow down the chain and
+//! shares flow back up through the translation.
+
+use ii_async_compat::prelude::*;
+
+use bosminer::backend;
+use bosminer::client;
+use bosminer::hub;
+use bosminer::job;
+use bosminer::test_utils::simulation::{Protocol, ScriptedPool};
+use bosminer::work;
+
+use bosminer_config::{ClientDescriptor, ClientUserInfo};
+
+use ii_stratum_proxy::server;
+use ii_wire::Address;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+const ADDR: &str = "127.0.0.1";
+const USER: &str = "user.worker";
+
+/// Pool difficulty is 1/2^POOL_DIFFICULTY_SHIFT
+const POOL_DIFFICULTY_SHIFT: usize = 16;
+/// The pool sends a new job after this number of accepted shares
+const SHARES_PER_JOB: usize = 3;
+
+/// Maximal time of each step of a scenario
+const STEP_TIMEOUT: Duration = Duration::from_secs(30);
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Starts V1 pool with a script of `job_count` jobs and a difficulty which can be solved by
+/// the CPU in a fraction of second
+fn start_pool(job_count: usize, shares_per_job: usize) -> ScriptedPool {
+    let target = (ii_bitcoin::Target::default().into_inner() << POOL_DIFFICULTY_SHIFT).into();
+    ScriptedPool::start_with_target(Protocol::StratumV1, job_count, shares_per_job, target)
+}
+
+/// Starts stratum proxy on a free port which translates connections to `pool`
+/// Returns the port the proxy listens on
+fn start_proxy(pool: &ScriptedPool) -> u16 {
+    let proxy = server::ProxyServer::listen(
+        Address(ADDR.into(), 0),
+        pool.addr(),
+        server::handle_connection,
+        None,
+    )
+    .expect("BUG: cannot start proxy");
+    let port = proxy
+        .local_addr()
+        .expect("BUG: missing proxy address")
+        .port();
+    tokio::spawn(proxy.run());
+    port
+}
+
+/// BOSminer instance with the CPU backend
+struct Miner {
+    /// Core holds only weak reference to the registry
+    _backend_registry: Arc<backend::Registry>,
+    clients: Vec<Arc<client::Handle>>,
+}
+
+impl Miner {
+    /// Starts mining on proxies listening on `proxy_ports` in the order of their priority
+    async fn start(proxy_ports: &[u16]) -> Self {
+        let backend_registry = Arc::new(backend::Registry::new());
+        let core = Arc::new(hub::Core::new(
+            bosminer_cpu::config::DEFAULT_MIDSTATE_COUNT,
+            work::DEFAULT_MAX_WORK_AGE,
+            Arc::new(job::DefaultSubmitPolicy::default()),
+            &backend_registry,
+            None,
+        ));
+        core.build_backend::<bosminer_cpu::Backend>(Default::default())
+            .await
+            .expect("BUG: cannot build CPU backend");
+        tokio::spawn(core.clone().run());
+
+        let group = core
+            .get_client_manager()
+            .create_or_get_default_group()
+            .await;
+        let mut clients = vec![];
+        for port in proxy_ports {
+            let url = format!("stratum2+tcp+insecure://{}:{}", ADDR, port);
+            let descriptor = ClientDescriptor::create(&url, &ClientUserInfo::new(USER, None), true)
+                .expect("BUG: invalid proxy URL");
+            clients.push(
+                group
+                    .push_client(client::Handle::new(descriptor, None, None))
+                    .await,
+            );
+        }
+
+        Self {
+            _backend_registry: backend_registry,
+            clients,
+        }
+    }
+}
+
+/// Waits until `condition` holds, panics with `step` description on timeout
+async fn wait_for<F: FnMut() -> bool>(step: &str, mut condition: F) {
+    let poll = async {
+        while !condition() {
+            tokio::time::delay_for(POLL_INTERVAL).await;
+        }
+    };
+    if poll.timeout(STEP_TIMEOUT).await.is_err() {
+        panic!("Timeout: {}", step);
+    }
+}
+
+#[tokio::test]
+async fn test_jobs_and_shares() {
+    const JOB_COUNT: usize = 3;
+
+    let pool = start_pool(JOB_COUNT, SHARES_PER_JOB);
+    let proxy_port = start_proxy(&pool);
+    let _miner = Miner::start(&[proxy_port]).await;
+
+    // Shares for several jobs prove that new jobs are followed by the miner
+    assert!(
+        pool.wait_for_script(STEP_TIMEOUT).await,
+        "Timeout: shares for {} jobs accepted",
+        JOB_COUNT
+    );
+    assert_eq!(pool.rejected_count(), 0);
+    assert_eq!(pool.connection_count(), 1);
+}
+
+#[tokio::test]
+async fn test_reconnect() {
+    let pool = start_pool(1, usize::MAX);
+    let proxy_port = start_proxy(&pool);
+    let miner = Miner::start(&[proxy_port]).await;
+    let client = &miner.clients[0];
+
+    wait_for("first share accepted", || pool.accepted_count() > 0).await;
+
+    // Pool outage is propagated through the proxy to the miner
+    pool.stop();
+    wait_for("client disconnected", || !client.is_running()).await;
+
+    pool.restart();
+    let accepted_count = pool.accepted_count();
+    wait_for("share accepted after reconnection", || {
+        pool.accepted_count() > accepted_count
+    })
+    .await;
+    assert!(client.is_running());
+    assert!(pool.connection_count() >= 2);
+    assert_eq!(pool.rejected_count(), 0);
+}
+
+#[tokio::test]
+async fn test_failover() {
+    let primary_pool = start_pool(1, usize::MAX);
+    let backup_pool = start_pool(1, usize::MAX);
+    let primary_proxy_port = start_proxy(&primary_pool);
+    let backup_proxy_port = start_proxy(&backup_pool);
+    let _miner = Miner::start(&[primary_proxy_port, backup_proxy_port]).await;
+
+    wait_for("share accepted by primary pool", || {
+        primary_pool.accepted_count() > 0
+    })
+    .await;
+    assert_eq!(backup_pool.accepted_count(), 0);
+
+    // Work is switched to the backup pool when the primary one fails
+    primary_pool.stop();
+    wait_for("share accepted by backup pool", || {
+        backup_pool.accepted_count() > 0
+    })
+    .await;
+    assert_eq!(primary_pool.rejected_count(), 0);
+    assert_eq!(backup_pool.rejected_count(), 0);
+}
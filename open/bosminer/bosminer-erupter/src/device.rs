@@ -29,7 +29,7 @@ use crate::Solution;
 
 use bosminer::work;
 
-use failure::{Fail, ResultExt};
+use crate::error::ResultExt;
 
 use std::cell::RefCell;
 use std::convert::TryInto;
@@ -180,7 +180,10 @@ impl<'a> BlockErupter<'a> {
                     .expect("slice with incorrect length"))
             }
             Err(libusb::Error::Timeout) => Ok(None),
-            Err(e) => Err(e.context(ErrorKind::Usb("cannot read nonce")).into()),
+            Err(e) => Err(error::Error::with_source(
+                ErrorKind::Usb("cannot read nonce"),
+                e,
+            )),
         }
     }
 
@@ -22,68 +22,19 @@
 
 //! The Block erupter errors
 
-use failure::{Backtrace, Context, Fail};
-use std::fmt::{self, Debug, Display};
+use thiserror::Error;
 
-pub struct Error {
-    inner: Context<ErrorKind>,
-}
-
-#[derive(Clone, Eq, PartialEq, Debug, Fail)]
+#[derive(Error, Clone, Eq, PartialEq, Debug)]
 pub enum ErrorKind {
     /// Lib USB error.
-    #[fail(display = "USB: {}", _0)]
+    #[error("USB: {0}")]
     Usb(&'static str),
     /// Error related to time measurement.
-    #[fail(display = "Timer: {}", _0)]
+    #[error("Timer: {0}")]
     Timer(&'static str),
 }
 
-/// Implement Fail trait instead of use Derive to get more control over custom type.
-/// The main advantage is customization of Context type which allows conversion of
-/// any error types to this custom error with general error kind by calling context
-/// method on any result type.
-impl Fail for Error {
-    fn cause(&self) -> Option<&dyn Fail> {
-        self.inner.cause()
-    }
-
-    fn backtrace(&self) -> Option<&Backtrace> {
-        self.inner.backtrace()
-    }
-}
-
-impl Display for Error {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        Display::fmt(&self.inner, f)
-    }
-}
-
-impl Debug for Error {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        Debug::fmt(&self.inner, f)
-    }
-}
-
-impl Error {
-    pub fn kind(&self) -> ErrorKind {
-        self.inner.get_context().clone()
-    }
-}
-
-impl From<ErrorKind> for Error {
-    fn from(kind: ErrorKind) -> Self {
-        Self {
-            inner: Context::new(kind),
-        }
-    }
-}
-
-impl From<Context<ErrorKind>> for Error {
-    fn from(inner: Context<ErrorKind>) -> Self {
-        Self { inner }
-    }
-}
+ii_error::error_type!(ErrorKind);
 
 impl From<Error> for bosminer::error::Error {
     fn from(error: Error) -> Self {
@@ -96,6 +47,3 @@ impl From<ErrorKind> for bosminer::error::Error {
         bosminer::error::backend::from_error_kind(kind)
     }
 }
-
-/// A specialized `Result` type bound to [`Error`].
-pub type Result<T> = std::result::Result<T, Error>;
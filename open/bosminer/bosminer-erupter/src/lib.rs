@@ -37,8 +37,7 @@ use bosminer_macros::WorkSolverNode;
 
 use error::ErrorKind;
 
-use ii_async_compat::tokio;
-use tokio::task;
+use ii_async_compat::runtime;
 
 use std::fmt;
 use std::sync::{Arc, Mutex};
@@ -134,7 +133,7 @@ impl Backend {
     fn enable(self: Arc<Self>) {
         // Spawn the future in a separate blocking pool (for blocking operations)
         // so that this doesn't block the regular threadpool.
-        task::spawn_blocking(move || {
+        runtime::spawn_blocking(move || {
             if let Err(e) = self.run() {
                 error!("{}", e);
             }
@@ -190,6 +189,8 @@ impl hal::Backend for Backend {
 
         Ok(hal::FrontendConfig {
             cgminer_custom_commands: None,
+            cgminer_access_control: None,
+            halt_handle: None,
         })
     }
 }
@@ -138,10 +138,14 @@ fn impl_derive_work_solver_node(
         member_start_time,
         member_last_share,
         member_best_share,
+        member_share_histogram,
         member_valid_network_diff,
         member_valid_job_diff,
         member_valid_backend_diff,
-        member_error_backend_diff
+        member_error_backend_diff,
+        member_accepted,
+        member_rejected,
+        member_stale
     )
 )]
 pub fn derive_mining_stats(input: TokenStream) -> TokenStream {
@@ -157,10 +161,14 @@ fn impl_derive_mining_stats(ast: &DeriveInput, derive_name: &str) -> proc_macro2
     let start_time = find_member(&fields, "member_start_time");
     let last_share = find_member(&fields, "member_last_share");
     let best_share = find_member(&fields, "member_best_share");
+    let share_histogram = find_member(&fields, "member_share_histogram");
     let valid_network_diff = find_member(&fields, "member_valid_network_diff");
     let valid_job_diff = find_member(&fields, "member_valid_job_diff");
     let valid_backend_diff = find_member(&fields, "member_valid_backend_diff");
     let error_backend_diff = find_member(&fields, "member_error_backend_diff");
+    let accepted = find_member(&fields, "member_accepted");
+    let rejected = find_member(&fields, "member_rejected");
+    let stale = find_member(&fields, "member_stale");
 
     quote! {
         impl#generics stats::Mining for #name#generics {
@@ -179,6 +187,11 @@ fn impl_derive_mining_stats(ast: &DeriveInput, derive_name: &str) -> proc_macro2
                 &self.#best_share
             }
 
+            #[inline]
+            fn share_histogram(&self) -> &stats::ShareHistogram {
+                &self.#share_histogram
+            }
+
             #[inline]
             fn valid_network_diff(&self) -> &stats::Meter {
                 &self.#valid_network_diff
@@ -198,6 +211,21 @@ fn impl_derive_mining_stats(ast: &DeriveInput, derive_name: &str) -> proc_macro2
             fn error_backend_diff(&self) -> &stats::Meter {
                 &self.#error_backend_diff
             }
+
+            #[inline]
+            fn accepted(&self) -> &stats::Meter {
+                &self.#accepted
+            }
+
+            #[inline]
+            fn rejected(&self) -> &stats::Meter {
+                &self.#rejected
+            }
+
+            #[inline]
+            fn stale(&self) -> &stats::Meter {
+                &self.#stale
+            }
         }
     }
 }
@@ -212,9 +240,12 @@ fn impl_derive_mining_stats(ast: &DeriveInput, derive_name: &str) -> proc_macro2
         member_generated_work,
         member_last_share,
         member_best_share,
+        member_share_histogram,
         member_accepted,
         member_rejected,
         member_stale,
+        member_submit_latency,
+        member_job_latency,
         member_valid_network_diff,
         member_valid_job_diff,
         member_valid_backend_diff,
@@ -240,9 +271,8 @@ fn impl_derive_client_stats(
     let valid_jobs = find_member(&fields, "member_valid_jobs");
     let invalid_jobs = find_member(&fields, "member_invalid_jobs");
     let generated_work = find_member(&fields, "member_generated_work");
-    let accepted = find_member(&fields, "member_accepted");
-    let rejected = find_member(&fields, "member_rejected");
-    let stale = find_member(&fields, "member_stale");
+    let submit_latency = find_member(&fields, "member_submit_latency");
+    let job_latency = find_member(&fields, "member_job_latency");
 
     stream.extend(quote! {
         impl#generics stats::Client for #name#generics {
@@ -262,18 +292,13 @@ fn impl_derive_client_stats(
             }
 
             #[inline]
-            fn accepted(&self) -> &stats::Meter {
-                &self.#accepted
+            fn submit_latency(&self) -> &stats::Latency {
+                &self.#submit_latency
             }
 
             #[inline]
-            fn rejected(&self) -> &stats::Meter {
-                &self.#rejected
-            }
-
-            #[inline]
-            fn stale(&self) -> &stats::Meter {
-                &self.#stale
+            fn job_latency(&self) -> &stats::Latency {
+                &self.#job_latency
             }
         }
     });
@@ -289,10 +314,15 @@ fn impl_derive_client_stats(
         member_generated_work,
         member_last_share,
         member_best_share,
+        member_share_histogram,
         member_valid_network_diff,
         member_valid_job_diff,
         member_valid_backend_diff,
-        member_error_backend_diff
+        member_error_backend_diff,
+        member_valid_chip_backend_diff,
+        member_accepted,
+        member_rejected,
+        member_stale
     )
 )]
 pub fn derive_work_solver_stats(input: TokenStream) -> TokenStream {
@@ -313,6 +343,7 @@ fn impl_derive_work_solver_stats(
     let fields = get_fields(&ast, derive_name);
     let last_work_time = find_member(&fields, "member_last_work_time");
     let generated_work = find_member(&fields, "member_generated_work");
+    let valid_chip_backend_diff = find_member(&fields, "member_valid_chip_backend_diff");
 
     stream.extend(quote! {
         impl#generics stats::WorkSolver for #name#generics {
@@ -325,6 +356,11 @@ fn impl_derive_work_solver_stats(
             fn generated_work(&self) -> &stats::CounterU64 {
                 &self.#generated_work
             }
+
+            #[inline]
+            fn valid_chip_backend_diff(&self) -> &stats::ChipMeters {
+                &self.#valid_chip_backend_diff
+            }
         }
     });
     stream
@@ -0,0 +1,432 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! This module provides alerting about significant miner events. Every event is sent as a JSON
+//! payload with HTTP POST request to all configured webhook URLs. Repeated events of the same
+//! kind from the same source (e.g. hash chain) are rate limited and failed requests are retried. Each webhook is notified independently
+//! so a slow or unreachable one does not delay the others.
+
+use ii_logging::macros::*;
+
+use crate::client;
+use crate::error;
+use crate::http;
+
+use futures::channel::mpsc;
+use futures::future;
+use futures::stream::StreamExt;
+use ii_async_compat::prelude::*;
+use runtime::delay_for;
+
+use serde::Serialize;
+use url::Url;
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time;
+
+/// Default minimal period between two alerts of the same kind from the same source
+pub const DEFAULT_MIN_INTERVAL: time::Duration = time::Duration::from_secs(10 * 60);
+
+/// Default number of attempts to deliver an alert to one webhook
+pub const DEFAULT_RETRIES: usize = 3;
+
+/// Delay before the first retry, it is doubled with each next attempt
+pub const RETRY_DELAY: time::Duration = time::Duration::from_secs(5);
+
+/// Timeout for the whole HTTP request
+pub const REQUEST_TIMEOUT: time::Duration = time::Duration::from_secs(10);
+
+/// Maximal time spent by delivery of pending alerts when the miner exits
+pub const FLUSH_TIMEOUT: time::Duration = time::Duration::from_secs(15);
+
+/// How often the state of clients is checked
+pub const CLIENT_WATCH_INTERVAL: time::Duration = time::Duration::from_secs(30);
+
+/// Significant events reported with alerts
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// Hash chain stopped responding or failed
+    ChainDead {
+        hashboard_idx: usize,
+        reason: String,
+    },
+    /// Temperature is too high or cannot be measured at all
+    Overtemperature {
+        temperature: Option<f32>,
+        reason: String,
+    },
+    /// Not enough fans are running
+    FanFailure { fans_running: usize, reason: String },
+    /// Hash chain returns results which are statistically implausible (e.g. stuck nonce counter)
+    HardwareFault {
+        hashboard_idx: usize,
+        fault: &'static str,
+        reason: String,
+    },
+    /// There is no working connection to any enabled pool
+    AllPoolsDown,
+}
+
+impl Event {
+    /// Events of the same kind from the same source share rate limiting
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::ChainDead { .. } => "chain_dead",
+            Self::Overtemperature { .. } => "overtemperature",
+            Self::FanFailure { .. } => "fan_failure",
+            Self::HardwareFault { .. } => "hardware_fault",
+            Self::AllPoolsDown => "all_pools_down",
+        }
+    }
+
+    /// Index of hashboard the event relates to, `None` for events of the whole miner
+    pub fn source(&self) -> Option<usize> {
+        match self {
+            Self::ChainDead { hashboard_idx, .. } | Self::HardwareFault { hashboard_idx, .. } => {
+                Some(*hashboard_idx)
+            }
+            Self::Overtemperature { .. } | Self::FanFailure { .. } | Self::AllPoolsDown => None,
+        }
+    }
+}
+
+/// Payload sent to webhooks
+#[derive(Serialize, Debug)]
+struct Payload<'a> {
+    /// Unix time (in seconds) when the event has occurred
+    timestamp: u64,
+    #[serde(flatten)]
+    event: &'a Event,
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Webhook URLs which are notified about each event
+    urls: Vec<Url>,
+    /// Minimal period between two alerts of the same kind from the same source
+    pub min_interval: time::Duration,
+    /// Number of attempts to deliver an alert to one webhook
+    pub retries: usize,
+}
+
+impl Config {
+    /// Adds webhook URL which is going to be notified about events. Only plain HTTP is supported.
+    pub fn add_webhook(&mut self, url: &str) -> error::Result<()> {
+        let url = Url::parse(url).map_err(|e| format!("invalid webhook URL '{}': {}", url, e))?;
+        if url.scheme() != "http" {
+            Err(format!("unsupported webhook scheme '{}'", url.scheme()))?;
+        }
+        if url.host_str().is_none() {
+            Err(format!("missing host in webhook URL '{}'", url))?;
+        }
+        self.urls.push(url);
+        Ok(())
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            urls: vec![],
+            min_interval: DEFAULT_MIN_INTERVAL,
+            retries: DEFAULT_RETRIES,
+        }
+    }
+}
+
+/// Queue of events for the task which delivers them to webhooks
+#[derive(Debug, Clone)]
+struct Webhooks {
+    sender: mpsc::UnboundedSender<Event>,
+    /// Number of events which have not been processed by the task yet
+    pending: Arc<AtomicUsize>,
+}
+
+/// Sender of alert events which are delivered to webhooks and distributed to all subscribers.
+/// It does nothing when there is neither webhook nor subscriber (e.g. alerting is disabled).
+#[derive(Debug, Clone, Default)]
+pub struct Sender {
+    inner: Vec<mpsc::UnboundedSender<Event>>,
+    webhooks: Option<Webhooks>,
+}
+
+impl Sender {
+    /// Registers new receiver of all future events. The receiver has to be registered before
+    /// the sender is cloned because the clones do not share subscribers.
+    pub fn subscribe(&mut self) -> mpsc::UnboundedReceiver<Event> {
+        let (sender, receiver) = mpsc::unbounded();
+        self.inner.push(sender);
+        receiver
+    }
+
+    pub fn notify(&self, event: Event) {
+        if let Some(webhooks) = self.webhooks.as_ref() {
+            webhooks.pending.fetch_add(1, Ordering::Relaxed);
+            if webhooks.sender.unbounded_send(event.clone()).is_err() {
+                webhooks.pending.fetch_sub(1, Ordering::Relaxed);
+                warn!("Alert: cannot send event because webhook task is not running");
+            }
+        }
+        for inner in self.inner.iter() {
+            if inner.unbounded_send(event.clone()).is_err() {
+                warn!("Alert: cannot send event because receiving task is not running");
+            }
+        }
+    }
+
+    /// Waits until all events notified so far have been delivered to webhooks or `timeout`
+    /// expires. Returns `true` when there is no pending event.
+    pub async fn flush(&self, timeout: time::Duration) -> bool {
+        const POLL_INTERVAL: time::Duration = time::Duration::from_millis(100);
+
+        let webhooks = match self.webhooks.as_ref() {
+            Some(webhooks) => webhooks,
+            None => return true,
+        };
+        let started = time::Instant::now();
+        loop {
+            let pending = webhooks.pending.load(Ordering::Relaxed);
+            if pending == 0 {
+                return true;
+            }
+            if started.elapsed() >= timeout {
+                warn!("Alert: dropping {} undelivered event(s)", pending);
+                return false;
+            }
+            delay_for(POLL_INTERVAL).await;
+        }
+    }
+}
+
+/// Decides which events are passed to webhooks
+#[derive(Debug)]
+struct RateLimiter {
+    min_interval: time::Duration,
+    /// Time of the last alert for each kind and source of events
+    last_sent: HashMap<(&'static str, Option<usize>), time::Instant>,
+}
+
+impl RateLimiter {
+    fn new(min_interval: time::Duration) -> Self {
+        Self {
+            min_interval,
+            last_sent: HashMap::new(),
+        }
+    }
+
+    fn check(&mut self, event: &Event, now: time::Instant) -> bool {
+        let key = (event.kind(), event.source());
+        match self.last_sent.get(&key) {
+            Some(last_sent) if now.duration_since(*last_sent) < self.min_interval => false,
+            _ => {
+                self.last_sent.insert(key, now);
+                true
+            }
+        }
+    }
+}
+
+/// Delivers `body` to webhook with given number of attempts
+async fn deliver(url: &Url, body: &[u8], retries: usize) {
+    let mut retry_delay = RETRY_DELAY;
+    for attempt in 1..=retries.max(1) {
+        let result = match http::post(url, &[("Content-Type", "application/json")], body)
+            .timeout(REQUEST_TIMEOUT)
+            .await
+        {
+            Ok(result) => result,
+            Err(_) => Err("request timeout".into()),
+        };
+        match result {
+            Ok(_) => return,
+            Err(e) => warn!(
+                "Alert: webhook '{}' failed (attempt {}/{}): {}",
+                url, attempt, retries, e
+            ),
+        }
+        if attempt < retries {
+            delay_for(retry_delay).await;
+            retry_delay *= 2;
+        }
+    }
+    error!("Alert: giving up delivery to webhook '{}'", url);
+}
+
+/// Delivers `event` to all webhooks at once
+async fn send(config: &Config, event: &Event) {
+    info!("Alert: sending event {:?}", event);
+    let payload = Payload {
+        timestamp: time::SystemTime::now()
+            .duration_since(time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs(),
+        event,
+    };
+    let body = serde_json::to_vec(&payload).expect("BUG: cannot serialize alert");
+    future::join_all(
+        config
+            .urls
+            .iter()
+            .map(|url| deliver(url, &body, config.retries)),
+    )
+    .await;
+}
+
+async fn task(
+    config: Config,
+    mut receiver: mpsc::UnboundedReceiver<Event>,
+    pending: Arc<AtomicUsize>,
+) {
+    let mut rate_limiter = RateLimiter::new(config.min_interval);
+    while let Some(event) = receiver.next().await {
+        if rate_limiter.check(&event, time::Instant::now()) {
+            send(&config, &event).await;
+        } else {
+            debug!("Alert: suppressing repeated event {:?}", event);
+        }
+        pending.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Starts alerting task and returns sender for new events. Alerting is disabled when there is
+/// no webhook configured.
+pub fn start(config: Config) -> Sender {
+    let mut sender = Sender::default();
+    if !config.urls.is_empty() {
+        let (webhook_sender, receiver) = mpsc::unbounded();
+        let pending = Arc::new(AtomicUsize::new(0));
+        runtime::spawn(task(config, receiver, pending.clone()));
+        sender.webhooks = Some(Webhooks {
+            sender: webhook_sender,
+            pending,
+        });
+    }
+    sender
+}
+
+/// Periodically checks all clients and notifies when none of enabled clients is running
+pub async fn client_watch_task(client_manager: client::Manager, sender: Sender) {
+    let mut all_down = false;
+    let mut interval = runtime::Interval::new(CLIENT_WATCH_INTERVAL);
+    while interval.tick().await.is_some() {
+        let mut enabled = false;
+        let mut running = false;
+        for group in client_manager.get_groups().await {
+            for client in group.get_clients().await {
+                enabled |= client.is_enabled();
+                running |= client.is_running();
+            }
+        }
+        let down = enabled && !running;
+        if down && !all_down {
+            sender.notify(Event::AllPoolsDown);
+        }
+        all_down = down;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_rate_limiter() {
+        let mut rate_limiter = RateLimiter::new(time::Duration::from_secs(60));
+        let now = time::Instant::now();
+
+        assert!(rate_limiter.check(&Event::AllPoolsDown, now));
+        assert!(!rate_limiter.check(&Event::AllPoolsDown, now + time::Duration::from_secs(30)));
+        // other kinds of events are not limited
+        let fan_failure = Event::FanFailure {
+            fans_running: 0,
+            reason: "not enough fans".to_string(),
+        };
+        assert!(rate_limiter.check(&fan_failure, now));
+        assert!(rate_limiter.check(&Event::AllPoolsDown, now + time::Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn test_rate_limiter_chains() {
+        let mut rate_limiter = RateLimiter::new(time::Duration::from_secs(60));
+        let now = time::Instant::now();
+        let chain_dead = |hashboard_idx| Event::ChainDead {
+            hashboard_idx,
+            reason: "timeout".to_string(),
+        };
+
+        assert!(rate_limiter.check(&chain_dead(6), now));
+        // failure of another chain is not suppressed by the first one
+        assert!(rate_limiter.check(&chain_dead(7), now + time::Duration::from_secs(10)));
+        assert!(!rate_limiter.check(&chain_dead(6), now + time::Duration::from_secs(20)));
+        assert!(!rate_limiter.check(&chain_dead(7), now + time::Duration::from_secs(30)));
+        assert!(rate_limiter.check(&chain_dead(6), now + time::Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn test_payload() {
+        let event = Event::ChainDead {
+            hashboard_idx: 8,
+            reason: "timeout".to_string(),
+        };
+        let payload = Payload {
+            timestamp: 1,
+            event: &event,
+        };
+        assert_eq!(
+            serde_json::to_string(&payload).expect("BUG: cannot serialize payload"),
+            r#"{"timestamp":1,"event":"chain_dead","hashboard_idx":8,"reason":"timeout"}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn test_flush() {
+        assert!(
+            Sender::default()
+                .flush(time::Duration::from_millis(0))
+                .await
+        );
+
+        // nothing listens on the port so the delivery is retried for a long time
+        let mut config = Config::default();
+        config
+            .add_webhook("http://127.0.0.1:1/hook")
+            .expect("BUG: cannot add webhook");
+        let sender = start(config);
+        assert!(sender.flush(time::Duration::from_millis(0)).await);
+        sender.notify(Event::AllPoolsDown);
+        assert!(!sender.flush(time::Duration::from_millis(200)).await);
+    }
+
+    #[test]
+    fn test_add_webhook() {
+        let mut config = Config::default();
+        config
+            .add_webhook("http://127.0.0.1:8080/hook")
+            .expect("BUG: cannot add webhook");
+        assert!(config.add_webhook("https://127.0.0.1/hook").is_err());
+        assert!(config.add_webhook("127.0.0.1/hook").is_err());
+        assert_eq!(config.urls.len(), 1);
+    }
+}
@@ -24,10 +24,24 @@ mod cgminer;
 
 use crate::hal;
 use crate::hub;
+use crate::stats;
 
 use std::sync::Arc;
 
-pub async fn run(core: Arc<hub::Core>, config: hal::FrontendConfig, signature: String) {
+pub async fn run(
+    core: Arc<hub::Core>,
+    config: hal::FrontendConfig,
+    signature: String,
+    lifetime_stats: Option<Arc<stats::persistent::Lifetime>>,
+) {
     let addr = "0.0.0.0:4028".parse().unwrap();
-    cgminer::run(core, addr, config.cgminer_custom_commands, signature).await;
+    cgminer::run(
+        core,
+        addr,
+        config.cgminer_custom_commands,
+        config.cgminer_access_control,
+        signature,
+        lifetime_stats,
+    )
+    .await;
 }
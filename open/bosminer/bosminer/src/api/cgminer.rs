@@ -27,12 +27,14 @@ use crate::client;
 use crate::error;
 use crate::hub;
 use crate::node::{self, Stats as _, WorkSolver, WorkSolverStats as _};
+use crate::stats::persistent::{self, BestShareRecord, Lifetime, PoolRecord};
 use crate::stats::{self, UnixTime as _};
 use crate::sync;
 use crate::version;
 
+use ii_cgminer_api::command::{BEST_SHARES, LATENCY, SHARES, SHARE_RATIO};
 use ii_cgminer_api::support::ValueExt as _;
-use ii_cgminer_api::{command, json, response};
+use ii_cgminer_api::{command, commands, json, response};
 
 use bosminer_config::{ClientDescriptor, ClientUserInfo};
 
@@ -52,11 +54,16 @@ const DEFAULT_LOG_INTERVAL: u32 = 5;
 
 struct Handler {
     core: Arc<hub::Core>,
+    /// Statistics restored from previous runs of the miner
+    lifetime_stats: Option<Arc<Lifetime>>,
 }
 
 impl Handler {
-    pub fn new(core: Arc<hub::Core>) -> Self {
-        Self { core }
+    pub fn new(core: Arc<hub::Core>, lifetime_stats: Option<Arc<Lifetime>>) -> Self {
+        Self {
+            core,
+            lifetime_stats,
+        }
     }
 
     async fn collect_data<C, F, T, U, V>(&self, container: C, base_idx: usize, f: F) -> Vec<T>
@@ -81,36 +88,24 @@ impl Handler {
         let valid_jobs = client_stats.valid_jobs().take_snapshot();
         let invalid_jobs = client_stats.invalid_jobs().take_snapshot();
         let generated_work = client_stats.generated_work().take_snapshot();
-        let accepted = client_stats.accepted().take_snapshot().await;
-        let rejected = client_stats.rejected().take_snapshot().await;
-        let stale = client_stats.stale().take_snapshot().await;
-        let last_share = client_stats.last_share().take_snapshot().await;
         let valid_backend_diff = client_stats.valid_backend_diff().take_snapshot().await;
-        let best_share = client_stats.best_share().take_snapshot();
+        let shares = stats::SharesSnapshot::take(client_stats).await;
+        let stats::SharesSnapshot {
+            accepted,
+            rejected,
+            stale,
+            last_share,
+            best_share,
+        } = shares.clone();
 
         let last_share_time = last_share
             .as_ref()
             .map_or(0, |share| share.time.get_unix_time().unwrap_or_default());
         let last_share_difficulty = last_share.map_or(0.0, |share| share.difficulty as f64);
 
-        let pool_accepted_shares = accepted.shares.as_f64();
-        let pool_rejected_shares = rejected.shares.as_f64();
-        let pool_stale_shares = stale.shares.as_f64();
-        let pool_total_shares = pool_accepted_shares + pool_rejected_shares + pool_stale_shares;
-        let pool_rejected_ratio = if pool_total_shares != 0.0 {
-            pool_rejected_shares / pool_total_shares * 100.0
-        } else {
-            0.0
-        };
-        let pool_stale_ratio = if pool_total_shares != 0.0 {
-            pool_stale_shares / pool_total_shares * 100.0
-        } else {
-            0.0
-        };
-
         let last_diff = last_job
             .as_ref()
-            .map(|job| job.target().get_difficulty() as f64)
+            .map(|job| ii_bitcoin::difficulty::target_to_difficulty(&job.target()))
             .unwrap_or(0.0);
         let current_block_version = last_job.map(|job| job.version()).unwrap_or_default();
 
@@ -157,9 +152,9 @@ impl Handler {
             diff1_shares: valid_backend_diff.solutions,
             proxy_type: "".to_string(),
             proxy: "".to_string(),
-            difficulty_accepted: pool_accepted_shares,
-            difficulty_rejected: pool_rejected_shares,
-            difficulty_stale: pool_stale_shares,
+            difficulty_accepted: accepted.shares.as_f64(),
+            difficulty_rejected: rejected.shares.as_f64(),
+            difficulty_stale: stale.shares.as_f64(),
             last_share_difficulty,
             work_difficulty: last_diff,
             has_stratum: true,
@@ -169,9 +164,9 @@ impl Handler {
             // TODO: get actual value from client (Asic Boost)
             has_vmask: true,
             has_gbt: false,
-            best_share: best_share.map(|inner| *inner).unwrap_or_default() as u64,
-            pool_rejected_ratio,
-            pool_stale_ratio,
+            best_share: best_share.map_or(0, |inner| inner.difficulty),
+            pool_rejected_ratio: shares.rejected_ratio(),
+            pool_stale_ratio: shares.stale_ratio(),
             bad_work: *invalid_jobs as u64,
             // TODO: BOSminer does not have coinbase for Stratum V2
             current_block_height: 0,
@@ -192,7 +187,7 @@ impl Handler {
         let mining_stats = work_solver.mining_stats();
         let work_solver_stats = work_solver.work_solver_stats();
         let last_work_time = work_solver_stats.last_work_time().take_snapshot().await;
-        let last_share = mining_stats.last_share().take_snapshot().await;
+        let shares = stats::SharesSnapshot::take(mining_stats).await;
         let valid_job_diff = mining_stats.valid_job_diff().take_snapshot().await;
         let valid_backend_diff = mining_stats.valid_backend_diff().take_snapshot().await;
         let error_backend_diff = mining_stats.error_backend_diff().take_snapshot().await;
@@ -202,10 +197,14 @@ impl Handler {
 
         let last_work_time =
             last_work_time.map_or(0, |time| time.get_unix_time().unwrap_or_default());
-        let last_share_time = last_share
+        let last_share_time = shares
+            .last_share
             .as_ref()
             .map_or(0, |share| share.time.get_unix_time().unwrap_or_default());
-        let last_share_difficulty = last_share.map_or(0.0, |share| share.difficulty as f64);
+        let last_share_difficulty = shares
+            .last_share
+            .as_ref()
+            .map_or(0.0, |share| share.difficulty as f64);
 
         let total_mega_hashes = valid_job_diff.shares.into_mega_hashes().into_f64();
         let backend_valid_solutions = valid_backend_diff.solutions;
@@ -216,6 +215,11 @@ impl Handler {
         } else {
             0.0
         };
+        let utility = if elapsed.as_secs() != 0 {
+            shares.accepted.solutions as f64 / elapsed.as_secs() as f64
+        } else {
+            shares.accepted.solutions as f64
+        } * 60.0;
 
         response::Asc {
             idx: idx as i32,
@@ -226,8 +230,11 @@ impl Handler {
             enabled: response::Bool::Y,
             // TODO: get actual status from work solver
             status: response::AscStatus::Alive,
-            // TODO: get actual temperature from work solver?
-            temperature: 0.0,
+            temperature: work_solver
+                .get_temperature()
+                .await
+                .map(f64::from)
+                .unwrap_or_default(),
             mhs_av: total_mega_hashes / elapsed.as_secs_f64(),
             mhs_5s: valid_backend_diff
                 .to_mega_hashes(*INTERVAL_5S, now)
@@ -241,27 +248,21 @@ impl Handler {
             mhs_15m: valid_backend_diff
                 .to_mega_hashes(*INTERVAL_15M, now)
                 .into_f64(),
-            // TODO: BOSminer does not account this information
-            accepted: 0,
-            // TODO: BOSminer does not account this information
-            rejected: 0,
+            accepted: shares.accepted.solutions as i32,
+            rejected: shares.rejected.solutions as i32,
             hardware_errors: backend_error_solutions as i32,
-            // TODO: BOSminer does not account accepted
-            utility: 0.0,
+            utility,
             // TODO: BOSminer does not account accepted
             last_share_pool: -1,
             last_share_time,
             total_mega_hashes,
             diff1_work: backend_valid_solutions,
-            // TODO: BOSminer does not account accepted
-            difficulty_accepted: 0.0,
-            // TODO: BOSminer does not account rejected
-            difficulty_rejected: 0.0,
+            difficulty_accepted: shares.accepted.shares.as_f64(),
+            difficulty_rejected: shares.rejected.shares.as_f64(),
             last_share_difficulty,
             last_valid_work: last_work_time,
             device_hardware_ratio: backend_error_ratio,
-            // TODO: BOSminer does not account rejected
-            device_rejected_ratio: 0.0,
+            device_rejected_ratio: shares.rejected_ratio(),
             device_elapsed: elapsed.as_secs(),
             hardware_error_mhs_15m: error_backend_diff
                 .to_mega_hashes(*INTERVAL_15M, now)
@@ -281,7 +282,9 @@ impl Handler {
         .await
     }
 
-    async fn get_pool_stats(idx: usize, _client: Arc<client::Handle>) -> response::PoolStats {
+    async fn get_pool_stats(idx: usize, client: Arc<client::Handle>) -> response::PoolStats {
+        let submit_latency = client.stats().submit_latency().take_snapshot().await;
+
         response::PoolStats {
             header: response::StatsHeader {
                 idx: idx as i32,
@@ -292,12 +295,15 @@ impl Handler {
                 max: 0.0,
                 min: 0.0,
             },
-            pool_calls: 0,
-            pool_attempts: 0,
-            pool_wait: 0.0,
-            pool_max: 0.0,
-            pool_min: 0.0,
-            pool_av: 0.0,
+            pool_calls: submit_latency.count as u32,
+            pool_attempts: submit_latency.count as u32,
+            pool_wait: submit_latency.total.as_secs_f64(),
+            pool_max: submit_latency.max.as_secs_f64(),
+            pool_min: submit_latency.min.as_secs_f64(),
+            pool_av: submit_latency
+                .average()
+                .map(|average| average.as_secs_f64())
+                .unwrap_or_default(),
             work_had_roll_time: false,
             work_can_roll: false,
             work_had_expire: false,
@@ -316,6 +322,39 @@ impl Handler {
         }
     }
 
+    fn get_latency_percentiles(
+        latency: &stats::latency::LatencySnapshot,
+    ) -> response::ext::LatencyPercentiles {
+        let percentile = |percentile| {
+            latency
+                .percentile(percentile)
+                .map(|latency| latency.as_secs_f64())
+                .unwrap_or_default()
+        };
+        response::ext::LatencyPercentiles {
+            samples: latency.recent_samples() as u32,
+            p50: percentile(50.0),
+            p90: percentile(90.0),
+            p99: percentile(99.0),
+        }
+    }
+
+    async fn get_pool_latency(
+        idx: usize,
+        client: Arc<client::Handle>,
+    ) -> response::ext::PoolLatency {
+        let client_stats = client.stats();
+        let submit_latency = client_stats.submit_latency().take_snapshot().await;
+        let job_latency = client_stats.job_latency().take_snapshot().await;
+
+        response::ext::PoolLatency {
+            idx: idx as i32,
+            url: client.descriptor().await.get_url(true, true, false),
+            submit: Self::get_latency_percentiles(&submit_latency),
+            job: Self::get_latency_percentiles(&job_latency),
+        }
+    }
+
     async fn collect_pool_stats(&self, base_idx: usize) -> Vec<response::PoolStats> {
         self.collect_data(self.get_clients(), base_idx, |idx, client| {
             async move { Self::get_pool_stats(idx, client).await }
@@ -397,6 +436,151 @@ impl Handler {
 
         ClientDescriptor::create(url, &ClientUserInfo::new(user, password), true).map_err(|_| ())
     }
+
+    async fn handle_shares(&self) -> command::Result<response::ext::Shares> {
+        let mining_stats = self.core.frontend.mining_stats();
+        let histogram = mining_stats.share_histogram().take_snapshot();
+        let elapsed = time::Instant::now().duration_since(*mining_stats.start_time());
+
+        Ok(response::ext::Shares {
+            elapsed: elapsed.as_secs(),
+            valid_shares: histogram.solutions(),
+            diff1_work: histogram.diff1_work(),
+            best_share: histogram.best_difficulty().unwrap_or_default() as u64,
+            expected_best_share: histogram.expected_best_difficulty(),
+            luck: histogram.best_difficulty_luck(),
+            histogram: histogram
+                .buckets()
+                .iter()
+                .map(|bucket| response::ext::ShareBucket {
+                    difficulty: bucket.difficulty,
+                    count: bucket.count,
+                    expected: bucket.expected,
+                    luck: bucket.luck(),
+                })
+                .collect(),
+        })
+    }
+
+    fn get_best_share(
+        idx: usize,
+        kind: &str,
+        name: String,
+        session: Option<BestShareRecord>,
+        lifetime: Option<&BestShareRecord>,
+    ) -> response::ext::BestShare {
+        let session = session.unwrap_or_default();
+        let lifetime = lifetime.copied().unwrap_or_default().max(session);
+
+        response::ext::BestShare {
+            idx: idx as i32,
+            kind: kind.to_string(),
+            name,
+            session_best_share: session.difficulty,
+            session_best_share_time: session.time as response::Time,
+            lifetime_best_share: lifetime.difficulty,
+            lifetime_best_share_time: lifetime.time as response::Time,
+        }
+    }
+
+    async fn handle_best_shares(&self) -> command::Result<response::ext::BestShares> {
+        let lifetime_stats = self.lifetime_stats.as_ref();
+        let frontend = &self.core.frontend;
+        let mut list = vec![Self::get_best_share(
+            0,
+            "Miner",
+            frontend.to_string(),
+            BestShareRecord::from_stats(frontend.mining_stats().best_share()),
+            lifetime_stats
+                .map(|lifetime| lifetime.get_best_share())
+                .as_ref(),
+        )];
+        for client in self.get_clients().await {
+            let url = client.descriptor().await.get_full_url();
+            list.push(Self::get_best_share(
+                list.len(),
+                "Pool",
+                url.clone(),
+                BestShareRecord::from_stats(client.stats().best_share()),
+                lifetime_stats.and_then(|lifetime| lifetime.pool_best_shares.get(&url)),
+            ));
+        }
+        for work_solver in self.core.get_work_solvers().await {
+            let name = work_solver.to_string();
+            list.push(Self::get_best_share(
+                list.len(),
+                "Chain",
+                name.clone(),
+                BestShareRecord::from_stats(work_solver.mining_stats().best_share()),
+                lifetime_stats.and_then(|lifetime| lifetime.chain_best_shares.get(&name)),
+            ));
+        }
+
+        Ok(response::ext::BestShares { list })
+    }
+
+    async fn handle_share_ratio(&self) -> command::Result<response::ext::ShareRatio> {
+        let lifetime_pools = self
+            .lifetime_stats
+            .as_ref()
+            .map(|lifetime| lifetime.pools.clone())
+            .unwrap_or_default();
+
+        let mut pools = vec![];
+        for (group, target_ratio) in self.core.get_client_manager().get_share_ratios().await {
+            for client in group.get_clients().await {
+                pools.push((
+                    group.descriptor.name.clone(),
+                    target_ratio,
+                    client.descriptor().await.get_full_url(),
+                    PoolRecord::from_client(&client).await,
+                ));
+            }
+        }
+        let session_shares: u64 = pools
+            .iter()
+            .map(|(_, _, _, record)| record.accepted_shares)
+            .sum();
+        let lifetime_shares = persistent::total_accepted_shares(&lifetime_pools) + session_shares;
+
+        let list = pools
+            .into_iter()
+            .enumerate()
+            .map(|(idx, (group, target_ratio, url, session))| {
+                let lifetime = lifetime_pools
+                    .get(&url)
+                    .copied()
+                    .unwrap_or_default()
+                    .merge(session);
+                response::ext::PoolShareRatio {
+                    idx: idx as i32,
+                    url,
+                    group,
+                    target_ratio,
+                    session_accepted: session.accepted_shares,
+                    session_rejected: session.rejected_shares,
+                    session_uptime: session.uptime,
+                    session_ratio: session.share_ratio(session_shares),
+                    lifetime_accepted: lifetime.accepted_shares,
+                    lifetime_rejected: lifetime.rejected_shares,
+                    lifetime_uptime: lifetime.uptime,
+                    lifetime_ratio: lifetime.share_ratio(lifetime_shares),
+                }
+            })
+            .collect();
+
+        Ok(response::ext::ShareRatio { list })
+    }
+
+    async fn handle_latency(&self) -> command::Result<response::ext::Latency> {
+        Ok(response::ext::Latency {
+            list: self
+                .collect_data(self.get_clients(), 0, |idx, client| {
+                    async move { Self::get_pool_latency(idx, client).await }
+                })
+                .await,
+        })
+    }
 }
 
 #[async_trait::async_trait]
@@ -535,7 +719,7 @@ impl command::Handler for Handler {
             difficulty_accepted: pools_accepted_shares,
             difficulty_rejected: pools_rejected_shares,
             difficulty_stale: pools_stale_shares,
-            best_share: best_share.map(|inner| *inner).unwrap_or_default() as u64,
+            best_share: best_share.map_or(0, |inner| inner.difficulty),
             device_hardware_ratio: backend_error_ratio,
             device_rejected_ratio: backend_rejected_ratio,
             pool_rejected_ratio: pools_rejected_ratio,
@@ -782,15 +966,28 @@ pub async fn run(
     core: Arc<hub::Core>,
     listen_addr: SocketAddr,
     custom_commands: Option<command::Map>,
+    access_control: Option<command::AccessControl>,
     signature: String,
+    lifetime_stats: Option<Arc<Lifetime>>,
 ) {
-    let handler = Handler::new(core);
-    let command_receiver = command::Receiver::new(
-        handler,
-        signature,
-        version::STRING.to_string(),
-        custom_commands,
-    );
+    // extended commands implemented for all backends
+    let ext_handler = Arc::new(Handler::new(core.clone(), lifetime_stats.clone()));
+    let mut commands = command::read_only(commands![
+        (SHARES: ParameterLess -> ext_handler.handle_shares),
+        (LATENCY: ParameterLess -> ext_handler.handle_latency),
+        (BEST_SHARES: ParameterLess -> ext_handler.handle_best_shares),
+        (SHARE_RATIO: ParameterLess -> ext_handler.handle_share_ratio)
+    ]);
+    if let Some(custom_commands) = custom_commands {
+        commands.extend(custom_commands);
+    }
+
+    let handler = Handler::new(core, lifetime_stats);
+    let mut command_receiver =
+        command::Receiver::new(handler, signature, version::STRING.to_string(), commands);
+    if let Some(access_control) = access_control {
+        command_receiver = command_receiver.with_access_control(access_control);
+    }
 
     ii_cgminer_api::run(command_receiver, listen_addr)
         .await
@@ -23,6 +23,7 @@
 //! This module contains common functionality related to mining protocol client and allows
 //! executing a specific type of mining protocol client instance.
 
+mod outage;
 mod scheduler;
 
 // Sub-modules with client implementation
@@ -46,13 +47,13 @@ use bosminer_config::{
     LoadBalanceStrategy,
 };
 
-use futures::channel::mpsc;
 use futures::lock::Mutex;
 use ii_async_compat::futures;
 
 use std::slice;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time;
 
 #[derive(Debug)]
 pub struct Handle {
@@ -61,7 +62,8 @@ pub struct Handle {
     node: Arc<dyn node::Client>,
     enabled: AtomicBool,
     engine_sender: Arc<work::EngineSender>,
-    solution_sender: mpsc::UnboundedSender<work::Solution>,
+    solution_sender: work::SolutionQueueSender,
+    submit_policy: job::SharedSubmitPolicy,
 }
 
 impl Handle {
@@ -75,11 +77,17 @@ impl Handle {
             stratum_v2::ExtensionChannelFromStratumSender,
         )>,
     ) -> Self {
-        let (solution_sender, solution_receiver) = mpsc::unbounded();
+        let (solution_sender, solution_receiver) = work::solution_queue("client solutions");
         // Initially register new client without ability to send work
         let engine_sender = Arc::new(work::EngineSender::new(None));
+        let submit_policy: job::SharedSubmitPolicy =
+            Arc::new(StdMutex::new(Arc::new(job::DefaultSubmitPolicy::default())));
 
-        let job_solver = job::Solver::new(engine_sender.clone(), solution_receiver);
+        let job_solver = job::Solver::new(
+            engine_sender.clone(),
+            solution_receiver,
+            submit_policy.clone(),
+        );
         let node: Arc<dyn node::Client> = match &descriptor.protocol {
             ClientProtocol::Drain => {
                 assert!(
@@ -88,7 +96,7 @@ impl Handle {
                 );
                 Arc::new(drain::Client::new(descriptor.get_full_url(), job_solver))
             }
-            ClientProtocol::StratumV1 => {
+            ClientProtocol::StratumV1 | ClientProtocol::StratumV1Tls => {
                 assert!(
                     channel.is_none(),
                     "BUG: protocol 'Stratum V1' does not support channel"
@@ -118,6 +126,7 @@ impl Handle {
             enabled: AtomicBool::new(false),
             engine_sender,
             solution_sender,
+            submit_policy,
         }
     }
 
@@ -142,6 +151,20 @@ impl Handle {
             .replace_engine_generator(engine_generator)
     }
 
+    /// Returns the submit policy that has been replaced
+    pub fn replace_submit_policy(
+        &self,
+        submit_policy: job::DynSubmitPolicy,
+    ) -> job::DynSubmitPolicy {
+        std::mem::replace(
+            &mut *self
+                .submit_policy
+                .lock()
+                .expect("BUG: cannot lock submit policy"),
+            submit_policy,
+        )
+    }
+
     /// Tests if solution should be delivered to this client
     /// NOTE: This comparison uses trait method `node::Info::get_unique_ptr` to unify dynamic
     /// objects to point to the same pointer otherwise direct comparison of self with other is never
@@ -179,6 +202,12 @@ impl Handle {
         self.node.status().status()
     }
 
+    /// Total time the client has been connected to remote server
+    #[inline]
+    pub fn connection_uptime(&self) -> time::Duration {
+        self.node.status().running_time()
+    }
+
     #[inline]
     fn start(&self) {
         if self.node.status().initiate_starting() {
@@ -274,6 +303,10 @@ pub struct Group {
     event_sender: event::Sender,
     /// All clients in the group must support the same amount of midstates
     midstate_count: usize,
+    /// No work is generated from jobs older than this
+    max_work_age: time::Duration,
+    /// Decides which solutions are submitted by clients in the group
+    submit_policy: job::DynSubmitPolicy,
 }
 
 impl Group {
@@ -281,12 +314,16 @@ impl Group {
         descriptor: GroupDescriptor,
         event_sender: event::Sender,
         midstate_count: usize,
+        max_work_age: time::Duration,
+        submit_policy: job::DynSubmitPolicy,
     ) -> Self {
         Self {
             descriptor,
             scheduler_client_handles: Mutex::new(vec![]),
             event_sender,
             midstate_count,
+            max_work_age,
+            submit_policy,
         }
     }
 
@@ -312,9 +349,13 @@ impl Group {
 
     pub async fn push_client(&self, client_handle: Handle) -> Arc<Handle> {
         let midstate_count = self.midstate_count;
+        let max_work_age = self.max_work_age;
         let _ = client_handle.replace_engine_generator(Box::new(move |job| {
-            Arc::new(work::engine::VersionRolling::new(job, midstate_count))
+            Arc::new(
+                work::engine::VersionRolling::new(job, midstate_count).with_max_age(max_work_age),
+            )
         }));
+        let _ = client_handle.replace_submit_policy(self.submit_policy.clone());
         let _ = client_handle.try_disable();
         client_handle.set_event_sender(self.event_sender.clone());
 
@@ -449,13 +490,16 @@ impl GroupRegistry {
     }
 
     /// Creates a new group that handles clients connected to pools that support `midstate_count`
-    /// of midstates.
+    /// of midstates. Work from jobs older than `max_work_age` is not generated and solutions are
+    /// submitted according to `submit_policy`.
     /// TODO: once this functionality is available through the API, we should review arbitrary
     ///  recalculation of quotas
     pub fn create_group(
         &mut self,
         descriptor: GroupDescriptor,
         midstate_count: usize,
+        max_work_age: time::Duration,
+        submit_policy: job::DynSubmitPolicy,
     ) -> Result<Arc<Group>, error::Client> {
         match descriptor.strategy() {
             LoadBalanceStrategy::Quota(quota) => {
@@ -476,6 +520,8 @@ impl GroupRegistry {
             descriptor,
             self.event_monitor.publish(),
             midstate_count,
+            max_work_age,
+            submit_policy,
         ));
         let scheduler_group_handle = scheduler::GroupHandle::new(group_handle.clone());
         self.list.push(scheduler_group_handle);
@@ -498,6 +544,20 @@ impl GroupRegistry {
             .map(|scheduler_group_handle| scheduler_group_handle.group_handle.clone())
     }
 
+    /// Returns public groups with share ratio computed from their load balance strategies
+    pub fn get_share_ratios(&self) -> Vec<(Arc<Group>, f64)> {
+        self.list
+            .iter()
+            .filter(|scheduler_group_handle| !scheduler_group_handle.is_private())
+            .map(|scheduler_group_handle| {
+                (
+                    scheduler_group_handle.group_handle.clone(),
+                    scheduler_group_handle.share_ratio,
+                )
+            })
+            .collect()
+    }
+
     /// Find client which given solution is associated with
     async fn find_client(&self, solution: &work::Solution) -> Option<Arc<Handle>> {
         for scheduler_group_handle in &self.list {
@@ -548,15 +608,23 @@ pub struct Manager {
     group_registry: Arc<Mutex<GroupRegistry>>,
     event_monitor: event::Monitor,
     midstate_count: usize,
+    max_work_age: time::Duration,
+    submit_policy: job::DynSubmitPolicy,
 }
 
 impl Manager {
-    pub fn new(midstate_count: usize) -> Self {
+    pub fn new(
+        midstate_count: usize,
+        max_work_age: time::Duration,
+        submit_policy: job::DynSubmitPolicy,
+    ) -> Self {
         let event_monitor = event::Monitor::new();
         Self {
             group_registry: Arc::new(Mutex::new(GroupRegistry::new(event_monitor.clone()))),
             event_monitor,
             midstate_count,
+            max_work_age,
+            submit_policy,
         }
     }
 
@@ -578,7 +646,10 @@ impl Manager {
                             pool_config.url.as_str(),
                             &ClientUserInfo::new(
                                 pool_config.user.as_str(),
-                                pool_config.password.as_deref(),
+                                pool_config
+                                    .password
+                                    .as_ref()
+                                    .map(|password| password.expose().as_str()),
                             ),
                             pool_config.enabled.unwrap_or(default_pool_enabled),
                         )
@@ -602,10 +673,12 @@ impl Manager {
         &self,
         descriptor: GroupDescriptor,
     ) -> Result<Arc<Group>, error::Client> {
-        self.group_registry
-            .lock()
-            .await
-            .create_group(descriptor, self.midstate_count)
+        self.group_registry.lock().await.create_group(
+            descriptor,
+            self.midstate_count,
+            self.max_work_age,
+            self.submit_policy.clone(),
+        )
     }
 
     pub async fn create_or_get_default_group(&self) -> Arc<Group> {
@@ -613,7 +686,12 @@ impl Manager {
         match group_registry.get_group(GroupDescriptor::DEFAULT_INDEX) {
             Some(group) => group,
             None => group_registry
-                .create_group(Default::default(), self.midstate_count)
+                .create_group(
+                    Default::default(),
+                    self.midstate_count,
+                    self.max_work_age,
+                    self.submit_policy.clone(),
+                )
                 .expect("BUG: cannot create default group"),
         }
     }
@@ -630,4 +708,9 @@ impl Manager {
     pub async fn get_groups(&self) -> Vec<Arc<Group>> {
         self.group_registry.lock().await.get_groups()
     }
+
+    #[inline]
+    pub async fn get_share_ratios(&self) -> Vec<(Arc<Group>, f64)> {
+        self.group_registry.lock().await.get_share_ratios()
+    }
 }
@@ -37,7 +37,6 @@ use futures::channel::mpsc;
 use futures::lock::Mutex;
 use ii_async_compat::prelude::*;
 use ii_async_compat::select;
-use tokio::time::delay_for;
 
 use std::fmt;
 use std::sync::atomic::{AtomicUsize, Ordering};
@@ -255,22 +254,17 @@ impl Client {
         self.last_job.lock().await.as_ref().map(|job| job.clone())
     }
 
-    async fn send_job_and_wait(self: Arc<Self>, difficulty: Difficulty, index: &mut u64) {
+    async fn send_job(self: Arc<Self>, difficulty: Difficulty, index: &mut u64) {
         let job = Arc::new(Job::new(self.clone(), difficulty, *index));
         *index += 1;
 
         self.update_last_job(job.clone()).await;
         self.job_sender.lock().await.send(job);
-
-        delay_for(Self::NEW_JOB_INTERVAL).await;
     }
 
     async fn account_solution(&self, solution: work::Solution) {
         let now = std::time::Instant::now();
-        self.stats
-            .accepted
-            .account_solution(&solution.job_target(), now)
-            .await;
+        stats::account_accepted(&solution.path(), solution.job_target(), now).await;
     }
 
     async fn main_loop(self: Arc<Self>) -> error::Result<()> {
@@ -279,10 +273,14 @@ impl Client {
         let difficulty: Difficulty = Default::default();
         let mut regulator = DifficultyRegulator::new(self.clone(), difficulty.clone()).await;
         let mut index = 0;
+        let mut new_job_interval = runtime::Interval::new(Self::NEW_JOB_INTERVAL);
 
+        self.clone().send_job(difficulty.clone(), &mut index).await;
         while !self.status.is_shutting_down() {
             select! {
-                _ = self.clone().send_job_and_wait(difficulty.clone(), &mut index).fuse() => {}
+                _ = new_job_interval.tick().fuse() => {
+                    self.clone().send_job(difficulty.clone(), &mut index).await;
+                }
                 solution = solution_receiver.receive().fuse() => {
                     match solution {
                         Some(solution) => self.account_solution(solution).await,
@@ -329,7 +327,7 @@ impl Client {
 #[async_trait]
 impl node::Client for Client {
     fn start(self: Arc<Self>) {
-        tokio::spawn(self.clone().main_task());
+        runtime::spawn(self.clone().main_task());
     }
 
     fn stop(&self) {
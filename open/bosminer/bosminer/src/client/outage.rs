@@ -0,0 +1,205 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Riding out short outages of the connection to the server
+//!
+//! Mining on the last job continues for a limited time after the connection has been lost.
+//! Solutions found in the meantime are queued and submitted when the connection is restored and
+//! the server sends a job with the same block header template. Otherwise they are discarded as
+//! stale.
+
+use ii_logging::macros::*;
+
+use crate::job;
+use crate::node;
+use crate::work;
+
+use ii_bitcoin::{HashTrait, MeetsTarget};
+use ii_stratum::v2::messages::SetNewPrevHash;
+
+use async_trait::async_trait;
+use futures::lock::Mutex;
+use ii_async_compat::{futures, runtime};
+
+use std::sync::Arc;
+use std::time;
+
+/// Maximal time of mining on the last job after the connection has been lost
+const GRACE_PERIOD: time::Duration = time::Duration::from_secs(60);
+
+/// Maximal number of solutions queued during connection outage
+const MAX_SOLUTIONS: usize = 1024;
+
+#[derive(Debug)]
+struct Inner {
+    since: time::Instant,
+    solutions: Vec<work::Solution>,
+}
+
+/// State of connection outage of a client
+#[derive(Debug, Default)]
+pub struct Outage {
+    inner: Mutex<Option<Inner>>,
+}
+
+impl Outage {
+    pub async fn is_active(&self) -> bool {
+        self.inner.lock().await.is_some()
+    }
+}
+
+/// Client which can keep mining during its connection outage
+#[async_trait]
+pub trait Client: node::Client + 'static {
+    fn outage(&self) -> &Outage;
+
+    /// Stops solving of all jobs received over the lost connection
+    async fn invalidate_jobs(&self);
+}
+
+/// Solutions of jobs with the same block header template can be submitted for the other one
+fn has_same_template<J: job::Bitcoin>(job: &J, other: &J) -> bool {
+    job.version() == other.version()
+        && job.previous_hash() == other.previous_hash()
+        && job.merkle_root() == other.merkle_root()
+        && job.bits() == other.bits()
+}
+
+async fn account_stale_solution<C: Client>(client: &C, solution: &work::Solution) {
+    client
+        .client_stats()
+        .stale()
+        .account_solution(solution.job_target(), solution.timestamp())
+        .await;
+}
+
+/// Starts connection outage of `client` when there is a valid job which can be worked on.
+/// Returns `false` when there is no such job.
+pub async fn begin<C: Client>(client: Arc<C>) -> bool {
+    let mut outage = client.outage().inner.lock().await;
+    if outage.is_some() {
+        return true;
+    }
+    match client.get_last_job().await {
+        Some(job) if job.is_valid() => {}
+        _ => return false,
+    }
+
+    warn!(
+        "Stratum: connection to {} lost, mining the last job for up to {:?}",
+        client, GRACE_PERIOD
+    );
+    let since = time::Instant::now();
+    outage.replace(Inner {
+        since,
+        solutions: vec![],
+    });
+    runtime::spawn(timeout_task(client.clone(), since));
+    true
+}
+
+async fn timeout_task<C: Client>(client: Arc<C>, since: time::Instant) {
+    runtime::delay_for(GRACE_PERIOD).await;
+    {
+        let outage = client.outage().inner.lock().await;
+        // Ignore the timeout when the outage has finished in the meantime
+        if outage.as_ref().map(|outage| outage.since) != Some(since) {
+            return;
+        }
+    }
+    warn!(
+        "Stratum: connection to {} has not been restored, discarding the last job",
+        client
+    );
+    // Jobs from the lost connection cannot be used anymore
+    client.invalidate_jobs().await;
+    discard(&*client).await;
+}
+
+/// Finishes connection outage and discards all queued solutions as stale
+pub async fn discard<C: Client>(client: &C) {
+    let outage = client.outage().inner.lock().await.take();
+    if let Some(outage) = outage {
+        for solution in outage.solutions.iter() {
+            account_stale_solution(client, solution).await;
+        }
+    }
+}
+
+/// Checks whether the server has only repeated the prevhash of the last job after the
+/// connection has been restored. The solutions queued during outage are then still valid.
+pub async fn resumes<C: Client>(client: &C, prevhash_msg: &SetNewPrevHash) -> bool {
+    if !client.outage().is_active().await {
+        return false;
+    }
+    client.get_last_job().await.map_or(false, |job| {
+        ii_bitcoin::DHash::from_slice(prevhash_msg.prev_hash.as_ref()).ok()
+            == Some(*job.previous_hash())
+    })
+}
+
+/// Queues `solution` when the connection has been lost during its solving. Otherwise the
+/// solution is returned back to be submitted.
+pub async fn queue<C: Client>(client: &C, solution: work::Solution) -> Option<work::Solution> {
+    let mut outage = client.outage().inner.lock().await;
+    let outage = match outage.as_mut() {
+        Some(outage) => outage,
+        None => return Some(solution),
+    };
+    if outage.solutions.len() < MAX_SOLUTIONS {
+        outage.solutions.push(solution);
+    } else {
+        account_stale_solution(client, &solution).await;
+    }
+    None
+}
+
+/// Finishes connection outage when the first `job` is received after reconnection. Returns
+/// queued solutions which have been found for a job with the same template. It means that the
+/// server has resumed previous session and the solutions are still valid. Job IDs are valid only
+/// within the connection so the solutions have to be submitted for the new `job`.
+pub async fn finish<C, J>(client: &C, job: &J) -> Vec<work::Solution>
+where
+    C: Client,
+    J: job::Bitcoin,
+{
+    let outage = match client.outage().inner.lock().await.take() {
+        Some(outage) => outage,
+        None => return vec![],
+    };
+    info!(
+        "Stratum: connection to {} restored after {:?}, {} solution(s) queued",
+        client,
+        outage.since.elapsed(),
+        outage.solutions.len()
+    );
+    let mut solutions = vec![];
+    for solution in outage.solutions {
+        let queued_job: &J = solution.job();
+        if has_same_template(queued_job, job) && solution.hash().meets(&job.target()) {
+            solutions.push(solution);
+        } else {
+            account_stale_solution(client, &solution).await;
+        }
+    }
+    solutions
+}
@@ -24,13 +24,19 @@ use crate::client;
 use crate::sync::event;
 use crate::work;
 
-use futures::channel::mpsc;
 use futures::lock::{Mutex, MutexGuard};
 use ii_async_compat::{futures, FutureExt};
 
 use std::sync::Arc;
 use std::time;
 
+/// Running client is considered degraded when this percentile of recent share submission
+/// round-trip times exceeds `MAX_SUBMIT_LATENCY`
+const DEGRADED_LATENCY_PERCENTILE: f64 = 90.0;
+const MAX_SUBMIT_LATENCY: time::Duration = time::Duration::from_secs(10);
+/// Minimal number of recent samples needed for latency evaluation
+const MIN_LATENCY_SAMPLES: usize = 10;
+
 /// This struct cannot be shared and it is possible to use mutable references. However, the
 /// client handle is shared object with interior mutability scheduler::ClientHandle. It solves
 /// many synchronization problems.
@@ -38,6 +44,8 @@ use std::time;
 pub struct ClientHandle {
     pub client_handle: Arc<client::Handle>,
     last_generated_work: u64,
+    /// The time when the client has been seen running for the first time since its last failure
+    running_since: Option<time::Instant>,
 }
 
 impl ClientHandle {
@@ -45,6 +53,23 @@ impl ClientHandle {
         Self {
             last_generated_work: Self::get_generated_work(&client_handle),
             client_handle,
+            running_since: None,
+        }
+    }
+
+    fn update_running_since(&mut self, now: time::Instant) {
+        if self.is_running() {
+            self.running_since.get_or_insert(now);
+        } else {
+            self.running_since = None;
+        }
+    }
+
+    /// Checks whether the client has been running for at least `delay`
+    fn is_stable(&self, now: time::Instant, delay: time::Duration) -> bool {
+        match self.running_since {
+            Some(running_since) => now.saturating_duration_since(running_since) >= delay,
+            None => false,
         }
     }
 
@@ -74,6 +99,23 @@ impl ClientHandle {
         }
     }
 
+    /// Checks whether the pool responds to share submissions too slowly
+    async fn is_degraded(&self) -> bool {
+        let submit_latency = self
+            .client_handle
+            .stats()
+            .submit_latency()
+            .take_snapshot()
+            .await;
+        if submit_latency.recent_samples() < MIN_LATENCY_SAMPLES {
+            return false;
+        }
+        match submit_latency.percentile(DEGRADED_LATENCY_PERCENTILE) {
+            Some(latency) => latency > MAX_SUBMIT_LATENCY,
+            None => false,
+        }
+    }
+
     fn get_generated_work(client_handle: &Arc<client::Handle>) -> u64 {
         *client_handle
             .node
@@ -146,14 +188,31 @@ impl GroupHandle {
     async fn update_status(&mut self) {
         let mut scheduler_client_handles = self.group_handle.scheduler_client_handles.lock().await;
         let mut generated_work_delta = 0;
+        let mut recovering_client = None;
+        let mut degraded_client = None;
 
-        self.active_client = None;
+        let now = time::Instant::now();
+        let fail_back_delay = self.group_handle.descriptor.fail_back_delay();
+        let previous_client = self.active_client.take();
         for scheduler_client_handle in scheduler_client_handles.iter_mut() {
             generated_work_delta += scheduler_client_handle.get_delta_and_update_generated_work();
+            scheduler_client_handle.update_running_since(now);
             match self.active_client {
                 None => {
                     if scheduler_client_handle.is_running() {
-                        self.active_client = Some(scheduler_client_handle.client_handle.clone());
+                        let client_handle = &scheduler_client_handle.client_handle;
+                        if scheduler_client_handle.is_degraded().await {
+                            // keep the connection but prefer following client with lower latency
+                            degraded_client.get_or_insert_with(|| client_handle.clone());
+                        } else if previous_client.as_ref() == Some(client_handle)
+                            || scheduler_client_handle.is_stable(now, fail_back_delay)
+                        {
+                            self.active_client = Some(client_handle.clone());
+                        } else {
+                            // do not fail back to the recovered client until it is stable
+                            // when the previous client is still working
+                            recovering_client.get_or_insert_with(|| client_handle.clone());
+                        }
                     } else {
                         let _ = scheduler_client_handle.try_start();
                     }
@@ -163,6 +222,10 @@ impl GroupHandle {
                 }
             }
         }
+        // use recovering or degraded client only when there is no better one
+        if self.active_client.is_none() {
+            self.active_client = recovering_client.or(degraded_client);
+        }
 
         self.generated_work += generated_work_delta;
     }
@@ -350,7 +413,7 @@ impl JobExecutor {
     pub async fn get_solution_sender(
         &self,
         solution: &work::Solution,
-    ) -> Option<mpsc::UnboundedSender<work::Solution>> {
+    ) -> Option<work::SolutionQueueSender> {
         let active_client = self.active_client().await;
 
         // solution receiver is probably active client which is work generated from
@@ -396,3 +459,66 @@ impl JobExecutor {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::hal;
+    use crate::test_utils::simulation::{Miner, Protocol, ScriptedPool};
+
+    use bosminer_config::GroupDescriptor;
+    use ii_async_compat::{runtime, tokio};
+
+    /// Maximal time of each phase of the fail-over scenario
+    const PHASE_TIMEOUT: time::Duration = time::Duration::from_secs(20);
+
+    /// Waits for another share accepted by `pool`
+    async fn wait_for_next_share(pool: &ScriptedPool) -> bool {
+        pool.wait_for_shares(pool.accepted_count() + 1, PHASE_TIMEOUT)
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_fail_back() {
+        let primary = ScriptedPool::start(Protocol::StratumV2, 1, usize::MAX);
+        let backup = ScriptedPool::start(Protocol::StratumV2, 1, usize::MAX);
+
+        let miner = Miner::start(hal::sim::Config::default()).await;
+        let group = miner
+            .core
+            .get_client_manager()
+            .create_group(GroupDescriptor::default().with_fail_back_delay(1))
+            .await
+            .expect("BUG: cannot create group");
+        let primary_client = miner.connect(&group, &primary).await;
+        let backup_client = miner.connect(&group, &backup).await;
+
+        assert!(
+            wait_for_next_share(&primary).await,
+            "primary pool has not received any share"
+        );
+        assert_eq!(backup.accepted_count(), 0);
+
+        primary.stop();
+        assert!(
+            wait_for_next_share(&backup).await,
+            "work has not failed over to backup pool"
+        );
+        assert!(!primary_client.is_running());
+
+        primary.restart();
+        assert!(
+            wait_for_next_share(&primary).await,
+            "work has not failed back to recovered primary pool"
+        );
+        // the backup client is stopped once the primary one is stable again
+        let started = time::Instant::now();
+        while backup_client.is_running() {
+            assert!(
+                started.elapsed() < PHASE_TIMEOUT,
+                "backup client has not been stopped"
+            );
+            runtime::delay_for(time::Duration::from_millis(10)).await;
+        }
+    }
+}
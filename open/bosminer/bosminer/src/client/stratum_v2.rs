@@ -33,7 +33,9 @@ use crate::stats;
 use crate::sync;
 use crate::work;
 
-use failure::ResultExt;
+use super::outage;
+
+use crate::error::ResultExt;
 
 use ii_bitcoin::HashTrait;
 
@@ -49,6 +51,7 @@ use ii_async_compat::select;
 use std::collections::VecDeque;
 use std::fmt;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
 use std::sync::Mutex as StdMutex;
 use std::sync::{Arc, Weak};
 use std::time;
@@ -68,9 +71,6 @@ use ii_stratum::v2::{build_message_from_frame, extensions, Handler};
 
 use std::collections::HashMap;
 
-// TODO: move it to the stratum crate
-const VERSION_MASK: u32 = 0x1fffe000;
-
 #[derive(Debug, Clone)]
 pub struct ConnectionDetails {
     /// TODO temporary field that denotes the protocol, it will be replaced by a `Connector`
@@ -107,6 +107,14 @@ pub struct StratumJob {
     time: u32,
     bits: u32,
     target: ii_bitcoin::Target,
+    /// Version bits negotiated on the connection the job has been received on
+    version_mask: u32,
+    /// Generation of prevhash the job has been built on (see `StratumClient::prevhash_generation`)
+    prevhash_generation: usize,
+    /// Connection the job has been received on (see `StratumClient::connection_generation`)
+    connection_generation: usize,
+    /// Receipt of the job for measurement of job latency
+    receipt: job::Receipt,
 }
 
 impl StratumJob {
@@ -115,6 +123,7 @@ impl StratumJob {
         job_msg: &NewMiningJob,
         prevhash_msg: &SetNewPrevHash,
         target: ii_bitcoin::Target,
+        received: time::Instant,
     ) -> Self {
         Self {
             client: Arc::downgrade(&client),
@@ -128,6 +137,10 @@ impl StratumJob {
             time: prevhash_msg.min_ntime,
             bits: prevhash_msg.nbits,
             target,
+            version_mask: client.version_mask.load(Ordering::Relaxed),
+            prevhash_generation: client.prevhash_generation.load(Ordering::Relaxed),
+            connection_generation: client.connection_generation.load(Ordering::Relaxed),
+            receipt: job::Receipt::new(received),
         }
     }
 }
@@ -142,7 +155,7 @@ impl job::Bitcoin for StratumJob {
     }
 
     fn version_mask(&self) -> u32 {
-        VERSION_MASK
+        self.version_mask
     }
 
     fn previous_hash(&self) -> &ii_bitcoin::DHash {
@@ -166,11 +179,14 @@ impl job::Bitcoin for StratumJob {
     }
 
     fn is_valid(&self) -> bool {
-        // TODO: currently there is no easy way to detect the job is valid -> we have to check
-        //  its presence in the registry. The inequality below was possible in the previous
-        //  iteration of the protocol
-        // self.block_height >= self.current_block_height.load(Ordering::Relaxed)
-        true
+        // The job is invalidated by every new prevhash (`clean_jobs` in V1 is translated to it)
+        self.client.upgrade().map_or(false, |client| {
+            client.prevhash_generation.load(Ordering::Relaxed) == self.prevhash_generation
+        })
+    }
+
+    fn receipt(&self) -> Option<&job::Receipt> {
+        Some(&self.receipt)
     }
 }
 
@@ -178,7 +194,7 @@ impl job::Bitcoin for StratumJob {
 /// to keep the sequence number monotonic so that we as a stratum V2 client can easily process bulk
 /// acknowledgements. The sequence number type has been selected as u32 to match
 /// up with the protocol.
-type SolutionQueue = Mutex<VecDeque<(work::Solution, u32)>>;
+type SolutionQueue = Mutex<VecDeque<(work::Solution, u32, time::Instant)>>;
 
 /// Helper task for `StratumClient` that implements Stratum V2 visitor which processes incoming
 /// messages from remote server.
@@ -188,6 +204,8 @@ struct StratumEventHandler {
     current_prevhash_msg: Option<SetNewPrevHash>,
     /// Mining target for the next job that is to be solved
     current_target: ii_bitcoin::Target,
+    /// The last job sent for solving which has not been processed by the main loop yet
+    new_job: Option<Arc<StratumJob>>,
 }
 
 impl StratumEventHandler {
@@ -197,13 +215,15 @@ impl StratumEventHandler {
             all_jobs: Default::default(),
             current_prevhash_msg: None,
             current_target,
+            new_job: None,
         }
     }
 
     /// Convert new mining job message into StratumJob and send it down the line for solving.
     ///
     /// * `job_msg` - job message used as a base for the StratumJob
-    async fn update_job(&mut self, job_msg: &NewMiningJob) {
+    /// * `received` - time when the message which triggered the job update has been received
+    async fn update_job(&mut self, job_msg: &NewMiningJob, received: time::Instant) {
         let job = Arc::new(StratumJob::new(
             self.client.clone(),
             job_msg,
@@ -211,9 +231,11 @@ impl StratumEventHandler {
                 .as_ref()
                 .expect("TODO: no prevhash"),
             self.current_target,
+            received,
         ));
         self.client.update_last_job(job.clone()).await;
-        self.client.job_sender.lock().await.send(job);
+        self.client.job_sender.lock().await.send(job.clone());
+        self.new_job.replace(job);
     }
 
     fn update_target(&mut self, value: Uint256Bytes) {
@@ -228,17 +250,20 @@ impl StratumEventHandler {
 
     async fn process_accepted_shares(&self, success_msg: &SubmitSharesSuccess) {
         let now = std::time::Instant::now();
-        while let Some((solution, seq_num)) = self.client.solutions.lock().await.pop_front() {
+        while let Some((solution, seq_num, submit_time)) =
+            self.client.solutions.lock().await.pop_front()
+        {
+            self.client
+                .client_stats
+                .submit_latency
+                .account(now.saturating_duration_since(submit_time), now)
+                .await;
             info!(
                 "Stratum: accepted solution #{} with nonce={:08x}",
                 seq_num,
                 solution.nonce()
             );
-            self.client
-                .client_stats
-                .accepted
-                .account_solution(&solution.job_target(), now)
-                .await;
+            stats::account_accepted(&solution.path(), solution.job_target(), now).await;
             if success_msg.last_seq_num == seq_num {
                 // all accepted solutions have been found
                 return;
@@ -252,18 +277,21 @@ impl StratumEventHandler {
 
     async fn process_rejected_shares(&self, error_msg: &SubmitSharesError) {
         let now = std::time::Instant::now();
-        while let Some((solution, seq_num)) = self.client.solutions.lock().await.pop_front() {
+        while let Some((solution, seq_num, submit_time)) =
+            self.client.solutions.lock().await.pop_front()
+        {
+            self.client
+                .client_stats
+                .submit_latency
+                .account(now.saturating_duration_since(submit_time), now)
+                .await;
             if error_msg.seq_num == seq_num {
                 info!(
                     "Stratum: rejected solution #{} with nonce={:08x}!",
                     seq_num,
                     solution.nonce()
                 );
-                self.client
-                    .client_stats
-                    .rejected
-                    .account_solution(&solution.job_target(), now)
-                    .await;
+                stats::account_rejected(&solution.path(), solution.job_target(), now).await;
                 // the rejected solution has been found
                 return;
             } else {
@@ -274,11 +302,7 @@ impl StratumEventHandler {
                     seq_num,
                     solution.nonce()
                 );
-                self.client
-                    .client_stats
-                    .accepted
-                    .account_solution(&solution.job_target(), now)
-                    .await;
+                stats::account_accepted(&solution.path(), solution.job_target(), now).await;
                 warn!(
                     "Stratum: the solution #{} precedes rejected solution #{}!",
                     seq_num, error_msg.seq_num
@@ -308,6 +332,7 @@ impl Handler for StratumEventHandler {
     //      - flush all other jobs
 
     async fn visit_new_mining_job(&mut self, _header: &Header, job_msg: &NewMiningJob) {
+        let received = time::Instant::now();
         // all jobs since last `prevmsg` have to be stored in job table
         self.all_jobs.insert(job_msg.job_id, job_msg.clone());
         // TODO: close connection when maximal capacity of `all_jobs` has been reached
@@ -320,12 +345,19 @@ impl Handler for StratumEventHandler {
         //  as it should prevented typically on the V2->V1->upstream translation proxies. These
         //  proxies should guarantee that no such case like a job without a prevhash would exist.
         if !job_msg.future_job && self.current_prevhash_msg.is_some() {
-            self.update_job(job_msg).await;
+            self.update_job(job_msg, received).await;
         }
     }
 
     async fn visit_set_new_prev_hash(&mut self, _header: &Header, prevhash_msg: &SetNewPrevHash) {
+        let received = time::Instant::now();
         self.current_prevhash_msg.replace(prevhash_msg.clone());
+        // invalidate all jobs built on the previous prevhash
+        if !outage::resumes(&*self.client, prevhash_msg).await {
+            self.client
+                .prevhash_generation
+                .fetch_add(1, Ordering::Relaxed);
+        }
 
         // find the future job with ID referenced in prevhash_msg
         let (_, mut future_job_msg) = self
@@ -342,7 +374,7 @@ impl Handler for StratumEventHandler {
             .insert(future_job_msg.job_id, future_job_msg.clone());
 
         // and start immediately solving it
-        self.update_job(&future_job_msg).await;
+        self.update_job(&future_job_msg, received).await;
     }
 
     async fn visit_set_target(&mut self, _header: &Header, target_msg: &SetTarget) {
@@ -425,14 +457,34 @@ where
 
     async fn process_solution(&mut self, solution: work::Solution) -> error::Result<()> {
         let job: &StratumJob = solution.job();
+        // Job IDs are valid only within the connection, the solution would be submitted for
+        // a different job with the same ID
+        if job.connection_generation != self.client.connection_generation.load(Ordering::Relaxed) {
+            info!(
+                "Dropping solution of job {} from previous connection",
+                job.id
+            );
+            return Ok(());
+        }
+        let (channel_id, job_id) = (job.channel_id, job.id);
+        self.submit_solution(solution, channel_id, job_id).await
+    }
 
+    /// Submits `solution` as a share of job `job_id` which can differ from the original job
+    /// of the solution (e.g. when the job has been sent again after reconnection)
+    async fn submit_solution(
+        &mut self,
+        solution: work::Solution,
+        channel_id: u32,
+        job_id: u32,
+    ) -> error::Result<()> {
         let seq_num = self.seq_num;
         self.seq_num = self.seq_num.wrapping_add(1);
 
         let share_msg = SubmitSharesStandard {
-            channel_id: job.channel_id,
+            channel_id,
             seq_num,
-            job_id: job.id,
+            job_id,
             nonce: solution.nonce(),
             ntime: solution.time(),
             version: solution.version(),
@@ -442,7 +494,7 @@ where
             .solutions
             .lock()
             .await
-            .push_back((solution, seq_num));
+            .push_back((solution, seq_num, time::Instant::now()));
         // send solutions back to the stratum server
         StratumClient::send_msg(&self.connection_tx, share_msg)
             .await
@@ -595,8 +647,11 @@ impl Handler for StratumConnectionHandler {
     async fn visit_setup_connection_success(
         &mut self,
         _header: &Header,
-        _success_msg: &SetupConnectionSuccess,
+        success_msg: &SetupConnectionSuccess,
     ) {
+        self.client
+            .version_mask
+            .store(success_msg.version_mask(), Ordering::Relaxed);
         self.status = Ok(()).into();
     }
 
@@ -662,7 +717,14 @@ pub struct StratumClient {
     // Last job has to be weak reference to prevent circular reference (the `StratumJob` keeps
     // reference to `StratumClient`)
     last_job: Mutex<Option<Arc<StratumJob>>>,
+    /// Version bits which may be rolled as negotiated by `SetupConnection` of the last connection
+    version_mask: AtomicU32,
+    /// Incremented with every `SetNewPrevHash` message which invalidates all previous jobs
+    prevhash_generation: AtomicUsize,
+    /// Incremented with every new connection to the server
+    connection_generation: AtomicUsize,
     solutions: SolutionQueue,
+    outage: outage::Outage,
     job_sender: Mutex<job::Sender>,
     solution_receiver: Mutex<job::SolutionReceiver>,
     /// Frames received from this channel will be forwarded to the network connection
@@ -690,7 +752,7 @@ impl StratumClient {
         // Dummy channel for Stratum client <-- extension communication
         let (sender_to_client, receiver_to_client) = mpsc::channel(1);
 
-        tokio::spawn(async move {
+        runtime::spawn(async move {
             info!(
                 "Stratum extension: starting dummy task[{:?}]... ",
                 connection_details
@@ -744,7 +806,11 @@ impl StratumClient {
             stop_sender: stop_sender,
             stop_receiver: Mutex::new(stop_receiver),
             last_job: Mutex::new(None),
+            version_mask: AtomicU32::new(0),
+            prevhash_generation: AtomicUsize::new(0),
+            connection_generation: AtomicUsize::new(0),
             solutions: Mutex::new(VecDeque::new()),
+            outage: Default::default(),
             job_sender: Mutex::new(solver.job_sender),
             solution_receiver: Mutex::new(solver.solution_receiver),
             extension_channel_receiver: Mutex::new(extension_channel_receiver),
@@ -856,7 +922,16 @@ impl StratumClient {
             select! {
                 frame = connection_rx.next().timeout(Self::EVENT_TIMEOUT).fuse() => {
                     match frame {
-                        Ok(Some(frame)) => self.handle_frame(frame?, &mut event_handler).await?,
+                        Ok(Some(frame)) => {
+                            self.handle_frame(frame?, &mut event_handler).await?;
+                            if let Some(job) = event_handler.new_job.take() {
+                                for solution in outage::finish(&*self, &*job).await {
+                                    solution_handler
+                                        .submit_solution(solution, job.channel_id, job.id)
+                                        .await?;
+                                }
+                            }
+                        }
                         Ok(None) | Err(_) => {
                             Err("The remote stratum server was disconnected prematurely")?;
                         }
@@ -870,7 +945,13 @@ impl StratumClient {
                 }
                 solution = solution_receiver.receive().fuse() => {
                     match solution {
-                        Some(solution) => solution_handler.process_solution(solution).await?,
+                        Some(solution) => {
+                            // solutions of work from the lost connection are kept until it is
+                            // known whether they can be submitted
+                            if let Some(solution) = outage::queue(&*self, solution).await {
+                                solution_handler.process_solution(solution).await?;
+                            }
+                        }
                         None => {
                             // TODO: initiate Destroying and remove error
                             Err("Standard application shutdown")?;
@@ -904,6 +985,7 @@ impl StratumClient {
     }
 
     async fn run(self: Arc<Self>) {
+        self.connection_generation.fetch_add(1, Ordering::Relaxed);
         let connection_handler = StratumConnectionHandler::new(self.clone());
         let connection_details = connection_handler.client.connection_details();
         let host_and_port = connection_details.get_host_and_port();
@@ -955,8 +1037,10 @@ impl StratumClient {
 
     async fn main_task(self: Arc<Self>) {
         // TODO: Count as a discarded solution?
-        // Flush all obsolete solutions from previous run
-        self.solution_receiver.lock().await.flush();
+        // Flush all obsolete solutions from previous run unless they can still be submitted
+        if !self.outage.is_active().await {
+            self.solution_receiver.lock().await.flush();
+        }
 
         loop {
             let mut stop_receiver = self.stop_receiver.lock().await;
@@ -982,11 +1066,19 @@ impl StratumClient {
                     e
                 );
             }
-            // Invalidate current job to stop working on it
-            self.job_sender.lock().await.invalidate();
-            // Flush all unprocessed solutions to empty buffer
-            // TODO: Count as a discarded solution?
-            self.solution_receiver.lock().await.flush();
+            // Keep working on current job for a while when only the connection has been lost
+            let connection_lost = matches!(
+                self.status.status(),
+                sync::Status::Failing | sync::Status::Declining | sync::Status::Recovering
+            );
+            if !connection_lost || !outage::begin(self.clone()).await {
+                // Invalidate current job to stop working on it
+                self.job_sender.lock().await.invalidate();
+                // Flush all unprocessed solutions to empty buffer
+                // TODO: Count as a discarded solution?
+                self.solution_receiver.lock().await.flush();
+                outage::discard(&*self).await;
+            }
             self.solutions.lock().await.clear();
 
             if self.status.can_stop() {
@@ -999,10 +1091,22 @@ impl StratumClient {
     }
 }
 
+#[async_trait]
+impl outage::Client for StratumClient {
+    fn outage(&self) -> &outage::Outage {
+        &self.outage
+    }
+
+    async fn invalidate_jobs(&self) {
+        self.prevhash_generation.fetch_add(1, Ordering::Relaxed);
+        self.job_sender.lock().await.invalidate();
+    }
+}
+
 #[async_trait]
 impl node::Client for StratumClient {
     fn start(self: Arc<Self>) {
-        tokio::spawn(self.clone().main_task());
+        runtime::spawn(self.clone().main_task());
     }
 
     fn stop(&self) {
@@ -201,7 +201,12 @@ impl Client {
             _ => {
                 let err_msg = "Cannot start telemetry client";
                 self.log_error(err_msg);
-                Err(error::ErrorKind::Stratum(err_msg.to_string()).into())
+                Err(
+                    error::ErrorKind::Stratum(ii_stratum::error::ErrorKind::General(
+                        err_msg.to_string(),
+                    ))
+                    .into(),
+                )
             }
         }
     }
@@ -218,7 +223,6 @@ impl Client {
         self.stratum_sender
             .try_send(frame)
             .context("submit message")
-            .map_err(Into::into)
     }
 
     /// Helper that logs about an error appending the current telemetry state
@@ -31,7 +31,9 @@ use crate::stats;
 use crate::sync;
 use crate::work;
 
-use failure::ResultExt;
+use super::outage;
+
+use crate::error::ResultExt;
 
 use ii_bitcoin::HashTrait;
 
@@ -47,6 +49,7 @@ use ii_async_compat::select;
 use std::collections::VecDeque;
 use std::fmt;
 use std::net::ToSocketAddrs;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
 use std::sync::{Arc, Weak};
 use std::time;
 
@@ -62,12 +65,37 @@ use ii_stratum::v2::types::*;
 use ii_stratum::v2::{build_message_from_frame, Handler};
 use ii_stratum::{v1, v2};
 use ii_stratum_proxy::translation::{V2ToV1Translation, V2ToV1TranslationOptions};
-use ii_wire::Connection;
+use runtime::net::TcpStream;
 
 use std::collections::HashMap;
 
-// TODO: move it to the stratum crate
-const VERSION_MASK: u32 = 0x1fffe000;
+/// Upstream V1 stream over plain TCP or TLS
+trait V1Stream: AsyncRead + AsyncWrite + Send + Unpin {}
+
+impl<T: AsyncRead + AsyncWrite + Send + Unpin> V1Stream for T {}
+
+type V1Framed = tokio_util::codec::Framed<Box<dyn V1Stream>, v1::Codec>;
+
+#[cfg(feature = "stratum-tls")]
+async fn connect_tls(host: &str, stream: TcpStream) -> error::Result<Box<dyn V1Stream>> {
+    use tokio_rustls::{rustls, webpki, TlsConnector};
+
+    let mut tls_config = rustls::ClientConfig::new();
+    tls_config
+        .root_store
+        .add_server_trust_anchors(&webpki_roots::TLS_SERVER_ROOTS);
+    let domain = webpki::DNSNameRef::try_from_ascii_str(host)
+        .map_err(|_| format!("invalid stratum server domain '{}'", host))?;
+    let stream = TlsConnector::from(Arc::new(tls_config))
+        .connect(domain, stream)
+        .await?;
+    Ok(Box::new(stream))
+}
+
+#[cfg(not(feature = "stratum-tls"))]
+async fn connect_tls(_host: &str, _stream: TcpStream) -> error::Result<Box<dyn V1Stream>> {
+    Err("TLS is not supported by this build (missing 'stratum-tls' feature)")?
+}
 
 #[derive(Debug)]
 pub struct ConnectionDetails {
@@ -75,6 +103,8 @@ pub struct ConnectionDetails {
     pub host: String,
     pub port: u16,
     pub fragment: Option<String>,
+    /// Connection to the server is secured by TLS
+    pub tls: bool,
 }
 
 impl ConnectionDetails {
@@ -84,6 +114,15 @@ impl ConnectionDetails {
             host: descriptor.host.clone(),
             port: descriptor.port(),
             fragment: descriptor.fragment.clone(),
+            tls: matches!(descriptor.protocol, ClientProtocol::StratumV1Tls),
+        }
+    }
+
+    fn scheme(&self) -> &'static str {
+        if self.tls {
+            ClientProtocol::SCHEME_STRATUM_V1_TLS
+        } else {
+            ClientProtocol::SCHEME_STRATUM_V1
         }
     }
 
@@ -112,6 +151,12 @@ pub struct StratumJob {
     time: u32,
     bits: u32,
     target: ii_bitcoin::Target,
+    /// Version bits negotiated on the connection the job has been received on
+    version_mask: u32,
+    /// Generation of prevhash the job has been built on (see `StratumClient::prevhash_generation`)
+    prevhash_generation: usize,
+    /// Receipt of the job for measurement of job latency
+    receipt: job::Receipt,
 }
 
 impl StratumJob {
@@ -120,6 +165,7 @@ impl StratumJob {
         job_msg: &NewMiningJob,
         prevhash_msg: &SetNewPrevHash,
         target: ii_bitcoin::Target,
+        received: time::Instant,
     ) -> Self {
         Self {
             client: Arc::downgrade(&client),
@@ -133,6 +179,9 @@ impl StratumJob {
             time: prevhash_msg.min_ntime,
             bits: prevhash_msg.nbits,
             target,
+            version_mask: client.version_mask.load(Ordering::Relaxed),
+            prevhash_generation: client.prevhash_generation.load(Ordering::Relaxed),
+            receipt: job::Receipt::new(received),
         }
     }
 }
@@ -147,7 +196,7 @@ impl job::Bitcoin for StratumJob {
     }
 
     fn version_mask(&self) -> u32 {
-        VERSION_MASK
+        self.version_mask
     }
 
     fn previous_hash(&self) -> &ii_bitcoin::DHash {
@@ -171,11 +220,14 @@ impl job::Bitcoin for StratumJob {
     }
 
     fn is_valid(&self) -> bool {
-        // TODO: currently there is no easy way to detect the job is valid -> we have to check
-        //  its presence in the registry. The inequality below was possible in the previous
-        //  iteration of the protocol
-        // self.block_height >= self.current_block_height.load(Ordering::Relaxed)
-        true
+        // The job is invalidated by every new prevhash (`clean_jobs` in V1 is translated to it)
+        self.client.upgrade().map_or(false, |client| {
+            client.prevhash_generation.load(Ordering::Relaxed) == self.prevhash_generation
+        })
+    }
+
+    fn receipt(&self) -> Option<&job::Receipt> {
+        Some(&self.receipt)
     }
 }
 
@@ -183,7 +235,7 @@ impl job::Bitcoin for StratumJob {
 /// to keep the sequence number monotonic so that we as a stratum V2 client can easily process bulk
 /// acknowledgements. The sequence number type has been selected as u32 to match
 /// up with the protocol.
-type SolutionQueue = Mutex<VecDeque<(work::Solution, u32)>>;
+type SolutionQueue = Mutex<VecDeque<(work::Solution, u32, time::Instant)>>;
 
 /// Helper task for `StratumClient` that implements Stratum V2 visitor which processes incoming
 /// messages from remote server.
@@ -193,6 +245,8 @@ struct StratumEventHandler {
     current_prevhash_msg: Option<SetNewPrevHash>,
     /// Mining target for the next job that is to be solved
     current_target: ii_bitcoin::Target,
+    /// The last job sent for solving which has not been processed by the main loop yet
+    new_job: Option<Arc<StratumJob>>,
 }
 
 impl StratumEventHandler {
@@ -202,13 +256,15 @@ impl StratumEventHandler {
             all_jobs: Default::default(),
             current_prevhash_msg: None,
             current_target,
+            new_job: None,
         }
     }
 
     /// Convert new mining job message into StratumJob and send it down the line for solving.
     ///
     /// * `job_msg` - job message used as a base for the StratumJob
-    async fn update_job(&mut self, job_msg: &NewMiningJob) {
+    /// * `received` - time when the message which triggered the job update has been received
+    async fn update_job(&mut self, job_msg: &NewMiningJob, received: time::Instant) {
         let job = Arc::new(StratumJob::new(
             self.client.clone(),
             job_msg,
@@ -216,9 +272,11 @@ impl StratumEventHandler {
                 .as_ref()
                 .expect("TODO: no prevhash"),
             self.current_target,
+            received,
         ));
         self.client.update_last_job(job.clone()).await;
-        self.client.job_sender.lock().await.send(job);
+        self.client.job_sender.lock().await.send(job.clone());
+        self.new_job.replace(job);
     }
 
     fn update_target(&mut self, value: Uint256Bytes) {
@@ -233,17 +291,20 @@ impl StratumEventHandler {
 
     async fn process_accepted_shares(&self, success_msg: &SubmitSharesSuccess) {
         let now = std::time::Instant::now();
-        while let Some((solution, seq_num)) = self.client.solutions.lock().await.pop_front() {
+        while let Some((solution, seq_num, submit_time)) =
+            self.client.solutions.lock().await.pop_front()
+        {
+            self.client
+                .client_stats
+                .submit_latency
+                .account(now.saturating_duration_since(submit_time), now)
+                .await;
             info!(
                 "Stratum: accepted solution #{} with nonce={:08x}",
                 seq_num,
                 solution.nonce()
             );
-            self.client
-                .client_stats
-                .accepted
-                .account_solution(&solution.job_target(), now)
-                .await;
+            stats::account_accepted(&solution.path(), solution.job_target(), now).await;
             if success_msg.last_seq_num == seq_num {
                 // all accepted solutions have been found
                 return;
@@ -257,18 +318,21 @@ impl StratumEventHandler {
 
     async fn process_rejected_shares(&self, error_msg: &SubmitSharesError) {
         let now = std::time::Instant::now();
-        while let Some((solution, seq_num)) = self.client.solutions.lock().await.pop_front() {
+        while let Some((solution, seq_num, submit_time)) =
+            self.client.solutions.lock().await.pop_front()
+        {
+            self.client
+                .client_stats
+                .submit_latency
+                .account(now.saturating_duration_since(submit_time), now)
+                .await;
             if error_msg.seq_num == seq_num {
                 info!(
                     "Stratum: rejected solution #{} with nonce={:08x}!",
                     seq_num,
                     solution.nonce()
                 );
-                self.client
-                    .client_stats
-                    .rejected
-                    .account_solution(&solution.job_target(), now)
-                    .await;
+                stats::account_rejected(&solution.path(), solution.job_target(), now).await;
                 // the rejected solution has been found
                 return;
             } else {
@@ -279,11 +343,7 @@ impl StratumEventHandler {
                     seq_num,
                     solution.nonce()
                 );
-                self.client
-                    .client_stats
-                    .accepted
-                    .account_solution(&solution.job_target(), now)
-                    .await;
+                stats::account_accepted(&solution.path(), solution.job_target(), now).await;
                 warn!(
                     "Stratum: the solution #{} precedes rejected solution #{}!",
                     seq_num, error_msg.seq_num
@@ -313,6 +373,7 @@ impl Handler for StratumEventHandler {
     //      - flush all other jobs
 
     async fn visit_new_mining_job(&mut self, _header: &Header, job_msg: &NewMiningJob) {
+        let received = time::Instant::now();
         // all jobs since last `prevmsg` have to be stored in job table
         self.all_jobs.insert(job_msg.job_id, job_msg.clone());
         // TODO: close connection when maximal capacity of `all_jobs` has been reached
@@ -325,12 +386,19 @@ impl Handler for StratumEventHandler {
         // should be dealt with in proxy, but let's put the `current_prevhash_msg` existence check
         // here anyway.
         if !job_msg.future_job && self.current_prevhash_msg.is_some() {
-            self.update_job(job_msg).await;
+            self.update_job(job_msg, received).await;
         }
     }
 
     async fn visit_set_new_prev_hash(&mut self, _header: &Header, prevhash_msg: &SetNewPrevHash) {
+        let received = time::Instant::now();
         self.current_prevhash_msg.replace(prevhash_msg.clone());
+        // invalidate all jobs built on the previous prevhash
+        if !outage::resumes(&*self.client, prevhash_msg).await {
+            self.client
+                .prevhash_generation
+                .fetch_add(1, Ordering::Relaxed);
+        }
 
         // find the future job with ID referenced in prevhash_msg
         let (_, mut future_job_msg) = self
@@ -347,7 +415,7 @@ impl Handler for StratumEventHandler {
             .insert(future_job_msg.job_id, future_job_msg.clone());
 
         // and start immediately solving it
-        self.update_job(&future_job_msg).await;
+        self.update_job(&future_job_msg, received).await;
     }
 
     async fn visit_set_target(&mut self, _header: &Header, target_msg: &SetTarget) {
@@ -407,7 +475,7 @@ where
     S: FrameSink,
     //    S: Sink<<Framing as ii_wire::Framing>::Tx, Error = E> + std::marker::Unpin + std::fmt::Debug
     //    + 'static,
-    //    E: failure::Fail + std::marker::Unpin + 'static
+    //    E: std::error::Error + std::marker::Unpin + 'static
 {
     fn new(client: Arc<StratumClient>, connection_tx: S) -> Self {
         Self {
@@ -419,14 +487,25 @@ where
 
     async fn process_solution(&mut self, solution: work::Solution) -> error::Result<()> {
         let job: &StratumJob = solution.job();
+        let (channel_id, job_id) = (job.channel_id, job.id);
+        self.submit_solution(solution, channel_id, job_id).await
+    }
 
+    /// Submits `solution` as a share of job `job_id` which can differ from the original job
+    /// of the solution (e.g. when the job has been sent again after reconnection)
+    async fn submit_solution(
+        &mut self,
+        solution: work::Solution,
+        channel_id: u32,
+        job_id: u32,
+    ) -> error::Result<()> {
         let seq_num = self.seq_num;
         self.seq_num = self.seq_num.wrapping_add(1);
 
         let share_msg = SubmitSharesStandard {
-            channel_id: job.channel_id,
+            channel_id,
             seq_num,
-            job_id: job.id,
+            job_id,
             nonce: solution.nonce(),
             ntime: solution.time(),
             version: solution.version(),
@@ -436,7 +515,7 @@ where
             .solutions
             .lock()
             .await
-            .push_back((solution, seq_num));
+            .push_back((solution, seq_num, time::Instant::now()));
         // send solutions back to the stratum server
         StratumClient::send_msg(&mut self.connection_tx, share_msg)
             .await
@@ -549,10 +628,9 @@ impl StratumConnectionHandler {
             .unwrap_or(Err("Unexpected response for stratum open channel".into()))
     }
 
-    async fn connect(self) -> error::Result<v1::Framed> {
-        let socket_addr = self
-            .client
-            .connection_details
+    async fn connect(self) -> error::Result<V1Framed> {
+        let connection_details = &self.client.connection_details;
+        let socket_addr = connection_details
             .get_host_and_port()
             .to_socket_addrs()
             .context("Invalid server address")?
@@ -561,11 +639,18 @@ impl StratumConnectionHandler {
             .next()
             .ok_or("Cannot resolve any IP address")?;
 
-        let connection = Connection::<v1::Framing>::connect(&socket_addr)
+        let stream = TcpStream::connect(&socket_addr)
             .await
             .context("Cannot connect to stratum server")?;
+        let stream: Box<dyn V1Stream> = if connection_details.tls {
+            connect_tls(&connection_details.host, stream)
+                .await
+                .context("Cannot establish TLS connection to stratum server")?
+        } else {
+            Box::new(stream)
+        };
 
-        Ok(connection.into_inner())
+        Ok(tokio_util::codec::Framed::new(stream, Default::default()))
     }
 
     /// Starts mining session and provides the initial target negotiated by the upstream endpoint
@@ -594,8 +679,11 @@ impl Handler for StratumConnectionHandler {
     async fn visit_setup_connection_success(
         &mut self,
         _header: &Header,
-        _success_msg: &SetupConnectionSuccess,
+        success_msg: &SetupConnectionSuccess,
     ) {
+        self.client
+            .version_mask
+            .store(success_msg.version_mask(), Ordering::Relaxed);
         self.status = Ok(()).into();
     }
 
@@ -639,7 +727,12 @@ pub struct StratumClient {
     // Last job has to be week reference to prevent circular reference (the `StratumJob` keeps
     // reference to `StratumClient`)
     last_job: Mutex<Option<Weak<StratumJob>>>,
+    /// Version bits which may be rolled as negotiated by `SetupConnection` of the last connection
+    version_mask: AtomicU32,
+    /// Incremented with every `SetNewPrevHash` message which invalidates all previous jobs
+    prevhash_generation: AtomicUsize,
     solutions: SolutionQueue,
+    outage: outage::Outage,
     job_sender: Mutex<job::Sender>,
     solution_receiver: Mutex<job::SolutionReceiver>,
 }
@@ -658,7 +751,10 @@ impl StratumClient {
             stop_sender: stop_sender,
             stop_receiver: Mutex::new(stop_receiver),
             last_job: Mutex::new(None),
+            version_mask: AtomicU32::new(0),
+            prevhash_generation: AtomicUsize::new(0),
             solutions: Mutex::new(VecDeque::new()),
+            outage: Default::default(),
             job_sender: Mutex::new(solver.job_sender),
             solution_receiver: Mutex::new(solver.solution_receiver),
         }
@@ -701,6 +797,13 @@ impl StratumClient {
                         Ok(Some(frame)) => {
                             let event_msg = build_message_from_frame(frame)?;
                             event_msg.accept(event_handler).await;
+                            if let Some(job) = event_handler.new_job.take() {
+                                for solution in outage::finish(&*self, &*job).await {
+                                    solution_handler
+                                        .submit_solution(solution, job.channel_id, job.id)
+                                        .await?;
+                                }
+                            }
                         }
                         Ok(None) | Err(_) => {
                             Err("The remote stratum server was disconnected prematurely")?;
@@ -709,7 +812,13 @@ impl StratumClient {
                 },
                 solution = solution_receiver.receive().fuse() => {
                     match solution {
-                        Some(solution) => solution_handler.process_solution(solution).await?,
+                        Some(solution) => {
+                            // solutions of work from the lost connection are kept until it is
+                            // known whether they can be submitted
+                            if let Some(solution) = outage::queue(&*self, solution).await {
+                                solution_handler.process_solution(solution).await?;
+                            }
+                        }
                         None => {
                             // TODO: initiate Destroying and remove error
                             Err("Standard application shutdown")?;
@@ -759,7 +868,7 @@ impl StratumClient {
                     };
                     let (translation_handler, v2_translation_rx, v2_translation_tx) =
                         TranslationHandler::new(v1_framed_connection, options);
-                    tokio::spawn(async move {
+                    runtime::spawn(async move {
                         let status = translation_handler.run().await;
                         info!("V2->V1 translation terminated: {:?}", status);
                     });
@@ -774,8 +883,10 @@ impl StratumClient {
 
     async fn main_task(self: Arc<Self>) {
         // TODO: Count as a discarded solution?
-        // Flush all obsolete solutions from previous run
-        self.solution_receiver.lock().await.flush();
+        // Flush all obsolete solutions from previous run unless they can still be submitted
+        if !self.outage.is_active().await {
+            self.solution_receiver.lock().await.flush();
+        }
 
         loop {
             let mut stop_receiver = self.stop_receiver.lock().await;
@@ -784,11 +895,19 @@ impl StratumClient {
                 _ = stop_receiver.next() => {}
             }
 
-            // Invalidate current job to stop working on it
-            self.job_sender.lock().await.invalidate();
-            // Flush all unprocessed solutions to empty buffer
-            // TODO: Count as a discarded solution?
-            self.solution_receiver.lock().await.flush();
+            // Keep working on current job for a while when only the connection has been lost
+            let connection_lost = matches!(
+                self.status.status(),
+                sync::Status::Failing | sync::Status::Declining | sync::Status::Recovering
+            );
+            if !connection_lost || !outage::begin(self.clone()).await {
+                // Invalidate current job to stop working on it
+                self.job_sender.lock().await.invalidate();
+                // Flush all unprocessed solutions to empty buffer
+                // TODO: Count as a discarded solution?
+                self.solution_receiver.lock().await.flush();
+                outage::discard(&*self).await;
+            }
             self.solutions.lock().await.clear();
 
             if self.status.can_stop() {
@@ -809,7 +928,7 @@ struct TranslationHandler {
     /// Actual protocol translator
     translation: V2ToV1Translation,
     /// Upstream V1 connection
-    v1_conn: v1::Framed,
+    v1_conn: V1Framed,
     /// Receiver for V1 frames from the translator that will be sent out via V1 connection
     v1_translation_rx: mpsc::Receiver<v1::Frame>,
     /// V2 Frames from the client that we use for feeding the translator
@@ -821,7 +940,7 @@ impl TranslationHandler {
 
     /// Builds the new translation handler and provides Tx/Rx communication ends
     fn new(
-        v1_conn: v1::Framed,
+        v1_conn: V1Framed,
         options: V2ToV1TranslationOptions,
     ) -> (Self, mpsc::Receiver<v2::Frame>, mpsc::Sender<v2::Frame>) {
         let (v1_translation_tx, v1_translation_rx) =
@@ -906,10 +1025,22 @@ impl TranslationHandler {
     }
 }
 
+#[async_trait]
+impl outage::Client for StratumClient {
+    fn outage(&self) -> &outage::Outage {
+        &self.outage
+    }
+
+    async fn invalidate_jobs(&self) {
+        self.prevhash_generation.fetch_add(1, Ordering::Relaxed);
+        self.job_sender.lock().await.invalidate();
+    }
+}
+
 #[async_trait]
 impl node::Client for StratumClient {
     fn start(self: Arc<Self>) {
-        tokio::spawn(self.clone().main_task());
+        runtime::spawn(self.clone().main_task());
     }
 
     fn stop(&self) {
@@ -936,7 +1067,7 @@ impl fmt::Display for StratumClient {
         write!(
             f,
             "{}://{}@{}",
-            ClientProtocol::SCHEME_STRATUM_V1,
+            self.connection_details.scheme(),
             self.connection_details.host,
             self.connection_details.user
         )
@@ -29,7 +29,7 @@ use crate::hal::{self, BackendConfig as _};
 use crate::hub;
 use crate::stats;
 
-use ii_async_compat::tokio;
+use ii_async_compat::runtime;
 
 use std::sync::Arc;
 
@@ -37,27 +37,54 @@ pub async fn main<T: hal::Backend>(backend_config: T::Config, signature: String)
     let backend_registry = Arc::new(backend::Registry::new());
     // Get frontend specific settings from backend config
     let backend_info = backend_config.info();
+    let persistent_stats_path = backend_config.persistent_stats_path();
 
     // Initialize hub core which manages all resources
     let core = Arc::new(hub::Core::new(
         backend_config.midstate_count(),
+        backend_config.max_work_age(),
+        backend_config.submit_policy(),
         &backend_registry,
         backend_info.clone(),
     ));
 
     // Create and initialize the backend
-    let frontend_config = core
+    let mut frontend_config = core
         .build_backend::<T>(backend_config)
         .await
         .expect("Backend initialization failed");
 
-    tokio::spawn(core.clone().run());
+    runtime::spawn(core.clone().run());
     // start statistics processing
-    tokio::spawn(stats::mining_task(
+    runtime::spawn(stats::mining_task(
         core.frontend.clone(),
         T::DEFAULT_HASHRATE_INTERVAL,
     ));
+    runtime::spawn(stats::shares_task(
+        core.clone(),
+        T::DEFAULT_HASHRATE_INTERVAL,
+    ));
+    let persistent_stats = stats::persistent::spawn(core.clone(), persistent_stats_path);
+    let lifetime_stats = persistent_stats.as_ref().map(|recorder| recorder.base());
+
+    if let Some(halt_handle) = frontend_config.halt_handle.take() {
+        // Do not lose statistics gathered since the last periodic store
+        if let Some(persistent_stats) = persistent_stats {
+            halt_handle
+                .add_exit_hook(async move { persistent_stats.store().await })
+                .await;
+        }
+        // On miner exit, halt the whole program
+        halt_handle
+            .add_exit_hook(async {
+                println!("Exiting.");
+                std::process::exit(0);
+            })
+            .await;
+        // Hook `Ctrl-C`, `SIGTERM` and other termination methods
+        halt_handle.hook_termination_signals();
+    }
 
     // the bosminer is controlled with API which also controls when the miner will end
-    api::run(core, frontend_config, signature).await;
+    api::run(core, frontend_config, signature, lifetime_stats).await;
 }
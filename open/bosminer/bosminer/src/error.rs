@@ -21,6 +21,10 @@
 // contact us at opensource@braiins.com.
 
 //! The bosminer errors
+//!
+//! Every error has a stable `ErrorKind` to match on. The error it has been created from is kept
+//! as `source()`, including errors coming from the stratum library and from the backends, so
+//! that they can be traced down to the original cause.
 
 mod client;
 
@@ -28,93 +32,67 @@ pub use client::ErrorKind as Client;
 
 use ii_async_compat::prelude::*;
 
-use failure::{Backtrace, Context, Fail};
-use std::fmt::{self, Debug, Display};
-
 use std::io;
 
-pub struct Error {
-    inner: Context<ErrorKind>,
-}
+use thiserror::Error;
 
-#[derive(Clone, Eq, PartialEq, Debug, Fail)]
+#[derive(Error, Clone, Eq, PartialEq, Debug)]
 pub enum ErrorKind {
     /// Standard input/output error
-    #[fail(display = "IO error: {}", _0)]
+    #[error("IO error: {0}")]
     Io(String),
 
     /// General error used for more specific input/output error
-    #[fail(display = "General error: {}", _0)]
+    #[error("General error: {0}")]
     General(String),
 
     /// Error generated by backend for selected target
-    #[fail(display = "Backend error: {}", _0)]
+    #[error("Backend error: {0}")]
     Backend(String),
 
-    /// Error generated by backend for selected target
-    #[fail(display = "Stratum error: {}", _0)]
-    Stratum(String),
+    /// Error generated by stratum protocol implementation
+    #[error("Stratum error: {0}")]
+    Stratum(ii_stratum::error::ErrorKind),
 
     /// Error related to clients
-    #[fail(display = "Client error: {}", _0)]
+    #[error("Client error: {0}")]
     Client(Client),
 }
 
-/// Implement Fail trait instead of use Derive to get more control over custom type.
-/// The main advantage is customization of Context type which allows conversion of
-/// any error types to this custom error with general error kind by calling context
-/// method on any result type.
-impl Fail for Error {
-    fn cause(&self) -> Option<&dyn Fail> {
-        self.inner.cause()
-    }
-
-    fn backtrace(&self) -> Option<&Backtrace> {
-        self.inner.backtrace()
-    }
-}
-
-impl Display for Error {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        Display::fmt(&self.inner, f)
-    }
-}
-
-impl Debug for Error {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        Debug::fmt(&self.inner, f)
-    }
-}
+ii_error::error_type!(ErrorKind);
 
-impl Error {
-    pub fn kind(&self) -> ErrorKind {
-        self.inner.get_context().clone()
+/// Context message provided by `ResultExt::context()`
+impl From<&str> for ErrorKind {
+    fn from(info: &str) -> Self {
+        ErrorKind::General(info.to_string())
     }
 }
 
-impl From<ErrorKind> for Error {
-    fn from(kind: ErrorKind) -> Self {
-        Self {
-            inner: Context::new(kind),
-        }
+impl From<String> for ErrorKind {
+    fn from(info: String) -> Self {
+        ErrorKind::General(info)
     }
 }
 
 impl From<ii_stratum::error::Error> for Error {
     fn from(stratum: ii_stratum::error::Error) -> Self {
-        ErrorKind::Stratum(stratum.to_string()).into()
+        let (kind, source) = stratum.into_parts();
+        Self {
+            kind: ErrorKind::Stratum(kind),
+            source,
+        }
     }
 }
 
 impl From<ii_wire::AddressParseError> for Error {
     fn from(address_error: ii_wire::AddressParseError) -> Self {
-        ErrorKind::General(address_error.to_string()).into()
+        Self::with_source(ErrorKind::General(address_error.to_string()), address_error)
     }
 }
 
 impl From<ii_wire::AttemptError> for Error {
     fn from(attempt_error: ii_wire::AttemptError) -> Self {
-        ErrorKind::General(attempt_error.to_string()).into()
+        Self::with_source(ErrorKind::General(attempt_error.to_string()), attempt_error)
     }
 }
 
@@ -124,12 +102,6 @@ impl From<Client> for Error {
     }
 }
 
-impl From<Context<ErrorKind>> for Error {
-    fn from(inner: Context<ErrorKind>) -> Self {
-        Self { inner }
-    }
-}
-
 impl From<&str> for Error {
     fn from(msg: &str) -> Self {
         ErrorKind::General(msg.to_string()).into()
@@ -144,98 +116,67 @@ impl From<String> for Error {
 
 impl From<io::Error> for Error {
     fn from(e: io::Error) -> Self {
-        let msg = e.to_string();
-        Self {
-            inner: e.context(ErrorKind::Io(msg)),
-        }
+        Self::with_source(ErrorKind::Io(e.to_string()), e)
     }
 }
 
 impl From<futures::channel::mpsc::SendError> for Error {
     fn from(e: futures::channel::mpsc::SendError) -> Self {
-        let msg = e.to_string();
-        Self {
-            inner: e.context(ErrorKind::Io(msg)),
-        }
+        Self::with_source(ErrorKind::Io(e.to_string()), e)
     }
 }
 
-impl From<Context<&str>> for Error {
-    fn from(context: Context<&str>) -> Self {
-        Self {
-            inner: context.map(|info| ErrorKind::General(info.to_string())),
-        }
-    }
-}
-
-impl From<Context<String>> for Error {
-    fn from(context: Context<String>) -> Self {
-        Self {
-            inner: context.map(|info| ErrorKind::General(info)),
-        }
-    }
-}
-
-pub trait ResultExt<T, E> {
-    fn context<D>(self, context: D) -> std::result::Result<T, Context<ErrorKind>>
-    where
-        D: Display + Send + Sync + 'static;
-
-    fn with_context<F, D>(self, f: F) -> std::result::Result<T, Context<ErrorKind>>
-    where
-        F: FnOnce(&E) -> D,
-        D: Display + Send + Sync + 'static;
-}
-
+/// Conversions of backend errors, their kinds are reported as `ErrorKind::Backend`
 pub mod backend {
-    pub use super::ResultExt;
-    use super::{Error, ErrorKind};
+    use super::{Error, ErrorKind, Result};
 
-    use failure::{Context, Fail};
+    use std::error::Error as StdError;
     use std::fmt::Display;
 
-    pub fn from_error<T: Fail>(error: T) -> Error {
-        let msg = error.to_string();
-        Error {
-            inner: error.context(ErrorKind::Backend(msg)),
-        }
+    pub fn from_error<T>(error: T) -> Error
+    where
+        T: StdError + Send + Sync + 'static,
+    {
+        Error::with_source(ErrorKind::Backend(error.to_string()), error)
     }
 
     pub fn from_error_kind<T: ToString>(kind: T) -> Error {
-        Error {
-            inner: Context::new(ErrorKind::Backend(kind.to_string())),
-        }
+        ErrorKind::Backend(kind.to_string()).into()
+    }
+
+    /// Variant of `super::ResultExt` for backends where the context is any backend specific
+    /// error kind or message
+    pub trait ResultExt<T, E> {
+        fn context<D>(self, context: D) -> Result<T>
+        where
+            D: Display;
+
+        fn with_context<F, D>(self, f: F) -> Result<T>
+        where
+            F: FnOnce(&E) -> D,
+            D: Display;
     }
 
-    impl<T, E> ResultExt<T, E> for Result<T, E>
+    impl<T, E> ResultExt<T, E> for std::result::Result<T, E>
     where
-        E: Fail,
+        E: StdError + Send + Sync + 'static,
     {
-        fn context<D>(self, context: D) -> Result<T, Context<ErrorKind>>
+        fn context<D>(self, context: D) -> Result<T>
         where
-            D: Display + Send + Sync + 'static,
+            D: Display,
         {
-            self.map_err(|failure| {
-                failure
-                    .context(context)
-                    .map(|info| ErrorKind::Backend(info.to_string()))
-            })
+            self.map_err(|e| Error::with_source(ErrorKind::Backend(context.to_string()), e))
         }
 
-        fn with_context<F, D>(self, f: F) -> Result<T, Context<ErrorKind>>
+        fn with_context<F, D>(self, f: F) -> Result<T>
         where
             F: FnOnce(&E) -> D,
-            D: Display + Send + Sync + 'static,
+            D: Display,
         {
-            self.map_err(|failure| {
-                let context = f(&failure);
-                failure
-                    .context(context)
-                    .map(|info| ErrorKind::Backend(info.to_string()))
+            self.map_err(|e| {
+                let kind = ErrorKind::Backend(f(&e).to_string());
+                Error::with_source(kind, e)
             })
         }
     }
 }
-
-/// A specialized `Result` type bound to [`Error`].
-pub type Result<T> = std::result::Result<T, Error>;
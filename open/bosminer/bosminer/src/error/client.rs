@@ -20,16 +20,16 @@
 // of such proprietary license or if you have any other questions, please
 // contact us at opensource@braiins.com.
 
-use failure::Fail;
+use thiserror::Error;
 
-#[derive(Clone, Eq, PartialEq, Debug, Fail)]
+#[derive(Error, Clone, Eq, PartialEq, Debug)]
 pub enum ErrorKind {
-    #[fail(display = "the client has been already unregistered")]
+    #[error("the client has been already unregistered")]
     Missing,
-    #[fail(display = "the client client has been registered")]
+    #[error("the client client has been registered")]
     Additional,
-    #[fail(display = "all client groups have only fixed share ratio")]
+    #[error("all client groups have only fixed share ratio")]
     OnlyFixedShareRatio,
-    #[fail(display = "total fixed share ratio is greater than or equal to 1.0")]
+    #[error("total fixed share ratio is greater than or equal to 1.0")]
     FixedShareRatioOverflow,
 }
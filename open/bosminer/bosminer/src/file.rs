@@ -0,0 +1,60 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Files which the miner keeps across restarts (e.g. statistics or tuned settings)
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Atomically replaces content of file at `path`. The data are written to a temporary file first
+/// which is then renamed to the target path. The rename is made durable by synchronizing the
+/// parent directory. It does blocking I/O so it should be called from a blocking task.
+pub fn write_atomically(path: &Path, content: &[u8]) -> io::Result<()> {
+    let tmp_path = path.with_extension("tmp");
+    {
+        let mut file = fs::File::create(&tmp_path)?;
+        file.write_all(content)?;
+        file.sync_all()?;
+    }
+    fs::rename(&tmp_path, path)?;
+    let parent = match path.parent() {
+        Some(parent) if parent != Path::new("") => parent,
+        _ => Path::new("."),
+    };
+    fs::File::open(parent)?.sync_all()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_write_atomically() {
+        let path = std::env::temp_dir().join(format!("bosminer_file_{}.json", std::process::id()));
+        write_atomically(&path, b"first").expect("BUG: cannot write file");
+        write_atomically(&path, b"second").expect("BUG: cannot replace file");
+        assert_eq!(fs::read(&path).expect("BUG: cannot read file"), b"second");
+        assert!(!path.with_extension("tmp").exists());
+        fs::remove_file(&path).expect("BUG: cannot remove file");
+    }
+}
@@ -20,8 +20,11 @@
 // of such proprietary license or if you have any other questions, please
 // contact us at opensource@braiins.com.
 
+pub mod sim;
+
 use crate::client;
 use crate::error;
+use crate::job;
 use crate::node;
 use crate::work;
 
@@ -30,6 +33,7 @@ use ii_stratum::v2::types::DeviceInfo;
 
 use std::convert::TryInto;
 use std::fmt::Debug;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -110,10 +114,28 @@ pub trait BackendConfig: Debug + Send + Sync {
     fn info(&self) -> Option<BackendInfo> {
         None
     }
+    /// Path to a file with lifetime statistics which are kept across restarts
+    fn persistent_stats_path(&self) -> Option<PathBuf> {
+        None
+    }
+    /// Solutions of jobs older than this are considered stale and no more work is generated
+    /// from them
+    fn max_work_age(&self) -> Duration {
+        work::DEFAULT_MAX_WORK_AGE
+    }
+    /// Policy deciding which solutions are submitted to pools
+    fn submit_policy(&self) -> job::DynSubmitPolicy {
+        Arc::new(job::DefaultSubmitPolicy::default())
+    }
 }
 
 pub struct FrontendConfig {
     pub cgminer_custom_commands: Option<command::Map>,
+    /// Authentication of API clients, `None` allows all commands without authentication
+    pub cgminer_access_control: Option<command::AccessControl>,
+    /// Halt context of a backend which terminates the whole program. The frontend hooks
+    /// termination signals to it and exits the process after its own exit hooks have been run.
+    pub halt_handle: Option<Arc<ii_stop::HaltHandle>>,
 }
 
 /// Minimal interface for running compatible backend with BOSminer crate
@@ -0,0 +1,513 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Simulated mining backend for development and testing of the work flow, statistics and pool
+//! logic without mining hardware
+//!
+//! Each simulated chain searches nonces of the actual work in software, but only for an easy
+//! `SEARCH_TARGET`. Every nonce found this way stands for a share of the configured backend
+//! difficulty and nonces are released at random intervals corresponding to the virtual hashrate
+//! of the chain regardless of the performance of the host. Such solutions do not meet the backend
+//! target so the frontend has to be configured with `SubmitPolicy` which validates them against
+//! the search target instead. Solutions which meet the job target are genuine and can be
+//! submitted to any pool.
+//!
+//! Hardware errors are injected by corrupting the nonce of a found solution.
+
+use crate::error;
+use crate::hal;
+use crate::job;
+use crate::node;
+use crate::stats;
+use crate::work;
+
+use bosminer_macros::WorkSolverNode;
+
+use ii_bitcoin::{HashTrait as _, MeetsTarget as _};
+
+use async_trait::async_trait;
+use futures::lock::Mutex;
+use ii_async_compat::prelude::*;
+
+use std::fmt;
+use std::mem;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Offset of the nonce in the binary representation of the block header
+const NONCE_OFFSET: usize = ii_bitcoin::BLOCK_HEADER_SIZE - mem::size_of::<u32>();
+
+/// Difficulty of `SEARCH_TARGET` is 1/2^SEARCH_TARGET_SHIFT so it takes 2^(32 - SHIFT) hashes on
+/// average to find a nonce
+const SEARCH_TARGET_SHIFT: usize = 20;
+
+/// Maximal delay of the virtual time of a chain behind the real time
+const MAX_LAG: Duration = Duration::from_secs(1);
+
+/// Maximal number of nonces tried to find a solution before the work is skipped
+const MAX_SEARCH_NONCES: u32 = 1 << 20;
+
+pub const DEFAULT_CHAIN_COUNT: usize = 3;
+/// Virtual hashrate of one chain in H/s
+pub const DEFAULT_CHAIN_HASHRATE: u128 = 1_000_000_000_000;
+/// Backend difficulty of released solutions, the same as the default ASIC difficulty of S9
+pub const DEFAULT_DIFFICULTY: usize = 64;
+
+/// Target which found nonces meet
+pub fn search_target() -> ii_bitcoin::Target {
+    (ii_bitcoin::Target::default().into_inner() << SEARCH_TARGET_SHIFT).into()
+}
+
+#[derive(Debug)]
+pub struct Config {
+    /// Number of simulated chains attached to the backend work hub
+    pub chain_count: usize,
+    /// Virtual hashrate of each chain in H/s
+    pub hashrate: u128,
+    /// Backend difficulty of solutions
+    pub difficulty: usize,
+    /// Ratio of solutions returned with a corrupted nonce (hardware errors)
+    pub error_rate: f64,
+    pub midstate_count: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            chain_count: DEFAULT_CHAIN_COUNT,
+            hashrate: DEFAULT_CHAIN_HASHRATE,
+            difficulty: DEFAULT_DIFFICULTY,
+            error_rate: 0.0,
+            midstate_count: 1,
+        }
+    }
+}
+
+impl Config {
+    /// Mean time between two solutions of one chain
+    fn solution_interval(&self) -> Duration {
+        Duration::from_secs_f64(self.difficulty as f64 * (1u64 << 32) as f64 / self.hashrate as f64)
+    }
+
+    /// Time it takes to exhaust the whole nonce space of work with all midstates
+    fn work_time(&self) -> Duration {
+        Duration::from_secs_f64(
+            self.midstate_count as f64 * (1u64 << 32) as f64 / self.hashrate as f64,
+        )
+    }
+}
+
+impl hal::BackendConfig for Config {
+    #[inline]
+    fn midstate_count(&self) -> usize {
+        self.midstate_count
+    }
+
+    fn submit_policy(&self) -> job::DynSubmitPolicy {
+        Arc::new(SubmitPolicy)
+    }
+}
+
+/// Submit policy which accepts solutions of simulated chains as backend shares
+#[derive(Debug, Default)]
+pub struct SubmitPolicy;
+
+impl job::SubmitPolicy for SubmitPolicy {
+    fn classify(&self, solution: &work::Solution) -> Option<stats::DiffTargetType> {
+        let hash = solution.hash();
+        if hash.meets(&solution.network_target()) {
+            Some(stats::DiffTargetType::Network)
+        } else if hash.meets(solution.job_target()) {
+            Some(stats::DiffTargetType::Job)
+        } else if hash.meets(&search_target()) {
+            Some(stats::DiffTargetType::Backend)
+        } else {
+            None
+        }
+    }
+}
+
+/// Solution found by a simulated chain
+#[derive(Debug)]
+pub struct Solution {
+    nonce: u32,
+    midstate_idx: usize,
+    target: ii_bitcoin::Target,
+}
+
+impl hal::BackendSolution for Solution {
+    #[inline]
+    fn nonce(&self) -> u32 {
+        self.nonce
+    }
+
+    #[inline]
+    fn midstate_idx(&self) -> usize {
+        self.midstate_idx
+    }
+
+    #[inline]
+    fn solution_idx(&self) -> usize {
+        0
+    }
+
+    #[inline]
+    fn target(&self) -> &ii_bitcoin::Target {
+        &self.target
+    }
+}
+
+/// Simple xorshift generator, the simulation does not need better randomness
+struct Random(u32);
+
+impl Random {
+    fn new(seed: u32) -> Self {
+        // zero state would generate only zeros
+        Self(seed | 1)
+    }
+
+    fn next(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+
+    /// Uniformly distributed number in the interval (0, 1)
+    fn uniform(&mut self) -> f64 {
+        (self.next() as f64 + 1.0) / (u32::MAX as f64 + 2.0)
+    }
+
+    /// Exponentially distributed interval with given `mean`
+    fn interval(&mut self, mean: Duration) -> Duration {
+        mean.mul_f64(-self.uniform().ln())
+    }
+}
+
+/// Try at most `MAX_SEARCH_NONCES` nonces starting at `start` and return the first one meeting
+/// `SEARCH_TARGET`
+fn search(work: &work::Assignment, midstate_idx: usize, start: u32) -> Option<u32> {
+    let target = search_target();
+    // only the nonce differs so the header is packed just once
+    let mut header_bytes = work.block_header(midstate_idx, 0).into_bytes();
+    (0..MAX_SEARCH_NONCES)
+        .map(|i| start.wrapping_add(i))
+        .find(|nonce| {
+            header_bytes[NONCE_OFFSET..].copy_from_slice(&nonce.to_le_bytes());
+            ii_bitcoin::DHash::hash(&header_bytes).meets(&target)
+        })
+}
+
+#[derive(Debug, WorkSolverNode)]
+pub struct Chain {
+    #[member_work_solver_stats]
+    work_solver_stats: stats::BasicWorkSolver,
+    idx: usize,
+    hashrate: u128,
+    solution_interval: Duration,
+    work_time: Duration,
+    error_rate: f64,
+    target: ii_bitcoin::Target,
+    work_generator: Mutex<Option<work::Generator>>,
+    solution_sender: work::SolutionSender,
+}
+
+impl Chain {
+    fn new(
+        idx: usize,
+        config: &Config,
+        work_generator: work::Generator,
+        solution_sender: work::SolutionSender,
+    ) -> Self {
+        Self {
+            work_solver_stats: Default::default(),
+            idx,
+            hashrate: config.hashrate,
+            solution_interval: config.solution_interval(),
+            work_time: config.work_time(),
+            error_rate: config.error_rate,
+            target: ii_bitcoin::Target::from_pool_difficulty(config.difficulty),
+            work_generator: Mutex::new(Some(work_generator)),
+            solution_sender,
+        }
+    }
+
+    /// Returns solution of `work` or `None` when no nonce has been found in time
+    async fn solve(&self, work: &work::Assignment, random: &mut Random) -> Option<Solution> {
+        let midstate_idx = random.next() as usize % work.midstates.len();
+        let start = random.next();
+        let search_work = work.clone();
+        // hashing is CPU bound so it must not block the regular threadpool
+        let nonce = runtime::spawn_blocking(move || search(&search_work, midstate_idx, start))
+            .await
+            .expect("BUG: simulated chain failed")?;
+        let nonce = if random.uniform() < self.error_rate {
+            // a corrupted nonce practically never meets the search target
+            nonce ^ 1
+        } else {
+            nonce
+        };
+        Some(Solution {
+            nonce,
+            midstate_idx,
+            target: self.target,
+        })
+    }
+
+    async fn run(self: Arc<Self>) {
+        let mut work_generator = self
+            .work_generator
+            .lock()
+            .await
+            .take()
+            .expect("BUG: missing work generator");
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("BUG: time before epoch")
+            .subsec_nanos();
+        let mut random = Random::new(seed.wrapping_add(self.idx as u32));
+
+        // Virtual time of the chain which is ahead of the real time while the chain is idle
+        let mut now = Instant::now();
+        let mut next_solution = now + random.interval(self.solution_interval);
+        while let Some(work) = work_generator.generate().await {
+            let work_end = now + self.work_time;
+            while next_solution < work_end {
+                if let Some(solution) = self.solve(&work, &mut random).await {
+                    runtime::delay_until(next_solution).await;
+                    self.solution_sender.send(work::Solution::new(
+                        work.clone(),
+                        solution,
+                        Some(next_solution),
+                    ));
+                }
+                next_solution += random.interval(self.solution_interval);
+            }
+            runtime::delay_until(work_end).await;
+            now = work_end;
+            // Do not try to catch up when the host cannot keep up with the virtual hashrate
+            let real_now = Instant::now();
+            if real_now > now + MAX_LAG {
+                now = real_now;
+                next_solution = next_solution.max(now);
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl node::WorkSolver for Chain {
+    fn get_id(&self) -> Option<usize> {
+        Some(self.idx)
+    }
+
+    async fn get_nominal_hashrate(&self) -> Option<ii_bitcoin::HashesUnit> {
+        Some(self.hashrate.into())
+    }
+}
+
+impl fmt::Display for Chain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Simulated chain {}", self.idx)
+    }
+}
+
+#[derive(Debug, WorkSolverNode)]
+pub struct Backend {
+    #[member_work_solver_stats]
+    work_solver_stats: stats::BasicWorkSolver,
+}
+
+impl Backend {
+    pub fn new() -> Self {
+        Self {
+            work_solver_stats: Default::default(),
+        }
+    }
+}
+
+#[async_trait]
+impl hal::Backend for Backend {
+    type Type = Self;
+    type Config = Config;
+
+    const DEFAULT_HASHRATE_INTERVAL: Duration = Duration::from_secs(60);
+    const JOB_TIMEOUT: Duration = Duration::from_secs(30);
+
+    fn create(_backend_config: &mut Config) -> hal::WorkNode<Self> {
+        node::WorkSolverType::WorkHub(Box::new(Self::new))
+    }
+
+    async fn init_work_hub(
+        backend_config: Config,
+        work_hub: work::SolverBuilder<Self>,
+    ) -> error::Result<hal::FrontendConfig> {
+        for idx in 0..backend_config.chain_count {
+            let chain = work_hub
+                .create_work_solver(|work_generator, solution_sender| {
+                    Chain::new(idx, &backend_config, work_generator, solution_sender)
+                })
+                .await;
+            runtime::spawn(chain.run());
+        }
+        Ok(hal::FrontendConfig {
+            cgminer_custom_commands: None,
+            cgminer_access_control: None,
+            halt_handle: None,
+        })
+    }
+
+    async fn init_work_solver(
+        _backend_config: Config,
+        _work_solver: Arc<Self>,
+    ) -> error::Result<hal::FrontendConfig> {
+        panic!("BUG: called `init_work_solver`");
+    }
+}
+
+#[async_trait]
+impl node::WorkSolver for Backend {
+    async fn get_nominal_hashrate(&self) -> Option<ii_bitcoin::HashesUnit> {
+        None
+    }
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Simulated backend")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::simulation::{Miner, Protocol, ScriptedPool};
+    use crate::test_utils::TEST_BLOCKS;
+
+    use job::SubmitPolicy as _;
+
+    /// Time of mining in simulation tests
+    const SIMULATION_TIME: Duration = Duration::from_secs(2);
+
+    #[test]
+    fn test_search() {
+        for block in TEST_BLOCKS.iter() {
+            let work: work::Assignment = block.into();
+            // skip the known solution of the block which meets even the difficulty 1
+            let nonce = search(&work, 0, block.nonce.wrapping_add(1)).expect("no nonce found");
+            let target = Default::default();
+            let solution = |nonce| Solution {
+                nonce,
+                midstate_idx: 0,
+                target,
+            };
+
+            let found = work::Solution::new(work.clone(), solution(nonce), None);
+            assert!(found.hash().meets(&search_target()));
+            assert!(!found.hash().meets(&target));
+            assert_eq!(
+                SubmitPolicy.classify(&found),
+                Some(stats::DiffTargetType::Backend)
+            );
+            // the default policy considers the solution a hardware error
+            assert_eq!(job::DefaultSubmitPolicy::default().classify(&found), None);
+
+            let corrupted = work::Solution::new(work, solution(nonce ^ 1), None);
+            assert_eq!(SubmitPolicy.classify(&corrupted), None);
+        }
+    }
+
+    #[test]
+    fn test_intervals() {
+        let config = Config {
+            hashrate: 1 << 40,
+            difficulty: 4,
+            ..Default::default()
+        };
+        assert_eq!(config.solution_interval(), Duration::from_micros(15625));
+        assert_eq!(config.work_time(), Duration::from_nanos(3906250));
+
+        let mut random = Random::new(0);
+        let mean = Duration::from_secs(1);
+        let count = 10000;
+        let sum: Duration = (0..count).map(|_| random.interval(mean)).sum();
+        let average = sum.as_secs_f64() / count as f64;
+        assert!((average - 1.0).abs() < 0.05, "average interval {}", average);
+    }
+
+    /// Mine jobs of a scripted pool with simulated backend and return statistics of all chains
+    async fn run_simulation(config: Config) -> Vec<(u64, u64)> {
+        let pool = ScriptedPool::start(Protocol::StratumV2, 1, usize::MAX);
+        let miner = Miner::start(config).await;
+        miner.connect_default(&pool).await;
+        runtime::delay_for(SIMULATION_TIME).await;
+
+        let mut chain_stats = vec![];
+        for work_solver in miner.core.get_work_solvers().await {
+            let mining_stats = work_solver.mining_stats();
+            chain_stats.push((
+                mining_stats
+                    .valid_backend_diff()
+                    .take_snapshot()
+                    .await
+                    .solutions,
+                mining_stats
+                    .error_backend_diff()
+                    .take_snapshot()
+                    .await
+                    .solutions,
+            ));
+        }
+        chain_stats
+    }
+
+    /// Configuration of chains with approximately 50 solutions per second
+    fn test_config(error_rate: f64) -> Config {
+        Config {
+            chain_count: 2,
+            hashrate: 50 << 32,
+            difficulty: 1,
+            error_rate,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_simulation() {
+        let chain_stats = run_simulation(test_config(0.0)).await;
+        assert_eq!(chain_stats.len(), 2);
+        for (valid, errors) in chain_stats {
+            assert!(valid > 0);
+            assert_eq!(errors, 0);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_error_injection() {
+        let chain_stats = run_simulation(test_config(1.0)).await;
+        assert_eq!(chain_stats.len(), 2);
+        for (valid, errors) in chain_stats {
+            assert_eq!(valid, 0);
+            assert!(errors > 0);
+        }
+    }
+}
@@ -0,0 +1,104 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Minimal HTTP client used for pushing alerts and telemetry to plain HTTP endpoints
+
+use crate::error;
+
+use ii_async_compat::prelude::*;
+use runtime::net::TcpStream;
+
+use url::Url;
+
+/// Sends one HTTP POST request with `body` and additional `headers` (e.g. `Content-Type`) and
+/// checks that server responds with success
+pub async fn post(url: &Url, headers: &[(&str, &str)], body: &[u8]) -> error::Result<()> {
+    let host = url.host_str().ok_or("missing host in URL")?;
+    let port = url.port_or_known_default().ok_or("missing port in URL")?;
+    let path = match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    };
+
+    let mut stream = TcpStream::connect((host, port)).await?;
+    let mut header = format!("POST {} HTTP/1.1\r\nHost: {}\r\n", path, host);
+    for (name, value) in headers {
+        header.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    header.push_str(&format!(
+        "Content-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len()
+    ));
+    stream.write_all(header.as_bytes()).await?;
+    stream.write_all(body).await?;
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await?;
+    let status_line = response
+        .split(|c| *c == b'\n')
+        .next()
+        .map(|line| String::from_utf8_lossy(line).trim().to_string())
+        .unwrap_or_default();
+    match status_line.split_whitespace().nth(1) {
+        Some(code) if code.starts_with('2') => Ok(()),
+        _ => Err(format!("unexpected response '{}'", status_line).into()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_post() {
+        let mut listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("BUG: cannot bind listener");
+        let addr = listener.local_addr().expect("BUG: missing local address");
+        let server = runtime::spawn(async move {
+            let (mut stream, _) = listener.accept().await.expect("BUG: cannot accept");
+            let mut request = vec![];
+            let mut buffer = [0u8; 1024];
+            while !request.ends_with(b"{}") {
+                let len = stream.read(&mut buffer).await.expect("BUG: cannot read");
+                assert_ne!(len, 0, "BUG: unexpected end of request");
+                request.extend_from_slice(&buffer[..len]);
+            }
+            stream
+                .write_all(b"HTTP/1.1 204 No Content\r\n\r\n")
+                .await
+                .expect("BUG: cannot write");
+            String::from_utf8_lossy(&request).to_string()
+        });
+
+        let url = Url::parse(&format!("http://{}/hook?id=1", addr)).expect("BUG: invalid URL");
+        post(&url, &[("Content-Type", "application/json")], b"{}")
+            .await
+            .expect("BUG: request failed");
+        let request = server.await.expect("BUG: server failed");
+        assert!(request.starts_with("POST /hook?id=1 HTTP/1.1\r\n"));
+        assert!(request.contains("\r\nContent-Type: application/json\r\n"));
+        assert!(request.ends_with("\r\n\r\n{}"));
+    }
+}
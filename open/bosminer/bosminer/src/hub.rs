@@ -29,15 +29,16 @@ use crate::backend;
 use crate::client;
 use crate::error;
 use crate::hal::{self, BackendConfig};
+use crate::job;
 use crate::node;
 use crate::work;
 
-use futures::channel::mpsc;
 use futures::lock::Mutex;
 use futures::stream::StreamExt;
-use ii_async_compat::{futures, tokio};
+use ii_async_compat::{futures, runtime};
 
 use std::sync::{Arc, Weak};
+use std::time;
 
 /// Handle external events. Currently it is used only wor handling exhausted work from work engine.
 /// It usually signals some serious problem in backend.
@@ -53,26 +54,43 @@ impl work::ExhaustedHandler for EventHandler {
 /// Responsible for delivering work solution to the client from which the work has been generated
 struct SolutionRouter {
     job_executor: Arc<client::JobExecutor>,
-    solution_receiver: mpsc::UnboundedReceiver<work::Solution>,
+    solution_receiver: work::SolutionQueueReceiver,
+    /// Number of dropped solutions which have already been reported
+    reported_drops: u64,
 }
 
 impl SolutionRouter {
     fn new(
         job_executor: Arc<client::JobExecutor>,
-        solution_receiver: mpsc::UnboundedReceiver<work::Solution>,
+        solution_receiver: work::SolutionQueueReceiver,
     ) -> Self {
         Self {
             job_executor,
             solution_receiver,
+            reported_drops: 0,
+        }
+    }
+
+    fn report_drops(&mut self) {
+        let stats = self.solution_receiver.stats();
+        if stats.dropped > self.reported_drops {
+            warn!(
+                "Hub: {} solutions have been dropped because the solution queue is full (high watermark {}/{})",
+                stats.dropped - self.reported_drops,
+                stats.high_watermark,
+                stats.capacity
+            );
+            self.reported_drops = stats.dropped;
         }
     }
 
     async fn run(mut self) {
         while let Some(solution) = self.solution_receiver.next().await {
+            self.report_drops();
             // NOTE: all solutions targeting to removed clients are discarded
             if let Some(solution_sender) = self.job_executor.get_solution_sender(&solution).await {
                 solution_sender
-                    .unbounded_send(solution)
+                    .try_send(solution)
                     .expect("solution queue send failed");
             } else {
                 warn!("Hub: solution has been discarded because client does not exist anymore");
@@ -88,7 +106,7 @@ pub struct Core {
     pub frontend: Arc<crate::Frontend>,
     job_executor: Arc<client::JobExecutor>,
     engine_receiver: work::EngineReceiver,
-    solution_sender: mpsc::UnboundedSender<work::Solution>,
+    solution_sender: work::SolutionQueueSender,
     solution_router: Mutex<Option<SolutionRouter>>,
     /// Registry of clients that are able to supply new jobs for mining
     client_manager: client::Manager,
@@ -98,15 +116,17 @@ pub struct Core {
 impl Core {
     pub fn new(
         midstate_count: usize,
+        max_work_age: time::Duration,
+        submit_policy: job::DynSubmitPolicy,
         backend_registry: &Arc<backend::Registry>,
         backend_info: Option<hal::BackendInfo>,
     ) -> Self {
         let frontend = Arc::new(crate::Frontend::new());
 
         let (engine_sender, engine_receiver) = work::engine_channel(EventHandler);
-        let (solution_sender, solution_receiver) = mpsc::unbounded();
+        let (solution_sender, solution_receiver) = work::solution_queue("hub solutions");
 
-        let client_manager = client::Manager::new(midstate_count);
+        let client_manager = client::Manager::new(midstate_count, max_work_age, submit_policy);
         let job_executor = Arc::new(client::JobExecutor::new(
             frontend.clone(),
             engine_sender,
@@ -209,7 +229,7 @@ impl Core {
             .take()
             .expect("missing solution router");
 
-        tokio::spawn(solution_router.run());
+        runtime::spawn(solution_router.run());
         self.job_executor.clone().run().await;
     }
 }
@@ -217,23 +237,28 @@ impl Core {
 #[cfg(test)]
 pub mod test {
     use super::*;
-    use crate::job;
     use crate::test_utils;
     use crate::Frontend;
 
-    use std::sync::Arc;
+    use ii_async_compat::tokio;
+
+    use std::sync::{Arc, Mutex as StdMutex};
 
     /// Create job solver for frontend (pool) and work solver builder for backend (as we expect a
     /// hierarchical structure in backends)
     fn build_solvers() -> (job::Solver, work::SolverBuilder<Frontend>) {
         let (engine_sender, engine_receiver) = work::engine_channel(EventHandler);
-        let (solution_sender, solution_receiver) = mpsc::unbounded();
+        let (solution_sender, solution_receiver) = work::solution_queue("test solutions");
         let frontend = Arc::new(crate::Frontend::new());
         let _ = engine_sender.replace_engine_generator(Box::new(move |job| {
             Arc::new(work::engine::VersionRolling::new(job, 1))
         }));
         (
-            job::Solver::new(Arc::new(engine_sender), solution_receiver),
+            job::Solver::new(
+                Arc::new(engine_sender),
+                solution_receiver,
+                Arc::new(StdMutex::new(Arc::new(job::DefaultSubmitPolicy::default()))),
+            ),
             work::SolverBuilder::new(
                 frontend,
                 Arc::new(backend::Registry::new()),
@@ -0,0 +1,294 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! This module periodically pushes miner telemetry to InfluxDB (or any other collector accepting
+//! InfluxDB line protocol, e.g. Telegraf). Each snapshot is written as following points:
+//!  - `<measurement>` - hashrate in GH/s, accepted/rejected shares and power estimate in Watts
+//!  - `<measurement>_chain` - temperature of each hash chain tagged with `chain` index
+//!  - `<measurement>_fan` - speed of each fan in RPM tagged with `fan` index
+//!
+//! All points are tagged with `host`. They are sent either with HTTP POST request to write
+//! endpoint (`http://host[:port]/write?db=<database>` for InfluxDB 1.x or
+//! `http://host[:port]/api/v2/write?org=<org>&bucket=<bucket>` for InfluxDB 2.x) or as one UDP
+//! datagram to `udp://host[:port]`.
+
+use ii_logging::macros::*;
+
+use crate::client;
+use crate::error;
+use crate::http;
+use crate::node;
+use crate::telemetry;
+
+use ii_async_compat::prelude::*;
+use runtime::delay_for;
+use runtime::net::UdpSocket;
+
+use url::Url;
+
+use std::fmt::{self, Write};
+use std::sync::Arc;
+use std::time;
+
+/// Default port of InfluxDB UDP listener
+pub const DEFAULT_UDP_PORT: u16 = 8089;
+
+/// Default period of telemetry export
+pub const DEFAULT_INTERVAL: time::Duration = time::Duration::from_secs(60);
+
+/// Default name of the main measurement, it is also a prefix of all other measurements
+pub const DEFAULT_MEASUREMENT: &str = "bosminer";
+
+/// Timeout for the whole HTTP request or sending of UDP datagram
+pub const REQUEST_TIMEOUT: time::Duration = time::Duration::from_secs(10);
+
+#[derive(Debug, Clone, PartialEq)]
+enum Endpoint {
+    Http(Url),
+    Udp { host: String, port: u16 },
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Http(url) => write!(f, "{}", url),
+            Self::Udp { host, port } => write!(f, "udp://{}:{}", host, port),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    endpoint: Endpoint,
+    /// Value of `host` tag attached to all points
+    pub host: String,
+    /// Name of the main measurement
+    pub measurement: String,
+    /// Token sent in `Authorization` header of HTTP requests (required by InfluxDB 2.x)
+    pub token: Option<String>,
+    /// Period of telemetry export
+    pub interval: time::Duration,
+}
+
+impl Config {
+    /// Creates configuration for endpoint with URL in format `http://host[:port]/path?query` or
+    /// `udp://host[:port]`
+    pub fn new(url: &str, host: String) -> error::Result<Self> {
+        let parsed_url =
+            Url::parse(url).map_err(|e| format!("invalid InfluxDB URL '{}': {}", url, e))?;
+        let endpoint = match parsed_url.scheme() {
+            "http" => {
+                if parsed_url.host_str().is_none() {
+                    Err(format!("missing host in InfluxDB URL '{}'", url))?;
+                }
+                Endpoint::Http(parsed_url)
+            }
+            "udp" => Endpoint::Udp {
+                host: parsed_url
+                    .host_str()
+                    .ok_or_else(|| format!("missing host in InfluxDB URL '{}'", url))?
+                    .to_string(),
+                port: parsed_url.port().unwrap_or(DEFAULT_UDP_PORT),
+            },
+            scheme => Err(format!("unsupported InfluxDB URL scheme '{}'", scheme))?,
+        };
+
+        Ok(Self {
+            endpoint,
+            host,
+            measurement: DEFAULT_MEASUREMENT.to_string(),
+            token: None,
+            interval: DEFAULT_INTERVAL,
+        })
+    }
+}
+
+/// Escapes measurement name, tag key or tag value with backslash
+fn escape(value: &str, special: &[char]) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if special.contains(&c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn escape_measurement(value: &str) -> String {
+    escape(value, &[',', ' '])
+}
+
+fn escape_tag(value: &str) -> String {
+    escape(value, &[',', '=', ' '])
+}
+
+/// Encodes telemetry snapshot to InfluxDB line protocol with timestamps in nanoseconds
+fn encode(measurement: &str, host: &str, telemetry: &telemetry::Telemetry) -> String {
+    let measurement = escape_measurement(measurement);
+    let host = escape_tag(host);
+    let timestamp = u128::from(telemetry.timestamp) * 1_000_000_000;
+    let hardware = &telemetry.hardware;
+
+    let mut lines = String::new();
+    write!(
+        lines,
+        "{},host={} hashrate={},accepted={}i,rejected={}i",
+        measurement, host, telemetry.hashrate, telemetry.accepted, telemetry.rejected
+    )
+    .expect("BUG: cannot write to string");
+    if let Some(power) = hardware.power {
+        write!(lines, ",power={}", power).expect("BUG: cannot write to string");
+    }
+    writeln!(lines, " {}", timestamp).expect("BUG: cannot write to string");
+
+    for (idx, temperature) in hardware.temperatures.iter().enumerate() {
+        // Unknown temperature is omitted because line protocol has no null value
+        if let Some(temperature) = temperature {
+            writeln!(
+                lines,
+                "{}_chain,host={},chain={} temperature={} {}",
+                measurement, host, idx, temperature, timestamp
+            )
+            .expect("BUG: cannot write to string");
+        }
+    }
+    for (idx, rpm) in hardware.fan_speeds.iter().enumerate() {
+        writeln!(
+            lines,
+            "{}_fan,host={},fan={} rpm={}i {}",
+            measurement, host, idx, rpm, timestamp
+        )
+        .expect("BUG: cannot write to string");
+    }
+    lines
+}
+
+async fn send(config: &Config, lines: &[u8]) -> error::Result<()> {
+    match &config.endpoint {
+        Endpoint::Http(url) => {
+            let authorization = config
+                .token
+                .as_ref()
+                .map(|token| format!("Token {}", token));
+            let mut headers = vec![("Content-Type", "text/plain; charset=utf-8")];
+            if let Some(authorization) = authorization.as_ref() {
+                headers.push(("Authorization", authorization.as_str()));
+            }
+            http::post(url, &headers, lines).await
+        }
+        Endpoint::Udp { host, port } => {
+            let mut socket = UdpSocket::bind("0.0.0.0:0").await?;
+            socket.send_to(lines, (host.as_str(), *port)).await?;
+            Ok(())
+        }
+    }
+}
+
+async fn task(config: Config, sources: telemetry::Sources) {
+    loop {
+        delay_for(config.interval).await;
+        let telemetry = sources.collect().await;
+        let lines = encode(&config.measurement, &config.host, &telemetry);
+        let result = match send(&config, lines.as_bytes())
+            .timeout(REQUEST_TIMEOUT)
+            .await
+        {
+            Ok(result) => result,
+            Err(_) => Err("request timeout".into()),
+        };
+        if let Err(e) = result {
+            warn!(
+                "InfluxDB: cannot write telemetry to '{}': {}",
+                config.endpoint, e
+            );
+        }
+    }
+}
+
+/// Starts exporting telemetry of a `node` (usually the whole backend) together with statistics
+/// from all clients and backend specific `hardware` telemetry
+pub fn start(
+    config: Config,
+    node: node::DynInfo,
+    client_manager: client::Manager,
+    hardware: Arc<dyn telemetry::HardwareSource>,
+) {
+    let sources = telemetry::Sources::new(node, client_manager, hardware);
+    runtime::spawn(task(config, sources));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_config() {
+        let config = Config::new("http://influx.local:8086/write?db=mining", "s9".to_string())
+            .expect("BUG: cannot create config");
+        assert_eq!(
+            config.endpoint,
+            Endpoint::Http(
+                Url::parse("http://influx.local:8086/write?db=mining").expect("BUG: invalid URL")
+            )
+        );
+        assert_eq!(config.measurement, DEFAULT_MEASUREMENT);
+
+        let config =
+            Config::new("udp://influx.local", "s9".to_string()).expect("BUG: cannot create config");
+        assert_eq!(
+            config.endpoint,
+            Endpoint::Udp {
+                host: "influx.local".to_string(),
+                port: DEFAULT_UDP_PORT
+            }
+        );
+        assert_eq!(config.endpoint.to_string(), "udp://influx.local:8089");
+
+        assert!(Config::new("https://influx.local", "s9".to_string()).is_err());
+        assert!(Config::new("influx.local:8086", "s9".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_encode() {
+        let telemetry = telemetry::Telemetry {
+            timestamp: 1,
+            hashrate: 13500.5,
+            accepted: 2,
+            rejected: 1,
+            hardware: telemetry::Hardware {
+                temperatures: vec![Some(75.5), None, Some(80.0)],
+                fan_speeds: vec![3000, 0],
+                power: Some(1350.0),
+            },
+        };
+        assert_eq!(
+            encode("bosminer", "my s9,1", &telemetry),
+            "bosminer,host=my\\ s9\\,1 hashrate=13500.5,accepted=2i,rejected=1i,power=1350 \
+             1000000000\n\
+             bosminer_chain,host=my\\ s9\\,1,chain=0 temperature=75.5 1000000000\n\
+             bosminer_chain,host=my\\ s9\\,1,chain=2 temperature=80 1000000000\n\
+             bosminer_fan,host=my\\ s9\\,1,fan=0 rpm=3000i 1000000000\n\
+             bosminer_fan,host=my\\ s9\\,1,fan=1 rpm=0i 1000000000\n"
+        );
+    }
+}
@@ -22,21 +22,22 @@
 
 use ii_logging::macros::*;
 
-use ii_bitcoin::{HashTrait as _, MeetsTarget};
+use ii_bitcoin::{difficulty, HashTrait as _, MeetsTarget};
 
 use crate::job;
 use crate::node;
 use crate::stats::{self, DiffTargetType};
 use crate::work;
 
-use futures::channel::mpsc;
 use futures::stream::StreamExt;
 use ii_async_compat::futures;
 
 use std::convert::TryInto;
 use std::fmt::Debug;
 use std::mem;
-use std::sync::{Arc, Weak};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex as StdMutex, Weak};
+use std::time;
 
 use downcast_rs::{impl_downcast, Downcast};
 
@@ -67,6 +68,10 @@ pub trait Bitcoin: Debug + Downcast + Send + Sync {
     fn target(&self) -> ii_bitcoin::Target;
     /// Checks if job is still valid for mining
     fn is_valid(&self) -> bool;
+    /// Receipt of the job from remote server used for measurement of job latency
+    fn receipt(&self) -> Option<&Receipt> {
+        None
+    }
 
     /// Extract least-significant word of merkle root that goes to chunk2 of SHA256
     /// The word is interpreted as a little endian number.
@@ -82,6 +87,108 @@ pub trait Bitcoin: Debug + Downcast + Send + Sync {
 }
 impl_downcast!(Bitcoin);
 
+/// Time when a job (or a message which triggered its creation) has been received. The job latency
+/// is measured until the first work of the job is taken by a work solver which also means that
+/// the previous job has been replaced in the hardware.
+#[derive(Debug)]
+pub struct Receipt {
+    time: time::Instant,
+    taken: AtomicBool,
+}
+
+impl Receipt {
+    pub fn new(time: time::Instant) -> Self {
+        Self {
+            time,
+            taken: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns the job latency when the job is taken for the first time
+    pub fn take(&self, now: time::Instant) -> Option<time::Duration> {
+        if self.taken.swap(true, Ordering::Relaxed) {
+            None
+        } else {
+            Some(now.saturating_duration_since(self.time))
+        }
+    }
+}
+
+impl Clone for Receipt {
+    fn clone(&self) -> Self {
+        Self {
+            time: self.time,
+            taken: AtomicBool::new(self.taken.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// Decides which solutions are submitted to the pool. The default implementation submits all
+/// solutions meeting the job target while solutions meeting only the backend target (given by
+/// hardware ticket mask) are used just for statistics. Custom policy can be provided by backend
+/// configuration (see `hal::BackendConfig::submit_policy`).
+pub trait SubmitPolicy: Debug + Send + Sync {
+    /// Returns the most difficult target met by `solution` or `None` when the solution does not
+    /// meet even the backend target (hardware error)
+    fn classify(&self, solution: &work::Solution) -> Option<DiffTargetType> {
+        let hash = solution.hash();
+        if hash.meets(&solution.network_target()) {
+            Some(DiffTargetType::Network)
+        } else if hash.meets(solution.job_target()) {
+            Some(DiffTargetType::Job)
+        } else if hash.meets(solution.backend_target()) {
+            Some(DiffTargetType::Backend)
+        } else {
+            None
+        }
+    }
+
+    /// Checks if `solution` classified as `target_type` should be submitted to the pool. It is
+    /// called after the solution has been accounted to statistics.
+    fn submit(&self, _solution: &work::Solution, target_type: DiffTargetType) -> bool {
+        target_type != DiffTargetType::Backend
+    }
+}
+
+pub type DynSubmitPolicy = Arc<dyn SubmitPolicy>;
+
+/// Submit policy shared by a client handle and its solution receiver so that it can be replaced
+/// after the client has been created
+pub type SharedSubmitPolicy = Arc<StdMutex<DynSubmitPolicy>>;
+
+/// Minimal difficulty of solutions submitted by `DefaultSubmitPolicy`. Difficulty 1 submits all
+/// solutions meeting the job target.
+pub const DEFAULT_MIN_SUBMIT_DIFFICULTY: usize = 1;
+
+/// Submits solutions meeting the job target with difficulty of at least `min_difficulty`.
+/// Solutions of lower difficulty are still accounted in local statistics, it protects the miner
+/// from flooding pools which set very low difficulty.
+#[derive(Debug)]
+pub struct DefaultSubmitPolicy {
+    min_target: ii_bitcoin::Target,
+}
+
+impl DefaultSubmitPolicy {
+    pub fn new(min_difficulty: usize) -> Self {
+        assert!(min_difficulty > 0, "BUG: zero minimal difficulty");
+        Self {
+            min_target: ii_bitcoin::Target::from_pool_difficulty(min_difficulty),
+        }
+    }
+}
+
+impl Default for DefaultSubmitPolicy {
+    fn default() -> Self {
+        Self::new(DEFAULT_MIN_SUBMIT_DIFFICULTY)
+    }
+}
+
+impl SubmitPolicy for DefaultSubmitPolicy {
+    fn submit(&self, solution: &work::Solution, target_type: DiffTargetType) -> bool {
+        target_type != DiffTargetType::Backend && solution.hash().meets(&self.min_target)
+    }
+}
+
 /// Compound object for job submission and solution reception intended to be passed to
 /// protocol handler
 pub struct Solver {
@@ -92,11 +199,12 @@ pub struct Solver {
 impl Solver {
     pub fn new(
         engine_sender: Arc<work::EngineSender>,
-        solution_receiver: mpsc::UnboundedReceiver<work::Solution>,
+        solution_receiver: work::SolutionQueueReceiver,
+        submit_policy: SharedSubmitPolicy,
     ) -> Self {
         Self {
             job_sender: Sender::new(engine_sender),
-            solution_receiver: SolutionReceiver::new(solution_receiver),
+            solution_receiver: SolutionReceiver::new(solution_receiver, submit_policy),
         }
     }
 }
@@ -156,16 +264,23 @@ impl Sender {
     }
 }
 
-/// Receives `work::Solution` via a channel and filters only solutions that meet the client/pool
-/// specified target
+/// Receives `work::Solution` via a channel and filters only solutions that should be submitted
+/// according to the submit policy
 #[derive(Debug)]
 pub struct SolutionReceiver {
-    solution_channel: mpsc::UnboundedReceiver<work::Solution>,
+    solution_channel: work::SolutionQueueReceiver,
+    submit_policy: SharedSubmitPolicy,
 }
 
 impl SolutionReceiver {
-    pub fn new(solution_channel: mpsc::UnboundedReceiver<work::Solution>) -> Self {
-        Self { solution_channel }
+    pub fn new(
+        solution_channel: work::SolutionQueueReceiver,
+        submit_policy: SharedSubmitPolicy,
+    ) -> Self {
+        Self {
+            solution_channel,
+            submit_policy,
+        }
     }
 
     fn trace_share(solution: &work::Solution, target: &ii_bitcoin::Target) {
@@ -186,35 +301,70 @@ impl SolutionReceiver {
         );
     }
 
-    pub async fn receive(&mut self) -> Option<work::Solution> {
-        while let Some(solution) = self.solution_channel.next().await {
-            let path = solution.path();
-            let time = solution.timestamp();
-            let hash = solution.hash();
-            let job_target = solution.job_target();
-
-            // compare block hash for given solution with all targets
-            // TODO: create tests for solution validation with all difficulty variants
-            assert!(&solution.network_target() <= job_target);
-            if hash.meets(&solution.network_target()) {
-                stats::account_valid_solution(&path, &solution, time, DiffTargetType::Network)
-                    .await;
-            } else if hash.meets(&job_target) {
-                stats::account_valid_solution(&path, &solution, time, DiffTargetType::Job).await;
-            } else if hash.meets(solution.backend_target()) {
-                stats::account_valid_solution(&path, &solution, time, DiffTargetType::Backend)
-                    .await;
-                // skip submitting the solution as we've only met backend difficulty
-                continue;
-            } else {
-                stats::account_error_backend_diff(&path, &solution.backend_target(), time).await;
+    /// Accounts received `solution` and checks if it should be submitted
+    async fn accept(&self, solution: &work::Solution) -> bool {
+        let path = solution.path();
+        let time = solution.timestamp();
+        let job_target = solution.job_target();
+        let submit_policy = self
+            .submit_policy
+            .lock()
+            .expect("BUG: cannot lock submit policy")
+            .clone();
+
+        assert!(&solution.network_target() <= job_target);
+        if !solution.has_valid_midstate() {
+            warn!(
+                "Dropping solution with nonce={:08x} of nonexistent midstate {}",
+                solution.nonce(),
+                solution.midstate_idx()
+            );
+            stats::account_error_backend_diff(&path, solution.backend_target(), time).await;
+            return false;
+        }
+        let target_type = match submit_policy.classify(solution) {
+            Some(target_type) => target_type,
+            None => {
+                stats::account_error_backend_diff(&path, solution.backend_target(), time).await;
                 // skip submitting the solution as this is a backend error
-                continue;
+                return false;
             }
+        };
+        let share_difficulty = difficulty::share_difficulty(solution.hash());
+        stats::account_valid_solution(&path, solution, share_difficulty, time, target_type).await;
+        if !submit_policy.submit(solution, target_type) {
+            // skip submitting the solution e.g. when we've only met backend difficulty
+            return false;
+        }
+        if !solution.hash().meets(job_target) {
+            // custom submit policy may let through solutions which would be rejected by the pool
+            // for not meeting the share target
+            warn!(
+                "Discarding solution with nonce={:08x} and difficulty {} not meeting share \
+                 target (diff={})",
+                solution.nonce(),
+                share_difficulty,
+                difficulty::target_to_difficulty(job_target)
+            );
+            return false;
+        }
 
-            if solution.has_valid_job() {
-                // TODO: Account solution to Discard meter
-                Self::trace_share(&solution, &job_target);
+        if solution.is_expired() {
+            // late solutions after new block or of too old job would be rejected anyway
+            info!(
+                "Discarding stale solution with nonce={:08x}",
+                solution.nonce()
+            );
+            stats::account_stale(&path, job_target, time).await;
+            return false;
+        }
+        Self::trace_share(solution, job_target);
+        true
+    }
+
+    pub async fn receive(&mut self) -> Option<work::Solution> {
+        while let Some(solution) = self.solution_channel.next().await {
+            if self.accept(&solution).await {
                 return Some(solution);
             }
         }
@@ -226,6 +376,142 @@ impl SolutionReceiver {
     /// TODO: We should review this regularly as there may be extensions in the mining protocol that
     /// may allow resume a mining session
     pub fn flush(&mut self) {
-        while let Ok(Some(_)) = self.solution_channel.try_next() {}
+        while self.solution_channel.try_recv().is_some() {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::hal;
+    use crate::node::Stats as _;
+    use crate::test_utils::{TestBlockBuilder as _, TEST_BLOCKS, TEST_CLIENT};
+
+    use ii_async_compat::tokio;
+
+    /// Backend solution with arbitrary nonce and midstate index, e.g. corrupted by hardware
+    #[derive(Debug)]
+    struct CorruptedSolution {
+        nonce: u32,
+        midstate_idx: usize,
+        target: ii_bitcoin::Target,
+    }
+
+    impl hal::BackendSolution for CorruptedSolution {
+        fn nonce(&self) -> u32 {
+            self.nonce
+        }
+
+        fn midstate_idx(&self) -> usize {
+            self.midstate_idx
+        }
+
+        fn solution_idx(&self) -> usize {
+            0
+        }
+
+        fn target(&self) -> &ii_bitcoin::Target {
+            &self.target
+        }
+    }
+
+    /// Policy which classifies and submits all solutions as backend shares
+    #[derive(Debug)]
+    struct SubmitAllPolicy;
+
+    impl SubmitPolicy for SubmitAllPolicy {
+        fn classify(&self, _solution: &work::Solution) -> Option<DiffTargetType> {
+            Some(DiffTargetType::Backend)
+        }
+
+        fn submit(&self, _solution: &work::Solution, _target_type: DiffTargetType) -> bool {
+            true
+        }
+    }
+
+    fn solution_receiver(submit_policy: DynSubmitPolicy) -> SolutionReceiver {
+        let (_, solution_channel) = work::solution_queue("test solutions");
+        SolutionReceiver::new(solution_channel, Arc::new(StdMutex::new(submit_policy)))
+    }
+
+    fn corrupted_solution(nonce: u32, midstate_idx: usize) -> work::Solution {
+        let block = &TEST_BLOCKS[0];
+        work::Solution::new(
+            block.into(),
+            CorruptedSolution {
+                nonce,
+                midstate_idx,
+                target: Default::default(),
+            },
+            None,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_solution_validation() {
+        let receiver = solution_receiver(Arc::new(DefaultSubmitPolicy::default()));
+        for block in TEST_BLOCKS.iter() {
+            assert!(receiver.accept(&block.into()).await);
+        }
+
+        let nonce = TEST_BLOCKS[0].nonce;
+        assert!(receiver.accept(&corrupted_solution(nonce, 0)).await);
+        // hash of a corrupted nonce does not meet even the backend target
+        assert!(!receiver.accept(&corrupted_solution(nonce ^ 1, 0)).await);
+        // the work has only one midstate
+        assert!(!receiver.accept(&corrupted_solution(nonce, 1)).await);
+    }
+
+    #[tokio::test]
+    async fn test_share_target_check() {
+        let receiver = solution_receiver(Arc::new(SubmitAllPolicy));
+        let nonce = TEST_BLOCKS[0].nonce;
+        assert!(receiver.accept(&corrupted_solution(nonce, 0)).await);
+        // the policy allows submitting the solution but it does not meet the share target
+        assert!(!receiver.accept(&corrupted_solution(nonce ^ 1, 0)).await);
+    }
+
+    #[tokio::test]
+    async fn test_best_share_difficulty() {
+        let receiver = solution_receiver(Arc::new(DefaultSubmitPolicy::default()));
+        let block = TEST_BLOCKS[0].change_target(ii_bitcoin::Target::from_pool_difficulty(1));
+        let solution: work::Solution = block.into();
+        let share_difficulty = difficulty::share_difficulty(solution.hash()) as u64;
+        // the block hash beats the job target by far
+        assert!(share_difficulty > solution.job_target().get_difficulty() as u64);
+        // the best share must not be truncated to 32 bits on 32-bit targets
+        assert!(share_difficulty > u64::from(u32::MAX));
+        assert!(receiver.accept(&solution).await);
+
+        let best_share = TEST_CLIENT
+            .mining_stats()
+            .best_share()
+            .take_snapshot()
+            .expect("BUG: missing best share");
+        // the test client is shared by other tests which may have found even better shares
+        assert!(best_share.difficulty >= share_difficulty);
+    }
+
+    #[tokio::test]
+    async fn test_min_submit_difficulty() {
+        let block = TEST_BLOCKS[0].change_target(ii_bitcoin::Target::from_pool_difficulty(1));
+        let solution: work::Solution = block.into();
+        let share_difficulty = difficulty::share_difficulty(solution.hash()) as usize;
+
+        let receiver = solution_receiver(Arc::new(DefaultSubmitPolicy::new(share_difficulty / 2)));
+        assert!(receiver.accept(&solution).await);
+        // the solution meets the job target but it is not difficult enough to be submitted
+        let receiver = solution_receiver(Arc::new(DefaultSubmitPolicy::new(share_difficulty * 2)));
+        assert!(!receiver.accept(&solution).await);
+    }
+
+    #[test]
+    fn test_receipt() {
+        let received = time::Instant::now();
+        let receipt = Receipt::new(received);
+        let latency = time::Duration::from_millis(20);
+        assert_eq!(receipt.take(received + latency), Some(latency));
+        // the latency is measured only for the first work of the job
+        assert_eq!(receipt.take(received + latency * 2), None);
     }
 }
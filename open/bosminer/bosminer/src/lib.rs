@@ -24,18 +24,24 @@
 // the default recursion limit if more complex statements are used
 #![recursion_limit = "256"]
 
+pub mod alert;
 mod api;
 pub mod backend;
 pub mod client;
 pub mod config;
 pub mod entry;
 pub mod error;
+pub mod file;
 pub mod hal;
+mod http;
 pub mod hub;
+pub mod influx;
 pub mod job;
+pub mod mqtt;
 pub mod node;
 pub mod stats;
 pub mod sync;
+pub mod telemetry;
 pub mod version;
 pub mod work;
 
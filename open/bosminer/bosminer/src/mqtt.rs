@@ -0,0 +1,297 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! This module publishes miner telemetry and alert events to MQTT broker. The messages are
+//! published with QoS 0 to following topics:
+//!  - `<prefix>/telemetry` - periodic JSON snapshot of hashrate, shares and hardware state
+//!  - `<prefix>/event` - JSON alert events (see `alert::Event`)
+//!  - `<prefix>/status` - retained `online`/`offline` status (`offline` is set by the broker
+//!    when the connection is lost)
+//!
+//! TLS (`mqtts://` scheme) is available with `mqtt-tls` feature.
+
+mod packet;
+
+use ii_logging::macros::*;
+
+use crate::alert;
+use crate::client;
+use crate::error;
+use crate::node;
+use crate::telemetry;
+
+use futures::channel::mpsc;
+use futures::stream::StreamExt;
+use ii_async_compat::prelude::*;
+use ii_async_compat::select;
+use runtime::delay_for;
+use runtime::net::TcpStream;
+
+use url::Url;
+
+use std::sync::Arc;
+use std::time;
+
+/// Default port for plain TCP connection
+pub const DEFAULT_PORT: u16 = 1883;
+
+/// Default port for TLS connection
+pub const DEFAULT_TLS_PORT: u16 = 8883;
+
+/// Default period of telemetry publishing
+pub const DEFAULT_INTERVAL: time::Duration = time::Duration::from_secs(60);
+
+/// Delay before reconnecting to the broker
+pub const RECONNECT_DELAY: time::Duration = time::Duration::from_secs(10);
+
+/// Timeout for establishing connection with the broker
+pub const CONNECT_TIMEOUT: time::Duration = time::Duration::from_secs(10);
+
+/// Broker disconnects the client when there is no packet within 1.5 times of keep alive
+/// interval, telemetry is published more often to keep the connection alive
+const KEEP_ALIVE_MARGIN: time::Duration = time::Duration::from_secs(30);
+
+const STATUS_ONLINE: &[u8] = b"online";
+const STATUS_OFFLINE: &[u8] = b"offline";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Transport {
+    Tcp,
+    Tls,
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    transport: Transport,
+    host: String,
+    port: u16,
+    pub client_id: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Prefix of all topics
+    pub topic: String,
+    /// Period of telemetry publishing
+    pub interval: time::Duration,
+}
+
+impl Config {
+    /// Creates configuration for broker with URL in format `mqtt://host[:port]` or
+    /// `mqtts://host[:port]`
+    pub fn new(broker: &str, client_id: String) -> error::Result<Self> {
+        let url = Url::parse(broker)
+            .map_err(|e| format!("invalid MQTT broker URL '{}': {}", broker, e))?;
+        let (transport, default_port) = match url.scheme() {
+            "mqtt" => (Transport::Tcp, DEFAULT_PORT),
+            "mqtts" if cfg!(feature = "mqtt-tls") => (Transport::Tls, DEFAULT_TLS_PORT),
+            "mqtts" => Err("MQTT over TLS is not supported by this build")?,
+            scheme => Err(format!("unsupported MQTT broker scheme '{}'", scheme))?,
+        };
+        let host = url
+            .host_str()
+            .ok_or_else(|| format!("missing host in MQTT broker URL '{}'", broker))?;
+
+        Ok(Self {
+            transport,
+            host: host.to_string(),
+            port: url.port().unwrap_or(default_port),
+            topic: format!("bosminer/{}", client_id),
+            client_id,
+            username: None,
+            password: None,
+            interval: DEFAULT_INTERVAL,
+        })
+    }
+
+    fn topic(&self, name: &str) -> String {
+        format!("{}/{}", self.topic, name)
+    }
+}
+
+/// Connection to the broker over any transport
+trait Connection: AsyncRead + AsyncWrite + Send + Unpin {}
+
+impl<T: AsyncRead + AsyncWrite + Send + Unpin> Connection for T {}
+
+type DynConnection = Box<dyn Connection>;
+
+#[cfg(feature = "mqtt-tls")]
+async fn connect_tls(host: &str, stream: TcpStream) -> error::Result<DynConnection> {
+    use tokio_rustls::{rustls, webpki, TlsConnector};
+
+    let mut tls_config = rustls::ClientConfig::new();
+    tls_config
+        .root_store
+        .add_server_trust_anchors(&webpki_roots::TLS_SERVER_ROOTS);
+    let domain = webpki::DNSNameRef::try_from_ascii_str(host)
+        .map_err(|_| format!("invalid MQTT broker domain '{}'", host))?;
+    let stream = TlsConnector::from(Arc::new(tls_config))
+        .connect(domain, stream)
+        .await?;
+    Ok(Box::new(stream))
+}
+
+#[cfg(not(feature = "mqtt-tls"))]
+async fn connect_tls(_host: &str, _stream: TcpStream) -> error::Result<DynConnection> {
+    panic!("BUG: TLS transport should be refused by configuration");
+}
+
+/// Connects to the broker and sends CONNECT packet which has to be acknowledged
+async fn connect(config: &Config) -> error::Result<DynConnection> {
+    let stream = TcpStream::connect((config.host.as_str(), config.port)).await?;
+    let mut stream = match config.transport {
+        Transport::Tcp => Box::new(stream),
+        Transport::Tls => connect_tls(&config.host, stream).await?,
+    };
+
+    let status_topic = config.topic("status");
+    let keep_alive = config.interval + KEEP_ALIVE_MARGIN;
+    let connect = packet::Connect {
+        client_id: &config.client_id,
+        keep_alive: keep_alive.as_secs().min(u16::MAX as u64) as u16,
+        username: config.username.as_deref(),
+        password: config.password.as_deref(),
+        will: Some(packet::Will {
+            topic: &status_topic,
+            payload: STATUS_OFFLINE,
+            retain: true,
+        }),
+    };
+    stream.write_all(&connect.encode()).await?;
+
+    let mut connack = [0u8; packet::CONNACK_SIZE];
+    stream.read_exact(&mut connack).await?;
+    packet::parse_connack(&connack).map_err(|e| format!("connection refused: {}", e))?;
+
+    stream
+        .write_all(&packet::publish(&status_topic, STATUS_ONLINE, true))
+        .await?;
+    Ok(stream)
+}
+
+/// Publishes telemetry and events until the connection fails
+async fn run(
+    config: &Config,
+    sources: &telemetry::Sources,
+    events: &mut mpsc::UnboundedReceiver<alert::Event>,
+) -> error::Result<()> {
+    let mut stream = match connect(config).timeout(CONNECT_TIMEOUT).await {
+        Ok(stream) => stream?,
+        Err(_) => Err("connection timeout")?,
+    };
+    info!("MQTT: connected to broker {}:{}", config.host, config.port);
+
+    let telemetry_topic = config.topic("telemetry");
+    let event_topic = config.topic("event");
+    // Events must not postpone telemetry so the period is kept by one interval for the whole
+    // connection
+    let mut interval = runtime::Interval::new(config.interval);
+    loop {
+        let (topic, payload) = select! {
+            _ = interval.next().fuse() => {
+                let telemetry = sources.collect().await;
+                (&telemetry_topic, serde_json::to_vec(&telemetry))
+            }
+            event = events.next() => match event {
+                Some(event) => (&event_topic, serde_json::to_vec(&event)),
+                None => {
+                    // Alerting has been terminated so there will be no more events
+                    stream.write_all(&packet::disconnect()).await?;
+                    return Ok(());
+                }
+            },
+        };
+        let payload = payload.expect("BUG: cannot serialize MQTT message");
+        stream
+            .write_all(&packet::publish(topic, &payload, false))
+            .await?;
+    }
+}
+
+async fn task(
+    config: Config,
+    sources: telemetry::Sources,
+    mut events: mpsc::UnboundedReceiver<alert::Event>,
+) {
+    loop {
+        match run(&config, &sources, &mut events).await {
+            Ok(_) => break,
+            Err(e) => warn!(
+                "MQTT: connection to broker {}:{} failed: {}",
+                config.host, config.port, e
+            ),
+        }
+        delay_for(RECONNECT_DELAY).await;
+    }
+}
+
+/// MQTT publisher which has been registered for alert events but it has not been started yet
+pub struct Publisher {
+    config: Config,
+    events: mpsc::UnboundedReceiver<alert::Event>,
+}
+
+impl Publisher {
+    /// Creates publisher and subscribes it to events from `alert_sender`
+    pub fn new(config: Config, alert_sender: &mut alert::Sender) -> Self {
+        Self {
+            config,
+            events: alert_sender.subscribe(),
+        }
+    }
+
+    /// Starts publishing telemetry of a `node` (usually the whole backend) together with
+    /// statistics from all clients and backend specific `hardware` telemetry
+    pub fn start(
+        self,
+        node: node::DynInfo,
+        client_manager: client::Manager,
+        hardware: Arc<dyn telemetry::HardwareSource>,
+    ) {
+        let sources = telemetry::Sources::new(node, client_manager, hardware);
+        runtime::spawn(task(self.config, sources, self.events));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_config() {
+        let config = Config::new("mqtt://broker.local", "s9".to_string())
+            .expect("BUG: cannot create config");
+        assert_eq!(config.transport, Transport::Tcp);
+        assert_eq!(config.host, "broker.local");
+        assert_eq!(config.port, DEFAULT_PORT);
+        assert_eq!(config.topic("telemetry"), "bosminer/s9/telemetry");
+
+        let config = Config::new("mqtt://broker.local:1234", "s9".to_string())
+            .expect("BUG: cannot create config");
+        assert_eq!(config.port, 1234);
+
+        assert!(Config::new("http://broker.local", "s9".to_string()).is_err());
+        assert_eq!(
+            Config::new("mqtts://broker.local", "s9".to_string()).is_ok(),
+            cfg!(feature = "mqtt-tls")
+        );
+    }
+}
@@ -0,0 +1,227 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Encoding of MQTT 3.1.1 control packets. Only the subset needed for publishing messages with
+//! QoS 0 is supported.
+
+/// Control packet types (already shifted to the upper nibble of the fixed header)
+const CONNECT: u8 = 0x10;
+const CONNACK: u8 = 0x20;
+const PUBLISH: u8 = 0x30;
+const DISCONNECT: u8 = 0xe0;
+
+/// Protocol level of MQTT 3.1.1
+const PROTOCOL_LEVEL: u8 = 4;
+
+/// Flags of CONNECT packet
+const FLAG_CLEAN_SESSION: u8 = 0x02;
+const FLAG_WILL: u8 = 0x04;
+const FLAG_WILL_RETAIN: u8 = 0x20;
+const FLAG_PASSWORD: u8 = 0x40;
+const FLAG_USERNAME: u8 = 0x80;
+
+/// Flag of PUBLISH packet
+const FLAG_RETAIN: u8 = 0x01;
+
+/// Size of CONNACK packet
+pub const CONNACK_SIZE: usize = 4;
+
+/// Maximal value of remaining length field
+const MAX_REMAINING_LENGTH: usize = 268_435_455;
+
+/// Message which is published by the broker when connection is lost unexpectedly
+#[derive(Debug, Clone, PartialEq)]
+pub struct Will<'a> {
+    pub topic: &'a str,
+    pub payload: &'a [u8],
+    pub retain: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Connect<'a> {
+    pub client_id: &'a str,
+    pub keep_alive: u16,
+    pub username: Option<&'a str>,
+    pub password: Option<&'a str>,
+    pub will: Option<Will<'a>>,
+}
+
+fn put_remaining_length(buffer: &mut Vec<u8>, mut length: usize) {
+    assert!(
+        length <= MAX_REMAINING_LENGTH,
+        "BUG: MQTT packet is too long"
+    );
+    loop {
+        let mut byte = (length % 128) as u8;
+        length /= 128;
+        if length > 0 {
+            byte |= 0x80;
+        }
+        buffer.push(byte);
+        if length == 0 {
+            break;
+        }
+    }
+}
+
+fn put_bytes(buffer: &mut Vec<u8>, bytes: &[u8]) {
+    assert!(
+        bytes.len() <= u16::MAX as usize,
+        "BUG: MQTT field is too long"
+    );
+    buffer.extend_from_slice(&(bytes.len() as u16).to_be_bytes());
+    buffer.extend_from_slice(bytes);
+}
+
+/// Builds packet from fixed header and its body
+fn packet(header: u8, body: Vec<u8>) -> Vec<u8> {
+    let mut buffer = Vec::with_capacity(body.len() + 5);
+    buffer.push(header);
+    put_remaining_length(&mut buffer, body.len());
+    buffer.extend(body);
+    buffer
+}
+
+impl<'a> Connect<'a> {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut flags = FLAG_CLEAN_SESSION;
+        if let Some(will) = self.will.as_ref() {
+            flags |= FLAG_WILL;
+            if will.retain {
+                flags |= FLAG_WILL_RETAIN;
+            }
+        }
+        if self.username.is_some() {
+            flags |= FLAG_USERNAME;
+        }
+        if self.password.is_some() {
+            flags |= FLAG_PASSWORD;
+        }
+
+        let mut body = Vec::new();
+        put_bytes(&mut body, b"MQTT");
+        body.push(PROTOCOL_LEVEL);
+        body.push(flags);
+        body.extend_from_slice(&self.keep_alive.to_be_bytes());
+
+        put_bytes(&mut body, self.client_id.as_bytes());
+        if let Some(will) = self.will.as_ref() {
+            put_bytes(&mut body, will.topic.as_bytes());
+            put_bytes(&mut body, will.payload);
+        }
+        if let Some(username) = self.username {
+            put_bytes(&mut body, username.as_bytes());
+        }
+        if let Some(password) = self.password {
+            put_bytes(&mut body, password.as_bytes());
+        }
+        packet(CONNECT, body)
+    }
+}
+
+/// Encodes PUBLISH packet with QoS 0
+pub fn publish(topic: &str, payload: &[u8], retain: bool) -> Vec<u8> {
+    let mut body = Vec::with_capacity(topic.len() + payload.len() + 2);
+    put_bytes(&mut body, topic.as_bytes());
+    body.extend_from_slice(payload);
+    let flags = if retain { FLAG_RETAIN } else { 0 };
+    packet(PUBLISH | flags, body)
+}
+
+pub fn disconnect() -> Vec<u8> {
+    packet(DISCONNECT, vec![])
+}
+
+/// Parses CONNACK packet and returns error with the reason when connection has been refused
+pub fn parse_connack(buffer: &[u8; CONNACK_SIZE]) -> Result<(), String> {
+    if buffer[0] != CONNACK || buffer[1] != 2 {
+        return Err(format!("unexpected packet {:02x?}", buffer));
+    }
+    match buffer[3] {
+        0 => Ok(()),
+        1 => Err("unacceptable protocol version".to_string()),
+        2 => Err("identifier rejected".to_string()),
+        3 => Err("server unavailable".to_string()),
+        4 => Err("bad user name or password".to_string()),
+        5 => Err("not authorized".to_string()),
+        code => Err(format!("unknown return code {}", code)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_remaining_length() {
+        let expected: [(usize, &[u8]); 5] = [
+            (0, &[0x00]),
+            (127, &[0x7f]),
+            (128, &[0x80, 0x01]),
+            (16_383, &[0xff, 0x7f]),
+            (2_097_152, &[0x80, 0x80, 0x80, 0x01]),
+        ];
+        for (length, encoded) in expected.iter() {
+            let mut buffer = vec![];
+            put_remaining_length(&mut buffer, *length);
+            assert_eq!(&buffer[..], *encoded);
+        }
+    }
+
+    #[test]
+    fn test_connect() {
+        let connect = Connect {
+            client_id: "s9",
+            keep_alive: 60,
+            username: Some("u"),
+            password: Some("p"),
+            will: Some(Will {
+                topic: "t",
+                payload: b"x",
+                retain: true,
+            }),
+        };
+        assert_eq!(
+            connect.encode(),
+            vec![
+                0x10, 26, 0, 4, b'M', b'Q', b'T', b'T', 4, 0xe6, 0, 60, 0, 2, b's', b'9', 0, 1,
+                b't', 0, 1, b'x', 0, 1, b'u', 0, 1, b'p',
+            ]
+        );
+    }
+
+    #[test]
+    fn test_publish() {
+        assert_eq!(
+            publish("a/b", b"{}", true),
+            vec![0x31, 7, 0, 3, b'a', b'/', b'b', b'{', b'}']
+        );
+        assert_eq!(disconnect(), vec![0xe0, 0]);
+    }
+
+    #[test]
+    fn test_connack() {
+        assert_eq!(parse_connack(&[0x20, 2, 0, 0]), Ok(()));
+        assert!(parse_connack(&[0x20, 2, 0, 5]).is_err());
+        assert!(parse_connack(&[0x30, 2, 0, 0]).is_err());
+    }
+}
@@ -101,6 +101,11 @@ pub trait WorkSolver: Info + WorkSolverStats {
     }
     /// Return nominal/expected hashrate in hashes per second
     async fn get_nominal_hashrate(&self) -> Option<ii_bitcoin::HashesUnit>;
+    /// Optionally return current temperature in degree Celsius (e.g. hash chain temperature
+    /// measured by sensors on the hashboard)
+    async fn get_temperature(&self) -> Option<f32> {
+        None
+    }
 }
 
 pub trait WorkSolverStats: Stats {
@@ -138,6 +143,10 @@ impl<T: ?Sized + WorkSolver> WorkSolver for Arc<T> {
     async fn get_nominal_hashrate(&self) -> Option<ii_bitcoin::HashesUnit> {
         self.as_ref().get_nominal_hashrate().await
     }
+
+    async fn get_temperature(&self) -> Option<f32> {
+        self.as_ref().get_temperature().await
+    }
 }
 
 impl<T: ?Sized + WorkSolverStats> WorkSolverStats for Arc<T> {
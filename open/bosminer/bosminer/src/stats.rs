@@ -20,8 +20,14 @@
 // of such proprietary license or if you have any other questions, please
 // contact us at opensource@braiins.com.
 
+pub mod chip;
+pub mod histogram;
+pub mod latency;
+pub mod persistent;
+
 use ii_logging::macros::*;
 
+use crate::hub;
 use crate::node;
 use crate::stats;
 use crate::work;
@@ -31,15 +37,21 @@ use bosminer_macros::{ClientStats, MiningStats, WorkSolverStats};
 use ii_stats::WindowedTimeMean;
 
 use futures::lock::Mutex;
-use ii_async_compat::{futures, tokio};
-use tokio::time::delay_for;
+use ii_async_compat::{futures, runtime};
 
+use std::convert::TryFrom;
 use std::fmt::Debug;
 use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::sync::Mutex as StdMutex;
 use std::time;
 
 use once_cell::sync::Lazy;
 
+pub use chip::ChipMeters;
+pub use histogram::ShareHistogram;
+pub use latency::Latency;
+
 pub static TIME_MEAN_INTERVAL_5S: Lazy<time::Duration> = Lazy::new(|| time::Duration::from_secs(5));
 pub static TIME_MEAN_INTERVAL_1M: Lazy<time::Duration> =
     Lazy::new(|| time::Duration::from_secs(1 * 60));
@@ -95,6 +107,30 @@ pub struct MeterSnapshot {
 }
 
 impl MeterSnapshot {
+    fn new(intervals: &[time::Duration]) -> Self {
+        Self {
+            solutions: 0,
+            shares: Default::default(),
+            time_means: intervals
+                .iter()
+                .map(|&interval| WindowedTimeMean::new(interval))
+                .collect(),
+        }
+    }
+
+    fn account_solution(&mut self, target: &ii_bitcoin::Target, time: time::Instant) {
+        let kilo_hashes = ii_bitcoin::Shares::new(target)
+            .into_kilo_hashes()
+            .into_f64();
+
+        // TODO: what to do when number overflows
+        self.solutions += 1;
+        self.shares.account_solution(target);
+        for time_mean in &mut self.time_means {
+            time_mean.insert(kilo_hashes, time);
+        }
+    }
+
     fn get_time_mean(&self, interval: time::Duration) -> &WindowedTimeMean {
         self.time_means
             .iter()
@@ -156,14 +192,7 @@ pub struct Meter {
 impl Meter {
     pub fn new(intervals: &Vec<time::Duration>) -> Self {
         Self {
-            inner: Mutex::new(MeterSnapshot {
-                solutions: 0,
-                shares: Default::default(),
-                time_means: intervals
-                    .iter()
-                    .map(|&interval| WindowedTimeMean::new(interval))
-                    .collect(),
-            }),
+            inner: Mutex::new(MeterSnapshot::new(intervals)),
         }
     }
 
@@ -172,17 +201,7 @@ impl Meter {
     }
 
     pub(crate) async fn account_solution(&self, target: &ii_bitcoin::Target, time: time::Instant) {
-        let mut meter = self.inner.lock().await;
-        let kilo_hashes = ii_bitcoin::Shares::new(target)
-            .into_kilo_hashes()
-            .into_f64();
-
-        // TODO: what to do when number overflows
-        meter.solutions += 1;
-        meter.shares.account_solution(target);
-        for time_mean in &mut meter.time_means {
-            time_mean.insert(kilo_hashes, time);
-        }
+        self.inner.lock().await.account_solution(target, time);
     }
 }
 
@@ -197,7 +216,7 @@ pub struct LastShareSnapshot {
     /// Time when the last share has been submitted
     pub time: time::SystemTime,
     /// Difficulty of the last share
-    pub difficulty: usize,
+    pub difficulty: u64,
 }
 
 #[derive(Debug)]
@@ -214,15 +233,11 @@ impl LastShare {
             .map(|inner| Snapshot::new(inner))
     }
 
-    pub(crate) async fn account_solution(
-        &self,
-        target: &ii_bitcoin::Target,
-        time: time::SystemTime,
-    ) {
-        self.inner.lock().await.replace(LastShareSnapshot {
-            time,
-            difficulty: target.get_difficulty(),
-        });
+    pub(crate) async fn account_solution(&self, difficulty: u64, time: time::SystemTime) {
+        self.inner
+            .lock()
+            .await
+            .replace(LastShareSnapshot { time, difficulty });
     }
 }
 
@@ -234,34 +249,36 @@ impl Default for LastShare {
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct BestShareSnapshot {
+    /// Time when the best share has been found
+    pub time: time::SystemTime,
+    /// Difficulty of the best share, it easily exceeds 32 bits so it is not stored as `usize`
+    pub difficulty: u64,
+}
+
 #[derive(Debug)]
 pub struct BestShare {
-    inner: AtomicUsize,
+    inner: StdMutex<Option<BestShareSnapshot>>,
 }
 
 impl BestShare {
-    const INVALID_DIFFICULTY: usize = 0;
-    pub fn take_snapshot(&self) -> Option<Snapshot<usize>> {
-        let difficulty = self.inner.load(Ordering::Relaxed);
-        if difficulty == Self::INVALID_DIFFICULTY {
-            None
-        } else {
-            Some(Snapshot::new(difficulty))
-        }
+    pub fn take_snapshot(&self) -> Option<Snapshot<BestShareSnapshot>> {
+        self.inner
+            .lock()
+            .expect("BUG: cannot lock best share")
+            .clone()
+            .map(Snapshot::new)
     }
 
-    pub(crate) fn account_solution(&self, target: &ii_bitcoin::Target) {
-        let new_diff = target.get_difficulty();
-        let mut old_diff = self.inner.load(Ordering::Relaxed);
-
-        while old_diff < new_diff {
-            let prev_diff = self
-                .inner
-                .compare_and_swap(old_diff, new_diff, Ordering::Relaxed);
-            if old_diff == prev_diff {
-                break;
-            } else {
-                old_diff = prev_diff;
+    /// Returns true when the solution with share `difficulty` is a new best share
+    pub(crate) fn account_solution(&self, difficulty: u64, time: time::SystemTime) -> bool {
+        let mut inner = self.inner.lock().expect("BUG: cannot lock best share");
+        match inner.as_ref() {
+            Some(best_share) if best_share.difficulty >= difficulty => false,
+            _ => {
+                inner.replace(BestShareSnapshot { time, difficulty });
+                true
             }
         }
     }
@@ -270,7 +287,7 @@ impl BestShare {
 impl Default for BestShare {
     fn default() -> Self {
         Self {
-            inner: AtomicUsize::new(Self::INVALID_DIFFICULTY),
+            inner: StdMutex::new(None),
         }
     }
 }
@@ -411,6 +428,8 @@ pub trait Mining: Send + Sync {
     /// Information about last valid share with at least job difficulty
     fn last_share(&self) -> &LastShare;
     fn best_share(&self) -> &BestShare;
+    /// Histogram of real difficulties of all valid solutions on backend difficulty
+    fn share_histogram(&self) -> &ShareHistogram;
     /// Statistics for all valid blocks on network difficulty
     fn valid_network_diff(&self) -> &Meter;
     /// Statistics for all valid jobs on job/pool difficulty
@@ -419,6 +438,12 @@ pub trait Mining: Send + Sync {
     fn valid_backend_diff(&self) -> &Meter;
     /// Statistics for all invalid work on backend difficulty (backend/HW error)
     fn error_backend_diff(&self) -> &Meter;
+    /// Shares accepted by remote server
+    fn accepted(&self) -> &Meter;
+    /// Shares rejected by remote server
+    fn rejected(&self) -> &Meter;
+    /// Valid shares rejected by remote server or discarded due to some error
+    fn stale(&self) -> &Meter;
 }
 
 pub trait Client: Mining {
@@ -428,12 +453,11 @@ pub trait Client: Mining {
     fn invalid_jobs(&self) -> &CounterUsize;
     /// Number of work generated from jobs by rolling or with extra nonce
     fn generated_work(&self) -> &CounterU64;
-    /// Shares accepted by remote server
-    fn accepted(&self) -> &Meter;
-    /// Shares rejected by remote server
-    fn rejected(&self) -> &Meter;
-    /// Valid shares rejected by remote server or discarded due to some error
-    fn stale(&self) -> &Meter;
+    /// Round-trip time of share submissions
+    fn submit_latency(&self) -> &Latency;
+    /// Delay between receiving a new job and taking its first work by a work solver which
+    /// replaces the previous job in the hardware
+    fn job_latency(&self) -> &Latency;
 }
 
 pub trait WorkSolver: Mining {
@@ -441,6 +465,9 @@ pub trait WorkSolver: Mining {
     fn last_work_time(&self) -> &Timestamp;
     /// Number of work generated from jobs by rolling or with extra nonce
     fn generated_work(&self) -> &CounterU64;
+    /// Statistics for valid work on backend difficulty split by chips. They are accounted by
+    /// backends and stay empty when the backend cannot tell which chip found a solution.
+    fn valid_chip_backend_diff(&self) -> &ChipMeters;
 }
 
 #[derive(Debug, MiningStats)]
@@ -451,6 +478,8 @@ pub struct BasicMining {
     pub last_share: LastShare,
     #[member_best_share]
     pub best_share: BestShare,
+    #[member_share_histogram]
+    pub share_histogram: ShareHistogram,
     #[member_valid_network_diff]
     pub valid_network_diff: Meter,
     #[member_valid_job_diff]
@@ -459,6 +488,12 @@ pub struct BasicMining {
     pub valid_backend_diff: Meter,
     #[member_error_backend_diff]
     pub error_backend_diff: Meter,
+    #[member_accepted]
+    pub accepted: Meter,
+    #[member_rejected]
+    pub rejected: Meter,
+    #[member_stale]
+    pub stale: Meter,
 }
 
 impl BasicMining {
@@ -467,10 +502,14 @@ impl BasicMining {
             start_time,
             last_share: Default::default(),
             best_share: Default::default(),
+            share_histogram: Default::default(),
             valid_network_diff: Meter::new(&intervals),
             valid_job_diff: Meter::new(&intervals),
             valid_backend_diff: Meter::new(&intervals),
             error_backend_diff: Meter::new(&intervals),
+            accepted: Meter::new(&intervals),
+            rejected: Meter::new(&intervals),
+            stale: Default::default(),
         }
     }
 }
@@ -495,12 +534,18 @@ pub struct BasicClient {
     pub last_share: LastShare,
     #[member_best_share]
     pub best_share: BestShare,
+    #[member_share_histogram]
+    pub share_histogram: ShareHistogram,
     #[member_accepted]
     pub accepted: stats::Meter,
     #[member_rejected]
     pub rejected: stats::Meter,
     #[member_stale]
     pub stale: stats::Meter,
+    #[member_submit_latency]
+    pub submit_latency: Latency,
+    #[member_job_latency]
+    pub job_latency: Latency,
     #[member_valid_network_diff]
     pub valid_network_diff: Meter,
     #[member_valid_job_diff]
@@ -520,9 +565,12 @@ impl BasicClient {
             generated_work: Default::default(),
             last_share: Default::default(),
             best_share: Default::default(),
+            share_histogram: Default::default(),
             accepted: Meter::new(&intervals),
             rejected: Meter::new(&intervals),
             stale: Default::default(),
+            submit_latency: Default::default(),
+            job_latency: Default::default(),
             valid_network_diff: Meter::new(&intervals),
             valid_job_diff: Meter::new(&intervals),
             valid_backend_diff: Meter::new(&intervals),
@@ -549,6 +597,8 @@ pub struct BasicWorkSolver {
     pub last_share: LastShare,
     #[member_best_share]
     pub best_share: BestShare,
+    #[member_share_histogram]
+    pub share_histogram: ShareHistogram,
     #[member_valid_network_diff]
     pub valid_network_diff: Meter,
     #[member_valid_job_diff]
@@ -557,6 +607,14 @@ pub struct BasicWorkSolver {
     pub valid_backend_diff: Meter,
     #[member_error_backend_diff]
     pub error_backend_diff: Meter,
+    #[member_valid_chip_backend_diff]
+    pub valid_chip_backend_diff: ChipMeters,
+    #[member_accepted]
+    pub accepted: Meter,
+    #[member_rejected]
+    pub rejected: Meter,
+    #[member_stale]
+    pub stale: Meter,
 }
 
 impl BasicWorkSolver {
@@ -565,12 +623,17 @@ impl BasicWorkSolver {
             start_time,
             last_share: Default::default(),
             best_share: Default::default(),
+            share_histogram: Default::default(),
             last_work_time: Default::default(),
             generated_work: Default::default(),
             valid_network_diff: Meter::new(&intervals),
             valid_job_diff: Meter::new(&intervals),
             valid_backend_diff: Meter::new(&intervals),
             error_backend_diff: Meter::new(&intervals),
+            valid_chip_backend_diff: Default::default(),
+            accepted: Meter::new(&intervals),
+            rejected: Meter::new(&intervals),
+            stale: Default::default(),
         }
     }
 }
@@ -605,10 +668,13 @@ account_impl!(account_valid_network_diff, valid_network_diff);
 account_impl!(account_valid_job_diff, valid_job_diff);
 account_impl!(account_valid_backend_diff, valid_backend_diff);
 account_impl!(account_error_backend_diff, error_backend_diff);
+account_impl!(account_accepted, accepted);
+account_impl!(account_rejected, rejected);
+account_impl!(account_stale, stale);
 
 /// Describes which difficulty target a particular solution has met.
 /// It also determines in which statistics a particular solution should be accounted.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub enum DiffTargetType {
     Network,
     Job,
@@ -619,13 +685,26 @@ pub enum DiffTargetType {
 /// `met_diff_target_type`. Higher level DiffTargetType also belongs to all lower level types e.g.:
 /// - solution that meets DiffTargetType::Network also belongs to DiffTargetType::{Job, Backend}
 /// - solution that meets DiffTargetType::Job also belongs to DiffTargetType::Job accounts
+///
+/// The `share_difficulty` is the real difficulty of the solution hash as determined during its
+/// validation (see `ii_bitcoin::difficulty::share_difficulty`).
 pub async fn account_valid_solution(
     path: &node::Path,
     solution: &work::Solution,
+    share_difficulty: f64,
     time: time::Instant,
     met_diff_target_type: DiffTargetType,
 ) {
     account_valid_backend_diff(path, solution.backend_target(), time).await;
+    let backend_difficulty = solution.backend_target().get_difficulty();
+    let difficulty = share_difficulty as u64;
+    // difficulties which do not fit into `usize` end up in the last bucket of the histogram
+    let histogram_difficulty = usize::try_from(difficulty).unwrap_or(usize::MAX);
+    for node in path {
+        node.mining_stats()
+            .share_histogram()
+            .account_solution(backend_difficulty, histogram_difficulty);
+    }
     if met_diff_target_type != DiffTargetType::Backend {
         let target = solution.job_target();
         account_valid_job_diff(path, target, time).await;
@@ -638,21 +717,105 @@ pub async fn account_valid_solution(
             );
         }
         // use only job difficulty for accounting the last share even if a hash of the solution
-        // meets higher difficulties
+        // meets higher difficulties, the best share is the real difficulty of the hash
+        let job_difficulty = ii_bitcoin::difficulty::target_to_difficulty(target) as u64;
+        let now = time::SystemTime::now();
+        let mut new_best_shares = vec![];
         for node in path {
             let mining_stats = node.mining_stats();
             mining_stats
                 .last_share()
-                .account_solution(target, time::SystemTime::now())
+                .account_solution(job_difficulty, now)
                 .await;
-            mining_stats.best_share().account_solution(target);
+            if mining_stats.best_share().account_solution(difficulty, now) {
+                new_best_shares.push(format!("'{}'", node));
+            }
+        }
+        if !new_best_shares.is_empty() {
+            info!(
+                "New best share with difficulty {} for {}",
+                difficulty,
+                new_best_shares.join(", ")
+            );
         }
     }
 }
 
+/// Summary of shares submitted on behalf of a mining node. The same accounting is done for pool
+/// clients and for work solvers (e.g. hash chains) so it can be presented uniformly by the API and
+/// in logs.
+#[derive(Debug, Clone)]
+pub struct SharesSnapshot {
+    pub accepted: MeterSnapshot,
+    pub rejected: MeterSnapshot,
+    pub stale: MeterSnapshot,
+    pub last_share: Option<LastShareSnapshot>,
+    pub best_share: Option<BestShareSnapshot>,
+}
+
+impl SharesSnapshot {
+    pub async fn take<T: Mining + ?Sized>(mining_stats: &T) -> Self {
+        Self {
+            accepted: mining_stats.accepted().take_snapshot().await.clone(),
+            rejected: mining_stats.rejected().take_snapshot().await.clone(),
+            stale: mining_stats.stale().take_snapshot().await.clone(),
+            last_share: mining_stats
+                .last_share()
+                .take_snapshot()
+                .await
+                .map(|share| share.clone()),
+            best_share: mining_stats
+                .best_share()
+                .take_snapshot()
+                .map(|share| share.clone()),
+        }
+    }
+
+    /// All shares which have been either submitted or discarded as stale
+    pub fn total_shares(&self) -> f64 {
+        self.accepted.shares.as_f64() + self.rejected.shares.as_f64() + self.stale.shares.as_f64()
+    }
+
+    fn ratio(&self, shares: &ii_bitcoin::Shares) -> f64 {
+        let total_shares = self.total_shares();
+        if total_shares != 0.0 {
+            shares.as_f64() / total_shares * 100.0
+        } else {
+            0.0
+        }
+    }
+
+    /// Percentage of rejected shares out of all shares
+    pub fn rejected_ratio(&self) -> f64 {
+        self.ratio(&self.rejected.shares)
+    }
+
+    /// Percentage of stale shares out of all shares
+    pub fn stale_ratio(&self) -> f64 {
+        self.ratio(&self.stale.shares)
+    }
+}
+
+impl std::fmt::Display for SharesSnapshot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "accepted: {}, rejected: {} ({:.2} %), stale: {} ({:.2} %), best share: {}",
+            self.accepted.solutions,
+            self.rejected.solutions,
+            self.rejected_ratio(),
+            self.stale.solutions,
+            self.stale_ratio(),
+            self.best_share
+                .as_ref()
+                .map_or(0, |best_share| best_share.difficulty)
+        )
+    }
+}
+
 pub async fn mining_task(node: node::DynInfo, interval: time::Duration) {
-    loop {
-        delay_for(time::Duration::from_secs(1)).await;
+    let mut log_interval = runtime::Interval::new(time::Duration::from_secs(1));
+    while log_interval.tick().await.is_some() {
         let valid_job_diff = node.mining_stats().valid_job_diff().take_snapshot().await;
         let valid_backend_diff = node
             .mining_stats()
@@ -669,3 +832,47 @@ pub async fn mining_task(node: node::DynInfo, interval: time::Duration) {
         );
     }
 }
+
+/// Periodically log share statistics of all pool clients and work solvers
+pub async fn shares_task(core: Arc<hub::Core>, interval: time::Duration) {
+    let mut log_interval = runtime::Interval::new(interval);
+    while log_interval.tick().await.is_some() {
+        for group in core.get_client_manager().get_groups().await {
+            for client in group.get_clients().await {
+                let shares = SharesSnapshot::take(client.stats()).await;
+                info!(
+                    "Shares for pool '{}': {}",
+                    client.descriptor().await.get_url(true, true, false),
+                    shares
+                );
+            }
+        }
+        for work_solver in core.get_work_solvers().await {
+            let shares = SharesSnapshot::take(work_solver.mining_stats()).await;
+            info!("Shares for '{}': {}", work_solver, shares);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_best_share_above_u32() {
+        let best_share = BestShare::default();
+        let now = time::SystemTime::now();
+        let difficulty = u64::from(u32::MAX) + 1;
+
+        assert!(best_share.account_solution(u64::from(u32::MAX), now));
+        assert!(best_share.account_solution(difficulty, now));
+        assert!(!best_share.account_solution(difficulty - 1, now));
+        assert_eq!(
+            best_share
+                .take_snapshot()
+                .expect("BUG: missing best share")
+                .difficulty,
+            difficulty
+        );
+    }
+}
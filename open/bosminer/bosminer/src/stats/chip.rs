@@ -0,0 +1,128 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Hashrate statistics of individual chips of a work solver
+//!
+//! Only backends which can tell which chip has found a solution account solutions to these
+//! meters. Meters of chips are created on demand so the number of chips does not have to be
+//! known in advance.
+
+use crate::stats::{MeterSnapshot, Snapshot};
+
+use futures::lock::Mutex;
+use ii_async_compat::futures;
+
+use once_cell::sync::Lazy;
+
+use std::sync::Arc;
+use std::time;
+
+static DEFAULT_CHIP_TIME_MEAN_INTERVALS: Lazy<Vec<time::Duration>> = Lazy::new(|| {
+    vec![
+        *super::TIME_MEAN_INTERVAL_1M,
+        *super::TIME_MEAN_INTERVAL_15M,
+        *super::TIME_MEAN_INTERVAL_24H,
+    ]
+});
+
+#[derive(Debug)]
+struct Inner {
+    intervals: Vec<time::Duration>,
+    /// Meters indexed by chip
+    chips: Vec<MeterSnapshot>,
+}
+
+/// Meters of valid work on backend difficulty split by chips. Cloning is cheap and all clones
+/// share the same meters.
+#[derive(Debug, Clone)]
+pub struct ChipMeters {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl ChipMeters {
+    pub fn new(intervals: &[time::Duration]) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Inner {
+                intervals: intervals.to_vec(),
+                chips: vec![],
+            })),
+        }
+    }
+
+    /// Snapshots of meters indexed by chip. Chips which have not found any solution yet may be
+    /// missing at the end.
+    pub async fn take_snapshot(&self) -> Snapshot<Vec<MeterSnapshot>> {
+        Snapshot::new(self.inner.lock().await.chips.clone())
+    }
+
+    pub async fn account_solution(
+        &self,
+        chip_idx: usize,
+        target: &ii_bitcoin::Target,
+        time: time::Instant,
+    ) {
+        let mut inner = self.inner.lock().await;
+        if inner.chips.len() <= chip_idx {
+            let meter = MeterSnapshot::new(&inner.intervals);
+            inner.chips.resize(chip_idx + 1, meter);
+        }
+        inner.chips[chip_idx].account_solution(target, time);
+    }
+}
+
+impl Default for ChipMeters {
+    fn default() -> Self {
+        Self::new(DEFAULT_CHIP_TIME_MEAN_INTERVALS.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use ii_async_compat::tokio;
+
+    #[tokio::test]
+    async fn test_chip_meters() {
+        let meters = ChipMeters::default();
+        assert!(meters.take_snapshot().await.is_empty());
+
+        let now = time::Instant::now();
+        let target = ii_bitcoin::Target::from_pool_difficulty(1);
+        meters.account_solution(2, &target, now).await;
+        meters.account_solution(2, &target, now).await;
+        meters.account_solution(0, &target, now).await;
+
+        let snapshot = meters.take_snapshot().await;
+        assert_eq!(snapshot.len(), 3);
+        assert_eq!(snapshot[0].solutions, 1);
+        assert_eq!(snapshot[1].solutions, 0);
+        assert_eq!(snapshot[2].solutions, 2);
+        let interval = *super::super::TIME_MEAN_INTERVAL_1M;
+        assert_eq!(snapshot[1].to_kilo_hashes(interval, now).into_f64(), 0.0);
+        assert!(snapshot[2].to_kilo_hashes(interval, now).into_f64() > 0.0);
+
+        // clones share the meters
+        meters.clone().account_solution(1, &target, now).await;
+        assert_eq!(meters.take_snapshot().await[1].solutions, 1);
+    }
+}
@@ -0,0 +1,266 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Histogram of difficulties of solved shares and "luck" statistics derived from it.
+//!
+//! Every valid solution meets backend target so the probability that its difficulty is at least
+//! `d` is `backend_difficulty / d`. Comparing real counts in histogram buckets with expected ones
+//! shows whether the hardware produces correctly distributed results and whether the accounting
+//! of shares is consistent.
+
+use crate::stats::Snapshot;
+
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+/// Number of buckets which covers all difficulties representable by `u64`
+pub const BUCKET_COUNT: usize = 64;
+
+/// Share histogram with logarithmic buckets. The bucket `i` counts shares with difficulty in the
+/// range `[2^i, 2^(i+1))`.
+#[derive(Debug)]
+pub struct ShareHistogram {
+    buckets: Vec<AtomicU64>,
+    /// The lowest backend difficulty of accounted solutions
+    backend_difficulty: AtomicUsize,
+    /// Sum of backend difficulties of accounted solutions
+    diff1_work: AtomicU64,
+    /// The highest difficulty of accounted solutions
+    best_difficulty: AtomicUsize,
+}
+
+impl ShareHistogram {
+    #[inline]
+    fn bucket_index(difficulty: usize) -> usize {
+        assert_ne!(difficulty, 0, "BUG: zero difficulty");
+        (usize::MAX.count_ones() - 1 - difficulty.leading_zeros()) as usize
+    }
+
+    pub fn take_snapshot(&self) -> Snapshot<HistogramSnapshot> {
+        Snapshot::new(HistogramSnapshot {
+            counts: self
+                .buckets
+                .iter()
+                .map(|bucket| bucket.load(Ordering::Relaxed))
+                .collect(),
+            backend_difficulty: self.backend_difficulty.load(Ordering::Relaxed),
+            diff1_work: self.diff1_work.load(Ordering::Relaxed),
+            best_difficulty: self.best_difficulty.load(Ordering::Relaxed),
+        })
+    }
+
+    /// Accounts a solution with real `difficulty` which has been solved at `backend_difficulty`
+    pub(crate) fn account_solution(&self, backend_difficulty: usize, difficulty: usize) {
+        // Solution without valid hash or target can only be result of some hardware failure
+        // which is accounted elsewhere
+        if backend_difficulty == 0 || difficulty < backend_difficulty {
+            return;
+        }
+        self.buckets[Self::bucket_index(difficulty)].fetch_add(1, Ordering::Relaxed);
+        self.backend_difficulty
+            .fetch_min(backend_difficulty, Ordering::Relaxed);
+        self.diff1_work
+            .fetch_add(backend_difficulty as u64, Ordering::Relaxed);
+        self.best_difficulty
+            .fetch_max(difficulty, Ordering::Relaxed);
+    }
+}
+
+impl Default for ShareHistogram {
+    fn default() -> Self {
+        Self {
+            buckets: (0..BUCKET_COUNT).map(|_| AtomicU64::new(0)).collect(),
+            backend_difficulty: AtomicUsize::new(usize::MAX),
+            diff1_work: AtomicU64::new(0),
+            best_difficulty: AtomicUsize::new(0),
+        }
+    }
+}
+
+/// Real and expected number of shares in one histogram bucket
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bucket {
+    /// The lowest difficulty of shares accounted in this bucket
+    pub difficulty: u64,
+    pub count: u64,
+    pub expected: f64,
+}
+
+impl Bucket {
+    /// Ratio of real and expected number of shares
+    pub fn luck(&self) -> f64 {
+        if self.expected > 0.0 {
+            self.count as f64 / self.expected
+        } else {
+            0.0
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HistogramSnapshot {
+    counts: Vec<u64>,
+    backend_difficulty: usize,
+    diff1_work: u64,
+    best_difficulty: usize,
+}
+
+impl HistogramSnapshot {
+    /// Total number of accounted solutions
+    pub fn solutions(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// The highest difficulty of accounted solutions
+    pub fn best_difficulty(&self) -> Option<usize> {
+        if self.best_difficulty > 0 {
+            Some(self.best_difficulty)
+        } else {
+            None
+        }
+    }
+
+    /// Amount of work in difficulty 1 shares done for all solutions. Each solution represents
+    /// work of its backend difficulty which may differ between solutions.
+    pub fn diff1_work(&self) -> f64 {
+        self.diff1_work as f64
+    }
+
+    /// The best difficulty that is expected to be found with current amount of work. There should
+    /// be one share with at least this difficulty on average.
+    pub fn expected_best_difficulty(&self) -> f64 {
+        self.diff1_work()
+    }
+
+    /// Ratio of the best and expected best difficulty
+    pub fn best_difficulty_luck(&self) -> f64 {
+        match self.best_difficulty() {
+            Some(best_difficulty) => best_difficulty as f64 / self.expected_best_difficulty(),
+            None => 0.0,
+        }
+    }
+
+    /// Returns all buckets from the lowest backend difficulty to the best share
+    pub fn buckets(&self) -> Vec<Bucket> {
+        let best_difficulty = match self.best_difficulty() {
+            Some(best_difficulty) => best_difficulty,
+            None => return vec![],
+        };
+        let diff1_work = self.diff1_work();
+        let first = ShareHistogram::bucket_index(self.backend_difficulty);
+        let last = ShareHistogram::bucket_index(best_difficulty);
+
+        (first..=last)
+            .map(|i| {
+                let lower = (1u128 << i) as f64;
+                let upper = (1u128 << (i + 1)) as f64;
+                // backend difficulty can be in the middle of the first bucket
+                let lower_bound = lower.max(self.backend_difficulty as f64);
+                Bucket {
+                    difficulty: 1u64 << i,
+                    count: self.counts[i],
+                    expected: diff1_work * (1.0 / lower_bound - 1.0 / upper),
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_bucket_index() {
+        assert_eq!(ShareHistogram::bucket_index(1), 0);
+        assert_eq!(ShareHistogram::bucket_index(64), 6);
+        assert_eq!(ShareHistogram::bucket_index(127), 6);
+        assert_eq!(
+            ShareHistogram::bucket_index(usize::MAX),
+            usize::MAX.count_ones() as usize - 1
+        );
+    }
+
+    #[test]
+    fn test_histogram() {
+        let histogram = ShareHistogram::default();
+        assert!(histogram.take_snapshot().buckets().is_empty());
+
+        // ideal distribution of 8 solutions at backend difficulty 64
+        for difficulty in &[64, 70, 80, 100, 130, 200, 300, 600] {
+            histogram.account_solution(64, *difficulty);
+        }
+        // invalid solution is ignored
+        histogram.account_solution(64, 10);
+
+        let snapshot = histogram.take_snapshot();
+        assert_eq!(snapshot.solutions(), 8);
+        assert_eq!(snapshot.best_difficulty(), Some(600));
+        assert_eq!(snapshot.diff1_work(), 512.0);
+        assert_eq!(snapshot.best_difficulty_luck(), 600.0 / 512.0);
+
+        let buckets = snapshot.buckets();
+        assert_eq!(
+            buckets,
+            vec![
+                Bucket {
+                    difficulty: 64,
+                    count: 4,
+                    expected: 4.0
+                },
+                Bucket {
+                    difficulty: 128,
+                    count: 2,
+                    expected: 2.0
+                },
+                Bucket {
+                    difficulty: 256,
+                    count: 1,
+                    expected: 1.0
+                },
+                Bucket {
+                    difficulty: 512,
+                    count: 1,
+                    expected: 0.5
+                },
+            ]
+        );
+        assert_eq!(buckets[0].luck(), 1.0);
+        assert_eq!(buckets[3].luck(), 2.0);
+    }
+
+    #[test]
+    fn test_diff1_work() {
+        let histogram = ShareHistogram::default();
+        assert_eq!(histogram.take_snapshot().diff1_work(), 0.0);
+
+        // backend difficulty has been changed between solutions
+        histogram.account_solution(64, 100);
+        histogram.account_solution(256, 300);
+        histogram.account_solution(256, 1000);
+
+        let snapshot = histogram.take_snapshot();
+        assert_eq!(snapshot.solutions(), 3);
+        assert_eq!(snapshot.diff1_work(), 576.0);
+        assert_eq!(snapshot.expected_best_difficulty(), 576.0);
+        assert_eq!(snapshot.buckets()[0].difficulty, 64);
+    }
+}
@@ -0,0 +1,172 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Latency statistics with percentiles computed from recent samples
+
+use crate::stats::Snapshot;
+
+use futures::lock::Mutex;
+use ii_async_compat::futures;
+
+use ii_stats::{Percentiles, PercentilesSnapshot};
+
+use std::time;
+
+/// Only samples from this period are used for computation of percentiles
+pub const SAMPLE_WINDOW: time::Duration = time::Duration::from_secs(10 * 60);
+
+/// Maximal number of samples kept for computation of percentiles
+pub const MAX_SAMPLES: usize = 1024;
+
+#[derive(Debug)]
+struct Inner {
+    /// Recent samples for computation of percentiles
+    samples: Percentiles<time::Duration>,
+    count: u64,
+    total: time::Duration,
+    min: Option<time::Duration>,
+    max: time::Duration,
+}
+
+impl Default for Inner {
+    fn default() -> Self {
+        Self {
+            samples: Percentiles::new(SAMPLE_WINDOW, MAX_SAMPLES),
+            count: 0,
+            total: Default::default(),
+            min: None,
+            max: Default::default(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Latency {
+    inner: Mutex<Inner>,
+}
+
+impl Latency {
+    pub async fn take_snapshot(&self) -> Snapshot<LatencySnapshot> {
+        let mut inner = self.inner.lock().await;
+        Snapshot::new(LatencySnapshot {
+            count: inner.count,
+            total: inner.total,
+            min: inner.min.unwrap_or_default(),
+            max: inner.max,
+            recent: inner.samples.take_snapshot(time::Instant::now()),
+        })
+    }
+
+    pub(crate) async fn account(&self, latency: time::Duration, time: time::Instant) {
+        let mut inner = self.inner.lock().await;
+        inner.count += 1;
+        inner.total += latency;
+        inner.min = Some(inner.min.map_or(latency, |min| min.min(latency)));
+        inner.max = inner.max.max(latency);
+        inner.samples.insert(latency, time);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LatencySnapshot {
+    /// Number of all measurements
+    pub count: u64,
+    /// Sum of all measured latencies
+    pub total: time::Duration,
+    pub min: time::Duration,
+    pub max: time::Duration,
+    /// Samples measured during last `SAMPLE_WINDOW`
+    recent: PercentilesSnapshot<time::Duration>,
+}
+
+impl LatencySnapshot {
+    pub fn average(&self) -> Option<time::Duration> {
+        if self.count > 0 {
+            Some(self.total.div_f64(self.count as f64))
+        } else {
+            None
+        }
+    }
+
+    /// Number of samples used for computation of percentiles
+    pub fn recent_samples(&self) -> usize {
+        self.recent.len()
+    }
+
+    /// Returns `percentile` (in range 0 to 100) of recent samples using nearest-rank method
+    pub fn percentile(&self, percentile: f64) -> Option<time::Duration> {
+        self.recent.percentile(percentile)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use ii_async_compat::tokio;
+
+    #[tokio::test]
+    async fn test_latency() {
+        let latency = Latency::default();
+        let snapshot = latency.take_snapshot().await;
+        assert_eq!(snapshot.average(), None);
+        assert_eq!(snapshot.percentile(50.0), None);
+
+        let now = time::Instant::now();
+        for ms in (1..=100).rev() {
+            latency.account(time::Duration::from_millis(ms), now).await;
+        }
+        let snapshot = latency.take_snapshot().await;
+        assert_eq!(snapshot.count, 100);
+        assert_eq!(snapshot.min, time::Duration::from_millis(1));
+        assert_eq!(snapshot.max, time::Duration::from_millis(100));
+        assert_eq!(
+            snapshot.average(),
+            Some(time::Duration::from_micros(50_500))
+        );
+        assert_eq!(snapshot.recent_samples(), 100);
+        assert_eq!(
+            snapshot.percentile(50.0),
+            Some(time::Duration::from_millis(50))
+        );
+        assert_eq!(
+            snapshot.percentile(99.0),
+            Some(time::Duration::from_millis(99))
+        );
+        assert_eq!(
+            snapshot.percentile(100.0),
+            Some(time::Duration::from_millis(100))
+        );
+        assert_eq!(
+            snapshot.percentile(0.0),
+            Some(time::Duration::from_millis(1))
+        );
+
+        // old samples are not used for percentiles
+        latency
+            .account(time::Duration::from_secs(1), now + SAMPLE_WINDOW * 2)
+            .await;
+        let snapshot = latency.take_snapshot().await;
+        assert_eq!(snapshot.count, 101);
+        assert_eq!(snapshot.recent_samples(), 1);
+    }
+}
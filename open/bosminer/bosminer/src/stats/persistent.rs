@@ -0,0 +1,602 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! This module keeps cumulative mining statistics across miner restarts. The statistics are
+//! periodically written to a file with relatively long period to reduce wear of flash memory.
+//! The file is always replaced atomically so a power loss cannot leave it half written. The last
+//! statistics are also stored when the miner is terminated.
+
+use ii_logging::macros::*;
+
+use crate::client;
+use crate::file;
+use crate::hub;
+use crate::node::Stats as _;
+use crate::stats;
+
+use ii_async_compat::runtime;
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::{BTreeMap, VecDeque};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time;
+
+/// Default period between two writes of persistent statistics
+pub const DEFAULT_STORE_INTERVAL: time::Duration = time::Duration::from_secs(15 * 60);
+
+/// Suffix of a copy of stored statistics which cannot be parsed
+pub const CORRUPTED_SUFFIX: &str = ".corrupt";
+
+/// Maximal number of records kept in uptime history
+pub const UPTIME_HISTORY_SIZE: usize = 32;
+
+/// Describes one run of the miner
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct UptimeRecord {
+    /// Unix time (in seconds) when the miner has been started
+    pub start_time: u64,
+    /// Duration of the run in seconds
+    pub duration: u64,
+}
+
+/// The best share found by a miner, pool or hash chain
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct BestShareRecord {
+    /// Real difficulty of the share hash, it easily exceeds 32 bits so it is not stored as `usize`
+    pub difficulty: u64,
+    /// Unix time (in seconds) when the share has been found
+    pub time: u64,
+}
+
+impl BestShareRecord {
+    pub fn from_stats(best_share: &stats::BestShare) -> Option<Self> {
+        best_share.take_snapshot().map(|best_share| Self {
+            difficulty: best_share.difficulty,
+            time: best_share
+                .time
+                .duration_since(time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        })
+    }
+
+    /// Returns the record with higher difficulty
+    pub fn max(self, other: Self) -> Self {
+        if other.difficulty > self.difficulty {
+            other
+        } else {
+            self
+        }
+    }
+}
+
+/// Merges records of the same pools or hash chains
+fn merge_best_shares(
+    base: &BTreeMap<String, BestShareRecord>,
+    session: &BTreeMap<String, BestShareRecord>,
+) -> BTreeMap<String, BestShareRecord> {
+    let mut best_shares = base.clone();
+    for (name, record) in session {
+        let best_share = best_shares.entry(name.clone()).or_default();
+        *best_share = best_share.max(*record);
+    }
+    best_shares
+}
+
+/// Work done for a pool measured in shares (sums of difficulties of submitted solutions)
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(default)]
+pub struct PoolRecord {
+    /// All shares accepted by the pool
+    pub accepted_shares: u64,
+    /// All shares rejected by the pool
+    pub rejected_shares: u64,
+    /// Time connected to the pool in seconds
+    pub uptime: u64,
+}
+
+impl PoolRecord {
+    pub async fn from_client(client: &client::Handle) -> Self {
+        let client_stats = client.stats();
+        Self {
+            accepted_shares: client_stats.accepted().take_snapshot().await.shares.value(),
+            rejected_shares: client_stats.rejected().take_snapshot().await.shares.value(),
+            uptime: client.connection_uptime().as_secs(),
+        }
+    }
+
+    pub fn merge(self, other: Self) -> Self {
+        Self {
+            accepted_shares: self.accepted_shares + other.accepted_shares,
+            rejected_shares: self.rejected_shares + other.rejected_shares,
+            uptime: self.uptime + other.uptime,
+        }
+    }
+
+    /// Ratio of shares accepted by the pool to `total_shares` accepted by all pools
+    pub fn share_ratio(&self, total_shares: u64) -> f64 {
+        if total_shares > 0 {
+            self.accepted_shares as f64 / total_shares as f64
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Sums shares accepted by all pools
+pub fn total_accepted_shares(pools: &BTreeMap<String, PoolRecord>) -> u64 {
+    pools.values().map(|record| record.accepted_shares).sum()
+}
+
+/// Merges records of the same pools
+fn merge_pools(
+    base: &BTreeMap<String, PoolRecord>,
+    session: &BTreeMap<String, PoolRecord>,
+) -> BTreeMap<String, PoolRecord> {
+    let mut pools = base.clone();
+    for (url, record) in session {
+        let pool = pools.entry(url.clone()).or_default();
+        *pool = pool.merge(*record);
+    }
+    pools
+}
+
+/// Cumulative statistics over all runs of the miner
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct Lifetime {
+    /// Number of valid solutions at job (pool) difficulty
+    pub solutions: u64,
+    /// All shares of valid solutions at job (pool) difficulty
+    pub shares: u64,
+    /// Number of solutions accepted by remote servers
+    pub accepted_solutions: u64,
+    /// All shares accepted by remote servers
+    pub accepted_shares: u64,
+    /// The highest difficulty of a share ever found
+    pub best_share: u64,
+    /// Unix time (in seconds) when the best share has been found
+    pub best_share_time: u64,
+    /// The best shares of pools identified by their URL
+    pub pool_best_shares: BTreeMap<String, BestShareRecord>,
+    /// The best shares of hash chains identified by their name
+    pub chain_best_shares: BTreeMap<String, BestShareRecord>,
+    /// Work done for pools identified by their URL
+    pub pools: BTreeMap<String, PoolRecord>,
+    /// Total time of mining in seconds
+    pub uptime: u64,
+    /// Last runs of the miner with the most recent one at the end
+    pub uptime_history: VecDeque<UptimeRecord>,
+}
+
+impl Lifetime {
+    /// Creates new lifetime statistics by adding statistics from current `session` to already
+    /// stored ones
+    pub fn merge(&self, session: &Session) -> Self {
+        let mut uptime_history = self.uptime_history.clone();
+        uptime_history.push_back(UptimeRecord {
+            start_time: session.start_time,
+            duration: session.uptime,
+        });
+        while uptime_history.len() > UPTIME_HISTORY_SIZE {
+            uptime_history.pop_front();
+        }
+        let best_share = self.get_best_share().max(session.best_share);
+
+        Self {
+            solutions: self.solutions + session.solutions,
+            shares: self.shares + session.shares,
+            accepted_solutions: self.accepted_solutions + session.accepted_solutions,
+            accepted_shares: self.accepted_shares + session.accepted_shares,
+            best_share: best_share.difficulty,
+            best_share_time: best_share.time,
+            pool_best_shares: merge_best_shares(&self.pool_best_shares, &session.pool_best_shares),
+            chain_best_shares: merge_best_shares(
+                &self.chain_best_shares,
+                &session.chain_best_shares,
+            ),
+            pools: merge_pools(&self.pools, &session.pools),
+            uptime: self.uptime + session.uptime,
+            uptime_history,
+        }
+    }
+
+    pub fn get_best_share(&self) -> BestShareRecord {
+        BestShareRecord {
+            difficulty: self.best_share,
+            time: self.best_share_time,
+        }
+    }
+}
+
+/// Statistics gathered from the current run of the miner
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Session {
+    pub start_time: u64,
+    pub solutions: u64,
+    pub shares: u64,
+    pub accepted_solutions: u64,
+    pub accepted_shares: u64,
+    pub best_share: BestShareRecord,
+    pub pool_best_shares: BTreeMap<String, BestShareRecord>,
+    pub chain_best_shares: BTreeMap<String, BestShareRecord>,
+    pub pools: BTreeMap<String, PoolRecord>,
+    pub uptime: u64,
+}
+
+impl Session {
+    /// Collects statistics from frontend and all registered clients
+    pub async fn collect(core: &hub::Core, start_time: time::SystemTime) -> Self {
+        let mining_stats = core.frontend.mining_stats();
+        let valid_job_diff = mining_stats.valid_job_diff().take_snapshot().await;
+        let best_share = BestShareRecord::from_stats(mining_stats.best_share()).unwrap_or_default();
+
+        let mut accepted_solutions = 0;
+        let mut accepted_shares = 0;
+        let mut pool_best_shares = BTreeMap::new();
+        let mut pools = BTreeMap::new();
+        for group in core.get_client_manager().get_groups().await {
+            for client in group.get_clients().await {
+                let url = client.descriptor().await.get_full_url();
+                let accepted = client.stats().accepted().take_snapshot().await;
+                accepted_solutions += accepted.solutions;
+                accepted_shares += accepted.shares.value();
+                if let Some(record) = BestShareRecord::from_stats(client.stats().best_share()) {
+                    pool_best_shares.insert(url.clone(), record);
+                }
+                let record = PoolRecord::from_client(&client).await;
+                let pool: &mut PoolRecord = pools.entry(url).or_default();
+                *pool = pool.merge(record);
+            }
+        }
+        let mut chain_best_shares = BTreeMap::new();
+        for work_solver in core.get_work_solvers().await {
+            if let Some(record) =
+                BestShareRecord::from_stats(work_solver.mining_stats().best_share())
+            {
+                chain_best_shares.insert(work_solver.to_string(), record);
+            }
+        }
+
+        let elapsed = mining_stats.start_time().elapsed();
+        Self {
+            start_time: start_time
+                .duration_since(time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            solutions: valid_job_diff.solutions,
+            shares: valid_job_diff.shares.value(),
+            accepted_solutions,
+            accepted_shares,
+            best_share,
+            pool_best_shares,
+            chain_best_shares,
+            pools,
+            uptime: elapsed.as_secs(),
+        }
+    }
+}
+
+/// File storage for lifetime statistics
+#[derive(Debug, Clone)]
+pub struct Storage {
+    path: PathBuf,
+}
+
+impl Storage {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Loads lifetime statistics. Returns `None` when the statistics have not been stored yet.
+    pub fn load(&self) -> io::Result<Option<Lifetime>> {
+        match fs::read(&self.path) {
+            Ok(content) => Ok(Some(serde_json::from_slice(&content)?)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Keeps a copy of statistics which cannot be loaded next to the original file so that they
+    /// are not lost when the file is replaced by the next store. Returns path of the copy.
+    pub fn keep_corrupted(&self) -> io::Result<PathBuf> {
+        let mut path = self.path.clone().into_os_string();
+        path.push(CORRUPTED_SUFFIX);
+        let path = PathBuf::from(path);
+        fs::copy(&self.path, &path)?;
+        Ok(path)
+    }
+
+    /// Atomically replaces stored statistics
+    pub fn store(&self, lifetime: &Lifetime) -> io::Result<()> {
+        file::write_atomically(&self.path, &serde_json::to_vec(lifetime)?)
+    }
+}
+
+/// Restores lifetime statistics stored by previous runs of the miner
+fn restore(storage: &Storage) -> Lifetime {
+    let base = match storage.load() {
+        Ok(lifetime) => lifetime.unwrap_or_default(),
+        Err(e) => {
+            warn!(
+                "Cannot restore persistent statistics from '{}': {}",
+                storage.path.display(),
+                e
+            );
+            // parsing errors are converted to these kinds by `serde_json`
+            if let io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof = e.kind() {
+                match storage.keep_corrupted() {
+                    Ok(path) => warn!(
+                        "Corrupted persistent statistics are kept in '{}' and will be replaced",
+                        path.display()
+                    ),
+                    Err(e) => warn!("Cannot keep corrupted persistent statistics: {}", e),
+                }
+            }
+            Default::default()
+        }
+    };
+    info!(
+        "Restored lifetime statistics: {} shares, best share {} (found at {}), uptime {} s",
+        base.shares, base.best_share, base.best_share_time, base.uptime
+    );
+    for (name, record) in base.chain_best_shares.iter() {
+        info!(
+            "Lifetime best share of '{}': {} (found at {})",
+            name, record.difficulty, record.time
+        );
+    }
+    for (url, record) in base.pools.iter() {
+        info!(
+            "Lifetime shares of '{}': {} accepted, {} rejected, uptime {} s",
+            url, record.accepted_shares, record.rejected_shares, record.uptime
+        );
+    }
+    base
+}
+
+/// Logs actual split of work between pools in the current session and over the whole lifetime
+fn log_share_ratios(session: &Session, lifetime: &Lifetime) {
+    let session_shares = total_accepted_shares(&session.pools);
+    let lifetime_shares = total_accepted_shares(&lifetime.pools);
+    for (url, record) in session.pools.iter() {
+        info!(
+            "Share ratio of '{}': {:.2}% in session, {:.2}% in lifetime",
+            url,
+            record.share_ratio(session_shares) * 100.0,
+            lifetime
+                .pools
+                .get(url)
+                .map_or(0.0, |record| record.share_ratio(lifetime_shares))
+                * 100.0
+        );
+    }
+}
+
+/// Keeps statistics restored from previous runs of the miner and stores them merged with
+/// statistics of the current session
+pub struct Recorder {
+    core: Arc<hub::Core>,
+    storage: Storage,
+    base: Arc<Lifetime>,
+    start_time: time::SystemTime,
+}
+
+impl Recorder {
+    pub fn new(core: Arc<hub::Core>, storage: Storage) -> Self {
+        let base = Arc::new(restore(&storage));
+        Self {
+            core,
+            storage,
+            base,
+            start_time: time::SystemTime::now(),
+        }
+    }
+
+    /// Statistics restored from previous runs of the miner
+    pub fn base(&self) -> Arc<Lifetime> {
+        self.base.clone()
+    }
+
+    /// Stores statistics of the current session merged with `base` statistics
+    pub async fn store(&self) {
+        let session = Session::collect(&self.core, self.start_time).await;
+        let lifetime = self.base.merge(&session);
+        log_share_ratios(&session, &lifetime);
+        let storage = self.storage.clone();
+        // writing of the file is blocking so it must not block the regular threadpool
+        let result = runtime::spawn_blocking(move || storage.store(&lifetime))
+            .await
+            .expect("BUG: storing of persistent statistics failed");
+        if let Err(e) = result {
+            warn!(
+                "Cannot store persistent statistics to '{}': {}",
+                self.storage.path.display(),
+                e
+            );
+        }
+    }
+
+    /// Periodically stores statistics
+    pub async fn task(self: Arc<Self>, interval: time::Duration) {
+        let mut interval = runtime::Interval::new(interval);
+        while interval.tick().await.is_some() {
+            self.store().await;
+        }
+    }
+}
+
+/// Spawns task for persistent statistics when the path is configured
+pub fn spawn(core: Arc<hub::Core>, path: Option<PathBuf>) -> Option<Arc<Recorder>> {
+    path.map(|path| {
+        let recorder = Arc::new(Recorder::new(core, Storage::new(path)));
+        runtime::spawn(recorder.clone().task(DEFAULT_STORE_INTERVAL));
+        recorder
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn session(start_time: u64, shares: u64, best_share: u64) -> Session {
+        Session {
+            start_time,
+            solutions: 1,
+            shares,
+            accepted_solutions: 1,
+            accepted_shares: shares,
+            best_share: BestShareRecord {
+                difficulty: best_share,
+                time: start_time,
+            },
+            uptime: 10,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_lifetime_merge() {
+        let base = Lifetime::default().merge(&session(100, 64, 128));
+        let lifetime = base.merge(&session(200, 32, 64));
+
+        assert_eq!(lifetime.solutions, 2);
+        assert_eq!(lifetime.shares, 96);
+        assert_eq!(lifetime.accepted_shares, 96);
+        assert_eq!(lifetime.best_share, 128);
+        assert_eq!(lifetime.best_share_time, 100);
+        assert_eq!(lifetime.uptime, 20);
+        assert_eq!(
+            lifetime.uptime_history.back(),
+            Some(&UptimeRecord {
+                start_time: 200,
+                duration: 10
+            })
+        );
+
+        let mut lifetime = Lifetime::default();
+        for i in 0..UPTIME_HISTORY_SIZE as u64 + 1 {
+            lifetime = lifetime.merge(&session(i, 0, 0));
+        }
+        assert_eq!(lifetime.uptime_history.len(), UPTIME_HISTORY_SIZE);
+        assert_eq!(lifetime.uptime_history.front().unwrap().start_time, 1);
+    }
+
+    #[test]
+    fn test_best_share_above_u32() {
+        let difficulty = u64::from(u32::MAX) + 1;
+        let lifetime = Lifetime::default().merge(&session(100, 0, difficulty));
+        assert_eq!(lifetime.best_share, difficulty);
+
+        let content = serde_json::to_vec(&lifetime).expect("BUG: cannot serialize statistics");
+        let restored: Lifetime =
+            serde_json::from_slice(&content).expect("BUG: cannot deserialize statistics");
+        assert_eq!(restored.get_best_share().difficulty, difficulty);
+    }
+
+    #[test]
+    fn test_best_shares_merge() {
+        let record = |difficulty, time| BestShareRecord { difficulty, time };
+        let mut first = session(100, 0, 0);
+        first
+            .chain_best_shares
+            .insert("6".to_string(), record(512, 110));
+        first
+            .chain_best_shares
+            .insert("7".to_string(), record(64, 120));
+        let mut second = session(200, 0, 0);
+        second
+            .chain_best_shares
+            .insert("7".to_string(), record(256, 210));
+        second
+            .pool_best_shares
+            .insert("pool".to_string(), record(128, 220));
+
+        let lifetime = Lifetime::default().merge(&first).merge(&second);
+        assert_eq!(lifetime.chain_best_shares.len(), 2);
+        assert_eq!(lifetime.chain_best_shares["6"], record(512, 110));
+        assert_eq!(lifetime.chain_best_shares["7"], record(256, 210));
+        assert_eq!(lifetime.pool_best_shares["pool"], record(128, 220));
+    }
+
+    #[test]
+    fn test_pools_merge() {
+        let record = |accepted_shares, rejected_shares, uptime| PoolRecord {
+            accepted_shares,
+            rejected_shares,
+            uptime,
+        };
+        let mut first = session(100, 0, 0);
+        first.pools.insert("main".to_string(), record(300, 10, 50));
+        first.pools.insert("fee".to_string(), record(100, 0, 50));
+        let mut second = session(200, 0, 0);
+        second
+            .pools
+            .insert("main".to_string(), record(600, 20, 100));
+
+        let lifetime = Lifetime::default().merge(&first).merge(&second);
+        assert_eq!(lifetime.pools.len(), 2);
+        assert_eq!(lifetime.pools["main"], record(900, 30, 150));
+        assert_eq!(lifetime.pools["fee"], record(100, 0, 50));
+
+        let total_shares = total_accepted_shares(&lifetime.pools);
+        assert_eq!(total_shares, 1000);
+        assert_eq!(lifetime.pools["main"].share_ratio(total_shares), 0.9);
+        assert_eq!(lifetime.pools["fee"].share_ratio(total_shares), 0.1);
+        assert_eq!(PoolRecord::default().share_ratio(0), 0.0);
+    }
+
+    #[test]
+    fn test_storage() {
+        let path = std::env::temp_dir().join(format!("bosminer_stats_{}.json", std::process::id()));
+        let storage = Storage::new(&path);
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(storage.load().expect("BUG: cannot load statistics"), None);
+        let lifetime = Lifetime::default().merge(&session(100, 64, 128));
+        storage
+            .store(&lifetime)
+            .expect("BUG: cannot store statistics");
+        assert_eq!(
+            storage.load().expect("BUG: cannot load statistics"),
+            Some(lifetime)
+        );
+
+        // corrupted file must be reported as an error and kept before it is replaced
+        fs::write(&path, b"{").expect("BUG: cannot write statistics");
+        assert!(storage.load().is_err());
+        assert_eq!(restore(&storage), Lifetime::default());
+        let corrupted_path = PathBuf::from(format!("{}{}", path.display(), CORRUPTED_SUFFIX));
+        assert_eq!(
+            fs::read(&corrupted_path).expect("BUG: cannot read corrupted statistics"),
+            b"{"
+        );
+        fs::remove_file(&path).expect("BUG: cannot remove statistics");
+        fs::remove_file(&corrupted_path).expect("BUG: cannot remove corrupted statistics");
+    }
+}
@@ -25,6 +25,7 @@ pub mod event;
 use std::fmt;
 use std::sync::atomic::Ordering;
 use std::sync::Mutex;
+use std::time;
 
 use atomic_enum::atomic_enum;
 
@@ -52,11 +53,19 @@ impl fmt::Display for Status {
     }
 }
 
+/// Time spent in `Running` state
+#[derive(Debug, Default)]
+struct RunningTime {
+    since: Option<time::Instant>,
+    total: time::Duration,
+}
+
 /// Monitor is intended for generic synchronization of node states
 #[derive(Debug)]
 pub struct StatusMonitor {
     status: AtomicStatus,
     event_sender: Mutex<Option<event::Sender>>,
+    running_time: Mutex<RunningTime>,
 }
 
 impl StatusMonitor {
@@ -136,6 +145,7 @@ impl StatusMonitor {
                             .compare_and_swap(status, Status::Running, Ordering::Relaxed);
                     if status == previous {
                         // Running has been set successfully
+                        self.start_running_time();
                         self.notify();
                         break;
                     }
@@ -171,6 +181,7 @@ impl StatusMonitor {
                             .compare_and_swap(status, Status::Stopping, Ordering::Relaxed);
                     if status == previous {
                         // Stopping has been initiated successfully
+                        self.stop_running_time();
                         return true;
                     }
                 }
@@ -206,6 +217,7 @@ impl StatusMonitor {
                             .compare_and_swap(status, Status::Failing, Ordering::Relaxed);
                     if status == previous {
                         // Failing has been set successfully
+                        self.stop_running_time();
                         break;
                     }
                 }
@@ -311,6 +323,36 @@ impl StatusMonitor {
         false
     }
 
+    /// Total time the node has spent in `Running` state
+    pub fn running_time(&self) -> time::Duration {
+        let running_time = self
+            .running_time
+            .lock()
+            .expect("BUG: cannot lock running time");
+        running_time.total
+            + running_time
+                .since
+                .map_or(Default::default(), |since| since.elapsed())
+    }
+
+    fn start_running_time(&self) {
+        self.running_time
+            .lock()
+            .expect("BUG: cannot lock running time")
+            .since
+            .get_or_insert_with(time::Instant::now);
+    }
+
+    fn stop_running_time(&self) {
+        let mut running_time = self
+            .running_time
+            .lock()
+            .expect("BUG: cannot lock running time");
+        if let Some(since) = running_time.since.take() {
+            running_time.total += since.elapsed();
+        }
+    }
+
     pub fn set_event_sender(&self, event_sender: event::Sender) -> Option<event::Sender> {
         self.event_sender
             .lock()
@@ -339,6 +381,7 @@ impl Default for StatusMonitor {
         Self {
             status: AtomicStatus::new(Status::Created),
             event_sender: Mutex::new(None),
+            running_time: Default::default(),
         }
     }
 }
@@ -0,0 +1,151 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! This module collects miner telemetry (hashrate, shares and backend specific hardware state)
+//! which is periodically exported by `mqtt` and `influx` modules
+
+use crate::client;
+use crate::node;
+use crate::stats;
+
+use async_trait::async_trait;
+
+use serde::Serialize;
+
+use std::sync::Arc;
+use std::time;
+
+/// Backend specific part of telemetry
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct Hardware {
+    /// Temperatures of hash chains in degree Celsius (`None` when temperature is unknown)
+    pub temperatures: Vec<Option<f32>>,
+    /// Speeds of all fans in RPM
+    pub fan_speeds: Vec<usize>,
+    /// Estimated power consumption in Watts
+    pub power: Option<f64>,
+}
+
+/// Source of backend specific telemetry
+#[async_trait]
+pub trait HardwareSource: Send + Sync {
+    async fn hardware(&self) -> Hardware;
+}
+
+/// Snapshot of the whole miner state
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Telemetry {
+    /// Unix time (in seconds) of the snapshot
+    pub timestamp: u64,
+    /// Hashrate at backend difficulty in GH/s averaged over the last minute
+    pub hashrate: f64,
+    /// Number of shares accepted by remote servers
+    pub accepted: u64,
+    /// Number of shares rejected by remote servers
+    pub rejected: u64,
+    #[serde(flatten)]
+    pub hardware: Hardware,
+}
+
+fn unix_time() -> u64 {
+    time::SystemTime::now()
+        .duration_since(time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Sources of all exported data
+pub struct Sources {
+    node: node::DynInfo,
+    client_manager: client::Manager,
+    hardware: Arc<dyn HardwareSource>,
+}
+
+impl Sources {
+    /// Collects telemetry of a `node` (usually the whole backend) together with statistics from
+    /// all clients and backend specific `hardware` telemetry
+    pub fn new(
+        node: node::DynInfo,
+        client_manager: client::Manager,
+        hardware: Arc<dyn HardwareSource>,
+    ) -> Self {
+        Self {
+            node,
+            client_manager,
+            hardware,
+        }
+    }
+
+    pub async fn collect(&self) -> Telemetry {
+        let valid_backend_diff = self
+            .node
+            .mining_stats()
+            .valid_backend_diff()
+            .take_snapshot()
+            .await;
+
+        let mut accepted = 0;
+        let mut rejected = 0;
+        for group in self.client_manager.get_groups().await {
+            for client in group.get_clients().await {
+                let client_stats = client.stats();
+                accepted += client_stats.accepted().take_snapshot().await.solutions;
+                rejected += client_stats.rejected().take_snapshot().await.solutions;
+            }
+        }
+
+        Telemetry {
+            timestamp: unix_time(),
+            hashrate: valid_backend_diff
+                .to_giga_hashes(*stats::TIME_MEAN_INTERVAL_1M, time::Instant::now())
+                .into_f64(),
+            accepted,
+            rejected,
+            hardware: self.hardware.hardware().await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_telemetry() {
+        let telemetry = Telemetry {
+            timestamp: 1,
+            hashrate: 13500.0,
+            accepted: 2,
+            rejected: 1,
+            hardware: Hardware {
+                temperatures: vec![Some(75.0), None],
+                fan_speeds: vec![3000],
+                power: None,
+            },
+        };
+        assert_eq!(
+            serde_json::to_string(&telemetry).expect("BUG: cannot serialize telemetry"),
+            r#"{"timestamp":1,"hashrate":13500.0,"accepted":2,"rejected":1,"#.to_string()
+                + r#""temperatures":[75.0,null],"fan_speeds":[3000],"power":null}"#
+        );
+    }
+}
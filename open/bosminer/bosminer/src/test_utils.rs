@@ -21,9 +21,10 @@
 // contact us at opensource@braiins.com.
 
 pub mod block_mining;
+pub mod simulation;
 
 use crate::hal;
-use crate::job::{self, Bitcoin as _};
+use crate::job;
 use crate::node;
 use crate::stats;
 use crate::sync;
@@ -136,7 +137,7 @@ impl job::Bitcoin for TestBlock {
     }
 
     fn version_mask(&self) -> u32 {
-        0
+        ii_bitcoin::BIP320_VERSION_MASK
     }
 
     fn previous_hash(&self) -> &ii_bitcoin::DHash {
@@ -216,15 +217,9 @@ impl hal::BackendSolution for TestSolution {
 
 impl From<&TestBlock> for work::Assignment {
     fn from(test_block: &TestBlock) -> Self {
-        let job = Arc::new(*test_block);
-        let time = job.time();
-
-        let mid = work::Midstate {
-            version: job.version(),
-            state: job.midstate,
-        };
-
-        Self::new(job, vec![mid], time)
+        work::AssignmentBuilder::new(Arc::new(*test_block))
+            .build()
+            .expect("BUG: invalid test block")
     }
 }
 
@@ -398,6 +393,7 @@ pub fn create_test_work_generator(work_solver: Arc<dyn node::WorkSolver>) -> wor
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::job::Bitcoin as _;
 
     use futures::executor::block_on;
     use ii_async_compat::futures;
@@ -38,8 +38,8 @@ use crate::work;
 
 use std::time::{Duration, Instant};
 
-use ii_async_compat::tokio;
-use tokio::time::delay_for;
+use ii_async_compat::runtime;
+use runtime::delay_for;
 
 use futures::channel::mpsc;
 use futures::lock::Mutex;
@@ -259,14 +259,14 @@ impl Registry {
 /// - build a solver and connect everything to it
 fn build_solvers() -> (
     work::EngineSender,
-    mpsc::UnboundedReceiver<work::Solution>,
+    work::SolutionQueueReceiver,
     mpsc::UnboundedReceiver<work::DynEngine>,
     work::SolverBuilder<crate::Frontend>,
 ) {
     let (reschedule_sender, reschedule_receiver) = mpsc::unbounded();
     let (engine_sender, engine_receiver) =
         work::engine_channel(ExhaustedWorkHandler::new(reschedule_sender));
-    let (solution_queue_tx, solution_queue_rx) = mpsc::unbounded();
+    let (solution_queue_tx, solution_queue_rx) = work::solution_queue("test solutions");
     (
         // Send engines here (preferably OneWork engines)
         engine_sender,
@@ -286,7 +286,7 @@ fn build_solvers() -> (
 }
 
 async fn collect_solutions(
-    mut solution_queue_rx: mpsc::UnboundedReceiver<work::Solution>,
+    mut solution_queue_rx: work::SolutionQueueReceiver,
     registry: Arc<Mutex<Registry>>,
 ) {
     while let Some(solution) = solution_queue_rx.next().await {
@@ -331,7 +331,7 @@ pub async fn run<T: hal::Backend>(mut backend_config: T::Config) {
     }
 
     // start task to collect solutions and put them to registry
-    tokio::spawn(collect_solutions(solution_queue_rx, registry.clone()));
+    runtime::spawn(collect_solutions(solution_queue_rx, registry.clone()));
 
     // TODO: first work sent to miner is for some reason ignored
     // workaround: send two works
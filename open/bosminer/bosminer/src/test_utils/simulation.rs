@@ -0,0 +1,836 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! End-to-end simulation harness that connects the real stratum clients of BOSminer to a scripted
+//! pool server and mines its jobs with the simulated backend (`hal::sim`). The pool listens on
+//! a local port, speaks stratum V1 or V2 and issues a script of jobs with a difficulty which is
+//! low enough for the simulated chains. Every share is validated against the job it has been
+//! submitted for. This way the full flow (job receipt -> work generation -> solution -> share
+//! submission -> accounting) can be verified on any host without mining hardware or a remote
+//! pool.
+
+use ii_logging::macros::*;
+
+use crate::backend;
+use crate::client;
+use crate::hal;
+use crate::hub;
+use crate::work;
+
+use bosminer_config::{ClientDescriptor, ClientUserInfo};
+
+use ii_bitcoin::{HashTrait as _, MeetsTarget as _};
+use ii_stratum::v1;
+use ii_stratum::v2::{
+    self,
+    messages::{
+        MessageType, NewMiningJob, OpenStandardMiningChannel, OpenStandardMiningChannelSuccess,
+        SetNewPrevHash, SetupConnectionSuccess, SubmitSharesError, SubmitSharesStandard,
+        SubmitSharesSuccess,
+    },
+    types::{Bytes0_32, Uint256Bytes},
+};
+use ii_wire::{Address, Connection, Server};
+
+use futures::future::{self, AbortHandle};
+use ii_async_compat::prelude::*;
+use runtime::delay_for;
+use tokio::net::TcpStream;
+
+use std::collections::HashSet;
+use std::convert::{TryFrom, TryInto};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex as StdMutex, MutexGuard as StdMutexGuard};
+use std::time::{Duration, Instant};
+
+const ADDR: &str = "127.0.0.1";
+const USER: &str = "user.worker";
+
+const EXTRA_NONCE_1: [u8; 4] = [0xde, 0xad, 0xbe, 0xef];
+const EXTRA_NONCE_2_SIZE: usize = 4;
+/// Coinbase of V1 jobs without the extra nonces
+const COIN_BASE_1: &str = "01000000010000000000000000000000000000000000000000";
+const COIN_BASE_2: &str =
+    "ffffffff0100f2052a010000001976a914000000000000000000000000000000000000000088ac00000000";
+const JOB_VERSION: u32 = 0x20000000;
+const JOB_BITS: u32 = 0x1d00ffff;
+/// Time of the first job, the following jobs are one second apart
+const JOB_TIME: u32 = 0x5d10bc0a;
+/// Only V2 channel of the pool
+const CHANNEL_ID: u32 = 0;
+
+/// Difficulty of the pool which is the same as the difficulty of nonces found by the simulated
+/// chains so all of them are submitted as shares
+pub fn pool_target() -> ii_bitcoin::Target {
+    hal::sim::search_target()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(hex: &str) -> Vec<u8> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).expect("BUG: invalid hex"))
+        .collect()
+}
+
+fn v1_rpc(json: serde_json::Value) -> v1::rpc::Rpc {
+    v1::rpc::Rpc::from_str(&json.to_string()).expect("BUG: invalid V1 message")
+}
+
+fn v1_result(id: Option<u32>, result: serde_json::Value) -> v1::rpc::Rpc {
+    v1_rpc(serde_json::json!({"id": id, "result": result, "error": null}))
+}
+
+fn v1_error(id: Option<u32>, code: i32, msg: &str) -> v1::rpc::Rpc {
+    v1_rpc(serde_json::json!({"id": id, "result": false, "error": [code, msg, null]}))
+}
+
+fn v1_notification(method: &str, params: serde_json::Value) -> v1::rpc::Rpc {
+    v1_rpc(serde_json::json!({"id": null, "method": method, "params": params}))
+}
+
+/// Mining protocol spoken by the scripted pool
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    StratumV1,
+    StratumV2,
+}
+
+/// Job issued by the scripted pool
+#[derive(Debug, Clone)]
+struct Job {
+    id: u32,
+    previous_hash: ii_bitcoin::DHash,
+    /// Merkle branch of V1 jobs (the merkle root is computed from the coinbase)
+    merkle_branch: Vec<ii_bitcoin::DHash>,
+    time: u32,
+}
+
+impl Job {
+    fn new(id: u32) -> Self {
+        Self {
+            id,
+            previous_hash: ii_bitcoin::DHash::hash(&id.to_le_bytes()),
+            merkle_branch: vec![
+                ii_bitcoin::DHash::hash(&[id as u8; 32]),
+                ii_bitcoin::DHash::hash(&[!id as u8; 32]),
+            ],
+            time: JOB_TIME + id,
+        }
+    }
+
+    /// Merkle root of the V1 job with given `extra_nonce_2`
+    fn v1_merkle_root(&self, extra_nonce_2: &[u8]) -> ii_bitcoin::DHash {
+        let coin_base = [
+            from_hex(COIN_BASE_1),
+            EXTRA_NONCE_1.to_vec(),
+            extra_nonce_2.to_vec(),
+            from_hex(COIN_BASE_2),
+        ]
+        .concat();
+        self.merkle_branch
+            .iter()
+            .fold(ii_bitcoin::DHash::hash(&coin_base), |root, hash| {
+                ii_bitcoin::DHash::hash(&[&root[..], &hash[..]].concat())
+            })
+    }
+
+    /// Merkle root of the V2 job which is the same as of the V1 job without the extra nonce 2
+    fn v2_merkle_root(&self) -> ii_bitcoin::DHash {
+        self.v1_merkle_root(&[0u8; EXTRA_NONCE_2_SIZE])
+    }
+
+    fn v1_notify(&self) -> v1::rpc::Rpc {
+        v1_notification(
+            "mining.notify",
+            serde_json::json!([
+                format!("{:x}", self.id),
+                // stratum V1 sends the previous hash with swapped bytes of each word
+                to_hex(
+                    &self.previous_hash[..]
+                        .chunks(4)
+                        .flat_map(|word| word.iter().rev().cloned())
+                        .collect::<Vec<_>>()
+                ),
+                COIN_BASE_1,
+                COIN_BASE_2,
+                self.merkle_branch
+                    .iter()
+                    .map(|hash| to_hex(&hash[..]))
+                    .collect::<Vec<_>>(),
+                format!("{:08x}", JOB_VERSION),
+                format!("{:08x}", JOB_BITS),
+                format!("{:08x}", self.time),
+                true,
+            ]),
+        )
+    }
+
+    fn v2_messages(&self) -> Vec<v2::Frame> {
+        vec![
+            NewMiningJob {
+                channel_id: CHANNEL_ID,
+                job_id: self.id,
+                future_job: true,
+                version: JOB_VERSION,
+                merkle_root: Uint256Bytes(self.v2_merkle_root().into_inner()),
+            }
+            .try_into()
+            .expect("BUG: cannot serialize job"),
+            SetNewPrevHash {
+                channel_id: CHANNEL_ID,
+                prev_hash: Uint256Bytes(self.previous_hash.into_inner()),
+                min_ntime: self.time,
+                job_id: self.id,
+                nbits: JOB_BITS,
+            }
+            .try_into()
+            .expect("BUG: cannot serialize prevhash"),
+        ]
+    }
+
+    /// Checks that the share meets the pool `target`
+    fn check(
+        &self,
+        target: &ii_bitcoin::Target,
+        merkle_root: ii_bitcoin::DHash,
+        version: u32,
+        time: u32,
+        nonce: u32,
+    ) -> Result<(), &'static str> {
+        if version & !ii_bitcoin::BIP320_VERSION_MASK != JOB_VERSION {
+            return Err("Invalid version bits");
+        }
+        let header = ii_bitcoin::BlockHeader {
+            version,
+            previous_hash: self.previous_hash.into_inner(),
+            merkle_root: merkle_root.into_inner(),
+            time,
+            bits: JOB_BITS,
+            nonce,
+        };
+        if !header.hash().meets(target) {
+            return Err("Low difficulty share");
+        }
+        Ok(())
+    }
+}
+
+/// Share as submitted to the pool, it is used for detection of duplicates
+#[derive(PartialEq, Eq, Hash)]
+struct Share {
+    job_id: u32,
+    merkle_root: ii_bitcoin::DHash,
+    version: u32,
+    time: u32,
+    nonce: u32,
+}
+
+/// State of the script shared by all connections to the pool
+#[derive(Default)]
+struct PoolState {
+    target: ii_bitcoin::Target,
+    job_count: usize,
+    shares_per_job: usize,
+    /// All jobs issued by the pool, the job ID is the index
+    jobs: Vec<Job>,
+    /// Number of shares accepted for the last job
+    job_shares: usize,
+    shares: HashSet<Share>,
+    accepted: usize,
+    rejected: usize,
+    connections: usize,
+    /// Handles of the listener and all connections for stopping the pool
+    abort_handles: Vec<AbortHandle>,
+}
+
+impl PoolState {
+    /// Returns the current job of the script, the first one is issued on demand
+    fn current_job(&mut self) -> Job {
+        if self.jobs.is_empty() {
+            self.jobs.push(Job::new(0));
+        }
+        self.jobs.last().expect("BUG: no job").clone()
+    }
+
+    fn is_finished(&self) -> bool {
+        self.jobs.len() == self.job_count && self.job_shares >= self.shares_per_job
+    }
+
+    /// Validates and accounts the share. Returns the next job of the script when the current
+    /// one has received all its shares.
+    fn submit(&mut self, share: Share) -> Result<Option<Job>, &'static str> {
+        let target = self.target;
+        let result = self
+            .jobs
+            .get(share.job_id as usize)
+            .ok_or("Job not found")
+            .and_then(|job| {
+                job.check(
+                    &target,
+                    share.merkle_root,
+                    share.version,
+                    share.time,
+                    share.nonce,
+                )
+            })
+            .and_then(|_| {
+                if self.shares.insert(share) {
+                    Ok(())
+                } else {
+                    Err("Duplicate share")
+                }
+            });
+        if let Err(msg) = result {
+            warn!("Scripted pool: rejecting share: {}", msg);
+            self.rejected += 1;
+            return Err(msg);
+        }
+
+        self.accepted += 1;
+        self.job_shares += 1;
+        if self.job_shares == self.shares_per_job && self.jobs.len() < self.job_count {
+            let job = Job::new(self.jobs.len() as u32);
+            self.jobs.push(job.clone());
+            self.job_shares = 0;
+            return Ok(Some(job));
+        }
+        Ok(None)
+    }
+
+    /// Returns all V1 messages the pool sends in reaction to `request`
+    fn handle_v1_request(&mut self, request: v1::rpc::Request) -> Vec<v1::rpc::Rpc> {
+        let id = request.id;
+        match request.payload.method {
+            v1::rpc::Method::Configure => vec![v1_result(
+                id,
+                serde_json::json!({
+                    "version-rolling": true,
+                    "version-rolling.mask": format!("{:08x}", ii_bitcoin::BIP320_VERSION_MASK),
+                }),
+            )],
+            v1::rpc::Method::Subscribe => vec![v1_result(
+                id,
+                serde_json::json!([
+                    [["mining.set_difficulty", "1"], ["mining.notify", "1"]],
+                    to_hex(&EXTRA_NONCE_1),
+                    EXTRA_NONCE_2_SIZE,
+                ]),
+            )],
+            v1::rpc::Method::ExtranonceSubscribe => vec![v1_result(id, serde_json::json!(true))],
+            v1::rpc::Method::Authorize => vec![
+                v1_result(id, serde_json::json!(true)),
+                v1_notification(
+                    "mining.set_difficulty",
+                    serde_json::json!([
+                        ii_bitcoin::difficulty::target_to_difficulty(&self.target) as f32
+                    ]),
+                ),
+                self.current_job().v1_notify(),
+            ],
+            v1::rpc::Method::Submit => {
+                let submit = v1::messages::Submit::try_from(request).expect("BUG: invalid submit");
+                let share = u32::from_str_radix(submit.job_id(), 16)
+                    .ok()
+                    .and_then(|job_id| self.jobs.get(job_id as usize))
+                    .filter(|_| submit.extra_nonce_2().len() == EXTRA_NONCE_2_SIZE)
+                    .map(|job| Share {
+                        job_id: job.id,
+                        merkle_root: job.v1_merkle_root(submit.extra_nonce_2()),
+                        version: (JOB_VERSION & !ii_bitcoin::BIP320_VERSION_MASK)
+                            | submit.version(),
+                        time: submit.time(),
+                        nonce: submit.nonce(),
+                    });
+                let result = match share {
+                    Some(share) => self.submit(share),
+                    None => {
+                        self.rejected += 1;
+                        Err("Job not found")
+                    }
+                };
+                match result {
+                    Ok(job) => {
+                        let mut messages = vec![v1_result(id, serde_json::json!(true))];
+                        messages.extend(job.map(|job| job.v1_notify()));
+                        messages
+                    }
+                    Err(msg) => vec![v1_error(id, 23, msg)],
+                }
+            }
+            _ => vec![],
+        }
+    }
+
+    /// Returns all V2 messages the pool sends in reaction to `frame`
+    fn handle_v2_frame(&mut self, frame: v2::Frame) -> Vec<v2::Frame> {
+        let msg_type = frame.header.msg_type;
+        if msg_type == MessageType::SetupConnection as u8 {
+            vec![SetupConnectionSuccess {
+                used_version: 2,
+                flags: 0,
+            }
+            .try_into()
+            .expect("BUG: cannot serialize setup connection")]
+        } else if msg_type == MessageType::OpenStandardMiningChannel as u8 {
+            let open_msg =
+                OpenStandardMiningChannel::try_from(frame).expect("BUG: invalid open channel");
+            let mut messages = vec![OpenStandardMiningChannelSuccess {
+                req_id: open_msg.req_id,
+                channel_id: CHANNEL_ID,
+                target: self.target.into(),
+                extranonce_prefix: Bytes0_32::new(),
+                group_channel_id: 0,
+            }
+            .try_into()
+            .expect("BUG: cannot serialize open channel")];
+            messages.extend(self.current_job().v2_messages());
+            messages
+        } else if msg_type == MessageType::SubmitSharesStandard as u8 {
+            let submit = SubmitSharesStandard::try_from(frame).expect("BUG: invalid submit");
+            let share = self.jobs.get(submit.job_id as usize).map(|job| Share {
+                job_id: job.id,
+                merkle_root: job.v2_merkle_root(),
+                version: submit.version,
+                time: submit.ntime,
+                nonce: submit.nonce,
+            });
+            let result = match share {
+                Some(share) => self.submit(share),
+                None => {
+                    self.rejected += 1;
+                    Err("Job not found")
+                }
+            };
+            match result {
+                Ok(job) => {
+                    let mut messages = vec![SubmitSharesSuccess {
+                        channel_id: CHANNEL_ID,
+                        last_seq_num: submit.seq_num,
+                        new_submits_accepted_count: 1,
+                        new_shares_sum: 1,
+                    }
+                    .try_into()
+                    .expect("BUG: cannot serialize submit result")];
+                    messages.extend(job.into_iter().flat_map(|job| job.v2_messages()));
+                    messages
+                }
+                Err(msg) => vec![SubmitSharesError {
+                    channel_id: CHANNEL_ID,
+                    seq_num: submit.seq_num,
+                    code: msg.try_into().expect("BUG: invalid error code"),
+                }
+                .try_into()
+                .expect("BUG: cannot serialize submit result")],
+            }
+        } else {
+            vec![]
+        }
+    }
+}
+
+/// Pool server which issues a script of jobs and checks all submitted shares. The next job is
+/// issued when the current one has received a given number of shares.
+pub struct ScriptedPool {
+    protocol: Protocol,
+    port: u16,
+    state: Arc<StdMutex<PoolState>>,
+}
+
+impl ScriptedPool {
+    /// Starts listening on a free local port
+    pub fn start(protocol: Protocol, job_count: usize, shares_per_job: usize) -> Self {
+        Self::start_with_target(protocol, job_count, shares_per_job, pool_target())
+    }
+
+    /// Starts listening on a free local port with shares checked against `target` instead of
+    /// the default pool target which is suitable for the simulated backend
+    pub fn start_with_target(
+        protocol: Protocol,
+        job_count: usize,
+        shares_per_job: usize,
+        target: ii_bitcoin::Target,
+    ) -> Self {
+        assert!(job_count > 0, "BUG: empty script");
+        let state = Arc::new(StdMutex::new(PoolState {
+            target,
+            job_count,
+            shares_per_job,
+            ..Default::default()
+        }));
+        let port = Self::listen(protocol, 0, state.clone());
+
+        Self {
+            protocol,
+            port,
+            state,
+        }
+    }
+
+    /// Starts listening again on the same port after the pool has been stopped
+    pub fn restart(&self) {
+        Self::listen(self.protocol, self.port, self.state.clone());
+    }
+
+    /// Spawns task accepting connections on `port` and returns the real port
+    fn listen(protocol: Protocol, port: u16, state: Arc<StdMutex<PoolState>>) -> u16 {
+        let mut server = Server::bind((ADDR, port)).expect("BUG: cannot bind pool");
+        let port = server
+            .local_addr()
+            .expect("BUG: missing pool address")
+            .port();
+
+        let accept_state = state.clone();
+        let (accept_task, abort_handle) =
+            future::abortable(async move {
+                while let Some(Ok(stream)) = server.next().await {
+                    let (connection_task, abort_handle) = future::abortable(
+                        Self::handle_connection(protocol, accept_state.clone(), stream),
+                    );
+                    let mut state = accept_state.lock().expect("BUG: cannot lock pool state");
+                    state.connections += 1;
+                    state.abort_handles.push(abort_handle);
+                    runtime::spawn(connection_task);
+                }
+            });
+        state
+            .lock()
+            .expect("BUG: cannot lock pool state")
+            .abort_handles
+            .push(abort_handle);
+        runtime::spawn(accept_task);
+        port
+    }
+
+    fn lock_state(&self) -> StdMutexGuard<'_, PoolState> {
+        self.state.lock().expect("BUG: cannot lock pool state")
+    }
+
+    async fn handle_connection(
+        protocol: Protocol,
+        state: Arc<StdMutex<PoolState>>,
+        stream: TcpStream,
+    ) {
+        match protocol {
+            Protocol::StratumV1 => {
+                let mut conn = Connection::<v1::Framing>::new(stream);
+                while let Some(Ok(frame)) = conn.next().await {
+                    let request = match v1::rpc::Rpc::try_from(frame) {
+                        Ok(v1::rpc::Rpc::Request(request)) => request,
+                        _ => break,
+                    };
+                    let messages = state
+                        .lock()
+                        .expect("BUG: cannot lock pool state")
+                        .handle_v1_request(request);
+                    for message in messages {
+                        let frame = message.try_into().expect("BUG: cannot convert to frame");
+                        if conn.send(frame).await.is_err() {
+                            return;
+                        }
+                    }
+                }
+            }
+            Protocol::StratumV2 => {
+                let mut conn = Connection::<v2::Framing>::new(stream);
+                while let Some(Ok(frame)) = conn.next().await {
+                    let frames = state
+                        .lock()
+                        .expect("BUG: cannot lock pool state")
+                        .handle_v2_frame(frame);
+                    for frame in frames {
+                        if conn.send(frame).await.is_err() {
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Closes the listener and all connections
+    pub fn stop(&self) {
+        for abort_handle in self.lock_state().abort_handles.drain(..) {
+            abort_handle.abort();
+        }
+    }
+
+    /// Local address the pool listens on
+    pub fn addr(&self) -> Address {
+        Address(ADDR.into(), self.port)
+    }
+
+    /// URL of the pool for the client descriptor
+    pub fn url(&self) -> String {
+        match self.protocol {
+            Protocol::StratumV1 => format!("stratum+tcp://{}:{}", ADDR, self.port),
+            Protocol::StratumV2 => format!("stratum2+tcp+insecure://{}:{}", ADDR, self.port),
+        }
+    }
+
+    /// Check if all jobs of the script have received their shares
+    pub fn is_finished(&self) -> bool {
+        self.lock_state().is_finished()
+    }
+
+    pub fn accepted_count(&self) -> usize {
+        self.lock_state().accepted
+    }
+
+    pub fn rejected_count(&self) -> usize {
+        self.lock_state().rejected
+    }
+
+    pub fn connection_count(&self) -> usize {
+        self.lock_state().connections
+    }
+
+    /// Wait until `condition` holds for the pool or the `timeout` expires
+    /// Returns `true` when the condition has been met in time
+    async fn wait_until<F: Fn(&Self) -> bool>(&self, condition: F, timeout: Duration) -> bool {
+        const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+        let started = Instant::now();
+        while !condition(self) {
+            if started.elapsed() >= timeout {
+                return false;
+            }
+            delay_for(POLL_INTERVAL).await;
+        }
+        true
+    }
+
+    /// Wait until the whole script has been processed or the `timeout` expires
+    /// Returns `true` when the script has been finished in time
+    pub async fn wait_for_script(&self, timeout: Duration) -> bool {
+        self.wait_until(Self::is_finished, timeout).await
+    }
+
+    /// Wait until the pool accepts at least `count` shares or the `timeout` expires
+    /// Returns `true` when the shares have been accepted in time
+    pub async fn wait_for_shares(&self, count: usize, timeout: Duration) -> bool {
+        self.wait_until(|pool| pool.accepted_count() >= count, timeout)
+            .await
+    }
+}
+
+impl Drop for ScriptedPool {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// BOSminer core with simulated backend which can be connected to scripted pools
+pub struct Miner {
+    pub core: Arc<hub::Core>,
+    /// The core keeps only weak reference to the registry of work solvers
+    _backend_registry: Arc<backend::Registry>,
+}
+
+impl Miner {
+    /// Starts the core with simulated backend configured by `backend_config`
+    pub async fn start(backend_config: hal::sim::Config) -> Self {
+        let backend_registry = Arc::new(backend::Registry::new());
+        let core = Arc::new(hub::Core::new(
+            backend_config.midstate_count,
+            work::DEFAULT_MAX_WORK_AGE,
+            Arc::new(hal::sim::SubmitPolicy),
+            &backend_registry,
+            None,
+        ));
+        core.build_backend::<hal::sim::Backend>(backend_config)
+            .await
+            .expect("BUG: cannot build simulated backend");
+        runtime::spawn(core.clone().run());
+
+        Self {
+            core,
+            _backend_registry: backend_registry,
+        }
+    }
+
+    /// Appends the real protocol client connected to `pool` to `group`
+    pub async fn connect(&self, group: &client::Group, pool: &ScriptedPool) -> Arc<client::Handle> {
+        let descriptor =
+            ClientDescriptor::create(&pool.url(), &ClientUserInfo::new(USER, None), true)
+                .expect("BUG: invalid pool URL");
+        group
+            .push_client(client::Handle::new(descriptor, None, None))
+            .await
+    }
+
+    /// Appends the real protocol client connected to `pool` to the default group
+    pub async fn connect_default(&self, pool: &ScriptedPool) -> Arc<client::Handle> {
+        let group = self
+            .core
+            .get_client_manager()
+            .create_or_get_default_group()
+            .await;
+        self.connect(&group, pool).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::node;
+    use crate::stats;
+
+    /// Maximal time the simulation can take
+    const SIMULATION_TIMEOUT: Duration = Duration::from_secs(30);
+    const JOB_COUNT: usize = 3;
+    const SHARES_PER_JOB: usize = 3;
+
+    /// Mine the pool script with simulated chains through the real client of `protocol` and
+    /// check the statistics
+    async fn run_simulation(protocol: Protocol) {
+        let pool = ScriptedPool::start(protocol, JOB_COUNT, SHARES_PER_JOB);
+        let backend_config = hal::sim::Config {
+            chain_count: 2,
+            hashrate: 50 << 32,
+            difficulty: 1,
+            ..Default::default()
+        };
+        let miner = Miner::start(backend_config).await;
+        let client_handle = miner.connect_default(&pool).await;
+
+        assert!(
+            pool.wait_for_script(SIMULATION_TIMEOUT).await,
+            "simulation with pool '{}' timed out (accepted: {}, rejected: {})",
+            pool.url(),
+            pool.accepted_count(),
+            pool.rejected_count()
+        );
+        // all shares submitted to the pool should be valid
+        assert_eq!(pool.rejected_count(), 0);
+        assert_eq!(pool.connection_count(), 1);
+        let pool_accepted = pool.accepted_count() as u64;
+        assert!(pool_accepted >= (JOB_COUNT * SHARES_PER_JOB) as u64);
+        pool.stop();
+
+        // check accounting in the client, the last responses may still be on the way
+        let client_stats = client_handle.stats();
+        let started = Instant::now();
+        while client_stats.accepted().take_snapshot().await.solutions < pool_accepted
+            && started.elapsed() < SIMULATION_TIMEOUT
+        {
+            delay_for(Duration::from_millis(10)).await;
+        }
+        assert_eq!(
+            client_stats.accepted().take_snapshot().await.solutions,
+            pool_accepted
+        );
+        assert_eq!(client_stats.rejected().take_snapshot().await.solutions, 0);
+        assert!(*client_stats.valid_jobs().take_snapshot() >= JOB_COUNT);
+        assert_eq!(*client_stats.invalid_jobs().take_snapshot(), 0);
+        assert!(*client_stats.generated_work().take_snapshot() > 0);
+        // every job of the script has been taken by the simulated chains
+        assert!(client_stats.job_latency().take_snapshot().await.count >= JOB_COUNT as u64);
+
+        // check accounting in the frontend and backend
+        assert!(miner.core.frontend.get_generated_work() > 0);
+        let frontend_shares =
+            stats::SharesSnapshot::take(node::Stats::mining_stats(miner.core.frontend.as_ref()))
+                .await;
+        assert_eq!(frontend_shares.accepted.solutions, pool_accepted);
+        assert_eq!(frontend_shares.rejected.solutions, 0);
+        let work_solvers = miner.core.get_work_solvers().await;
+        assert_eq!(work_solvers.len(), 2);
+        let mut solver_accepted = 0;
+        for work_solver in work_solvers.iter() {
+            let shares = stats::SharesSnapshot::take(work_solver.mining_stats()).await;
+            assert_eq!(shares.rejected.solutions, 0);
+            solver_accepted += shares.accepted.solutions;
+            assert_eq!(
+                work_solver
+                    .mining_stats()
+                    .error_backend_diff()
+                    .take_snapshot()
+                    .await
+                    .solutions,
+                0
+            );
+        }
+        assert_eq!(solver_accepted, pool_accepted);
+    }
+
+    /// Interrupt the connection to the pool for a moment and check that the solutions found
+    /// in the meantime are submitted after reconnection instead of being discarded as stale
+    async fn run_outage(protocol: Protocol) {
+        const OUTAGE_TIME: Duration = Duration::from_secs(1);
+
+        let pool = ScriptedPool::start(protocol, 1, usize::MAX);
+        let backend_config = hal::sim::Config {
+            hashrate: 50 << 32,
+            difficulty: 1,
+            ..Default::default()
+        };
+        let miner = Miner::start(backend_config).await;
+        let client_handle = miner.connect_default(&pool).await;
+        assert!(
+            pool.wait_for_shares(1, SIMULATION_TIMEOUT).await,
+            "pool '{}' has not received any share",
+            pool.url()
+        );
+
+        pool.stop();
+        // mining continues on the last job during the outage
+        let client_stats = client_handle.stats();
+        let generated_work = *client_stats.generated_work().take_snapshot();
+        delay_for(OUTAGE_TIME).await;
+        assert!(*client_stats.generated_work().take_snapshot() > generated_work);
+
+        let accepted = pool.accepted_count();
+        pool.restart();
+        assert!(
+            pool.wait_for_shares(accepted + 1, SIMULATION_TIMEOUT).await,
+            "pool '{}' has not received any share after reconnection",
+            pool.url()
+        );
+        assert_eq!(pool.rejected_count(), 0);
+        assert!(pool.connection_count() > 1);
+        assert_eq!(client_stats.stale().take_snapshot().await.solutions, 0);
+    }
+
+    #[tokio::test]
+    async fn test_simulation_stratum_v1() {
+        run_simulation(Protocol::StratumV1).await;
+    }
+
+    #[tokio::test]
+    async fn test_simulation_stratum_v2() {
+        run_simulation(Protocol::StratumV2).await;
+    }
+
+    #[tokio::test]
+    async fn test_outage_stratum_v1() {
+        run_outage(Protocol::StratumV1).await;
+    }
+
+    #[tokio::test]
+    async fn test_outage_stratum_v2() {
+        run_outage(Protocol::StratumV2).await;
+    }
+}
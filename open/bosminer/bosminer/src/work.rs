@@ -23,6 +23,7 @@
 //! Basic components for building WorkEngine broadcasting infrastructure and to send WorkEngines
 //! to the actual work solving (mining) backends
 
+mod builder;
 pub mod engine;
 mod solver;
 
@@ -32,10 +33,11 @@ use crate::node;
 
 use ii_bitcoin::HashTrait as _;
 
+pub use builder::{AssignmentBuilder, InvalidWork, MIDSTATE_COUNTS};
 pub use solver::{Generator, SolutionSender, SolverBuilder};
 
+use ii_async_compat::channel::{self, watch};
 use ii_async_compat::prelude::*;
-use tokio::sync::watch;
 
 use once_cell::sync::OnceCell;
 
@@ -45,6 +47,26 @@ use std::mem;
 use std::sync::{Arc, Mutex as StdMutex, MutexGuard as StdMutexGuard, Weak};
 use std::time;
 
+/// Default upper bound on time since reception of a job after which its work is considered stale
+pub const DEFAULT_MAX_WORK_AGE: time::Duration = time::Duration::from_secs(120);
+
+/// Maximum number of solutions buffered in a solution queue. The queues are drained continuously
+/// so they fill up only when the consumer is stuck.
+pub const SOLUTION_QUEUE_CAPACITY: usize = 4096;
+
+pub type SolutionQueueSender = channel::Sender<Solution>;
+pub type SolutionQueueReceiver = channel::Receiver<Solution>;
+
+/// Creates a bounded queue for solutions. When it overflows the oldest solutions are dropped
+/// because they are the most likely to be stale anyway.
+pub fn solution_queue(name: &'static str) -> (SolutionQueueSender, SolutionQueueReceiver) {
+    channel::bounded(
+        name,
+        SOLUTION_QUEUE_CAPACITY,
+        channel::OverflowPolicy::DropOldest,
+    )
+}
+
 pub enum LoopState<T> {
     /// Mining work is exhausted
     Exhausted,
@@ -95,6 +117,8 @@ pub struct Assignment {
     pub midstates: Vec<Midstate>,
     /// nTime value for current work
     pub ntime: u32,
+    /// Time after which solutions of this work are considered stale
+    deadline: Option<time::Instant>,
 }
 
 impl Assignment {
@@ -104,9 +128,21 @@ impl Assignment {
             job,
             midstates,
             ntime,
+            deadline: None,
         }
     }
 
+    pub fn with_deadline(mut self, deadline: Option<time::Instant>) -> Self {
+        self.deadline = deadline;
+        self
+    }
+
+    /// Work expires when its job has been invalidated (e.g. by new block) or when it is older
+    /// than maximal work age
+    pub fn is_expired(&self, now: time::Instant) -> bool {
+        !self.job.is_valid() || self.deadline.map_or(false, |deadline| now >= deadline)
+    }
+
     /// Return origin from which the work has been generated
     #[inline]
     pub fn origin(&self) -> Weak<dyn node::Client> {
@@ -125,11 +161,29 @@ impl Assignment {
         self.job.bits()
     }
 
+    /// Return current pool target of the job
+    #[inline]
+    pub fn target(&self) -> ii_bitcoin::Target {
+        self.job.target()
+    }
+
     /// Return number of generated work associated within this work assignment
     #[inline]
     pub fn generated_work_amount(&self) -> usize {
         self.midstates.len()
     }
+
+    /// Build Bitcoin block header for the midstate with index `midstate_idx` and given `nonce`
+    pub fn block_header(&self, midstate_idx: usize, nonce: u32) -> ii_bitcoin::BlockHeader {
+        ii_bitcoin::BlockHeader {
+            version: self.midstates[midstate_idx].version,
+            previous_hash: self.job.previous_hash().into_inner(),
+            merkle_root: self.job.merkle_root().into_inner(),
+            time: self.ntime,
+            bits: self.job.bits(),
+            nonce,
+        }
+    }
 }
 
 /// Container with mining work and a corresponding solution received at a particular time
@@ -222,6 +276,13 @@ impl Solution {
         self.solution.midstate_idx()
     }
 
+    /// Check that the midstate index reported by backend refers to a midstate of the work. The
+    /// block header cannot be reconstructed for solutions corrupted by hardware otherwise.
+    #[inline]
+    pub fn has_valid_midstate(&self) -> bool {
+        self.midstate_idx() < self.work.midstates.len()
+    }
+
     /// Return double hash of this solution
     #[inline]
     pub fn hash(&self) -> &ii_bitcoin::DHash {
@@ -230,16 +291,7 @@ impl Solution {
 
     /// Converts mining work solution to Bitcoin block header structure which is packable
     pub fn get_block_header(&self) -> ii_bitcoin::BlockHeader {
-        let job = &self.work.job;
-
-        ii_bitcoin::BlockHeader {
-            version: self.version(),
-            previous_hash: job.previous_hash().into_inner(),
-            merkle_root: job.merkle_root().into_inner(),
-            time: self.time(),
-            bits: job.bits(),
-            nonce: self.nonce(),
-        }
+        self.work.block_header(self.midstate_idx(), self.nonce())
     }
 
     #[inline]
@@ -247,6 +299,12 @@ impl Solution {
         self.work.job.is_valid()
     }
 
+    /// Solution is stale when it has been received after its work expired
+    #[inline]
+    pub fn is_expired(&self) -> bool {
+        self.work.is_expired(self.timestamp)
+    }
+
     /// Return the whole unique path starting from job origin and ending in backend.
     pub fn path(&self) -> node::Path {
         // Arc does not support dynamic casting to trait bounds so there must be used another Arc
@@ -0,0 +1,259 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Builder of mining work which computes midstates from the job block header
+//!
+//! All fields of the work which are not part of the midstates (merkle root tail, nBits) are
+//! taken from the job so they are always consistent with the midstates. The invariants of the
+//! work are validated before the work is handed over to the backend.
+
+use super::{Assignment, Midstate};
+use crate::job;
+
+use ii_bitcoin::HashTrait as _;
+
+use thiserror::Error;
+
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time;
+
+/// Numbers of midstates which a work can be composed of
+pub const MIDSTATE_COUNTS: [usize; 3] = [1, 2, 4];
+
+/// Violated invariant of the work being built
+#[derive(Error, Clone, Eq, PartialEq, Debug)]
+pub enum InvalidWork {
+    #[error("unsupported number of midstates: {0}")]
+    MidstateCount(usize),
+    #[error(
+        "version {version:#010x} differs from job version {job_version:#010x} outside of \
+         version mask {version_mask:#010x}"
+    )]
+    Version {
+        version: u32,
+        job_version: u32,
+        version_mask: u32,
+    },
+    #[error("version {0:#010x} is used by more than one midstate")]
+    DuplicateVersion(u32),
+    #[error("ntime {ntime} is out of the rolling range of job time {job_time}")]
+    Ntime { ntime: u32, job_time: u32 },
+}
+
+/// Builds work for the job with one midstate for each version. Without any version the work
+/// has a single midstate for the original version of the job.
+#[derive(Debug, Clone)]
+pub struct AssignmentBuilder {
+    job: Arc<dyn job::Bitcoin>,
+    versions: Vec<u32>,
+    ntime: u32,
+    deadline: Option<time::Instant>,
+}
+
+impl AssignmentBuilder {
+    pub fn new(job: Arc<dyn job::Bitcoin>) -> Self {
+        let ntime = job.time();
+        Self {
+            job,
+            versions: Vec::with_capacity(MIDSTATE_COUNTS[MIDSTATE_COUNTS.len() - 1]),
+            ntime,
+            deadline: None,
+        }
+    }
+
+    /// Adds a midstate for the block header with (rolled) `version`
+    pub fn version(mut self, version: u32) -> Self {
+        self.versions.push(version);
+        self
+    }
+
+    /// Adds a midstate for each of `versions`
+    pub fn versions(mut self, versions: impl IntoIterator<Item = u32>) -> Self {
+        self.versions.extend(versions);
+        self
+    }
+
+    /// Sets rolled ntime of the work. The ntime of the job is used by default.
+    pub fn ntime(mut self, ntime: u32) -> Self {
+        self.ntime = ntime;
+        self
+    }
+
+    pub fn deadline(mut self, deadline: Option<time::Instant>) -> Self {
+        self.deadline = deadline;
+        self
+    }
+
+    fn validate(&self) -> Result<(), InvalidWork> {
+        let midstate_count = self.versions.len().max(1);
+        if !MIDSTATE_COUNTS.contains(&midstate_count) {
+            Err(InvalidWork::MidstateCount(midstate_count))?
+        }
+
+        let job_version = self.job.version();
+        let version_mask = self.job.version_mask() & ii_bitcoin::BIP320_VERSION_MASK;
+        let mut versions = HashSet::with_capacity(self.versions.len());
+        for &version in self.versions.iter() {
+            if (version ^ job_version) & !version_mask != 0 {
+                Err(InvalidWork::Version {
+                    version,
+                    job_version,
+                    version_mask,
+                })?
+            }
+            if !versions.insert(version) {
+                Err(InvalidWork::DuplicateVersion(version))?
+            }
+        }
+
+        let job_time = self.job.time();
+        if self.ntime.wrapping_sub(job_time) >= super::engine::ROLL_NTIME_SECONDS {
+            Err(InvalidWork::Ntime {
+                ntime: self.ntime,
+                job_time,
+            })?
+        }
+        Ok(())
+    }
+
+    /// Validates the work and computes its midstates
+    pub fn build(mut self) -> Result<Assignment, InvalidWork> {
+        self.validate()?;
+        if self.versions.is_empty() {
+            self.versions.push(self.job.version());
+        }
+
+        // prepare block chunk1 with all invariants
+        let mut block_chunk1 = ii_bitcoin::BlockHeader {
+            previous_hash: self.job.previous_hash().into_inner(),
+            merkle_root: self.job.merkle_root().into_inner(),
+            ..Default::default()
+        };
+        let midstates = self
+            .versions
+            .iter()
+            .map(|&version| {
+                block_chunk1.version = version;
+                Midstate {
+                    version,
+                    state: block_chunk1.midstate(),
+                }
+            })
+            .collect();
+
+        Ok(Assignment::new(self.job, midstates, self.ntime).with_deadline(self.deadline))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::job::Bitcoin;
+    use crate::test_utils;
+
+    #[test]
+    fn test_build_test_blocks() {
+        for block in test_utils::TEST_BLOCKS.iter() {
+            let work = AssignmentBuilder::new(Arc::new(*block)).build().unwrap();
+            assert_eq!(work.midstates.len(), 1);
+            assert_eq!(work.midstates[0].version, block.version);
+            assert_eq!(work.midstates[0].state, block.midstate);
+            assert_eq!(work.ntime, block.time);
+            assert_eq!(work.bits(), block.bits);
+            assert_eq!(work.merkle_root_tail(), block.merkle_root_tail());
+            assert_eq!(work.block_header(0, block.nonce).hash(), block.hash);
+        }
+    }
+
+    #[test]
+    fn test_rolled_midstates() {
+        let job = Arc::new(test_utils::TEST_BLOCKS[0]);
+        let base_version = job.version();
+        for &midstate_count in MIDSTATE_COUNTS.iter() {
+            let versions = (0..midstate_count as u32).map(|i| base_version | (i << 13));
+            let work = AssignmentBuilder::new(job.clone())
+                .versions(versions.clone())
+                .ntime(job.time() + 1)
+                .build()
+                .unwrap();
+            assert_eq!(work.midstates.len(), midstate_count);
+            assert_eq!(work.ntime, job.time() + 1);
+            for (midstate, version) in work.midstates.iter().zip(versions) {
+                let header = ii_bitcoin::BlockHeader {
+                    version,
+                    previous_hash: job.previous_hash().into_inner(),
+                    merkle_root: job.merkle_root().into_inner(),
+                    ..Default::default()
+                };
+                assert_eq!(midstate.version, version);
+                assert_eq!(midstate.state, header.midstate());
+            }
+        }
+    }
+
+    #[test]
+    fn test_invalid_work() {
+        let job = Arc::new(test_utils::TEST_BLOCKS[0]);
+        let version = job.version();
+        let builder = || AssignmentBuilder::new(job.clone());
+
+        assert_eq!(
+            builder().versions(vec![version; 3]).build().unwrap_err(),
+            InvalidWork::MidstateCount(3)
+        );
+        assert_eq!(
+            builder()
+                .versions((0..8).map(|i| version | (i << 13)))
+                .build()
+                .unwrap_err(),
+            InvalidWork::MidstateCount(8)
+        );
+        assert_eq!(
+            builder()
+                .version(version)
+                .version(version)
+                .build()
+                .unwrap_err(),
+            InvalidWork::DuplicateVersion(version)
+        );
+        assert_eq!(
+            builder().version(version ^ 2).build().unwrap_err(),
+            InvalidWork::Version {
+                version: version ^ 2,
+                job_version: version,
+                version_mask: ii_bitcoin::BIP320_VERSION_MASK,
+            }
+        );
+        assert_eq!(
+            builder().ntime(job.time() - 1).build().unwrap_err(),
+            InvalidWork::Ntime {
+                ntime: job.time() - 1,
+                job_time: job.time(),
+            }
+        );
+        assert!(builder()
+            .ntime(job.time() + super::super::engine::ROLL_NTIME_SECONDS)
+            .build()
+            .is_err());
+    }
+}
@@ -25,6 +25,9 @@
 use super::*;
 use crate::job;
 
+use ii_async_compat::clock::{self, DynClock};
+use ii_logging::macros::*;
+
 use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::Arc;
 
@@ -43,13 +46,10 @@ impl Engine for ExhaustedWork {
     }
 }
 
-/// BIP320 specifies sixteen bits in block header nVersion field
-/// The maximal index represent the range which is excluded so it must be incremented by 1.
-const BIP320_UPPER_BOUND_EXCLUSIVE_INDEX: u32 = ii_bitcoin::BIP320_VERSION_MAX + 1;
 /// Once we exhaust the version we roll, we have to roll ntime.
 /// The current limit gives us support for miners with speed up to 2.4 PH/s
 /// hash_space * roll_ntime_seconds / new_stratum_job_every_sec = 2**(32 + 16) * 256 / 30 = 2.4e15
-const ROLL_NTIME_SECONDS: u32 = 256;
+pub const ROLL_NTIME_SECONDS: u32 = 256;
 
 /// Primitive for atomic range counter
 /// This structure can be freely shared among parallel processes and each range is returned only to
@@ -128,11 +128,29 @@ impl AtomicRange {
     }
 }
 
+/// Spread bits of `value` (starting with the least significant one) to positions of set bits
+/// in `mask`
+fn deposit_bits(mut value: u32, mut mask: u32) -> u32 {
+    let mut result = 0;
+    while mask != 0 {
+        let lowest_bit = mask & mask.wrapping_neg();
+        if value & 1 != 0 {
+            result |= lowest_bit;
+        }
+        value >>= 1;
+        mask &= !lowest_bit;
+    }
+    result
+}
+
 /// Version rolling implements WorkEngine trait and represents a shared source of work for mining
 /// backends. Each instance takes care of atomically allocating version field ranges until the
 /// range is full exhausted. After version has been rolled over, ntime is incremented and version
 /// resetted to 0. The limit of `ntime` range is determined by `ROLL_NTIME_SECONDS`.
 ///
+/// Only BIP320 bits allowed by the version mask of the job (e.g. negotiated with the pool) are
+/// rolled. Each midstate of generated work carries its own rolled version.
+///
 /// TODO: Rolling ntime together with version IS A HACK. This needs to be fixed properly by raising
 /// `ntime` in sync with real-time clock.
 #[derive(Debug, Clone)]
@@ -140,47 +158,81 @@ pub struct VersionRolling {
     job: Arc<dyn job::Bitcoin>,
     /// Number of midstates that each generated work covers
     midstate_count: usize,
-    /// Current range of the rolled part of the version (before BIP320 shift)
-    /// We keep current version in lower 16 bits and `ntime_offset`
-    /// in upper 8 bits. When version overflows, the ntime_offset gets
-    /// automatically incremented.
+    /// Current range of the rolled part of the version (before it is spread to `version_mask`)
+    /// We keep current version index modulo `version_count` and `ntime_offset` as the
+    /// quotient. When version overflows, the ntime_offset gets automatically incremented.
     curr_range: AtomicRange,
-    /// Base Bitcoin block header version with BIP320 bits cleared
+    /// Base Bitcoin block header version with rolled bits cleared
     base_version: u32,
+    /// Bits of the version field which are rolled
+    version_mask: u32,
+    /// Number of distinct versions given by `version_mask`
+    version_count: u32,
+    /// Time after which the job is too old and no more work is generated from it
+    deadline: Option<time::Instant>,
+    /// Source of time for the job expiration
+    clock: DynClock,
 }
 
 impl VersionRolling {
     pub fn new(job: Arc<dyn job::Bitcoin>, midstate_count: usize) -> Self {
-        let base_version = job.version() & !ii_bitcoin::BIP320_VERSION_MASK;
-        // we have to be sure we have no "leftover" midstates when we roll
-        assert_eq!(
-            BIP320_UPPER_BOUND_EXCLUSIVE_INDEX % (midstate_count as u32),
+        Self::with_clock(job, midstate_count, clock::system())
+    }
+
+    pub fn with_clock(job: Arc<dyn job::Bitcoin>, midstate_count: usize, clock: DynClock) -> Self {
+        let version_mask = job.version_mask() & ii_bitcoin::BIP320_VERSION_MASK;
+        let base_version = job.version() & !version_mask;
+        let version_count = 1 << version_mask.count_ones();
+        // we have to be sure we have no "leftover" midstates when we roll so work cannot be
+        // generated at all when the version mask does not allow enough distinct versions
+        let max_index = if version_count % (midstate_count as u32) == 0 {
+            version_count * ROLL_NTIME_SECONDS
+        } else {
+            warn!(
+                "Version mask {:#010x} does not allow rolling of {} midstates",
+                job.version_mask(),
+                midstate_count
+            );
             0
-        );
+        };
         Self {
             job,
             midstate_count,
-            curr_range: AtomicRange::new(
-                0,
-                BIP320_UPPER_BOUND_EXCLUSIVE_INDEX * ROLL_NTIME_SECONDS,
-                midstate_count as u32,
-            ),
+            curr_range: AtomicRange::new(0, max_index, midstate_count as u32),
             base_version,
+            version_mask,
+            version_count,
+            deadline: None,
+            clock,
         }
     }
 
+    /// Limits the time for which work is generated from the job. The age is measured from
+    /// creation of the engine which corresponds to reception of the job.
+    pub fn with_max_age(mut self, max_age: time::Duration) -> Self {
+        self.deadline = Some(self.clock.deadline(max_age));
+        self
+    }
+
+    /// Check if the job has been invalidated or is too old
+    fn is_expired(&self) -> bool {
+        !self.job.is_valid()
+            || self
+                .deadline
+                .map_or(false, |deadline| self.clock.now() >= deadline)
+    }
+
     /// Convert the allocated index to a block version as per BIP320
     #[inline]
     fn get_block_version(&self, index: u32) -> u32 {
-        let version = index % BIP320_UPPER_BOUND_EXCLUSIVE_INDEX;
-        assert!(version <= ii_bitcoin::BIP320_VERSION_MAX);
-        self.base_version | (version << ii_bitcoin::BIP320_VERSION_SHIFT)
+        let version = index % self.version_count;
+        self.base_version | deposit_bits(version, self.version_mask)
     }
 
     /// Convert the allocated index to a ntime offset
     #[inline]
     fn get_ntime_offset(&self, index: u32) -> u32 {
-        let ntime_offset = index / BIP320_UPPER_BOUND_EXCLUSIVE_INDEX;
+        let ntime_offset = index / self.version_count;
         assert!(ntime_offset < ROLL_NTIME_SECONDS);
         ntime_offset
     }
@@ -196,6 +248,11 @@ impl Engine for VersionRolling {
     }
 
     fn next_work(&self) -> LoopState<Assignment> {
+        // purge the rest of work when it cannot produce useful solutions anymore
+        if self.is_expired() {
+            self.terminate();
+            return LoopState::Exhausted;
+        }
         // determine next range of indexes from version space
         let (current, next) = match self.curr_range.next() {
             // return immediately when the space is exhausted
@@ -206,25 +263,6 @@ impl Engine for VersionRolling {
 
         // check if given range is the same as number of midstates
         assert_eq!(self.midstate_count, (next - current) as usize);
-        let mut midstates = Vec::with_capacity(self.midstate_count);
-
-        // prepare block chunk1 with all invariants
-        let mut block_chunk1 = ii_bitcoin::BlockHeader {
-            previous_hash: self.job.previous_hash().into_inner(),
-            merkle_root: self.job.merkle_root().into_inner(),
-            ..Default::default()
-        };
-
-        // generate all midstates from given range of indexes
-        for index in current..next {
-            // use index for generation compatible header version
-            let version = self.get_block_version(index);
-            block_chunk1.version = version;
-            midstates.push(Midstate {
-                version,
-                state: block_chunk1.midstate(),
-            })
-        }
 
         // Once we exhaust version-rolling-space, we start rolling ntime.
         // We can be sure ntime offset is common for all blocks, because `midstate_count`
@@ -233,7 +271,13 @@ impl Engine for VersionRolling {
         let ntime_offset = self.get_ntime_offset(current);
         assert_eq!(ntime_offset, self.get_ntime_offset(next - 1));
 
-        let work = Assignment::new(self.job.clone(), midstates, self.job.time() + ntime_offset);
+        // generate all midstates from given range of indexes
+        let work = AssignmentBuilder::new(self.job.clone())
+            .versions((current..next).map(|index| self.get_block_version(index)))
+            .ntime(self.job.time() + ntime_offset)
+            .deadline(self.deadline)
+            .build()
+            .expect("BUG: version rolling generated invalid work");
         if self.curr_range.is_exhausted(next) {
             // when the whole version space has been exhausted then mark the generated work as
             // a last one (the next call of this method will return 'Exhausted')
@@ -250,6 +294,12 @@ pub mod test {
     use crate::job::Bitcoin;
     use crate::test_utils;
 
+    use ii_async_compat::clock::{Clock, ManualClock};
+
+    /// BIP320 specifies sixteen bits in block header nVersion field
+    /// The maximal index represent the range which is excluded so it must be incremented by 1.
+    const BIP320_UPPER_BOUND_EXCLUSIVE_INDEX: u32 = ii_bitcoin::BIP320_VERSION_MAX + 1;
+
     fn compare_range(start: u32, stop: u32, step: u32) {
         let range = AtomicRange::new(start, stop, step);
         for i in (start..stop - (step - 1)).step_by(step as usize) {
@@ -403,4 +453,149 @@ pub mod test {
         }
         assert!(engine.is_exhausted());
     }
+
+    #[test]
+    fn test_expired_work() {
+        // use first test block for job
+        let job = Arc::new(test_utils::TEST_BLOCKS[0]);
+        let engine =
+            VersionRolling::new(job.clone(), 1).with_max_age(time::Duration::from_secs(60));
+
+        // work from young job carries its deadline
+        let work = engine.next_work().unwrap();
+        assert!(!work.is_expired(time::Instant::now()));
+        assert!(work.is_expired(time::Instant::now() + time::Duration::from_secs(60)));
+
+        // too old job is purged
+        let engine = VersionRolling::new(job, 1).with_max_age(time::Duration::from_secs(0));
+        assert!(!engine.is_exhausted());
+        match engine.next_work() {
+            LoopState::Exhausted => {}
+            _ => panic!("expected 'LoopState::Exhausted'"),
+        }
+        assert!(engine.is_exhausted());
+    }
+
+    #[test]
+    fn test_expired_work_clock() {
+        let job = Arc::new(test_utils::TEST_BLOCKS[0]);
+        let clock = Arc::new(ManualClock::new());
+        let engine = VersionRolling::with_clock(job, 1, clock.clone())
+            .with_max_age(time::Duration::from_secs(60));
+
+        clock.advance(time::Duration::from_secs(59));
+        let work = engine.next_work().unwrap();
+        assert!(!work.is_expired(clock.now()));
+
+        // the job expires exactly at its deadline
+        clock.advance(time::Duration::from_secs(1));
+        assert!(work.is_expired(clock.now()));
+        match engine.next_work() {
+            LoopState::Exhausted => {}
+            _ => panic!("expected 'LoopState::Exhausted'"),
+        }
+        assert!(engine.is_exhausted());
+    }
+
+    /// Test block which allows rolling only of bits in `version_mask`
+    #[derive(Debug)]
+    struct MaskedJob {
+        block: test_utils::TestBlock,
+        version_mask: u32,
+    }
+
+    impl job::Bitcoin for MaskedJob {
+        fn origin(&self) -> std::sync::Weak<dyn node::Client> {
+            self.block.origin()
+        }
+
+        fn version(&self) -> u32 {
+            self.block.version()
+        }
+
+        fn version_mask(&self) -> u32 {
+            self.version_mask
+        }
+
+        fn previous_hash(&self) -> &ii_bitcoin::DHash {
+            self.block.previous_hash()
+        }
+
+        fn merkle_root(&self) -> &ii_bitcoin::DHash {
+            self.block.merkle_root()
+        }
+
+        fn time(&self) -> u32 {
+            self.block.time()
+        }
+
+        fn bits(&self) -> u32 {
+            self.block.bits()
+        }
+
+        fn target(&self) -> ii_bitcoin::Target {
+            self.block.target()
+        }
+
+        fn is_valid(&self) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn test_deposit_bits() {
+        assert_eq!(
+            deposit_bits(0xffff, ii_bitcoin::BIP320_VERSION_MASK),
+            0x1fffe000
+        );
+        assert_eq!(deposit_bits(0b101, 0x0001_a000), 0x0001_2000);
+        assert_eq!(deposit_bits(0b11, 0), 0);
+    }
+
+    #[test]
+    fn test_version_mask() {
+        let block = test_utils::TEST_BLOCKS[0];
+        // two bits give four distinct versions for four midstates
+        let job = Arc::new(MaskedJob {
+            block,
+            version_mask: 0x0000_6000 | !ii_bitcoin::BIP320_VERSION_MASK,
+        });
+        let engine = VersionRolling::new(job.clone(), 4);
+
+        let work = engine.next_work().unwrap();
+        assert_eq!(work.ntime, block.time());
+        let versions: Vec<_> = work.midstates.iter().map(|mid| mid.version).collect();
+        let base_version = block.version() & !0x0000_6000;
+        assert_eq!(
+            versions,
+            vec![
+                base_version,
+                base_version | 0x2000,
+                base_version | 0x4000,
+                base_version | 0x6000
+            ]
+        );
+        for (midstate, version) in work.midstates.iter().zip(versions) {
+            let mut header = work.block_header(0, 0);
+            header.version = version;
+            assert_eq!(midstate.state, header.midstate());
+        }
+
+        // once the versions are exhausted the ntime is rolled
+        let work = engine.next_work().unwrap();
+        assert_eq!(work.ntime, block.time() + 1);
+        assert_eq!(work.midstates[0].version, base_version);
+
+        // the mask does not allow enough versions
+        let job = Arc::new(MaskedJob {
+            block,
+            version_mask: 0x0000_2000,
+        });
+        let engine = VersionRolling::new(job, 4);
+        assert!(engine.is_exhausted());
+        match engine.next_work() {
+            LoopState::Exhausted => {}
+            _ => panic!("expected 'LoopState::Exhausted'"),
+        }
+    }
 }
@@ -24,7 +24,6 @@ use super::*;
 use crate::backend;
 use crate::node;
 
-use futures::channel::mpsc;
 use futures::lock::Mutex;
 use ii_async_compat::futures;
 
@@ -63,7 +62,7 @@ where
         base_work_solver: Arc<T>,
         hierarchy_builder: Arc<dyn backend::HierarchyBuilder>,
         engine_receiver: EngineReceiver,
-        solution_sender: mpsc::UnboundedSender<Solution>,
+        solution_sender: super::SolutionQueueSender,
     ) -> Self {
         Self {
             node: NodeType::Base(base_work_solver),
@@ -225,7 +224,12 @@ impl Generator {
             let work_amount = work.generated_work_amount() as u64;
             // account generated work on the client side
             if let Some(origin) = work.origin().upgrade() {
-                origin.client_stats().generated_work().add(work_amount);
+                let client_stats = origin.client_stats();
+                client_stats.generated_work().add(work_amount);
+                let now = time::Instant::now();
+                if let Some(latency) = work.job.receipt().and_then(|receipt| receipt.take(now)) {
+                    client_stats.job_latency().account(latency, now).await;
+                }
             } else {
                 // Origin has been removed and no one will receive any solution
                 engine.terminate();
@@ -252,12 +256,14 @@ impl Generator {
 /// This struct is to be passed to the underlying mining backend. It allows submission of
 /// `work::Solution`
 #[derive(Debug, Clone)]
-pub struct SolutionSender(mpsc::UnboundedSender<Solution>);
+pub struct SolutionSender(super::SolutionQueueSender);
 
 impl SolutionSender {
+    /// Submits the solution without blocking, the oldest solution is dropped when the queue is
+    /// full
     pub fn send(&self, solution: Solution) {
         self.0
-            .unbounded_send(solution)
+            .try_send(solution)
             .expect("solution queue send failed");
     }
 }
@@ -0,0 +1,246 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Arithmetic of 256-bit targets and difficulties
+//!
+//! Difficulty is the ratio of the difficulty 1 target and some other target. Pools express it as
+//! a floating point number that does not have to be an integer so all conversions here are done
+//! in floating point with the exception of integral difficulties which are converted exactly.
+
+use crate::{DHash, HashTrait as _, Target, DIFFICULTY_1_TARGET_BYTES};
+
+use uint::U256;
+
+/// 2^64 represented as floating point number for conversion of 64-bit words of U256
+const WORD_BASE: f64 = 18_446_744_073_709_551_616.0;
+
+/// Number of bits in mantissa of `f64` including the implicit leading bit
+const F64_MANTISSA_BITS: i32 = 53;
+
+/// Target with difficulty 1
+#[inline]
+pub fn difficulty_1_target() -> U256 {
+    U256::from_big_endian(&DIFFICULTY_1_TARGET_BYTES)
+}
+
+/// Convert 256-bit integer to the nearest floating point number
+pub fn u256_to_f64(value: &U256) -> f64 {
+    value
+        .0
+        .iter()
+        .rev()
+        .fold(0.0, |result, &word| result * WORD_BASE + word as f64)
+}
+
+/// Convert non-negative floating point number to 256-bit integer. The fractional part is
+/// truncated. Returns `None` when the value is negative, not finite or out of range of `U256`.
+pub fn f64_to_u256(value: f64) -> Option<U256> {
+    if !value.is_finite() || value < 0.0 {
+        return None;
+    }
+    if value < 1.0 {
+        return Some(U256::zero());
+    }
+    let bits = value.to_bits();
+    // the value is normalized so that the mantissa is an integer with the implicit leading bit
+    let exponent = ((bits >> 52) & 0x7ff) as i32 - 1023 - (F64_MANTISSA_BITS - 1);
+    let mantissa = U256::from((bits & ((1u64 << 52) - 1)) | (1u64 << 52));
+    if exponent >= 0 {
+        if exponent + F64_MANTISSA_BITS > 256 {
+            return None;
+        }
+        Some(mantissa << exponent as usize)
+    } else {
+        Some(mantissa >> (-exponent) as usize)
+    }
+}
+
+/// Convert (pool) difficulty to target. Integral difficulties give the same result as
+/// `Target::from_pool_difficulty`.
+pub fn difficulty_to_target(difficulty: f64) -> Result<Target, &'static str> {
+    if !difficulty.is_finite() || difficulty <= 0.0 {
+        return Err("difficulty must be a positive number");
+    }
+    let target = if difficulty.fract() == 0.0 && difficulty <= std::u64::MAX as f64 {
+        difficulty_1_target() / U256::from(difficulty as u64)
+    } else {
+        f64_to_u256(u256_to_f64(&difficulty_1_target()) / difficulty)
+            .ok_or("difficulty is too low")?
+    };
+    // zero target cannot be met by any hash
+    Ok(target.max(U256::one()).into())
+}
+
+/// Convert target to (pool) difficulty. Zero target has infinite difficulty.
+pub fn target_to_difficulty(target: &Target) -> f64 {
+    u256_to_f64(&difficulty_1_target()) / u256_to_f64(target.as_ref())
+}
+
+/// Decode target from its compact representation used by Bitcoin protocol (`nBits`)
+pub fn compact_to_target(bits: u32) -> Result<Target, &'static str> {
+    // this code is inspired by `rust-bitcoin` crate implementation
+    // original comment:
+    //
+    // This is a floating-point "compact" encoding originally used by
+    // OpenSSL, which satoshi put into consensus code, so we're stuck
+    // with it. The exponent needs to have 3 subtracted from it, hence
+    // this goofy decoding code:
+    // TODO: use packed structure
+    let mantissa = bits & 0xffffff;
+    let exponent = bits >> 24;
+
+    // the mantissa is signed but may not be negative
+    if mantissa > 0x7fffff {
+        return Err("largest legal value for mantissa has been exceeded");
+    }
+
+    Ok(if exponent <= 3 {
+        Into::<U256>::into(mantissa >> (8 * (3 - exponent)))
+    } else {
+        Into::<U256>::into(mantissa) << (8 * (exponent - 3))
+    }
+    .into())
+}
+
+/// Encode target to its compact representation used by Bitcoin protocol (`nBits`)
+pub fn target_to_compact(target: &Target) -> u32 {
+    // this code is inspired by `rust-bitcoin` crate implementation
+    let target = target.as_ref();
+    let mut exponent = (target.bits() + 7) / 8;
+    let mut mantissa = if exponent <= 3 {
+        (target.low_u64() << (8 * (3 - exponent))) as u32
+    } else {
+        (*target >> (8 * (exponent - 3))).low_u32()
+    };
+
+    if (mantissa & 0x00800000) != 0 {
+        mantissa >>= 8;
+        exponent += 1;
+    }
+
+    // TODO: use packed structure
+    mantissa | (exponent << 24) as u32
+}
+
+/// Real difficulty of a share given by its double hash, i.e. the highest difficulty whose target
+/// the hash meets
+pub fn share_difficulty(hash: &DHash) -> f64 {
+    target_to_difficulty(&hash.into_inner().into())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{MeetsTarget, TEST_BLOCKS};
+
+    #[test]
+    fn test_u256_f64_conversion() {
+        assert_eq!(u256_to_f64(&U256::zero()), 0.0);
+        assert_eq!(u256_to_f64(&U256::from(12345u64)), 12345.0);
+        assert_eq!(u256_to_f64(&(U256::one() << 200)), 2f64.powi(200));
+        assert_eq!(
+            u256_to_f64(&difficulty_1_target()),
+            65535.0 * 2f64.powi(208)
+        );
+
+        assert_eq!(f64_to_u256(0.5), Some(U256::zero()));
+        assert_eq!(f64_to_u256(12345.75), Some(U256::from(12345u64)));
+        assert_eq!(f64_to_u256(2f64.powi(200)), Some(U256::one() << 200));
+        assert_eq!(
+            f64_to_u256(u256_to_f64(&difficulty_1_target())),
+            Some(difficulty_1_target())
+        );
+        assert_eq!(f64_to_u256(2f64.powi(256)), None);
+        assert_eq!(f64_to_u256(-1.0), None);
+        assert_eq!(f64_to_u256(std::f64::NAN), None);
+    }
+
+    #[test]
+    fn test_difficulty_target_conversion() {
+        for &difficulty in [1usize, 2, 3, 1000, 65536, 4_000_000_000].iter() {
+            let target = difficulty_to_target(difficulty as f64).unwrap();
+            assert_eq!(target, Target::from_pool_difficulty(difficulty));
+            assert_eq!(target.get_difficulty(), difficulty);
+            let real_difficulty = target_to_difficulty(&target);
+            assert!((real_difficulty - difficulty as f64).abs() / (difficulty as f64) < 1e-9);
+        }
+
+        // fractional difficulty yields target above difficulty 1
+        let target = difficulty_to_target(0.5).unwrap();
+        assert_eq!(
+            target.into_inner(),
+            difficulty_1_target() * U256::from(2u64)
+        );
+        assert_eq!(target_to_difficulty(&target), 0.5);
+
+        assert!(difficulty_to_target(0.0).is_err());
+        assert!(difficulty_to_target(-1.0).is_err());
+        assert!(difficulty_to_target(std::f64::INFINITY).is_err());
+        assert!(difficulty_to_target(1e-300).is_err());
+
+        // extreme difficulty still yields a target which can be met
+        assert_eq!(
+            difficulty_to_target(1e300).unwrap().into_inner(),
+            U256::one()
+        );
+        assert_eq!(
+            target_to_difficulty(&U256::zero().into()),
+            std::f64::INFINITY
+        );
+    }
+
+    #[test]
+    fn test_compact() {
+        assert_eq!(
+            compact_to_target(0x1d00ffff).unwrap().into_inner(),
+            difficulty_1_target()
+        );
+        assert_eq!(
+            compact_to_target(0x03123456).unwrap().into_inner(),
+            U256::from(0x123456u64)
+        );
+        assert_eq!(
+            compact_to_target(0x01120000).unwrap().into_inner(),
+            U256::from(0x12u64)
+        );
+        assert!(compact_to_target(0x04923456).is_err());
+        for block in TEST_BLOCKS.iter() {
+            let target = compact_to_target(block.bits).unwrap();
+            assert_eq!(target_to_compact(&target), block.bits);
+        }
+    }
+
+    #[test]
+    fn test_share_difficulty() {
+        for block in TEST_BLOCKS.iter() {
+            let network_target = compact_to_target(block.bits).unwrap();
+            let difficulty = share_difficulty(&block.hash);
+            // block hash meets the network difficulty
+            assert!(difficulty >= target_to_difficulty(&network_target));
+            // and no target of higher difficulty than its share difficulty
+            let target = difficulty_to_target(difficulty.ceil() + 1.0).unwrap();
+            assert!(!block.hash.meets(&target));
+            let target = difficulty_to_target(difficulty.floor()).unwrap();
+            assert!(block.hash.meets(&target));
+        }
+    }
+}
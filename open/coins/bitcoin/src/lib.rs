@@ -20,6 +20,7 @@
 // of such proprietary license or if you have any other questions, please
 // contact us at opensource@braiins.com.
 
+pub mod difficulty;
 pub mod test_blocks;
 
 // reexport Bitcoin test structures
@@ -222,8 +223,9 @@ impl<'a, T: FromMidstateWord<T>> DoubleEndedIterator for MidstateWords<'a, T> {
 pub struct Target(uint::U256);
 
 impl Target {
+    #[inline]
     fn difficulty_1_target() -> uint::U256 {
-        uint::U256::from_big_endian(&DIFFICULTY_1_TARGET_BYTES)
+        difficulty::difficulty_1_target()
     }
 
     /// Create target from hexadecimal string which has the same representation as Bitcoin SHA256
@@ -245,52 +247,21 @@ impl Target {
 
     /// Create target from its compact representation used by Bitcoin protocol
     pub fn from_compact(bits: u32) -> Result<Self, &'static str> {
-        // this code is inspired by `rust-bitcoin` crate implementation
-        // original comment:
-        //
-        // This is a floating-point "compact" encoding originally used by
-        // OpenSSL, which satoshi put into consensus code, so we're stuck
-        // with it. The exponent needs to have 3 subtracted from it, hence
-        // this goofy decoding code:
-        // TODO: use packed structure
-        let mantissa = bits & 0xffffff;
-        let exponent = bits >> 24;
-
-        // the mantissa is signed but may not be negative
-        if mantissa > 0x7fffff {
-            return Err("largest legal value for mantissa has been exceeded");
-        }
-
-        Ok(if exponent <= 3 {
-            Into::<uint::U256>::into(mantissa >> (8 * (3 - exponent)))
-        } else {
-            Into::<uint::U256>::into(mantissa) << (8 * (exponent - 3))
-        }
-        .into())
+        difficulty::compact_to_target(bits)
     }
 
     /// Convert target to pool difficulty
     pub fn get_difficulty(&self) -> usize {
+        if self.0.is_zero() {
+            // zero target cannot be met by any hash
+            return std::usize::MAX;
+        }
         (Self::difficulty_1_target() / self.0).low_u64() as usize
     }
 
     /// Convert target to its compact representation used by Bitcoin protocol
     pub fn into_compact(self) -> u32 {
-        // this code is inspired by `rust-bitcoin` crate implementation
-        let mut exponent = (self.0.bits() + 7) / 8;
-        let mut mantissa = if exponent <= 3 {
-            (self.0.low_u64() << (8 * (3 - exponent))) as u32
-        } else {
-            (self.0 >> (8 * (exponent - 3))).low_u32()
-        };
-
-        if (mantissa & 0x00800000) != 0 {
-            mantissa >>= 8;
-            exponent += 1;
-        }
-
-        // TODO: use packed structure
-        mantissa | (exponent << 24) as u32
+        difficulty::target_to_compact(&self)
     }
 
     /// Yields the U256 number that represents the target
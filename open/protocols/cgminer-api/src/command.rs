@@ -62,6 +62,25 @@ pub const DEVDETAILS: &str = "devdetails";
 pub const TEMPCTRL: &str = "tempctrl";
 pub const TEMPS: &str = "temps";
 pub const FANS: &str = "fans";
+pub const SHARES: &str = "shares";
+pub const LATENCY: &str = "latency";
+pub const BEST_SHARES: &str = "bestshares";
+pub const EFFICIENCY: &str = "efficiency";
+pub const PAUSE: &str = "pause";
+pub const RESUME: &str = "resume";
+pub const SHARE_RATIO: &str = "shareratio";
+pub const LOST_WORK: &str = "lostwork";
+pub const RESTART: &str = "restart";
+pub const POWER_TARGET: &str = "powertarget";
+pub const CHIPS: &str = "chips";
+pub const CHIP_ERRORS: &str = "chiperrors";
+pub const RESET_CHIP_ERRORS: &str = "resetchiperrors";
+pub const MIDSTATE_COUNT: &str = "midstatecount";
+pub const SELF_TEST: &str = "selftest";
+pub const REGISTERS: &str = "registers";
+pub const CHIP_TEMPS: &str = "chiptemps";
+pub const PRESET: &str = "preset";
+pub const PSU: &str = "psu";
 
 pub type Result<T> = std::result::Result<T, response::Error>;
 /// Type describing command table
@@ -138,10 +157,58 @@ impl HandlerType {
     }
 }
 
+/// Privilege level required for a command or granted to a client
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Privilege {
+    /// Commands which only read the state of the miner
+    ReadOnly,
+    /// Commands which change configuration or state of the miner
+    Admin,
+}
+
+/// Maps authentication tokens sent in the `token` field of a request to privileges
+#[derive(Debug, Clone)]
+pub struct AccessControl {
+    /// Privilege granted to requests without a valid token, `None` denies all commands
+    anonymous: Option<Privilege>,
+    tokens: HashMap<String, Privilege>,
+}
+
+impl AccessControl {
+    pub fn new<T>(anonymous: T) -> Self
+    where
+        T: Into<Option<Privilege>>,
+    {
+        Self {
+            anonymous: anonymous.into(),
+            tokens: HashMap::new(),
+        }
+    }
+
+    pub fn add_token(&mut self, token: String, privilege: Privilege) {
+        self.tokens.insert(token, privilege);
+    }
+
+    /// Resolves privilege of a request with optional `token`
+    pub fn privilege(&self, token: Option<&str>) -> Option<Privilege> {
+        token
+            .and_then(|token| self.tokens.get(token).copied())
+            .or(self.anonymous)
+    }
+}
+
+impl Default for AccessControl {
+    /// Full access without authentication
+    fn default() -> Self {
+        Self::new(Privilege::Admin)
+    }
+}
+
 /// Describes individual commands and async handler associated with this command
 pub struct Descriptor {
     handler: HandlerType,
     parameter_check: Option<ParameterCheckHandler>,
+    privilege: Privilege,
 }
 
 impl Descriptor {
@@ -152,13 +219,37 @@ impl Descriptor {
         Self {
             handler,
             parameter_check: parameter_check.into(),
+            privilege: Privilege::Admin,
         }
     }
 
+    /// Sets privilege required for the command. Administrator privilege is required by default
+    /// so that a new command is never exposed to read-only clients by accident.
+    pub fn with_privilege(mut self, privilege: Privilege) -> Self {
+        self.privilege = privilege;
+        self
+    }
+
     #[inline]
     pub fn has_parameters(&self) -> bool {
         self.handler.has_parameters()
     }
+
+    #[inline]
+    pub fn is_allowed(&self, privilege: Option<Privilege>) -> bool {
+        match privilege {
+            Some(privilege) => privilege >= self.privilege,
+            None => false,
+        }
+    }
+}
+
+/// Allows read-only clients to access all `commands`
+pub fn read_only(mut commands: Map) -> Map {
+    for descriptor in commands.values_mut() {
+        descriptor.privilege = Privilege::ReadOnly;
+    }
+    commands
 }
 
 /// Generates a descriptor for a specified command type (`ParameterLess` or `Parameter`) that also
@@ -219,6 +310,7 @@ pub struct Receiver<T = UnixTime> {
     miner_signature: String,
     miner_version: String,
     description: String,
+    access_control: AccessControl,
     _marker: marker::PhantomData<T>,
 }
 
@@ -254,18 +346,13 @@ where
         let check_asc: ParameterCheckHandler =
             Box::new(|command, parameter| Self::check_asc(command, parameter));
 
-        let mut commands = commands![
+        let mut commands = read_only(commands![
             // generic commands
             (POOLS: ParameterLess -> handler.handle_pools),
             (DEVS: ParameterLess -> handler.handle_devs),
             (EDEVS: ParameterLess -> handler.handle_edevs),
             (SUMMARY: ParameterLess -> handler.handle_summary),
-            (SWITCH_POOL: Parameter(check_switch_pool) -> handler.handle_switch_pool),
             (CONFIG: ParameterLess -> handler.handle_config),
-            (ENABLE_POOL: Parameter(check_enable_pool) -> handler.handle_enable_pool),
-            (DISABLE_POOL: Parameter(check_disable_pool) -> handler.handle_disable_pool),
-            (ADD_POOL: Parameter(check_add_pool) -> handler.handle_add_pool),
-            (REMOVE_POOL: Parameter(check_remove_pool) -> handler.handle_remove_pool),
             (STATS: ParameterLess -> handler.handle_stats),
             (ESTATS: ParameterLess -> handler.handle_estats),
             (COIN: ParameterLess -> handler.handle_coin),
@@ -275,7 +362,15 @@ where
             // special built-in commands
             (VERSION: BuiltIn(Version)),
             (CHECK: BuiltIn(Check))
-        ];
+        ]);
+        // commands changing the configuration require administrator privilege
+        commands.extend(commands![
+            (SWITCH_POOL: Parameter(check_switch_pool) -> handler.handle_switch_pool),
+            (ENABLE_POOL: Parameter(check_enable_pool) -> handler.handle_enable_pool),
+            (DISABLE_POOL: Parameter(check_disable_pool) -> handler.handle_disable_pool),
+            (ADD_POOL: Parameter(check_add_pool) -> handler.handle_add_pool),
+            (REMOVE_POOL: Parameter(check_remove_pool) -> handler.handle_remove_pool)
+        ]);
 
         if let Some(custom_commands) = custom_commands.into() {
             commands.extend(custom_commands.into_iter());
@@ -287,10 +382,17 @@ where
             miner_signature,
             miner_version,
             description,
+            access_control: Default::default(),
             _marker: marker::PhantomData,
         }
     }
 
+    /// Restricts access to commands according to privileges of authentication tokens
+    pub fn with_access_control(mut self, access_control: AccessControl) -> Self {
+        self.access_control = access_control;
+        self
+    }
+
     fn check_add_pool(_command: &str, parameter: &Option<&json::Value>) -> Result<()> {
         const ARG_COUNT: usize = 3;
         match parameter {
@@ -333,31 +435,41 @@ where
         })
     }
 
-    fn handle_check(&self, parameter: Option<&json::Value>) -> Result<response::Check> {
+    fn handle_check(
+        &self,
+        parameter: Option<&json::Value>,
+        privilege: Option<Privilege>,
+    ) -> Result<response::Check> {
         let command =
             parameter.ok_or_else(|| response::Error::from(response::ErrorCode::MissingCheckCmd))?;
-        let result = match command {
-            json::Value::String(command) => self.commands.get(command.as_str()).into(),
-            _ => response::Bool::N,
+        let descriptor = match command {
+            json::Value::String(command) => self.commands.get(command.as_str()),
+            _ => None,
         };
 
         Ok(response::Check {
-            exists: result,
-            access: result,
+            exists: descriptor.into(),
+            access: descriptor
+                .filter(|descriptor| descriptor.is_allowed(privilege))
+                .into(),
         })
     }
 
     /// Handles a single `command` with optional `parameter`. `multi_command` flag ensures that no
-    /// command with parameters can be processed in batched mode.
+    /// command with parameters can be processed in batched mode. The command is processed only
+    /// when the client's `privilege` is sufficient.
     async fn handle_single(
         &self,
         command: &str,
         parameter: Option<&json::Value>,
         multi_command: bool,
+        privilege: Option<Privilege>,
     ) -> response::Dispatch {
         let dispatch = match self.commands.get(command) {
             Some(descriptor) => {
-                if multi_command && descriptor.has_parameters() {
+                if (multi_command && descriptor.has_parameters())
+                    || !descriptor.is_allowed(privilege)
+                {
                     Err(response::ErrorCode::AccessDeniedCmd(command.to_string()).into())
                 } else {
                     let check_result = descriptor
@@ -371,9 +483,9 @@ where
                             HandlerType::Version => {
                                 self.handle_version().map(|response| response.into())
                             }
-                            HandlerType::Check => {
-                                self.handle_check(parameter).map(|response| response.into())
-                            }
+                            HandlerType::Check => self
+                                .handle_check(parameter, privilege)
+                                .map(|response| response.into()),
                         },
                         Err(response) => Err(response),
                     }
@@ -413,17 +525,27 @@ where
             .filter(|command| command.len() > 0)
             .collect();
         let parameter = command_request.value.get("parameter");
+        let privilege = self.access_control.privilege(
+            command_request
+                .value
+                .get("token")
+                .and_then(json::Value::as_str),
+        );
 
         if commands.len() == 0 {
             self.get_single_response(response::ErrorCode::InvalidCommand.into())
         } else if commands.len() == 1 {
-            self.get_single_response(self.handle_single(command, parameter, false).await)
+            self.get_single_response(
+                self.handle_single(command, parameter, false, privilege)
+                    .await,
+            )
         } else {
             let mut responses = MultiResponse::new();
             for command in commands {
-                if let ResponseType::Single(response) =
-                    self.get_single_response(self.handle_single(command, parameter, true).await)
-                {
+                if let ResponseType::Single(response) = self.get_single_response(
+                    self.handle_single(command, parameter, true, privilege)
+                        .await,
+                ) {
                     responses.add_response(command, response);
                 }
             }
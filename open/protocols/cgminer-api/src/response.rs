@@ -129,6 +129,25 @@ pub enum StatusCode {
     TempCtrl = 200,
     Temps = 201,
     Fans = 202,
+    Shares = 203,
+    Latency = 204,
+    BestShares = 205,
+    Efficiency = 206,
+    Pause = 207,
+    Resume = 208,
+    ShareRatio = 209,
+    LostWork = 210,
+    Restart = 211,
+    PowerTarget = 212,
+    Chips = 213,
+    ChipErrors = 214,
+    ResetChipErrors = 215,
+    MidstateCount = 216,
+    SelfTest = 217,
+    Registers = 218,
+    ChipTemps = 219,
+    Preset = 220,
+    Psu = 221,
 
     // info status codes
     PoolAlreadyEnabled = 49,
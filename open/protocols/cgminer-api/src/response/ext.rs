@@ -125,3 +125,683 @@ impl From<Fans> for Dispatch {
         )
     }
 }
+
+/// Number of solved shares with difficulty in the range `[difficulty, 2 * difficulty)`
+#[derive(Serialize, PartialEq, Clone, Debug)]
+pub struct ShareBucket {
+    #[serde(rename = "Difficulty")]
+    pub difficulty: u64,
+    #[serde(rename = "Count")]
+    pub count: u64,
+    /// Number of shares expected to be found with the same amount of work
+    #[serde(rename = "Expected")]
+    pub expected: f64,
+    #[serde(rename = "Luck")]
+    pub luck: f64,
+}
+
+/// Histogram of solved share difficulties with luck statistics
+#[derive(Serialize, PartialEq, Clone, Debug)]
+pub struct Shares {
+    #[serde(rename = "Elapsed")]
+    pub elapsed: u64,
+    #[serde(rename = "Valid Shares")]
+    pub valid_shares: u64,
+    #[serde(rename = "Diff1 Work")]
+    pub diff1_work: f64,
+    #[serde(rename = "Best Share")]
+    pub best_share: u64,
+    #[serde(rename = "Expected Best Share")]
+    pub expected_best_share: f64,
+    /// Ratio of the best and expected best share
+    #[serde(rename = "Luck")]
+    pub luck: f64,
+    #[serde(rename = "Histogram")]
+    pub histogram: Vec<ShareBucket>,
+}
+
+impl From<Shares> for Dispatch {
+    fn from(shares: Shares) -> Self {
+        Dispatch::from_success(
+            StatusCode::Shares.into(),
+            "Share statistics".to_string(),
+            Some(Body {
+                name: "SHARES",
+                list: vec![shares],
+            }),
+        )
+    }
+}
+
+/// Percentiles of latencies (in seconds) measured during last few minutes
+#[derive(Serialize, PartialEq, Clone, Debug)]
+pub struct LatencyPercentiles {
+    #[serde(rename = "Samples")]
+    pub samples: u32,
+    #[serde(rename = "P50")]
+    pub p50: Interval,
+    #[serde(rename = "P90")]
+    pub p90: Interval,
+    #[serde(rename = "P99")]
+    pub p99: Interval,
+}
+
+#[derive(Serialize, PartialEq, Clone, Debug)]
+pub struct PoolLatency {
+    #[serde(rename = "LATENCY")]
+    pub idx: i32,
+    #[serde(rename = "URL")]
+    pub url: String,
+    /// Round-trip time of share submissions
+    #[serde(rename = "Submit")]
+    pub submit: LatencyPercentiles,
+    /// Delay between receiving a new job and dispatching it to the hardware
+    #[serde(rename = "Job")]
+    pub job: LatencyPercentiles,
+}
+
+pub struct Latency {
+    pub list: Vec<PoolLatency>,
+}
+
+impl From<Latency> for Dispatch {
+    fn from(latency: Latency) -> Self {
+        let pool_count = latency.list.len();
+        Dispatch::from_success(
+            StatusCode::Latency.into(),
+            format!("{} Pool(s)", pool_count),
+            Some(Body {
+                name: "LATENCY",
+                list: latency.list,
+            }),
+        )
+    }
+}
+
+/// The best share of the miner, a pool or a hash chain found during current session and during
+/// the whole lifetime of the miner
+#[derive(Serialize, PartialEq, Clone, Debug)]
+pub struct BestShare {
+    #[serde(rename = "BESTSHARE")]
+    pub idx: i32,
+    /// One of "Miner", "Pool" or "Chain"
+    #[serde(rename = "Type")]
+    pub kind: String,
+    #[serde(rename = "Name")]
+    pub name: String,
+    #[serde(rename = "Session Best Share")]
+    pub session_best_share: u64,
+    #[serde(rename = "Session Best Share Time")]
+    pub session_best_share_time: Time,
+    #[serde(rename = "Lifetime Best Share")]
+    pub lifetime_best_share: u64,
+    #[serde(rename = "Lifetime Best Share Time")]
+    pub lifetime_best_share_time: Time,
+}
+
+pub struct BestShares {
+    pub list: Vec<BestShare>,
+}
+
+impl From<BestShares> for Dispatch {
+    fn from(best_shares: BestShares) -> Self {
+        Dispatch::from_success(
+            StatusCode::BestShares.into(),
+            "Best shares".to_string(),
+            Some(Body {
+                name: "BESTSHARES",
+                list: best_shares.list,
+            }),
+        )
+    }
+}
+
+/// Estimated power and energy efficiency (J/TH) of one hash chain
+#[derive(Serialize, PartialEq, Clone, Debug)]
+pub struct ChainEfficiency {
+    #[serde(rename = "EFFICIENCY")]
+    pub idx: i32,
+    #[serde(rename = "ID")]
+    pub id: i32,
+    /// Current power estimate in W
+    #[serde(rename = "Power")]
+    pub power: f64,
+    #[serde(rename = "J/TH 5s")]
+    pub efficiency_5s: f64,
+    #[serde(rename = "J/TH 1m")]
+    pub efficiency_1m: f64,
+    #[serde(rename = "J/TH 15m")]
+    pub efficiency_15m: f64,
+}
+
+pub struct Efficiency {
+    pub list: Vec<ChainEfficiency>,
+}
+
+impl From<Efficiency> for Dispatch {
+    fn from(efficiency: Efficiency) -> Self {
+        let chain_count = efficiency.list.len();
+        Dispatch::from_success(
+            StatusCode::Efficiency.into(),
+            format!("{} Chain(s)", chain_count),
+            Some(Body {
+                name: "EFFICIENCY",
+                list: efficiency.list,
+            }),
+        )
+    }
+}
+
+/// State of mining controlled by pause and resume commands
+#[derive(Serialize, PartialEq, Clone, Debug)]
+pub struct MiningState {
+    #[serde(rename = "Paused")]
+    pub paused: bool,
+    /// Number of hash chains stopped by pause
+    #[serde(rename = "Paused Chains")]
+    pub paused_chains: u32,
+    /// Seconds remaining to automatic resume
+    #[serde(rename = "Resume In")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resume_in: Option<u64>,
+}
+
+pub struct Pause {
+    pub state: MiningState,
+}
+
+impl From<Pause> for Dispatch {
+    fn from(pause: Pause) -> Self {
+        Dispatch::from_success(
+            StatusCode::Pause.into(),
+            "Mining paused".to_string(),
+            Some(Body {
+                name: "PAUSE",
+                list: vec![pause.state],
+            }),
+        )
+    }
+}
+
+pub struct Resume {
+    pub state: MiningState,
+}
+
+impl From<Resume> for Dispatch {
+    fn from(resume: Resume) -> Self {
+        Dispatch::from_success(
+            StatusCode::Resume.into(),
+            "Mining resumed".to_string(),
+            Some(Body {
+                name: "RESUME",
+                list: vec![resume.state],
+            }),
+        )
+    }
+}
+
+/// Split of work between pools compared with the ratio given by configuration of pool groups.
+/// Shares are sums of difficulties of submitted solutions.
+#[derive(Serialize, PartialEq, Clone, Debug)]
+pub struct PoolShareRatio {
+    #[serde(rename = "SHARERATIO")]
+    pub idx: i32,
+    #[serde(rename = "URL")]
+    pub url: String,
+    #[serde(rename = "Group")]
+    pub group: String,
+    /// Configured share ratio of the whole group
+    #[serde(rename = "Target Ratio")]
+    pub target_ratio: f64,
+    #[serde(rename = "Session Accepted")]
+    pub session_accepted: u64,
+    #[serde(rename = "Session Rejected")]
+    pub session_rejected: u64,
+    /// Seconds connected to the pool
+    #[serde(rename = "Session Uptime")]
+    pub session_uptime: u64,
+    /// Ratio of shares accepted by the pool to shares accepted by all pools
+    #[serde(rename = "Session Ratio")]
+    pub session_ratio: f64,
+    #[serde(rename = "Lifetime Accepted")]
+    pub lifetime_accepted: u64,
+    #[serde(rename = "Lifetime Rejected")]
+    pub lifetime_rejected: u64,
+    #[serde(rename = "Lifetime Uptime")]
+    pub lifetime_uptime: u64,
+    #[serde(rename = "Lifetime Ratio")]
+    pub lifetime_ratio: f64,
+}
+
+pub struct ShareRatio {
+    pub list: Vec<PoolShareRatio>,
+}
+
+impl From<ShareRatio> for Dispatch {
+    fn from(share_ratio: ShareRatio) -> Self {
+        let pool_count = share_ratio.list.len();
+        Dispatch::from_success(
+            StatusCode::ShareRatio.into(),
+            format!("{} Pool(s)", pool_count),
+            Some(Body {
+                name: "SHARERATIO",
+                list: share_ratio.list,
+            }),
+        )
+    }
+}
+
+/// Work sent to one hash chain which has never returned any solution
+#[derive(Serialize, PartialEq, Clone, Debug)]
+pub struct ChainLostWork {
+    #[serde(rename = "LOSTWORK")]
+    pub idx: i32,
+    #[serde(rename = "ID")]
+    pub id: i32,
+    /// Number of work items which have been processed by chips for sufficiently long time
+    #[serde(rename = "Retired Work")]
+    pub retired_work: u64,
+    /// Number of retired work items without any solution
+    #[serde(rename = "Empty Work")]
+    pub empty_work: u64,
+    /// Number of work items without any solution which would be expected at current difficulty
+    #[serde(rename = "Expected Empty Work")]
+    pub expected_empty_work: f64,
+    /// Estimated number of work items lost by hardware
+    #[serde(rename = "Lost Work")]
+    pub lost_work: u64,
+}
+
+pub struct LostWork {
+    pub list: Vec<ChainLostWork>,
+}
+
+impl From<LostWork> for Dispatch {
+    fn from(lost_work: LostWork) -> Self {
+        let chain_count = lost_work.list.len();
+        Dispatch::from_success(
+            StatusCode::LostWork.into(),
+            format!("{} Chain(s)", chain_count),
+            Some(Body {
+                name: "LOSTWORK",
+                list: lost_work.list,
+            }),
+        )
+    }
+}
+
+#[derive(Serialize, PartialEq, Clone, Debug)]
+pub struct RestartState {
+    /// Number of hash chains being initialized again
+    #[serde(rename = "Restarted Chains")]
+    pub restarted_chains: u32,
+}
+
+pub struct Restart {
+    pub state: RestartState,
+}
+
+impl From<Restart> for Dispatch {
+    fn from(restart: Restart) -> Self {
+        Dispatch::from_success(
+            StatusCode::Restart.into(),
+            "Restarting backend".to_string(),
+            Some(Body {
+                name: "RESTART",
+                list: vec![restart.state],
+            }),
+        )
+    }
+}
+
+/// State of power target mode limiting power consumption of the miner
+#[derive(Serialize, PartialEq, Clone, Debug)]
+pub struct PowerTargetState {
+    /// Power limit in W, it is missing when the mode is disabled
+    #[serde(rename = "Power Target")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<f64>,
+    /// Estimated power of all hash chains in W
+    #[serde(rename = "Power")]
+    pub power: f64,
+    /// Number of hash chains clocked down to fit into the limit
+    #[serde(rename = "Limited Chains")]
+    pub limited_chains: u32,
+}
+
+pub struct PowerTarget {
+    pub state: PowerTargetState,
+}
+
+impl From<PowerTarget> for Dispatch {
+    fn from(power_target: PowerTarget) -> Self {
+        Dispatch::from_success(
+            StatusCode::PowerTarget.into(),
+            "Power target".to_string(),
+            Some(Body {
+                name: "POWERTARGET",
+                list: vec![power_target.state],
+            }),
+        )
+    }
+}
+
+/// Number of midstates used by hash chains (1 when AsicBoost is disabled)
+#[derive(Serialize, PartialEq, Clone, Debug)]
+pub struct MidstateCountState {
+    #[serde(rename = "Midstate Count")]
+    pub midstate_count: u32,
+    /// Number of hash chains being initialized again with the new midstate count
+    #[serde(rename = "Restarted Chains")]
+    pub restarted_chains: u32,
+}
+
+pub struct MidstateCount {
+    pub state: MidstateCountState,
+}
+
+impl From<MidstateCount> for Dispatch {
+    fn from(midstate_count: MidstateCount) -> Self {
+        Dispatch::from_success(
+            StatusCode::MidstateCount.into(),
+            "Midstate count".to_string(),
+            Some(Body {
+                name: "MIDSTATECOUNT",
+                list: vec![midstate_count.state],
+            }),
+        )
+    }
+}
+
+/// Effective hashrate of one chip computed from its valid solutions on backend difficulty
+#[derive(Serialize, PartialEq, Clone, Debug)]
+pub struct ChipHashrate {
+    #[serde(rename = "CHIPS")]
+    pub idx: i32,
+    /// ID of hash chain
+    #[serde(rename = "ID")]
+    pub id: i32,
+    /// Index of chip on the hash chain
+    #[serde(rename = "Chip")]
+    pub chip: i32,
+    #[serde(rename = "Solutions")]
+    pub solutions: u64,
+    #[serde(rename = "MHS 1m")]
+    pub mhs_1m: f64,
+    #[serde(rename = "MHS 15m")]
+    pub mhs_15m: f64,
+    #[serde(rename = "MHS 24h")]
+    pub mhs_24h: f64,
+}
+
+pub struct Chips {
+    pub list: Vec<ChipHashrate>,
+}
+
+impl From<Chips> for Dispatch {
+    fn from(chips: Chips) -> Self {
+        let chip_count = chips.list.len();
+        Dispatch::from_success(
+            StatusCode::Chips.into(),
+            format!("{} Chip(s)", chip_count),
+            Some(Body {
+                name: "CHIPS",
+                list: chips.list,
+            }),
+        )
+    }
+}
+
+/// Hardware errors of one chip accounted since the last reset of counters
+#[derive(Serialize, PartialEq, Clone, Debug)]
+pub struct ChipError {
+    #[serde(rename = "CHIPERRORS")]
+    pub idx: i32,
+    /// ID of hash chain
+    #[serde(rename = "ID")]
+    pub id: i32,
+    /// Index of chip on the hash chain
+    #[serde(rename = "Chip")]
+    pub chip: i32,
+    /// Number of valid nonces
+    #[serde(rename = "Nonces")]
+    pub nonces: u64,
+    /// Number of invalid (not meeting ASIC target), duplicate or mismatched nonces
+    #[serde(rename = "Errors")]
+    pub errors: u64,
+    /// Number of suppressed duplicate nonces (included in `errors`)
+    #[serde(rename = "Duplicates")]
+    pub duplicates: u64,
+    /// Percentage of errors out of all nonces
+    #[serde(rename = "Error Rate")]
+    pub error_rate: f64,
+    /// Responses of the whole hash chain dropped due to CRC mismatch
+    #[serde(rename = "Chain CRC Errors")]
+    pub chain_crc_errors: u64,
+    /// Malformed responses of the whole hash chain which passed the CRC check
+    #[serde(rename = "Chain RX Errors")]
+    pub chain_rx_errors: u64,
+    /// Nonces of the whole hash chain outside of subranges of enumerated chips
+    #[serde(rename = "Chain Subrange Errors")]
+    pub chain_subrange_errors: u64,
+    /// Solutions of the chip are dropped because it has been found faulty (the chip keeps
+    /// hashing, its nonce subrange is not reassigned)
+    #[serde(rename = "Isolated")]
+    pub isolated: bool,
+}
+
+pub struct ChipErrors {
+    pub list: Vec<ChipError>,
+}
+
+impl From<ChipErrors> for Dispatch {
+    fn from(chip_errors: ChipErrors) -> Self {
+        let chip_count = chip_errors.list.len();
+        Dispatch::from_success(
+            StatusCode::ChipErrors.into(),
+            format!("{} Chip(s)", chip_count),
+            Some(Body {
+                name: "CHIPERRORS",
+                list: chip_errors.list,
+            }),
+        )
+    }
+}
+
+#[derive(Serialize, PartialEq, Clone, Debug)]
+pub struct ResetChipErrorsState {
+    /// Number of hash chains with counters reset
+    #[serde(rename = "Reset Chains")]
+    pub reset_chains: u32,
+}
+
+pub struct ResetChipErrors {
+    pub state: ResetChipErrorsState,
+}
+
+impl From<ResetChipErrors> for Dispatch {
+    fn from(reset_chip_errors: ResetChipErrors) -> Self {
+        Dispatch::from_success(
+            StatusCode::ResetChipErrors.into(),
+            "Chip error counters reset".to_string(),
+            Some(Body {
+                name: "RESETCHIPERRORS",
+                list: vec![reset_chip_errors.state],
+            }),
+        )
+    }
+}
+
+/// Result of self-test of one chip
+#[derive(Serialize, PartialEq, Clone, Debug)]
+pub struct ChipSelfTest {
+    #[serde(rename = "SELFTEST")]
+    pub idx: i32,
+    /// ID of hash chain
+    #[serde(rename = "ID")]
+    pub id: i32,
+    /// Index of chip on the hash chain
+    #[serde(rename = "Chip")]
+    pub chip: i32,
+    /// One of `passed`, `failed` or `untested` (when no test vector covers the chip)
+    #[serde(rename = "Status")]
+    pub status: String,
+}
+
+pub struct SelfTest {
+    pub list: Vec<ChipSelfTest>,
+}
+
+impl From<SelfTest> for Dispatch {
+    fn from(self_test: SelfTest) -> Self {
+        let chip_count = self_test.list.len();
+        Dispatch::from_success(
+            StatusCode::SelfTest.into(),
+            format!("{} Chip(s)", chip_count),
+            Some(Body {
+                name: "SELFTEST",
+                list: self_test.list,
+            }),
+        )
+    }
+}
+
+/// Register dump of one hash chain
+#[derive(Serialize, PartialEq, Clone, Debug)]
+pub struct ChainRegisters {
+    #[serde(rename = "REGISTERS")]
+    pub idx: i32,
+    /// ID of hash chain
+    #[serde(rename = "ID")]
+    pub id: i32,
+    /// Structured register dump as produced by the miner backend
+    #[serde(rename = "Dump")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dump: Option<json::Value>,
+    /// Reason why the registers could not be dumped
+    #[serde(rename = "Error")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+pub struct Registers {
+    pub list: Vec<ChainRegisters>,
+}
+
+impl From<Registers> for Dispatch {
+    fn from(registers: Registers) -> Self {
+        let chain_count = registers.list.len();
+        Dispatch::from_success(
+            StatusCode::Registers.into(),
+            format!("{} Chain(s)", chain_count),
+            Some(Body {
+                name: "REGISTERS",
+                list: registers.list,
+            }),
+        )
+    }
+}
+
+/// Temperature measured by sensor attached to one chip
+#[derive(Serialize, PartialEq, Clone, Debug)]
+pub struct ChipTemp {
+    #[serde(rename = "CHIPTEMP")]
+    pub idx: i32,
+    /// ID of hash chain
+    #[serde(rename = "ID")]
+    pub id: i32,
+    /// Index of chip on the hash chain
+    #[serde(rename = "Chip")]
+    pub chip: i32,
+    /// Board temperature near the chip
+    #[serde(rename = "Board")]
+    pub board: f64,
+    /// Die temperature of the chip
+    #[serde(rename = "Temperature")]
+    pub temperature: f64,
+}
+
+pub struct ChipTemps {
+    pub list: Vec<ChipTemp>,
+}
+
+impl From<ChipTemps> for Dispatch {
+    fn from(chip_temps: ChipTemps) -> Self {
+        let chip_count = chip_temps.list.len();
+        Dispatch::from_success(
+            StatusCode::ChipTemps.into(),
+            format!("{} Chip(s)", chip_count),
+            Some(Body {
+                name: "CHIPTEMPS",
+                list: chip_temps.list,
+            }),
+        )
+    }
+}
+
+/// Preset of frequency, voltage and fan target temperature selected for one hash chain
+#[derive(Serialize, PartialEq, Clone, Debug)]
+pub struct ChainPreset {
+    #[serde(rename = "PRESET")]
+    pub idx: i32,
+    /// ID of hash chain
+    #[serde(rename = "ID")]
+    pub id: i32,
+    /// Name of the preset, it is empty when the chain runs with its configured settings
+    #[serde(rename = "Preset")]
+    pub preset: String,
+}
+
+pub struct Presets {
+    pub list: Vec<ChainPreset>,
+}
+
+impl From<Presets> for Dispatch {
+    fn from(presets: Presets) -> Self {
+        let chain_count = presets.list.len();
+        Dispatch::from_success(
+            StatusCode::Preset.into(),
+            format!("{} Chain(s)", chain_count),
+            Some(Body {
+                name: "PRESETS",
+                list: presets.list,
+            }),
+        )
+    }
+}
+
+/// Telemetry of the power supply, values which cannot be read are missing
+#[derive(Serialize, PartialEq, Clone, Debug)]
+pub struct PsuState {
+    /// Input voltage in V
+    #[serde(rename = "Input Voltage")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input_voltage: Option<f64>,
+    /// Output current in A
+    #[serde(rename = "Output Current")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_current: Option<f64>,
+    #[serde(rename = "Temperature")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    /// Mining is paused by the under-voltage protection
+    #[serde(rename = "Under Voltage")]
+    pub under_voltage: bool,
+}
+
+pub struct Psu {
+    pub state: PsuState,
+}
+
+impl From<Psu> for Dispatch {
+    fn from(psu: Psu) -> Self {
+        Dispatch::from_success(
+            StatusCode::Psu.into(),
+            "PSU".to_string(),
+            Some(Body {
+                name: "PSU",
+                list: vec![psu.state],
+            }),
+        )
+    }
+}
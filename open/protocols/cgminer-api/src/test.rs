@@ -29,7 +29,7 @@ use crate::command;
 use crate::commands;
 use crate::response;
 
-use utils::{assert_json_eq, codec_roundtrip};
+use utils::{assert_json_eq, codec_roundtrip, codec_roundtrip_with_access_control};
 
 use ii_async_compat::tokio;
 
@@ -338,3 +338,114 @@ async fn test_multiple_custom_commands() {
 
     assert_json_eq(&response, &expected);
 }
+
+fn test_access_control() -> command::AccessControl {
+    let mut access_control = command::AccessControl::new(command::Privilege::ReadOnly);
+    access_control.add_token("secret".to_string(), command::Privilege::Admin);
+    access_control
+}
+
+#[tokio::test]
+async fn test_access_denied() {
+    let command: json::Value = json::json!({
+        "command": "addpool",
+        "parameter": "url,user,pass",
+        "token": "invalid"
+    });
+    let response = codec_roundtrip_with_access_control(command, None, test_access_control()).await;
+    let expected = json::json!({
+        "STATUS": [{
+            "STATUS": "E",
+            "When": 0,
+            "Code": 45,
+            "Msg": "Access denied to 'addpool' command",
+            "Description": "TestMiner v1.0",
+        }],
+        "id": 1
+    });
+
+    assert_json_eq(&response, &expected);
+}
+
+#[tokio::test]
+async fn test_access_granted() {
+    let command: json::Value = json::json!({
+        "command": "addpool",
+        "parameter": "url,user,pass",
+        "token": "secret"
+    });
+    let response = codec_roundtrip_with_access_control(command, None, test_access_control()).await;
+    let expected = json::json!({
+        "STATUS": [{
+            "STATUS": "S",
+            "When": 0,
+            "Code": 55,
+            "Msg": "Added pool 0: ''",
+            "Description": "TestMiner v1.0",
+        }],
+        "id": 1
+    });
+
+    assert_json_eq(&response, &expected);
+}
+
+#[tokio::test]
+async fn test_check_access() {
+    let command: json::Value = json::json!({
+        "command": "check",
+        "parameter": "removepool"
+    });
+    let response = codec_roundtrip_with_access_control(command, None, test_access_control()).await;
+    let expected = json::json!({
+        "STATUS": [{
+            "STATUS": "S",
+            "When": 0,
+            "Code": 72,
+            "Msg": "Check command",
+            "Description": "TestMiner v1.0",
+        }],
+        "CHECK": [{
+            "Exists": "Y",
+            "Access": "N"
+        }],
+        "id": 1
+    });
+
+    assert_json_eq(&response, &expected);
+
+    // anonymous requests are denied completely without read-only access
+    let command: json::Value = json::json!({ "command": "version" });
+    let response =
+        codec_roundtrip_with_access_control(command, None, command::AccessControl::new(None)).await;
+    assert_eq!(response["STATUS"][0]["Code"], 45);
+}
+
+#[tokio::test]
+async fn test_custom_command_privilege() {
+    let handler = Arc::new(TestCustomHandler);
+
+    const ADMIN_COMMAND: &str = "admin_command";
+    const READ_ONLY_COMMAND: &str = "read_only_command";
+    let custom_commands = || {
+        let mut custom_commands = commands![
+            (ADMIN_COMMAND: ParameterLess -> handler.handle_command_one)
+        ];
+        custom_commands.extend(command::read_only(commands![
+            (READ_ONLY_COMMAND: ParameterLess -> handler.handle_command_one)
+        ]));
+        custom_commands
+    };
+
+    // custom commands require administrator privilege unless marked as read-only
+    let command: json::Value = json::json!({ "command": ADMIN_COMMAND });
+    let response =
+        codec_roundtrip_with_access_control(command, custom_commands(), test_access_control())
+            .await;
+    assert_eq!(response["STATUS"][0]["Code"], 45);
+
+    let command: json::Value = json::json!({ "command": READ_ONLY_COMMAND });
+    let response =
+        codec_roundtrip_with_access_control(command, custom_commands(), test_access_control())
+            .await;
+    assert_eq!(response["STATUS"][0]["Code"], 301);
+}
@@ -42,6 +42,17 @@ impl support::When for ZeroTime {
 }
 
 pub async fn codec_roundtrip<T>(command: json::Value, custom_commands: T) -> Value
+where
+    T: Into<Option<command::Map>>,
+{
+    codec_roundtrip_with_access_control(command, custom_commands, Default::default()).await
+}
+
+pub async fn codec_roundtrip_with_access_control<T>(
+    command: json::Value,
+    custom_commands: T,
+    access_control: command::AccessControl,
+) -> Value
 where
     T: Into<Option<command::Map>>,
 {
@@ -50,7 +61,8 @@ where
         "TestMiner".to_string(),
         "v1.0".to_string(),
         custom_commands,
-    );
+    )
+    .with_access_control(access_control);
     let mut codec = Codec::default();
 
     let mut command_buf = BytesMut::with_capacity(256);
@@ -21,84 +21,59 @@
 // contact us at opensource@braiins.com.
 
 //! Module that represents stratum protocol errors
+//!
+//! `Error` consists of a stable `ErrorKind` that callers can match on and of an optional
+//! underlying error which is preserved as `source()`, so that the whole chain of errors can be
+//! reported.
 
-use failure::{Backtrace, Context, Fail};
 use std;
-use std::fmt::{self, Display};
+use std::fmt;
 use std::io;
 
 use ii_async_compat::tokio_util;
+use thiserror::Error;
 
-#[derive(Debug)]
-pub struct Error {
-    inner: Context<ErrorKind>,
-}
-
-#[derive(Clone, Eq, PartialEq, Debug, Fail)]
+#[derive(Error, Clone, Eq, PartialEq, Debug)]
 pub enum ErrorKind {
     /// Input/Output error.
-    #[fail(display = "I/O error: {}", _0)]
+    #[error("I/O error: {0}")]
     Io(String),
 
     /// Errors emitted by serde
-    #[fail(display = "Serde: {}", _0)]
+    #[error("Serde: {0}")]
     Serde(String),
 
     /// General error used for more specific .
-    #[fail(display = "General error: {}", _0)]
+    #[error("General error: {0}")]
     General(String),
 
     /// Unexpected version of something.
-    #[fail(display = "Unexpected {} version: {}, expected: {}", _0, _1, _2)]
+    #[error("Unexpected {0} version: {1}, expected: {2}")]
     UnexpectedVersion(String, String, String),
 
-    #[fail(display = "Noise handshake error: {}", _0)]
+    #[error("Noise handshake error: {0}")]
     Noise(String),
 
     /// Stratum version 1 error
-    #[fail(display = "V1 error: {}", _0)]
+    #[error("V1 error: {0}")]
     V1(super::v1::error::ErrorKind),
     /// Stratum version 2 error
-    #[fail(display = "V2 error: {}", _0)]
+    #[error("V2 error: {0}")]
     V2(super::v2::error::ErrorKind),
 }
 
-/// Implement Fail trait instead of use Derive to get more control over custom type.
-/// The main advantage is customization of Context type which allows conversion of
-/// any error types to this custom error with general error kind by calling context
-/// method on any result type.
-impl Fail for Error {
-    fn cause(&self) -> Option<&dyn Fail> {
-        self.inner.cause()
-    }
+ii_error::error_type!(ErrorKind);
 
-    fn backtrace(&self) -> Option<&Backtrace> {
-        self.inner.backtrace()
+/// Context message provided by `ResultExt::context()`
+impl From<&str> for ErrorKind {
+    fn from(info: &str) -> Self {
+        ErrorKind::General(info.to_string())
     }
 }
 
-impl Display for Error {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        Display::fmt(&self.inner, f)
-    }
-}
-
-impl Error {
-    pub fn kind(&self) -> ErrorKind {
-        self.inner.get_context().clone()
-    }
-
-    pub fn into_inner(self) -> Context<ErrorKind> {
-        self.inner
-    }
-}
-
-/// Convenience conversion to Error from ErrorKind that carries the context
-impl From<ErrorKind> for Error {
-    fn from(kind: ErrorKind) -> Self {
-        Self {
-            inner: Context::new(kind),
-        }
+impl From<String> for ErrorKind {
+    fn from(info: String) -> Self {
+        ErrorKind::General(info)
     }
 }
 
@@ -116,111 +91,84 @@ impl From<super::v2::error::ErrorKind> for Error {
     }
 }
 
-impl From<Context<ErrorKind>> for Error {
-    fn from(inner: Context<ErrorKind>) -> Self {
-        Self { inner }
-    }
-}
-
 impl From<io::Error> for Error {
     fn from(e: io::Error) -> Self {
-        let msg = e.to_string();
-        Self {
-            inner: e.context(ErrorKind::Io(msg)),
-        }
+        Self::with_source(ErrorKind::Io(e.to_string()), e)
     }
 }
 
 impl From<fmt::Error> for Error {
     fn from(e: fmt::Error) -> Self {
-        let msg = e.to_string();
-        Self {
-            inner: e.context(ErrorKind::General(msg)),
-        }
+        Self::with_source(ErrorKind::General(e.to_string()), e)
     }
 }
 
 impl From<tokio_util::codec::LinesCodecError> for Error {
     fn from(e: tokio_util::codec::LinesCodecError) -> Self {
-        let msg = e.to_string();
-        Self {
-            inner: e.context(ErrorKind::Io(msg)),
-        }
+        Self::with_source(ErrorKind::Io(e.to_string()), e)
     }
 }
 
 impl From<snow::error::Error> for Error {
     fn from(e: snow::error::Error) -> Self {
-        let msg = e.to_string();
-        Self {
-            inner: e.context(ErrorKind::Noise(msg)),
-        }
+        Self::with_source(ErrorKind::Noise(e.to_string()), e)
     }
 }
 
 impl From<ed25519_dalek::SignatureError> for Error {
     fn from(e: ed25519_dalek::SignatureError) -> Self {
-        let msg = e.to_string();
-        Self {
-            inner: e.context(ErrorKind::Noise(msg)),
-        }
+        Self::with_source(ErrorKind::Noise(e.to_string()), e)
     }
 }
 
 impl From<bs58::decode::Error> for Error {
     fn from(e: bs58::decode::Error) -> Self {
-        let msg = e.to_string();
-        Self {
-            inner: e.context(ErrorKind::Noise(msg)),
-        }
+        Self::with_source(ErrorKind::Noise(e.to_string()), e)
     }
 }
 
 impl From<std::str::Utf8Error> for Error {
     fn from(e: std::str::Utf8Error) -> Self {
-        let msg = e.to_string();
-        Self {
-            inner: e.context(ErrorKind::General(msg)),
-        }
-    }
-}
-
-impl From<Context<&str>> for Error {
-    fn from(context: Context<&str>) -> Self {
-        Self {
-            inner: context.map(|info| ErrorKind::General(info.to_string())),
-        }
-    }
-}
-
-impl From<Context<String>> for Error {
-    fn from(context: Context<String>) -> Self {
-        Self {
-            inner: context.map(|info| ErrorKind::General(info)),
-        }
+        Self::with_source(ErrorKind::General(e.to_string()), e)
     }
 }
 
 impl From<serde_json::error::Error> for Error {
     fn from(e: serde_json::error::Error) -> Self {
-        let msg = e.to_string();
-        Self {
-            inner: e.context(ErrorKind::Serde(msg)),
-        }
+        Self::with_source(ErrorKind::Serde(e.to_string()), e)
     }
 }
 
 impl From<super::v2::serialization::Error> for Error {
     fn from(e: super::v2::serialization::Error) -> Self {
-        let msg = e.to_string();
-        Self {
-            inner: e.context(ErrorKind::Serde(msg)),
+        Self::with_source(ErrorKind::Serde(e.to_string()), e)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::v2;
+    use std::error::Error as StdError;
+
+    #[test]
+    fn test_error_chain() {
+        let result: std::result::Result<(), _> = Err(io::Error::other("broken pipe"));
+        let error = result.context("Cannot send frame").unwrap_err();
+        assert_eq!(
+            error.kind(),
+            ErrorKind::General("Cannot send frame".to_string())
+        );
+        assert_eq!(error.to_string(), "General error: Cannot send frame");
+        let source = error.source().expect("missing source");
+        assert_eq!(source.to_string(), "broken pipe");
+        assert!(source.downcast_ref::<io::Error>().is_some());
+
+        let error: Error = v2::error::ErrorKind::UnknownMessage("x".into()).into();
+        assert!(error.source().is_none());
+        match error.kind() {
+            ErrorKind::V2(v2::error::ErrorKind::UnknownMessage(_)) => (),
+            kind => panic!("unexpected error kind {:?}", kind),
         }
     }
 }
-
-/// A specialized `Result` type bound to [`Error`].
-pub type Result<T> = std::result::Result<T, Error>;
-
-/// Re-export failure's ResultExt for easier usage
-pub use failure::ResultExt;
@@ -23,16 +23,16 @@
 //! Keytool that allows:
 //! - generating public/secret keypair for ED25519 curve
 //! - generating and signing a stratum server certificate with a specified master secret key
-//! - validating a specified certificate
+//! - validating a specified certificate and optionally checking it against the signing authority
+//!   and the server secret key
 
 use anyhow::{anyhow, Context, Result};
 use ii_stratum::v2::noise;
-use rand::rngs::OsRng;
 use std::convert::{TryFrom, TryInto};
 use std::fs::{File, OpenOptions};
 use std::io::{Read, Write};
 use std::path::PathBuf;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 use structopt::StructOpt;
 
 /// All commands recognized by the keytool
@@ -48,6 +48,8 @@ enum Command {
     GenNoiseKey(GenNoiseKeyCommand),
     /// Sign a specified key
     SignKey(SignKeyCommand),
+    /// Verify a specified certificate
+    VerifyCert(VerifyCertCommand),
 }
 
 /// Generates keypair suitable for certification authority and stores secret and public key into
@@ -74,8 +76,7 @@ impl GenCAKeyCommand {
     fn execute(self) -> Result<()> {
         print!("Generating ED25519 keypair...");
 
-        let mut csprng = OsRng {};
-        let keypair = ed25519_dalek::Keypair::generate(&mut csprng);
+        let keypair = noise::auth::generate_authority_keypair();
 
         write_to_file(
             &self.public_key_file,
@@ -149,86 +150,162 @@ struct SignKeyCommand {
     /// How many days the generated certificate should be valid for
     #[structopt(short, long, default_value = "90")]
     valid_for_days: usize,
+    /// Unix timestamp of the start of the certificate validity (now by default)
+    #[structopt(long)]
+    valid_from: Option<u64>,
+    /// Output file for the certificate (derived from the public key file name by default)
+    #[structopt(short, long, parse(from_os_str))]
+    certificate_file: Option<PathBuf>,
 }
 
 impl SignKeyCommand {
-    fn open_file(file: &PathBuf, descr: &str) -> Result<File> {
-        OpenOptions::new().read(true).open(file).context(format!(
-            "cannot open {} ({:?})",
-            descr,
-            file.clone().into_os_string()
-        ))
-    }
-
-    fn read_from_file<T: TryFrom<String>>(
-        file_path_buf: &PathBuf,
-        error_context_descr: &str,
-    ) -> Result<T>
-    where
-        T: TryFrom<String>,
-        <T as std::convert::TryFrom<std::string::String>>::Error: std::fmt::Display,
-    {
-        let mut file = Self::open_file(file_path_buf, error_context_descr)?;
-        let mut file_content = String::new();
-        file.read_to_string(&mut file_content).context(format!(
-            "Cannot read {} ({:?})",
-            error_context_descr, file_path_buf
-        ))?;
-
-        let parsed_file_content = T::try_from(file_content).map_err(|e| {
-            anyhow!(
-                "Cannot parse {} ({:?}) {}",
-                error_context_descr,
-                file_path_buf,
-                e
-            )
-        })?;
-
-        Ok(parsed_file_content)
-    }
-
     fn execute(self) -> Result<()> {
-        let public_key = Self::read_from_file::<noise::auth::StaticPublicKeyFormat>(
+        let public_key = read_from_file::<noise::auth::StaticPublicKeyFormat>(
             &self.public_key_to_sign,
             "static public key to sign",
         )?;
 
-        let authority_secret_key = Self::read_from_file::<noise::auth::Ed25519SecretKeyFormat>(
+        let authority_secret_key = read_from_file::<noise::auth::Ed25519SecretKeyFormat>(
             &self.signing_key,
             "signing key",
         )?
         .into_inner();
+        let authority_keypair = noise::auth::authority_keypair_from_secret(authority_secret_key);
 
-        // Dalek crate requires the full Keypair for signing
-        let authority_keypair = ed25519_dalek::Keypair {
-            // Derive the public key from the secret key
-            public: (&authority_secret_key).into(),
-            secret: authority_secret_key,
+        let valid_from = match self.valid_from {
+            Some(timestamp) => SystemTime::UNIX_EPOCH + Duration::from_secs(timestamp),
+            None => SystemTime::now(),
+        };
+        let valid_for = Duration::from_secs((self.valid_for_days * 24 * 60 * 60) as u64);
+        let header = noise::auth::SignedPartHeader::new(valid_from, valid_from + valid_for)
+            .map_err(|e| anyhow!("{}", e))?;
+
+        let certificate =
+            noise::auth::Certificate::sign(header, public_key.into_inner(), &authority_keypair)
+                .map_err(|e| anyhow!("{}", e))
+                .context("Signing certificate")?;
+
+        // Derive the certificate file name from the public key filename unless specified
+        let cert_file = match self.certificate_file {
+            Some(cert_file) => cert_file,
+            None => {
+                let mut cert_file = self.public_key_to_sign.clone();
+                cert_file.set_extension("cert");
+                cert_file
+            }
         };
 
-        let header = noise::auth::SignedPartHeader::with_duration(Duration::from_secs(
-            (self.valid_for_days * 24 * 60 * 60) as u64,
-        ))
-        .map_err(|e| anyhow!("{}", e))?;
+        write_to_file(&cert_file, certificate, "certificate")
+    }
+}
 
-        let signed_part =
-            noise::auth::SignedPart::new(header, public_key.into_inner(), authority_keypair.public);
+/// Command that verifies signature and validity of a certificate. Optionally, it checks that the
+/// certificate has been signed by the expected authority and that it matches the secret key the
+/// server is configured with.
+#[derive(Debug, StructOpt)]
+struct VerifyCertCommand {
+    /// Certificate to be verified
+    #[structopt(short, long, parse(from_os_str))]
+    certificate: PathBuf,
+    /// Public key of the authority that is expected to have signed the certificate
+    #[structopt(short, long, parse(from_os_str))]
+    authority_public_key: Option<PathBuf>,
+    /// Noise static secret key that is expected to match the certified public key
+    #[structopt(short, long, parse(from_os_str))]
+    secret_key: Option<PathBuf>,
+}
 
-        let signature = signed_part
-            .sign_with(&authority_keypair)
+impl VerifyCertCommand {
+    fn execute(self) -> Result<()> {
+        let certificate =
+            read_from_file::<noise::auth::Certificate>(&self.certificate, "certificate")?;
+
+        println!(
+            "Signed by: {}",
+            noise::auth::EncodedEd25519PublicKey::new(certificate.authority_public_key())
+        );
+        println!(
+            "Valid from: {}",
+            format_unix_time(certificate.header().valid_from())
+        );
+        println!(
+            "Not valid after: {}",
+            format_unix_time(certificate.header().not_valid_after())
+        );
+
+        certificate
+            .validate()
             .map_err(|e| anyhow!("{}", e))
-            .context("Signing certificate")?;
+            .context("Invalid certificate")?;
+
+        if let Some(authority_public_key) = self.authority_public_key.as_ref() {
+            let authority_public_key = read_from_file::<noise::auth::Ed25519PublicKeyFormat>(
+                authority_public_key,
+                "authority public key",
+            )?
+            .into_inner();
+            certificate
+                .verify_authority(&authority_public_key)
+                .map_err(|e| anyhow!("{}", e))?;
+        }
+        if let Some(secret_key) = self.secret_key.as_ref() {
+            let secret_key = read_from_file::<noise::auth::StaticSecretKeyFormat>(
+                secret_key,
+                "noise static secret key",
+            )?
+            .into_inner();
+            certificate
+                .validate_secret_key(&secret_key)
+                .map_err(|e| anyhow!("{}", e))?;
+        }
+        println!("Certificate is valid");
 
-        // Final step is to compose the certificate from all components and serialize it into a file
-        let certificate = noise::auth::Certificate::new(signed_part, signature);
-        // Derive the certificate file name from the public key filename
-        let mut cert_file = self.public_key_to_sign.clone();
-        cert_file.set_extension("cert");
-
-        write_to_file(&cert_file, certificate, "certificate")
+        Ok(())
     }
 }
 
+/// Formats time as unix timestamp, the precision of certificate times is seconds
+fn format_unix_time(time: SystemTime) -> String {
+    let timestamp = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+    format!("{} (unix time)", timestamp)
+}
+
+fn open_file(file: &PathBuf, descr: &str) -> Result<File> {
+    OpenOptions::new().read(true).open(file).context(format!(
+        "cannot open {} ({:?})",
+        descr,
+        file.clone().into_os_string()
+    ))
+}
+
+/// Helper that reads any String deserializable type from a specified path
+fn read_from_file<T>(file_path_buf: &PathBuf, error_context_descr: &str) -> Result<T>
+where
+    T: TryFrom<String>,
+    <T as std::convert::TryFrom<std::string::String>>::Error: std::fmt::Display,
+{
+    let mut file = open_file(file_path_buf, error_context_descr)?;
+    let mut file_content = String::new();
+    file.read_to_string(&mut file_content).context(format!(
+        "Cannot read {} ({:?})",
+        error_context_descr, file_path_buf
+    ))?;
+
+    let parsed_file_content = T::try_from(file_content).map_err(|e| {
+        anyhow!(
+            "Cannot parse {} ({:?}) {}",
+            error_context_descr,
+            file_path_buf,
+            e
+        )
+    })?;
+
+    Ok(parsed_file_content)
+}
+
 /// Helper that opens a new file for writing or emits an error with specified context description
 /// if the file already exists. This is important to prevent overwriting already generated files.
 fn open_new_file(file: &PathBuf, descr: &str) -> Result<File> {
@@ -277,5 +354,6 @@ fn main() -> Result<()> {
         Command::GenCAKey(gen_key_cmd) => gen_key_cmd.execute(),
         Command::GenNoiseKey(gen_key_cmd) => gen_key_cmd.execute(),
         Command::SignKey(sign_key_cmd) => sign_key_cmd.execute(),
+        Command::VerifyCert(verify_cert_cmd) => verify_cert_cmd.execute(),
     }
 }
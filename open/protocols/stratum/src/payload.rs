@@ -117,12 +117,10 @@ impl<P: Protocol> Payload<P> {
             Self::SerializedBytes(payload) => writer
                 .write(payload)
                 .context("Serialize static payload")
-                .map(|_| ())
-                .map_err(Into::into),
+                .map(|_| ()),
             Self::LazyBytes(payload) => payload
                 .serialize_to_writer(writer)
-                .context("Serialize dynamic payload")
-                .map_err(Into::into),
+                .context("Serialize dynamic payload"),
         }
     }
 }
@@ -0,0 +1,749 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Diagnostic tool that connects to a stratum pool, performs the full handshake and reports how
+//! long each phase took:
+//! - V1: TCP connect, subscribe, authorize, first job (`mining.notify`)
+//! - V2: TCP connect, noise handshake, setup connection, open channel, first job (`NewMiningJob`
+//!   together with its `SetNewPrevHash`)
+//!
+//! Optionally, a test share is submitted for the first job to measure the round trip of share
+//! validation. The share is not backed by any real work, therefore the pool is expected to
+//! reject it and the rejection is not considered a failure.
+
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use ii_async_compat::prelude::*;
+use ii_async_compat::tokio;
+use ii_stratum::v1::{self, rpc::StratumError, rpc::StratumResult, MessageId};
+use ii_stratum::v2::{
+    self,
+    framing::Header,
+    messages::{
+        NewMiningJob, OpenStandardMiningChannel, OpenStandardMiningChannelError,
+        OpenStandardMiningChannelSuccess, SetNewPrevHash, SetupConnection, SetupConnectionError,
+        SetupConnectionSuccess, SubmitSharesError, SubmitSharesStandard, SubmitSharesSuccess,
+    },
+    types::{DeviceInfo, Str0_255},
+};
+use std::collections::{HashMap, VecDeque};
+use std::convert::{TryFrom, TryInto};
+use std::fmt;
+use std::future::Future;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use structopt::StructOpt;
+use tokio::net::TcpStream;
+
+/// Identification of the tool sent to the pool
+const AGENT: &str = concat!("ii-stratum-ping/", env!("CARGO_PKG_VERSION"));
+
+/// Nominal hashrate announced when opening a V2 channel (1 GH/s)
+const NOMINAL_HASHRATE: f32 = 1e9;
+
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "ii-stratum-ping",
+    about = "Tool for diagnosing connectivity to Stratum V1 and V2 pools"
+)]
+struct Args {
+    /// Pool URL in one of the forms 'stratum+tcp://host[:port]',
+    /// 'stratum2+tcp://host[:port]/<authority public key>' or 'stratum2+tcp+insecure://host[:port]'
+    url: PoolUrl,
+    /// User name (and worker) used for authorization or opening the mining channel
+    #[structopt(short, long)]
+    user: String,
+    /// Password used for V1 authorization
+    #[structopt(short, long, default_value = "")]
+    password: String,
+    /// Submit a test share for the first job received from the pool
+    #[structopt(short, long)]
+    submit: bool,
+    /// How many seconds to wait for each phase to complete
+    #[structopt(short, long, default_value = "10")]
+    timeout: u64,
+}
+
+#[derive(Debug, Clone)]
+enum Protocol {
+    V1,
+    V2(v2::noise::auth::EncodedEd25519PublicKey),
+    V2Insecure,
+}
+
+/// Parsed pool URL
+#[derive(Debug, Clone)]
+struct PoolUrl {
+    protocol: Protocol,
+    host: String,
+    port: u16,
+}
+
+impl PoolUrl {
+    const SCHEME_STRATUM_V1: &'static str = "stratum+tcp";
+    const SCHEME_STRATUM_V2: &'static str = "stratum2+tcp";
+    const SCHEME_STRATUM_V2_INSECURE: &'static str = "stratum2+tcp+insecure";
+
+    const DEFAULT_PORT_STRATUM_V1: u16 = 3333;
+    const DEFAULT_PORT_STRATUM_V2: u16 = 3336;
+}
+
+impl FromStr for PoolUrl {
+    type Err = anyhow::Error;
+
+    fn from_str(url: &str) -> Result<Self> {
+        let mut parts = url.splitn(2, "://");
+        let scheme = parts.next().expect("BUG: missing URL scheme");
+        let rest = parts
+            .next()
+            .ok_or_else(|| anyhow!("missing scheme in URL '{}'", url))?;
+        let mut parts = rest.splitn(2, '/');
+        let host_port = parts.next().expect("BUG: missing URL host");
+        let path = parts.next().unwrap_or("");
+
+        let (protocol, default_port) = match scheme {
+            Self::SCHEME_STRATUM_V1 => (Protocol::V1, Self::DEFAULT_PORT_STRATUM_V1),
+            Self::SCHEME_STRATUM_V2 => {
+                if path.is_empty() {
+                    bail!("missing upstream authority public key in URL '{}'", url);
+                }
+                let public_key =
+                    v2::noise::auth::EncodedEd25519PublicKey::try_from(path.to_string())
+                        .map_err(|e| anyhow!("invalid upstream authority public key: {}", e))?;
+                (Protocol::V2(public_key), Self::DEFAULT_PORT_STRATUM_V2)
+            }
+            Self::SCHEME_STRATUM_V2_INSECURE => {
+                (Protocol::V2Insecure, Self::DEFAULT_PORT_STRATUM_V2)
+            }
+            _ => bail!("unsupported scheme '{}'", scheme),
+        };
+        if !path.is_empty() && !matches!(protocol, Protocol::V2(_)) {
+            bail!("unexpected path in URL '{}'", url);
+        }
+
+        let (host, port) = match host_port.rfind(':') {
+            Some(i) => (
+                &host_port[..i],
+                host_port[i + 1..]
+                    .parse()
+                    .with_context(|| format!("invalid port in URL '{}'", url))?,
+            ),
+            None => (host_port, default_port),
+        };
+        if host.is_empty() {
+            bail!("missing host in URL '{}'", url);
+        }
+
+        Ok(Self {
+            protocol,
+            host: host.to_string(),
+            port,
+        })
+    }
+}
+
+impl fmt::Display for PoolUrl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.protocol {
+            Protocol::V1 => write!(f, "{}://", Self::SCHEME_STRATUM_V1)?,
+            Protocol::V2(_) => write!(f, "{}://", Self::SCHEME_STRATUM_V2)?,
+            Protocol::V2Insecure => write!(f, "{}://", Self::SCHEME_STRATUM_V2_INSECURE)?,
+        }
+        write!(f, "{}:{}", self.host, self.port)
+    }
+}
+
+/// Runs the individual phases of the test, enforces their timeout and prints how long each of
+/// them took
+struct Phases {
+    timeout: Duration,
+    started: Instant,
+}
+
+impl Phases {
+    fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            started: Instant::now(),
+        }
+    }
+
+    fn print(name: &str, duration: Duration, detail: &str) {
+        let line = format!(
+            "{:<20}{:>10.3} ms  {}",
+            name,
+            duration.as_secs_f64() * 1000.0,
+            detail
+        );
+        println!("{}", line.trim_end());
+    }
+
+    /// Runs a phase which provides its result together with a detail to be reported
+    async fn run<T, F>(&self, name: &str, phase: F) -> Result<T>
+    where
+        F: Future<Output = Result<(T, String)>>,
+    {
+        let started = Instant::now();
+        let (value, detail) = phase
+            .timeout(self.timeout)
+            .await
+            .map_err(|_| anyhow!("{} timed out after {:?}", name, self.timeout))?
+            .with_context(|| format!("{} failed", name))?;
+        Self::print(name, started.elapsed(), &detail);
+        Ok(value)
+    }
+
+    fn finish(self) {
+        Self::print("total", self.started.elapsed(), "");
+    }
+}
+
+async fn connect(phases: &Phases, url: &PoolUrl) -> Result<TcpStream> {
+    phases
+        .run("connect", async {
+            let stream = TcpStream::connect((url.host.as_str(), url.port)).await?;
+            let detail = format!("{}", stream.peer_addr()?);
+            Ok((stream, detail))
+        })
+        .await
+}
+
+/// Messages received from a V1 pool that are relevant for the test
+#[derive(Debug)]
+enum V1Event {
+    Result(MessageId, StratumResult),
+    Error(MessageId, StratumError),
+    SetDifficulty(f32),
+    Notify(v1::messages::Notify),
+}
+
+#[derive(Default)]
+struct V1Collector {
+    events: VecDeque<V1Event>,
+}
+
+#[async_trait]
+impl v1::Handler for V1Collector {
+    async fn visit_stratum_result(&mut self, id: &MessageId, payload: &StratumResult) {
+        self.events.push_back(V1Event::Result(*id, payload.clone()));
+    }
+
+    async fn visit_stratum_error(&mut self, id: &MessageId, payload: &StratumError) {
+        self.events.push_back(V1Event::Error(*id, payload.clone()));
+    }
+
+    async fn visit_set_difficulty(
+        &mut self,
+        _id: &MessageId,
+        payload: &v1::messages::SetDifficulty,
+    ) {
+        self.events
+            .push_back(V1Event::SetDifficulty(payload.value()));
+    }
+
+    async fn visit_notify(&mut self, _id: &MessageId, payload: &v1::messages::Notify) {
+        self.events.push_back(V1Event::Notify(payload.clone()));
+    }
+}
+
+struct V1Session {
+    framed: v1::Framed,
+    collector: V1Collector,
+    next_id: u32,
+    difficulty: Option<f32>,
+    job: Option<v1::messages::Notify>,
+}
+
+impl V1Session {
+    fn new(stream: TcpStream) -> Self {
+        Self {
+            framed: ii_wire::Connection::<v1::Framing>::new(stream).into_inner(),
+            collector: Default::default(),
+            next_id: 0,
+            difficulty: None,
+            job: None,
+        }
+    }
+
+    async fn next_event(&mut self) -> Result<V1Event> {
+        loop {
+            if let Some(event) = self.collector.events.pop_front() {
+                return Ok(event);
+            }
+            let frame = self
+                .framed
+                .next()
+                .await
+                .ok_or_else(|| anyhow!("connection closed by the pool"))??;
+            match v1::build_message_from_frame(frame) {
+                Ok(message) => message.accept(&mut self.collector).await,
+                Err(e) => eprintln!("Ignoring message: {}", e),
+            }
+        }
+    }
+
+    /// Stores notifications so that they are available once they are needed
+    fn store_notification(&mut self, event: V1Event) {
+        match event {
+            V1Event::SetDifficulty(difficulty) => self.difficulty = Some(difficulty),
+            V1Event::Notify(job) => self.job = Some(job),
+            // Responses to requests that are no longer awaited
+            V1Event::Result(..) | V1Event::Error(..) => {}
+        }
+    }
+
+    /// Sends request and waits for a matching response
+    async fn request<M>(
+        &mut self,
+        method: M,
+    ) -> Result<std::result::Result<StratumResult, StratumError>>
+    where
+        M: TryInto<v1::rpc::RequestPayload, Error = ii_stratum::error::Error>,
+    {
+        let id = self.next_id;
+        self.next_id += 1;
+        let request = v1::rpc::Request {
+            id: Some(id),
+            payload: method.try_into()?,
+        };
+        let frame = v1::Frame::try_from(v1::rpc::Rpc::from(request))?;
+        self.framed.send(frame).await?;
+
+        loop {
+            match self.next_event().await? {
+                V1Event::Result(Some(response_id), result) if response_id == id => {
+                    return Ok(Ok(result))
+                }
+                V1Event::Error(Some(response_id), error) if response_id == id => {
+                    return Ok(Err(error))
+                }
+                event => self.store_notification(event),
+            }
+        }
+    }
+
+    async fn first_job(&mut self) -> Result<v1::messages::Notify> {
+        loop {
+            if let Some(job) = self.job.take() {
+                return Ok(job);
+            }
+            let event = self.next_event().await?;
+            self.store_notification(event);
+        }
+    }
+}
+
+fn format_v1_error(error: &StratumError) -> String {
+    format!("{} (code {})", error.1, error.0)
+}
+
+async fn ping_v1(args: &Args, phases: &Phases, stream: TcpStream) -> Result<()> {
+    let mut session = V1Session::new(stream);
+
+    let extra_nonce_2_size = phases
+        .run("subscribe", async {
+            let result = session
+                .request(v1::messages::Subscribe(
+                    Some(AGENT.to_string()),
+                    None,
+                    None,
+                    None,
+                ))
+                .await?
+                .map_err(|e| anyhow!("{}", format_v1_error(&e)))?;
+            let result = v1::messages::SubscribeResult::try_from(&result)?;
+            let size = result.extra_nonce_2_size();
+            Ok((size, format!("extranonce 2 size: {}", size)))
+        })
+        .await?;
+
+    phases
+        .run("authorize", async {
+            let result = session
+                .request(v1::messages::Authorize(
+                    args.user.clone(),
+                    args.password.clone(),
+                ))
+                .await?
+                .map_err(|e| anyhow!("{}", format_v1_error(&e)))?;
+            if !v1::messages::BooleanResult::try_from(&result)?.0 {
+                bail!("user '{}' has not been authorized", args.user);
+            }
+            Ok(((), format!("user: {}", args.user)))
+        })
+        .await?;
+
+    let job = phases
+        .run("first job", async {
+            let job = session.first_job().await?;
+            let detail = match session.difficulty {
+                Some(difficulty) => format!("job: {}, difficulty: {}", job.job_id(), difficulty),
+                None => format!("job: {}", job.job_id()),
+            };
+            Ok((job, detail))
+        })
+        .await?;
+
+    if args.submit {
+        phases
+            .run("submit", async {
+                let result = session
+                    .request(v1::messages::Submit::new(
+                        args.user.clone(),
+                        v1::messages::JobId::from_str(job.job_id()),
+                        &vec![0; extra_nonce_2_size],
+                        job.time(),
+                        0,
+                        job.version(),
+                    ))
+                    .await?;
+                let detail = match result {
+                    Ok(result) => match v1::messages::BooleanResult::try_from(&result) {
+                        Ok(v1::messages::BooleanResult(true)) => "accepted".to_string(),
+                        _ => "rejected".to_string(),
+                    },
+                    Err(e) => format!("rejected: {}", format_v1_error(&e)),
+                };
+                Ok(((), detail))
+            })
+            .await?;
+    }
+    Ok(())
+}
+
+/// Messages received from a V2 pool that are relevant for the test
+#[derive(Debug)]
+enum V2Event {
+    SetupConnectionSuccess(SetupConnectionSuccess),
+    SetupConnectionError(SetupConnectionError),
+    OpenChannelSuccess(OpenStandardMiningChannelSuccess),
+    OpenChannelError(OpenStandardMiningChannelError),
+    NewMiningJob(NewMiningJob),
+    SetNewPrevHash(SetNewPrevHash),
+    SubmitSharesSuccess(SubmitSharesSuccess),
+    SubmitSharesError(SubmitSharesError),
+}
+
+#[derive(Default)]
+struct V2Collector {
+    events: VecDeque<V2Event>,
+}
+
+#[async_trait]
+impl v2::Handler for V2Collector {
+    async fn visit_setup_connection_success(
+        &mut self,
+        _header: &Header,
+        payload: &SetupConnectionSuccess,
+    ) {
+        self.events
+            .push_back(V2Event::SetupConnectionSuccess(payload.clone()));
+    }
+
+    async fn visit_setup_connection_error(
+        &mut self,
+        _header: &Header,
+        payload: &SetupConnectionError,
+    ) {
+        self.events
+            .push_back(V2Event::SetupConnectionError(payload.clone()));
+    }
+
+    async fn visit_open_standard_mining_channel_success(
+        &mut self,
+        _header: &Header,
+        payload: &OpenStandardMiningChannelSuccess,
+    ) {
+        self.events
+            .push_back(V2Event::OpenChannelSuccess(payload.clone()));
+    }
+
+    async fn visit_open_standard_mining_channel_error(
+        &mut self,
+        _header: &Header,
+        payload: &OpenStandardMiningChannelError,
+    ) {
+        self.events
+            .push_back(V2Event::OpenChannelError(payload.clone()));
+    }
+
+    async fn visit_new_mining_job(&mut self, _header: &Header, payload: &NewMiningJob) {
+        self.events
+            .push_back(V2Event::NewMiningJob(payload.clone()));
+    }
+
+    async fn visit_set_new_prev_hash(&mut self, _header: &Header, payload: &SetNewPrevHash) {
+        self.events
+            .push_back(V2Event::SetNewPrevHash(payload.clone()));
+    }
+
+    async fn visit_submit_shares_success(
+        &mut self,
+        _header: &Header,
+        payload: &SubmitSharesSuccess,
+    ) {
+        self.events
+            .push_back(V2Event::SubmitSharesSuccess(payload.clone()));
+    }
+
+    async fn visit_submit_shares_error(&mut self, _header: &Header, payload: &SubmitSharesError) {
+        self.events
+            .push_back(V2Event::SubmitSharesError(payload.clone()));
+    }
+}
+
+struct V2Session {
+    framed: v2::Framed,
+    collector: V2Collector,
+    jobs: HashMap<u32, NewMiningJob>,
+    prev_hash: Option<SetNewPrevHash>,
+}
+
+impl V2Session {
+    fn new(framed: v2::Framed) -> Self {
+        Self {
+            framed,
+            collector: Default::default(),
+            jobs: HashMap::new(),
+            prev_hash: None,
+        }
+    }
+
+    async fn send<M>(&mut self, message: M) -> Result<()>
+    where
+        M: TryInto<v2::Frame, Error = ii_stratum::error::Error>,
+    {
+        let frame = message.try_into()?;
+        self.framed.send(frame).await?;
+        Ok(())
+    }
+
+    async fn next_event(&mut self) -> Result<V2Event> {
+        loop {
+            if let Some(event) = self.collector.events.pop_front() {
+                return Ok(event);
+            }
+            let frame = self
+                .framed
+                .next()
+                .await
+                .ok_or_else(|| anyhow!("connection closed by the pool"))??;
+            match v2::build_message_from_frame(frame) {
+                Ok(message) => message.accept(&mut self.collector).await,
+                Err(e) => eprintln!("Ignoring message: {}", e),
+            }
+        }
+    }
+
+    /// Stores mining job related messages so that they are available once they are needed
+    fn store_notification(&mut self, event: V2Event) {
+        match event {
+            V2Event::NewMiningJob(job) => {
+                self.jobs.insert(job.job_id, job);
+            }
+            V2Event::SetNewPrevHash(prev_hash) => {
+                // All jobs except the one referred by the new prevhash are invalidated
+                self.jobs.retain(|job_id, _| *job_id == prev_hash.job_id);
+                self.prev_hash = Some(prev_hash);
+            }
+            _ => {}
+        }
+    }
+
+    async fn first_job(&mut self) -> Result<(NewMiningJob, SetNewPrevHash)> {
+        loop {
+            if let Some(prev_hash) = self.prev_hash.as_ref() {
+                if let Some(job) = self.jobs.get(&prev_hash.job_id) {
+                    return Ok((job.clone(), prev_hash.clone()));
+                }
+            }
+            let event = self.next_event().await?;
+            self.store_notification(event);
+        }
+    }
+}
+
+async fn ping_v2(args: &Args, phases: &Phases, stream: TcpStream) -> Result<()> {
+    let framed = match &args.url.protocol {
+        Protocol::V2(public_key) => {
+            phases
+                .run("noise handshake", async {
+                    let initiator = v2::noise::Initiator::new(public_key.clone().into_inner());
+                    Ok((initiator.connect(stream).await?, String::new()))
+                })
+                .await?
+        }
+        Protocol::V2Insecure => ii_wire::Connection::<v2::Framing>::new(stream).into_inner(),
+        Protocol::V1 => panic!("BUG: unexpected stratum V1 protocol"),
+    };
+    let mut session = V2Session::new(framed);
+
+    phases
+        .run("setup connection", async {
+            session
+                .send(SetupConnection {
+                    protocol: 0,
+                    min_version: 2,
+                    max_version: 2,
+                    flags: 0,
+                    endpoint_host: Str0_255::from_string(args.url.host.clone()),
+                    endpoint_port: args.url.port,
+                    device: DeviceInfo {
+                        vendor: Str0_255::from_string("Braiins".to_string()),
+                        hw_rev: Str0_255::from_string(String::new()),
+                        fw_ver: Str0_255::from_string(AGENT.to_string()),
+                        dev_id: Str0_255::from_string(String::new()),
+                    },
+                })
+                .await?;
+            loop {
+                match session.next_event().await? {
+                    V2Event::SetupConnectionSuccess(success) => {
+                        return Ok(((), format!("version: {}", success.used_version)))
+                    }
+                    V2Event::SetupConnectionError(error) => bail!("{}", error.code.to_string()),
+                    event => session.store_notification(event),
+                }
+            }
+        })
+        .await?;
+
+    let channel = phases
+        .run("open channel", async {
+            session
+                .send(OpenStandardMiningChannel {
+                    req_id: 0,
+                    user: args
+                        .user
+                        .clone()
+                        .try_into()
+                        .map_err(|_| anyhow!("invalid user name '{}'", args.user))?,
+                    nominal_hashrate: NOMINAL_HASHRATE,
+                    // Maximum bitcoin target is 0xffff << 208 (= difficulty 1 share)
+                    max_target: ii_bitcoin::Target::default().into(),
+                })
+                .await?;
+            loop {
+                match session.next_event().await? {
+                    V2Event::OpenChannelSuccess(success) => {
+                        let difficulty = ii_bitcoin::Target::from(success.target).get_difficulty();
+                        let detail = format!(
+                            "channel: {}, difficulty: {}",
+                            success.channel_id, difficulty
+                        );
+                        return Ok((success, detail));
+                    }
+                    V2Event::OpenChannelError(error) => bail!("{}", error.code.to_string()),
+                    event => session.store_notification(event),
+                }
+            }
+        })
+        .await?;
+
+    let (job, prev_hash) = phases
+        .run("first job", async {
+            let (job, prev_hash) = session.first_job().await?;
+            let detail = format!("job: {}", job.job_id);
+            Ok(((job, prev_hash), detail))
+        })
+        .await?;
+
+    if args.submit {
+        phases
+            .run("submit", async {
+                session
+                    .send(SubmitSharesStandard {
+                        channel_id: channel.channel_id,
+                        seq_num: 0,
+                        job_id: job.job_id,
+                        nonce: 0,
+                        ntime: prev_hash.min_ntime,
+                        version: job.version,
+                    })
+                    .await?;
+                loop {
+                    match session.next_event().await? {
+                        V2Event::SubmitSharesSuccess(success) => {
+                            let detail =
+                                format!("accepted: {} shares", success.new_submits_accepted_count);
+                            return Ok(((), detail));
+                        }
+                        V2Event::SubmitSharesError(error) => {
+                            return Ok(((), format!("rejected: {}", error.code.to_string())))
+                        }
+                        event => session.store_notification(event),
+                    }
+                }
+            })
+            .await?;
+    }
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::from_args();
+    let phases = Phases::new(Duration::from_secs(args.timeout));
+
+    println!("Pinging {}", args.url);
+    let stream = connect(&phases, &args.url).await?;
+    match args.url.protocol {
+        Protocol::V1 => ping_v1(&args, &phases, stream).await?,
+        Protocol::V2(_) | Protocol::V2Insecure => ping_v2(&args, &phases, stream).await?,
+    }
+    phases.finish();
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_pool_url() {
+        let url = PoolUrl::from_str("stratum+tcp://pool.example.com").expect("BUG: valid URL");
+        assert!(matches!(url.protocol, Protocol::V1));
+        assert_eq!(url.host, "pool.example.com");
+        assert_eq!(url.port, PoolUrl::DEFAULT_PORT_STRATUM_V1);
+
+        let url =
+            PoolUrl::from_str("stratum2+tcp+insecure://10.0.0.1:3337").expect("BUG: valid URL");
+        assert!(matches!(url.protocol, Protocol::V2Insecure));
+        assert_eq!(url.to_string(), "stratum2+tcp+insecure://10.0.0.1:3337");
+
+        let secret_key =
+            ed25519_dalek::SecretKey::from_bytes(&[1; 32]).expect("BUG: invalid secret key");
+        let public_key: String =
+            v2::noise::auth::EncodedEd25519PublicKey::new((&secret_key).into()).into();
+        let url = PoolUrl::from_str(&format!("stratum2+tcp://v2.example.com/{}", public_key))
+            .expect("BUG: valid URL");
+        assert!(matches!(url.protocol, Protocol::V2(_)));
+        assert_eq!(url.port, PoolUrl::DEFAULT_PORT_STRATUM_V2);
+
+        assert!(PoolUrl::from_str("stratum2+tcp://v2.example.com").is_err());
+        assert!(PoolUrl::from_str("stratum+tcp://pool.example.com/path").is_err());
+        assert!(PoolUrl::from_str("stratum+tcp://pool.example.com:port").is_err());
+        assert!(PoolUrl::from_str("http://pool.example.com").is_err());
+        assert!(PoolUrl::from_str("pool.example.com").is_err());
+    }
+}
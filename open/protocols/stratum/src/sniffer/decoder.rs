@@ -0,0 +1,512 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Decoding of captured stratum byte streams into messages
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use ii_async_compat::bytes::BytesMut;
+use ii_async_compat::tokio_util::codec::Decoder;
+use ii_stratum::v1;
+use ii_stratum::v2::{
+    self, extensions,
+    framing::Header,
+    messages::*,
+    telemetry::{self, messages::*},
+};
+use packed_struct::PrimitiveEnum;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+/// Protocol spoken on a stream. The protocol is detected from the first data unless it is
+/// specified explicitly.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProtocolHint {
+    Auto,
+    V1,
+    V2,
+}
+
+impl FromStr for ProtocolHint {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "auto" => Ok(Self::Auto),
+            "v1" => Ok(Self::V1),
+            "v2" => Ok(Self::V2),
+            _ => Err(anyhow!(
+                "unknown protocol '{}', expected: auto, v1 or v2",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Direction {
+    ToPool,
+    ToMiner,
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Direction::ToPool => write!(f, "miner -> pool"),
+            Direction::ToMiner => write!(f, "pool -> miner"),
+        }
+    }
+}
+
+/// Single message decoded from a stream
+#[derive(Debug, Clone, PartialEq)]
+pub struct Decoded {
+    pub protocol: &'static str,
+    pub name: String,
+    /// Size of the message on the wire
+    pub size: usize,
+    /// Human readable contents of the message
+    pub detail: String,
+}
+
+impl Decoded {
+    const UNDECODED: &'static str = "<undecoded>";
+
+    fn undecoded(size: usize, detail: String) -> Self {
+        Self {
+            protocol: "?",
+            name: Self::UNDECODED.to_string(),
+            size,
+            detail,
+        }
+    }
+}
+
+enum State {
+    Detecting(ProtocolHint),
+    V1(v1::Codec),
+    V2(v2::Codec),
+    /// The rest of the stream is only accounted, the reason is reported with the first chunk
+    Undecodable(Option<String>),
+}
+
+/// Decoder of one direction of a TCP connection. The data may be pushed in arbitrary chunks,
+/// partial messages are kept until they are complete.
+pub struct StreamDecoder {
+    state: State,
+    buf: BytesMut,
+}
+
+impl StreamDecoder {
+    pub fn new(hint: ProtocolHint) -> Self {
+        Self {
+            state: State::Detecting(hint),
+            buf: BytesMut::new(),
+        }
+    }
+
+    /// Stops decoding of the stream, e.g. when some of its data have been lost
+    pub fn interrupt(&mut self, reason: String) {
+        if let State::Undecodable(_) = self.state {
+            return;
+        }
+        self.buf.clear();
+        self.state = State::Undecodable(Some(reason));
+    }
+
+    /// Detects the protocol from the first bytes of the stream
+    fn detect(&self, hint: ProtocolHint) -> Option<State> {
+        match hint {
+            ProtocolHint::V1 => return Some(State::V1(Default::default())),
+            ProtocolHint::V2 => return Some(State::V2(Default::default())),
+            ProtocolHint::Auto => {}
+        }
+        if self.buf.first()? == &b'{' {
+            return Some(State::V1(Default::default()));
+        }
+        if self.buf.len() < Header::SIZE {
+            return None;
+        }
+        // Plain V2 stream always starts with a known message of the base protocol. Anything else
+        // is most likely a noise handshake which starts with a length of the message instead.
+        let header = Header::deserialize(&mut BytesMut::from(&self.buf[..Header::SIZE]));
+        if header.extension_type == extensions::BASE
+            && v2::messages::MessageType::from_primitive(header.msg_type).is_some()
+        {
+            Some(State::V2(Default::default()))
+        } else {
+            Some(State::Undecodable(Some(
+                "neither V1 nor plain V2 stream, possibly encrypted".to_string(),
+            )))
+        }
+    }
+
+    /// Appends `data` to the stream and decodes all messages that are complete
+    pub async fn push(&mut self, data: &[u8]) -> Vec<Decoded> {
+        let mut decoded = Vec::new();
+        if let State::Undecodable(reason) = &mut self.state {
+            decoded.push(Decoded::undecoded(
+                data.len(),
+                reason.take().unwrap_or_default(),
+            ));
+            return decoded;
+        }
+        self.buf.extend_from_slice(data);
+
+        if let State::Detecting(hint) = self.state {
+            match self.detect(hint) {
+                Some(State::Undecodable(reason)) => {
+                    // Account all data buffered during the detection
+                    decoded.push(Decoded::undecoded(
+                        self.buf.len(),
+                        reason.unwrap_or_default(),
+                    ));
+                    self.buf.clear();
+                    self.state = State::Undecodable(None);
+                    return decoded;
+                }
+                Some(state) => self.state = state,
+                None => return decoded,
+            }
+        }
+        loop {
+            let len = self.buf.len();
+            let result = match &mut self.state {
+                State::V1(codec) => match codec.decode(&mut self.buf) {
+                    Ok(Some(frame)) => Ok(Some(decode_v1(frame, len - self.buf.len()))),
+                    Ok(None) => Ok(None),
+                    Err(e) => Err(e),
+                },
+                State::V2(codec) => match codec.decode(&mut self.buf) {
+                    Ok(Some(frame)) => Ok(Some(decode_v2(frame, len - self.buf.len()).await)),
+                    Ok(None) => Ok(None),
+                    Err(e) => Err(e),
+                },
+                State::Detecting(_) | State::Undecodable(_) => Ok(None),
+            };
+            match result {
+                Ok(Some(message)) => decoded.push(message),
+                Ok(None) => break,
+                Err(e) => {
+                    decoded.push(Decoded::undecoded(
+                        self.buf.len(),
+                        format!("cannot decode stream: {}", e),
+                    ));
+                    self.buf.clear();
+                    self.state = State::Undecodable(None);
+                    break;
+                }
+            }
+        }
+        decoded
+    }
+}
+
+fn decode_v1(frame: v1::Frame, size: usize) -> Decoded {
+    let bytes = match frame.into_inner().into_bytes_mut() {
+        Ok(bytes) => bytes,
+        Err(e) => return Decoded::undecoded(size, format!("invalid frame: {}", e)),
+    };
+    let text = String::from_utf8_lossy(&bytes[..]).trim().to_string();
+    let value: serde_json::Value = match serde_json::from_slice(&bytes[..]) {
+        Ok(value) => value,
+        Err(e) => {
+            return Decoded {
+                protocol: "V1",
+                name: "<invalid JSON>".to_string(),
+                size,
+                detail: format!("{}: {}", e, text),
+            }
+        }
+    };
+    let name = match value.get("method").and_then(|method| method.as_str()) {
+        Some(method) => method.to_string(),
+        None if matches!(value.get("error"), Some(error) if !error.is_null()) => {
+            "error response".to_string()
+        }
+        None => "response".to_string(),
+    };
+    // Check the message can also be handled by the protocol implementation
+    let detail = match v1::rpc::Rpc::try_from(&bytes[..]) {
+        Ok(_) => text,
+        Err(e) => format!("INVALID ({}): {}", e, text),
+    };
+    Decoded {
+        protocol: "V1",
+        name,
+        size,
+        detail,
+    }
+}
+
+fn v2_message_name(header: &Header) -> String {
+    let name = match header.extension_type {
+        extensions::BASE => v2::messages::MessageType::from_primitive(header.msg_type)
+            .map(|msg_type| format!("{:?}", msg_type)),
+        extensions::TELEMETRY => telemetry::messages::MessageType::from_primitive(header.msg_type)
+            .map(|msg_type| format!("{:?}", msg_type)),
+        _ => None,
+    };
+    name.unwrap_or_else(|| {
+        format!(
+            "unknown (extension: {:#06x}, type: {:#04x})",
+            header.extension_type, header.msg_type
+        )
+    })
+}
+
+async fn decode_v2(frame: v2::Frame, size: usize) -> Decoded {
+    let name = v2_message_name(&frame.header);
+    let message = match frame.header.extension_type {
+        extensions::BASE => v2::build_message_from_frame(frame),
+        extensions::TELEMETRY => telemetry::messages::build_message_from_frame(frame),
+        _ => {
+            return Decoded {
+                protocol: "V2",
+                name,
+                size,
+                detail: String::new(),
+            }
+        }
+    };
+    let detail = match message {
+        Ok(message) => {
+            let mut printer = V2Printer::default();
+            message.accept(&mut printer).await;
+            printer.0.unwrap_or_default()
+        }
+        Err(e) => format!("INVALID: {}", e),
+    };
+    Decoded {
+        protocol: "V2",
+        name,
+        size,
+        detail,
+    }
+}
+
+/// Handler that renders any visited message
+#[derive(Default)]
+struct V2Printer(Option<String>);
+
+macro_rules! impl_v2_printer {
+    ($($visit_fn:tt: $payload:tt),* $(,)?) => {
+        // NOTE: $visit_fn and $payload need to be tt because of https://github.com/dtolnay/async-trait/issues/46
+        #[async_trait]
+        impl v2::Handler for V2Printer {
+            $(
+                async fn $visit_fn(&mut self, _header: &Header, payload: &$payload) {
+                    self.0 = Some(format!("{:?}", payload));
+                }
+            )*
+        }
+    };
+}
+
+impl_v2_printer!(
+    visit_setup_connection: SetupConnection,
+    visit_setup_connection_success: SetupConnectionSuccess,
+    visit_setup_connection_error: SetupConnectionError,
+    visit_open_standard_mining_channel: OpenStandardMiningChannel,
+    visit_open_standard_mining_channel_success: OpenStandardMiningChannelSuccess,
+    visit_open_standard_mining_channel_error: OpenStandardMiningChannelError,
+    visit_update_channel: UpdateChannel,
+    visit_update_channel_error: UpdateChannelError,
+    visit_submit_shares_standard: SubmitSharesStandard,
+    visit_submit_shares_success: SubmitSharesSuccess,
+    visit_submit_shares_error: SubmitSharesError,
+    visit_new_mining_job: NewMiningJob,
+    visit_set_new_prev_hash: SetNewPrevHash,
+    visit_set_target: SetTarget,
+    visit_open_telemetry_channel: OpenTelemetryChannel,
+    visit_open_telemetry_channel_success: OpenTelemetryChannelSuccess,
+    visit_open_telemetry_channel_error: OpenTelemetryChannelError,
+    visit_submit_telemetry_data: SubmitTelemetryData,
+    visit_submit_telemetry_data_success: SubmitTelemetryDataSuccess,
+    visit_submit_telemetry_data_error: SubmitTelemetryDataError,
+);
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct MessageStats {
+    pub count: u64,
+    pub bytes: u64,
+}
+
+/// Number and size of messages per protocol, direction and message type
+#[derive(Debug, Default)]
+pub struct Stats {
+    messages: BTreeMap<(&'static str, Direction, String), MessageStats>,
+}
+
+impl Stats {
+    pub fn account(&mut self, direction: Direction, message: &Decoded) {
+        let stats = self
+            .messages
+            .entry((message.protocol, direction, message.name.clone()))
+            .or_default();
+        stats.count += 1;
+        stats.bytes += message.size as u64;
+    }
+
+    pub fn merge(&mut self, other: &Stats) {
+        for (key, other_stats) in other.messages.iter() {
+            let stats = self.messages.entry(key.clone()).or_default();
+            stats.count += other_stats.count;
+            stats.bytes += other_stats.bytes;
+        }
+    }
+
+    pub fn get(&self, protocol: &'static str, direction: Direction, name: &str) -> MessageStats {
+        self.messages
+            .get(&(protocol, direction, name.to_string()))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<8} {:<14} {:<40} {:>10} {:>12}",
+            "protocol", "direction", "message", "count", "bytes"
+        )?;
+        for ((protocol, direction, name), stats) in self.messages.iter() {
+            writeln!(
+                f,
+                "{:<8} {:<14} {:<40} {:>10} {:>12}",
+                protocol,
+                direction.to_string(),
+                name,
+                stats.count,
+                stats.bytes
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ii_async_compat::tokio;
+    use ii_async_compat::tokio_util::codec::Encoder;
+    use ii_stratum::test_utils;
+    use std::convert::TryInto;
+
+    async fn push_all(decoder: &mut StreamDecoder, data: &[u8], chunk: usize) -> Vec<Decoded> {
+        let mut decoded = Vec::new();
+        for part in data.chunks(chunk) {
+            decoded.extend(decoder.push(part).await);
+        }
+        decoded
+    }
+
+    #[tokio::test]
+    async fn test_decode_v1_chunks() {
+        let data = concat!(
+            r#"{"id":1,"method":"mining.subscribe","params":["Braiins OS"]}"#,
+            "\n",
+            r#"{"id":1,"result":true,"error":null}"#,
+            "\n",
+            r#"{"id":2,"result":null,"error":[21,"Job not found",null]}"#,
+            "\n",
+        );
+        let mut decoder = StreamDecoder::new(ProtocolHint::Auto);
+        let decoded = push_all(&mut decoder, data.as_bytes(), 7).await;
+        let names: Vec<_> = decoded
+            .iter()
+            .map(|message| message.name.as_str())
+            .collect();
+        assert_eq!(
+            names,
+            vec!["mining.subscribe", "response", "error response"]
+        );
+        assert!(decoded.iter().all(|message| message.protocol == "V1"));
+        assert_eq!(
+            decoded.iter().map(|message| message.size).sum::<usize>(),
+            data.len()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_decode_v2_and_stats() {
+        let mut data = BytesMut::new();
+        let mut codec = v2::Codec::default();
+        for _ in 0..2 {
+            let frame: v2::Frame = test_utils::v2::build_setup_connection()
+                .try_into()
+                .expect("BUG: cannot build frame");
+            codec
+                .encode(frame, &mut data)
+                .expect("BUG: cannot encode frame");
+        }
+        let mut decoder = StreamDecoder::new(ProtocolHint::Auto);
+        let decoded = push_all(&mut decoder, &data[..], 5).await;
+        assert_eq!(decoded.len(), 2);
+        assert_eq!(decoded[0].protocol, "V2");
+        assert_eq!(decoded[0].name, "SetupConnection");
+        assert!(decoded[0].detail.contains("stratum.slushpool.com"));
+
+        let mut stats = Stats::default();
+        assert!(stats.is_empty());
+        for message in decoded.iter() {
+            stats.account(Direction::ToPool, message);
+        }
+        let mut total = Stats::default();
+        total.merge(&stats);
+        total.merge(&stats);
+        assert_eq!(
+            total.get("V2", Direction::ToPool, "SetupConnection"),
+            MessageStats {
+                count: 4,
+                bytes: 2 * data.len() as u64,
+            }
+        );
+        assert_eq!(
+            total.get("V2", Direction::ToMiner, "SetupConnection"),
+            MessageStats::default()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_undecodable_stream() {
+        let mut decoder = StreamDecoder::new(ProtocolHint::Auto);
+        let decoded = decoder
+            .push(&[0x20, 0x00, 0xff, 0xff, 0xff, 0xff, 0x01])
+            .await;
+        assert_eq!(decoded.len(), 1);
+        assert_eq!(decoded[0].name, Decoded::UNDECODED);
+        assert!(!decoded[0].detail.is_empty());
+
+        // The reason is reported only once
+        let decoded = decoder.push(&[0x01, 0x02]).await;
+        assert_eq!(decoded[0].size, 2);
+        assert!(decoded[0].detail.is_empty());
+    }
+}
@@ -0,0 +1,450 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Sniffer that decodes stratum traffic between miners and pools for debugging of
+//! incompatibilities. The traffic is either read from a pcap capture file or the sniffer acts
+//! as a transparent TCP tap that forwards miner connections to the pool. Each message is printed
+//! with a timestamp and statistics of all messages are printed at the end.
+//!
+//! Stratum V1 and unencrypted V2 streams are decoded, noise encrypted V2 streams are only
+//! accounted.
+
+mod decoder;
+mod pcap;
+
+use anyhow::{Context, Result};
+use decoder::{Decoded, Direction, ProtocolHint, Stats, StreamDecoder};
+use ii_async_compat::futures::channel::mpsc;
+use ii_async_compat::prelude::*;
+use ii_async_compat::tokio;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use structopt::StructOpt;
+use tokio::net::TcpStream;
+
+/// All commands recognized by the sniffer
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "ii-stratum-sniffer",
+    about = "Tool for decoding Stratum V1 and V2 traffic between miners and pools"
+)]
+enum Command {
+    /// Decode traffic captured in a pcap file
+    Pcap(PcapCommand),
+    /// Forward miner connections to a pool and decode the traffic passing through
+    Tap(TapCommand),
+}
+
+/// Options shared by all commands
+#[derive(Debug, StructOpt)]
+struct DecodeOptions {
+    /// Protocol of the traffic: auto, v1 or v2
+    #[structopt(long, default_value = "auto")]
+    protocol: ProtocolHint,
+    /// Print only the statistics
+    #[structopt(short, long)]
+    quiet: bool,
+}
+
+#[derive(Debug, StructOpt)]
+struct PcapCommand {
+    /// Capture file in the pcap format
+    #[structopt(parse(from_os_str))]
+    file: PathBuf,
+    /// Port of the pool, only traffic from and to this port is decoded
+    #[structopt(short, long)]
+    port: Option<u16>,
+    #[structopt(flatten)]
+    options: DecodeOptions,
+}
+
+#[derive(Debug, StructOpt)]
+struct TapCommand {
+    /// Address to accept miner connections on
+    #[structopt(short, long)]
+    listen: String,
+    /// Address of the pool
+    #[structopt(short, long)]
+    upstream: String,
+    #[structopt(flatten)]
+    options: DecodeOptions,
+}
+
+/// Prints decoded traffic
+#[derive(Debug, Clone, Copy)]
+struct Output {
+    quiet: bool,
+}
+
+impl Output {
+    fn print_connection(&self, timestamp: Duration, connection: &Connection, event: &str) {
+        if !self.quiet {
+            println!(
+                "[{:>12.6}] #{} miner {} pool {}: {}",
+                timestamp.as_secs_f64(),
+                connection.id,
+                connection.miner,
+                connection.pool,
+                event
+            );
+        }
+    }
+
+    fn print_message(
+        &self,
+        timestamp: Duration,
+        connection_id: usize,
+        direction: Direction,
+        message: &Decoded,
+    ) {
+        if !self.quiet {
+            let line = format!(
+                "[{:>12.6}] #{} {} {} {} ({} B) {}",
+                timestamp.as_secs_f64(),
+                connection_id,
+                direction,
+                message.protocol,
+                message.name,
+                message.size,
+                message.detail
+            );
+            println!("{}", line.trim_end());
+        }
+    }
+}
+
+/// One direction of a TCP connection
+struct Flow {
+    decoder: StreamDecoder,
+    /// Sequence number of the next expected data, it is only tracked for captured traffic
+    next_seq: Option<u32>,
+}
+
+impl Flow {
+    fn new(hint: ProtocolHint) -> Self {
+        Self {
+            decoder: StreamDecoder::new(hint),
+            next_seq: None,
+        }
+    }
+
+    /// Returns the part of `payload` starting at sequence number `seq` that hasn't been seen
+    /// yet. Retransmitted data are skipped and a gap in the data stops decoding of the stream.
+    fn new_data<'a>(&mut self, seq: u32, payload: &'a [u8]) -> &'a [u8] {
+        let next_seq = *self.next_seq.get_or_insert(seq);
+        // Distance in the sequence number space that wraps around
+        let offset = seq.wrapping_sub(next_seq) as i32;
+        let data = if offset > 0 {
+            self.decoder
+                .interrupt(format!("{} bytes missing in the capture", offset));
+            payload
+        } else {
+            let already_seen = offset.unsigned_abs() as usize;
+            payload.get(already_seen..).unwrap_or(&[])
+        };
+        if !data.is_empty() {
+            self.next_seq = Some(seq.wrapping_add(payload.len() as u32));
+        }
+        data
+    }
+}
+
+/// Decoding state of a TCP connection between a miner and a pool
+struct Connection {
+    id: usize,
+    miner: SocketAddr,
+    pool: SocketAddr,
+    to_pool: Flow,
+    to_miner: Flow,
+    stats: Stats,
+}
+
+impl Connection {
+    fn new(id: usize, miner: SocketAddr, pool: SocketAddr, hint: ProtocolHint) -> Self {
+        Self {
+            id,
+            miner,
+            pool,
+            to_pool: Flow::new(hint),
+            to_miner: Flow::new(hint),
+            stats: Default::default(),
+        }
+    }
+
+    fn flow(&mut self, direction: Direction) -> &mut Flow {
+        match direction {
+            Direction::ToPool => &mut self.to_pool,
+            Direction::ToMiner => &mut self.to_miner,
+        }
+    }
+
+    async fn push(
+        &mut self,
+        output: &Output,
+        timestamp: Duration,
+        direction: Direction,
+        data: &[u8],
+    ) {
+        for message in self.flow(direction).decoder.push(data).await {
+            self.stats.account(direction, &message);
+            output.print_message(timestamp, self.id, direction, &message);
+        }
+    }
+}
+
+impl PcapCommand {
+    /// Determines the direction of a segment and the addresses of the miner and the pool
+    fn orient(
+        &self,
+        connections: &HashMap<(SocketAddr, SocketAddr), Connection>,
+        segment: &pcap::TcpSegment,
+    ) -> Option<(Direction, SocketAddr, SocketAddr)> {
+        let to_pool = Some((Direction::ToPool, segment.src, segment.dst));
+        let to_miner = Some((Direction::ToMiner, segment.dst, segment.src));
+        if let Some(port) = self.port {
+            return if segment.dst.port() == port {
+                to_pool
+            } else if segment.src.port() == port {
+                to_miner
+            } else {
+                None
+            };
+        }
+        if connections.contains_key(&(segment.src, segment.dst)) {
+            to_pool
+        } else if connections.contains_key(&(segment.dst, segment.src)) {
+            to_miner
+        } else if segment.syn {
+            // Miner is the side that opens the connection
+            if segment.ack {
+                to_miner
+            } else {
+                to_pool
+            }
+        } else if segment.src.port() > segment.dst.port() {
+            // Otherwise assume the miner uses an ephemeral port
+            to_pool
+        } else {
+            to_miner
+        }
+    }
+
+    async fn execute(self) -> Result<()> {
+        let file =
+            File::open(&self.file).with_context(|| format!("cannot open {:?}", self.file))?;
+        let mut reader = pcap::Reader::new(BufReader::new(file))?;
+        let output = Output {
+            quiet: self.options.quiet,
+        };
+
+        let mut connections = HashMap::new();
+        let mut stats = Stats::default();
+        let mut next_id = 1;
+        let mut first_timestamp = None;
+
+        while let Some(packet) = reader.next_packet()? {
+            let segment = match pcap::parse_tcp(reader.link_type(), &packet.data) {
+                Ok(Some(segment)) => segment,
+                Ok(None) => continue,
+                Err(e) => {
+                    eprintln!("Skipping packet: {}", e);
+                    continue;
+                }
+            };
+            let (direction, miner, pool) = match self.orient(&connections, &segment) {
+                Some(orientation) => orientation,
+                None => continue,
+            };
+            let timestamp = packet.timestamp - *first_timestamp.get_or_insert(packet.timestamp);
+
+            // New connection replaces any previous one between the same addresses
+            if segment.syn && !segment.ack {
+                if let Some(connection) = connections.remove(&(miner, pool)) {
+                    stats.merge(&connection.stats);
+                }
+            }
+            let connection = connections.entry((miner, pool)).or_insert_with(|| {
+                let connection = Connection::new(next_id, miner, pool, self.options.protocol);
+                next_id += 1;
+                output.print_connection(timestamp, &connection, "connection");
+                connection
+            });
+
+            let flow = connection.flow(direction);
+            if segment.syn {
+                flow.next_seq = Some(segment.seq.wrapping_add(1));
+            }
+            let data = flow.new_data(segment.seq, segment.payload);
+            if !data.is_empty() {
+                connection.push(&output, timestamp, direction, data).await;
+            }
+            if segment.truncated {
+                connection
+                    .flow(direction)
+                    .decoder
+                    .interrupt("packet truncated in the capture".to_string());
+            }
+            if segment.fin || segment.rst {
+                let event = format!(
+                    "{} closed by {}",
+                    if segment.rst { "reset" } else { "connection" },
+                    match direction {
+                        Direction::ToPool => "miner",
+                        Direction::ToMiner => "pool",
+                    }
+                );
+                output.print_connection(timestamp, connection, &event);
+            }
+        }
+
+        for connection in connections.values() {
+            stats.merge(&connection.stats);
+        }
+        if stats.is_empty() {
+            println!("No stratum traffic found");
+        } else {
+            println!("{}", stats);
+        }
+        Ok(())
+    }
+}
+
+impl TapCommand {
+    async fn execute(self) -> Result<()> {
+        let mut server = ii_wire::Server::bind(&self.listen)
+            .with_context(|| format!("cannot listen on {}", self.listen))?;
+        let started = Instant::now();
+        let output = Output {
+            quiet: self.options.quiet,
+        };
+        let upstream = Arc::new(self.upstream);
+
+        let mut next_id = 1;
+        while let Some(miner_stream) = server.next().await {
+            let miner_stream = match miner_stream {
+                Ok(miner_stream) => miner_stream,
+                Err(e) => {
+                    eprintln!("Cannot accept connection: {}", e);
+                    continue;
+                }
+            };
+            let id = next_id;
+            next_id += 1;
+            let upstream = upstream.clone();
+            let protocol = self.options.protocol;
+            tokio::spawn(async move {
+                if let Err(e) =
+                    Self::tap(id, miner_stream, &upstream, protocol, output, started).await
+                {
+                    eprintln!("#{}: {:#}", id, e);
+                }
+            });
+        }
+        Ok(())
+    }
+
+    /// Forwards data between the miner and the pool and decodes them
+    async fn tap(
+        id: usize,
+        miner_stream: TcpStream,
+        upstream: &str,
+        protocol: ProtocolHint,
+        output: Output,
+        started: Instant,
+    ) -> Result<()> {
+        let pool_stream = TcpStream::connect(upstream)
+            .await
+            .with_context(|| format!("cannot connect to {}", upstream))?;
+        let mut connection = Connection::new(
+            id,
+            miner_stream.peer_addr()?,
+            pool_stream.peer_addr()?,
+            protocol,
+        );
+        output.print_connection(started.elapsed(), &connection, "connection");
+
+        let (data_tx, mut data_rx) = mpsc::unbounded();
+        let (miner_rx, miner_tx) = tokio::io::split(miner_stream);
+        let (pool_rx, pool_tx) = tokio::io::split(pool_stream);
+        tokio::spawn(Self::forward(
+            miner_rx,
+            pool_tx,
+            Direction::ToPool,
+            data_tx.clone(),
+        ));
+        tokio::spawn(Self::forward(
+            pool_rx,
+            miner_tx,
+            Direction::ToMiner,
+            data_tx,
+        ));
+
+        // The channel is closed once both directions have been closed
+        while let Some((timestamp, direction, data)) = data_rx.next().await {
+            let timestamp: Instant = timestamp;
+            connection
+                .push(&output, timestamp - started, direction, &data)
+                .await;
+        }
+        output.print_connection(started.elapsed(), &connection, "connection closed");
+        println!("{}", connection.stats);
+        Ok(())
+    }
+
+    async fn forward<R, W>(
+        mut rx: R,
+        mut tx: W,
+        direction: Direction,
+        data_tx: mpsc::UnboundedSender<(Instant, Direction, Vec<u8>)>,
+    ) where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        let mut buf = vec![0u8; 16384];
+        loop {
+            let len = match rx.read(&mut buf).await {
+                Ok(0) | Err(_) => break,
+                Ok(len) => len,
+            };
+            if tx.write_all(&buf[..len]).await.is_err() {
+                break;
+            }
+            // Decoding is best effort, it must not affect the forwarding
+            let _ = data_tx.unbounded_send((Instant::now(), direction, buf[..len].to_vec()));
+        }
+        // Propagate the end of the stream to the other side
+        let _ = tx.shutdown().await;
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    match Command::from_args() {
+        Command::Pcap(pcap_cmd) => pcap_cmd.execute().await,
+        Command::Tap(tap_cmd) => tap_cmd.execute().await,
+    }
+}
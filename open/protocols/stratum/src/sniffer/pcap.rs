@@ -0,0 +1,375 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Minimal reader of classic pcap capture files that extracts TCP segments. Only the link types
+//! commonly produced by `tcpdump` and `wireshark` are supported. The newer pcapng format has to
+//! be converted first, e.g. with `editcap -F pcap`.
+
+use anyhow::{anyhow, bail, Context, Result};
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use std::io::{self, Read};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+
+const MAGIC_MICROS: u32 = 0xa1b2_c3d4;
+const MAGIC_NANOS: u32 = 0xa1b2_3c4d;
+
+const GLOBAL_HEADER_SIZE: usize = 24;
+const RECORD_HEADER_SIZE: usize = 16;
+/// Sanity limit for the size of a single captured packet
+const MAX_RECORD_SIZE: usize = 256 * 1024;
+
+const LINKTYPE_NULL: u32 = 0;
+const LINKTYPE_ETHERNET: u32 = 1;
+const LINKTYPE_RAW: u32 = 101;
+const LINKTYPE_LINUX_SLL: u32 = 113;
+const LINKTYPE_LINUX_SLL2: u32 = 276;
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_IPV6: u16 = 0x86dd;
+const ETHERTYPE_VLAN: u16 = 0x8100;
+
+const IP_PROTOCOL_TCP: u8 = 6;
+
+/// Packet as stored in the capture file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Packet {
+    /// Time of the capture since the unix epoch
+    pub timestamp: Duration,
+    pub data: Vec<u8>,
+}
+
+pub struct Reader<R> {
+    input: R,
+    big_endian: bool,
+    nanos: bool,
+    link_type: u32,
+}
+
+impl<R: Read> Reader<R> {
+    pub fn new(mut input: R) -> Result<Self> {
+        let mut header = [0u8; GLOBAL_HEADER_SIZE];
+        input
+            .read_exact(&mut header)
+            .context("cannot read pcap header")?;
+
+        let (big_endian, nanos) = match (
+            LittleEndian::read_u32(&header[0..4]),
+            BigEndian::read_u32(&header[0..4]),
+        ) {
+            (MAGIC_MICROS, _) => (false, false),
+            (MAGIC_NANOS, _) => (false, true),
+            (_, MAGIC_MICROS) => (true, false),
+            (_, MAGIC_NANOS) => (true, true),
+            _ => bail!("not a pcap file (pcapng has to be converted to pcap first)"),
+        };
+        let mut reader = Self {
+            input,
+            big_endian,
+            nanos,
+            link_type: 0,
+        };
+        reader.link_type = reader.read_u32(&header[20..24]);
+        match reader.link_type {
+            LINKTYPE_NULL | LINKTYPE_ETHERNET | LINKTYPE_RAW | LINKTYPE_LINUX_SLL
+            | LINKTYPE_LINUX_SLL2 => Ok(reader),
+            link_type => bail!("unsupported pcap link type {}", link_type),
+        }
+    }
+
+    fn read_u32(&self, buf: &[u8]) -> u32 {
+        if self.big_endian {
+            BigEndian::read_u32(buf)
+        } else {
+            LittleEndian::read_u32(buf)
+        }
+    }
+
+    pub fn link_type(&self) -> u32 {
+        self.link_type
+    }
+
+    /// Reads next packet, `None` is returned at the end of the file
+    pub fn next_packet(&mut self) -> Result<Option<Packet>> {
+        let mut header = [0u8; RECORD_HEADER_SIZE];
+        match self.input.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e).context("cannot read pcap record"),
+        }
+        let seconds = self.read_u32(&header[0..4]);
+        let fraction = self.read_u32(&header[4..8]);
+        let size = self.read_u32(&header[8..12]) as usize;
+        if size > MAX_RECORD_SIZE {
+            bail!("invalid pcap record size {}", size);
+        }
+
+        let mut data = vec![0u8; size];
+        self.input
+            .read_exact(&mut data)
+            .context("truncated pcap record")?;
+
+        let fraction = if self.nanos {
+            Duration::from_nanos(fraction.into())
+        } else {
+            Duration::from_micros(fraction.into())
+        };
+        Ok(Some(Packet {
+            timestamp: Duration::from_secs(seconds.into()) + fraction,
+            data,
+        }))
+    }
+}
+
+/// TCP segment extracted from a packet
+#[derive(Debug, Clone, PartialEq)]
+pub struct TcpSegment<'a> {
+    pub src: SocketAddr,
+    pub dst: SocketAddr,
+    pub seq: u32,
+    pub syn: bool,
+    pub ack: bool,
+    pub fin: bool,
+    pub rst: bool,
+    pub payload: &'a [u8],
+    /// Part of the payload is missing because the packet has not been fully captured
+    pub truncated: bool,
+}
+
+/// Extracts TCP segment from a packet of the specified link type. Packets that don't carry TCP
+/// are ignored.
+pub fn parse_tcp(link_type: u32, data: &[u8]) -> Result<Option<TcpSegment<'_>>> {
+    let (ethertype, ip_packet) = match link_type {
+        LINKTYPE_ETHERNET => {
+            let mut offset = 12;
+            let mut ethertype = BigEndian::read_u16(slice(data, offset, 2)?);
+            while ethertype == ETHERTYPE_VLAN {
+                offset += 4;
+                ethertype = BigEndian::read_u16(slice(data, offset, 2)?);
+            }
+            (Some(ethertype), &data[offset + 2..])
+        }
+        LINKTYPE_LINUX_SLL => (Some(BigEndian::read_u16(slice(data, 14, 2)?)), &data[16..]),
+        LINKTYPE_LINUX_SLL2 => (
+            Some(BigEndian::read_u16(slice(data, 0, 2)?)),
+            payload(data, 20)?,
+        ),
+        // Address family is in host byte order of the capturing machine, let the IP header decide
+        LINKTYPE_NULL => (None, payload(data, 4)?),
+        LINKTYPE_RAW => (None, data),
+        link_type => bail!("unsupported pcap link type {}", link_type),
+    };
+    let version = match ip_packet.first() {
+        Some(byte) => byte >> 4,
+        None => return Ok(None),
+    };
+    match (ethertype, version) {
+        (Some(ETHERTYPE_IPV4), 4) | (None, 4) => parse_ipv4(ip_packet),
+        (Some(ETHERTYPE_IPV6), 6) | (None, 6) => parse_ipv6(ip_packet),
+        _ => Ok(None),
+    }
+}
+
+fn slice(data: &[u8], offset: usize, len: usize) -> Result<&[u8]> {
+    data.get(offset..offset + len)
+        .ok_or_else(|| anyhow!("truncated packet header"))
+}
+
+fn payload(data: &[u8], offset: usize) -> Result<&[u8]> {
+    data.get(offset..)
+        .ok_or_else(|| anyhow!("truncated packet header"))
+}
+
+fn parse_ipv4(packet: &[u8]) -> Result<Option<TcpSegment<'_>>> {
+    let header = slice(packet, 0, 20)?;
+    let header_len = usize::from(header[0] & 0x0f) * 4;
+    let total_len = usize::from(BigEndian::read_u16(&header[2..4]));
+    let fragment = BigEndian::read_u16(&header[6..8]);
+    if header[9] != IP_PROTOCOL_TCP {
+        return Ok(None);
+    }
+    // More fragments flag or non-zero offset, fragmented stratum traffic is not supported
+    if fragment & 0x3fff != 0 {
+        bail!("fragmented IPv4 packet");
+    }
+    let src = IpAddr::V4(Ipv4Addr::new(
+        header[12], header[13], header[14], header[15],
+    ));
+    let dst = IpAddr::V4(Ipv4Addr::new(
+        header[16], header[17], header[18], header[19],
+    ));
+    parse_tcp_segment(src, dst, packet, header_len, total_len)
+}
+
+fn parse_ipv6(packet: &[u8]) -> Result<Option<TcpSegment<'_>>> {
+    const HEADER_LEN: usize = 40;
+
+    let header = slice(packet, 0, HEADER_LEN)?;
+    // Extension headers are not supported
+    if header[6] != IP_PROTOCOL_TCP {
+        return Ok(None);
+    }
+    let payload_len = usize::from(BigEndian::read_u16(&header[4..6]));
+    let mut src = [0u8; 16];
+    src.copy_from_slice(&header[8..24]);
+    let mut dst = [0u8; 16];
+    dst.copy_from_slice(&header[24..40]);
+    parse_tcp_segment(
+        IpAddr::V6(Ipv6Addr::from(src)),
+        IpAddr::V6(Ipv6Addr::from(dst)),
+        packet,
+        HEADER_LEN,
+        HEADER_LEN + payload_len,
+    )
+}
+
+fn parse_tcp_segment(
+    src: IpAddr,
+    dst: IpAddr,
+    packet: &[u8],
+    ip_header_len: usize,
+    ip_total_len: usize,
+) -> Result<Option<TcpSegment<'_>>> {
+    let header = slice(packet, ip_header_len, 20)?;
+    let header_len = usize::from(header[12] >> 4) * 4;
+    let flags = header[13];
+    let payload_start = ip_header_len + header_len;
+    if ip_total_len < payload_start {
+        bail!("invalid TCP header length");
+    }
+    // Link layer may pad the packet while the capture may have been cut short
+    let payload_end = ip_total_len.min(packet.len());
+    let payload = packet.get(payload_start..payload_end).unwrap_or(&[]);
+
+    Ok(Some(TcpSegment {
+        src: SocketAddr::new(src, BigEndian::read_u16(&header[0..2])),
+        dst: SocketAddr::new(dst, BigEndian::read_u16(&header[2..4])),
+        seq: BigEndian::read_u32(&header[4..8]),
+        fin: flags & 0x01 != 0,
+        syn: flags & 0x02 != 0,
+        rst: flags & 0x04 != 0,
+        ack: flags & 0x10 != 0,
+        payload,
+        truncated: packet.len() < ip_total_len,
+    }))
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use byteorder::WriteBytesExt;
+
+    /// Builds Ethernet frame with IPv4 and TCP headers
+    pub fn build_ethernet_packet(
+        src: SocketAddr,
+        dst: SocketAddr,
+        seq: u32,
+        flags: u8,
+        payload: &[u8],
+    ) -> Vec<u8> {
+        let (src_ip, dst_ip) = match (src.ip(), dst.ip()) {
+            (IpAddr::V4(src_ip), IpAddr::V4(dst_ip)) => (src_ip, dst_ip),
+            _ => panic!("BUG: only IPv4 addresses are supported"),
+        };
+        let mut packet = vec![0u8; 12];
+        packet.write_u16::<BigEndian>(ETHERTYPE_IPV4).unwrap();
+        // IPv4 header
+        packet.push(0x45);
+        packet.push(0);
+        packet
+            .write_u16::<BigEndian>((20 + 20 + payload.len()) as u16)
+            .unwrap();
+        packet.extend_from_slice(&[0, 0, 0x40, 0, 64, IP_PROTOCOL_TCP, 0, 0]);
+        packet.extend_from_slice(&src_ip.octets());
+        packet.extend_from_slice(&dst_ip.octets());
+        // TCP header
+        packet.write_u16::<BigEndian>(src.port()).unwrap();
+        packet.write_u16::<BigEndian>(dst.port()).unwrap();
+        packet.write_u32::<BigEndian>(seq).unwrap();
+        packet.write_u32::<BigEndian>(0).unwrap();
+        packet.push(5 << 4);
+        packet.push(flags);
+        packet.extend_from_slice(&[0; 6]);
+        packet.extend_from_slice(payload);
+        packet
+    }
+
+    /// Builds little endian pcap file with microsecond timestamps from Ethernet frames
+    pub fn build_pcap(packets: &[(Duration, Vec<u8>)]) -> Vec<u8> {
+        let mut file = vec![];
+        file.write_u32::<LittleEndian>(MAGIC_MICROS).unwrap();
+        file.write_u16::<LittleEndian>(2).unwrap();
+        file.write_u16::<LittleEndian>(4).unwrap();
+        file.extend_from_slice(&[0; 8]);
+        file.write_u32::<LittleEndian>(65535).unwrap();
+        file.write_u32::<LittleEndian>(LINKTYPE_ETHERNET).unwrap();
+        for (timestamp, data) in packets {
+            file.write_u32::<LittleEndian>(timestamp.as_secs() as u32)
+                .unwrap();
+            file.write_u32::<LittleEndian>(timestamp.subsec_micros())
+                .unwrap();
+            file.write_u32::<LittleEndian>(data.len() as u32).unwrap();
+            file.write_u32::<LittleEndian>(data.len() as u32).unwrap();
+            file.extend_from_slice(data);
+        }
+        file
+    }
+
+    #[test]
+    fn test_read_tcp_segments() {
+        let miner: SocketAddr = "10.0.0.2:50000".parse().unwrap();
+        let pool: SocketAddr = "10.0.0.1:3333".parse().unwrap();
+        let file = build_pcap(&[
+            (
+                Duration::from_micros(1_000_001),
+                build_ethernet_packet(miner, pool, 100, 0x02, &[]),
+            ),
+            (
+                Duration::from_micros(1_000_002),
+                build_ethernet_packet(miner, pool, 101, 0x18, b"hello"),
+            ),
+        ]);
+
+        let mut reader = Reader::new(&file[..]).expect("BUG: cannot read pcap header");
+        let packet = reader
+            .next_packet()
+            .expect("BUG: cannot read packet")
+            .expect("BUG: missing packet");
+        assert_eq!(packet.timestamp, Duration::from_micros(1_000_001));
+        let segment = parse_tcp(reader.link_type(), &packet.data)
+            .expect("BUG: cannot parse packet")
+            .expect("BUG: missing TCP segment");
+        assert!(segment.syn && !segment.ack);
+        assert_eq!((segment.src, segment.dst, segment.seq), (miner, pool, 100));
+
+        let packet = reader.next_packet().unwrap().unwrap();
+        let segment = parse_tcp(reader.link_type(), &packet.data)
+            .unwrap()
+            .unwrap();
+        assert!(segment.ack && !segment.syn);
+        assert_eq!(segment.payload, b"hello");
+        assert!(!segment.truncated);
+
+        assert_eq!(reader.next_packet().expect("BUG: cannot read end"), None);
+        assert!(Reader::new(&[0u8; GLOBAL_HEADER_SIZE][..]).is_err());
+    }
+}
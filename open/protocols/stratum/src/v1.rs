@@ -39,10 +39,10 @@ use hex::FromHexError;
 use serde::{Deserialize, Serialize};
 use std::convert::TryFrom;
 use std::mem::size_of;
-use tokio::net::TcpStream;
 
-use ii_async_compat::prelude::{tokio, tokio_util};
+use ii_async_compat::prelude::{runtime, tokio_util};
 use ii_logging::macros::*;
+use runtime::net::TcpStream;
 
 //// Tcp stream that produces/consumes V2 frames
 pub type Framed = tokio_util::codec::Framed<TcpStream, <Framing as ii_wire::Framing>::Codec>;
@@ -112,6 +112,10 @@ pub fn build_message_from_frame(frame: framing::Frame) -> Result<Message<Protoco
                     as Box<dyn AnyPayload<Protocol>>,
                 Method::Subscribe => Box::new(messages::Subscribe::try_from(request)?)
                     as Box<dyn AnyPayload<Protocol>>,
+                Method::ExtranonceSubscribe => {
+                    Box::new(messages::ExtranonceSubscribe::try_from(request)?)
+                        as Box<dyn AnyPayload<Protocol>>
+                }
                 Method::Submit => {
                     Box::new(messages::Submit::try_from(request)?) as Box<dyn AnyPayload<Protocol>>
                 }
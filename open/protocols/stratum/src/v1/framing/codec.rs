@@ -39,15 +39,15 @@ impl Decoder for Codec {
     type Error = Error;
 
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        let frame_str = self.0.decode(src)?;
-        let mut bytes = match frame_str {
-            // Note, creating `BytesMut` instance this way creates another copy of the incoming
-            // data. We would have to implement a custom decode that would buffer the data
-            // directly in 1BytesMut`
-            // this copies the frame into the
-            Some(frame_str) => BytesMut::from(frame_str.as_bytes()),
+        let frame_str = match self.0.decode(src)? {
+            Some(frame_str) => frame_str,
             None => return Ok(None),
         };
+        // Note, copying the frame creates another copy of the incoming data. We would have to
+        // implement a custom decode that would buffer the data directly in `BytesMut`. The pooled
+        // buffer at least saves the allocation.
+        let mut bytes = ii_bufpool::frame_pool().get();
+        bytes.extend_from_slice(frame_str.as_bytes());
         Frame::deserialize(&mut bytes).map(Some)
     }
 }
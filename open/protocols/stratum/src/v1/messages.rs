@@ -123,9 +123,7 @@ macro_rules! impl_conversion_response {
                 // TODO this is needs to be fixed within the deserialization stack with regards
                 // to the visitor pattern. We shouldn't clone any part of the incoming message
                 // However, since the result is being passed by reference
-                serde_json::from_value(result.0.clone())
-                    .context("Failed to parse response")
-                    .map_err(Into::into)
+                serde_json::from_value(result.0.clone()).context("Failed to parse response")
             }
         }
     };
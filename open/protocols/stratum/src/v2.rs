@@ -39,9 +39,9 @@ use crate::{AnyPayload, Message};
 use async_trait::async_trait;
 use packed_struct::prelude::*;
 use std::convert::TryFrom;
-use tokio::net::TcpStream;
 
 use ii_async_compat::prelude::*;
+use runtime::net::TcpStream;
 use ii_logging::macros::*;
 use ii_wire;
 
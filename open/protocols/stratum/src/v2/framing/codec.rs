@@ -107,11 +107,11 @@ impl Encoder for Codec {
         item: Self::Item,
         dst: &mut BytesMut,
     ) -> std::result::Result<(), Self::Error> {
-        let mut encoded_frame = BytesMut::new();
+        let mut encoded_frame = ii_bufpool::frame_pool().get();
         item.serialize(&mut encoded_frame)?;
         match self.noise_codec {
-            Some(ref mut noise_codec) => noise_codec.encode(encoded_frame, dst)?,
-            None => dst.unsplit(encoded_frame),
+            Some(ref mut noise_codec) => noise_codec.encode(encoded_frame.split(), dst)?,
+            None => dst.extend_from_slice(&encoded_frame),
         }
         Ok(())
     }
@@ -102,6 +102,20 @@ pub struct SetupConnectionSuccess {
     pub flags: u32,
 }
 
+impl SetupConnectionSuccess {
+    /// Flag of the mining protocol: upstream node will not accept any changes to the version field
+    pub const REQUIRES_FIXED_VERSION: u32 = 0x1;
+
+    /// Version bits which may be rolled on the connection as negotiated by upstream node
+    pub fn version_mask(&self) -> u32 {
+        if self.flags & Self::REQUIRES_FIXED_VERSION != 0 {
+            0
+        } else {
+            crate::BIP320_N_VERSION_MASK
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct SetupConnectionError {
     pub flags: u32,
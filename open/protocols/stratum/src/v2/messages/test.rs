@@ -55,3 +55,14 @@ fn test_serialize_setup_connection() {
         serialized_message
     );
 }
+
+#[test]
+fn test_setup_connection_success_version_mask() {
+    let mut message = SetupConnectionSuccess {
+        used_version: 2,
+        flags: 0,
+    };
+    assert_eq!(message.version_mask(), crate::BIP320_N_VERSION_MASK);
+    message.flags = SetupConnectionSuccess::REQUIRES_FIXED_VERSION;
+    assert_eq!(message.version_mask(), 0);
+}
@@ -25,13 +25,16 @@
 //! TransportState of the noise, that will be used for running the AEAD communnication.
 
 use bytes::{Bytes, BytesMut};
+use ii_bufpool::BufPool;
+use lazy_static::lazy_static;
+use snow::resolvers::{CryptoResolver, DefaultResolver};
 use snow::{params::NoiseParams, Builder, HandshakeState, TransportState};
 use std::convert::TryFrom;
-use tokio::net::TcpStream;
 use tokio_util::codec::{Framed, FramedParts};
 
 use ii_async_compat::prelude::*;
 use ii_wire;
+use runtime::net::TcpStream;
 
 use crate::error::{Error, ErrorKind, Result, ResultExt};
 use crate::v2;
@@ -57,6 +60,11 @@ pub const MAX_MESSAGE_SIZE: usize = 65535;
 pub const TAGLEN: usize = 16;
 pub const MAX_PAYLOAD_SIZE: usize = MAX_MESSAGE_SIZE - TAGLEN;
 
+lazy_static! {
+    /// Scratch buffers for encryption and decryption of transport messages
+    static ref TRANSPORT_BUF_POOL: BufPool = BufPool::new(MAX_MESSAGE_SIZE, 4);
+}
+
 /// Special framing for noise messages, Helper struct that groups all framing related associated
 /// types (Frame + Error + Codec) for the `ii_wire::Framing` trait
 #[derive(Debug)]
@@ -79,6 +87,25 @@ pub fn generate_keypair() -> Result<StaticKeypair> {
     builder.generate_keypair().map_err(Into::into)
 }
 
+/// Derives the static public key that corresponds to `secret_key` for the current params. This
+/// allows checking that a secret key matches the public key in a certificate.
+pub fn public_key_from_secret(secret_key: &StaticSecretKey) -> Result<StaticPublicKey> {
+    let params: NoiseParams = PARAMS.parse().expect("BUG: cannot parse noise parameters");
+    let mut dh = DefaultResolver
+        .resolve_dh(&params.dh)
+        .expect("BUG: unsupported noise DH function");
+    if secret_key.len() != dh.priv_len() {
+        return Err(ErrorKind::Noise(format!(
+            "Invalid secret key length: {}, expected: {}",
+            secret_key.len(),
+            dh.priv_len()
+        ))
+        .into());
+    }
+    dh.set(&secret_key[..]);
+    Ok(dh.pubkey().to_vec())
+}
+
 pub struct Initiator {
     stage: usize,
     handshake_state: HandshakeState,
@@ -295,9 +322,10 @@ impl TransportMode {
     /// It is an adaptor for not a very convenient interface of Snow that requires fixed size
     /// buffers
     pub fn read(&mut self, encrypted_msg: BytesMut, decrypted_msg: &mut BytesMut) -> Result<()> {
-        let mut out_vec = vec![0u8; MAX_MESSAGE_SIZE];
-        let msg_len = self.inner.read_message(&encrypted_msg[..], &mut out_vec)?;
-        decrypted_msg.extend_from_slice(&out_vec[..msg_len]);
+        let mut out_buf = TRANSPORT_BUF_POOL.get();
+        out_buf.resize(MAX_MESSAGE_SIZE, 0);
+        let msg_len = self.inner.read_message(&encrypted_msg[..], &mut out_buf)?;
+        decrypted_msg.extend_from_slice(&out_buf[..msg_len]);
 
         Ok(())
     }
@@ -307,9 +335,10 @@ impl TransportMode {
     /// It is an adaptor for not a very convenient interface of Snow that requires fixed size
     /// buffers
     pub fn write(&mut self, plain_msg: BytesMut, encrypted_msg: &mut BytesMut) -> Result<()> {
-        let mut out_vec = vec![0u8; MAX_MESSAGE_SIZE];
-        let msg_len = self.inner.write_message(&plain_msg[..], &mut out_vec)?;
-        encrypted_msg.extend_from_slice(&out_vec[..msg_len]);
+        let mut out_buf = TRANSPORT_BUF_POOL.get();
+        out_buf.resize(MAX_MESSAGE_SIZE, 0);
+        let msg_len = self.inner.write_message(&plain_msg[..], &mut out_buf)?;
+        encrypted_msg.extend_from_slice(&out_buf[..msg_len]);
 
         Ok(())
     }
@@ -466,7 +495,7 @@ pub(crate) mod test {
 
         // Spawn server task that reacts to any incoming message and responds
         // with SetupConnectionSuccess
-        tokio::spawn(async move {
+        runtime::spawn(async move {
             let responder = Responder::new(&static_keypair, signature_noise_message);
 
             let conn = server
@@ -23,6 +23,7 @@
 //! Authentication module that provides pubkey and certificate handling API
 
 use bytes::{buf::BufMutExt, BytesMut};
+use rand::rngs::OsRng;
 use serde::{Deserialize, Serialize};
 use std::convert::TryFrom;
 use std::time::{Duration, SystemTime};
@@ -35,6 +36,22 @@ use crate::v2::{self, noise::StaticPublicKey};
 mod formats;
 pub use formats::*;
 
+/// Generates keypair of a certification authority that signs static public keys of stratum
+/// servers
+pub fn generate_authority_keypair() -> ed25519_dalek::Keypair {
+    let mut csprng = OsRng {};
+    ed25519_dalek::Keypair::generate(&mut csprng)
+}
+
+/// Builds the full authority keypair as ed25519_dalek requires it for signing
+pub fn authority_keypair_from_secret(secret: ed25519_dalek::SecretKey) -> ed25519_dalek::Keypair {
+    ed25519_dalek::Keypair {
+        // Derive the public key from the secret key
+        public: (&secret).into(),
+        secret,
+    }
+}
+
 /// Header of the `SignedPart` that will also be part of the `Certificate`
 #[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
 pub struct SignedPartHeader {
@@ -48,9 +65,16 @@ pub struct SignedPartHeader {
 impl SignedPartHeader {
     const VERSION: u16 = 0;
 
-    pub fn with_duration(valid_for: Duration) -> Result<Self> {
-        let valid_from = SystemTime::now();
-        let not_valid_after = valid_from + valid_for;
+    /// Builds header for a certificate that is valid within the specified window. Note that the
+    /// times are stored with a precision of seconds.
+    pub fn new(valid_from: SystemTime, not_valid_after: SystemTime) -> Result<Self> {
+        if not_valid_after <= valid_from {
+            return Err(ErrorKind::Noise(format!(
+                "Certificate validity ends ({:?}) before it starts ({:?})",
+                not_valid_after, valid_from
+            ))
+            .into());
+        }
         Ok(Self {
             version: Self::VERSION,
             valid_from: Self::system_time_to_unix_time_u32(&valid_from)?,
@@ -58,6 +82,12 @@ impl SignedPartHeader {
         })
     }
 
+    /// Builds header for a certificate that is valid from now for the `valid_for` duration
+    pub fn with_duration(valid_for: Duration) -> Result<Self> {
+        let valid_from = SystemTime::now();
+        Self::new(valid_from, valid_from + valid_for)
+    }
+
     pub fn valid_from(&self) -> SystemTime {
         Self::unix_time_u32_to_system_time(self.valid_from)
             .expect("BUG: cannot provide 'valid_from' time")
@@ -80,7 +110,7 @@ impl SignedPartHeader {
         if now_timestamp > self.not_valid_after {
             return Err(ErrorKind::Noise(format!(
                 "Certificate expired, not valid after: {:?}, now: {:?}",
-                self.valid_from, now
+                self.not_valid_after, now
             ))
             .into());
         }
@@ -212,7 +242,6 @@ pub(crate) mod test {
         super::{generate_keypair, StaticKeypair},
         *,
     };
-    use rand::rngs::OsRng;
     const TEST_CERT_VALIDITY: Duration = Duration::from_secs(3600);
 
     // Helper that builds a `SignedPart` (as a base e.g. for a noise message or a certificate),
@@ -224,10 +253,9 @@ pub(crate) mod test {
         StaticKeypair,
         ed25519_dalek::Signature,
     ) {
-        let mut csprng = OsRng {};
         let to_be_signed_keypair =
             generate_keypair().expect("BUG: cannot generate noise static keypair");
-        let authority_keypair = ed25519_dalek::Keypair::generate(&mut csprng);
+        let authority_keypair = generate_authority_keypair();
         let header = SignedPartHeader::with_duration(TEST_CERT_VALIDITY)
             .expect("BUG: cannot prepare certificate header");
 
@@ -281,6 +309,22 @@ pub(crate) mod test {
         );
     }
 
+    #[test]
+    fn header_time_validity_window() {
+        let valid_from = SystemTime::now() + Duration::from_secs(3600);
+        let header = SignedPartHeader::new(valid_from, valid_from + TEST_CERT_VALIDITY)
+            .expect("BUG: cannot build certificate header");
+        assert!(header.verify_expiration(SystemTime::now()).is_err());
+        header
+            .verify_expiration(valid_from + Duration::from_secs(10))
+            .expect("BUG: certificate should be evaluated as valid!");
+
+        assert!(
+            SignedPartHeader::new(valid_from, valid_from).is_err(),
+            "BUG: empty validity window accepted"
+        );
+    }
+
     #[test]
     fn signature_noise_message_serialization() {
         let (signed_part, authority_keypair, _static_keypair, _signature) =
@@ -28,8 +28,8 @@ use std::fmt;
 use std::time::SystemTime;
 
 use super::{SignatureNoiseMessage, SignedPart, SignedPartHeader};
-use crate::error::{Error, Result};
-use crate::v2::noise::{StaticPublicKey, StaticSecretKey};
+use crate::error::{Error, ErrorKind, Result};
+use crate::v2::noise::{public_key_from_secret, StaticPublicKey, StaticSecretKey};
 
 /// Generates implementation for the encoded type, Display trait and the file format and
 macro_rules! impl_basic_type {
@@ -230,35 +230,72 @@ impl Certificate {
         }
     }
 
-    // TODO research if it is possible to generate the public key via existing 'snow' API as we don't
-    // want to cross the API boundary that carefully hides the underalying type of the keys
-    //    /// TODO implement unit test
-    //    /// Ensures that the secret key generates the same public key as the one present in this
-    //    /// certificate
-    //    pub fn validate_secret_key(&self, secret_key: StaticSecretKey) -> Result<StaticPublicKey> {
-    //        let public_key = SecretStaticKeyFormat::new(ed25519_dalek::PublicKey::from(secret_key));
-    //
-    //        match public_key == self.pubkey {
-    //            true => Ok(public_key.into_inner()),
-    //            false => Err(ErrorKind::Noise(format!(
-    //                "Invalid certificate: public key({}) doesn't match public key({}) generated from \
-    //                 secret key",
-    //                public_key.inner, self.pubkey.inner,
-    //            ))
-    //            .into()),
-    //        }
-    //    }
+    /// Signs `public_key` with the authority keypair and builds the certificate
+    pub fn sign(
+        header: SignedPartHeader,
+        public_key: StaticPublicKey,
+        authority_keypair: &ed25519_dalek::Keypair,
+    ) -> Result<Self> {
+        let signed_part = SignedPart::new(header, public_key, authority_keypair.public);
+        let signature = signed_part.sign_with(authority_keypair)?;
+        Ok(Self::new(signed_part, signature))
+    }
+
+    pub fn header(&self) -> &SignedPartHeader {
+        &self.signed_part_header
+    }
+
+    pub fn authority_public_key(&self) -> ed25519_dalek::PublicKey {
+        self.authority_public_key.clone().into_inner()
+    }
+
+    /// Ensures that the secret key generates the same public key as the one present in this
+    /// certificate
+    pub fn validate_secret_key(&self, secret_key: &StaticSecretKey) -> Result<()> {
+        let public_key = public_key_from_secret(secret_key)?;
+        let certificate_public_key = self.public_key.clone().into_inner();
+        if public_key != certificate_public_key {
+            return Err(ErrorKind::Noise(format!(
+                "Invalid certificate: public key ({}) doesn't match public key ({}) generated from \
+                 secret key",
+                EncodedStaticPublicKey::new(certificate_public_key),
+                EncodedStaticPublicKey::new(public_key),
+            ))
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Ensures that the certificate has been signed by the expected authority. Note that the
+    /// signature itself is checked by `validate()`.
+    pub fn verify_authority(&self, authority_public_key: &ed25519_dalek::PublicKey) -> Result<()> {
+        let certificate_authority_public_key = self.authority_public_key();
+        if *authority_public_key != certificate_authority_public_key {
+            return Err(ErrorKind::Noise(format!(
+                "Certificate signed by unexpected authority ({}), expected: {}",
+                EncodedEd25519PublicKey::new(certificate_authority_public_key),
+                EncodedEd25519PublicKey::new(*authority_public_key),
+            ))
+            .into());
+        }
+        Ok(())
+    }
 
     /// See  https://docs.rs/ed25519-dalek/1.0.0-pre.3/ed25519_dalek/struct.PublicKey.html on
     /// details for the strict verification
     pub fn validate(&self) -> Result<()> {
+        self.validate_at(SystemTime::now())
+    }
+
+    /// Verifies the signature and that the certificate is valid at the specified time
+    pub fn validate_at(&self, now: SystemTime) -> Result<()> {
         let signed_part = SignedPart::new(
             self.signed_part_header.clone(),
             self.public_key.clone().into_inner(),
             self.authority_public_key.clone().into_inner(),
         );
         signed_part.verify(&self.signature.clone().into_inner())?;
-        signed_part.verify_expiration(SystemTime::now())
+        signed_part.verify_expiration(now)
     }
 
     pub fn from_noise_message(
@@ -323,4 +360,78 @@ pub mod test {
 
         assert_eq!(certificate, deserialized_cert, "Certificates don't match!");
     }
+
+    #[test]
+    fn certificate_verify_authority() {
+        let (signed_part, authority_keypair, static_keypair, _signature) =
+            build_test_signed_part_and_auth();
+        let certificate = Certificate::sign(
+            signed_part.header,
+            static_keypair.public,
+            &authority_keypair,
+        )
+        .expect("BUG: cannot sign certificate");
+
+        certificate
+            .verify_authority(&authority_keypair.public)
+            .expect("BUG: certificate not signed by the authority");
+        let other_authority_keypair = super::super::generate_authority_keypair();
+        assert!(
+            certificate
+                .verify_authority(&other_authority_keypair.public)
+                .is_err(),
+            "BUG: certificate accepted for a different authority"
+        );
+    }
+
+    #[test]
+    fn certificate_validate_secret_key() {
+        let (signed_part, _authority_keypair, static_keypair, signature) =
+            build_test_signed_part_and_auth();
+        let certificate = Certificate::new(signed_part, signature);
+
+        certificate
+            .validate_secret_key(&static_keypair.private)
+            .expect("BUG: secret key doesn't match the certificate");
+        let other_keypair =
+            crate::v2::noise::generate_keypair().expect("BUG: cannot generate noise keypair");
+        assert!(
+            certificate
+                .validate_secret_key(&other_keypair.private)
+                .is_err(),
+            "BUG: certificate accepted a different secret key"
+        );
+    }
+
+    #[test]
+    fn certificate_validate_at() {
+        let (signed_part, _authority_keypair, _static_keypair, signature) =
+            build_test_signed_part_and_auth();
+        let certificate = Certificate::new(signed_part, signature);
+        let not_valid_after = certificate.header().not_valid_after();
+
+        certificate
+            .validate_at(not_valid_after)
+            .expect("BUG: certificate not valid at the end of its validity");
+        assert!(
+            certificate
+                .validate_at(not_valid_after + std::time::Duration::from_secs(1))
+                .is_err(),
+            "BUG: expired certificate evaluated as valid"
+        );
+    }
+
+    #[test]
+    fn certificate_string_serialization() {
+        let (signed_part, _authority_keypair, _static_keypair, signature) =
+            build_test_signed_part_and_auth();
+        let certificate = Certificate::new(signed_part, signature);
+
+        let serialized_cert =
+            String::try_from(certificate.clone()).expect("BUG: cannot serialize certificate");
+        let deserialized_cert =
+            Certificate::try_from(serialized_cert).expect("BUG: cannot deserialize certificate");
+
+        assert_eq!(certificate, deserialized_cert, "Certificates don't match!");
+    }
 }
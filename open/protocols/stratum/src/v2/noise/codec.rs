@@ -84,9 +84,9 @@ impl Decoder for Codec {
             State::HandShake => noise_msg,
             State::Transport(transport_mode) => match noise_msg {
                 Some(msg) => {
-                    let mut decrypted_msg = BytesMut::new();
+                    let mut decrypted_msg = ii_bufpool::frame_pool().get();
                     transport_mode.read(msg, &mut decrypted_msg)?;
-                    Some(decrypted_msg)
+                    Some(decrypted_msg.split())
                 }
                 None => None,
             },
@@ -27,10 +27,11 @@ use std::str::FromStr;
 use std::time::{Duration, Instant};
 use std::vec;
 
-use tokio::net::TcpStream;
-use tokio::time;
+use runtime::net::TcpStream;
 
+use ii_async_compat::clock::{self, DynClock};
 use ii_async_compat::prelude::*;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 use thiserror::Error;
 
 #[derive(Error, PartialEq, Eq, Debug)]
@@ -52,6 +53,9 @@ impl fmt::Display for AddressParseError {
 /// server sockets.
 ///
 /// You can also use `connect()` to create a `Connection` directly.
+///
+/// `Address` is (de)serialized in the same `"hostname:port"` format so that it can be used
+/// directly in configuration files.
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct Address(pub String, pub u16);
 
@@ -122,6 +126,19 @@ impl fmt::Display for Address {
     }
 }
 
+impl Serialize for Address {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Address {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let addr = String::deserialize(deserializer)?;
+        Address::from_str(&addr).map_err(de::Error::custom)
+    }
+}
+
 /// Backoff generation for `ReConnection`.
 pub trait Backoff: Send + fmt::Debug {
     /// Called by `ReConnection` when next sleep duration is required.
@@ -184,7 +201,47 @@ impl Backoff for DefaultBackoff {
 
 impl Default for DefaultBackoff {
     fn default() -> Self {
-        Self::new(Duration::from_millis(100), Duration::from_secs(5))
+        BackoffParams::default().into()
+    }
+}
+
+impl From<BackoffParams> for DefaultBackoff {
+    fn from(params: BackoffParams) -> Self {
+        Self::new(params.unit, params.max)
+    }
+}
+
+/// Parameters of the default backoff as they are specified in configuration files.
+/// Durations are (de)serialized in milliseconds: `{ unit_ms = 100, max_ms = 5000 }`.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct BackoffParams {
+    /// Duration which multiplies numbers of the fibonacci sequence
+    #[serde(rename = "unit_ms", with = "duration_millis")]
+    pub unit: Duration,
+    /// Maximum backoff ever returned
+    #[serde(rename = "max_ms", with = "duration_millis")]
+    pub max: Duration,
+}
+
+impl Default for BackoffParams {
+    fn default() -> Self {
+        Self {
+            unit: Duration::from_millis(100),
+            max: Duration::from_secs(5),
+        }
+    }
+}
+
+mod duration_millis {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(duration.as_millis() as u64)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        u64::deserialize(deserializer).map(Duration::from_millis)
     }
 }
 
@@ -246,6 +303,8 @@ pub struct Client {
     /// Time of the first attempt, reset if the connection is established,
     /// see AttemptError::start_time
     start_time: Option<Instant>,
+    /// Source of time for backoff delays
+    clock: DynClock,
 }
 
 impl Client {
@@ -267,6 +326,7 @@ impl Client {
             next_delay: None,
             retries: 0,
             start_time: None,
+            clock: clock::system(),
         }
     }
 
@@ -278,13 +338,22 @@ impl Client {
         self.backoff = Box::new(backoff);
     }
 
+    /// Use the default backoff with parameters obtained e.g. from a configuration file
+    pub fn set_backoff_params(&mut self, params: BackoffParams) {
+        self.set_backoff(DefaultBackoff::from(params));
+    }
+
+    pub fn set_clock(&mut self, clock: DynClock) {
+        self.clock = clock;
+    }
+
     pub async fn next(&mut self) -> Result<TcpStream, AttemptError> {
-        self.start_time.get_or_insert(Instant::now());
+        self.start_time.get_or_insert(self.clock.now());
 
         if let Some((when, delay)) = self.next_delay.take() {
-            let since_last_attempt = Instant::now().duration_since(when);
+            let since_last_attempt = self.clock.now().duration_since(when);
             if delay > since_last_attempt {
-                time::delay_for(delay - since_last_attempt).await;
+                self.clock.sleep(delay - since_last_attempt).await;
             }
         }
 
@@ -297,7 +366,7 @@ impl Client {
             }
             Err(err) => {
                 let backoff = self.backoff.next();
-                self.next_delay = Some((Instant::now(), backoff));
+                self.next_delay = Some((self.clock.now(), backoff));
                 self.retries += 1;
                 let start_time = self.start_time.unwrap();
                 Err(AttemptError::new(backoff, self.retries, start_time, err))
@@ -310,6 +379,9 @@ impl Client {
 mod tests {
     use super::*;
 
+    use ii_async_compat::clock::{Clock, ManualClock};
+    use std::sync::Arc;
+
     #[test]
     fn wire_address_parsing() {
         assert_eq!(
@@ -327,4 +399,57 @@ mod tests {
         assert_eq!(Address::from_str(":"), Err(AddressParseError));
         assert_eq!(Address::from_str(":123"), Err(AddressParseError));
     }
+
+    #[test]
+    fn wire_serde() {
+        let addr: Address = serde_json::from_str("\"localhost:443\"").expect("invalid address");
+        assert_eq!(addr, Address("localhost".into(), 443));
+        assert_eq!(
+            serde_json::to_string(&addr).expect("cannot serialize"),
+            "\"localhost:443\""
+        );
+        assert!(serde_json::from_str::<Address>("\"localhost\"").is_err());
+
+        let params: BackoffParams =
+            serde_json::from_str(r#"{"unit_ms": 200, "max_ms": 10000}"#).expect("invalid params");
+        assert_eq!(
+            params,
+            BackoffParams {
+                unit: Duration::from_millis(200),
+                max: Duration::from_secs(10),
+            }
+        );
+        assert_eq!(
+            serde_json::to_string(&BackoffParams::default()).expect("cannot serialize"),
+            r#"{"unit_ms":100,"max_ms":5000}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn wire_client_backoff() {
+        // get a local address which refuses connections
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .expect("cannot bind")
+            .local_addr()
+            .expect("cannot get address");
+        let clock = Arc::new(ManualClock::new());
+        let mut client = Client::new(Address(addr.ip().to_string(), addr.port()));
+        client.set_clock(clock.clone());
+
+        let error = client.next().await.expect_err("connection should fail");
+        assert_eq!(error.retries, 1);
+        assert_eq!(error.start_time, clock.now());
+        let delay = error.next_attempt_in;
+        assert_eq!(delay, Duration::from_millis(100));
+
+        // the next attempt is postponed until the backoff elapses
+        let next = client.next();
+        futures::pin_mut!(next);
+        clock.advance(delay / 2);
+        assert!(futures::poll!(&mut next).is_pending());
+        clock.advance(delay / 2);
+        let error = next.await.expect_err("connection should fail");
+        assert_eq!(error.retries, 2);
+        assert_eq!(error.next_attempt_in, Duration::from_millis(100));
+    }
 }
@@ -20,6 +20,7 @@
 // of such proprietary license or if you have any other questions, please
 // contact us at opensource@braiins.com.
 
+use std::fmt;
 use std::net::TcpListener as StdTcpListener;
 use std::net::ToSocketAddrs as StdToSocketAddrs;
 use std::pin::Pin;
@@ -27,13 +28,18 @@ use std::task::{Context, Poll};
 
 use ii_async_compat::prelude::*;
 use pin_project::pin_project;
-use tokio::net::{TcpListener, TcpStream};
+use runtime::net::{TcpListener, TcpStream};
+
+type HaltFuture = Pin<Box<dyn Future<Output = Option<ii_stop::DoneSender>> + Send + Sync>>;
 
 #[pin_project]
-#[derive(Debug)]
 pub struct Server {
     #[pin]
     tcp: TcpListener,
+    /// Notification of halting the termination context in which the server runs
+    halt: Option<HaltFuture>,
+    /// The server has been halted and doesn't accept any connection
+    halted: bool,
 }
 
 impl Server {
@@ -41,7 +47,31 @@ impl Server {
         let tcp = StdTcpListener::bind(addr)?;
         let tcp = TcpListener::from_std(tcp)?;
 
-        Ok(Server { tcp })
+        Ok(Server {
+            tcp,
+            halt: None,
+            halted: false,
+        })
+    }
+
+    /// Stop accepting incoming connections (the stream ends) when termination context of
+    /// `notify_receiver` is halted. The halt is confirmed right away.
+    pub fn with_halt(mut self, notify_receiver: ii_stop::NotifyReceiver) -> Self {
+        self.halt = Some(Box::pin(notify_receiver.wait_for_halt()));
+        self
+    }
+
+    pub fn local_addr(&self) -> std::io::Result<std::net::SocketAddr> {
+        self.tcp.local_addr()
+    }
+}
+
+impl fmt::Debug for Server {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Server")
+            .field("tcp", &self.tcp)
+            .field("halted", &self.halted)
+            .finish()
     }
 }
 
@@ -49,8 +79,51 @@ impl Stream for Server {
     type Item = std::io::Result<TcpStream>;
 
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
-        let mut tcp = self.project().tcp;
+        let this = self.project();
+        if *this.halted {
+            return Poll::Ready(None);
+        }
+        if let Some(halt) = this.halt.as_mut() {
+            if let Poll::Ready(done_sender) = halt.as_mut().poll(cx) {
+                *this.halt = None;
+                // Halt sender may have been dropped and then the server just keeps running
+                if let Some(done_sender) = done_sender {
+                    *this.halted = true;
+                    done_sender.confirm();
+                    return Poll::Ready(None);
+                }
+            }
+        }
 
+        let mut tcp = this.tcp;
         Pin::new(&mut tcp.incoming()).poll_next(cx)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn server_halt() {
+        let (halt_handle, halt_receiver) = ii_stop::make_pair(Duration::from_secs(1));
+        let mut server = Server::bind("127.0.0.1:0")
+            .expect("cannot bind server")
+            .with_halt(halt_receiver.register_client("server".into()).await);
+
+        // Connection is established by the kernel before it is accepted
+        let _client = std::net::TcpStream::connect(server.local_addr().expect("no local address"))
+            .expect("connect failed");
+        server
+            .next()
+            .await
+            .expect("server ended")
+            .expect("accept failed");
+
+        // The halt is confirmed only once the server is polled
+        let (_, next) = future::join(halt_handle.send_halt(), server.next()).await;
+        assert!(next.is_none());
+    }
+}
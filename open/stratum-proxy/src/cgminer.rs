@@ -0,0 +1,531 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Read-only CGMiner compatible API exposing statistics of the proxy so that monitoring tools
+//! written for mining devices can scrape it without custom adapters
+//!
+//! Each translated V2 connection is reported as one ASC device and the upstream V1 server as the
+//! only pool. The proxy measures hashrate within a single window (`stats::SHARE_METER_WINDOW`)
+//! which is reported for all hashrate intervals.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time;
+
+use ii_cgminer_api::support::ValueExt as _;
+use ii_cgminer_api::{command, json, response};
+use ii_stats::share::difficulty_to_hashes;
+use ii_wire::Address;
+
+use crate::error::Result;
+use crate::stats::{self, WorkerEntry};
+
+/// Signature reported by the `version` command
+const SIGNATURE: &str = "StratumProxy";
+
+/// Index of the upstream V1 server in the `pools` response
+const POOL_IDX: i32 = 0;
+
+fn unix_time(time: Option<time::SystemTime>) -> response::Time {
+    time.and_then(|time| time.duration_since(time::UNIX_EPOCH).ok())
+        .map_or(0, |duration| duration.as_secs() as response::Time)
+}
+
+fn percent(part: f64, total: f64) -> response::Percent {
+    if total > 0.0 {
+        part / total * 100.0
+    } else {
+        0.0
+    }
+}
+
+/// Average rate per minute of `count` events that happened within `elapsed`
+fn per_minute(count: u64, elapsed: time::Duration) -> response::Utility {
+    if elapsed.as_secs() != 0 {
+        count as f64 / elapsed.as_secs_f64() * 60.0
+    } else {
+        count as f64
+    }
+}
+
+/// Average mega hashes per second corresponding to `difficulty` of shares found within `elapsed`
+fn average_mhs(difficulty: f64, elapsed: time::Duration) -> response::MegaHashes {
+    if elapsed.as_secs() != 0 {
+        difficulty_to_hashes(difficulty) / elapsed.as_secs_f64() / 1e6
+    } else {
+        0.0
+    }
+}
+
+struct Handler {
+    registry: Arc<stats::Registry>,
+    upstream_address: Address,
+}
+
+impl Handler {
+    fn new(registry: Arc<stats::Registry>, upstream_address: Address) -> Self {
+        Self {
+            registry,
+            upstream_address,
+        }
+    }
+
+    fn pool_url(&self) -> String {
+        format!("stratum+tcp://{}", self.upstream_address)
+    }
+
+    fn workers(&self) -> Vec<WorkerEntry> {
+        self.registry.workers(time::Instant::now())
+    }
+
+    /// Worker with the most recent share
+    fn last_share_worker(workers: &[WorkerEntry]) -> Option<&WorkerEntry> {
+        workers
+            .iter()
+            .filter(|worker| worker.stats.last_share_time.is_some())
+            .max_by_key(|worker| worker.stats.last_share_time)
+    }
+
+    fn get_asc_status(idx: usize, worker: WorkerEntry) -> response::Asc {
+        let peer_addr = worker.peer_addr;
+        let stats = worker.stats;
+        let totals = stats.totals;
+        let mhs = stats.hashrate / 1e6;
+        let last_share_time = unix_time(stats.last_share_time);
+
+        response::Asc {
+            idx: idx as i32,
+            // Monitoring tools display the name, the user identifies the worker best
+            name: stats.user.unwrap_or_else(|| peer_addr.to_string()),
+            id: worker.id as i32,
+            enabled: response::Bool::Y,
+            status: if stats.difficulty > 0.0 {
+                response::AscStatus::Alive
+            } else {
+                response::AscStatus::Initialising
+            },
+            temperature: 0.0,
+            mhs_av: average_mhs(totals.accepted_difficulty, stats.elapsed),
+            mhs_5s: mhs,
+            mhs_1m: mhs,
+            mhs_5m: mhs,
+            mhs_15m: mhs,
+            accepted: totals.accepted_count as i32,
+            rejected: totals.rejected_count as i32,
+            hardware_errors: 0,
+            utility: per_minute(totals.accepted_count, stats.elapsed),
+            last_share_pool: POOL_IDX,
+            last_share_time,
+            total_mega_hashes: difficulty_to_hashes(totals.accepted_difficulty) / 1e6,
+            diff1_work: totals.accepted_difficulty as u64,
+            difficulty_accepted: totals.accepted_difficulty,
+            difficulty_rejected: totals.rejected_difficulty,
+            last_share_difficulty: stats.last_share_difficulty,
+            last_valid_work: last_share_time,
+            device_hardware_ratio: 0.0,
+            device_rejected_ratio: percent(
+                totals.rejected_difficulty,
+                totals.accepted_difficulty + totals.rejected_difficulty,
+            ),
+            device_elapsed: stats.elapsed.as_secs(),
+            hardware_error_mhs_15m: 0.0,
+            nominal_mhs: 0.0,
+        }
+    }
+
+    fn get_pool_status(&self) -> response::Pool {
+        let workers = self.workers();
+        let totals = self.registry.totals();
+        let last_share_worker = Self::last_share_worker(&workers);
+        let mut users: Vec<_> = workers
+            .iter()
+            .filter_map(|worker| worker.stats.user.clone())
+            .collect();
+        users.sort();
+        users.dedup();
+        let difficulty = last_share_worker
+            .or_else(|| workers.last())
+            .map_or(0.0, |worker| worker.stats.difficulty);
+
+        response::Pool {
+            idx: POOL_IDX,
+            url: self.pool_url(),
+            status: response::PoolStatus::Alive,
+            priority: 0,
+            quota: 1,
+            long_poll: response::Bool::N,
+            getworks: totals.job_count as u32,
+            accepted: totals.accepted_count,
+            rejected: totals.rejected_count,
+            works: totals.job_count as i32,
+            discarded: 0,
+            stale: 0,
+            get_failures: 0,
+            remote_failures: 0,
+            user: users.join(","),
+            last_share_time: unix_time(
+                last_share_worker.and_then(|worker| worker.stats.last_share_time),
+            ),
+            diff1_shares: totals.accepted_difficulty as u64,
+            proxy_type: "".to_string(),
+            proxy: "".to_string(),
+            difficulty_accepted: totals.accepted_difficulty,
+            difficulty_rejected: totals.rejected_difficulty,
+            difficulty_stale: 0.0,
+            last_share_difficulty: last_share_worker
+                .map_or(0.0, |worker| worker.stats.last_share_difficulty),
+            work_difficulty: difficulty,
+            has_stratum: true,
+            stratum_active: !workers.is_empty(),
+            stratum_url: self.upstream_address.to_string(),
+            stratum_difficulty: difficulty,
+            has_vmask: true,
+            has_gbt: false,
+            best_share: 0,
+            pool_rejected_ratio: percent(
+                totals.rejected_count as f64,
+                (totals.accepted_count + totals.rejected_count) as f64,
+            ),
+            pool_stale_ratio: 0.0,
+            bad_work: 0,
+            current_block_height: 0,
+            current_block_version: 0,
+            asic_boost: true,
+        }
+    }
+
+    fn get_asc_stats(idx: usize, worker: &WorkerEntry) -> response::AscStats {
+        response::AscStats {
+            header: response::StatsHeader {
+                idx: idx as i32,
+                id: format!("SV2{}", worker.id),
+                elapsed: worker.stats.elapsed.as_secs(),
+                calls: 0,
+                wait: 0.0,
+                max: 0.0,
+                min: 0.0,
+            },
+        }
+    }
+
+    fn access_denied<T>(command: &str) -> command::Result<T> {
+        Err(response::ErrorCode::AccessDeniedCmd(command.to_string()).into())
+    }
+}
+
+#[async_trait::async_trait]
+impl command::Handler for Handler {
+    async fn handle_pools(&self) -> command::Result<response::Pools> {
+        Ok(response::Pools {
+            list: vec![self.get_pool_status()],
+        })
+    }
+
+    async fn handle_devs(&self) -> command::Result<response::Devs> {
+        Ok(response::Devs {
+            list: self
+                .workers()
+                .into_iter()
+                .enumerate()
+                .map(|(idx, worker)| Self::get_asc_status(idx, worker))
+                .collect(),
+        })
+    }
+
+    async fn handle_edevs(&self) -> command::Result<response::Devs> {
+        self.handle_devs().await
+    }
+
+    async fn handle_summary(&self) -> command::Result<response::Summary> {
+        let now = time::Instant::now();
+        let elapsed = self.registry.elapsed(now);
+        let totals = self.registry.totals();
+        let workers = self.registry.workers(now);
+        let mhs = workers
+            .iter()
+            .map(|worker| worker.stats.hashrate)
+            .sum::<f64>()
+            / 1e6;
+        let total_mega_hashes = difficulty_to_hashes(totals.accepted_difficulty) / 1e6;
+        let rejected_ratio = percent(
+            totals.rejected_count as f64,
+            (totals.accepted_count + totals.rejected_count) as f64,
+        );
+        let last_getwork = unix_time(
+            Self::last_share_worker(&workers).and_then(|worker| worker.stats.last_share_time),
+        );
+
+        Ok(response::Summary {
+            elapsed: elapsed.as_secs(),
+            mhs_av: average_mhs(totals.accepted_difficulty, elapsed),
+            mhs_5s: mhs,
+            mhs_1m: mhs,
+            mhs_5m: mhs,
+            mhs_15m: mhs,
+            mhs_24h: mhs,
+            found_blocks: 0,
+            getworks: totals.job_count,
+            accepted: totals.accepted_count,
+            rejected: totals.rejected_count,
+            hardware_errors: 0,
+            utility: per_minute(totals.accepted_count, elapsed),
+            discarded: 0,
+            stale: 0,
+            get_failures: 0,
+            local_work: 0,
+            remote_failures: 0,
+            network_blocks: 0,
+            total_mega_hashes,
+            work_utility: if elapsed.as_secs() != 0 {
+                totals.accepted_difficulty / elapsed.as_secs_f64() * 60.0
+            } else {
+                0.0
+            },
+            difficulty_accepted: totals.accepted_difficulty,
+            difficulty_rejected: totals.rejected_difficulty,
+            difficulty_stale: 0.0,
+            best_share: 0,
+            device_hardware_ratio: 0.0,
+            device_rejected_ratio: percent(
+                totals.rejected_difficulty,
+                totals.accepted_difficulty + totals.rejected_difficulty,
+            ),
+            pool_rejected_ratio: rejected_ratio,
+            pool_stale_ratio: 0.0,
+            last_getwork,
+        })
+    }
+
+    async fn handle_config(&self) -> command::Result<response::Config> {
+        Ok(response::Config {
+            asc_count: self.workers().len() as i32,
+            pga_count: 0,
+            pool_count: 1,
+            strategy: response::MultipoolStrategy::Failover,
+            log_interval: 0,
+            device_code: String::new(),
+            os: std::env::consts::OS.to_string(),
+            hotplug: "None".to_string(),
+        })
+    }
+
+    async fn handle_enable_pool(
+        &self,
+        _parameter: Option<&json::Value>,
+    ) -> command::Result<response::EnablePool> {
+        Self::access_denied("enablepool")
+    }
+
+    async fn handle_disable_pool(
+        &self,
+        _parameter: Option<&json::Value>,
+    ) -> command::Result<response::DisablePool> {
+        Self::access_denied("disablepool")
+    }
+
+    async fn handle_add_pool(
+        &self,
+        _parameter: Option<&json::Value>,
+    ) -> command::Result<response::AddPool> {
+        Self::access_denied("addpool")
+    }
+
+    async fn handle_remove_pool(
+        &self,
+        _parameter: Option<&json::Value>,
+    ) -> command::Result<response::RemovePool> {
+        Self::access_denied("removepool")
+    }
+
+    async fn handle_switch_pool(
+        &self,
+        _parameter: Option<&json::Value>,
+    ) -> command::Result<response::SwitchPool> {
+        Self::access_denied("switchpool")
+    }
+
+    async fn handle_stats(&self) -> command::Result<response::Stats> {
+        self.handle_estats().await
+    }
+
+    async fn handle_estats(&self) -> command::Result<response::Stats> {
+        Ok(response::Stats {
+            asc_stats: self
+                .workers()
+                .iter()
+                .enumerate()
+                .map(|(idx, worker)| Self::get_asc_stats(idx, worker))
+                .collect(),
+            pool_stats: vec![],
+        })
+    }
+
+    async fn handle_coin(&self) -> command::Result<response::Coin> {
+        Ok(response::Coin {
+            hash_method: "sha256".to_string(),
+            current_block_time: 0.0,
+            current_block_hash: "".to_string(),
+            lp: false,
+            network_difficulty: 0.0,
+        })
+    }
+
+    async fn handle_asc_count(&self) -> command::Result<response::AscCount> {
+        Ok(response::AscCount {
+            count: self.workers().len() as i32,
+        })
+    }
+
+    async fn handle_asc(&self, parameter: Option<&json::Value>) -> command::Result<response::Asc> {
+        let idx = parameter
+            .expect("BUG: missing ASC parameter")
+            .to_i32()
+            .expect("BUG: invalid ASC parameter type");
+
+        let mut workers = self.workers();
+        let worker_count = workers.len();
+        if idx < 0 || idx as usize >= worker_count {
+            return Err(response::ErrorCode::InvalidAscId(idx, worker_count as i32 - 1).into());
+        }
+        Ok(Self::get_asc_status(
+            idx as usize,
+            workers.swap_remove(idx as usize),
+        ))
+    }
+
+    async fn handle_lcd(&self) -> command::Result<response::Lcd> {
+        let summary = self.handle_summary().await?;
+        let workers = self.workers();
+        let last_share_worker = Self::last_share_worker(&workers);
+
+        Ok(response::Lcd {
+            elapsed: summary.elapsed,
+            ghs_av: summary.mhs_av / 1e3,
+            ghs_5m: summary.mhs_5m / 1e3,
+            ghs_5s: summary.mhs_5s / 1e3,
+            temperature: 0.0,
+            last_share_difficulty: last_share_worker
+                .map_or(0.0, |worker| worker.stats.last_share_difficulty),
+            last_share_time: summary.last_getwork,
+            best_share: 0,
+            last_valid_work: summary.last_getwork,
+            found_blocks: 0,
+            current_pool: self.pool_url(),
+            user: last_share_worker
+                .and_then(|worker| worker.stats.user.clone())
+                .unwrap_or_default(),
+        })
+    }
+}
+
+/// Runs the API server on `listen_addr`, commands changing the configuration are denied
+pub async fn run(
+    registry: Arc<stats::Registry>,
+    upstream_address: Address,
+    listen_addr: SocketAddr,
+) -> Result<()> {
+    let handler = Handler::new(registry, upstream_address);
+    let command_receiver = command::Receiver::new(
+        handler,
+        SIGNATURE.to_string(),
+        env!("CARGO_PKG_VERSION").to_string(),
+        None,
+    )
+    .with_access_control(command::AccessControl::new(command::Privilege::ReadOnly));
+
+    ii_cgminer_api::run(command_receiver, listen_addr).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use command::Handler as _;
+    use ii_async_compat::tokio;
+
+    fn handler() -> (Handler, Arc<stats::Registry>) {
+        let registry = Arc::new(stats::Registry::default());
+        let handler = Handler::new(
+            registry.clone(),
+            Address("stratum.example.com".to_string(), 3333),
+        );
+        (handler, registry)
+    }
+
+    #[tokio::test]
+    async fn test_worker_mapping() {
+        let (handler, registry) = handler();
+        let addr: SocketAddr = "10.0.0.1:40000".parse().unwrap();
+
+        let (_, pending) = registry.register(addr);
+        let (id, worker) = registry.register(addr);
+        pending.account_job();
+        worker.set_user("farm.worker1".to_string());
+        worker.set_difficulty(1024.0);
+        worker.account_job();
+        worker.account_accepted(1024.0);
+        worker.account_accepted(1024.0);
+        worker.account_rejected(1024.0);
+
+        let devs = handler.handle_devs().await.ok().unwrap().list;
+        assert_eq!(devs.len(), 2);
+        assert_eq!(devs[0].name, addr.to_string());
+        assert_eq!(devs[0].status, response::AscStatus::Initialising);
+        assert_eq!(devs[1].idx, 1);
+        assert_eq!(devs[1].id, id as i32);
+        assert_eq!(devs[1].name, "farm.worker1");
+        assert_eq!(devs[1].status, response::AscStatus::Alive);
+        assert_eq!(devs[1].accepted, 2);
+        assert_eq!(devs[1].rejected, 1);
+        assert_eq!(devs[1].difficulty_accepted, 2048.0);
+        assert_eq!(devs[1].last_share_difficulty, 1024.0);
+        assert_ne!(devs[1].last_share_time, 0);
+        assert!(devs[1].mhs_5m > 0.0);
+
+        let asc = handler
+            .handle_asc(Some(&json::json!(1)))
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!(asc, devs[1]);
+        assert!(handler.handle_asc(Some(&json::json!(2))).await.is_err());
+
+        let pools = handler.handle_pools().await.ok().unwrap().list;
+        assert_eq!(pools.len(), 1);
+        assert_eq!(pools[0].url, "stratum+tcp://stratum.example.com:3333");
+        assert_eq!(pools[0].user, "farm.worker1");
+        assert_eq!(pools[0].getworks, 2);
+        assert_eq!(pools[0].accepted, 2);
+        assert_eq!(pools[0].rejected, 1);
+        assert_eq!(pools[0].stratum_difficulty, 1024.0);
+
+        // closed connections are still accounted in the summary
+        registry.unregister(id);
+        assert_eq!(handler.handle_devs().await.ok().unwrap().list.len(), 1);
+        let summary = handler.handle_summary().await.ok().unwrap();
+        assert_eq!(summary.getworks, 2);
+        assert_eq!(summary.accepted, 2);
+        assert_eq!(summary.rejected, 1);
+        assert_eq!(summary.difficulty_accepted, 2048.0);
+        assert_eq!(summary.mhs_5m, 0.0);
+    }
+}
@@ -21,124 +21,102 @@
 // contact us at opensource@braiins.com.
 
 //! Module that represents custom stratum proxy errors
+//!
+//! Errors coming from the stratum library keep their stratum `ErrorKind` so that the proxy can
+//! still match on them and their underlying error is preserved as `source()`.
 
-use failure::{Backtrace, Context, Fail};
 use std;
-use std::fmt::{self, Display};
 use std::io;
 
 use ii_async_compat::prelude::*;
+use thiserror::Error;
 
-#[derive(Debug)]
-pub struct Error {
-    inner: Context<ErrorKind>,
-}
-
-#[derive(Clone, Eq, PartialEq, Debug, Fail)]
+#[derive(Error, Clone, Eq, PartialEq, Debug)]
 pub enum ErrorKind {
     /// General error used for more specific .
-    #[fail(display = "General error: {}", _0)]
+    #[error("General error: {0}")]
     General(String),
 
     /// General error used for more specific .
-    #[fail(display = "Stratum error: {}", _0)]
+    #[error("Stratum error: {0}")]
     Stratum(ii_stratum::error::ErrorKind),
 
     /// Bitcoin Hashes error.
-    #[fail(display = "Bitcoin Hashes error: {}", _0)]
+    #[error("Bitcoin Hashes error: {0}")]
     BitcoinHashes(String),
 
     /// Input/Output error.
-    #[fail(display = "I/O error: {}", _0)]
+    #[error("I/O error: {0}")]
     Io(String),
 
     /// CLI usage / configuration error
-    #[fail(display = "Could not parse `{}` as IP address", _0)]
+    #[error("Could not parse `{0}` as IP address")]
     BadIp(String),
-}
-
-/// Implement Fail trait instead of use Derive to get more control over custom type.
-/// The main advantage is customization of Context type which allows conversion of
-/// any error types to this custom error with general error kind by calling context
-/// method on any result type.
-impl Fail for Error {
-    fn cause(&self) -> Option<&dyn Fail> {
-        self.inner.cause()
-    }
 
-    fn backtrace(&self) -> Option<&Backtrace> {
-        self.inner.backtrace()
-    }
+    /// Invalid configuration file, environment or command line.
+    #[error("Configuration error: {0}")]
+    Config(ii_config::ErrorKind),
 }
 
-impl Display for Error {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        Display::fmt(&self.inner, f)
-    }
-}
+ii_error::error_type!(ErrorKind);
 
-impl Error {
-    pub fn kind(&self) -> ErrorKind {
-        self.inner.get_context().clone()
+/// Context message provided by `ResultExt::context()`
+impl From<&str> for ErrorKind {
+    fn from(info: &str) -> Self {
+        ErrorKind::General(info.to_string())
     }
+}
 
-    pub fn into_inner(self) -> Context<ErrorKind> {
-        self.inner
+impl From<String> for ErrorKind {
+    fn from(info: String) -> Self {
+        ErrorKind::General(info)
     }
 }
 
-/// Convenience conversion to Error from ErrorKind that carries the context
-impl From<ErrorKind> for Error {
-    fn from(kind: ErrorKind) -> Self {
+impl From<ii_stratum::error::Error> for Error {
+    fn from(e: ii_stratum::error::Error) -> Self {
+        let (kind, source) = e.into_parts();
         Self {
-            inner: Context::new(kind),
+            kind: ErrorKind::Stratum(kind),
+            source,
         }
     }
 }
 
-impl From<ii_stratum::error::Error> for Error {
-    fn from(e: ii_stratum::error::Error) -> Self {
+impl From<ii_config::Error> for Error {
+    fn from(e: ii_config::Error) -> Self {
+        let (kind, source) = e.into_parts();
         Self {
-            inner: e.into_inner().map(|kind| ErrorKind::Stratum(kind)),
+            kind: ErrorKind::Config(kind),
+            source,
         }
     }
 }
 
 impl<T> From<futures::channel::mpsc::TrySendError<T>> for Error
 where
-    T: failure::Fail,
+    T: Send + Sync + 'static,
 {
     fn from(e: futures::channel::mpsc::TrySendError<T>) -> Self {
-        let msg = e.to_string();
-        Self {
-            inner: e.context(ErrorKind::Io(msg)),
-        }
+        Self::with_source(ErrorKind::Io(e.to_string()), e)
     }
 }
 
 impl From<io::Error> for Error {
     fn from(e: io::Error) -> Self {
-        let msg = e.to_string();
-        Self {
-            inner: e.context(ErrorKind::Io(msg)),
-        }
+        Self::with_source(ErrorKind::Io(e.to_string()), e)
     }
 }
 
 impl From<tokio::time::Elapsed> for Error {
     fn from(e: tokio::time::Elapsed) -> Self {
-        let msg = e.to_string();
-        Self {
-            inner: e.context(ErrorKind::Io(msg)),
-        }
+        Self::with_source(ErrorKind::Io(e.to_string()), e)
     }
 }
 
 impl From<std::str::Utf8Error> for Error {
     fn from(e: std::str::Utf8Error) -> Self {
-        Self {
-            inner: e.context(ErrorKind::General(e.to_string())),
-        }
+        Self::with_source(ErrorKind::General(e.to_string()), e)
     }
 }
 
@@ -156,35 +134,6 @@ impl From<String> for Error {
 
 impl From<bitcoin_hashes::error::Error> for Error {
     fn from(e: bitcoin_hashes::error::Error) -> Self {
-        Self {
-            inner: e.context(ErrorKind::BitcoinHashes(e.to_string())),
-        }
-    }
-}
-impl From<Context<&str>> for Error {
-    fn from(context: Context<&str>) -> Self {
-        Self {
-            inner: context.map(|info| ErrorKind::General(info.to_string())),
-        }
-    }
-}
-
-impl From<Context<String>> for Error {
-    fn from(context: Context<String>) -> Self {
-        Self {
-            inner: context.map(|info| ErrorKind::General(info)),
-        }
+        Self::with_source(ErrorKind::BitcoinHashes(e.to_string()), e)
     }
 }
-
-impl From<Context<ErrorKind>> for Error {
-    fn from(context: Context<ErrorKind>) -> Self {
-        Self { inner: context }
-    }
-}
-
-/// A specialized `Result` type bound to [`Error`].
-pub type Result<T> = std::result::Result<T, Error>;
-
-/// Re-export failure's ResultExt for easier usage
-pub use failure::ResultExt;
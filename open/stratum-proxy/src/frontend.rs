@@ -20,28 +20,46 @@
 // of such proprietary license or if you have any other questions, please
 // contact us at opensource@braiins.com.
 
+use serde::Deserialize;
 use std::convert::TryFrom;
-use std::path::PathBuf;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
 use structopt::StructOpt;
 use tokio::{fs::File, io::AsyncReadExt};
 
 use ii_async_compat::tokio;
+use ii_config::{Invalid, Loader, Validate};
 use ii_stratum::v2;
 use ii_wire::Address;
 
 use crate::error::{Result, ResultExt};
 
+/// Prefix of environment variables overriding the configuration file, the upstream is set e.g. by
+/// `STRATUM_PROXY__UPSTREAM_ADDRESS`
+pub const ENV_PREFIX: &str = "STRATUM_PROXY";
+
+/// Default address to listen on for incoming Stratum V2 connections
+const DEFAULT_LISTEN_ADDRESS: (&str, u16) = ("localhost", 3336);
+
+/// Command line options, they override values from the configuration file and the environment
 #[derive(StructOpt, Debug)]
 #[structopt(name = "stratum-proxy", about = "Stratum V2->V1 translating proxy.")]
 pub struct Args {
+    /// Configuration file
+    #[structopt(
+        long,
+        parse(from_os_str),
+        help = "TOML file with the same keys as fields of the proxy configuration"
+    )]
+    pub config: Option<PathBuf>,
+
     /// Listen address
     #[structopt(
         short = "l",
         long = "listen",
-        default_value = "localhost:3336",
-        help = "Address to listen on for incoming Stratum V2 connections"
+        help = "Address to listen on for incoming Stratum V2 connections [default: localhost:3336]"
     )]
-    pub listen_address: Address,
+    pub listen_address: Option<Address>,
 
     /// Remote V1 endpoint where to connect to
     #[structopt(
@@ -50,7 +68,7 @@ pub struct Args {
         name = "HOSTNAME:PORT",
         help = "Address of the upstream Stratum V1 server that the proxy connects to"
     )]
-    pub upstream_address: Address,
+    pub upstream_address: Option<Address>,
 
     #[structopt(
         long,
@@ -59,15 +77,98 @@ pub struct Args {
     pub insecure: bool,
 
     /// Certificate file
-    #[structopt(short = "c", long, parse(from_os_str), required_unless("insecure"))]
+    #[structopt(short = "c", long, parse(from_os_str))]
     pub certificate_file: Option<PathBuf>,
 
     /// Secret key as counter part of the public key in the configured public certificate
-    #[structopt(short = "s", long, parse(from_os_str), required_unless("insecure"))]
+    #[structopt(short = "s", long, parse(from_os_str))]
     pub secret_key_file: Option<PathBuf>,
+
+    /// CGMiner API listen address
+    #[structopt(
+        long = "cgminer-api",
+        name = "IP:PORT",
+        help = "Address to listen on for read-only CGMiner API requests [default: disabled]"
+    )]
+    pub cgminer_api_address: Option<SocketAddr>,
+}
+
+fn path_value(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
 }
 
 impl Args {
+    /// Builds the proxy configuration from the configuration file, environment and command line
+    pub fn load_config(&self) -> Result<Config> {
+        let mut loader = Loader::new().env_prefix(ENV_PREFIX);
+        if let Some(path) = self.config.as_ref() {
+            loader = loader.file(path);
+        }
+        if let Some(address) = self.listen_address.as_ref() {
+            loader = loader.set("listen_address", address.to_string(), "--listen");
+        }
+        if let Some(address) = self.upstream_address.as_ref() {
+            loader = loader.set("upstream_address", address.to_string(), "--v1-upstream");
+        }
+        if self.insecure {
+            loader = loader.set("insecure", true, "--insecure");
+        }
+        if let Some(path) = self.certificate_file.as_ref() {
+            loader = loader.set("certificate_file", path_value(path), "--certificate-file");
+        }
+        if let Some(path) = self.secret_key_file.as_ref() {
+            loader = loader.set("secret_key_file", path_value(path), "--secret-key-file");
+        }
+        if let Some(address) = self.cgminer_api_address.as_ref() {
+            loader = loader.set("cgminer_api_address", address.to_string(), "--cgminer-api");
+        }
+        Ok(loader.load()?)
+    }
+}
+
+fn default_listen_address() -> Address {
+    Address(
+        DEFAULT_LISTEN_ADDRESS.0.to_string(),
+        DEFAULT_LISTEN_ADDRESS.1,
+    )
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default = "default_listen_address")]
+    pub listen_address: Address,
+    pub upstream_address: Address,
+    #[serde(default)]
+    pub insecure: bool,
+    pub certificate_file: Option<PathBuf>,
+    pub secret_key_file: Option<PathBuf>,
+    /// Statistics of connections are served via CGMiner API when set
+    pub cgminer_api_address: Option<SocketAddr>,
+}
+
+impl Validate for Config {
+    fn validate(&self) -> std::result::Result<(), Invalid> {
+        if self.insecure {
+            return Ok(());
+        }
+        if self.certificate_file.is_none() {
+            return Err(Invalid::new(
+                "certificate_file",
+                "certificate is required unless `insecure` is set",
+            ));
+        }
+        if self.secret_key_file.is_none() {
+            return Err(Invalid::new(
+                "secret_key_file",
+                "secret key is required unless `insecure` is set",
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Config {
     /// Optionally read certificate and secret keypair
     /// Return:
     ///  - None - when `insecure` is true
@@ -107,7 +208,8 @@ pub async fn read_from_file<T: TryFrom<String>>(
 ) -> Result<T>
 where
     T: TryFrom<String>,
-    <T as std::convert::TryFrom<std::string::String>>::Error: failure::Fail, //std::fmt::Display,
+    <T as std::convert::TryFrom<std::string::String>>::Error:
+        std::error::Error + Send + Sync + 'static,
 {
     let file_path_buf =
         file_path_buf.expect(format!("BUG: missing path {}", error_context_descr).as_str());
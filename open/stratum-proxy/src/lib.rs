@@ -27,8 +27,10 @@
 // the default recursion limit if more complex statements are used
 #![recursion_limit = "256"]
 
+pub mod cgminer;
 pub mod error;
 pub mod frontend;
 pub mod server;
+pub mod stats;
 pub mod translation;
 pub mod util;
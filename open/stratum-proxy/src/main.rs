@@ -23,42 +23,58 @@
 //! Simple proxy that translates V2 protocol from clients to V1 protocol and connects to a
 //! requested pool
 
-use std::cell::RefCell;
+use std::sync::Arc;
+use std::time::Duration;
 use structopt::StructOpt;
 
-use ctrlc;
-
 use ii_async_compat::tokio;
+use ii_logging::macros::*;
 use ii_stratum_proxy::{
+    cgminer,
     error::{Result, ResultExt},
     frontend::Args,
-    server,
+    server, stats,
 };
 
+/// Timeout for termination of the proxy server
+const HALT_TIMEOUT: Duration = Duration::from_secs(5);
+
 #[tokio::main]
 async fn main() -> Result<()> {
     ii_async_compat::setup_panic_handling();
     let _log_guard =
         ii_logging::setup_for_app(ii_logging::LoggingConfig::ASYNC_LOGGER_DRAIN_CHANNEL_SIZE);
 
-    let args = Args::from_args();
+    let config = Args::from_args().load_config()?;
+
+    let certificate_secret_key_pair = config.read_certificate_secret_key_pair().await?;
 
-    let certificate_secret_key_pair = args.read_certificate_secret_key_pair().await?;
+    let registry = Arc::new(stats::Registry::default());
+    if let Some(api_address) = config.cgminer_api_address {
+        let api = cgminer::run(
+            registry.clone(),
+            config.upstream_address.clone(),
+            api_address,
+        );
+        tokio::spawn(async move {
+            if let Err(err) = api.await {
+                error!("CGMiner API failed: {}", err);
+            }
+        });
+    }
 
+    let (halt_handle, halt_receiver) = ii_stop::make_pair(HALT_TIMEOUT);
     let server = server::ProxyServer::listen(
-        args.listen_address,
-        args.upstream_address,
-        server::handle_connection,
+        config.listen_address,
+        config.upstream_address,
+        server::handle_connection_with_stats(registry),
         certificate_secret_key_pair,
     )
-    .context("Cannot bind the server")?;
+    .context("Cannot bind the server")?
+    .with_halt(halt_receiver.register_client("proxy server".into()).await);
 
-    let quit = RefCell::new(server.quit_channel());
-    ctrlc::set_handler(move || {
-        // Received SIGINT, tell the server task to shut down:
-        let _ = quit.try_borrow_mut().map(|mut quit| quit.try_send(()));
-    })
-    .expect("Could not set SIGINT handler");
+    // Received `SIGINT`, `SIGTERM` or `SIGHUP` tells the server task to shut down
+    halt_handle.hook_termination_signals();
 
     server.run().await;
     Ok(())
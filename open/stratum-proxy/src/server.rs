@@ -26,7 +26,7 @@ use std::sync::Arc;
 use std::time;
 
 use futures::channel::mpsc;
-use futures::future::{self, Either};
+use futures::future::{self, BoxFuture, Either};
 use tokio::net::TcpStream;
 
 use ii_async_compat::prelude::*;
@@ -37,6 +37,7 @@ use ii_stratum::v2;
 use ii_wire::{Address, Client, Connection, Server};
 
 use crate::error::{ErrorKind, Result, ResultExt};
+use crate::stats;
 use crate::translation::V2ToV1Translation;
 
 /// Represents a single protocol translation session (one V2 client talking to one V1 server)
@@ -69,13 +70,15 @@ impl ConnTranslation {
         v2_peer_addr: SocketAddr,
         v1_conn: v1::Framed,
         v1_peer_addr: SocketAddr,
+        stats: Arc<stats::Worker>,
     ) -> Self {
         let (v1_translation_tx, v1_translation_rx) =
             mpsc::channel(Self::MAX_TRANSLATION_CHANNEL_SIZE);
         let (v2_translation_tx, v2_translation_rx) =
             mpsc::channel(Self::MAX_TRANSLATION_CHANNEL_SIZE);
         let translation =
-            V2ToV1Translation::new(v1_translation_tx, v2_translation_tx, Default::default());
+            V2ToV1Translation::new(v1_translation_tx, v2_translation_tx, Default::default())
+                .with_stats(stats);
 
         Self {
             translation,
@@ -228,11 +231,39 @@ pub async fn handle_connection(
     v1_conn: v1::Framed,
     v1_peer_addr: SocketAddr,
 ) -> Result<()> {
-    let translation = ConnTranslation::new(v2_conn, v2_peer_addr, v1_conn, v1_peer_addr);
+    let translation = ConnTranslation::new(
+        v2_conn,
+        v2_peer_addr,
+        v1_conn,
+        v1_peer_addr,
+        Default::default(),
+    );
 
     translation.run().await
 }
 
+/// Builds a connection handler for `ProxyServer` that registers statistics of each connection in
+/// `registry` for as long as the connection lasts
+pub fn handle_connection_with_stats(
+    registry: Arc<stats::Registry>,
+) -> impl Fn(v2::Framed, SocketAddr, v1::Framed, SocketAddr) -> BoxFuture<'static, Result<()>>
+       + Send
+       + Sync
+       + 'static {
+    move |v2_conn, v2_peer_addr, v1_conn, v1_peer_addr| {
+        let registry = registry.clone();
+        async move {
+            let (id, worker) = registry.register(v2_peer_addr);
+            let translation =
+                ConnTranslation::new(v2_conn, v2_peer_addr, v1_conn, v1_peer_addr, worker);
+            let result = translation.run().await;
+            registry.unregister(id);
+            result
+        }
+        .boxed()
+    }
+}
+
 /// Security context is held by the server and provided to each (noise secured) connection so
 /// that it can successfully perform the noise handshake and authenticate itself to the client
 /// NOTE: this struct doesn't intentionally derive Debug to prevent leakage of the secure key
@@ -255,10 +286,10 @@ impl SecurityContext {
             .build_noise_message()
             .serialize_to_bytes_mut()?
             .freeze();
-        // TODO secret key validation is currently not possible
-        //let public_key = certificate.validate_secret_key(&secret_key)?;
+        let secret_key = secret_key.into_inner();
+        certificate.validate_secret_key(&secret_key)?;
         let static_key_pair = v2::noise::StaticKeypair {
-            private: secret_key.into_inner(),
+            private: secret_key,
             public: certificate.public_key.into_inner(),
         };
 
@@ -416,6 +447,17 @@ where
         })
     }
 
+    /// Terminate the server task when termination context of `notify_receiver` is halted
+    pub fn with_halt(mut self, notify_receiver: ii_stop::NotifyReceiver) -> Self {
+        self.server = self.server.with_halt(notify_receiver);
+        self
+    }
+
+    /// Local address of the listening socket, it resolves the port when listening on port 0
+    pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.server.local_addr()
+    }
+
     /// Obtain the quit channel transmit end,
     /// which can be used to terminate the server task.
     pub fn quit_channel(&self) -> mpsc::Sender<()> {
@@ -0,0 +1,304 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Statistics of translated connections shared between the translation and monitoring services
+//!
+//! Each connection accounts its shares into a `Worker` which is registered in a `Registry` for
+//! the lifetime of the connection. Totals of closed connections are retained by the registry so
+//! that the aggregated counters do not drop when a worker reconnects.
+
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time;
+
+use ii_stats::ShareMeter;
+
+/// Window for computation of hashrate and reject rate from submitted shares
+pub const SHARE_METER_WINDOW: time::Duration = time::Duration::from_secs(5 * 60);
+
+#[derive(Debug)]
+struct WorkerInner {
+    user: Option<String>,
+    /// Difficulty from the latest mining.set_difficulty message
+    difficulty: f64,
+    job_count: u64,
+    last_share_time: Option<time::SystemTime>,
+    last_share_difficulty: f64,
+    share_meter: ShareMeter,
+}
+
+/// Statistics of a single translated connection (one V2 worker)
+#[derive(Debug)]
+pub struct Worker {
+    start_time: time::Instant,
+    inner: Mutex<WorkerInner>,
+}
+
+impl Default for Worker {
+    fn default() -> Self {
+        Self {
+            start_time: time::Instant::now(),
+            inner: Mutex::new(WorkerInner {
+                user: None,
+                difficulty: 0.0,
+                job_count: 0,
+                last_share_time: None,
+                last_share_difficulty: 0.0,
+                share_meter: ShareMeter::new(SHARE_METER_WINDOW),
+            }),
+        }
+    }
+}
+
+impl Worker {
+    fn lock(&self) -> std::sync::MutexGuard<'_, WorkerInner> {
+        self.inner.lock().expect("BUG: worker statistics poisoned")
+    }
+
+    pub fn set_user(&self, user: String) {
+        self.lock().user.replace(user);
+    }
+
+    pub fn set_difficulty(&self, difficulty: f64) {
+        self.lock().difficulty = difficulty;
+    }
+
+    /// Accounts a job sent to the worker
+    pub fn account_job(&self) {
+        self.lock().job_count += 1;
+    }
+
+    pub fn account_accepted(&self, difficulty: f64) {
+        let mut inner = self.lock();
+        inner
+            .share_meter
+            .account_accepted(difficulty, time::Instant::now());
+        inner.last_share_time = Some(time::SystemTime::now());
+        inner.last_share_difficulty = difficulty;
+    }
+
+    pub fn account_rejected(&self, difficulty: f64) {
+        let mut inner = self.lock();
+        inner
+            .share_meter
+            .account_rejected(difficulty, time::Instant::now());
+        inner.last_share_time = Some(time::SystemTime::now());
+        inner.last_share_difficulty = difficulty;
+    }
+
+    /// Current state of the statistics, rates are computed within `SHARE_METER_WINDOW` ending at
+    /// `now`
+    pub fn snapshot(&self, now: time::Instant) -> WorkerSnapshot {
+        let inner = self.lock();
+        WorkerSnapshot {
+            user: inner.user.clone(),
+            elapsed: now.saturating_duration_since(self.start_time),
+            difficulty: inner.difficulty,
+            last_share_time: inner.last_share_time,
+            last_share_difficulty: inner.last_share_difficulty,
+            hashrate: inner.share_meter.hashrate(now),
+            reject_ratio: inner.share_meter.reject_ratio(now),
+            totals: Totals {
+                job_count: inner.job_count,
+                accepted_count: inner.share_meter.accepted_count(),
+                rejected_count: inner.share_meter.rejected_count(),
+                accepted_difficulty: inner.share_meter.accepted_difficulty(),
+                rejected_difficulty: inner.share_meter.rejected_difficulty(),
+            },
+        }
+    }
+}
+
+/// Counters which are summed over all workers
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Totals {
+    pub job_count: u64,
+    pub accepted_count: u64,
+    pub rejected_count: u64,
+    pub accepted_difficulty: f64,
+    pub rejected_difficulty: f64,
+}
+
+impl Totals {
+    fn add(&mut self, other: &Totals) {
+        self.job_count += other.job_count;
+        self.accepted_count += other.accepted_count;
+        self.rejected_count += other.rejected_count;
+        self.accepted_difficulty += other.accepted_difficulty;
+        self.rejected_difficulty += other.rejected_difficulty;
+    }
+}
+
+/// Point-in-time copy of `Worker` statistics
+#[derive(Debug, Clone)]
+pub struct WorkerSnapshot {
+    /// User authorized on the channel, `None` until the channel has been open
+    pub user: Option<String>,
+    /// Time since the connection has been established
+    pub elapsed: time::Duration,
+    pub difficulty: f64,
+    pub last_share_time: Option<time::SystemTime>,
+    pub last_share_difficulty: f64,
+    /// Hashes per second corresponding to shares accepted within `SHARE_METER_WINDOW`
+    pub hashrate: f64,
+    /// See `ShareMeter::reject_ratio()`
+    pub reject_ratio: Option<f64>,
+    pub totals: Totals,
+}
+
+/// Registered worker together with the connection details
+#[derive(Debug, Clone)]
+pub struct WorkerEntry {
+    /// Unique identifier of the connection within the registry
+    pub id: usize,
+    /// Address of the V2 peer
+    pub peer_addr: SocketAddr,
+    pub stats: WorkerSnapshot,
+}
+
+#[derive(Debug, Default)]
+struct RegistryInner {
+    next_id: usize,
+    workers: Vec<(usize, SocketAddr, Arc<Worker>)>,
+    /// Totals of workers which have already been unregistered
+    closed: Totals,
+}
+
+/// Statistics of all connections handled by the proxy
+#[derive(Debug)]
+pub struct Registry {
+    start_time: time::Instant,
+    inner: Mutex<RegistryInner>,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Self {
+            start_time: time::Instant::now(),
+            inner: Default::default(),
+        }
+    }
+}
+
+impl Registry {
+    fn lock(&self) -> std::sync::MutexGuard<'_, RegistryInner> {
+        self.inner
+            .lock()
+            .expect("BUG: statistics registry poisoned")
+    }
+
+    /// Time since the registry has been created
+    pub fn elapsed(&self, now: time::Instant) -> time::Duration {
+        now.saturating_duration_since(self.start_time)
+    }
+
+    /// Registers statistics of a new connection from `peer_addr`, the returned identifier is used
+    /// for unregistering it
+    pub fn register(&self, peer_addr: SocketAddr) -> (usize, Arc<Worker>) {
+        let mut inner = self.lock();
+        let id = inner.next_id;
+        inner.next_id += 1;
+        let worker = Arc::new(Worker::default());
+        inner.workers.push((id, peer_addr, worker.clone()));
+        (id, worker)
+    }
+
+    /// Removes a closed connection, its counters are retained in `totals()`
+    pub fn unregister(&self, id: usize) {
+        let mut inner = self.lock();
+        if let Some(idx) = inner
+            .workers
+            .iter()
+            .position(|(worker_id, ..)| *worker_id == id)
+        {
+            let (_, _, worker) = inner.workers.remove(idx);
+            let totals = worker.snapshot(time::Instant::now()).totals;
+            inner.closed.add(&totals);
+        }
+    }
+
+    /// Snapshots of all open connections in the order of registration
+    pub fn workers(&self, now: time::Instant) -> Vec<WorkerEntry> {
+        self.lock()
+            .workers
+            .iter()
+            .map(|(id, peer_addr, worker)| WorkerEntry {
+                id: *id,
+                peer_addr: *peer_addr,
+                stats: worker.snapshot(now),
+            })
+            .collect()
+    }
+
+    /// Counters summed over open and closed connections
+    pub fn totals(&self) -> Totals {
+        let inner = self.lock();
+        let now = time::Instant::now();
+        let mut totals = inner.closed.clone();
+        for (_, _, worker) in inner.workers.iter() {
+            totals.add(&worker.snapshot(now).totals);
+        }
+        totals
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_registry() {
+        let registry = Registry::default();
+        let addr: SocketAddr = "127.0.0.1:3336".parse().unwrap();
+
+        let (id1, worker1) = registry.register(addr);
+        let (id2, worker2) = registry.register(addr);
+        assert_ne!(id1, id2);
+        worker1.set_user("user.1".to_string());
+        worker1.account_job();
+        worker1.account_accepted(512.0);
+        worker2.account_accepted(1024.0);
+        worker2.account_rejected(1024.0);
+
+        let workers = registry.workers(time::Instant::now());
+        assert_eq!(workers.len(), 2);
+        assert_eq!(workers[0].id, id1);
+        assert_eq!(workers[0].stats.user, Some("user.1".to_string()));
+        assert_eq!(workers[0].stats.last_share_difficulty, 512.0);
+        assert!(workers[0].stats.hashrate > 0.0);
+        assert_eq!(workers[1].stats.reject_ratio, Some(0.5));
+
+        // counters of closed connections are retained
+        registry.unregister(id2);
+        assert_eq!(registry.workers(time::Instant::now()).len(), 1);
+        assert_eq!(
+            registry.totals(),
+            Totals {
+                job_count: 1,
+                accepted_count: 2,
+                rejected_count: 1,
+                accepted_difficulty: 1536.0,
+                rejected_difficulty: 1024.0,
+            }
+        );
+    }
+}
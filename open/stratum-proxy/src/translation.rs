@@ -26,6 +26,8 @@ use std::convert::TryFrom;
 use std::convert::TryInto;
 use std::fmt;
 use std::mem::size_of;
+use std::sync::Arc;
+use std::time;
 
 use ii_async_compat::{bytes, futures};
 
@@ -45,8 +47,11 @@ use ii_stratum::v2::{
 use ii_logging::macros::*;
 
 use crate::error::{Error, Result, ResultExt};
+use crate::stats;
 use crate::util;
 
+#[cfg(test)]
+mod fuzz;
 #[cfg(test)]
 mod test;
 
@@ -170,6 +175,10 @@ pub struct V2ToV1Translation {
     v2_job_id: SeqId,
     /// Translates V2 job ID to V1 job ID
     v2_to_v1_job_map: JobMap,
+    /// Difficulty from the latest mining.set_difficulty message
+    v1_difficulty: f64,
+    /// Accepted and rejected shares and other statistics of the channel
+    stats: Arc<stats::Worker>,
     /// Options for translation
     options: V2ToV1TranslationOptions,
 }
@@ -212,10 +221,19 @@ impl V2ToV1Translation {
             v2_req_id: SeqId::new(),
             v2_job_id: SeqId::new(),
             v2_to_v1_job_map: JobMap::default(),
+            v1_difficulty: 0.0,
+            stats: Default::default(),
             options,
         }
     }
 
+    /// Accounts statistics of the channel into `stats` (e.g. a worker registered in
+    /// `stats::Registry`) instead of private statistics
+    pub fn with_stats(mut self, stats: Arc<stats::Worker>) -> Self {
+        self.stats = stats;
+        self
+    }
+
     /// Builds a V1 request from V1 method and assigns a unique identifier to it
     fn v1_method_into_message<M, E>(
         &mut self,
@@ -286,6 +304,22 @@ impl V2ToV1Translation {
         }
     }
 
+    /// Converts V1 pool difficulty to target. Difficulty below 1 (used by test pools) results in
+    /// a target above the difficulty 1 target, it saturates at the maximal target.
+    fn difficulty_to_target(difficulty: f32) -> uint::U256 {
+        if difficulty >= 1.0 {
+            Self::DIFF1_TARGET / (difficulty as u32)
+        } else {
+            let (target, overflow) = Self::DIFF1_TARGET
+                .overflowing_mul(uint::U256::from((1.0 / difficulty).round() as u64));
+            if overflow {
+                uint::U256::max_value()
+            } else {
+                target
+            }
+        }
+    }
+
     /// Send new target
     /// TODO extend the translation unit test accordingly
     fn send_set_target(&mut self) -> Result<()> {
@@ -358,8 +392,7 @@ impl V2ToV1Translation {
         // Extract version mask and verify it matches the maximum possible value
         let proposed_version_mask: v1::messages::VersionMask =
             serde_json::from_value(payload.0["version-rolling.mask"].clone())
-                .context("Failed to parse version-rolling mask")
-                .map_err(|e| ii_stratum::error::Error::from(e))?;
+                .context("Failed to parse version-rolling mask")?;
 
         trace!(
             "Evaluating: version-rolling state == {:?} && mask=={:x?}",
@@ -580,7 +613,9 @@ impl V2ToV1Translation {
                         new_submits_accepted_count: 1,
                         new_shares_sum: 1, // TODO is this really 1?
                     };
+                    self.stats.account_accepted(self.v1_difficulty);
                     self.log_session_details("Share accepted");
+                    self.log_share_stats();
                     util::submit_message(&mut self.v2_tx, success_msg)
                 } else {
                     // TODO use reject_shares() method once we can track the original payload message
@@ -594,6 +629,8 @@ impl V2ToV1Translation {
                             .expect("BUG: incorrect error message"),
                     };
                     info!("Share rejected for {}", v2_channel_details.user.to_string());
+                    self.stats.account_rejected(self.v1_difficulty);
+                    self.log_share_stats();
                     util::submit_message(&mut self.v2_tx, err_msg)
                 }
             })
@@ -623,6 +660,8 @@ impl V2ToV1Translation {
                 .try_into()
                 .expect("BUG: wrong error code string"),
         };
+        self.stats.account_rejected(self.v1_difficulty);
+        self.log_share_stats();
 
         util::submit_message(&mut self.v2_tx, err_msg)
     }
@@ -803,6 +842,7 @@ impl V2ToV1Translation {
         }
 
         util::submit_message(&mut self.v2_tx, v2_job)?;
+        self.stats.account_job();
 
         if let Some(set_new_prev_hash) = maybe_set_new_prev_hash {
             util::submit_message(&mut self.v2_tx, set_new_prev_hash)?
@@ -840,6 +880,19 @@ impl V2ToV1Translation {
         .ok();
     }
 
+    /// Shares are accounted with the current difficulty when the result arrives, which may differ
+    /// from the difficulty of the submitted job right after a difficulty change
+    fn log_share_stats(&self) {
+        let stats = self.stats.snapshot(time::Instant::now());
+        debug!(
+            "Shares accepted: {} rejected: {} hashrate: {:.2} TH/s reject ratio: {:.2}%",
+            stats.totals.accepted_count,
+            stats.totals.rejected_count,
+            stats.hashrate / 1e12,
+            stats.reject_ratio.unwrap_or_default() * 100.0,
+        );
+    }
+
     fn log_session_details(&self, msg: &str) {
         let v2_channel_details = self
             .v2_channel_details
@@ -902,8 +955,9 @@ impl v1::Handler for V2ToV1Translation {
             self.state,
             payload,
         );
-        let diff = payload.value() as u32;
-        self.v2_target = Some(Self::DIFF1_TARGET / diff);
+        self.v1_difficulty = payload.value() as f64;
+        self.stats.set_difficulty(self.v1_difficulty);
+        self.v2_target = Some(Self::difficulty_to_target(payload.value()));
         if self.v1_authorized && self.v1_extra_nonce1.is_some() {
             // Initial set difficulty finalizes open channel if all preconditions are met
             if self.state == V2ToV1TranslationState::OpenStandardMiningChannelPending {
@@ -1081,6 +1135,7 @@ impl v2::Handler for V2ToV1Translation {
         // Connection details are present by now
         if let Some(conn_details) = self.v2_conn_details.as_ref() {
             self.v2_channel_details = Some(payload.clone());
+            self.stats.set_user(payload.user.to_string());
             self.state = V2ToV1TranslationState::OpenStandardMiningChannelPending;
 
             let hostname: String = conn_details
@@ -0,0 +1,1060 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Differential fuzzing of the V2->V1 translation
+//!
+//! The harness generates random but valid sequences of V2 downstream messages and runs them
+//! through the translation. A simulated V1 pool answers the V1 requests emitted by the
+//! translation in random order and sends random jobs and difficulty changes. A model of the
+//! reverse mapping translates everything produced by the translation back and compares it with
+//! the originating messages, e.g. each `mining.submit` has to map back onto the submitted V2 share
+//! and each `NewMiningJob` + `SetNewPrevHash` pair onto the V1 job it has been built from.
+//!
+//! A failing run reports its seed, the run can be reproduced with `II_PROXY_FUZZ_SEED=<seed>`.
+//! The number of runs can be raised with `II_PROXY_FUZZ_ITERATIONS=<count>`.
+
+use super::*;
+
+use ii_async_compat::tokio;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::collections::HashSet;
+use std::str::FromStr;
+
+const DEFAULT_ITERATIONS: u64 = 64;
+/// Number of random steps performed with an operational channel
+const STEPS: usize = 100;
+const QUEUE_SIZE: usize = 32;
+
+/// Previous block hash, coinbase parts etc. are generated in these sizes
+const HASH_SIZE: usize = 32;
+const MAX_COIN_BASE_SIZE: usize = 100;
+const MAX_MERKLE_BRANCH_LEN: usize = 12;
+
+/// V1 request emitted by the translation reduced to its semantic content
+#[derive(Clone, PartialEq, Debug)]
+enum V1Request {
+    Configure {
+        features: Vec<String>,
+        version_mask: Option<u32>,
+    },
+    Subscribe {
+        agent_signature: Option<String>,
+        url: Option<String>,
+    },
+    ExtranonceSubscribe,
+    Authorize {
+        user: String,
+    },
+    Submit(v1::messages::Submit),
+}
+
+/// Collects V1 requests sent upstream, any other message is left out
+#[derive(Default)]
+struct V1Collector(Vec<(v1::MessageId, V1Request)>);
+
+#[async_trait]
+impl v1::Handler for V1Collector {
+    async fn visit_configure(&mut self, id: &v1::MessageId, payload: &v1::messages::Configure) {
+        let version_mask = payload.1["version-rolling.mask"]
+            .as_str()
+            .and_then(|mask| u32::from_str_radix(mask, 16).ok());
+        self.0.push((
+            *id,
+            V1Request::Configure {
+                features: payload.0.clone(),
+                version_mask,
+            },
+        ));
+    }
+
+    async fn visit_subscribe(&mut self, id: &v1::MessageId, payload: &v1::messages::Subscribe) {
+        self.0.push((
+            *id,
+            V1Request::Subscribe {
+                agent_signature: payload.agent_signature().cloned(),
+                url: payload.url().cloned(),
+            },
+        ));
+    }
+
+    async fn visit_extranonce_subscribe(
+        &mut self,
+        id: &v1::MessageId,
+        _payload: &v1::messages::ExtranonceSubscribe,
+    ) {
+        self.0.push((*id, V1Request::ExtranonceSubscribe));
+    }
+
+    async fn visit_authorize(&mut self, id: &v1::MessageId, payload: &v1::messages::Authorize) {
+        self.0.push((
+            *id,
+            V1Request::Authorize {
+                user: payload.name().clone(),
+            },
+        ));
+    }
+
+    async fn visit_submit(&mut self, id: &v1::MessageId, payload: &v1::messages::Submit) {
+        self.0.push((*id, V1Request::Submit(payload.clone())));
+    }
+}
+
+/// V2 message sent downstream by the translation. Error codes are left out as they are free
+/// form text.
+#[derive(Clone, PartialEq, Debug)]
+enum V2Response {
+    SetupConnectionSuccess(v2::messages::SetupConnectionSuccess),
+    SetupConnectionError,
+    OpenStandardMiningChannelSuccess(v2::messages::OpenStandardMiningChannelSuccess),
+    OpenStandardMiningChannelError { req_id: u32 },
+    NewMiningJob(v2::messages::NewMiningJob),
+    SetNewPrevHash(v2::messages::SetNewPrevHash),
+    SetTarget(v2::messages::SetTarget),
+    SubmitSharesSuccess(v2::messages::SubmitSharesSuccess),
+    SubmitSharesError { channel_id: u32, seq_num: u32 },
+}
+
+#[derive(Default)]
+struct V2Collector(Vec<V2Response>);
+
+#[async_trait]
+impl v2::Handler for V2Collector {
+    async fn visit_setup_connection_success(
+        &mut self,
+        _header: &v2::framing::Header,
+        payload: &v2::messages::SetupConnectionSuccess,
+    ) {
+        self.0
+            .push(V2Response::SetupConnectionSuccess(payload.clone()));
+    }
+
+    async fn visit_setup_connection_error(
+        &mut self,
+        _header: &v2::framing::Header,
+        _payload: &v2::messages::SetupConnectionError,
+    ) {
+        self.0.push(V2Response::SetupConnectionError);
+    }
+
+    async fn visit_open_standard_mining_channel_success(
+        &mut self,
+        _header: &v2::framing::Header,
+        payload: &v2::messages::OpenStandardMiningChannelSuccess,
+    ) {
+        self.0.push(V2Response::OpenStandardMiningChannelSuccess(
+            payload.clone(),
+        ));
+    }
+
+    async fn visit_open_standard_mining_channel_error(
+        &mut self,
+        _header: &v2::framing::Header,
+        payload: &v2::messages::OpenStandardMiningChannelError,
+    ) {
+        self.0.push(V2Response::OpenStandardMiningChannelError {
+            req_id: payload.req_id,
+        });
+    }
+
+    async fn visit_new_mining_job(
+        &mut self,
+        _header: &v2::framing::Header,
+        payload: &v2::messages::NewMiningJob,
+    ) {
+        self.0.push(V2Response::NewMiningJob(payload.clone()));
+    }
+
+    async fn visit_set_new_prev_hash(
+        &mut self,
+        _header: &v2::framing::Header,
+        payload: &v2::messages::SetNewPrevHash,
+    ) {
+        self.0.push(V2Response::SetNewPrevHash(payload.clone()));
+    }
+
+    async fn visit_set_target(
+        &mut self,
+        _header: &v2::framing::Header,
+        payload: &v2::messages::SetTarget,
+    ) {
+        self.0.push(V2Response::SetTarget(payload.clone()));
+    }
+
+    async fn visit_submit_shares_success(
+        &mut self,
+        _header: &v2::framing::Header,
+        payload: &v2::messages::SubmitSharesSuccess,
+    ) {
+        self.0
+            .push(V2Response::SubmitSharesSuccess(payload.clone()));
+    }
+
+    async fn visit_submit_shares_error(
+        &mut self,
+        _header: &v2::framing::Header,
+        payload: &v2::messages::SubmitSharesError,
+    ) {
+        self.0.push(V2Response::SubmitSharesError {
+            channel_id: payload.channel_id,
+            seq_num: payload.seq_num,
+        });
+    }
+}
+
+fn random_bytes(rng: &mut StdRng, len: usize) -> Vec<u8> {
+    (0..len).map(|_| rng.gen()).collect()
+}
+
+fn random_hash(rng: &mut StdRng) -> [u8; HASH_SIZE] {
+    let mut hash = [0; HASH_SIZE];
+    rng.fill(&mut hash[..]);
+    hash
+}
+
+fn random_string(rng: &mut StdRng, charset: &[u8], min_len: usize, max_len: usize) -> String {
+    let len = rng.gen_range(min_len, max_len + 1);
+    (0..len)
+        .map(|_| *charset.choose(rng).expect("BUG: empty charset") as char)
+        .collect()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn v1_rpc(json: serde_json::Value) -> v1::rpc::Rpc {
+    v1::rpc::Rpc::from_str(&json.to_string()).expect("BUG: invalid V1 message")
+}
+
+fn v1_result(id: u32, result: serde_json::Value) -> v1::rpc::Rpc {
+    v1_rpc(serde_json::json!({"id": id, "result": result, "error": null}))
+}
+
+/// Target corresponding to pool difficulty `difficulty`
+fn difficulty_to_target(difficulty: u32) -> Uint256Bytes {
+    let mut diff1_target = [0u8; HASH_SIZE];
+    diff1_target[4] = 0xff;
+    diff1_target[5] = 0xff;
+    (uint::U256::from_big_endian(&diff1_target) / difficulty).into()
+}
+
+/// Model of extra nonce 2 that carries the channel ID in little endian padded with zeros
+fn extra_nonce_2_to_channel_id(extra_nonce_2: &[u8]) -> Option<u32> {
+    let (channel_id, padding) = extra_nonce_2.split_at(extra_nonce_2.len().min(size_of::<u32>()));
+    if padding.iter().any(|byte| *byte != 0) {
+        return None;
+    }
+    let mut channel_id_bytes = [0; size_of::<u32>()];
+    channel_id_bytes[..channel_id.len()].copy_from_slice(channel_id);
+    Some(u32::from_le_bytes(channel_id_bytes))
+}
+
+/// Mining job generated by the simulated V1 pool
+#[derive(Clone, Debug)]
+struct V1Job {
+    job_id: String,
+    /// Previous block hash in the byte order used by stratum V1
+    prev_hash: [u8; HASH_SIZE],
+    coin_base_1: Vec<u8>,
+    coin_base_2: Vec<u8>,
+    merkle_branch: Vec<[u8; HASH_SIZE]>,
+    version: u32,
+    bits: u32,
+    time: u32,
+    clean_jobs: bool,
+}
+
+impl V1Job {
+    fn random(rng: &mut StdRng, serial: u32) -> Self {
+        let coin_base_1_len = rng.gen_range(0, MAX_COIN_BASE_SIZE);
+        let coin_base_2_len = rng.gen_range(0, MAX_COIN_BASE_SIZE);
+        let merkle_branch_len = rng.gen_range(0, MAX_MERKLE_BRANCH_LEN + 1);
+        Self {
+            // The serial number keeps the job IDs unique
+            job_id: format!("{:x}{}", rng.gen::<u16>(), serial),
+            prev_hash: random_hash(rng),
+            coin_base_1: random_bytes(rng, coin_base_1_len),
+            coin_base_2: random_bytes(rng, coin_base_2_len),
+            merkle_branch: (0..merkle_branch_len).map(|_| random_hash(rng)).collect(),
+            version: rng.gen(),
+            bits: rng.gen(),
+            time: rng.gen(),
+            clean_jobs: rng.gen(),
+        }
+    }
+
+    fn to_notify(&self) -> v1::rpc::Rpc {
+        let merkle_branch: Vec<_> = self.merkle_branch.iter().map(|hash| to_hex(hash)).collect();
+        v1_rpc(serde_json::json!({
+            "id": null,
+            "method": "mining.notify",
+            "params": [
+                self.job_id,
+                to_hex(&self.prev_hash),
+                to_hex(&self.coin_base_1),
+                to_hex(&self.coin_base_2),
+                merkle_branch,
+                format!("{:08x}", self.version),
+                format!("{:08x}", self.bits),
+                format!("{:08x}", self.time),
+                self.clean_jobs,
+            ]
+        }))
+    }
+
+    fn merkle_root(&self, extra_nonce_1: &[u8], extra_nonce_2: &[u8]) -> Uint256Bytes {
+        let coin_base = [
+            &self.coin_base_1[..],
+            extra_nonce_1,
+            extra_nonce_2,
+            &self.coin_base_2[..],
+        ]
+        .concat();
+        let merkle_root = self
+            .merkle_branch
+            .iter()
+            .fold(sha256d::Hash::hash(&coin_base), |root, hash| {
+                sha256d::Hash::hash(&[&root[..], &hash[..]].concat())
+            });
+        Uint256Bytes(merkle_root.into_inner())
+    }
+}
+
+/// Job fields carried by `NewMiningJob` and `SetNewPrevHash` in terms of the V1 job
+#[derive(PartialEq, Debug)]
+struct JobFields {
+    channel_ids: (u32, u32),
+    job_ids: (u32, u32),
+    future_job: bool,
+    prev_hash: [u8; HASH_SIZE],
+    merkle_root: Uint256Bytes,
+    version: u32,
+    bits: u32,
+    time: u32,
+}
+
+/// Message from the simulated pool that may arrive in arbitrary order while a channel is being
+/// opened
+#[derive(Debug)]
+enum PoolEvent {
+    SubscribeResult(u32),
+    ExtranonceSubscribeResult(u32, bool),
+    AuthorizeResult(u32),
+    SetDifficulty(u32),
+    Notify(V1Job),
+}
+
+impl fmt::Display for PoolEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PoolEvent::SubscribeResult(id) => write!(f, "subscribe result {}", id),
+            PoolEvent::ExtranonceSubscribeResult(id, enabled) => {
+                write!(f, "extranonce subscribe result {}: {}", id, enabled)
+            }
+            PoolEvent::AuthorizeResult(id) => write!(f, "authorize result {}", id),
+            PoolEvent::SetDifficulty(difficulty) => write!(f, "difficulty {}", difficulty),
+            PoolEvent::Notify(job) => write!(f, "notify of job {}", job.job_id),
+        }
+    }
+}
+
+/// Downstream message that the translation is expected to emit
+#[derive(Debug)]
+enum Expected {
+    Response(V2Response),
+    /// `NewMiningJob` and `SetNewPrevHash` that map back onto the specified V1 job
+    Job(V1Job),
+}
+
+struct Harness {
+    seed: u64,
+    rng: StdRng,
+    translation: V2ToV1Translation,
+    v1_rx: mpsc::Receiver<v1::Frame>,
+    v2_rx: mpsc::Receiver<v2::Frame>,
+
+    setup_connection: v2::messages::SetupConnection,
+    open_channel: v2::messages::OpenStandardMiningChannel,
+    try_enable_xnsub: bool,
+    next_seq_num: u32,
+
+    xnsub_enabled: bool,
+    extra_nonce_1: Vec<u8>,
+    extra_nonce_2_size: usize,
+    job_serial: u32,
+
+    /// Jobs that can be submitted, V2 job ID maps back onto the originating V1 job
+    v2_jobs: HashMap<u32, V1Job>,
+    /// All V2 job IDs ever announced to the downstream
+    used_v2_job_ids: HashSet<u32>,
+    /// Shares submitted upstream waiting for the pool result
+    pending_shares: Vec<(u32, v2::messages::SubmitSharesStandard)>,
+    accepted_count: u64,
+    rejected_count: u64,
+}
+
+impl Harness {
+    fn new(seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let (v1_tx, v1_rx) = mpsc::channel(QUEUE_SIZE);
+        let (v2_tx, v2_rx) = mpsc::channel(QUEUE_SIZE);
+        let try_enable_xnsub = rng.gen();
+        let translation =
+            V2ToV1Translation::new(v1_tx, v2_tx, V2ToV1TranslationOptions { try_enable_xnsub });
+
+        let alphanumeric = b"abcdefghijklmnopqrstuvwxyz0123456789";
+        let printable: Vec<u8> = (b' '..=b'~').collect();
+        let device = v2::types::DeviceInfo {
+            vendor: random_string(&mut rng, &printable, 0, 32)
+                .try_into()
+                .unwrap(),
+            hw_rev: random_string(&mut rng, &printable, 0, 8)
+                .try_into()
+                .unwrap(),
+            fw_ver: random_string(&mut rng, &printable, 0, 64)
+                .try_into()
+                .unwrap(),
+            dev_id: random_string(&mut rng, &printable, 0, 32)
+                .try_into()
+                .unwrap(),
+        };
+        let endpoint_host = format!(
+            "{}.{}",
+            random_string(&mut rng, alphanumeric, 1, 32),
+            random_string(&mut rng, alphanumeric, 2, 3)
+        );
+        let setup_connection = v2::messages::SetupConnection {
+            protocol: 0,
+            max_version: 2,
+            min_version: 2,
+            flags: 0,
+            endpoint_host: endpoint_host.as_str().try_into().unwrap(),
+            endpoint_port: rng.gen(),
+            device,
+        };
+        let user = format!(
+            "{}.{}",
+            random_string(&mut rng, alphanumeric, 1, 16),
+            random_string(&mut rng, &printable, 1, 16)
+        );
+        let open_channel = v2::messages::OpenStandardMiningChannel {
+            req_id: rng.gen(),
+            user: user.as_str().try_into().unwrap(),
+            nominal_hashrate: rng.gen_range(1e9, 1e15),
+            max_target: Uint256Bytes([0xff; HASH_SIZE]),
+        };
+
+        Self {
+            seed,
+            rng,
+            translation,
+            v1_rx,
+            v2_rx,
+            setup_connection,
+            open_channel,
+            try_enable_xnsub,
+            next_seq_num: 0,
+            xnsub_enabled: false,
+            extra_nonce_1: vec![],
+            extra_nonce_2_size: 0,
+            job_serial: 0,
+            v2_jobs: HashMap::new(),
+            used_v2_job_ids: HashSet::new(),
+            pending_shares: vec![],
+            accepted_count: 0,
+            rejected_count: 0,
+        }
+    }
+
+    fn fail(&self, message: String) -> ! {
+        panic!(
+            "Translation fuzzing failed with seed {}: {}",
+            self.seed, message
+        );
+    }
+
+    fn check_eq<T: PartialEq + fmt::Debug>(&self, actual: T, expected: T, context: &str) {
+        if actual != expected {
+            self.fail(format!(
+                "{}: got {:?}, expected {:?}",
+                context, actual, expected
+            ));
+        }
+    }
+
+    async fn send_v2<M>(&mut self, message: M)
+    where
+        M: TryInto<v2::Frame, Error = ii_stratum::error::Error>,
+    {
+        let frame = message
+            .try_into()
+            .expect("BUG: cannot serialize V2 message");
+        let message = v2::build_message_from_frame(frame).expect("BUG: cannot deserialize");
+        message.accept(&mut self.translation).await;
+    }
+
+    async fn send_v1(&mut self, message: v1::rpc::Rpc) {
+        let frame: v1::Frame = message
+            .try_into()
+            .expect("BUG: cannot serialize V1 message");
+        let message = v1::build_message_from_frame(frame).expect("BUG: cannot deserialize");
+        message.accept(&mut self.translation).await;
+    }
+
+    /// Returns all V1 requests sent upstream since the last call
+    async fn v1_requests(&mut self) -> Vec<(u32, V1Request)> {
+        let mut collector = V1Collector::default();
+        while let Ok(Some(frame)) = self.v1_rx.try_next() {
+            let count = collector.0.len();
+            let message = v1::build_message_from_frame(frame).expect("BUG: cannot deserialize");
+            message.accept(&mut collector).await;
+            if collector.0.len() == count {
+                self.fail("unexpected V1 message sent upstream".to_string());
+            }
+        }
+        collector
+            .0
+            .into_iter()
+            .map(|(id, request)| match id {
+                Some(id) => (id, request),
+                None => self.fail(format!("V1 request without ID: {:?}", request)),
+            })
+            .collect()
+    }
+
+    /// Returns all V2 messages sent downstream since the last call
+    async fn v2_responses(&mut self) -> Vec<V2Response> {
+        let mut collector = V2Collector::default();
+        while let Ok(Some(frame)) = self.v2_rx.try_next() {
+            let count = collector.0.len();
+            let message = v2::build_message_from_frame(frame).expect("BUG: cannot deserialize");
+            message.accept(&mut collector).await;
+            if collector.0.len() == count {
+                self.fail("unexpected V2 message sent downstream".to_string());
+            }
+        }
+        collector.0
+    }
+
+    async fn check_no_v1_request(&mut self, context: &str) {
+        let requests = self.v1_requests().await;
+        self.check_eq(requests, vec![], context);
+    }
+
+    /// Compares all messages sent downstream with the expected ones
+    async fn check_v2_responses(&mut self, expected: Vec<Expected>, context: &str) {
+        let mut actual = self.v2_responses().await.into_iter();
+        for expected in expected {
+            match expected {
+                Expected::Response(response) => {
+                    self.check_eq(actual.next(), Some(response), context);
+                }
+                Expected::Job(job) => match (actual.next(), actual.next()) {
+                    (
+                        Some(V2Response::NewMiningJob(new_job)),
+                        Some(V2Response::SetNewPrevHash(prev_hash)),
+                    ) => self.check_job(new_job, prev_hash, job, context),
+                    other => self.fail(format!(
+                        "{}: got {:?}, expected job and prev hash for job {}",
+                        context, other, job.job_id
+                    )),
+                },
+            }
+        }
+        if let Some(response) = actual.next() {
+            self.fail(format!("{}: unexpected {:?}", context, response));
+        }
+    }
+
+    /// Maps a new V2 job back onto the V1 job and registers it for submitting shares
+    fn check_job(
+        &mut self,
+        new_job: v2::messages::NewMiningJob,
+        prev_hash: v2::messages::SetNewPrevHash,
+        job: V1Job,
+        context: &str,
+    ) {
+        if !self.used_v2_job_ids.insert(new_job.job_id) {
+            self.fail(format!("{}: reused V2 job ID {}", context, new_job.job_id));
+        }
+        // Stratum V1 sends the previous block hash with swapped 32-bit words
+        let mut v1_prev_hash = prev_hash.prev_hash.0;
+        for word in v1_prev_hash.chunks_mut(size_of::<u32>()) {
+            word.reverse();
+        }
+        let translated_job = JobFields {
+            channel_ids: (new_job.channel_id, prev_hash.channel_id),
+            job_ids: (new_job.job_id, prev_hash.job_id),
+            future_job: new_job.future_job,
+            prev_hash: v1_prev_hash,
+            merkle_root: new_job.merkle_root,
+            version: new_job.version,
+            bits: prev_hash.nbits,
+            time: prev_hash.min_ntime,
+        };
+        let extra_nonce_2 = vec![0; self.extra_nonce_2_size];
+        let expected_job = JobFields {
+            channel_ids: (V2ToV1Translation::CHANNEL_ID, V2ToV1Translation::CHANNEL_ID),
+            job_ids: (new_job.job_id, new_job.job_id),
+            // Jobs are always sent as future jobs so that the prev hash can make them active
+            future_job: true,
+            prev_hash: job.prev_hash,
+            merkle_root: job.merkle_root(&self.extra_nonce_1, &extra_nonce_2),
+            version: job.version,
+            bits: job.bits,
+            time: job.time,
+        };
+        self.check_eq(translated_job, expected_job, context);
+
+        // New prev hash invalidates all previous jobs
+        self.v2_jobs.clear();
+        self.v2_jobs.insert(new_job.job_id, job);
+    }
+
+    fn random_job(&mut self) -> V1Job {
+        self.job_serial += 1;
+        V1Job::random(&mut self.rng, self.job_serial)
+    }
+
+    fn random_extra_nonce_1(&mut self) -> Vec<u8> {
+        let len = self.rng.gen_range(1, 9);
+        random_bytes(&mut self.rng, len)
+    }
+
+    fn random_difficulty(&mut self) -> u32 {
+        let bits = self.rng.gen_range(0, 21);
+        self.rng.gen_range(1 << bits, 2 << bits)
+    }
+
+    /// Returns false when the simulated pool refused the connection
+    async fn setup_connection(&mut self) -> bool {
+        let context = "SetupConnection";
+        self.send_v2(self.setup_connection.clone()).await;
+        let requests = self.v1_requests().await;
+        let id = match requests.as_slice() {
+            [(id, V1Request::Configure { .. })] => *id,
+            _ => self.fail(format!("{}: got {:?}", context, requests)),
+        };
+        self.check_eq(
+            requests[0].1.clone(),
+            V1Request::Configure {
+                features: vec!["version-rolling".to_string()],
+                version_mask: Some(ii_stratum::BIP320_N_VERSION_MASK),
+            },
+            context,
+        );
+
+        let version_rolling = self.rng.gen_range(0, 16) != 0;
+        self.send_v1(v1_result(
+            id,
+            serde_json::json!({
+                "version-rolling": version_rolling,
+                "version-rolling.mask": format!("{:08x}", ii_stratum::BIP320_N_VERSION_MASK),
+            }),
+        ))
+        .await;
+        let expected = if version_rolling {
+            V2Response::SetupConnectionSuccess(v2::messages::SetupConnectionSuccess {
+                used_version: V2ToV1Translation::PROTOCOL_VERSION as u16,
+                flags: 0,
+            })
+        } else {
+            V2Response::SetupConnectionError
+        };
+        self.check_v2_responses(vec![Expected::Response(expected)], context)
+            .await;
+        version_rolling
+    }
+
+    async fn open_channel(&mut self) {
+        let context = "OpenStandardMiningChannel";
+        self.send_v2(self.open_channel.clone()).await;
+        let requests = self.v1_requests().await;
+
+        let host: String = self.setup_connection.endpoint_host.to_string();
+        let mut expected_requests = vec![V1Request::Subscribe {
+            agent_signature: Some(self.setup_connection.device.fw_ver.to_string()),
+            url: Some(format!("{}:{}", host, self.setup_connection.endpoint_port)),
+        }];
+        if self.try_enable_xnsub {
+            expected_requests.push(V1Request::ExtranonceSubscribe);
+        }
+        expected_requests.push(V1Request::Authorize {
+            user: self.open_channel.user.to_string(),
+        });
+        self.check_eq(
+            requests
+                .iter()
+                .map(|(_, request)| request.clone())
+                .collect::<Vec<_>>(),
+            expected_requests,
+            context,
+        );
+
+        // The pool answers the requests and starts sending jobs in arbitrary order
+        let mut events = vec![PoolEvent::SetDifficulty(self.random_difficulty())];
+        for (id, request) in requests.iter() {
+            events.push(match request {
+                V1Request::Subscribe { .. } => PoolEvent::SubscribeResult(*id),
+                V1Request::ExtranonceSubscribe => {
+                    PoolEvent::ExtranonceSubscribeResult(*id, self.rng.gen())
+                }
+                _ => PoolEvent::AuthorizeResult(*id),
+            });
+        }
+        for _ in 0..self.rng.gen_range(0, 3) {
+            events.push(PoolEvent::Notify(self.random_job()));
+        }
+        events.shuffle(&mut self.rng);
+
+        let (mut subscribed, mut authorized) = (false, false);
+        let mut target = None;
+        let mut operational = false;
+        let mut deferred_job = None;
+        for event in events {
+            let context = format!("{}: {}", context, event);
+            let mut expected = vec![];
+            match event {
+                PoolEvent::SubscribeResult(id) => {
+                    self.extra_nonce_1 = self.random_extra_nonce_1();
+                    self.extra_nonce_2_size = self.rng.gen_range(2, 9);
+                    self.send_v1(v1_result(
+                        id,
+                        serde_json::json!([
+                            [["mining.set_difficulty", "1"], ["mining.notify", "1"]],
+                            to_hex(&self.extra_nonce_1),
+                            self.extra_nonce_2_size,
+                        ]),
+                    ))
+                    .await;
+                    subscribed = true;
+                }
+                PoolEvent::ExtranonceSubscribeResult(id, enabled) => {
+                    self.send_v1(v1_result(id, serde_json::json!(enabled)))
+                        .await;
+                    self.xnsub_enabled = enabled;
+                }
+                PoolEvent::AuthorizeResult(id) => {
+                    self.send_v1(v1_result(id, serde_json::json!(true))).await;
+                    authorized = true;
+                }
+                PoolEvent::SetDifficulty(difficulty) => {
+                    self.send_set_difficulty(difficulty).await;
+                    target = Some(difficulty_to_target(difficulty));
+                }
+                PoolEvent::Notify(job) => {
+                    self.send_v1(job.to_notify()).await;
+                    if operational {
+                        expected.push(Expected::Job(job));
+                    } else {
+                        // Only the latest job is kept until the channel is open
+                        deferred_job = Some(job);
+                    }
+                }
+            }
+            if let (false, true, true, Some(target)) =
+                (operational, subscribed, authorized, &target)
+            {
+                operational = true;
+                expected.push(Expected::Response(
+                    V2Response::OpenStandardMiningChannelSuccess(
+                        v2::messages::OpenStandardMiningChannelSuccess {
+                            req_id: self.open_channel.req_id,
+                            channel_id: V2ToV1Translation::CHANNEL_ID,
+                            target: *target,
+                            extranonce_prefix: Bytes0_32::new(),
+                            group_channel_id: V2ToV1Translation::DEFAULT_GROUP_CHANNEL_ID,
+                        },
+                    ),
+                ));
+                if let Some(job) = deferred_job.take() {
+                    expected.push(Expected::Job(job));
+                }
+            }
+            self.check_no_v1_request(&context).await;
+            self.check_v2_responses(expected, &context).await;
+        }
+    }
+
+    async fn send_set_difficulty(&mut self, difficulty: u32) {
+        self.send_v1(v1_rpc(serde_json::json!({
+            "id": null,
+            "method": "mining.set_difficulty",
+            "params": [difficulty as f64],
+        })))
+        .await;
+    }
+
+    fn next_share(&mut self, job_id: u32) -> v2::messages::SubmitSharesStandard {
+        let (version, time) = match self.v2_jobs.get(&job_id) {
+            Some(job) => (job.version, job.time),
+            None => (self.rng.gen(), self.rng.gen()),
+        };
+        let share = v2::messages::SubmitSharesStandard {
+            channel_id: V2ToV1Translation::CHANNEL_ID,
+            seq_num: self.next_seq_num,
+            job_id,
+            nonce: self.rng.gen(),
+            ntime: time.wrapping_add(self.rng.gen_range(0, 600)),
+            version: (version & !ii_stratum::BIP320_N_VERSION_MASK)
+                | (self.rng.gen::<u32>() & ii_stratum::BIP320_N_VERSION_MASK),
+        };
+        self.next_seq_num = self.next_seq_num.wrapping_add(1);
+        share
+    }
+
+    /// Maps `mining.submit` back onto a V2 share
+    fn submit_to_share(
+        &self,
+        submit: &v1::messages::Submit,
+        seq_num: u32,
+        context: &str,
+    ) -> v2::messages::SubmitSharesStandard {
+        self.check_eq(
+            submit.user_name(),
+            &self.open_channel.user.to_string(),
+            context,
+        );
+        self.check_eq(
+            submit.extra_nonce_2().len(),
+            self.extra_nonce_2_size,
+            context,
+        );
+        let channel_id = extra_nonce_2_to_channel_id(submit.extra_nonce_2())
+            .unwrap_or_else(|| self.fail(format!("{}: invalid extra nonce 2", context)));
+        let (job_id, job) = self
+            .v2_jobs
+            .iter()
+            .find(|(_, job)| &job.job_id == submit.job_id())
+            .unwrap_or_else(|| self.fail(format!("{}: submit for unknown job", context)));
+        // Only the rolled version bits are submitted
+        self.check_eq(
+            submit.version() & !ii_stratum::BIP320_N_VERSION_MASK,
+            0,
+            &format!("{}: version bits outside of the mask", context),
+        );
+        v2::messages::SubmitSharesStandard {
+            channel_id,
+            seq_num,
+            job_id: *job_id,
+            nonce: submit.nonce(),
+            ntime: submit.time(),
+            version: (job.version & !ii_stratum::BIP320_N_VERSION_MASK) | submit.version(),
+        }
+    }
+
+    async fn submit_valid_share(&mut self) {
+        let job_id = *self
+            .v2_jobs
+            .keys()
+            .next()
+            .expect("BUG: no job for submitting shares");
+        let share = self.next_share(job_id);
+        let context = format!("{:?}", share);
+        self.send_v2(share.clone()).await;
+
+        let requests = self.v1_requests().await;
+        match requests.as_slice() {
+            [(id, V1Request::Submit(submit))] => {
+                let translated_share = self.submit_to_share(submit, share.seq_num, &context);
+                self.check_eq(translated_share, share.clone(), &context);
+                self.pending_shares.push((*id, share));
+            }
+            _ => self.fail(format!("{}: got {:?}", context, requests)),
+        }
+        self.check_v2_responses(vec![], &context).await;
+    }
+
+    async fn submit_stale_share(&mut self) {
+        // Either a job invalidated by a prev hash or a job that has never existed
+        let stale_job_ids: Vec<_> = self
+            .used_v2_job_ids
+            .iter()
+            .filter(|job_id| !self.v2_jobs.contains_key(job_id))
+            .cloned()
+            .collect();
+        let job_id = match stale_job_ids.choose(&mut self.rng) {
+            Some(job_id) if self.rng.gen() => *job_id,
+            _ => loop {
+                let job_id = self.rng.gen();
+                if !self.used_v2_job_ids.contains(&job_id) {
+                    break job_id;
+                }
+            },
+        };
+        let share = self.next_share(job_id);
+        let context = format!("stale {:?}", share);
+        self.send_v2(share.clone()).await;
+        self.check_no_v1_request(&context).await;
+        self.check_v2_responses(
+            vec![Expected::Response(V2Response::SubmitSharesError {
+                channel_id: share.channel_id,
+                seq_num: share.seq_num,
+            })],
+            &context,
+        )
+        .await;
+    }
+
+    /// The pool answers a random pending share, i.e. the results may arrive out of order
+    async fn answer_share(&mut self) {
+        let index = self.rng.gen_range(0, self.pending_shares.len());
+        let (id, share) = self.pending_shares.swap_remove(index);
+        let context = format!("result of {:?}", share);
+        // NOTE: the translation doesn't track sequence numbers of the submitted shares yet, all
+        // results are reported with sequence number 0
+        let (result, expected) = match self.rng.gen_range(0, 4) {
+            0 => (
+                serde_json::json!({"id": id, "result": false, "error": null}),
+                V2Response::SubmitSharesError {
+                    channel_id: V2ToV1Translation::CHANNEL_ID,
+                    seq_num: 0,
+                },
+            ),
+            1 => (
+                serde_json::json!({
+                    "id": id,
+                    "result": null,
+                    "error": [23, "Low difficulty share", null],
+                }),
+                V2Response::SubmitSharesError {
+                    channel_id: V2ToV1Translation::CHANNEL_ID,
+                    seq_num: 0,
+                },
+            ),
+            _ => (
+                serde_json::json!({"id": id, "result": true, "error": null}),
+                V2Response::SubmitSharesSuccess(v2::messages::SubmitSharesSuccess {
+                    channel_id: V2ToV1Translation::CHANNEL_ID,
+                    last_seq_num: 0,
+                    new_submits_accepted_count: 1,
+                    new_shares_sum: 1,
+                }),
+            ),
+        };
+        if let V2Response::SubmitSharesSuccess(_) = expected {
+            self.accepted_count += 1;
+        } else {
+            self.rejected_count += 1;
+        }
+        self.send_v1(v1_rpc(result)).await;
+        self.check_no_v1_request(&context).await;
+        self.check_v2_responses(vec![Expected::Response(expected)], &context)
+            .await;
+    }
+
+    async fn notify(&mut self) {
+        let job = self.random_job();
+        let context = format!("notify of job {}", job.job_id);
+        self.send_v1(job.to_notify()).await;
+        self.check_no_v1_request(&context).await;
+        self.check_v2_responses(vec![Expected::Job(job)], &context)
+            .await;
+    }
+
+    async fn set_difficulty(&mut self) {
+        let difficulty = self.random_difficulty();
+        let context = format!("difficulty {}", difficulty);
+        self.send_set_difficulty(difficulty).await;
+        self.check_no_v1_request(&context).await;
+        self.check_v2_responses(
+            vec![Expected::Response(V2Response::SetTarget(
+                v2::messages::SetTarget {
+                    channel_id: V2ToV1Translation::CHANNEL_ID,
+                    max_target: difficulty_to_target(difficulty),
+                },
+            ))],
+            &context,
+        )
+        .await;
+    }
+
+    /// Changes extra nonce 1 of the following jobs. The size of extra nonce 2 is kept as the
+    /// pending jobs still use it.
+    async fn set_extranonce(&mut self) {
+        self.extra_nonce_1 = self.random_extra_nonce_1();
+        let context = format!("extranonce {:x?}", self.extra_nonce_1);
+        self.send_v1(v1_rpc(serde_json::json!({
+            "id": null,
+            "method": "mining.set_extranonce",
+            "params": [to_hex(&self.extra_nonce_1), self.extra_nonce_2_size],
+        })))
+        .await;
+        self.check_no_v1_request(&context).await;
+        self.check_v2_responses(vec![], &context).await;
+    }
+
+    async fn random_step(&mut self) {
+        match self.rng.gen_range(0, 10) {
+            0..=3 if !self.v2_jobs.is_empty() => self.submit_valid_share().await,
+            4 => self.submit_stale_share().await,
+            5 | 6 => self.notify().await,
+            7 => self.set_difficulty().await,
+            8 if self.xnsub_enabled => self.set_extranonce().await,
+            _ if !self.pending_shares.is_empty() => self.answer_share().await,
+            _ => self.notify().await,
+        }
+    }
+
+    async fn run(&mut self) {
+        if !self.setup_connection().await {
+            return;
+        }
+        self.open_channel().await;
+        for _ in 0..STEPS {
+            self.random_step().await;
+        }
+        while !self.pending_shares.is_empty() {
+            self.answer_share().await;
+        }
+        let totals = self.translation.stats.snapshot(time::Instant::now()).totals;
+        self.check_eq(
+            totals.accepted_count,
+            self.accepted_count,
+            "accepted shares",
+        );
+        self.check_eq(
+            totals.rejected_count,
+            self.rejected_count,
+            "rejected shares",
+        );
+    }
+}
+
+fn env_u64(name: &str) -> Option<u64> {
+    std::env::var(name)
+        .ok()
+        .map(|value| value.parse().expect("BUG: invalid number in environment"))
+}
+
+#[tokio::test]
+async fn test_translation_fuzz() {
+    let seeds = match env_u64("II_PROXY_FUZZ_SEED") {
+        Some(seed) => seed..seed + 1,
+        None => 0..env_u64("II_PROXY_FUZZ_ITERATIONS").unwrap_or(DEFAULT_ITERATIONS),
+    };
+    for seed in seeds {
+        Harness::new(seed).run().await;
+    }
+}
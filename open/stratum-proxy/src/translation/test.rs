@@ -162,6 +162,9 @@ async fn test_setup_connection_translate() {
     .await;
     // Expect SubmitSharesSuccess to be generated
     v2_verify_generated_response_message(&mut v2_rx).await;
+    let totals = translation.stats.snapshot(time::Instant::now()).totals;
+    assert_eq!(totals.accepted_count, 1);
+    assert_eq!(totals.rejected_count, 0);
     // });
 }
 
@@ -184,3 +187,22 @@ fn test_diff_1_bitcoin_target() {
         V2ToV1Translation::DIFF1_TARGET
     );
 }
+
+#[test]
+fn test_difficulty_to_target() {
+    let diff_1_target = V2ToV1Translation::DIFF1_TARGET;
+    assert_eq!(V2ToV1Translation::difficulty_to_target(1.0), diff_1_target);
+    assert_eq!(
+        V2ToV1Translation::difficulty_to_target(1024.0),
+        diff_1_target / uint::U256::from(1024)
+    );
+    // Fractional difficulty used by test pools makes the target easier than difficulty 1
+    assert_eq!(
+        V2ToV1Translation::difficulty_to_target(1.0 / 65536.0),
+        diff_1_target * uint::U256::from(65536)
+    );
+    assert_eq!(
+        V2ToV1Translation::difficulty_to_target(0.0),
+        uint::U256::max_value()
+    );
+}
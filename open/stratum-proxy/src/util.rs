@@ -38,7 +38,5 @@ where
     let frame = msg
         .try_into()
         .expect("BUG: Could convert the message to frame");
-    tx.try_send(frame)
-        .context("submit message")
-        .map_err(Into::into)
+    tx.try_send(frame).context("submit message")
 }
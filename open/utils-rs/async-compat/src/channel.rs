@@ -0,0 +1,588 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Bounded channels with explicit overflow handling and occupancy metrics
+//!
+//! Unbounded queues hide a slow consumer until the memory runs out. The `bounded()` channel has
+//! a fixed capacity and an `OverflowPolicy` which decides what happens when a sender finds it
+//! full: the oldest message is dropped, the sender waits for a free slot or the send fails.
+//! Both ends can report `Stats` about the queue occupancy and about the messages which did not
+//! make it through.
+//!
+//! The `watch` submodule wraps the runtime watch channel and counts the values that were
+//! overwritten before a receiver managed to observe them.
+
+use futures::future;
+use futures::prelude::*;
+use futures::task::{Context, Poll, Waker};
+
+use std::collections::VecDeque;
+use std::error::Error as StdError;
+use std::fmt;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Decides what happens with a message sent to a full channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// The oldest message in the queue is discarded to make room for the new one
+    DropOldest,
+    /// `Sender::send()` waits until the receiver frees a slot, `Sender::try_send()` fails
+    Backpressure,
+    /// The new message is rejected
+    Fail,
+}
+
+/// Snapshot of channel metrics
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stats {
+    pub capacity: usize,
+    /// Number of messages currently in the queue
+    pub len: usize,
+    /// Maximum number of messages that have been in the queue at once
+    pub high_watermark: usize,
+    /// Total number of messages accepted by the channel
+    pub sent: u64,
+    /// Total number of messages taken by the receiver
+    pub received: u64,
+    /// Messages discarded by `OverflowPolicy::DropOldest`
+    pub dropped: u64,
+    /// Messages refused because the channel was full
+    pub rejected: u64,
+}
+
+impl Stats {
+    /// Fraction of the capacity which is currently used
+    pub fn occupancy(&self) -> f64 {
+        self.len as f64 / self.capacity as f64
+    }
+}
+
+/// Error returned when a message cannot be sent, the message is handed back to the caller
+#[derive(PartialEq, Eq)]
+pub enum SendError<T> {
+    /// The channel is full and the overflow policy does not allow to enqueue the message
+    Full(T),
+    /// The receiver has been dropped
+    Closed(T),
+}
+
+impl<T> SendError<T> {
+    pub fn is_full(&self) -> bool {
+        matches!(self, SendError::Full(_))
+    }
+
+    pub fn is_closed(&self) -> bool {
+        matches!(self, SendError::Closed(_))
+    }
+
+    pub fn into_inner(self) -> T {
+        match self {
+            SendError::Full(item) | SendError::Closed(item) => item,
+        }
+    }
+}
+
+impl<T> fmt::Debug for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SendError::Full(_) => write!(f, "Full(..)"),
+            SendError::Closed(_) => write!(f, "Closed(..)"),
+        }
+    }
+}
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SendError::Full(_) => write!(f, "channel is full"),
+            SendError::Closed(_) => write!(f, "channel is closed"),
+        }
+    }
+}
+
+impl<T> StdError for SendError<T> {}
+
+struct State<T> {
+    queue: VecDeque<T>,
+    receiver_alive: bool,
+    sender_count: usize,
+    receiver_waker: Option<Waker>,
+    /// Senders waiting for a free slot with `OverflowPolicy::Backpressure`
+    sender_wakers: Vec<Waker>,
+    high_watermark: usize,
+    sent: u64,
+    received: u64,
+    dropped: u64,
+    rejected: u64,
+}
+
+impl<T> State<T> {
+    fn wake_receiver(&mut self) {
+        if let Some(waker) = self.receiver_waker.take() {
+            waker.wake();
+        }
+    }
+
+    fn wake_senders(&mut self) {
+        for waker in self.sender_wakers.drain(..) {
+            waker.wake();
+        }
+    }
+}
+
+struct Shared<T> {
+    name: &'static str,
+    capacity: usize,
+    policy: OverflowPolicy,
+    state: Mutex<State<T>>,
+}
+
+impl<T> Shared<T> {
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        self.state.lock().expect("BUG: cannot lock channel state")
+    }
+
+    fn stats(&self) -> Stats {
+        let state = self.lock();
+        Stats {
+            capacity: self.capacity,
+            len: state.queue.len(),
+            high_watermark: state.high_watermark,
+            sent: state.sent,
+            received: state.received,
+            dropped: state.dropped,
+            rejected: state.rejected,
+        }
+    }
+
+    /// Enqueues `item` according to the overflow policy
+    fn push(&self, state: &mut State<T>, item: T) -> Result<(), SendError<T>> {
+        if !state.receiver_alive {
+            return Err(SendError::Closed(item));
+        }
+        if state.queue.len() >= self.capacity {
+            match self.policy {
+                OverflowPolicy::DropOldest => {
+                    state.queue.pop_front();
+                    state.dropped += 1;
+                }
+                OverflowPolicy::Backpressure | OverflowPolicy::Fail => {
+                    state.rejected += 1;
+                    return Err(SendError::Full(item));
+                }
+            }
+        }
+        state.queue.push_back(item);
+        state.sent += 1;
+        state.high_watermark = state.high_watermark.max(state.queue.len());
+        state.wake_receiver();
+        Ok(())
+    }
+}
+
+/// Creates a channel which holds at most `capacity` messages. The `name` identifies the channel
+/// in debug output.
+pub fn bounded<T>(
+    name: &'static str,
+    capacity: usize,
+    policy: OverflowPolicy,
+) -> (Sender<T>, Receiver<T>) {
+    assert!(capacity > 0, "BUG: channel '{}' has zero capacity", name);
+    let shared = Arc::new(Shared {
+        name,
+        capacity,
+        policy,
+        state: Mutex::new(State {
+            queue: VecDeque::with_capacity(capacity),
+            receiver_alive: true,
+            sender_count: 1,
+            receiver_waker: None,
+            sender_wakers: vec![],
+            high_watermark: 0,
+            sent: 0,
+            received: 0,
+            dropped: 0,
+            rejected: 0,
+        }),
+    });
+    (
+        Sender {
+            shared: shared.clone(),
+        },
+        Receiver { shared },
+    )
+}
+
+/// Sending side of a bounded channel, it can be cloned to get multiple producers
+pub struct Sender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Sender<T> {
+    /// Sends the message without waiting. With `OverflowPolicy::DropOldest` it fails only when
+    /// the receiver is gone.
+    pub fn try_send(&self, item: T) -> Result<(), SendError<T>> {
+        let mut state = self.shared.lock();
+        self.shared.push(&mut state, item)
+    }
+
+    /// Sends the message and with `OverflowPolicy::Backpressure` waits while the channel is full.
+    /// Other policies behave the same as `try_send()`.
+    pub async fn send(&self, item: T) -> Result<(), SendError<T>> {
+        let mut item = Some(item);
+        future::poll_fn(|cx| {
+            let mut state = self.shared.lock();
+            if self.shared.policy == OverflowPolicy::Backpressure
+                && state.receiver_alive
+                && state.queue.len() >= self.shared.capacity
+            {
+                state.sender_wakers.push(cx.waker().clone());
+                return Poll::Pending;
+            }
+            let item = item.take().expect("BUG: message already sent");
+            Poll::Ready(self.shared.push(&mut state, item))
+        })
+        .await
+    }
+
+    /// Returns true when the receiver has been dropped
+    pub fn is_closed(&self) -> bool {
+        !self.shared.lock().receiver_alive
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.shared.name
+    }
+
+    pub fn stats(&self) -> Stats {
+        self.shared.stats()
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.shared.lock().sender_count += 1;
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.sender_count -= 1;
+        if state.sender_count == 0 {
+            state.wake_receiver();
+        }
+    }
+}
+
+impl<T> fmt::Debug for Sender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sender")
+            .field("name", &self.shared.name)
+            .field("policy", &self.shared.policy)
+            .field("stats", &self.stats())
+            .finish()
+    }
+}
+
+/// Receiving side of a bounded channel. The stream ends once all senders are dropped and the
+/// queue has been drained.
+pub struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Receiver<T> {
+    /// Takes the next message if there is any
+    pub fn try_recv(&mut self) -> Option<T> {
+        let mut state = self.shared.lock();
+        let item = state.queue.pop_front();
+        if item.is_some() {
+            state.received += 1;
+            state.wake_senders();
+        }
+        item
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.shared.name
+    }
+
+    pub fn stats(&self) -> Stats {
+        self.shared.stats()
+    }
+}
+
+impl<T> Stream for Receiver<T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        let mut state = self.shared.lock();
+        match state.queue.pop_front() {
+            Some(item) => {
+                state.received += 1;
+                state.wake_senders();
+                Poll::Ready(Some(item))
+            }
+            None if state.sender_count == 0 => Poll::Ready(None),
+            None => {
+                state.receiver_waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.receiver_alive = false;
+        state.queue.clear();
+        state.wake_senders();
+    }
+}
+
+impl<T> fmt::Debug for Receiver<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Receiver")
+            .field("name", &self.shared.name)
+            .field("policy", &self.shared.policy)
+            .field("stats", &self.stats())
+            .finish()
+    }
+}
+
+pub mod watch {
+    //! Watch channel which keeps only the latest value and counts the values that receivers
+    //! have missed
+
+    use futures::prelude::*;
+    use futures::task::{Context, Poll};
+    use tokio::sync::watch;
+
+    use std::pin::Pin;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+
+    pub use watch::error::SendError;
+    pub use watch::Ref;
+
+    /// Snapshot of watch channel metrics
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Stats {
+        /// Number of values broadcast after the initial one
+        pub updates: u64,
+        /// Values which have been replaced before a receiver observed them, summed over all
+        /// receivers
+        pub skipped: u64,
+    }
+
+    #[derive(Debug, Default)]
+    struct Counters {
+        updates: AtomicU64,
+        skipped: AtomicU64,
+    }
+
+    impl Counters {
+        fn stats(&self) -> Stats {
+            Stats {
+                updates: self.updates.load(Ordering::Relaxed),
+                skipped: self.skipped.load(Ordering::Relaxed),
+            }
+        }
+    }
+
+    pub fn channel<T: Clone>(init: T) -> (Sender<T>, Receiver<T>) {
+        let (inner_sender, inner_receiver) = watch::channel(init);
+        let counters = Arc::new(Counters::default());
+        (
+            Sender {
+                inner: inner_sender,
+                counters: counters.clone(),
+            },
+            Receiver {
+                inner: inner_receiver,
+                counters,
+                seen_updates: 0,
+            },
+        )
+    }
+
+    #[derive(Debug)]
+    pub struct Sender<T> {
+        inner: watch::Sender<T>,
+        counters: Arc<Counters>,
+    }
+
+    impl<T> Sender<T> {
+        /// Replaces the current value and notifies all receivers
+        pub fn broadcast(&self, value: T) -> Result<(), SendError<T>> {
+            self.counters.updates.fetch_add(1, Ordering::Relaxed);
+            self.inner.broadcast(value)
+        }
+
+        pub fn stats(&self) -> Stats {
+            self.counters.stats()
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct Receiver<T> {
+        inner: watch::Receiver<T>,
+        counters: Arc<Counters>,
+        /// Number of updates at the time of the last observed value
+        seen_updates: u64,
+    }
+
+    impl<T> Receiver<T> {
+        /// Returns reference to the latest value without marking it as observed
+        pub fn borrow(&self) -> Ref<'_, T> {
+            self.inner.borrow()
+        }
+
+        pub fn stats(&self) -> Stats {
+            self.counters.stats()
+        }
+
+        fn observe(&mut self) {
+            let updates = self.counters.updates.load(Ordering::Relaxed);
+            let skipped = updates.saturating_sub(self.seen_updates + 1);
+            if skipped > 0 {
+                self.counters.skipped.fetch_add(skipped, Ordering::Relaxed);
+            }
+            self.seen_updates = self.seen_updates.max(updates);
+        }
+    }
+
+    impl<T> Clone for Receiver<T> {
+        fn clone(&self) -> Self {
+            Self {
+                inner: self.inner.clone(),
+                counters: self.counters.clone(),
+                seen_updates: self.seen_updates,
+            }
+        }
+    }
+
+    impl<T: Clone> Stream for Receiver<T> {
+        type Item = T;
+
+        fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+            let this = self.get_mut();
+            let value = futures::ready!(Pin::new(&mut this.inner).poll_next(cx));
+            if value.is_some() {
+                this.observe();
+            }
+            Poll::Ready(value)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_drop_oldest() {
+        let (sender, mut receiver) = bounded("test", 2, OverflowPolicy::DropOldest);
+        for i in 0..5 {
+            sender.try_send(i).expect("BUG: send failed");
+        }
+        assert_eq!(receiver.try_recv(), Some(3));
+        assert_eq!(receiver.try_recv(), Some(4));
+        assert_eq!(receiver.try_recv(), None);
+        assert_eq!(
+            receiver.stats(),
+            Stats {
+                capacity: 2,
+                len: 0,
+                high_watermark: 2,
+                sent: 5,
+                received: 2,
+                dropped: 3,
+                rejected: 0,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fail() {
+        let (sender, mut receiver) = bounded("test", 1, OverflowPolicy::Fail);
+        sender.send(1).await.expect("BUG: send failed");
+        assert_eq!(sender.send(2).await, Err(SendError::Full(2)));
+        assert_eq!(sender.try_send(3), Err(SendError::Full(3)));
+        assert_eq!(sender.stats().rejected, 2);
+        assert_eq!(sender.stats().occupancy(), 1.0);
+
+        // the stream ends after the queue has been drained
+        drop(sender);
+        assert_eq!(receiver.next().await, Some(1));
+        assert_eq!(receiver.next().await, None);
+    }
+
+    #[tokio::test]
+    async fn test_backpressure() {
+        let (sender, mut receiver) = bounded("test", 1, OverflowPolicy::Backpressure);
+        sender.send(1).await.expect("BUG: send failed");
+        assert!(sender.try_send(2).unwrap_err().is_full());
+
+        let mut blocked_send = sender.send(3).boxed();
+        assert!(futures::poll!(&mut blocked_send).is_pending());
+        assert_eq!(receiver.next().await, Some(1));
+        assert_eq!(futures::poll!(&mut blocked_send), Poll::Ready(Ok(())));
+        assert_eq!(receiver.try_recv(), Some(3));
+
+        // waiting sender is released when the receiver goes away
+        sender.send(4).await.expect("BUG: send failed");
+        let mut blocked_send = sender.send(5).boxed();
+        assert!(futures::poll!(&mut blocked_send).is_pending());
+        drop(receiver);
+        assert_eq!(
+            futures::poll!(&mut blocked_send),
+            Poll::Ready(Err(SendError::Closed(5)))
+        );
+        assert!(sender.is_closed());
+    }
+
+    #[tokio::test]
+    async fn test_watch() {
+        let (sender, mut receiver) = watch::channel(0);
+        // the initial value is observed first
+        assert_eq!(receiver.next().await, Some(0));
+
+        sender.broadcast(1).expect("BUG: broadcast failed");
+        sender.broadcast(2).expect("BUG: broadcast failed");
+        sender.broadcast(3).expect("BUG: broadcast failed");
+        assert_eq!(*receiver.borrow(), 3);
+        assert_eq!(receiver.next().await, Some(3));
+        assert_eq!(
+            sender.stats(),
+            watch::Stats {
+                updates: 3,
+                skipped: 2,
+            }
+        );
+    }
+}
@@ -0,0 +1,169 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Source of time for timeout and deadline logic
+//!
+//! Code which measures time or sleeps should do so through a `Clock` passed to it rather than
+//! calling `Instant::now()` or `runtime::delay_for()` directly. The `SystemClock` is used in
+//! production while tests use `ManualClock` which only moves forward when it is explicitly
+//! advanced, so the tested logic runs deterministically without any real sleeps.
+
+use crate::runtime;
+
+use futures::channel::oneshot;
+use futures::future::{self, BoxFuture};
+use futures::prelude::*;
+
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+pub trait Clock: Debug + Send + Sync {
+    /// Returns current time
+    fn now(&self) -> Instant;
+
+    /// Returns future which completes when `duration` has elapsed on this clock
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()>;
+
+    /// Returns the instant when `timeout` starting now elapses
+    fn deadline(&self, timeout: Duration) -> Instant {
+        self.now() + timeout
+    }
+}
+
+pub type DynClock = Arc<dyn Clock>;
+
+/// Returns shared instance of the real clock
+pub fn system() -> DynClock {
+    Arc::new(SystemClock)
+}
+
+/// Real time clock backed by the async runtime timer
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        runtime::delay_for(duration).boxed()
+    }
+}
+
+#[derive(Debug)]
+struct ManualClockInner {
+    now: Instant,
+    /// Pending sleeps with the time of their completion
+    sleepers: Vec<(Instant, oneshot::Sender<()>)>,
+}
+
+/// Clock for tests which stands still until it is moved forward with `advance()`
+#[derive(Debug)]
+pub struct ManualClock {
+    inner: Mutex<ManualClockInner>,
+}
+
+impl ManualClock {
+    pub fn new() -> Self {
+        Self {
+            inner: Mutex::new(ManualClockInner {
+                now: Instant::now(),
+                sleepers: vec![],
+            }),
+        }
+    }
+
+    /// Moves the clock forward by `duration` and completes all sleeps which have elapsed
+    pub fn advance(&self, duration: Duration) {
+        let mut inner = self.inner.lock().expect("BUG: cannot lock manual clock");
+        inner.now += duration;
+        let now = inner.now;
+        let (elapsed, pending) = inner
+            .sleepers
+            .drain(..)
+            .partition(|(deadline, _)| *deadline <= now);
+        inner.sleepers = pending;
+        for (_, sender) in elapsed {
+            // the sleep future may have been dropped in the meantime
+            let _ = sender.send(());
+        }
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.inner
+            .lock()
+            .expect("BUG: cannot lock manual clock")
+            .now
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        let mut inner = self.inner.lock().expect("BUG: cannot lock manual clock");
+        if duration == Duration::from_secs(0) {
+            return future::ready(()).boxed();
+        }
+        let (sender, receiver) = oneshot::channel();
+        let deadline = inner.now + duration;
+        inner.sleepers.push((deadline, sender));
+        // the sleep also completes when the clock is dropped
+        receiver.map(|_| ()).boxed()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use futures::task::Poll;
+
+    #[tokio::test]
+    async fn test_manual_clock() {
+        let clock = ManualClock::new();
+        let started = clock.now();
+        assert_eq!(
+            clock.deadline(Duration::from_secs(10)),
+            started + Duration::from_secs(10)
+        );
+
+        let mut short_sleep = clock.sleep(Duration::from_secs(1));
+        let mut long_sleep = clock.sleep(Duration::from_secs(10));
+        assert_eq!(futures::poll!(&mut short_sleep), Poll::Pending);
+
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(clock.now(), started + Duration::from_secs(5));
+        assert_eq!(futures::poll!(&mut short_sleep), Poll::Ready(()));
+        assert_eq!(futures::poll!(&mut long_sleep), Poll::Pending);
+
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(futures::poll!(&mut long_sleep), Poll::Ready(()));
+        clock.sleep(Duration::from_secs(0)).await;
+    }
+}
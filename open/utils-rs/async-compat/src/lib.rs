@@ -26,15 +26,20 @@ pub use futures;
 pub use tokio;
 pub use tokio_util;
 
+pub mod channel;
+pub mod clock;
+pub mod runtime;
+pub mod task;
+
 /// A general async prelude.
 ///
 /// Re-exports `futures::prelude::*`, along with `tokio`, `tokio_util`
 /// and `FutureExt` (custom extensions).
 pub mod prelude {
-    pub use super::{bytes, futures, tokio, tokio_util, FutureExt as _};
+    pub use super::{bytes, futures, runtime, tokio, tokio_util, FutureExt as _};
 
+    pub use super::runtime::io::{AsyncBufRead, AsyncRead, AsyncWrite};
     pub use futures::prelude::*;
-    pub use tokio::io::{AsyncBufRead, AsyncRead, AsyncWrite};
     pub use tokio::prelude::*;
 
     pub use stream_cancel::{StreamExt as _, Tripwire};
@@ -51,11 +56,12 @@ use std::sync::Once;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
+use crate::runtime::{JoinError, JoinHandle};
+
 use futures::prelude::*;
 use stream_cancel::Trigger;
+use tokio::signal;
 use tokio::sync::{mpsc, oneshot};
-use tokio::task::{JoinError, JoinHandle};
-use tokio::{signal, time};
 
 /// This registers a customized panic hook with the stdlib.
 /// The customized panic hook does the same thing as the default
@@ -64,7 +70,9 @@ use tokio::{signal, time};
 ///
 /// This means that a panic in Tokio threadpool worker thread
 /// will bring down the whole program as if the panic
-/// occured on the main thread.
+/// occured on the main thread. The only exception are panics in tasks
+/// spawned with `task::spawn()` once the application has taken their
+/// failure channel, see the `task` module.
 ///
 /// This function can be called any number of times,
 /// but the hook will be set only on the first call.
@@ -77,7 +85,9 @@ pub fn setup_panic_handling() {
 
         let our_hook = move |pi: &PanicInfo| {
             default_hook(pi);
-            process::abort();
+            if !task::is_panic_handled() {
+                process::abort();
+            }
         };
 
         panic::set_hook(Box::new(our_hook));
@@ -89,12 +99,12 @@ pub fn setup_panic_handling() {
 pub trait FutureExt: Future {
     /// Require a `Future` to complete before the specified duration has elapsed.
     ///
-    /// This is a chainable alias for `tokio::time::timeout()`.
-    fn timeout(self, timeout: Duration) -> time::Timeout<Self>
+    /// This is a chainable alias for `runtime::timeout()`.
+    fn timeout(self, timeout: Duration) -> runtime::Timeout<Self>
     where
         Self: Sized,
     {
-        time::timeout(timeout, self)
+        runtime::timeout(timeout, self)
     }
 }
 
@@ -262,7 +272,7 @@ impl HaltHandle {
         FN: FnOnce(Tripwire) -> FT,
     {
         let ft = f(self.tripwire.clone());
-        let task = tokio::spawn(ft);
+        let task = runtime::spawn(ft);
 
         // Add the task join handle to tasks_tx (used by join()).
         // Errors are ignored here - send() on an unbounded channel
@@ -306,7 +316,7 @@ impl HaltHandle {
             .compare_and_swap(false, true, Ordering::SeqCst)
         {
             let ft = f(self);
-            tokio::spawn(async move {
+            runtime::spawn(async move {
                 signal::ctrl_c().await.expect("Error listening for SIGINT");
                 ft.await;
             });
@@ -0,0 +1,423 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Thin abstraction over the async runtime
+//!
+//! Consumers should spawn tasks, wait on timers and use IO traits only through this module so
+//! that switching to a different runtime (or its new major version) means changing just this
+//! module. The items keep the semantics of their tokio counterparts.
+//!
+//! Tokio backs the module by default. With feature `async-std` tasks are run by the async-std
+//! executor and timers are the async-std ones. Networking, signals and codecs are still built on
+//! tokio so its reactor is then driven by a background thread and every task spawned through
+//! this module (and the future passed to `block_on`) is polled inside of its context.
+
+use futures::prelude::*;
+use futures::task::{Context, Poll};
+use stream_cancel::Tripwire;
+
+use std::pin::Pin;
+use std::time::{Duration, Instant};
+
+pub use backend::{
+    block_on, delay_for, delay_until, spawn, spawn_blocking, timeout, Elapsed, JoinError,
+    JoinHandle, Timeout,
+};
+
+/// IO traits used by all codecs and connections
+pub mod io {
+    pub use tokio::io::{AsyncBufRead, AsyncRead, AsyncWrite};
+}
+
+/// Networking primitives
+pub mod net {
+    pub use tokio::net::{TcpListener, TcpStream, UdpSocket};
+}
+
+#[cfg(not(feature = "async-std"))]
+mod backend {
+    use futures::prelude::*;
+    use futures::task::{Context, Poll};
+
+    use std::time::{Duration, Instant};
+
+    /// Handle for awaiting result of a spawned task
+    pub type JoinHandle<T> = tokio::task::JoinHandle<T>;
+
+    /// Error returned by awaiting `JoinHandle` when the task has panicked or has been cancelled
+    pub type JoinError = tokio::task::JoinError;
+
+    /// Future returned by `timeout`
+    pub type Timeout<F> = tokio::time::Timeout<F>;
+
+    /// Error returned by `Timeout` when the duration has elapsed before the future completed
+    pub type Elapsed = tokio::time::Elapsed;
+
+    /// Runs `future` to completion on a new multi-threaded runtime
+    pub fn block_on<F: Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new()
+            .threaded_scheduler()
+            .enable_all()
+            .build()
+            .expect("BUG: cannot create tokio runtime")
+            .block_on(future)
+    }
+
+    /// Spawns a new asynchronous task running in the background
+    pub fn spawn<T>(task: T) -> JoinHandle<T::Output>
+    where
+        T: Future + Send + 'static,
+        T::Output: Send + 'static,
+    {
+        tokio::spawn(task)
+    }
+
+    /// Runs blocking function `f` on a thread dedicated to blocking operations
+    pub fn spawn_blocking<F, R>(f: F) -> JoinHandle<R>
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        tokio::task::spawn_blocking(f)
+    }
+
+    /// Waits until `duration` has elapsed
+    pub async fn delay_for(duration: Duration) {
+        tokio::time::delay_for(duration).await
+    }
+
+    /// Waits until `deadline` is reached
+    pub async fn delay_until(deadline: Instant) {
+        tokio::time::delay_until(deadline.into()).await
+    }
+
+    /// Requires `future` to complete before `duration` has elapsed
+    pub fn timeout<F: Future>(duration: Duration, future: F) -> Timeout<F> {
+        tokio::time::timeout(duration, future)
+    }
+
+    #[derive(Debug)]
+    pub(super) struct Ticker(tokio::time::Interval);
+
+    impl Ticker {
+        pub fn new(period: Duration) -> Self {
+            Self(tokio::time::interval_at(
+                tokio::time::Instant::now() + period,
+                period,
+            ))
+        }
+
+        pub fn poll_tick(&mut self, cx: &mut Context<'_>) -> Poll<Instant> {
+            self.0.poll_tick(cx).map(|instant| instant.into_std())
+        }
+    }
+}
+
+#[cfg(feature = "async-std")]
+mod backend {
+    use crate::task::panic_message;
+
+    use futures::future::BoxFuture;
+    use futures::prelude::*;
+    use futures::ready;
+    use futures::task::{Context, Poll};
+    use once_cell::sync::Lazy;
+
+    use std::error::Error as StdError;
+    use std::fmt;
+    use std::io;
+    use std::panic::AssertUnwindSafe;
+    use std::pin::Pin;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    /// Tokio runtime which only drives the reactor (networking, signals) in a background thread
+    static TOKIO: Lazy<tokio::runtime::Handle> = Lazy::new(|| {
+        let mut runtime = tokio::runtime::Builder::new()
+            .basic_scheduler()
+            .enable_all()
+            .build()
+            .expect("BUG: cannot create tokio reactor");
+        let handle = runtime.handle().clone();
+        thread::Builder::new()
+            .name("tokio-reactor".to_string())
+            .spawn(move || runtime.block_on(future::pending::<()>()))
+            .expect("BUG: cannot start tokio reactor thread");
+        handle
+    });
+
+    /// Future polled inside of the tokio context so that it can create tokio IO resources
+    struct EnterTokio<F> {
+        inner: Pin<Box<F>>,
+    }
+
+    impl<F: Future> EnterTokio<F> {
+        fn new(future: F) -> Self {
+            Self {
+                inner: Box::pin(future),
+            }
+        }
+    }
+
+    impl<F: Future> Future for EnterTokio<F> {
+        type Output = F::Output;
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+            TOKIO.enter(|| self.inner.as_mut().poll(cx))
+        }
+    }
+
+    /// Handle for awaiting result of a spawned task
+    pub struct JoinHandle<T> {
+        inner: async_std::task::JoinHandle<Result<T, JoinError>>,
+    }
+
+    impl<T> Future for JoinHandle<T> {
+        type Output = Result<T, JoinError>;
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+            self.inner.poll_unpin(cx)
+        }
+    }
+
+    impl<T> fmt::Debug for JoinHandle<T> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_struct("JoinHandle").finish()
+        }
+    }
+
+    /// Error returned by awaiting `JoinHandle` when the task has panicked
+    #[derive(Debug)]
+    pub struct JoinError {
+        message: String,
+    }
+
+    impl fmt::Display for JoinError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "task panicked: {}", self.message)
+        }
+    }
+
+    impl StdError for JoinError {}
+
+    /// Future returned by `timeout`
+    pub struct Timeout<F> {
+        future: Pin<Box<F>>,
+        delay: BoxFuture<'static, ()>,
+    }
+
+    impl<F: Future> Future for Timeout<F> {
+        type Output = Result<F::Output, Elapsed>;
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+            if let Poll::Ready(output) = self.future.as_mut().poll(cx) {
+                return Poll::Ready(Ok(output));
+            }
+            self.delay.as_mut().poll(cx).map(|_| Err(Elapsed(())))
+        }
+    }
+
+    /// Error returned by `Timeout` when the duration has elapsed before the future completed
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Elapsed(());
+
+    impl fmt::Display for Elapsed {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "deadline has elapsed")
+        }
+    }
+
+    impl StdError for Elapsed {}
+
+    impl From<Elapsed> for io::Error {
+        fn from(e: Elapsed) -> Self {
+            io::Error::new(io::ErrorKind::TimedOut, e)
+        }
+    }
+
+    /// Runs `future` to completion on the async-std executor
+    pub fn block_on<F: Future>(future: F) -> F::Output {
+        async_std::task::block_on(EnterTokio::new(future))
+    }
+
+    /// Spawns a new asynchronous task running in the background
+    pub fn spawn<T>(task: T) -> JoinHandle<T::Output>
+    where
+        T: Future + Send + 'static,
+        T::Output: Send + 'static,
+    {
+        let task = AssertUnwindSafe(EnterTokio::new(task))
+            .catch_unwind()
+            .map(|result| {
+                result.map_err(|payload| JoinError {
+                    message: panic_message(payload),
+                })
+            });
+        JoinHandle {
+            inner: async_std::task::spawn(task),
+        }
+    }
+
+    /// Runs blocking function `f` on a thread dedicated to blocking operations
+    pub fn spawn_blocking<F, R>(f: F) -> JoinHandle<R>
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        spawn(async_std::task::spawn_blocking(f))
+    }
+
+    /// Waits until `duration` has elapsed
+    pub async fn delay_for(duration: Duration) {
+        async_std::task::sleep(duration).await
+    }
+
+    /// Waits until `deadline` is reached
+    pub async fn delay_until(deadline: Instant) {
+        delay_for(deadline.saturating_duration_since(Instant::now())).await
+    }
+
+    /// Requires `future` to complete before `duration` has elapsed
+    pub fn timeout<F: Future>(duration: Duration, future: F) -> Timeout<F> {
+        Timeout {
+            future: Box::pin(future),
+            delay: delay_for(duration).boxed(),
+        }
+    }
+
+    pub(super) struct Ticker {
+        period: Duration,
+        deadline: Instant,
+        delay: BoxFuture<'static, ()>,
+    }
+
+    impl Ticker {
+        pub fn new(period: Duration) -> Self {
+            let deadline = Instant::now() + period;
+            Self {
+                period,
+                deadline,
+                delay: delay_until(deadline).boxed(),
+            }
+        }
+
+        pub fn poll_tick(&mut self, cx: &mut Context<'_>) -> Poll<Instant> {
+            ready!(self.delay.as_mut().poll(cx));
+            let tick = self.deadline;
+            // next deadline is derived from the previous one so that the ticks do not drift
+            self.deadline += self.period;
+            self.delay = delay_until(self.deadline).boxed();
+            Poll::Ready(tick)
+        }
+    }
+
+    impl fmt::Debug for Ticker {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_struct("Ticker")
+                .field("period", &self.period)
+                .field("deadline", &self.deadline)
+                .finish()
+        }
+    }
+}
+
+/// Stream of ticks evenly spaced by `period`. Unlike repeated `delay_for` it does not drift when
+/// the work done between ticks takes some time, and it can be cancelled with a `Tripwire`
+/// instead of selecting on a separate termination future.
+#[derive(Debug)]
+pub struct Interval {
+    inner: backend::Ticker,
+    tripwire: Option<Tripwire>,
+}
+
+impl Interval {
+    /// Creates an interval whose first tick completes after one `period`
+    pub fn new(period: Duration) -> Self {
+        Self {
+            inner: backend::Ticker::new(period),
+            tripwire: None,
+        }
+    }
+
+    /// Ends the interval when `tripwire` is triggered
+    pub fn with_tripwire(mut self, tripwire: Tripwire) -> Self {
+        self.tripwire = Some(tripwire);
+        self
+    }
+
+    /// Waits for the next tick. Returns `None` when the interval has been cancelled.
+    pub async fn tick(&mut self) -> Option<Instant> {
+        self.next().await
+    }
+}
+
+impl Stream for Interval {
+    type Item = Instant;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if let Some(tripwire) = self.tripwire.as_mut() {
+            if let Poll::Ready(true) = Pin::new(tripwire).poll(cx) {
+                return Poll::Ready(None);
+            }
+        }
+        self.inner.poll_tick(cx).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_spawn_and_delay() {
+        let started = Instant::now();
+        let result = spawn(async {
+            delay_for(Duration::from_millis(10)).await;
+            42
+        })
+        .await
+        .expect("task failed");
+        assert_eq!(result, 42);
+        assert!(started.elapsed() >= Duration::from_millis(10));
+
+        delay_until(Instant::now() + Duration::from_millis(10)).await;
+        timeout(Duration::from_millis(10), future::pending::<()>())
+            .await
+            .expect_err("timeout expected");
+    }
+
+    #[tokio::test]
+    async fn test_interval() {
+        let period = Duration::from_millis(10);
+        let started = Instant::now();
+        let mut interval = Interval::new(period);
+        interval.tick().await.expect("interval cancelled");
+        interval.tick().await.expect("interval cancelled");
+        assert!(started.elapsed() >= period * 2);
+
+        let (trigger, tripwire) = Tripwire::new();
+        let mut interval = Interval::new(period).with_tripwire(tripwire);
+        interval.tick().await.expect("interval cancelled");
+        drop(trigger);
+        assert_eq!(interval.tick().await, None);
+    }
+}
@@ -0,0 +1,308 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Named tasks with panic monitoring
+//!
+//! Tasks spawned with `spawn()` or `spawn_supervised()` catch panics of their futures and report
+//! them as `TaskFailure` to the central failure channel. The application takes the channel once
+//! with `take_failures()` and decides what to do (e.g. halt the whole miner) instead of leaving
+//! the rest of the program running without the task. Supervised tasks are additionally restarted
+//! according to their `RestartPolicy`.
+//!
+//! The panic hook installed by `setup_panic_handling()` does not abort the program on panics
+//! inside of monitored tasks once the failure channel has been taken.
+
+use crate::runtime::{self, JoinHandle};
+
+use futures::channel::mpsc;
+use futures::future::BoxFuture;
+use futures::prelude::*;
+use futures::task::{Context, Poll};
+use once_cell::sync::Lazy;
+
+use std::any::Any;
+use std::cell::Cell;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Receiver side of the central failure channel
+pub type FailureReceiver = mpsc::UnboundedReceiver<TaskFailure>;
+
+struct Failures {
+    tx: mpsc::UnboundedSender<TaskFailure>,
+    rx: Mutex<Option<FailureReceiver>>,
+    /// Set when the receiver has been taken by the application
+    handled: AtomicBool,
+}
+
+static FAILURES: Lazy<Failures> = Lazy::new(|| {
+    let (tx, rx) = mpsc::unbounded();
+    Failures {
+        tx,
+        rx: Mutex::new(Some(rx)),
+        handled: AtomicBool::new(false),
+    }
+});
+
+thread_local! {
+    /// Number of nested monitored futures which are being polled on this thread
+    static MONITORED: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Takes the receiver of failures of all monitored tasks. It can be taken only once, subsequent
+/// calls return `None`.
+pub fn take_failures() -> Option<FailureReceiver> {
+    let rx = FAILURES
+        .rx
+        .lock()
+        .expect("BUG: cannot lock failure channel")
+        .take();
+    if rx.is_some() {
+        FAILURES.handled.store(true, Ordering::SeqCst);
+    }
+    rx
+}
+
+/// Returns true when the current panic is going to be reported through the failure channel
+pub(crate) fn is_panic_handled() -> bool {
+    MONITORED.with(|monitored| monitored.get() > 0) && FAILURES.handled.load(Ordering::SeqCst)
+}
+
+/// Report of a panicking task
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaskFailure {
+    /// Name of the task given to `spawn()`
+    pub name: String,
+    /// Panic message
+    pub message: String,
+    /// Whether the task is going to be restarted by its supervisor
+    pub restarting: bool,
+}
+
+impl fmt::Display for TaskFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "task '{}' panicked: {}", self.name, self.message)?;
+        if self.restarting {
+            write!(f, " (restarting)")?;
+        }
+        Ok(())
+    }
+}
+
+fn report(failure: TaskFailure) {
+    // The receiver may have been dropped by the application
+    let _ = FAILURES.tx.unbounded_send(failure);
+}
+
+pub(crate) fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic payload".to_string()
+    }
+}
+
+/// Future which resolves to `Err` with panic message when the inner future panics
+pub struct CatchPanic<T> {
+    inner: BoxFuture<'static, T>,
+}
+
+impl<T> Future for CatchPanic<T> {
+    type Output = Result<T, String>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        MONITORED.with(|monitored| monitored.set(monitored.get() + 1));
+        let result = panic::catch_unwind(AssertUnwindSafe(|| self.inner.as_mut().poll(cx)));
+        MONITORED.with(|monitored| monitored.set(monitored.get() - 1));
+        match result {
+            Ok(poll) => poll.map(Ok),
+            Err(payload) => Poll::Ready(Err(panic_message(payload))),
+        }
+    }
+}
+
+/// Wraps `future` so that its panic is returned as an error
+pub fn catch_panic<F>(future: F) -> CatchPanic<F::Output>
+where
+    F: Future + Send + 'static,
+{
+    CatchPanic {
+        inner: future.boxed(),
+    }
+}
+
+/// Spawns a task named `name` which reports its panic to the failure channel. The join handle
+/// resolves to `None` when the task has panicked.
+pub fn spawn<F>(name: impl Into<String>, future: F) -> JoinHandle<Option<F::Output>>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    let name = name.into();
+    runtime::spawn(async move {
+        match catch_panic(future).await {
+            Ok(output) => Some(output),
+            Err(message) => {
+                report(TaskFailure {
+                    name,
+                    message,
+                    restarting: false,
+                });
+                None
+            }
+        }
+    })
+}
+
+/// Determines how many times a panicking supervised task is restarted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RestartPolicy {
+    /// Maximum number of restarts, `None` means the task is restarted forever
+    pub max_restarts: Option<u32>,
+    /// Delay before the task is started again
+    pub delay: Duration,
+}
+
+impl RestartPolicy {
+    pub const DEFAULT_DELAY: Duration = Duration::from_secs(1);
+
+    /// The task is never restarted
+    pub fn never() -> Self {
+        Self {
+            max_restarts: Some(0),
+            delay: Self::DEFAULT_DELAY,
+        }
+    }
+
+    /// The task is restarted every time it panics
+    pub fn always() -> Self {
+        Self {
+            max_restarts: None,
+            delay: Self::DEFAULT_DELAY,
+        }
+    }
+
+    fn can_restart(&self, restarts: u32) -> bool {
+        match self.max_restarts {
+            Some(max) => restarts < max,
+            None => true,
+        }
+    }
+}
+
+/// Spawns a task named `name` running the future created by `f` which is created and started
+/// again after it panics according to `policy`. The task ends when the future completes or when
+/// it panics and cannot be restarted anymore.
+pub fn spawn_supervised<F, FN>(
+    name: impl Into<String>,
+    policy: RestartPolicy,
+    mut f: FN,
+) -> JoinHandle<()>
+where
+    F: Future<Output = ()> + Send + 'static,
+    FN: FnMut() -> F + Send + 'static,
+{
+    let name = name.into();
+    runtime::spawn(async move {
+        let mut restarts = 0;
+        while let Err(message) = catch_panic(f()).await {
+            let restarting = policy.can_restart(restarts);
+            report(TaskFailure {
+                name: name.clone(),
+                message,
+                restarting,
+            });
+            if !restarting {
+                break;
+            }
+            restarts += 1;
+            runtime::delay_for(policy.delay).await;
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_spawn_monitored() {
+        let mut failures = take_failures().expect("BUG: failure channel already taken");
+        assert!(take_failures().is_none());
+
+        assert_eq!(
+            spawn("ok", async { 1 }).await.expect("join failed"),
+            Some(1)
+        );
+        let result = spawn("failing", async { panic!("task failed") }).await;
+        assert_eq!(result.expect("join failed"), None::<()>);
+        assert_eq!(
+            failures.next().await,
+            Some(TaskFailure {
+                name: "failing".to_string(),
+                message: "task failed".to_string(),
+                restarting: false,
+            })
+        );
+
+        // supervised task is restarted until it succeeds or runs out of restarts
+        let runs = Arc::new(AtomicUsize::new(0));
+        let task_runs = runs.clone();
+        let policy = RestartPolicy {
+            max_restarts: Some(2),
+            delay: Duration::from_millis(1),
+        };
+        spawn_supervised("supervised", policy, move || {
+            let run = task_runs.fetch_add(1, Ordering::SeqCst);
+            async move {
+                if run < 5 {
+                    panic!("run {} failed", run);
+                }
+            }
+        })
+        .await
+        .expect("join failed");
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+        let restarting: Vec<_> = failures
+            .take(3)
+            .map(|failure| (failure.message, failure.restarting))
+            .collect()
+            .await;
+        assert_eq!(
+            restarting,
+            vec![
+                ("run 0 failed".to_string(), true),
+                ("run 1 failed".to_string(), true),
+                ("run 2 failed".to_string(), false),
+            ]
+        );
+    }
+}
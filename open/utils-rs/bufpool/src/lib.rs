@@ -0,0 +1,290 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Pools of byte buffers for reducing allocator pressure
+//!
+//! A `Buf` taken from `BufPool` derefs to `BytesMut` and returns its allocation back to the pool
+//! when it is dropped. The pool is lock-free: each slot is an atomic pointer to an idle buffer
+//! which is taken by swapping it for null and returned by a compare-and-swap of null. When no
+//! slot holds a buffer a new one is allocated and when no slot is free the returned buffer is
+//! freed.
+//!
+//! The typical usage is to fill a pooled buffer and `split()` the data off. The split part shares
+//! the allocation with the pooled buffer and once it is dropped, the allocation is reused without
+//! copying by the next `BufPool::get()`.
+//!
+//! Shared pools sized for stratum frames and for command responses of mining hardware are
+//! provided by `frame_pool()` and `command_pool()`. Work sent to the hardware does not go
+//! through a pool, each work TX path keeps a single frame buffer which it reuses.
+
+use ii_async_compat::bytes;
+
+use bytes::BytesMut;
+use lazy_static::lazy_static;
+
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, Ordering};
+use std::sync::Arc;
+
+/// Capacity of buffers in `frame_pool()`, it covers all but exceptionally big stratum frames
+pub const FRAME_BUF_CAPACITY: usize = 4096;
+/// Capacity of buffers in `command_pool()`, it covers responses to chip commands
+pub const COMMAND_BUF_CAPACITY: usize = 64;
+
+/// Number of idle buffers kept by the shared pools
+const SHARED_POOL_SIZE: usize = 32;
+
+/// Buffers which have grown over this multiple of the pool buffer capacity are not returned to
+/// the pool so that an occasional big message does not keep the memory allocated
+const MAX_GROWTH_FACTOR: usize = 4;
+
+lazy_static! {
+    static ref FRAME_POOL: BufPool = BufPool::new(FRAME_BUF_CAPACITY, SHARED_POOL_SIZE);
+    static ref COMMAND_POOL: BufPool = BufPool::new(COMMAND_BUF_CAPACITY, SHARED_POOL_SIZE);
+}
+
+/// Shared pool for buffers used by protocol codecs
+pub fn frame_pool() -> &'static BufPool {
+    &FRAME_POOL
+}
+
+/// Shared pool for buffers with command responses received from the mining hardware
+pub fn command_pool() -> &'static BufPool {
+    &COMMAND_POOL
+}
+
+#[derive(Debug)]
+struct Inner {
+    /// Idle buffers owned by the pool (created by `Box::into_raw`), an empty slot is null
+    slots: Vec<AtomicPtr<BytesMut>>,
+    /// Minimal capacity of each buffer returned by `get()`
+    buf_capacity: usize,
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        for slot in self.slots.iter_mut() {
+            let buf = *slot.get_mut();
+            if !buf.is_null() {
+                drop(unsafe { Box::from_raw(buf) });
+            }
+        }
+    }
+}
+
+/// Bounded pool of byte buffers. Cloning the pool is cheap and all clones share the buffers.
+#[derive(Debug, Clone)]
+pub struct BufPool {
+    inner: Arc<Inner>,
+}
+
+impl BufPool {
+    /// Creates a pool of buffers with capacity of at least `buf_capacity` bytes which keeps up to
+    /// `size` idle buffers
+    pub fn new(buf_capacity: usize, size: usize) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                slots: (0..size).map(|_| AtomicPtr::new(ptr::null_mut())).collect(),
+                buf_capacity,
+            }),
+        }
+    }
+
+    pub fn buf_capacity(&self) -> usize {
+        self.inner.buf_capacity
+    }
+
+    /// Number of idle buffers currently held by the pool
+    pub fn idle_count(&self) -> usize {
+        self.inner
+            .slots
+            .iter()
+            .filter(|slot| !slot.load(Ordering::Relaxed).is_null())
+            .count()
+    }
+
+    /// Returns an empty buffer with capacity of at least `buf_capacity()` bytes. An idle buffer
+    /// is reused when there is any, otherwise a new one is allocated.
+    pub fn get(&self) -> Buf {
+        let mut buf = self
+            .inner
+            .slots
+            .iter()
+            .filter(|slot| !slot.load(Ordering::Relaxed).is_null())
+            .map(|slot| slot.swap(ptr::null_mut(), Ordering::Acquire))
+            .find(|buf| !buf.is_null())
+            // The buffer has been owned by the slot which has just been emptied
+            .map(|buf| unsafe { Box::from_raw(buf) })
+            .unwrap_or_else(|| Box::new(BytesMut::new()));
+        // Reclaims the whole allocation when the parts split off of the buffer have already been
+        // dropped, otherwise allocates a new one
+        buf.reserve(self.inner.buf_capacity);
+        Buf {
+            buf: Some(buf),
+            pool: self.clone(),
+        }
+    }
+
+    fn put(&self, mut buf: Box<BytesMut>) {
+        if buf.capacity() > self.inner.buf_capacity * MAX_GROWTH_FACTOR {
+            return;
+        }
+        buf.clear();
+        let buf = Box::into_raw(buf);
+        for slot in self.inner.slots.iter() {
+            if slot
+                .compare_exchange(ptr::null_mut(), buf, Ordering::Release, Ordering::Relaxed)
+                .is_ok()
+            {
+                return;
+            }
+        }
+        // The pool is full, the buffer is freed
+        drop(unsafe { Box::from_raw(buf) });
+    }
+}
+
+/// Buffer borrowed from `BufPool`
+pub struct Buf {
+    /// It is `None` only after the buffer has been detached from the pool. The buffer is boxed
+    /// so that it can be stored in an atomic slot of the pool without another allocation.
+    buf: Option<Box<BytesMut>>,
+    pool: BufPool,
+}
+
+impl Buf {
+    /// Detaches the buffer from the pool so that its allocation is not reused
+    pub fn into_inner(mut self) -> BytesMut {
+        *self.buf.take().expect("BUG: missing pooled buffer")
+    }
+}
+
+impl Deref for Buf {
+    type Target = BytesMut;
+
+    fn deref(&self) -> &BytesMut {
+        self.buf.as_ref().expect("BUG: missing pooled buffer")
+    }
+}
+
+impl DerefMut for Buf {
+    fn deref_mut(&mut self) -> &mut BytesMut {
+        self.buf.as_mut().expect("BUG: missing pooled buffer")
+    }
+}
+
+impl Drop for Buf {
+    fn drop(&mut self) {
+        if let Some(buf) = self.buf.take() {
+            self.pool.put(buf);
+        }
+    }
+}
+
+/// Formats the contents of the buffer (honoring e.g. hexadecimal flags)
+impl fmt::Debug for Buf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self[..], f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_reuse() {
+        let pool = BufPool::new(64, 2);
+        let mut buf = pool.get();
+        assert_eq!(buf.len(), 0);
+        assert!(buf.capacity() >= 64);
+        buf.extend_from_slice(&[1, 2, 3]);
+        let ptr = buf.as_ptr();
+        drop(buf);
+        assert_eq!(pool.idle_count(), 1);
+
+        // the same allocation is returned empty
+        let buf = pool.get();
+        assert_eq!(buf.len(), 0);
+        assert_eq!(buf.as_ptr(), ptr);
+        assert_eq!(pool.idle_count(), 0);
+
+        // detached buffer is not returned to the pool
+        drop(buf.into_inner());
+        assert_eq!(pool.idle_count(), 0);
+    }
+
+    #[test]
+    fn test_split_reuse() {
+        let pool = BufPool::new(64, 2);
+        let mut buf = pool.get();
+        buf.extend_from_slice(&[1, 2, 3]);
+        let ptr = buf.as_ptr();
+        let data = buf.split();
+        drop(buf);
+        assert_eq!(&data[..], &[1, 2, 3]);
+
+        // the allocation is reclaimed when the split off data is no longer used
+        drop(data);
+        let buf = pool.get();
+        assert_eq!(buf.as_ptr(), ptr);
+        assert!(buf.capacity() >= 64);
+    }
+
+    #[test]
+    fn test_bounded() {
+        let pool = BufPool::new(64, 2);
+        let bufs: Vec<_> = (0..3).map(|_| pool.get()).collect();
+        drop(bufs);
+        assert_eq!(pool.idle_count(), 2);
+
+        // grown buffers are freed
+        let mut buf = pool.get();
+        buf.reserve(64 * MAX_GROWTH_FACTOR + 1);
+        drop(buf);
+        assert_eq!(pool.idle_count(), 1);
+    }
+
+    #[test]
+    fn test_concurrent() {
+        let pool = BufPool::new(64, 4);
+        let threads: Vec<_> = (0..8)
+            .map(|i| {
+                let pool = pool.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..1000 {
+                        let mut buf = pool.get();
+                        assert_eq!(buf.len(), 0);
+                        buf.extend_from_slice(&[i; 16]);
+                        assert_eq!(&buf[..], &[i; 16]);
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().expect("BUG: thread panicked");
+        }
+        assert!(pool.idle_count() <= 4);
+    }
+}
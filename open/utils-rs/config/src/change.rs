@@ -0,0 +1,124 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Detection of configuration file modifications for hot reload
+
+use ii_async_compat::clock::Clock;
+
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Tracks contents of a configuration file. The contents are compared rather than modification
+/// times because the timestamp granularity of some filesystems is too coarse and because saving
+/// a file without any modification should not trigger a reload.
+#[derive(Debug, Clone)]
+pub struct ChangeDetector {
+    path: PathBuf,
+    /// Hash of the last seen contents, `None` when the file cannot be read
+    fingerprint: Option<u64>,
+}
+
+impl ChangeDetector {
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        let path = path.into();
+        let fingerprint = Self::fingerprint(&path);
+        Self { path, fingerprint }
+    }
+
+    fn fingerprint(path: &Path) -> Option<u64> {
+        let contents = fs::read(path).ok()?;
+        let mut hasher = DefaultHasher::new();
+        contents.hash(&mut hasher);
+        Some(hasher.finish())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns true when the file has been modified, created or removed since the detector has
+    /// been created or since the previous call
+    pub fn has_changed(&mut self) -> bool {
+        let fingerprint = Self::fingerprint(&self.path);
+        if fingerprint == self.fingerprint {
+            return false;
+        }
+        self.fingerprint = fingerprint;
+        true
+    }
+
+    /// Checks the file every `interval` and completes once it has changed
+    pub async fn changed(&mut self, clock: &dyn Clock, interval: Duration) {
+        loop {
+            clock.sleep(interval).await;
+            if self.has_changed() {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use ii_async_compat::clock::ManualClock;
+    use ii_async_compat::futures;
+    use ii_async_compat::prelude::*;
+    use ii_async_compat::tokio;
+
+    use futures::task::Poll;
+
+    #[tokio::test]
+    async fn test_change_detector() {
+        let path =
+            std::env::temp_dir().join(format!("ii-config-change-{}.toml", std::process::id()));
+        fs::write(&path, "a = 1").expect("BUG: cannot write config");
+
+        let mut detector = ChangeDetector::new(&path);
+        assert!(!detector.has_changed());
+        // rewriting the same contents is not a change
+        fs::write(&path, "a = 1").expect("BUG: cannot write config");
+        assert!(!detector.has_changed());
+        fs::write(&path, "a = 2").expect("BUG: cannot write config");
+        assert!(detector.has_changed());
+        assert!(!detector.has_changed());
+
+        let clock = ManualClock::new();
+        {
+            let mut changed = detector
+                .changed(&clock, Duration::from_secs(1))
+                .boxed_local();
+            assert_eq!(futures::poll!(&mut changed), Poll::Pending);
+            clock.advance(Duration::from_secs(1));
+            assert_eq!(futures::poll!(&mut changed), Poll::Pending);
+
+            fs::remove_file(&path).expect("BUG: cannot remove config");
+            clock.advance(Duration::from_secs(1));
+            assert_eq!(futures::poll!(&mut changed), Poll::Ready(()));
+        }
+        assert!(!detector.has_changed());
+    }
+}
@@ -0,0 +1,59 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Errors of configuration loading
+
+use thiserror::Error;
+
+#[derive(Error, Clone, Eq, PartialEq, Debug)]
+pub enum ErrorKind {
+    /// General error used for more specific input/output error.
+    #[error("{0}")]
+    General(String),
+
+    /// Configuration file cannot be read.
+    #[error("cannot read configuration file '{0}'")]
+    Io(String),
+
+    /// Configuration file is not a valid TOML document.
+    #[error("{location}: {message}")]
+    Syntax { location: String, message: String },
+
+    /// Value has a wrong type, is missing or does not pass validation. The location describes
+    /// where the offending value comes from.
+    #[error("{location}: {message}")]
+    Invalid { location: String, message: String },
+}
+
+ii_error::error_type!(ErrorKind);
+
+impl From<&str> for ErrorKind {
+    fn from(info: &str) -> Self {
+        ErrorKind::General(info.to_string())
+    }
+}
+
+impl From<String> for ErrorKind {
+    fn from(info: String) -> Self {
+        ErrorKind::General(info)
+    }
+}
@@ -0,0 +1,377 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Merging of configuration layers and tracking where each value comes from
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::PathBuf;
+
+/// Separator of nested keys in names of environment variables
+pub const ENV_KEY_SEPARATOR: &str = "__";
+
+/// Source of a configuration value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Origin {
+    /// Configuration file with the line and column (both starting at 1) of the value if known
+    File {
+        path: PathBuf,
+        position: Option<(usize, usize)>,
+    },
+    /// Name of the environment variable
+    Env(String),
+    /// Command line option
+    Cli(String),
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Origin::File {
+                path,
+                position: Some((line, column)),
+            } => write!(f, "{}:{}:{}", path.display(), line, column),
+            Origin::File {
+                path,
+                position: None,
+            } => write!(f, "{}", path.display()),
+            Origin::Env(name) => write!(f, "environment variable {}", name),
+            Origin::Cli(option) => write!(f, "command line option {}", option),
+        }
+    }
+}
+
+/// Removes array indexes from a key path, `group[0].pool[1].url` becomes `group.pool.url`
+fn strip_indexes(path: &str) -> String {
+    let mut stripped = String::with_capacity(path.len());
+    let mut in_index = false;
+    for c in path.chars() {
+        match c {
+            '[' => in_index = true,
+            ']' => in_index = false,
+            c if !in_index => stripped.push(c),
+            _ => {}
+        }
+    }
+    stripped
+}
+
+fn join_key(prefix: &str, key: &str) -> String {
+    if prefix.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", prefix, key)
+    }
+}
+
+/// Splits dotted key into its parts and removes quotes around them
+fn split_key(key: &str) -> Vec<&str> {
+    key.split('.')
+        .map(|part| part.trim().trim_matches(|c| c == '"' || c == '\''))
+        .collect()
+}
+
+/// Finds positions of keys and table headers in a TOML document. It is a simple line based scan
+/// which covers the way configuration files are written, keys inside of multi-line values and
+/// inline tables are not located.
+pub(crate) fn scan_positions(text: &str) -> BTreeMap<String, (usize, usize)> {
+    let mut positions = BTreeMap::new();
+    // Index of the latest table in each array of tables
+    let mut array_indexes: BTreeMap<String, usize> = BTreeMap::new();
+    let mut table = String::new();
+
+    for (line_idx, line) in text.lines().enumerate() {
+        let trimmed = line.trim_start();
+        let column = line.len() - trimmed.len() + 1;
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        if trimmed.starts_with('[') {
+            let is_array = trimmed.starts_with("[[");
+            let header = trimmed
+                .trim_start_matches('[')
+                .split(']')
+                .next()
+                .unwrap_or_default();
+            let parts = split_key(header);
+            let (last, parents) = match parts.split_last() {
+                Some(split) => split,
+                None => continue,
+            };
+            // parent tables refer to the latest element of arrays of tables
+            let mut path = String::new();
+            for part in parents {
+                path = join_key(&path, part);
+                if let Some(idx) = array_indexes.get(&path) {
+                    path = format!("{}[{}]", path, idx);
+                }
+            }
+            path = join_key(&path, last);
+            if is_array {
+                let idx = array_indexes
+                    .entry(path.clone())
+                    .and_modify(|idx| *idx += 1)
+                    .or_insert(0);
+                path = format!("{}[{}]", path, idx);
+            }
+            positions
+                .entry(path.clone())
+                .or_insert((line_idx + 1, column));
+            table = path;
+        } else if let Some(eq_pos) = trimmed.find('=') {
+            let mut path = table.clone();
+            for part in split_key(&trimmed[..eq_pos]) {
+                path = join_key(&path, part);
+            }
+            positions.entry(path).or_insert((line_idx + 1, column));
+        }
+    }
+    positions
+}
+
+/// Value from a higher layer than the configuration file
+#[derive(Debug, Clone)]
+pub(crate) struct Override {
+    pub key: String,
+    pub value: toml::Value,
+    /// Original text when the value has been parsed from a string
+    pub raw: Option<String>,
+    pub origin: Origin,
+}
+
+impl Override {
+    /// Interprets `raw` as a TOML value, anything which is not valid TOML is taken as a string
+    pub fn parse(key: &str, raw: &str, origin: Origin) -> Self {
+        let value = format!("value = {}", raw)
+            .parse::<toml::Value>()
+            .ok()
+            .and_then(|mut document| document.as_table_mut()?.remove("value"))
+            .unwrap_or_else(|| toml::Value::String(raw.to_string()));
+        Self {
+            key: key.to_string(),
+            value,
+            raw: Some(raw.to_string()),
+            origin,
+        }
+    }
+
+    /// Creates overrides from environment variables named `<prefix>__<KEY>` where parts of nested
+    /// keys are separated by `__` as well
+    pub fn from_env<I>(prefix: &str, vars: I) -> Vec<Self>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let prefix = format!("{}{}", prefix, ENV_KEY_SEPARATOR);
+        vars.into_iter()
+            .filter_map(|(name, raw)| {
+                let key = name
+                    .strip_prefix(prefix.as_str())?
+                    .split(ENV_KEY_SEPARATOR)
+                    .map(str::to_lowercase)
+                    .collect::<Vec<_>>()
+                    .join(".");
+                if key.is_empty() {
+                    return None;
+                }
+                Some(Self::parse(&key, &raw, Origin::Env(name)))
+            })
+            .collect()
+    }
+
+    /// Stores the value into `document`, missing tables on its path are created
+    pub fn apply(&self, document: &mut toml::Value) -> Result<(), String> {
+        let parts: Vec<_> = self.key.split('.').collect();
+        let (last, parents) = parts.split_last().expect("BUG: empty key");
+        let mut table = document
+            .as_table_mut()
+            .expect("BUG: configuration is not a table");
+        for part in parents {
+            table = table
+                .entry(part.to_string())
+                .or_insert_with(|| toml::Value::Table(Default::default()))
+                .as_table_mut()
+                .ok_or_else(|| format!("cannot set `{}`: `{}` is not a table", self.key, part))?;
+        }
+        table.insert(last.to_string(), self.value.clone());
+        Ok(())
+    }
+}
+
+/// Merged configuration document with origins of its values
+#[derive(Debug, Clone)]
+pub(crate) struct Layers {
+    pub document: toml::Value,
+    origins: BTreeMap<String, Origin>,
+    /// Origin of keys which cannot be located more precisely
+    default_origin: Option<Origin>,
+}
+
+impl Layers {
+    pub fn new() -> Self {
+        Self {
+            document: toml::Value::Table(Default::default()),
+            origins: BTreeMap::new(),
+            default_origin: None,
+        }
+    }
+
+    pub fn set_file(&mut self, path: PathBuf, text: &str, document: toml::Value) {
+        for (key, position) in scan_positions(text) {
+            self.origins.insert(
+                key,
+                Origin::File {
+                    path: path.clone(),
+                    position: Some(position),
+                },
+            );
+        }
+        self.default_origin = Some(Origin::File {
+            path,
+            position: None,
+        });
+        self.document = document;
+    }
+
+    pub fn apply(&mut self, value: &Override) -> Result<(), String> {
+        value.apply(&mut self.document)?;
+        self.origins.insert(value.key.clone(), value.origin.clone());
+        Ok(())
+    }
+
+    /// Returns origin of the value at `path` or of its nearest parent which is known. The path
+    /// may contain array indexes or not.
+    pub fn origin(&self, path: &str) -> Option<&Origin> {
+        let mut path = path;
+        loop {
+            if let Some(origin) = self.origins.get(path) {
+                return Some(origin);
+            }
+            let stripped = strip_indexes(path);
+            if let Some((_, origin)) = self
+                .origins
+                .iter()
+                .find(|(key, _)| strip_indexes(key) == stripped)
+            {
+                return Some(origin);
+            }
+            match path.rfind('.') {
+                Some(pos) => path = &path[..pos],
+                None => return self.default_origin.as_ref(),
+            }
+        }
+    }
+
+    /// Formats location of the value at `path`
+    pub fn location(&self, path: Option<&str>) -> String {
+        path.and_then(|path| self.origin(path))
+            .or(self.default_origin.as_ref())
+            .map(|origin| origin.to_string())
+            .unwrap_or_else(|| "configuration".to_string())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_scan_positions() {
+        let text = "\
+# comment
+[format]
+version = \"1.0\"
+
+[[group]]
+name = \"a\"
+  [[group.pool]]
+  url = \"stratum2+tcp://a\"
+  [[group.pool]]
+  url = \"stratum2+tcp://b\"
+[[group]]
+name = \"b\"
+[hash_chain_global]
+voltage.value = 8.8
+";
+        let positions = scan_positions(text);
+        assert_eq!(positions.get("format"), Some(&(2, 1)));
+        assert_eq!(positions.get("format.version"), Some(&(3, 1)));
+        assert_eq!(positions.get("group[0].name"), Some(&(6, 1)));
+        assert_eq!(positions.get("group[0].pool[1].url"), Some(&(10, 3)));
+        assert_eq!(positions.get("group[1].name"), Some(&(12, 1)));
+        assert_eq!(
+            positions.get("hash_chain_global.voltage.value"),
+            Some(&(14, 1))
+        );
+    }
+
+    #[test]
+    fn test_overrides() {
+        assert_eq!(
+            Override::parse("a", "650", Origin::Cli("--a".into())).value,
+            toml::Value::Integer(650)
+        );
+        assert_eq!(
+            Override::parse("a", "[1, 2]", Origin::Cli("--a".into())).value,
+            toml::Value::Array(vec![1.into(), 2.into()])
+        );
+        assert_eq!(
+            Override::parse("a", "stratum2+tcp://pool", Origin::Cli("--a".into())).value,
+            toml::Value::String("stratum2+tcp://pool".into())
+        );
+
+        let overrides = Override::from_env(
+            "TEST",
+            vec![
+                (
+                    "TEST__HASH_CHAIN__FREQUENCY".to_string(),
+                    "600.5".to_string(),
+                ),
+                ("TEST_LOG".to_string(), "debug".to_string()),
+                ("OTHER__A".to_string(), "1".to_string()),
+            ],
+        );
+        assert_eq!(overrides.len(), 1);
+        assert_eq!(overrides[0].key, "hash_chain.frequency");
+        assert_eq!(overrides[0].value, toml::Value::Float(600.5));
+
+        let mut layers = Layers::new();
+        layers
+            .apply(&overrides[0])
+            .expect("BUG: cannot apply override");
+        assert_eq!(
+            layers.document["hash_chain"]["frequency"],
+            toml::Value::Float(600.5)
+        );
+        assert!(layers
+            .apply(&Override::parse(
+                "hash_chain.frequency.x",
+                "1",
+                Origin::Cli("--x".into())
+            ))
+            .is_err());
+        assert_eq!(
+            layers.location(Some("hash_chain.frequency")),
+            "environment variable TEST__HASH_CHAIN__FREQUENCY"
+        );
+        assert_eq!(layers.location(Some("missing")), "configuration");
+    }
+}
@@ -0,0 +1,425 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Layered configuration loading shared by the binaries
+//!
+//! A `Loader` builds the configuration from up to three layers where each one overrides the
+//! previous one:
+//!
+//! 1. TOML file
+//! 2. environment variables named `<PREFIX>__<KEY>` where parts of nested keys are separated by
+//!    `__` too, e.g. `BOSMINER__HASH_CHAIN_GLOBAL__FREQUENCY` sets `hash_chain_global.frequency`
+//! 3. values set from command line options
+//!
+//! Values coming from the environment and the command line are parsed as TOML values (`650` is an
+//! integer, `true` is a boolean) and anything else is taken as a string. A parsed value is also
+//! tried as a plain string when the target field is a string. The merged document is
+//! deserialized into the resulting type and checked by its `Validate` implementation. Errors point
+//! to the origin of the offending value: position in the file, environment variable or command
+//! line option.
+//!
+//! `Secret` hides sensitive values from debug output and `ChangeDetector` tells when
+//! the configuration file has been modified so that it can be reloaded.
+
+mod change;
+pub mod error;
+mod layer;
+mod secret;
+
+pub use change::ChangeDetector;
+pub use error::{Error, ErrorKind, Result};
+pub use layer::{Origin, ENV_KEY_SEPARATOR};
+pub use secret::Secret;
+
+// reexport common crates
+pub use toml;
+
+use layer::{Layers, Override};
+
+use serde::de::DeserializeOwned;
+
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Constraint violation found by `Validate::validate()`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Invalid {
+    /// Dotted path of the offending value, array elements are addressed as `group[0].pool[1]`
+    pub key: String,
+    pub message: String,
+}
+
+impl Invalid {
+    pub fn new<K: Into<String>, M: Into<String>>(key: K, message: M) -> Self {
+        Self {
+            key: key.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Invalid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} for key `{}`", self.message, self.key)
+    }
+}
+
+/// Checks of configuration which cannot be expressed with types
+pub trait Validate {
+    fn validate(&self) -> std::result::Result<(), Invalid> {
+        Ok(())
+    }
+}
+
+/// Removes position from a TOML error message and returns the key the error relates to
+fn split_toml_error(error: &toml::de::Error) -> (String, Option<String>) {
+    let mut message = error.to_string();
+    if error.line_col().is_some() {
+        if let Some(pos) = message.rfind(" at line ") {
+            message.truncate(pos);
+        }
+    }
+    let key = message
+        .rfind(" for key `")
+        .map(|pos| {
+            message[pos..]
+                .trim_start_matches(" for key `")
+                .trim_end_matches('`')
+        })
+        .map(str::to_string);
+    (message, key)
+}
+
+/// Builder of configuration layers
+#[derive(Debug, Clone, Default)]
+pub struct Loader {
+    file: Option<PathBuf>,
+    file_required: bool,
+    env_prefix: Option<String>,
+    overrides: Vec<Override>,
+}
+
+impl Loader {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Use configuration file at `path` which has to exist
+    pub fn file<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.file = Some(path.into());
+        self.file_required = true;
+        self
+    }
+
+    /// Use configuration file at `path` when it exists
+    pub fn optional_file<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.file = Some(path.into());
+        self.file_required = false;
+        self
+    }
+
+    /// Take overrides from environment variables starting with `prefix`
+    pub fn env_prefix<S: Into<String>>(mut self, prefix: S) -> Self {
+        self.env_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Override `key` with a typed value of command line `option`
+    pub fn set<V: Into<toml::Value>>(mut self, key: &str, value: V, option: &str) -> Self {
+        self.overrides.push(Override {
+            key: key.to_string(),
+            value: value.into(),
+            raw: None,
+            origin: Origin::Cli(option.to_string()),
+        });
+        self
+    }
+
+    /// Override `key` with text of command line `option` which is parsed the same way as
+    /// environment variables
+    pub fn set_raw(mut self, key: &str, raw: &str, option: &str) -> Self {
+        self.overrides
+            .push(Override::parse(key, raw, Origin::Cli(option.to_string())));
+        self
+    }
+
+    pub fn file_path(&self) -> Option<&Path> {
+        self.file.as_deref()
+    }
+
+    /// Returns detector of changes of the configuration file
+    pub fn change_detector(&self) -> Option<ChangeDetector> {
+        self.file.as_ref().map(ChangeDetector::new)
+    }
+
+    /// Merges all layers and converts them to the configuration type
+    pub fn load<T>(&self) -> Result<T>
+    where
+        T: DeserializeOwned + Validate,
+    {
+        self.load_with_env(std::env::vars())
+    }
+
+    fn read_file(&self) -> Result<Layers> {
+        let mut layers = Layers::new();
+        let path = match &self.file {
+            Some(path) => path,
+            None => return Ok(layers),
+        };
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound && !self.file_required => {
+                return Ok(layers)
+            }
+            Err(e) => {
+                return Err(Error::with_source(
+                    ErrorKind::Io(path.display().to_string()),
+                    e,
+                ))
+            }
+        };
+        let document = text.parse::<toml::Value>().map_err(|e| {
+            let (message, _) = split_toml_error(&e);
+            ErrorKind::Syntax {
+                location: Origin::File {
+                    path: path.clone(),
+                    position: e.line_col().map(|(line, column)| (line + 1, column + 1)),
+                }
+                .to_string(),
+                message,
+            }
+        })?;
+        layers.set_file(path.clone(), &text, document);
+        Ok(layers)
+    }
+
+    fn load_with_env<T, I>(&self, vars: I) -> Result<T>
+    where
+        T: DeserializeOwned + Validate,
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let mut layers = self.read_file()?;
+        let mut overrides = match &self.env_prefix {
+            Some(prefix) => Override::from_env(prefix, vars),
+            None => vec![],
+        };
+        overrides.extend(self.overrides.iter().cloned());
+        for value in overrides.iter() {
+            layers.apply(value).map_err(|message| ErrorKind::Invalid {
+                location: value.origin.to_string(),
+                message,
+            })?;
+        }
+
+        let config: T = Self::deserialize(&mut layers, &overrides)?;
+        config.validate().map_err(|invalid| ErrorKind::Invalid {
+            location: layers.location(Some(&invalid.key)),
+            message: invalid.to_string(),
+        })?;
+        Ok(config)
+    }
+
+    fn deserialize<T: DeserializeOwned>(layers: &mut Layers, overrides: &[Override]) -> Result<T> {
+        let mut retried_keys = HashSet::new();
+        loop {
+            // The merged document is serialized so that errors carry the key they relate to
+            let text = toml::to_string(&layers.document).map_err(|e| {
+                Error::with_source(ErrorKind::General("cannot merge configuration".into()), e)
+            })?;
+            let error = match toml::from_str(&text) {
+                Ok(config) => return Ok(config),
+                Err(e) => e,
+            };
+            let (message, key) = split_toml_error(&error);
+
+            // Try the original text of a value from the environment or the command line which
+            // has been parsed to another type than a string
+            let string_override = key
+                .as_ref()
+                .and_then(|key| overrides.iter().rev().find(|value| &value.key == key))
+                .filter(|value| !value.value.is_str() && retried_keys.insert(value.key.clone()))
+                .and_then(|value| {
+                    value.raw.as_ref().map(|raw| Override {
+                        value: toml::Value::String(raw.clone()),
+                        ..value.clone()
+                    })
+                });
+            match string_override {
+                Some(value) => layers
+                    .apply(&value)
+                    .expect("BUG: cannot apply already applied override"),
+                None => {
+                    return Err(ErrorKind::Invalid {
+                        location: layers.location(key.as_deref()),
+                        message,
+                    }
+                    .into())
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use serde::Deserialize;
+
+    #[derive(Deserialize, Debug)]
+    #[serde(deny_unknown_fields)]
+    struct Pool {
+        url: String,
+    }
+
+    #[derive(Deserialize, Debug)]
+    #[serde(deny_unknown_fields)]
+    struct Config {
+        frequency: f64,
+        #[serde(default)]
+        asic_boost: bool,
+        worker: Option<String>,
+        #[serde(rename = "pool", default)]
+        pools: Vec<Pool>,
+    }
+
+    impl Validate for Config {
+        fn validate(&self) -> std::result::Result<(), Invalid> {
+            for (i, pool) in self.pools.iter().enumerate() {
+                if !pool.url.contains("://") {
+                    return Err(Invalid::new(format!("pool[{}].url", i), "missing scheme"));
+                }
+            }
+            Ok(())
+        }
+    }
+
+    fn test_file(name: &str, contents: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("ii-config-{}-{}.toml", name, std::process::id()));
+        fs::write(&path, contents).expect("BUG: cannot write config");
+        path
+    }
+
+    fn vars(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    /// Loads configuration which is expected to be invalid and returns the error location
+    fn error_location(loader: &Loader, env: &[(&str, &str)]) -> String {
+        match loader.load_with_env::<Config, _>(vars(env)) {
+            Ok(config) => panic!("unexpected success {:?}", config),
+            Err(e) => match e.kind() {
+                ErrorKind::Invalid { location, .. } | ErrorKind::Syntax { location, .. } => {
+                    location
+                }
+                kind => panic!("unexpected error {:?}", kind),
+            },
+        }
+    }
+
+    #[test]
+    fn test_layers() {
+        let path = test_file(
+            "layers",
+            "frequency = 650.0\nworker = \"a\"\n\n[[pool]]\nurl = \"stratum+tcp://a:3333\"\n",
+        );
+        let loader = Loader::new().file(&path).env_prefix("TEST");
+
+        let config: Config = loader.load_with_env(vec![]).expect("BUG: load failed");
+        assert_eq!(config.frequency, 650.0);
+        assert_eq!(config.worker.as_deref(), Some("a"));
+        assert_eq!(config.pools[0].url, "stratum+tcp://a:3333");
+
+        // environment overrides the file and command line overrides the environment
+        let config: Config = loader
+            .clone()
+            .set("asic_boost", true, "--asic-boost")
+            .set_raw("frequency", "600", "--frequency")
+            .load_with_env(vars(&[
+                ("TEST__FREQUENCY", "500.5"),
+                ("TEST__ASIC_BOOST", "false"),
+                ("TEST__WORKER", "1234"),
+            ]))
+            .expect("BUG: load failed");
+        assert_eq!(config.frequency, 600.0);
+        assert!(config.asic_boost);
+        // number is accepted for a string field
+        assert_eq!(config.worker.as_deref(), Some("1234"));
+
+        // optional file does not have to exist
+        let _ = fs::remove_file(&path);
+        let config: Config = Loader::new()
+            .optional_file(&path)
+            .set("frequency", 700.0, "--frequency")
+            .load_with_env(vec![])
+            .expect("BUG: load failed");
+        assert_eq!(config.frequency, 700.0);
+        assert!(config.pools.is_empty());
+
+        match Loader::new().file(&path).load::<Config>() {
+            Err(e) => {
+                assert_eq!(e.kind(), ErrorKind::Io(path.display().to_string()));
+                assert!(std::error::Error::source(&e).is_some());
+            }
+            Ok(config) => panic!("unexpected success {:?}", config),
+        }
+    }
+
+    #[test]
+    fn test_error_locations() {
+        let path = test_file(
+            "errors",
+            "frequency = 650.0\n\n[[pool]]\nurl = \"stratum+tcp://a:3333\"\n\n[[pool]]\n  url = \"b:3333\"\n",
+        );
+        let file = path.display().to_string();
+        let loader = Loader::new().file(&path).env_prefix("TEST");
+
+        // validation error of the second pool
+        assert_eq!(error_location(&loader, &[]), format!("{}:7:3", file));
+        let loader = loader.set_raw("pool", "[]", "--no-pools");
+        assert_eq!(
+            error_location(&loader, &[("TEST__FREQUENCY", "fast")]),
+            "environment variable TEST__FREQUENCY"
+        );
+        assert_eq!(
+            error_location(&loader, &[("TEST__FREQUENCY__MHZ", "650")]),
+            "environment variable TEST__FREQUENCY__MHZ"
+        );
+        assert_eq!(
+            error_location(&loader.clone().set("boost", true, "--boost"), &[]),
+            file
+        );
+
+        fs::write(&path, "asic_boost = true\nfrequency = \"fast\"\n").expect("BUG: cannot write");
+        assert_eq!(error_location(&loader, &[]), format!("{}:2:1", file));
+
+        fs::write(&path, "frequency = 650.0\nasic_boost = \n").expect("BUG: cannot write");
+        assert_eq!(error_location(&loader, &[]), format!("{}:2:14", file));
+        let _ = fs::remove_file(path);
+    }
+}
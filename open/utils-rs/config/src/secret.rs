@@ -0,0 +1,90 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Wrapper for sensitive configuration values
+
+use serde::{Deserialize, Serialize};
+
+use std::fmt;
+
+/// Value which is (de)serialized as is but never shows up in debug output, so that passwords do
+/// not end up in logs when a whole configuration structure is printed
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct Secret<T>(T);
+
+impl<T> Secret<T> {
+    pub fn new(value: T) -> Self {
+        Self(value)
+    }
+
+    /// Gives access to the secret value, the only way to read it
+    pub fn expose(&self) -> &T {
+        &self.0
+    }
+
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> From<T> for Secret<T> {
+    fn from(value: T) -> Self {
+        Self(value)
+    }
+}
+
+impl<T> fmt::Debug for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Secret(***)")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Deserialize, Debug)]
+    struct Credentials {
+        user: String,
+        password: Option<Secret<String>>,
+    }
+
+    #[test]
+    fn test_redaction() {
+        let credentials: Credentials =
+            toml::from_str("user = \"miner\"\npassword = \"hunter2\"").expect("BUG: parse failed");
+        assert_eq!(
+            credentials.password.as_ref().map(|p| p.expose().as_str()),
+            Some("hunter2")
+        );
+
+        let debug = format!("{:?}", credentials);
+        assert_eq!(credentials.user, "miner");
+        assert!(debug.contains("miner"));
+        assert!(!debug.contains("hunter2"));
+        assert_eq!(
+            toml::to_string(&Secret::new("hunter2")).expect("BUG: serialize failed"),
+            "\"hunter2\""
+        );
+    }
+}
@@ -65,6 +65,12 @@
 //! * `error,hello=warn/[0-9] scopes` turn on global error logging and also
 //!   warn for hello. In both cases the log message must include a single digit
 //!   number followed by 'scopes'.
+//!
+//! ## Changing levels at runtime
+//!
+//! Levels set by `LevelHandle::set_level()` take precedence over the directives the logger has
+//! been built with. The handle can be obtained with `EnvLogger::level_handle()` before the logger
+//! is moved into a drain chain.
 
 #![doc(html_logo_url = "http://www.rust-lang.org/logos/rust-logo-128x128-blk-v2.png",
        html_favicon_url = "http://www.rust-lang.org/favicon.ico",
@@ -78,6 +84,7 @@ extern crate slog_scope;
 extern crate log;
 
 use std::{env, result, sync};
+use std::sync::{Arc, RwLock};
 use std::cell::RefCell;
 use slog::*;
 
@@ -98,6 +105,64 @@ pub struct EnvLogger<T : Drain> {
     drain : T,
     directives: Vec<LogDirective>,
     filter: Option<filter::Filter>,
+    overrides: LevelHandle,
+}
+
+/// Shared handle for overriding levels of a running `EnvLogger`
+#[derive(Clone, Default)]
+pub struct LevelHandle {
+    directives: Arc<RwLock<Vec<LogDirective>>>,
+}
+
+impl LevelHandle {
+    /// The given module (if any) will log at most the specified level regardless of the
+    /// directives the logger has been built with. Setting a level for the same module again
+    /// replaces the previous override.
+    pub fn set_level(&self, module: Option<&str>, level: FilterLevel) {
+        let mut directives = self.directives.write().expect("BUG: cannot lock log levels");
+        let name = module.map(|s| s.to_string());
+        directives.retain(|directive| directive.name != name);
+        directives.push(LogDirective {
+            name: name,
+            level: level,
+        });
+        sort_directives(&mut directives);
+    }
+
+    /// Removes all overrides so that only the original directives apply
+    pub fn reset(&self) {
+        self.directives.write().expect("BUG: cannot lock log levels").clear();
+    }
+
+    /// Returns `None` when no override matches the module
+    fn enabled(&self, level: Level, module: &str) -> Option<bool> {
+        let directives = self.directives.read().expect("BUG: cannot lock log levels");
+        find_directive(&directives, module)
+            .map(|directive| level.as_usize() <= directive.level.as_usize())
+    }
+
+    fn max_level(&self) -> Option<FilterLevel> {
+        self.directives.read().expect("BUG: cannot lock log levels")
+            .iter().map(|d| d.level).max()
+    }
+}
+
+/// Sort the directives by length of their name, this allows a
+/// little more efficient lookup at runtime.
+fn sort_directives(directives: &mut Vec<LogDirective>) {
+    directives.sort_by(|a, b| {
+        let alen = a.name.as_ref().map(|a| a.len()).unwrap_or(0);
+        let blen = b.name.as_ref().map(|b| b.len()).unwrap_or(0);
+        alen.cmp(&blen)
+    });
+}
+
+/// Search for the longest match, the vector is assumed to be pre-sorted.
+fn find_directive<'a>(directives: &'a [LogDirective], module: &str) -> Option<&'a LogDirective> {
+    directives.iter().rev().find(|directive| match directive.name {
+        Some(ref name) => module.starts_with(&**name),
+        None => true,
+    })
 }
 
 /// LogBuilder acts as builder for initializing the EnvLogger.
@@ -157,13 +222,7 @@ impl<T : Drain> LogBuilder<T> {
                 level: FilterLevel::Error,
             });
         } else {
-            // Sort the directives by length of their name, this allows a
-            // little more efficient lookup at runtime.
-            self.directives.sort_by(|a, b| {
-                let alen = a.name.as_ref().map(|a| a.len()).unwrap_or(0);
-                let blen = b.name.as_ref().map(|b| b.len()).unwrap_or(0);
-                alen.cmp(&blen)
-            });
+            sort_directives(&mut self.directives);
         }
 
         let LogBuilder {
@@ -176,6 +235,7 @@ impl<T : Drain> LogBuilder<T> {
             drain: drain,
             directives: directives,
             filter: filter,
+            overrides: LevelHandle::default(),
         }
     }
 }
@@ -193,21 +253,23 @@ impl<T : Drain> EnvLogger<T> {
 
     pub fn filter(&self) -> FilterLevel {
         self.directives.iter()
-            .map(|d| d.level).max()
+            .map(|d| d.level)
+            .chain(self.overrides.max_level())
+            .max()
             .unwrap_or(FilterLevel::Off)
     }
 
+    /// Returns handle for changing levels after the logger has been moved into a drain chain
+    pub fn level_handle(&self) -> LevelHandle {
+        self.overrides.clone()
+    }
+
     fn enabled(&self, level: Level, module: &str) -> bool {
-        // Search for the longest match, the vector is assumed to be pre-sorted.
-        for directive in self.directives.iter().rev() {
-            match directive.name {
-                Some(ref name) if !module.starts_with(&**name) => {},
-                Some(..) | None => {
-                    return level.as_usize() <= directive.level.as_usize()
-                }
-            }
+        if let Some(enabled) = self.overrides.enabled(level, module) {
+            return enabled;
         }
-        false
+        find_directive(&self.directives, module)
+            .map_or(false, |directive| level.as_usize() <= directive.level.as_usize())
     }
 }
 
@@ -365,6 +427,27 @@ mod tests {
         assert!(!logger.enabled(Level::Debug, "crate2"));
     }
 
+    #[test]
+    fn level_override() {
+        let logger = LogBuilder::new(slog::Discard).parse("info,crate1::mod1=warn").build();
+        let handle = logger.level_handle();
+        handle.set_level(Some("crate1"), FilterLevel::Debug);
+        assert!(logger.enabled(Level::Debug, "crate1::mod1"));
+        assert!(!logger.enabled(Level::Trace, "crate1::mod1"));
+        assert!(!logger.enabled(Level::Debug, "crate2::mod2"));
+
+        handle.set_level(Some("crate1"), FilterLevel::Error);
+        handle.set_level(None, FilterLevel::Off);
+        assert!(!logger.enabled(Level::Warning, "crate1::mod1"));
+        assert!(!logger.enabled(Level::Error, "crate2::mod2"));
+        assert_eq!(logger.filter(), FilterLevel::Info);
+
+        handle.reset();
+        assert!(logger.enabled(Level::Warning, "crate1::mod1"));
+        assert!(!logger.enabled(Level::Info, "crate1::mod1"));
+        assert!(logger.enabled(Level::Info, "crate2::mod2"));
+    }
+
     #[test]
     fn parse_default() {
         let logger = LogBuilder::new(slog::Discard).parse("info,crate1::mod1=warn").build();
@@ -0,0 +1,216 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Common Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Common Public License for more details.
+//
+// You should have received a copy of the GNU Common Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Error type shared by all crates
+//!
+//! Every crate has its own `Error` which consists of a stable `ErrorKind` that callers can match
+//! on and of an optional underlying error which is preserved as `source()`, so that the whole
+//! chain of errors can be reported. The crate defines only its `ErrorKind` (and conversions of
+//! foreign errors) and generates the rest with `error_type!`:
+//!
+//! ```ignore
+//! #[derive(thiserror::Error, Clone, Eq, PartialEq, Debug)]
+//! pub enum ErrorKind {
+//!     #[error("I/O error: {0}")]
+//!     Io(String),
+//! }
+//!
+//! ii_error::error_type!(ErrorKind);
+//!
+//! impl From<std::io::Error> for Error {
+//!     fn from(e: std::io::Error) -> Self {
+//!         Self::with_source(ErrorKind::Io(e.to_string()), e)
+//!     }
+//! }
+//! ```
+//!
+//! `Error` cannot be a generic type defined here because the orphan rules would not allow the
+//! crates to implement conversions of foreign errors into it.
+
+/// Boxed underlying error
+pub type Source = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+/// Generates `Error` with kind `$kind`, a specialized `Result` and `ResultExt` for attaching
+/// a context to results with any error
+#[macro_export]
+macro_rules! error_type {
+    ($kind:ty) => {
+        pub use $crate::Source;
+
+        #[derive(Debug)]
+        pub struct Error {
+            kind: $kind,
+            source: Option<Source>,
+        }
+
+        impl Error {
+            /// Creates error of `kind` caused by `source`
+            pub fn with_source<E>(kind: $kind, source: E) -> Self
+            where
+                E: Into<Source>,
+            {
+                Self {
+                    kind,
+                    source: Some(source.into()),
+                }
+            }
+
+            pub fn kind(&self) -> $kind {
+                self.kind.clone()
+            }
+
+            /// Splits the error into its kind and the underlying error
+            pub fn into_parts(self) -> ($kind, Option<Source>) {
+                (self.kind, self.source)
+            }
+        }
+
+        impl ::std::fmt::Display for Error {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                ::std::fmt::Display::fmt(&self.kind, f)
+            }
+        }
+
+        impl ::std::error::Error for Error {
+            fn source(&self) -> Option<&(dyn ::std::error::Error + 'static)> {
+                self.source
+                    .as_ref()
+                    .map(|source| source.as_ref() as &(dyn ::std::error::Error + 'static))
+            }
+        }
+
+        /// Convenience conversion to Error from ErrorKind that carries the context
+        impl From<$kind> for Error {
+            fn from(kind: $kind) -> Self {
+                Self { kind, source: None }
+            }
+        }
+
+        /// A specialized `Result` type bound to [`Error`].
+        pub type Result<T> = ::std::result::Result<T, Error>;
+
+        /// Extension of results with any error for attaching a context. The context is anything
+        /// convertible into the error kind (e.g. a message when the crate provides such
+        /// conversion). The original error is kept as the source of the resulting error.
+        pub trait ResultExt<T, E> {
+            fn context<D>(self, context: D) -> Result<T>
+            where
+                D: Into<$kind>;
+
+            fn with_context<F, D>(self, f: F) -> Result<T>
+            where
+                F: FnOnce(&E) -> D,
+                D: Into<$kind>;
+        }
+
+        impl<T, E> ResultExt<T, E> for ::std::result::Result<T, E>
+        where
+            E: ::std::error::Error + Send + Sync + 'static,
+        {
+            fn context<D>(self, context: D) -> Result<T>
+            where
+                D: Into<$kind>,
+            {
+                self.map_err(|e| Error::with_source(context.into(), e))
+            }
+
+            fn with_context<F, D>(self, f: F) -> Result<T>
+            where
+                F: FnOnce(&E) -> D,
+                D: Into<$kind>,
+            {
+                self.map_err(|e| {
+                    let kind = f(&e).into();
+                    Error::with_source(kind, e)
+                })
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use std::error::Error as StdError;
+    use std::io;
+
+    #[derive(thiserror::Error, Clone, Eq, PartialEq, Debug)]
+    pub enum ErrorKind {
+        #[error("General error: {0}")]
+        General(String),
+
+        #[error("I/O error: {0}")]
+        Io(String),
+    }
+
+    impl From<&str> for ErrorKind {
+        fn from(info: &str) -> Self {
+            ErrorKind::General(info.to_string())
+        }
+    }
+
+    error_type!(ErrorKind);
+
+    impl From<io::Error> for Error {
+        fn from(e: io::Error) -> Self {
+            Self::with_source(ErrorKind::Io(e.to_string()), e)
+        }
+    }
+
+    fn broken_pipe() -> io::Result<()> {
+        Err(io::Error::new(io::ErrorKind::BrokenPipe, "broken pipe"))
+    }
+
+    #[test]
+    fn test_error_chain() {
+        let error = broken_pipe().context("Cannot send frame").unwrap_err();
+        assert_eq!(
+            error.kind(),
+            ErrorKind::General("Cannot send frame".to_string())
+        );
+        assert_eq!(error.to_string(), "General error: Cannot send frame");
+        let source = error.source().expect("BUG: missing source");
+        assert_eq!(source.to_string(), "broken pipe");
+        assert!(source.downcast_ref::<io::Error>().is_some());
+
+        let error = broken_pipe()
+            .with_context(|e| ErrorKind::Io(format!("{:?}", e.kind())))
+            .unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::Io("BrokenPipe".to_string()));
+
+        let error: Error = ErrorKind::General("no source".to_string()).into();
+        assert!(error.source().is_none());
+        let (kind, source) = error.into_parts();
+        assert_eq!(kind, ErrorKind::General("no source".to_string()));
+        assert!(source.is_none());
+    }
+
+    #[test]
+    fn test_conversion() {
+        fn send() -> Result<()> {
+            broken_pipe()?;
+            Ok(())
+        }
+        let error = send().unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::Io("broken pipe".to_string()));
+        assert!(error.source().is_some());
+    }
+}
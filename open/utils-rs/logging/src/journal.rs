@@ -0,0 +1,189 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Drain logging to the systemd journal using its native protocol
+//!
+//! Each record is sent as a single datagram of `FIELD=value` lines. Besides the message and
+//! the syslog priority the record carries its source location and all structured key-value
+//! pairs as journal fields, so that e.g. `journalctl CHAIN=6` shows records of a single chain.
+
+use std::io;
+use std::os::unix::net::UnixDatagram;
+use std::path::PathBuf;
+
+use slog::{Drain, OwnedKVList, Record};
+
+use crate::syslog::{default_identifier, severity, Fields};
+
+/// Default path of the journal socket
+pub const DEFAULT_JOURNAL_SOCKET: &str = "/run/systemd/journal/socket";
+
+/// Maximal length of journal field name
+const MAX_FIELD_NAME_LEN: usize = 64;
+
+/// Configuration of `LoggingTarget::Journal`
+#[derive(Clone, Debug)]
+pub struct JournalConfig {
+    /// Path of the journal socket
+    pub socket_path: PathBuf,
+    /// Value of `SYSLOG_IDENTIFIER` field, the program name by default
+    pub identifier: String,
+}
+
+impl Default for JournalConfig {
+    fn default() -> Self {
+        Self {
+            socket_path: DEFAULT_JOURNAL_SOCKET.into(),
+            identifier: default_identifier(),
+        }
+    }
+}
+
+/// Converts key of a record to a valid journal field name. It may contain only uppercase
+/// letters, digits and underscores and must start with a letter.
+fn field_name(key: &str) -> String {
+    let name: String = key
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' => c.to_ascii_uppercase(),
+            _ => '_',
+        })
+        .skip_while(|c| !c.is_ascii_uppercase())
+        .take(MAX_FIELD_NAME_LEN)
+        .collect();
+    if name.is_empty() {
+        "FIELD".to_string()
+    } else {
+        name
+    }
+}
+
+/// Appends a field to journal message. Values spanning multiple lines are serialized with
+/// explicit length.
+fn append_field(message: &mut Vec<u8>, name: &str, value: &str) {
+    message.extend_from_slice(name.as_bytes());
+    if value.contains('\n') {
+        message.push(b'\n');
+        message.extend_from_slice(&(value.len() as u64).to_le_bytes());
+    } else {
+        message.push(b'=');
+    }
+    message.extend_from_slice(value.as_bytes());
+    message.push(b'\n');
+}
+
+/// Drain which sends each record as a single datagram to the journal.
+///
+/// The socket is not connected so that logging resumes on its own after the journal daemon is
+/// restarted. A record which cannot be delivered is reported as an error of the drain.
+pub struct JournalDrain {
+    socket: UnixDatagram,
+    config: JournalConfig,
+}
+
+impl JournalDrain {
+    pub fn new(config: JournalConfig) -> io::Result<Self> {
+        // Fail early when the journal is not running at all
+        std::fs::metadata(&config.socket_path)?;
+        Ok(Self {
+            socket: UnixDatagram::unbound()?,
+            config,
+        })
+    }
+
+    fn format(&self, record: &Record, values: &OwnedKVList) -> Vec<u8> {
+        let mut message = Vec::new();
+        append_field(&mut message, "MESSAGE", &record.msg().to_string());
+        append_field(
+            &mut message,
+            "PRIORITY",
+            &severity(record.level()).to_string(),
+        );
+        append_field(&mut message, "SYSLOG_IDENTIFIER", &self.config.identifier);
+        append_field(&mut message, "CODE_FILE", record.file());
+        append_field(&mut message, "CODE_LINE", &record.line().to_string());
+        append_field(&mut message, "CODE_MODULE", record.module());
+        for (key, value) in Fields::collect(record, values).0 {
+            append_field(&mut message, &field_name(key), &value);
+        }
+        message
+    }
+}
+
+impl Drain for JournalDrain {
+    type Ok = ();
+    type Err = io::Error;
+
+    fn log(&self, record: &Record, values: &OwnedKVList) -> io::Result<()> {
+        let message = self.format(record, values);
+        self.socket
+            .send_to(&message, &self.config.socket_path)
+            .map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use slog::{error, o, Logger};
+
+    #[test]
+    fn test_field_name() {
+        assert_eq!(field_name("chain"), "CHAIN");
+        assert_eq!(field_name("pool-url"), "POOL_URL");
+        assert_eq!(field_name("_1st"), "ST");
+        assert_eq!(field_name("__"), "FIELD");
+        assert_eq!(field_name(&"x".repeat(100)).len(), MAX_FIELD_NAME_LEN);
+    }
+
+    #[test]
+    fn test_journal_drain() {
+        let dir = tempfile::tempdir().expect("cannot create temporary directory");
+        let socket_path = dir.path().join("socket");
+        let server = UnixDatagram::bind(&socket_path).expect("cannot bind journal socket");
+
+        let drain = JournalDrain::new(JournalConfig {
+            socket_path,
+            identifier: "test".to_string(),
+        })
+        .expect("cannot create journal drain");
+        let logger = Logger::root(drain.fuse(), o!("chain" => 6));
+
+        let mut buf = [0u8; 512];
+        error!(logger, "Chain failed"; "reason" => "line 1\nline 2");
+        let line = line!() - 1;
+        let len = server.recv(&mut buf).expect("cannot receive record");
+
+        let mut expected = format!(
+            "MESSAGE=Chain failed\nPRIORITY=3\nSYSLOG_IDENTIFIER=test\nCODE_FILE={}\n\
+             CODE_LINE={}\nCODE_MODULE={}\nREASON\n",
+            file!(),
+            line,
+            module_path!()
+        )
+        .into_bytes();
+        expected.extend_from_slice(&13u64.to_le_bytes());
+        expected.extend_from_slice(b"line 1\nline 2\nCHAIN=6\n");
+        assert_eq!(&buf[..len], &expected[..]);
+    }
+}
@@ -42,7 +42,22 @@
 //! The global logger is also configured with `slog_envlogger`,
 //! that is, it applies filters set via the `RUST_LOG` env variable.
 //! Refer to the [`env_logger` documentation](https://docs.rs/env_logger/0.6.2/env_logger/)
-//! for more information.
+//! for more information. Levels can also be changed while the application
+//! is running with `set_level()`, these take precedence over `RUST_LOG`.
+//!
+//! Records are passed to a bounded queue of the asynchronous drain. When the
+//! queue is full because the output (e.g. flash storage) is slow, records are
+//! dropped and the number of dropped records is reported later, so the logging
+//! never blocks the caller.
+//!
+//! Records may carry structured key-value pairs following the message
+//! and a semicolon, e.g. `info!("Chain started"; "chain" => idx)`.
+//!
+//! Besides the terminal and files, logs can be sent to the system logger
+//! (`LoggingTarget::Syslog`) or to the systemd journal (`LoggingTarget::Journal`)
+//! so that they can be collected by the standard infrastructure. Levels are mapped
+//! to syslog priorities and the key-value pairs are passed along as structured fields.
+//! Records which cannot be delivered to these outputs are dropped.
 //!
 //! If no configuration is set with `set_logger_config()` et al.,
 //! the global logger will by default use `LoggingConfig::for_testing()`,
@@ -59,15 +74,21 @@ use std::path::{Path, PathBuf};
 use std::sync::{Mutex, MutexGuard};
 
 use lazy_static::lazy_static;
-use slog::{o, Discard, Drain, FilterLevel, Logger};
-use slog_async::{Async, AsyncGuard};
-use slog_envlogger::EnvLogger;
+use slog::{o, Discard, Drain, Logger};
+use slog_async::{Async, AsyncGuard, OverflowStrategy};
+use slog_envlogger::{EnvLogger, LevelHandle};
 use slog_term;
 
+mod journal;
+mod syslog;
+
+pub use journal::{JournalConfig, JournalDrain, DEFAULT_JOURNAL_SOCKET};
+pub use syslog::{severity, Facility, SyslogConfig, SyslogDrain, DEFAULT_SYSLOG_SOCKET};
+
 // Re-export slog things for easy access to slog by dependers
 // and also because these are used by macros
 pub use slog;
-pub use slog::Level;
+pub use slog::{FilterLevel, Level};
 
 /// Logging target configuration: Where to log
 #[derive(Clone, Debug)]
@@ -78,6 +99,10 @@ pub enum LoggingTarget {
     Stdout,
     /// Log to a file
     File(PathBuf),
+    /// Log to the system logger
+    Syslog(SyslogConfig),
+    /// Log to the systemd journal
+    Journal(JournalConfig),
     /// Don't log anything anywhere
     None,
 }
@@ -187,6 +212,18 @@ pub fn setup_for_app(drain_channel_size: usize) -> FlushGuard {
     setup(LoggingConfig::for_app(drain_channel_size))
 }
 
+/// Change level of `module` (or of all modules when it is `None`) in the global logger.
+/// Convenience function, see `GuardedLogger::set_level()`.
+pub fn set_level(module: Option<&str>, level: FilterLevel) {
+    LOGGER.set_level(module, level);
+}
+
+/// Drop all levels changed with `set_level()`. Convenience function, see
+/// `GuardedLogger::reset_levels()`.
+pub fn reset_levels() {
+    LOGGER.reset_levels();
+}
+
 /// Sets up envlogger filter for a drain, with proper default settings
 fn get_envlogger_drain<D: Drain>(drain: D, default_level: Level) -> EnvLogger<D> {
     let builder = slog_envlogger::LogBuilder::new(drain);
@@ -243,6 +280,32 @@ fn get_file_drain(path: &Path) -> impl Drain<Ok = (), Err = impl fmt::Debug> {
     file_drain
 }
 
+/// Create drain for logger sending records to the system logger
+fn get_syslog_drain(config: &SyslogConfig) -> impl Drain<Ok = (), Err = impl fmt::Debug> {
+    SyslogDrain::new(config.clone())
+        .unwrap_or_else(|e| {
+            panic!(
+                "Logging setup error: Could not use syslog socket `{}`: {}",
+                config.socket_path.display(),
+                e
+            )
+        })
+        .ignore_res()
+}
+
+/// Create drain for logger sending records to the systemd journal
+fn get_journal_drain(config: &JournalConfig) -> impl Drain<Ok = (), Err = impl fmt::Debug> {
+    JournalDrain::new(config.clone())
+        .unwrap_or_else(|e| {
+            panic!(
+                "Logging setup error: Could not use journal socket `{}`: {}",
+                config.socket_path.display(),
+                e
+            )
+        })
+        .ignore_res()
+}
+
 /// Logger flush RAII guard.
 ///
 /// The guard ensures logs are flushed when it goes out of scope.
@@ -259,6 +322,8 @@ pub struct FlushGuard(Option<AsyncGuard>);
 pub struct GuardedLogger {
     pub logger: Logger,
     guard: Mutex<FlushGuard>,
+    /// Runtime level overrides of the envlogger filter, `None` when nothing is logged
+    levels: Option<LevelHandle>,
 }
 
 impl GuardedLogger {
@@ -270,6 +335,8 @@ impl GuardedLogger {
             Stderr => Self::with_drain(config, get_terminal_drain(true)),
             Stdout => Self::with_drain(config, get_terminal_drain(false)),
             File(path) => Self::with_drain(config, get_file_drain(path)),
+            Syslog(syslog) => Self::with_drain(config, get_syslog_drain(syslog)),
+            Journal(journal) => Self::with_drain(config, get_journal_drain(journal)),
         }
     }
 
@@ -279,12 +346,16 @@ impl GuardedLogger {
         D: Drain<Ok = (), Err = E> + Send + 'static,
     {
         let drain = get_envlogger_drain(drain, config.level);
+        let levels = drain.level_handle();
+        // Never block the caller when the output cannot keep up with the records
         let (drain, guard) = Async::new(drain.fuse())
             .chan_size(config.drain_channel_size)
+            .overflow_strategy(OverflowStrategy::DropAndReport)
             .build_with_guard();
         Self {
             logger: Logger::root(drain.fuse(), o!()),
             guard: Mutex::new(FlushGuard(Some(guard))),
+            levels: Some(levels),
         }
     }
 
@@ -292,6 +363,23 @@ impl GuardedLogger {
         Self {
             logger: Logger::root(Discard, o!()),
             guard: Mutex::new(FlushGuard(None)),
+            levels: None,
+        }
+    }
+
+    /// Change level of `module` (or of all modules when it is `None`) while the logger is
+    /// running. The module is matched as a prefix of the module path the same way as in
+    /// `RUST_LOG`. The level takes precedence over the configured one and over `RUST_LOG`.
+    pub fn set_level(&self, module: Option<&str>, level: FilterLevel) {
+        if let Some(levels) = self.levels.as_ref() {
+            levels.set_level(module, level);
+        }
+    }
+
+    /// Drop all levels changed with `set_level()`
+    pub fn reset_levels(&self) {
+        if let Some(levels) = self.levels.as_ref() {
+            levels.reset();
         }
     }
 
@@ -0,0 +1,249 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Drain logging to the system logger over its local datagram socket
+//!
+//! Records are sent in the traditional BSD syslog format (RFC 3164) without timestamp and
+//! hostname, which are filled in by the syslog daemon on reception. Structured key-value pairs
+//! are appended to the message as `key=value`.
+
+use std::env;
+use std::fmt::{self, Write};
+use std::io;
+use std::os::unix::net::UnixDatagram;
+use std::path::PathBuf;
+
+use slog::{Drain, Level, OwnedKVList, Record, KV};
+
+/// Default path of the system logger socket
+pub const DEFAULT_SYSLOG_SOCKET: &str = "/dev/log";
+
+/// Syslog severity of a log record level
+pub fn severity(level: Level) -> u8 {
+    match level {
+        Level::Critical => 2,
+        Level::Error => 3,
+        Level::Warning => 4,
+        Level::Info => 6,
+        Level::Debug | Level::Trace => 7,
+    }
+}
+
+/// Syslog facility the records are logged with
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Facility {
+    User,
+    Daemon,
+    Local0,
+    Local1,
+    Local2,
+    Local3,
+    Local4,
+    Local5,
+    Local6,
+    Local7,
+}
+
+impl Default for Facility {
+    fn default() -> Facility {
+        Facility::Daemon
+    }
+}
+
+impl Facility {
+    pub fn code(self) -> u8 {
+        match self {
+            Facility::User => 1,
+            Facility::Daemon => 3,
+            Facility::Local0 => 16,
+            Facility::Local1 => 17,
+            Facility::Local2 => 18,
+            Facility::Local3 => 19,
+            Facility::Local4 => 20,
+            Facility::Local5 => 21,
+            Facility::Local6 => 22,
+            Facility::Local7 => 23,
+        }
+    }
+}
+
+/// Name of the running program used to identify its records in the system log
+pub(crate) fn default_identifier() -> String {
+    env::args()
+        .next()
+        .as_ref()
+        .and_then(|arg0| PathBuf::from(arg0).file_name().map(|name| name.to_owned()))
+        .and_then(|name| name.into_string().ok())
+        .unwrap_or_else(|| "bosminer".to_string())
+}
+
+/// Configuration of `LoggingTarget::Syslog`
+#[derive(Clone, Debug)]
+pub struct SyslogConfig {
+    /// Path of the system logger socket
+    pub socket_path: PathBuf,
+    /// Tag of the records, the program name by default
+    pub identifier: String,
+    pub facility: Facility,
+}
+
+impl Default for SyslogConfig {
+    fn default() -> Self {
+        Self {
+            socket_path: DEFAULT_SYSLOG_SOCKET.into(),
+            identifier: default_identifier(),
+            facility: Default::default(),
+        }
+    }
+}
+
+/// Collects structured key-value pairs of a record as strings
+#[derive(Default)]
+pub(crate) struct Fields(pub Vec<(&'static str, String)>);
+
+impl Fields {
+    pub fn collect(record: &Record, values: &OwnedKVList) -> Self {
+        let mut fields = Self::default();
+        // Serialization into strings cannot fail
+        let _ = record.kv().serialize(record, &mut fields);
+        let _ = values.serialize(record, &mut fields);
+        fields
+    }
+}
+
+impl slog::Serializer for Fields {
+    fn emit_arguments(&mut self, key: slog::Key, val: &fmt::Arguments) -> slog::Result {
+        self.0.push((key, val.to_string()));
+        Ok(())
+    }
+}
+
+/// Drain which sends each record as a single datagram to the system logger.
+///
+/// The socket is not connected so that logging resumes on its own after the syslog daemon is
+/// restarted. A record which cannot be delivered is reported as an error of the drain.
+pub struct SyslogDrain {
+    socket: UnixDatagram,
+    config: SyslogConfig,
+    pid: u32,
+}
+
+impl SyslogDrain {
+    pub fn new(config: SyslogConfig) -> io::Result<Self> {
+        // Fail early when there is no system logger at all
+        std::fs::metadata(&config.socket_path)?;
+        Ok(Self {
+            socket: UnixDatagram::unbound()?,
+            config,
+            pid: std::process::id(),
+        })
+    }
+
+    fn format(&self, record: &Record, values: &OwnedKVList) -> String {
+        let priority = self.config.facility.code() * 8 + severity(record.level());
+        let mut message = format!(
+            "<{}>{}[{}]: {}",
+            priority,
+            self.config.identifier,
+            self.pid,
+            record.msg()
+        );
+        for (key, value) in Fields::collect(record, values).0 {
+            if value.is_empty() || value.contains(|c: char| c.is_whitespace() || c == '"') {
+                write!(message, " {}={:?}", key, value)
+            } else {
+                write!(message, " {}={}", key, value)
+            }
+            .expect("BUG: cannot format syslog message");
+        }
+        message
+    }
+}
+
+impl Drain for SyslogDrain {
+    type Ok = ();
+    type Err = io::Error;
+
+    fn log(&self, record: &Record, values: &OwnedKVList) -> io::Result<()> {
+        let message = self.format(record, values);
+        self.socket
+            .send_to(message.as_bytes(), &self.config.socket_path)
+            .map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use slog::{info, o, warn, Logger};
+
+    #[test]
+    fn test_severity() {
+        assert_eq!(severity(Level::Critical), 2);
+        assert_eq!(severity(Level::Error), 3);
+        assert_eq!(severity(Level::Warning), 4);
+        assert_eq!(severity(Level::Info), 6);
+        assert_eq!(severity(Level::Trace), 7);
+    }
+
+    #[test]
+    fn test_syslog_drain() {
+        let dir = tempfile::tempdir().expect("cannot create temporary directory");
+        let socket_path = dir.path().join("log");
+        let server = UnixDatagram::bind(&socket_path).expect("cannot bind syslog socket");
+
+        // Missing socket is reported on setup
+        assert!(SyslogDrain::new(SyslogConfig {
+            socket_path: dir.path().join("missing"),
+            ..Default::default()
+        })
+        .is_err());
+
+        let drain = SyslogDrain::new(SyslogConfig {
+            socket_path,
+            identifier: "test".to_string(),
+            facility: Facility::Local0,
+        })
+        .expect("cannot create syslog drain");
+        let pid = std::process::id();
+        let logger = Logger::root(drain.fuse(), o!("chain" => 6));
+
+        let mut buf = [0u8; 256];
+        info!(logger, "Chain started"; "frequency" => 650);
+        let len = server.recv(&mut buf).expect("cannot receive record");
+        assert_eq!(
+            std::str::from_utf8(&buf[..len]).unwrap(),
+            format!("<134>test[{}]: Chain started frequency=650 chain=6", pid)
+        );
+
+        warn!(logger, "Pool failed"; "url" => "stratum+tcp://pool", "reason" => "no route");
+        let len = server.recv(&mut buf).expect("cannot receive record");
+        assert_eq!(
+            std::str::from_utf8(&buf[..len]).unwrap(),
+            format!(
+                "<132>test[{}]: Pool failed reason=\"no route\" url=stratum+tcp://pool chain=6",
+                pid
+            )
+        );
+    }
+}
@@ -0,0 +1,145 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Sum of values accounted within a sliding time window
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Sums samples within a sliding window of given length. The window is split into buckets of
+/// equal length and the oldest bucket is discarded as a whole, so the window slides with
+/// the resolution of one bucket.
+#[derive(Debug, Clone)]
+pub struct WindowedCounter {
+    window: Duration,
+    bucket_len: Duration,
+    /// Time of the first sample which the bucket indexes are related to
+    origin: Option<Instant>,
+    /// Non-empty buckets as pairs of bucket index and sum of its samples
+    buckets: VecDeque<(u64, f64)>,
+    /// Sum of all samples since the counter was created
+    total: f64,
+}
+
+impl WindowedCounter {
+    pub fn new(window: Duration, bucket_count: u32) -> Self {
+        assert!(bucket_count > 0);
+        let bucket_len = window / bucket_count;
+        assert!(bucket_len > Duration::from_secs(0));
+        Self {
+            window,
+            bucket_len,
+            origin: None,
+            buckets: VecDeque::new(),
+            total: 0.0,
+        }
+    }
+
+    #[inline]
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// Sum of all samples ever inserted
+    #[inline]
+    pub fn total(&self) -> f64 {
+        self.total
+    }
+
+    fn bucket_index(&self, origin: Instant, now: Instant) -> u64 {
+        (now.saturating_duration_since(origin).as_nanos() / self.bucket_len.as_nanos()) as u64
+    }
+
+    fn bucket_count(&self) -> u64 {
+        (self.window.as_nanos() / self.bucket_len.as_nanos()) as u64
+    }
+
+    pub fn insert(&mut self, sample: f64, now: Instant) {
+        let origin = *self.origin.get_or_insert(now);
+        let index = self.bucket_index(origin, now);
+        self.total += sample;
+        // Sample which arrived late (with time older than the newest bucket) is accounted to
+        // the newest bucket
+        match self.buckets.back_mut() {
+            Some((last_index, sum)) if *last_index >= index => *sum += sample,
+            _ => self.buckets.push_back((index, sample)),
+        }
+        let first_valid = (index + 1).saturating_sub(self.bucket_count());
+        while let Some((index, _)) = self.buckets.front() {
+            if *index >= first_valid {
+                break;
+            }
+            self.buckets.pop_front();
+        }
+    }
+
+    /// Sum of samples inserted within the window ending at `now`
+    pub fn sum(&self, now: Instant) -> f64 {
+        match self.origin {
+            None => 0.0,
+            Some(origin) => {
+                let first_valid =
+                    (self.bucket_index(origin, now) + 1).saturating_sub(self.bucket_count());
+                self.buckets
+                    .iter()
+                    .filter(|(index, _)| *index >= first_valid)
+                    .map(|(_, sum)| sum)
+                    .sum()
+            }
+        }
+    }
+
+    /// Sum of samples within the window divided by the window length in seconds. The rate is
+    /// underestimated until the counter has been running for the whole window.
+    pub fn rate(&self, now: Instant) -> f64 {
+        self.sum(now) / self.window.as_secs_f64()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_windowed_counter() {
+        let start = Instant::now();
+        let mut counter = WindowedCounter::new(Duration::from_secs(10), 10);
+        assert_eq!(counter.sum(start), 0.0);
+
+        for i in 0..10 {
+            counter.insert(1.0, start + Duration::from_secs(i));
+        }
+        assert_eq!(counter.sum(start + Duration::from_millis(9500)), 10.0);
+        assert_eq!(counter.rate(start + Duration::from_millis(9500)), 1.0);
+
+        // the oldest buckets slide out of the window
+        assert_eq!(counter.sum(start + Duration::from_secs(12)), 7.0);
+        counter.insert(2.0, start + Duration::from_secs(15));
+        assert_eq!(counter.sum(start + Duration::from_secs(15)), 6.0);
+        assert_eq!(counter.sum(start + Duration::from_secs(30)), 0.0);
+        assert_eq!(counter.total(), 12.0);
+
+        // late sample goes to the newest bucket
+        counter.insert(1.0, start);
+        assert_eq!(counter.sum(start + Duration::from_secs(15)), 7.0);
+    }
+}
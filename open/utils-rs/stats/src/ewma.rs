@@ -0,0 +1,102 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Exponentially weighted moving average of a rate
+
+use std::time::{Duration, Instant};
+
+/// Rate of samples per second with exponentially decaying weight of older samples. Unlike
+/// windowed means, it needs constant memory and does not jump when a sample leaves the window,
+/// which makes it suitable for smoothing of rates computed from sparse events (e.g. shares).
+#[derive(Debug, Clone, Copy)]
+pub struct Ewma {
+    /// Time constant of the exponential decay in seconds
+    tau: f64,
+    /// Rate at the time of `updated`
+    rate: f64,
+    updated: Option<Instant>,
+}
+
+impl Ewma {
+    /// Creates an average whose sample weight drops to 1/e after `time_constant`
+    pub fn new(time_constant: Duration) -> Self {
+        assert!(time_constant > Duration::from_secs(0));
+        Self {
+            tau: time_constant.as_secs_f64(),
+            rate: 0.0,
+            updated: None,
+        }
+    }
+
+    #[inline]
+    pub fn time_constant(&self) -> Duration {
+        Duration::from_secs_f64(self.tau)
+    }
+
+    fn decay(&self, now: Instant) -> f64 {
+        match self.updated {
+            None => 1.0,
+            Some(updated) => {
+                let elapsed = now.saturating_duration_since(updated).as_secs_f64();
+                (-elapsed / self.tau).exp()
+            }
+        }
+    }
+
+    pub fn insert(&mut self, sample: f64, now: Instant) {
+        self.rate = self.rate * self.decay(now) + sample / self.tau;
+        // Keep the latest time so that late samples do not move the average back in time
+        match self.updated {
+            Some(updated) if updated >= now => {}
+            _ => self.updated = Some(now),
+        }
+    }
+
+    /// Rate of samples per second at time `now`
+    pub fn rate(&self, now: Instant) -> f64 {
+        self.rate * self.decay(now)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_ewma() {
+        let start = Instant::now();
+        let mut ewma = Ewma::new(Duration::from_secs(60));
+        assert_eq!(ewma.rate(start), 0.0);
+
+        // steady rate of 2 per second converges to 2
+        for i in 0..3600 {
+            ewma.insert(2.0, start + Duration::from_secs(i));
+        }
+        let now = start + Duration::from_secs(3600);
+        assert!((ewma.rate(now) - 2.0).abs() < 0.05);
+
+        // the rate decays to 1/e after one time constant without samples
+        let rate = ewma.rate(now);
+        let decayed = ewma.rate(now + Duration::from_secs(60));
+        assert!((decayed - rate / std::f64::consts::E).abs() < 1e-9);
+    }
+}
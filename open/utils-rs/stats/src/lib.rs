@@ -21,6 +21,25 @@
 // contact us at opensource@braiins.com.
 
 //! This crate is intended for various statistical algorithms used mainly for mining.
+//!
+//! All algorithms take the time of each sample and measurement from the caller, so they can be
+//! tested deterministically and shared by components with different sources of time:
+//!
+//! * `WindowedTimeMean` - approximate arithmetic mean of samples within a time interval
+//! * `WindowedCounter` - exact sum of samples within a sliding window
+//! * `Ewma` - exponentially weighted moving rate of samples
+//! * `Percentiles` - percentiles of recent samples
+//! * `ShareMeter` - hashrate and reject rate derived from difficulty of submitted shares
+
+mod counter;
+mod ewma;
+mod percentile;
+pub mod share;
+
+pub use counter::WindowedCounter;
+pub use ewma::Ewma;
+pub use percentile::{Percentiles, PercentilesSnapshot};
+pub use share::ShareMeter;
 
 use std::time::{Duration, Instant};
 
@@ -0,0 +1,136 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Percentiles of samples taken within a recent time window
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Keeps samples from the last `window` bounded to `max_samples` (the oldest samples are
+/// discarded first) so that percentiles can be computed from them
+#[derive(Debug, Clone)]
+pub struct Percentiles<T> {
+    window: Duration,
+    max_samples: usize,
+    /// Recent samples with time of measurement
+    samples: VecDeque<(Instant, T)>,
+}
+
+impl<T> Percentiles<T>
+where
+    T: Ord + Copy,
+{
+    pub fn new(window: Duration, max_samples: usize) -> Self {
+        assert!(max_samples > 0);
+        Self {
+            window,
+            max_samples,
+            samples: VecDeque::new(),
+        }
+    }
+
+    fn remove_old_samples(&mut self, now: Instant) {
+        while let Some((time, _)) = self.samples.front() {
+            if now.saturating_duration_since(*time) <= self.window
+                && self.samples.len() <= self.max_samples
+            {
+                break;
+            }
+            self.samples.pop_front();
+        }
+    }
+
+    pub fn insert(&mut self, sample: T, now: Instant) {
+        self.samples.push_back((now, sample));
+        self.remove_old_samples(now);
+    }
+
+    /// Discards samples older than the window and returns the remaining ones sorted
+    pub fn take_snapshot(&mut self, now: Instant) -> PercentilesSnapshot<T> {
+        self.remove_old_samples(now);
+        let mut samples: Vec<_> = self.samples.iter().map(|(_, sample)| *sample).collect();
+        samples.sort();
+        PercentilesSnapshot { samples }
+    }
+}
+
+/// Sorted samples for computation of percentiles
+#[derive(Debug, Clone)]
+pub struct PercentilesSnapshot<T> {
+    samples: Vec<T>,
+}
+
+impl<T> PercentilesSnapshot<T>
+where
+    T: Copy,
+{
+    /// Number of samples used for computation of percentiles
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Returns `percentile` (in range 0 to 100) of the samples using nearest-rank method
+    pub fn percentile(&self, percentile: f64) -> Option<T> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        let rank = (percentile / 100.0 * self.samples.len() as f64).ceil() as usize;
+        Some(self.samples[rank.max(1).min(self.samples.len()) - 1])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_percentiles() {
+        let start = Instant::now();
+        let mut percentiles = Percentiles::new(Duration::from_secs(60), 100);
+        assert_eq!(percentiles.take_snapshot(start).percentile(50.0), None);
+
+        for i in (1..=100).rev() {
+            percentiles.insert(i, start);
+        }
+        let snapshot = percentiles.take_snapshot(start);
+        assert_eq!(snapshot.len(), 100);
+        assert_eq!(snapshot.percentile(0.0), Some(1));
+        assert_eq!(snapshot.percentile(50.0), Some(50));
+        assert_eq!(snapshot.percentile(99.0), Some(99));
+        assert_eq!(snapshot.percentile(100.0), Some(100));
+
+        // the number of samples is bounded
+        percentiles.insert(1000, start + Duration::from_secs(1));
+        let snapshot = percentiles.take_snapshot(start + Duration::from_secs(1));
+        assert_eq!(snapshot.len(), 100);
+        assert_eq!(snapshot.percentile(0.0), Some(1));
+        assert_eq!(snapshot.percentile(100.0), Some(1000));
+
+        // old samples are discarded
+        let snapshot = percentiles.take_snapshot(start + Duration::from_secs(61));
+        assert_eq!(snapshot.len(), 1);
+    }
+}
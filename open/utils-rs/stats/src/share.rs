@@ -0,0 +1,154 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Hashrate and reject rate computed from difficulty of submitted shares
+
+use crate::WindowedCounter;
+
+use std::time::{Duration, Instant};
+
+/// Expected number of hashes needed for finding a share of difficulty 1
+pub const HASHES_PER_DIFFICULTY: f64 = 4_294_967_296.0;
+
+/// Number of buckets of windowed counters used by `ShareMeter`
+const BUCKET_COUNT: u32 = 60;
+
+/// Converts difficulty of shares to the expected number of hashes needed to find them
+#[inline]
+pub fn difficulty_to_hashes(difficulty: f64) -> f64 {
+    difficulty * HASHES_PER_DIFFICULTY
+}
+
+/// Accounts accepted and rejected shares by their difficulty within a sliding window
+#[derive(Debug, Clone)]
+pub struct ShareMeter {
+    accepted: WindowedCounter,
+    rejected: WindowedCounter,
+    accepted_count: u64,
+    rejected_count: u64,
+}
+
+impl ShareMeter {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            accepted: WindowedCounter::new(window, BUCKET_COUNT),
+            rejected: WindowedCounter::new(window, BUCKET_COUNT),
+            accepted_count: 0,
+            rejected_count: 0,
+        }
+    }
+
+    #[inline]
+    pub fn window(&self) -> Duration {
+        self.accepted.window()
+    }
+
+    pub fn account_accepted(&mut self, difficulty: f64, now: Instant) {
+        self.accepted_count += 1;
+        self.accepted.insert(difficulty, now);
+    }
+
+    pub fn account_rejected(&mut self, difficulty: f64, now: Instant) {
+        self.rejected_count += 1;
+        self.rejected.insert(difficulty, now);
+    }
+
+    /// Number of all accepted shares
+    #[inline]
+    pub fn accepted_count(&self) -> u64 {
+        self.accepted_count
+    }
+
+    /// Number of all rejected shares
+    #[inline]
+    pub fn rejected_count(&self) -> u64 {
+        self.rejected_count
+    }
+
+    /// Sum of difficulty of all accepted shares
+    #[inline]
+    pub fn accepted_difficulty(&self) -> f64 {
+        self.accepted.total()
+    }
+
+    /// Sum of difficulty of all rejected shares
+    #[inline]
+    pub fn rejected_difficulty(&self) -> f64 {
+        self.rejected.total()
+    }
+
+    /// Hashes per second corresponding to accepted shares within the window
+    pub fn hashrate(&self, now: Instant) -> f64 {
+        difficulty_to_hashes(self.accepted.rate(now))
+    }
+
+    /// Hashes per second corresponding to all (accepted and rejected) shares within the window
+    pub fn total_hashrate(&self, now: Instant) -> f64 {
+        difficulty_to_hashes(self.accepted.rate(now) + self.rejected.rate(now))
+    }
+
+    /// Ratio of rejected difficulty to all difficulty submitted within the window. It is `None`
+    /// when no share has been submitted within the window.
+    pub fn reject_ratio(&self, now: Instant) -> Option<f64> {
+        let rejected = self.rejected.sum(now);
+        let total = self.accepted.sum(now) + rejected;
+        if total > 0.0 {
+            Some(rejected / total)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_share_meter() {
+        let start = Instant::now();
+        let mut meter = ShareMeter::new(Duration::from_secs(60));
+        assert_eq!(meter.hashrate(start), 0.0);
+        assert_eq!(meter.reject_ratio(start), None);
+
+        // 1 TH/s finds a share of difficulty 1000 every 4.29 seconds
+        let difficulty = 1000.0;
+        let period = difficulty_to_hashes(difficulty) / 1e12;
+        let mut time = 0.0;
+        while time < 60.0 {
+            meter.account_accepted(difficulty, start + Duration::from_secs_f64(time));
+            time += period;
+        }
+        meter.account_rejected(difficulty, start + Duration::from_secs(59));
+        let now = start + Duration::from_secs_f64(59.5);
+        assert!((meter.hashrate(now) / 1e12 - 1.0).abs() < 0.1);
+        assert!(meter.total_hashrate(now) > meter.hashrate(now));
+        assert_eq!(meter.accepted_count(), 14);
+        assert_eq!(meter.rejected_count(), 1);
+        assert_eq!(meter.reject_ratio(now), Some(1.0 / 15.0));
+        assert_eq!(meter.accepted_difficulty(), 14_000.0);
+
+        // shares out of the window are not accounted
+        assert_eq!(meter.hashrate(start + Duration::from_secs(120)), 0.0);
+        assert_eq!(meter.reject_ratio(start + Duration::from_secs(120)), None);
+    }
+}
@@ -20,28 +20,50 @@
 // of such proprietary license or if you have any other questions, please
 // contact us at opensource@braiins.com.
 
-//! This module provides a way to
+//! This crate provides a way to
 //!   * spawn tasks in "termination context"
 //!   * terminate that context
 //!   * wait for "termination" in normal context, do cleanup, and notify the terminator that we
 //!     have completed termination
+//!   * link termination contexts into a hierarchy so that halting a parent halts its children
 //!
 //! Termination context means that task is run `select`-ed on termination condition, and when
 //! that condition is signaled, select returns and the task is dropped.
 
+use std::error::Error as StdError;
+use std::fmt;
 use std::pin::Pin;
 use std::sync::Arc;
 use std::time::Duration;
 
-use crate::error;
-use error::ErrorKind;
-
 use futures::channel::mpsc;
 use futures::future::{select, Either};
 use futures::lock::Mutex;
 use ii_async_compat::prelude::*;
+use ii_async_compat::task;
 use tokio::signal::unix::{signal, SignalKind};
-use tokio::task;
+
+/// Error when halting a client of termination context
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// Client has dropped the confirmation handle without confirming the halt
+    Dropped(String),
+    /// Client hasn't confirmed the halt within halt timeout
+    Timeout(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Dropped(name) => write!(fmt, "failed to halt client {}: dropped handle", name),
+            Error::Timeout(name) => write!(fmt, "failed to halt client {}: timeout", name),
+        }
+    }
+}
+
+impl StdError for Error {}
+
+pub type Result<T> = std::result::Result<T, Error>;
 
 /// Token sent by halted task to confirm that halting is done
 struct Done;
@@ -75,6 +97,8 @@ fn make_done_pair() -> (DoneSender, DoneReceiver) {
 /// In case the sender ends, the `recv` part of channel receives `None` as EOF
 pub struct NotifyReceiver {
     notify_rx: mpsc::UnboundedReceiver<DoneSender>,
+    /// Name of the client which is also used for naming of spawned tasks
+    name: String,
 }
 
 impl NotifyReceiver {
@@ -84,11 +108,13 @@ impl NotifyReceiver {
         self.notify_rx.next().await
     }
 
+    /// Spawn a new task which runs `f` when `Halt` is received. A panic of the task is reported
+    /// through `ii_async_compat::task` failure channel.
     pub fn spawn_halt_handler<F>(self, f: F)
     where
         F: Future<Output = ()> + 'static + Send,
     {
-        tokio::spawn(async move {
+        task::spawn(self.name.clone(), async move {
             if let Some(done_sender) = self.wait_for_halt().await {
                 f.await;
                 done_sender.confirm();
@@ -96,20 +122,19 @@ impl NotifyReceiver {
         });
     }
 
-    /// Spawn a new task that is dropped when `Halt` is received
+    /// Spawn a new task that is dropped when `Halt` is received. A panic of the task is reported
+    /// through `ii_async_compat::task` failure channel.
     pub fn spawn<F>(self, f: F)
     where
         F: Future<Output = ()> + 'static + Send,
     {
-        tokio::spawn(async move {
+        task::spawn(self.name.clone(), async move {
             match select(f.boxed(), self.wait_for_halt().boxed()).await {
                 // in case we received halt notification, reply and exit
                 Either::Right((halt_result, _)) => {
-                    match halt_result {
-                        // confirm we are done (there's no cleanup)
-                        Some(done_sender) => done_sender.confirm(),
-                        // halt sender was dropped
-                        None => (),
+                    // confirm we are done (there's no cleanup), unless halt sender was dropped
+                    if let Some(done_sender) = halt_result {
+                        done_sender.confirm();
                     }
                 }
                 Either::Left(_) => {
@@ -147,36 +172,51 @@ fn make_notify_pair(name: String) -> (NotifySender, NotifyReceiver) {
     let (notify_tx, notify_rx) = mpsc::unbounded();
 
     (
-        NotifySender { notify_tx, name },
-        NotifyReceiver { notify_rx },
+        NotifySender {
+            notify_tx,
+            name: name.clone(),
+        },
+        NotifyReceiver { notify_rx, name },
     )
 }
 
 /// Clonable receiver that can register clients for halt notification
-/// It's kept separate from `Sender` to split responsibilities.
-/// TODO: Receiver seems to have no responsibility except for client registration, consider
-///  moving it to Sender...
+/// It's kept separate from `HaltHandle` to split responsibilities.
 #[derive(Clone)]
-pub struct Receiver {
-    sender: Arc<Sender>,
+pub struct HaltReceiver {
+    handle: Arc<HaltHandle>,
 }
 
-impl Receiver {
+impl HaltReceiver {
     pub async fn register_client(&self, name: String) -> NotifyReceiver {
-        self.sender.clone().register_client(name).await
+        self.handle.clone().register_client(name).await
+    }
+
+    /// Create new termination context which is halted (as client `name`) when this context is
+    /// halted. The child context can still be halted on its own.
+    pub async fn spawn_child(
+        &self,
+        name: String,
+        halt_timeout: Duration,
+    ) -> (Arc<HaltHandle>, HaltReceiver) {
+        let (child_handle, child_receiver) = make_pair(halt_timeout);
+        self.register_client(name)
+            .await
+            .spawn_halt_handler(child_handle.clone().send_halt());
+        (child_handle, child_receiver)
     }
 }
 
 /// One halt context capable of notifying all of registered `clients`
-pub struct Sender {
+pub struct HaltHandle {
     clients: Mutex<Vec<NotifySender>>,
     exit_hooks: Mutex<Vec<Pin<Box<dyn Future<Output = ()> + 'static + Send>>>>,
     /// How long to wait for client to finish
     halt_timeout: Duration,
 }
 
-impl Sender {
-    /// Create new Sender
+impl HaltHandle {
+    /// Create new HaltHandle
     fn new(halt_timeout: Duration) -> Arc<Self> {
         Arc::new(Self {
             clients: Mutex::new(Vec::new()),
@@ -185,7 +225,7 @@ impl Sender {
         })
     }
 
-    /// Register one client. Available only through `Receiver` API
+    /// Register one client. Available only through `HaltReceiver` API
     async fn register_client(self: Arc<Self>, name: String) -> NotifyReceiver {
         let (notify_sender, notify_receiver) = make_notify_pair(name);
         self.clients.lock().await.push(notify_sender);
@@ -205,7 +245,7 @@ impl Sender {
     /// tasks was halted (we send them channel to reply back) and one of them would be dropped
     /// before it had a chance to run (ie. as a result of another task that is being terminated
     /// dropping it in termination handler) it wouldn't respond with "termination successful".
-    async fn send_halt_internal(self: Arc<Self>) -> error::Result<()> {
+    async fn send_halt_internal(self: Arc<Self>) -> Result<()> {
         // take the list of clients
         let mut clients: Vec<_> = self.clients.lock().await.drain(..).collect();
 
@@ -221,15 +261,9 @@ impl Sender {
             match done_wait.done_rx.next().timeout(self.halt_timeout).await {
                 Ok(confirm) => match confirm {
                     Some(_) => (),
-                    None => Err(ErrorKind::Halt(format!(
-                        "failed to halt client {}: dropped handle",
-                        client.name
-                    )))?,
+                    None => Err(Error::Dropped(client.name))?,
                 },
-                Err(_) => Err(ErrorKind::Halt(format!(
-                    "failed to halt client {}: timeout",
-                    client.name
-                )))?,
+                Err(_) => Err(Error::Timeout(client.name))?,
             }
         }
 
@@ -244,17 +278,18 @@ impl Sender {
     /// additional threads.
     pub fn hook_termination_signals(self: Arc<Self>) {
         // Hook `SIGINT`, `SIGHUP` and `SIGTERM`
-        for signal_type in vec![
+        for signal_type in [
             SignalKind::interrupt(),
             SignalKind::hangup(),
             SignalKind::terminate(),
         ] {
             let halt_sender = self.clone();
-            tokio::spawn(async move {
-                if let Some(_) = signal(signal_type)
+            runtime::spawn(async move {
+                if signal(signal_type)
                     .expect("BUG: failed hooking signal")
                     .next()
                     .await
+                    .is_some()
                 {
                     // Exit after receiving signal
                     halt_sender.send_halt().await;
@@ -265,7 +300,7 @@ impl Sender {
 
     pub async fn send_halt(self: Arc<Self>) {
         let (finish_tx, mut finish_rx) = mpsc::unbounded();
-        let handle: task::JoinHandle<error::Result<()>> = tokio::spawn(async move {
+        let handle: runtime::JoinHandle<Result<()>> = runtime::spawn(async move {
             self.send_halt_internal().await?;
             let _result = finish_tx.unbounded_send(());
             Ok(())
@@ -278,21 +313,21 @@ impl Sender {
     }
 }
 
-/// Build a halt sender/receiver pair
-pub fn make_pair(halt_timeout: Duration) -> (Arc<Sender>, Receiver) {
-    let sender = Sender::new(halt_timeout);
-    let receiver = Receiver {
-        sender: sender.clone(),
+/// Build a halt handle/receiver pair
+pub fn make_pair(halt_timeout: Duration) -> (Arc<HaltHandle>, HaltReceiver) {
+    let handle = HaltHandle::new(halt_timeout);
+    let receiver = HaltReceiver {
+        handle: handle.clone(),
     };
 
-    (sender, receiver)
+    (handle, receiver)
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use runtime::delay_for;
     use std::sync::atomic::{AtomicBool, Ordering};
-    use tokio::time::delay_for;
 
     // Test that if cleanup after halt takes too long, halter will panic
     #[tokio::test]
@@ -301,7 +336,7 @@ mod test {
         let (sender, receiver) = make_pair(Duration::from_millis(10));
         let notify_receiver = receiver.register_client("test".into()).await;
 
-        tokio::spawn(async move {
+        runtime::spawn(async move {
             if let Some(done) = notify_receiver.wait_for_halt().await {
                 // do a long halt cleanup
                 delay_for(Duration::from_secs(100)).await;
@@ -320,7 +355,7 @@ mod test {
         let (sender, receiver) = make_pair(Duration::from_millis(10));
         let notify_receiver = receiver.register_client("test".into()).await;
 
-        tokio::spawn(async move {
+        runtime::spawn(async move {
             if let Some(done) = notify_receiver.wait_for_halt().await {
                 // do not send halt confirmation, drop the handle
                 drop(done);
@@ -337,7 +372,7 @@ mod test {
         let (sender, receiver) = make_pair(Duration::from_millis(10));
         let notify_receiver = receiver.register_client("test".into()).await;
 
-        tokio::spawn(async move {
+        runtime::spawn(async move {
             if let Some(done) = notify_receiver.wait_for_halt().await {
                 done.confirm();
             }
@@ -368,6 +403,27 @@ mod test {
         assert!(chan_tx.unbounded_send(()).is_err());
     }
 
+    // Test that halting parent termination context halts its child context as well
+    #[tokio::test]
+    async fn test_halt_child() {
+        let (sender, receiver) = make_pair(Duration::from_millis(50));
+        let (_child_sender, child_receiver) = receiver
+            .spawn_child("child".into(), Duration::from_millis(10))
+            .await;
+        let notify_receiver = child_receiver.register_client("test".into()).await;
+        // This channel is used to detect other side was halted
+        let (chan_tx, mut chan_rx) = mpsc::unbounded();
+
+        notify_receiver.spawn(async move {
+            // This should never return
+            assert!(chan_rx.next().await.is_some())
+        });
+
+        // Halt of the parent should succeed and propagate to the child
+        sender.send_halt().await;
+        assert!(chan_tx.unbounded_send(()).is_err());
+    }
+
     // Test that if task in termination context issues halt request, the halt request will finish
     // and terminate all registered tasks, not just itself.
     #[tokio::test]
@@ -385,7 +441,7 @@ mod test {
         });
         // Task 2: started in normal context, waits for halt
         let flag_writer = halted_flag.clone();
-        tokio::spawn(async move {
+        runtime::spawn(async move {
             if let Some(done) = notify_receiver2.wait_for_halt().await {
                 flag_writer.store(true, Ordering::Relaxed);
                 done.confirm();
@@ -397,7 +453,7 @@ mod test {
             done.confirm();
             // Wait for task 2 to run
             delay_for(Duration::from_millis(50)).await;
-            assert_eq!(halted_flag.load(Ordering::Relaxed), true);
+            assert!(halted_flag.load(Ordering::Relaxed));
         } else {
             panic!("no halt received!");
         }